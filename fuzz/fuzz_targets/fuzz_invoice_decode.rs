@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pl_00_intro::internal::fuzz::fuzz_invoice_decode;
+
+fuzz_target!(|data: &[u8]| fuzz_invoice_decode(data));
@@ -0,0 +1,5 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pl_00_intro::internal::fuzz::fuzz_tlv_roundtrip;
+
+fuzz_target!(|data: &[u8]| fuzz_tlv_roundtrip(data));
@@ -0,0 +1,84 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use clap::Parser;
+use pl_00_intro::internal::grading::{
+    completion_percent, parse_test_output, test_filter, ExerciseResult, EXERCISES_ADVANCED_TEST_MODULE,
+    EXERCISES_TEST_MODULE,
+};
+use pl_00_intro::internal::hints::hints_help;
+use std::io::IsTerminal;
+use std::process::{exit, Command};
+
+/// Runs the exercise tests and shows which exercises pass, so instructors
+/// don't have to read through `cargo test` output.
+#[derive(Parser)]
+#[command(name = "grader")]
+#[command(about = "Grade the Programming Lightning exercises", long_about = None, after_help = hints_help())]
+struct Cli {
+    #[arg(short = 'e', long, help = "Only grade this exercise")]
+    exercise: Option<u32>,
+    #[arg(short = 'a', long, help = "Grade the advanced exercises")]
+    advanced: bool,
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Colors `text`, unless the output isn't a terminal or `NO_COLOR` is set.
+fn paint(text: &str, color: &str) -> String {
+    if std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+fn print_table(results: &[ExerciseResult]) {
+    println!("{}", paint("Exercise  Result  Tests", BOLD));
+    for result in results {
+        let (status, color) = if result.is_pass() { ("PASS", GREEN) } else { ("FAIL", RED) };
+        let line = format!(
+            "{:>8}  {}    {}/{}  {}",
+            result.exercise,
+            paint(status, color),
+            result.passed,
+            result.total(),
+            result.failed.join(", ")
+        );
+        println!("{}", line.trim_end());
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let module = if cli.advanced { EXERCISES_ADVANCED_TEST_MODULE } else { EXERCISES_TEST_MODULE };
+
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["test", "--lib", "--no-fail-fast", "--", &test_filter(module, cli.exercise)])
+        .output()
+        .expect("could not run cargo test");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let results = parse_test_output(&stdout, module);
+
+    if results.is_empty() {
+        // Most likely an exercise doesn't compile, and cargo says why.
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        match cli.exercise {
+            Some(exercise) => eprintln!("no tests ran for exercise {}", exercise),
+            None => eprintln!("no exercise tests ran"),
+        }
+        exit(2);
+    }
+
+    print_table(&results);
+    let passed = results.iter().filter(|result| result.is_pass()).count();
+    println!(
+        "\n{}",
+        paint(&format!("{} of {} exercises complete ({}%)", passed, results.len(), completion_percent(&results)), BOLD)
+    );
+    if passed < results.len() {
+        exit(1);
+    }
+}
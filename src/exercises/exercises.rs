@@ -13,13 +13,83 @@ use bitcoin::script::{Builder, ScriptBuf, ScriptHash};
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Scalar, SecretKey};
 use bitcoin::transaction::Version;
 use bitcoin::PublicKey;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::bip32::KeySource;
+use bitcoin::psbt::Psbt;
+use bitcoin::Amount;
 use bitcoin::{Block, OutPoint, PubkeyHash, Sequence, Transaction, TxIn, TxOut, Witness};
 use internal::key_utils::{
-    add_privkeys, add_pubkeys, hash_pubkeys, privkey_multipication_tweak, pubkey_from_secret,
-    pubkey_multipication_tweak,
+    add_privkeys, add_pubkeys, derive_public_key, hash_pubkeys, privkey_multipication_tweak, pubkey_from_secret,
+    check_compressed, pubkey_multipication_tweak, sort_funding_keys,
 };
-use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
-use internal::tx_utils::{build_output, build_transaction};
+use internal::errors::{ChannelError, ExerciseError, GossipError, OnionError};
+use internal::channel_utils::channel_id_from_funding;
+use internal::fee_utils::{
+    commit_tx_fee, fee_for_weight, htlc_success_fee, min_replacement_fee, htlc_timeout_fee, ANCHOR_OUTPUT_VALUE, DELAYED_SWEEP_TX_WEIGHT,
+    balances_after_funder_fee, ANCHOR_CPFP_TX_WEIGHT, FUNDING_WITNESS_WEIGHT,
+    HtlcFeeMode, P2WPKH_INPUT_WEIGHT, P2WPKH_OUTPUT_WEIGHT,
+};
+use internal::script_utils::{
+    build_anchor_htlc_offerer_witness_script, build_anchor_htlc_receiver_witness_script,
+    build_anchor_output_script, build_anchor_to_remote_script, build_htlc_offerer_witness_script,
+    build_htlc_receiver_witness_script, p2wpkh_output_script,
+};
+use internal::htlc_utils::{htlc_witness_script, is_htlc_trimmed, Htlc, HtlcOutput};
+use internal::payments::{PaymentHash, PaymentHash160, PaymentPreimage, PaymentSecret};
+use internal::keys::{ChannelKeys, DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey};
+use internal::psbt_utils::{finalize_funding_psbt, sign_funding_psbt};
+use internal::sign_utils::{commitment_sighash_to_sign, counterparty_htlc_sighash_type, generate_p2wsh_signature};
+use internal::watchtower::{breach_key, decrypt_blob, encrypt_blob, penalty_tx_fee, BreachHint, JusticeBlob};
+use internal::tx_utils::{
+    build_output, build_transaction, check_output_amount, check_to_self_delay, commitment_number_obscure_factor,
+    obscured_commitment_transaction_numbers, recover_commitment_number, sort_outputs_bolt3, strip_cltv_expiries,
+    to_self_delay_sequence,
+};
+use internal::tlv::{encode_tu64, write_tlv_stream, TlvRecord};
+use internal::channel::{
+    ChannelParams, CommitmentEvent, CounterpartyCommitments, FundingSpend, HtlcState, ReestablishAction,
+};
+use internal::gossip_utils::verify_channel_update;
+use internal::graph::{ChannelPolicy, GraphChannel, NetworkGraph};
+use internal::routing::{Route, RouteHop};
+use internal::invoice::{
+    bytes_to_words, encode_bech32, features_to_words, invoice_hrp, signing_hash, timestamp_to_words, u64_to_words,
+    write_tagged_field, Invoice, DEFAULT_EXPIRY, DESCRIPTION_TAG, EXPIRY_TAG, FEATURES_TAG, PAYMENT_HASH_TAG,
+    PAYMENT_SECRET_TAG,
+};
+use internal::errors::InvoiceError;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use internal::keys::ChannelKeyManager;
+use internal::units::MilliSat;
+use internal::wire::{
+    encode_features, ChannelAnnouncement, ChannelReestablish, ChannelUpdate, OpenChannel, ShortChannelId, ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED, ANNOUNCE_CHANNEL_FLAG, CHANNEL_TYPE_TLV,
+    ANCHOR_OUTPUTS_REQUIRED,
+    MAX_ACCEPTED_HTLCS, PAYMENT_SECRET_REQUIRED, STATIC_REMOTE_KEY_REQUIRED, UPFRONT_SHUTDOWN_SCRIPT_TLV,
+    VAR_ONION_OPTIN_REQUIRED,
+};
+use bitcoin::constants::ChainHash;
+use bitcoin::Network;
+use crate::exercises::onion::{build_forwarding_payloads, construct_onion_packet, PaymentOnion};
+use internal::onion_utils::{HopPayload, AMT_TO_FORWARD_TLV, KEYSEND_PREIMAGE_TLV, OUTGOING_CLTV_VALUE_TLV};
+use internal::shachain::MAX_SHACHAIN_INDEX;
+use internal::wire::RevokeAndAck;
+use internal::errors::WatchtowerError;
+use internal::chain_source::ChainUtxo;
+use internal::chain_utils::{confirmations, merkle_branch, merkle_parent, MerkleBranch};
+use bitcoin::hash_types::TxMerkleNode;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::Txid;
+use internal::errors::{ClosingError, ShutdownScriptError};
+use internal::script_utils::validate_shutdown_script;
+use internal::wire::{ClosingSigned, FeeRange, Shutdown};
+use internal::backup::{backup_key, RestoredChannel, StaticChannelBackup, STATIC_CHANNEL_BACKUP_VERSION};
+use internal::errors::BackupError;
+use internal::keys::ChannelPublicKeys;
+use internal::wire::Reader;
 
 //
 // Exercise 1
@@ -41,11 +111,16 @@ pub fn two_of_two_multisig_witness_script(pubkey1: &PublicKey, pubkey2: &PublicK
 // Exercise 2
 //
 
+/// The funding transaction, with the keys in the 2-of-2 in the order they
+/// are passed: the workbook always passes the lesser key, Alice's, first.
+/// With keys in any other order this is a different funding output, and so
+/// a different funding outpoint, from `build_funding_transaction_with_script`,
+/// which sorts them the way BOLT 3 requires.
 pub fn build_funding_transaction(
     txins: Vec<TxIn>,
     alice_pubkey: &PublicKey,
     bob_pubkey: &PublicKey,
-    amount: u64,
+    amount: Amount,
 ) -> Transaction {
     let witness_script = two_of_two_multisig_witness_script(alice_pubkey, bob_pubkey);
     let p2wsh_script_pubkey = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
@@ -53,6 +128,58 @@ pub fn build_funding_transaction(
     build_transaction(Version::TWO, LockTime::ZERO, txins, vec![output])
 }
 
+/// The funding transaction and its witness script, with the keys sorted
+/// first, so both sides build the same funding outpoint whichever order
+/// they pass them in.
+pub fn build_funding_transaction_with_script(
+    txins: Vec<TxIn>,
+    alice_pubkey: &PublicKey,
+    bob_pubkey: &PublicKey,
+    amount: Amount,
+) -> (Transaction, ScriptBuf) {
+    let (first, second) = sort_funding_keys(*alice_pubkey, *bob_pubkey);
+
+    let witness_script = two_of_two_multisig_witness_script(&first, &second);
+    let tx = build_funding_transaction(txins, &first, &second, amount);
+
+    (tx, witness_script)
+}
+
+/// The funding transaction as a PSBT, carrying what an external signer needs:
+/// the UTXO each input spends, and the witness script and key origins of the
+/// funding output.
+pub fn build_funding_psbt(
+    txins: Vec<TxIn>,
+    input_utxos: Vec<TxOut>,
+    alice_pubkey: &PublicKey,
+    alice_key_source: KeySource,
+    bob_pubkey: &PublicKey,
+    bob_key_source: KeySource,
+    amount: Amount,
+) -> Result<Psbt, ChannelError> {
+    if txins.len() != input_utxos.len() {
+        return Err(ChannelError::InputValuesMismatch { inputs: txins.len(), values: input_utxos.len() });
+    }
+    if let Some(input) = txins.iter().position(|txin| !txin.script_sig.is_empty() || !txin.witness.is_empty()) {
+        return Err(ChannelError::SignedInput { input });
+    }
+
+    let (tx, witness_script) = build_funding_transaction_with_script(txins, alice_pubkey, bob_pubkey, amount);
+
+    let mut psbt = Psbt::from_unsigned_tx(tx).expect("inputs were checked to be unsigned");
+
+    for (input, utxo) in psbt.inputs.iter_mut().zip(input_utxos) {
+        input.witness_utxo = Some(utxo);
+    }
+
+    let funding_output = &mut psbt.outputs[0];
+    funding_output.witness_script = Some(witness_script);
+    funding_output.bip32_derivation.insert(alice_pubkey.inner, alice_key_source);
+    funding_output.bip32_derivation.insert(bob_pubkey.inner, bob_key_source);
+
+    Ok(psbt)
+}
+
 //
 // Exercise 3
 //
@@ -61,8 +188,8 @@ pub fn build_refund_transaction(
     funding_txin: TxIn,
     alice_pubkey: PublicKey,
     bob_pubkey: PublicKey,
-    alice_balance: u64,
-    bob_balance: u64,
+    alice_balance: Amount,
+    bob_balance: Amount,
 ) -> Transaction {
     let alice_script = p2wpkh_output_script(alice_pubkey);
     let bob_script = p2wpkh_output_script(bob_pubkey);
@@ -70,10 +197,7 @@ pub fn build_refund_transaction(
     let alice_output = build_output(alice_balance, alice_script);
     let bob_output = build_output(bob_balance, bob_script);
 
-    let mut outputs = vec![alice_output, bob_output];
-    outputs.sort_by(|a, b| {
-        a.value.cmp(&b.value).then_with(|| a.script_pubkey.cmp(&b.script_pubkey))
-    });
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(vec![(alice_output, None), (bob_output, None)]));
 
     build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs)
 }
@@ -120,21 +244,25 @@ pub fn generate_revocation_privkey(
 //
 
 pub fn to_local(
-    revocation_key: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_key: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     to_self_delay: i64,
-) -> ScriptBuf {
-    Builder::new()
+) -> Result<ScriptBuf, ExerciseError> {
+    check_to_self_delay(to_self_delay)?;
+    check_compressed("revocation key", &revocation_key.0)?;
+    check_compressed("to_local delayed pubkey", &to_local_delayed_pubkey.0)?;
+
+    Ok(Builder::new()
         .push_opcode(opcodes::OP_IF)
-        .push_key(revocation_key)
+        .push_key(&revocation_key.0)
         .push_opcode(opcodes::OP_ELSE)
         .push_int(to_self_delay)
         .push_opcode(opcodes::OP_CSV)
         .push_opcode(opcodes::OP_DROP)
-        .push_key(to_local_delayed_pubkey)
+        .push_key(&to_local_delayed_pubkey.0)
         .push_opcode(opcodes::OP_ENDIF)
         .push_opcode(opcodes::OP_CHECKSIG)
-        .into_script()
+        .into_script())
 }
 
 //
@@ -143,26 +271,42 @@ pub fn to_local(
 
 pub fn build_commitment_transaction(
     funding_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     remote_pubkey: PublicKey,
     to_self_delay: i64,
-    local_amount: u64,
-    remote_amount: u64,
-) -> Transaction {
-    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    feerate_per_kw: u64,
+    obscure_factor: u64,
+    commitment_number: u64,
+) -> Result<Transaction, ExerciseError> {
+    // the funder pays the commitment fee out of its own output
+    let fee = commit_tx_fee(feerate_per_kw, 0, false);
+    let (local_amount, remote_amount) =
+        balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    check_output_amount("to_local", local_amount)?;
+    check_output_amount("to_remote", remote_amount)?;
+    check_compressed("remote pubkey", &remote_pubkey)?;
+
+    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
     let to_local_p2wsh = ScriptBuf::new_p2wsh(&to_local_script.wscript_hash());
     let local_output = build_output(local_amount, to_local_p2wsh);
 
     let remote_script = p2wpkh_output_script(remote_pubkey);
     let remote_output = build_output(remote_amount, remote_script);
 
-    let mut outputs = vec![local_output, remote_output];
-    outputs.sort_by(|a, b| {
-        a.value.cmp(&b.value).then_with(|| a.script_pubkey.cmp(&b.script_pubkey))
-    });
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(vec![(local_output, None), (remote_output, None)]));
 
-    build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs)
+    // the locktime carries the lower half of the obscured commitment number,
+    // and the funding input's sequence the upper half
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+    let mut funding_txin = funding_txin;
+    funding_txin.sequence = sequence;
+
+    Ok(build_transaction(Version::TWO, locktime, vec![funding_txin], outputs))
 }
 
 //
@@ -171,40 +315,77 @@ pub fn build_commitment_transaction(
 
 pub fn build_htlc_commitment_transaction(
     funding_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    remote_htlc_pubkey: &PublicKey,
-    local_htlc_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     remote_pubkey: PublicKey,
     to_self_delay: i64,
-    payment_hash160: &[u8; 20],
-    htlc_amount: u64,
-    local_amount: u64,
-    remote_amount: u64,
-) -> Transaction {
-    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
+    htlcs: &[Htlc],
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+    obscure_factor: u64,
+    commitment_number: u64,
+) -> Result<Transaction, ExerciseError> {
+    // a trimmed HTLC gets no output, so it adds nothing to the fee either
+    let untrimmed_htlcs: Vec<&Htlc> = htlcs
+        .iter()
+        .filter(|htlc| {
+            let amount = htlc.amount_msat.to_amount_floor();
+            !is_htlc_trimmed(amount, dust_limit, feerate_per_kw, htlc.offered, HtlcFeeMode::Legacy)
+        })
+        .collect();
+
+    // the funder pays the commitment fee, which grows with each HTLC output
+    let fee = commit_tx_fee(feerate_per_kw, untrimmed_htlcs.len(), false);
+    let (local_amount, remote_amount) =
+        balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    check_output_amount("to_local", local_amount)?;
+    check_output_amount("to_remote", remote_amount)?;
+    check_compressed("remote pubkey", &remote_pubkey)?;
+
+    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
     let to_local_p2wsh = ScriptBuf::new_p2wsh(&to_local_script.wscript_hash());
     let local_output = build_output(local_amount, to_local_p2wsh);
 
     let remote_script = p2wpkh_output_script(remote_pubkey);
     let remote_output = build_output(remote_amount, remote_script);
 
-    let htlc_script = build_htlc_offerer_witness_script(
-        revocation_pubkey, 
-        remote_htlc_pubkey, 
-        local_htlc_pubkey, 
-        payment_hash160
-    );
+    let mut outputs = vec![(local_output, None), (remote_output, None)];
 
-    let htlc_p2wsh = ScriptBuf::new_p2wsh(&htlc_script.wscript_hash());
-    let htlc_output = build_output(htlc_amount, htlc_p2wsh);
+    // HTLCs we offered time out back to us; ones we received pay us with the preimage
+    for htlc in untrimmed_htlcs {
+        let htlc_amount = htlc.amount_msat.to_amount_floor();
+        check_output_amount("HTLC", htlc_amount)?;
 
-    let mut outputs = vec![local_output, remote_output, htlc_output];
-    outputs.sort_by(|a, b| {
-        a.value.cmp(&b.value).then_with(|| a.script_pubkey.cmp(&b.script_pubkey))
-    });
+        let htlc_script = if htlc.offered {
+            build_htlc_offerer_witness_script(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, &htlc.payment_hash)
+        } else {
+            build_htlc_receiver_witness_script(
+                revocation_pubkey,
+                remote_htlc_pubkey,
+                local_htlc_pubkey,
+                &htlc.payment_hash,
+                htlc.cltv_expiry,
+            )
+        };
 
-    build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs)
+        let htlc_p2wsh = ScriptBuf::new_p2wsh(&htlc_script.wscript_hash());
+        outputs.push((build_output(htlc_amount, htlc_p2wsh), Some(htlc.cltv_expiry)));
+    }
+
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(outputs));
+
+    // the commitment number is obscured just like in Exercise 7
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+    let mut funding_txin = funding_txin;
+    funding_txin.sequence = sequence;
+
+    Ok(build_transaction(Version::TWO, locktime, vec![funding_txin], outputs))
 }
 
 //
@@ -213,17 +394,1624 @@ pub fn build_htlc_commitment_transaction(
 
 pub fn build_htlc_timeout_transaction(
     htlc_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     to_self_delay: i64,
     cltv_expiry: u32,
-    htlc_amount: u64,
-) -> Transaction {
-    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
+    htlc_amount: Amount,
+    htlc_fee_mode: HtlcFeeMode,
+) -> Result<Transaction, ExerciseError> {
+    check_output_amount("HTLC", htlc_amount)?;
+
+    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
     let to_local_p2wsh = ScriptBuf::new_p2wsh(&to_local_script.wscript_hash());
     let output = build_output(htlc_amount, to_local_p2wsh);
 
+    // the anchor HTLC scripts require `1 OP_CSV` on this spend
+    let mut htlc_txin = htlc_txin;
+    if htlc_fee_mode.anchors() {
+        htlc_txin.sequence = Sequence(1);
+    }
+
     let mut tx = build_transaction(Version::TWO, LockTime::ZERO, vec![htlc_txin], vec![output]);
     tx.lock_time = LockTime::from_consensus(cltv_expiry);
+    Ok(tx)
+}
+//
+// Exercise 10
+//
+
+pub fn build_commitment_transaction_with_anchors(
+    funding_txin: TxIn,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    remote_pubkey: PublicKey,
+    local_funding_pubkey: &FundingPubkey,
+    remote_funding_pubkey: &FundingPubkey,
+    to_self_delay: i64,
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ChannelError> {
+    check_compressed("remote pubkey", &remote_pubkey)?;
+
+    // the funder pays the commitment fee and both anchors
+    let fee = commit_tx_fee(feerate_per_kw, 0, true) + ANCHOR_OUTPUT_VALUE * 2;
+    let (local_amount, remote_amount) = balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    let mut outputs = vec![];
+
+    // an anchor is only added while its owner still has something to fee-bump
+    if local_amount >= dust_limit {
+        let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
+        outputs.push(build_output(local_amount, to_local_script.to_p2wsh()));
+
+        let anchor_script = build_anchor_output_script(local_funding_pubkey);
+        outputs.push(build_output(ANCHOR_OUTPUT_VALUE, anchor_script.to_p2wsh()));
+    }
+
+    if remote_amount >= dust_limit {
+        let to_remote_script = build_anchor_to_remote_script(&remote_pubkey);
+        outputs.push(build_output(remote_amount, to_remote_script.to_p2wsh()));
+
+        let anchor_script = build_anchor_output_script(remote_funding_pubkey);
+        outputs.push(build_output(ANCHOR_OUTPUT_VALUE, anchor_script.to_p2wsh()));
+    }
+
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(outputs.into_iter().map(|output| (output, None)).collect()));
+
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs))
+}
+
+//
+// Exercise 11
+//
+
+pub fn validate_htlc_tx_set(commitment_tx: &Transaction, htlc_txs: &[Transaction]) -> Result<(), ChannelError> {
+    let commitment_txid = commitment_tx.compute_txid();
+    let mut spent_vouts = vec![];
+
+    for (i, htlc_tx) in htlc_txs.iter().enumerate() {
+        let txin = htlc_tx.input.first().ok_or(ChannelError::HtlcTxMissingInput { index: i })?;
+
+        let outpoint = txin.previous_output;
+        if outpoint.txid != commitment_txid {
+            return Err(ChannelError::HtlcTxSpendsOtherTx { index: i });
+        }
+
+        if outpoint.vout as usize >= commitment_tx.output.len() {
+            return Err(ChannelError::HtlcTxUnknownVout { index: i, vout: outpoint.vout });
+        }
+
+        if spent_vouts.contains(&outpoint.vout) {
+            return Err(ChannelError::HtlcTxDuplicateVout { index: i, vout: outpoint.vout });
+        }
+        spent_vouts.push(outpoint.vout);
+    }
+
+    Ok(())
+}
+
+//
+// Exercise 12
+//
+
+pub fn validate_htlc_outputs(
+    tx: &Transaction,
+    expected: &[HtlcOutput],
+    keys: &ChannelKeys,
+    params: &ChannelParams,
+) -> Result<(), ChannelError> {
+    let mut matched = vec![false; tx.output.len()];
+
+    for htlc_output in expected {
+        let htlc = htlc_output.htlc;
+        let script_pubkey = htlc_witness_script(keys, &htlc, params.anchors()).to_p2wsh();
+
+        let index = tx
+            .output
+            .iter()
+            .enumerate()
+            .position(|(i, output)| {
+                !matched[i] && output.script_pubkey == script_pubkey && output.value == htlc_output.amount_sat()
+            })
+            .ok_or(ChannelError::MissingHtlcOutput {
+                offered: htlc.offered,
+                amount_sat: htlc_output.amount_sat().to_sat(),
+            })?;
+        matched[index] = true;
+    }
+
+    // everything else must be to_local, to_remote or an anchor, any of which
+    // may have been left out as dust
+    let to_local_script =
+        to_local(&keys.revocation_pubkey, &keys.to_local_delayed_pubkey, params.to_self_delay as i64)?.to_p2wsh();
+    let mut other_scripts = vec![to_local_script];
+    if params.anchors() {
+        other_scripts.push(build_anchor_to_remote_script(&keys.remote_pubkey).to_p2wsh());
+        other_scripts.push(build_anchor_output_script(&params.local_funding_pubkey()).to_p2wsh());
+        other_scripts.push(build_anchor_output_script(&params.remote_funding_pubkey()).to_p2wsh());
+    } else {
+        other_scripts.push(p2wpkh_output_script(keys.remote_pubkey));
+    }
+
+    for (index, output) in tx.output.iter().enumerate() {
+        if !matched[index] && !other_scripts.contains(&output.script_pubkey) {
+            return Err(ChannelError::UnexpectedOutput { index });
+        }
+    }
+
+    Ok(())
+}
+
+//
+// Exercise 13
+//
+
+/// The pre-anchor HTLC-timeout transaction, paying the fee at `feerate_per_kw`
+/// out of the HTLC, or an error if that pushes its output below `dust_limit`.
+///
+/// Without anchors the HTLC transactions are signed with SIGHASH_ALL, so no
+/// input can be added later to bump their fee: the feerate agreed when the
+/// HTLC was added is the only fee they will ever pay. `max_feerate_for_htlc`
+/// gives the highest one that keeps the output above dust.
+pub fn build_htlc_timeout_transaction_with_fee(
+    htlc_txin: TxIn,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    to_self_delay: i64,
+    cltv_expiry: u32,
+    htlc_amount: Amount,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ChannelError> {
+    let fee = htlc_timeout_fee(feerate_per_kw, false);
+    let output_value = htlc_amount.checked_sub(fee).unwrap_or(Amount::ZERO);
+
+    if output_value < dust_limit {
+        return Err(ChannelError::DustOutput {
+            index: 0,
+            value: output_value.to_sat(),
+            dust_limit: dust_limit.to_sat(),
+        });
+    }
+
+    Ok(build_htlc_timeout_transaction(
+        htlc_txin,
+        revocation_pubkey,
+        to_local_delayed_pubkey,
+        to_self_delay,
+        cltv_expiry,
+        output_value,
+        HtlcFeeMode::Legacy,
+    )?)
+}
+
+//
+// Exercise 14
+//
+
+pub fn build_all_htlc_transactions(
+    commitment_tx: &Transaction,
+    htlc_outputs: &[HtlcOutput],
+    keys: &ChannelKeys,
+    params: &ChannelParams,
+) -> Result<Vec<(Transaction, ScriptBuf)>, ExerciseError> {
+    let commitment_txid = commitment_tx.compute_txid();
+    let to_self_delay = params.to_self_delay as i64;
+    let mut htlc_txs = vec![];
+
+    for htlc_output in htlc_outputs {
+        // trimmed HTLCs have no output to spend
+        let Some(vout) = htlc_output.output_index else {
+            continue;
+        };
+        let htlc = htlc_output.htlc;
+        let htlc_script = htlc_witness_script(keys, &htlc, params.anchors());
+
+        let htlc_txin = TxIn {
+            previous_output: OutPoint { txid: commitment_txid, vout },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        };
+
+        let fee = if htlc.offered {
+            params.htlc_fee_mode.htlc_timeout_fee(params.feerate_per_kw)
+        } else {
+            params.htlc_fee_mode.htlc_success_fee(params.feerate_per_kw)
+        };
+        let output_value = htlc_output.amount_sat().checked_sub(fee).ok_or(ExerciseError::InsufficientFunds {
+            needed: fee.to_sat(),
+            available: htlc_output.amount_sat().to_sat(),
+        })?;
+
+        if htlc.offered {
+            let timeout_tx = build_htlc_timeout_transaction(
+                htlc_txin,
+                &keys.revocation_pubkey,
+                &keys.to_local_delayed_pubkey,
+                to_self_delay,
+                htlc.cltv_expiry,
+                output_value,
+                params.htlc_fee_mode,
+            )?;
+            htlc_txs.push((timeout_tx, htlc_script));
+        } else {
+            let success_tx = build_htlc_success_transaction(
+                htlc_txin,
+                &keys.revocation_pubkey,
+                &keys.to_local_delayed_pubkey,
+                to_self_delay,
+                output_value,
+                params.htlc_fee_mode,
+            )?;
+            htlc_txs.push((success_tx, htlc_script));
+        }
+    }
+
+    Ok(htlc_txs)
+}
+
+//
+// Exercise 15
+//
+
+pub fn received_htlc_witness_script(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+    cltv_expiry: u32,
+) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(opcodes::OP_DUP)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(revocation_pubkey.0.pubkey_hash())
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_key(&remote_htlc_pubkey.0)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_opcode(opcodes::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(payment_hash.0)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_int(2)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_key(&local_htlc_pubkey.0)
+        .push_int(2)
+        .push_opcode(opcodes::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_opcode(opcodes::OP_DROP)
+        .push_int(cltv_expiry as i64)
+        .push_opcode(opcodes::OP_CLTV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ENDIF)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+//
+// Exercise 16
+//
+
+pub fn build_htlc_success_transaction(
+    htlc_txin: TxIn,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    to_self_delay: i64,
+    htlc_amount: Amount,
+    htlc_fee_mode: HtlcFeeMode,
+) -> Result<Transaction, ExerciseError> {
+    check_output_amount("HTLC", htlc_amount)?;
+
+    let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
+    let output = build_output(htlc_amount, to_local_script.to_p2wsh());
+
+    // the anchor HTLC scripts require `1 OP_CSV` on this spend
+    let mut htlc_txin = htlc_txin;
+    if htlc_fee_mode.anchors() {
+        htlc_txin.sequence = Sequence(1);
+    }
+
+    // unlike the timeout path, the preimage can be used right away
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, vec![htlc_txin], vec![output]))
+}
+
+//
+// Exercise 17
+//
+
+pub fn build_closing_transaction(
+    funding_txin: TxIn,
+    local_shutdown_script: ScriptBuf,
+    remote_shutdown_script: ScriptBuf,
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ChannelError> {
+    let mut funding_txin = funding_txin;
+    funding_txin.sequence = Sequence::MAX;
+
+    let build_outputs = |local_amount: Amount, remote_amount: Amount| {
+        vec![
+            (build_output(local_amount, local_shutdown_script.clone()), None),
+            (build_output(remote_amount, remote_shutdown_script.clone()), None),
+        ]
+    };
+
+    // the fee is estimated with both outputs and the signed funding witness
+    let unsigned_tx = build_transaction(
+        Version::TWO,
+        LockTime::ZERO,
+        vec![funding_txin.clone()],
+        strip_cltv_expiries(build_outputs(local_amount, remote_amount)),
+    );
+    let fee = fee_for_weight(feerate_per_kw, unsigned_tx.weight().to_wu() + FUNDING_WITNESS_WEIGHT);
+    let (local_amount, remote_amount) = balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    let mut outputs = build_outputs(local_amount, remote_amount);
+    outputs.retain(|(output, _)| output.value >= dust_limit);
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(outputs));
+
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs))
+}
+
+//
+// Exercise 18
+//
+
+pub fn obscure_commitment_transaction(
+    commitment_tx: Transaction,
+    opener_payment_basepoint: &PublicKey,
+    accepter_payment_basepoint: &PublicKey,
+    commitment_number: u64,
+) -> Transaction {
+    let obscure_factor = commitment_number_obscure_factor(opener_payment_basepoint, accepter_payment_basepoint);
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+
+    let mut tx = commitment_tx;
+    tx.lock_time = locktime;
+    tx.input[0].sequence = sequence;
     tx
-}
\ No newline at end of file
+}
+
+//
+// Exercise 19
+//
+
+pub fn build_trimmed_htlc_commitment_transaction(
+    funding_txin: TxIn,
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    remote_pubkey: PublicKey,
+    to_self_delay: i64,
+    htlcs: &[Htlc],
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ExerciseError> {
+    check_compressed("remote pubkey", &remote_pubkey)?;
+
+    // a trimmed HTLC gets no output: its value goes to the fee
+    let untrimmed_htlcs: Vec<&Htlc> = htlcs
+        .iter()
+        .filter(|htlc| {
+            let amount = htlc.amount_msat.to_amount_floor();
+            !is_htlc_trimmed(amount, dust_limit, feerate_per_kw, htlc.offered, HtlcFeeMode::Legacy)
+        })
+        .collect();
+
+    // the funder pays the commitment fee out of its own output
+    let fee = commit_tx_fee(feerate_per_kw, untrimmed_htlcs.len(), false);
+    let (local_amount, remote_amount) =
+        balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    let mut outputs = vec![];
+
+    for htlc in untrimmed_htlcs {
+        let htlc_amount = htlc.amount_msat.to_amount_floor();
+
+        let htlc_script = if htlc.offered {
+            build_htlc_offerer_witness_script(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, &htlc.payment_hash)
+        } else {
+            build_htlc_receiver_witness_script(
+                revocation_pubkey,
+                remote_htlc_pubkey,
+                local_htlc_pubkey,
+                &htlc.payment_hash,
+                htlc.cltv_expiry,
+            )
+        };
+        outputs.push((build_output(htlc_amount, htlc_script.to_p2wsh()), Some(htlc.cltv_expiry)));
+    }
+
+    if local_amount >= dust_limit {
+        let to_local_script = to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
+        outputs.push((build_output(local_amount, to_local_script.to_p2wsh()), None));
+    }
+
+    if remote_amount >= dust_limit {
+        outputs.push((build_output(remote_amount, p2wpkh_output_script(remote_pubkey)), None));
+    }
+
+    let outputs = strip_cltv_expiries(sort_outputs_bolt3(outputs));
+
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, vec![funding_txin], outputs))
+}
+
+//
+// Exercise 20
+//
+
+pub fn generate_per_commitment_secret(seed: &[u8; 32], index: u64) -> [u8; 32] {
+    let mut secret = *seed;
+
+    for i in 0..48 {
+        let bitpos = 47 - i;
+        if index & (1 << bitpos) == (1 << bitpos) {
+            secret[bitpos / 8] ^= 1 << (bitpos & 7);
+            secret = bitcoin::hashes::sha256::Hash::hash(&secret).to_byte_array();
+        }
+    }
+
+    secret
+}
+
+//
+// Exercise 21
+//
+
+pub fn can_derive(from_index: u64, to_index: u64) -> bool {
+    // only the bits below the lowest set bit of `from_index` can still be flipped
+    let bits = from_index.trailing_zeros().min(48);
+    let mask = !((1u64 << bits) - 1);
+
+    to_index & mask == from_index
+}
+
+//
+// Exercise 22
+//
+
+/// Returns `(localpubkey, local_delayedpubkey, local_htlcpubkey)`.
+pub fn derive_commitment_keys(
+    payment_basepoint: secp256k1PublicKey,
+    delayed_payment_basepoint: secp256k1PublicKey,
+    htlc_basepoint: secp256k1PublicKey,
+    per_commitment_point: secp256k1PublicKey,
+) -> (secp256k1PublicKey, secp256k1PublicKey, secp256k1PublicKey) {
+    (
+        derive_public_key(payment_basepoint, per_commitment_point),
+        derive_public_key(delayed_payment_basepoint, per_commitment_point),
+        derive_public_key(htlc_basepoint, per_commitment_point),
+    )
+}
+
+//
+// Exercise 23
+//
+
+pub fn build_funding_witness(
+    funding_script: &ScriptBuf,
+    pubkey1: &PublicKey,
+    signature1: &Signature,
+    pubkey2: &PublicKey,
+    signature2: &Signature,
+) -> Witness {
+    let mut signature1_der = signature1.serialize_der().to_vec();
+    signature1_der.push(EcdsaSighashType::All as u8);
+
+    let mut signature2_der = signature2.serialize_der().to_vec();
+    signature2_der.push(EcdsaSighashType::All as u8);
+
+    // the signatures must follow the order of the keys in the script
+    let (first_key, _) = sort_funding_keys(*pubkey1, *pubkey2);
+    let (first_signature, second_signature) = if first_key == *pubkey1 {
+        (signature1_der, signature2_der)
+    } else {
+        (signature2_der, signature1_der)
+    };
+
+    let mut witness = Witness::new();
+    // OP_CHECKMULTISIG pops one element too many
+    witness.push(Vec::new());
+    witness.push(first_signature);
+    witness.push(second_signature);
+    witness.push(funding_script.as_bytes());
+    witness
+}
+
+//
+// Exercise 24
+//
+
+/// The digest both funding keys sign to authorize `commitment_tx`, whose only
+/// input spends the funding output.
+pub fn compute_commitment_sighash(
+    commitment_tx: &Transaction,
+    local_funding_pubkey: &FundingPubkey,
+    remote_funding_pubkey: &FundingPubkey,
+    channel_value: Amount,
+) -> [u8; 32] {
+    let (first_key, second_key) = sort_funding_keys(local_funding_pubkey.0, remote_funding_pubkey.0);
+    let funding_script = two_of_two_multisig_witness_script(&first_key, &second_key);
+
+    commitment_sighash_to_sign(commitment_tx, &funding_script, channel_value.to_sat())
+}
+
+//
+// Exercise 25
+//
+
+pub fn sign_commitment_transaction(
+    tx: &Transaction,
+    funding_witness_script: &ScriptBuf,
+    channel_value: Amount,
+    funding_secret_key: &SecretKey,
+) -> Signature {
+    generate_p2wsh_signature(
+        tx.clone(),
+        0,
+        funding_witness_script,
+        channel_value.to_sat(),
+        EcdsaSighashType::All,
+        *funding_secret_key,
+    )
+}
+
+//
+// Exercise 26
+//
+
+/// Sweep the `to_local` output of a revoked commitment through the OP_IF
+/// branch, which needs no delay, only the revocation key.
+pub fn build_penalty_transaction(
+    revoked_to_local_outpoint: OutPoint,
+    to_local_amount: Amount,
+    revocation_privkey: &SecretKey,
+    to_local_script: &ScriptBuf,
+    destination_script: ScriptBuf,
+    feerate: u64,
+) -> Result<Transaction, ExerciseError> {
+    let txin = TxIn {
+        previous_output: revoked_to_local_outpoint,
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    };
+
+    let fee = penalty_tx_fee(1, 0, feerate);
+    let output_value = to_local_amount
+        .checked_sub(fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: to_local_amount.to_sat() })?;
+    let output = build_output(output_value, destination_script);
+
+    let mut tx = build_transaction(Version::TWO, LockTime::ZERO, vec![txin], vec![output]);
+
+    let signature = generate_p2wsh_signature(
+        tx.clone(),
+        0,
+        to_local_script,
+        to_local_amount.to_sat(),
+        EcdsaSighashType::All,
+        *revocation_privkey,
+    );
+    let mut signature_der = signature.serialize_der().to_vec();
+    signature_der.push(EcdsaSighashType::All as u8);
+
+    // a non-empty element takes the OP_IF branch
+    tx.input[0].witness.push(signature_der);
+    tx.input[0].witness.push([1u8]);
+    tx.input[0].witness.push(to_local_script.as_bytes());
+
+    Ok(tx)
+}
+
+//
+// Exercise 27
+//
+
+/// Sweep our own `to_local` output through the OP_ELSE branch once
+/// `to_self_delay` blocks have passed since the commitment confirmed.
+pub fn build_delayed_sweep_transaction(
+    to_local_outpoint: OutPoint,
+    to_local_amount: Amount,
+    delayed_payment_privkey: &SecretKey,
+    to_local_script: &ScriptBuf,
+    to_self_delay: u16,
+    destination_script: ScriptBuf,
+    feerate: u64,
+) -> Result<Transaction, ExerciseError> {
+    let txin = TxIn {
+        previous_output: to_local_outpoint,
+        script_sig: ScriptBuf::new(),
+        sequence: to_self_delay_sequence(to_self_delay),
+        witness: Witness::new(),
+    };
+
+    let fee = fee_for_weight(feerate, DELAYED_SWEEP_TX_WEIGHT);
+    let output_value = to_local_amount
+        .checked_sub(fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: to_local_amount.to_sat() })?;
+    let output = build_output(output_value, destination_script);
+
+    let mut tx = build_transaction(Version::TWO, LockTime::ZERO, vec![txin], vec![output]);
+
+    let signature = generate_p2wsh_signature(
+        tx.clone(),
+        0,
+        to_local_script,
+        to_local_amount.to_sat(),
+        EcdsaSighashType::All,
+        *delayed_payment_privkey,
+    );
+    let mut signature_der = signature.serialize_der().to_vec();
+    signature_der.push(EcdsaSighashType::All as u8);
+
+    // an empty element takes the OP_ELSE branch
+    tx.input[0].witness.push(signature_der);
+    tx.input[0].witness.push(Vec::new());
+    tx.input[0].witness.push(to_local_script.as_bytes());
+
+    Ok(tx)
+}
+
+//
+// Exercise 28
+//
+
+/// Sign the HTLC-timeout transaction with our HTLC key and attach the
+/// `0 <remotehtlcsig> <localhtlcsig> <> <offered_htlc_script>` witness.
+///
+/// `remote_htlc_signature` was made by the counterparty over the transaction
+/// with its BOLT 3 input sequence.
+pub fn finalize_htlc_timeout_transaction(
+    htlc_timeout_tx: Transaction,
+    offered_htlc_script: &ScriptBuf,
+    htlc_amount: Amount,
+    remote_htlc_signature: &Signature,
+    local_htlc_privkey: &SecretKey,
+    anchors: bool,
+) -> Transaction {
+    let mut tx = htlc_timeout_tx;
+
+    // the anchor HTLC scripts require `1 OP_CSV`
+    tx.input[0].sequence = if anchors { Sequence(1) } else { Sequence::ZERO };
+
+    let local_signature = generate_p2wsh_signature(
+        tx.clone(),
+        0,
+        offered_htlc_script,
+        htlc_amount.to_sat(),
+        EcdsaSighashType::All,
+        *local_htlc_privkey,
+    );
+
+    let mut remote_signature_der = remote_htlc_signature.serialize_der().to_vec();
+    remote_signature_der.push(counterparty_htlc_sighash_type(anchors) as u8);
+
+    let mut local_signature_der = local_signature.serialize_der().to_vec();
+    local_signature_der.push(EcdsaSighashType::All as u8);
+
+    let witness = &mut tx.input[0].witness;
+    // OP_CHECKMULTISIG pops one element too many
+    witness.push(Vec::new());
+    witness.push(remote_signature_der);
+    witness.push(local_signature_der);
+    // an empty element instead of the preimage takes the timeout branch
+    witness.push(Vec::new());
+    witness.push(offered_htlc_script.as_bytes());
+
+    tx
+}
+
+//
+// Exercise 29
+//
+
+/// `<remotehtlcsig> <payment_preimage> <offered_htlc_script>`: the receiver
+/// of an offered HTLC claims it straight from the offerer's commitment.
+pub fn offered_htlc_preimage_witness(
+    remote_htlc_signature: &Signature,
+    payment_preimage: &PaymentPreimage,
+    offered_htlc_script: &ScriptBuf,
+) -> Witness {
+    let mut signature_der = remote_htlc_signature.serialize_der().to_vec();
+    signature_der.push(EcdsaSighashType::All as u8);
+
+    let mut witness = Witness::new();
+    witness.push(signature_der);
+    witness.push(payment_preimage.0);
+    witness.push(offered_htlc_script.as_bytes());
+    witness
+}
+
+/// `0 <remotehtlcsig> <localhtlcsig> <payment_preimage> <received_htlc_script>`:
+/// the HTLC-success transaction spending a received HTLC from our commitment.
+pub fn htlc_success_witness(
+    remote_htlc_signature: &Signature,
+    local_htlc_signature: &Signature,
+    payment_preimage: &PaymentPreimage,
+    received_htlc_script: &ScriptBuf,
+    anchors: bool,
+) -> Witness {
+    let mut remote_signature_der = remote_htlc_signature.serialize_der().to_vec();
+    remote_signature_der.push(counterparty_htlc_sighash_type(anchors) as u8);
+
+    let mut local_signature_der = local_htlc_signature.serialize_der().to_vec();
+    local_signature_der.push(EcdsaSighashType::All as u8);
+
+    let mut witness = Witness::new();
+    // OP_CHECKMULTISIG pops one element too many
+    witness.push(Vec::new());
+    witness.push(remote_signature_der);
+    witness.push(local_signature_der);
+    witness.push(payment_preimage.0);
+    witness.push(received_htlc_script.as_bytes());
+    witness
+}
+
+//
+// Exercise 30
+//
+
+/// The `channel_id` both peers use once `funding_tx` is known, or `None` if it
+/// doesn't pay to `funding_script`.
+pub fn channel_id_for_funding_transaction(funding_tx: &Transaction, funding_script: &ScriptBuf) -> Option<[u8; 32]> {
+    let funding_spk = funding_script.to_p2wsh();
+    let output_index = funding_tx.output.iter().position(|output| output.script_pubkey == funding_spk)?;
+
+    Some(channel_id_from_funding(funding_tx.compute_txid(), output_index as u16))
+}
+
+//
+// Exercise 31
+//
+
+/// Bob's side of a cooperative spend of the funding output: add his
+/// signature to the PSBT Alice signed, finalize it and extract the
+/// transaction to broadcast.
+pub fn cosign_funding_psbt(alice_signed_psbt: Psbt, bob_secret_key: &SecretKey) -> Result<Transaction, ChannelError> {
+    let psbt = sign_funding_psbt(alice_signed_psbt, bob_secret_key);
+    let psbt = finalize_funding_psbt(psbt)?;
+
+    Ok(psbt.extract_tx()?)
+}
+
+//
+// Exercise 32
+//
+
+/// The TLV stream at the end of `open_channel`: the `upfront_shutdown_script`
+/// (type 0) and the `channel_type` feature bits (type 1), each only if given.
+pub fn encode_open_channel_tlvs(upfront_shutdown_script: Option<&ScriptBuf>, channel_type: Option<&[u8]>) -> Vec<u8> {
+    let mut records = Vec::new();
+
+    if let Some(script) = upfront_shutdown_script {
+        records.push(TlvRecord::new(UPFRONT_SHUTDOWN_SCRIPT_TLV, script.to_bytes()));
+    }
+    if let Some(channel_type) = channel_type {
+        records.push(TlvRecord::new(CHANNEL_TYPE_TLV, channel_type.to_vec()));
+    }
+
+    write_tlv_stream(&records).expect("the shutdown script and channel type records have distinct types")
+}
+
+//
+// Exercise 33
+//
+
+/// The `open_channel` the funder sends for a channel described by `params`.
+/// `counterparty_to_self_delay` is the delay we ask the peer to put on its
+/// own `to_local`; the reserve is 1% of the funding amount, but never below
+/// our dust limit.
+pub fn build_open_channel(
+    network: Network,
+    temporary_channel_id: [u8; 32],
+    params: &ChannelParams,
+    funding_amount: Amount,
+    push_msat: MilliSat,
+    counterparty_to_self_delay: u16,
+    first_per_commitment_point: secp256k1PublicKey,
+) -> OpenChannel {
+    let keys = &params.local_keys;
+
+    let mut channel_type = vec![STATIC_REMOTE_KEY_REQUIRED];
+    match params.htlc_fee_mode {
+        HtlcFeeMode::Legacy => {}
+        HtlcFeeMode::Anchors => channel_type.push(ANCHOR_OUTPUTS_REQUIRED),
+        HtlcFeeMode::ZeroFeeAnchors => channel_type.push(ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED),
+    }
+
+    OpenChannel {
+        chain_hash: ChainHash::using_genesis_block(network),
+        temporary_channel_id,
+        funding_satoshis: funding_amount,
+        push_msat,
+        dust_limit_satoshis: params.dust_limit,
+        max_htlc_value_in_flight_msat: MilliSat::from_amount(funding_amount).expect("funding amounts fit in msat"),
+        channel_reserve_satoshis: std::cmp::max(funding_amount / 100, params.dust_limit),
+        htlc_minimum_msat: MilliSat::from_msat(1),
+        feerate_per_kw: params.feerate_per_kw as u32,
+        to_self_delay: counterparty_to_self_delay,
+        max_accepted_htlcs: MAX_ACCEPTED_HTLCS,
+        funding_pubkey: keys.funding_pubkey,
+        revocation_basepoint: keys.revocation_basepoint,
+        payment_basepoint: keys.payment_basepoint,
+        delayed_payment_basepoint: keys.delayed_payment_basepoint,
+        htlc_basepoint: keys.htlc_basepoint,
+        first_per_commitment_point,
+        channel_flags: ANNOUNCE_CHANNEL_FLAG,
+        upfront_shutdown_script: None,
+        channel_type: Some(encode_features(&channel_type)),
+    }
+}
+
+//
+// Exercise 34
+//
+
+/// Compare the peer's `channel_reestablish` with our own state.
+/// `local_commitment_number` is the number of our latest commitment (every
+/// earlier one is revoked), and `remote_commitment_number` the number of the
+/// latest commitment of theirs we signed.
+pub fn handle_channel_reestablish(
+    keys: &ChannelKeyManager,
+    local_commitment_number: u64,
+    remote_commitment_number: u64,
+    msg: &ChannelReestablish,
+) -> ReestablishAction {
+    // The secret the peer should have last received from us, which revoked
+    // commitment `next_revocation_number - 1`.
+    let expected_secret = |next_revocation_number: u64| match next_revocation_number {
+        0 => [0; 32],
+        n => keys.per_commitment_secret(n - 1),
+    };
+
+    if msg.next_revocation_number > local_commitment_number {
+        return if msg.your_last_per_commitment_secret == expected_secret(msg.next_revocation_number) {
+            ReestablishAction::FallenBehind
+        } else {
+            ReestablishAction::FailChannel
+        };
+    }
+    if msg.your_last_per_commitment_secret != expected_secret(msg.next_revocation_number) {
+        return ReestablishAction::FailChannel;
+    }
+
+    let revoke_and_ack = match local_commitment_number - msg.next_revocation_number {
+        0 => false,
+        1 => true,
+        _ => return ReestablishAction::FailChannel,
+    };
+    let commitment_signed = match msg.next_commitment_number {
+        n if n == remote_commitment_number + 1 => false,
+        n if n == remote_commitment_number => true,
+        _ => return ReestablishAction::FailChannel,
+    };
+
+    if commitment_signed || revoke_and_ack {
+        ReestablishAction::Retransmit { commitment_signed, revoke_and_ack }
+    } else {
+        ReestablishAction::Proceed
+    }
+}
+
+//
+// Exercise 35
+//
+
+/// Check a `channel_announcement` against the funding output it claims:
+/// the node ids are in order, all four keys signed it, and the two bitcoin
+/// keys really are the keys of the 2-of-2 in `funding_output`.
+pub fn verify_channel_announcement(
+    announcement: &ChannelAnnouncement,
+    funding_output: &TxOut,
+) -> Result<(), GossipError> {
+    let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+
+    if announcement.node_id_1.serialize() >= announcement.node_id_2.serialize() {
+        return Err(GossipError::NodeIdsNotOrdered);
+    }
+
+    let message = bitcoin::secp256k1::Message::from_digest(announcement.signing_hash());
+    for (signature, key, name) in [
+        (&announcement.node_signature_1, &announcement.node_id_1, "node_id_1"),
+        (&announcement.node_signature_2, &announcement.node_id_2, "node_id_2"),
+        (&announcement.bitcoin_signature_1, &announcement.bitcoin_key_1, "bitcoin_key_1"),
+        (&announcement.bitcoin_signature_2, &announcement.bitcoin_key_2, "bitcoin_key_2"),
+    ] {
+        secp.verify_ecdsa(&message, signature, key).map_err(|_| GossipError::InvalidSignature(name))?;
+    }
+
+    let (first, second) =
+        sort_funding_keys(PublicKey::new(announcement.bitcoin_key_1), PublicKey::new(announcement.bitcoin_key_2));
+    let funding_script = two_of_two_multisig_witness_script(&first, &second);
+    if funding_script.to_p2wsh() != funding_output.script_pubkey {
+        return Err(GossipError::FundingScriptMismatch);
+    }
+
+    Ok(())
+}
+
+//
+// Exercise 36
+//
+
+/// Apply a `channel_update` to the direction of `channel` it describes.
+/// Returns `false` if the update is no newer than the policy we already have.
+pub fn apply_channel_update(channel: &mut GraphChannel, update: &ChannelUpdate) -> Result<bool, GossipError> {
+    if update.short_channel_id != channel.short_channel_id {
+        return Err(GossipError::UnknownChannel(update.short_channel_id.0));
+    }
+    verify_channel_update(update, &channel.node_one, &channel.node_two)?;
+
+    let capacity_msat = MilliSat::from_amount(channel.capacity).expect("channel capacities fit in msat");
+    if update.htlc_maximum_msat > capacity_msat {
+        return Err(GossipError::HtlcMaximumAboveCapacity {
+            htlc_maximum_msat: update.htlc_maximum_msat.to_msat(),
+            capacity_msat: capacity_msat.to_msat(),
+        });
+    }
+
+    let policy = channel.policy_mut(update.direction());
+    if policy.is_some_and(|policy| policy.last_update >= update.timestamp) {
+        return Ok(false);
+    }
+
+    *policy = Some(ChannelPolicy {
+        enabled: !update.is_disabled(),
+        cltv_expiry_delta: update.cltv_expiry_delta,
+        htlc_minimum_msat: update.htlc_minimum_msat,
+        htlc_maximum_msat: update.htlc_maximum_msat,
+        fee_base_msat: update.fee_base_msat,
+        fee_proportional_millionths: update.fee_proportional_millionths,
+        last_update: update.timestamp,
+    });
+    Ok(true)
+}
+
+//
+// Exercise 37
+//
+
+/// The cheapest route that delivers `amount_msat` to `destination`, with the
+/// CLTV deltas of the forwarding nodes adding up to at most `max_cltv`.
+///
+/// Fees depend on the amount each node forwards, which depends on the fees
+/// after it, so the search runs backwards from the destination.
+pub fn find_route(
+    graph: &NetworkGraph,
+    source: &secp256k1PublicKey,
+    destination: &secp256k1PublicKey,
+    amount_msat: MilliSat,
+    max_cltv: u32,
+) -> Option<Route> {
+    // For each node reached: the HTLC amount and CLTV delta on the channel
+    // into it, and where that channel leads on the way to the destination.
+    let mut best: BTreeMap<secp256k1PublicKey, (MilliSat, u32)> = BTreeMap::new();
+    let mut next_hop: BTreeMap<secp256k1PublicKey, (ShortChannelId, secp256k1PublicKey)> = BTreeMap::new();
+    let mut queue = BinaryHeap::new();
+
+    best.insert(*destination, (amount_msat, 0));
+    queue.push(Reverse((amount_msat, 0u32, *destination)));
+
+    while let Some(Reverse((amount, cltv, node))) = queue.pop() {
+        if best.get(&node) != Some(&(amount, cltv)) {
+            continue;
+        }
+        if node == *source {
+            break;
+        }
+
+        for channel in graph.incoming_channels(&node) {
+            let Some(policy) = channel.policy() else { continue };
+            let capacity_msat = MilliSat::from_amount(channel.channel.capacity).expect("channel capacities fit in msat");
+            if !policy.can_carry(amount) || amount > capacity_msat {
+                continue;
+            }
+
+            // The sender doesn't pay itself a fee for its own channel.
+            let previous = channel.source();
+            let (previous_amount, previous_cltv) = if previous == *source {
+                (amount, cltv)
+            } else {
+                (amount.checked_add(policy.fee_for(amount))?, cltv + policy.cltv_expiry_delta as u32)
+            };
+            if previous_cltv > max_cltv {
+                continue;
+            }
+
+            if best.get(&previous).is_none_or(|&known| (previous_amount, previous_cltv) < known) {
+                best.insert(previous, (previous_amount, previous_cltv));
+                next_hop.insert(previous, (channel.channel.short_channel_id, node));
+                queue.push(Reverse((previous_amount, previous_cltv, previous)));
+            }
+        }
+    }
+
+    let mut hops = Vec::new();
+    let mut node = *source;
+    while node != *destination {
+        let (short_channel_id, next) = *next_hop.get(&node)?;
+        let (amount_msat, cltv_expiry_delta) = best[&next];
+        hops.push(RouteHop { node_id: next, short_channel_id, amount_msat, cltv_expiry_delta });
+        node = next;
+    }
+
+    Some(Route { hops })
+}
+
+//
+// Exercise 38
+//
+
+/// The payment hash to lock the HTLC that pays `invoice` to. The invoice
+/// carries SHA256(preimage), but the HTLC scripts run the preimage through
+/// `OP_HASH160`, so they compare against RIPEMD160 of the invoice's hash.
+pub fn htlc_payment_hash(invoice: &Invoice) -> PaymentHash160 {
+    PaymentHash160(Ripemd160::hash(&invoice.payment_hash.0).to_byte_array())
+}
+
+//
+// Exercise 39
+//
+
+/// A signed BOLT 11 invoice for `payment_hash`, payable on `network`. The
+/// `x` field is left out when `expiry` is the default of an hour.
+pub fn create_invoice(
+    privkey: &SecretKey,
+    network: Network,
+    amount_msat: Option<MilliSat>,
+    payment_hash: &PaymentHash,
+    payment_secret: &PaymentSecret,
+    description: &str,
+    expiry: u64,
+    timestamp: u64,
+) -> Result<String, InvoiceError> {
+    let hrp = invoice_hrp(network, amount_msat);
+
+    let mut data = timestamp_to_words(timestamp);
+    write_tagged_field(&mut data, PAYMENT_SECRET_TAG, &bytes_to_words(&payment_secret.0))?;
+    write_tagged_field(&mut data, PAYMENT_HASH_TAG, &bytes_to_words(&payment_hash.0))?;
+    write_tagged_field(&mut data, DESCRIPTION_TAG, &bytes_to_words(description.as_bytes()))?;
+    if expiry != DEFAULT_EXPIRY {
+        write_tagged_field(&mut data, EXPIRY_TAG, &u64_to_words(expiry))?;
+    }
+    let features = features_to_words(&[VAR_ONION_OPTIN_REQUIRED, PAYMENT_SECRET_REQUIRED]);
+    write_tagged_field(&mut data, FEATURES_TAG, &features)?;
+
+    // The recovery id goes after the signature, so the payer can recover our
+    // node id without an `n` field.
+    let message = Message::from_digest(signing_hash(&hrp, &data));
+    let (recovery_id, signature) = Secp256k1::new().sign_ecdsa_recoverable(&message, privkey).serialize_compact();
+    let mut signature_bytes = signature.to_vec();
+    signature_bytes.push(recovery_id.to_i32() as u8);
+    data.extend(bytes_to_words(&signature_bytes));
+
+    Ok(encode_bech32(&hrp, &data))
+}
+
+//
+// Exercise 40
+//
+
+/// A keysend pays a node without asking it for an invoice first. The sender
+/// picks the preimage itself, locks the payment to its SHA256, and hands it
+/// to the recipient in the final hop's payload under `KEYSEND_PREIMAGE_TLV`.
+/// Only the recipient can peel the onion that far, so the hops in between
+/// never see it. There is no invoice, so there is no `payment_secret` either.
+///
+/// Returns the onion along with the payment hash it was built for.
+pub fn build_keysend_onion(
+    route: &Route,
+    final_cltv_expiry: u32,
+    preimage: &PaymentPreimage,
+    session_key: &SecretKey,
+) -> Result<(PaymentOnion, PaymentHash), OnionError> {
+    let Some(last) = route.hops.last() else {
+        return Err(OnionError::HopCountMismatch { hops: 0, payloads: 0 });
+    };
+    let payment_hash = preimage.hash();
+
+    let mut payloads = build_forwarding_payloads(route, final_cltv_expiry);
+    let records = vec![
+        TlvRecord::new(AMT_TO_FORWARD_TLV, encode_tu64(last.amount_msat.to_msat())),
+        TlvRecord::new(OUTGOING_CLTV_VALUE_TLV, encode_tu64(final_cltv_expiry as u64)),
+        TlvRecord::new(KEYSEND_PREIMAGE_TLV, preimage.0.to_vec()),
+    ];
+    payloads.push(write_tlv_stream(&records).expect("the final hop records have distinct types"));
+
+    let hops: Vec<secp256k1PublicKey> = route.hops.iter().map(|hop| hop.node_id).collect();
+    let packet = construct_onion_packet(session_key, &hops, &payloads, &payment_hash.0)?;
+
+    let onion = PaymentOnion {
+        packet,
+        amount_msat: route.total_amount(),
+        cltv_expiry: final_cltv_expiry + route.total_cltv_expiry_delta(),
+    };
+    Ok((onion, payment_hash))
+}
+
+/// The recipient only settles a keysend if the preimage it was sent really
+/// is the one behind the HTLC's `payment_hash`. Otherwise the HTLC can't be
+/// claimed with it and has to be failed back.
+pub fn settle_keysend(payload: &HopPayload, payment_hash: &PaymentHash) -> Option<PaymentPreimage> {
+    let preimage = payload.keysend_preimage?;
+    (preimage.hash() == *payment_hash).then_some(preimage)
+}
+
+//
+// Exercise 41
+//
+
+/// The BOLT 2 state transition table: where an HTLC goes when one of its
+/// commitments is signed or revoked.
+///
+/// An update we proposed goes into the counterparty's commitment when we
+/// sign it, is safe from that side once the commitment before it is revoked,
+/// and only then can the counterparty put it into ours. An update the
+/// counterparty proposed goes the other way round: into our commitment
+/// first, then into theirs. Any event not in the table leaves the HTLC where
+/// it is.
+pub fn next_htlc_state(state: HtlcState, event: CommitmentEvent) -> HtlcState {
+    use CommitmentEvent::*;
+    use HtlcState::*;
+
+    match (state, event) {
+        (LocalAddProposed, SendCommitmentSigned) => LocalAddSigned,
+        (LocalAddSigned, ReceiveRevokeAndAck) => LocalAddAcked,
+        (LocalAddAcked, ReceiveCommitmentSigned) => Committed,
+
+        (RemoteAddProposed, ReceiveCommitmentSigned) => RemoteAddAcked,
+        (RemoteAddAcked, SendCommitmentSigned) => RemoteAddSigned,
+        (RemoteAddSigned, ReceiveRevokeAndAck) => Committed,
+
+        (LocalFulfillProposed, SendCommitmentSigned) => LocalFulfillSigned,
+        (LocalFulfillSigned, ReceiveRevokeAndAck) => LocalFulfillAcked,
+        (LocalFulfillAcked, ReceiveCommitmentSigned) => Fulfilled,
+
+        (RemoteFulfillProposed, ReceiveCommitmentSigned) => RemoteFulfillAcked,
+        (RemoteFulfillAcked, SendCommitmentSigned) => RemoteFulfillSigned,
+        (RemoteFulfillSigned, ReceiveRevokeAndAck) => Fulfilled,
+
+        (state, _) => state,
+    }
+}
+
+//
+// Exercise 42
+//
+
+/// The counterparty revoked its current commitment. Before moving on to the
+/// next one, check that the secret it revealed is really the secret behind
+/// the point that commitment was built with: with it, Exercise 5 gives us
+/// the revocation key to take everything if that commitment is ever
+/// broadcast. A secret for any other point is no use, so reject it.
+///
+/// Keep the secret in the shachain store at its commitment's index. Leave
+/// `counterparty` as it was if anything is wrong.
+pub fn handle_revoke_and_ack(
+    counterparty: &mut CounterpartyCommitments,
+    revoke_and_ack: &RevokeAndAck,
+) -> Result<(), ChannelError> {
+    let commitment_number = counterparty.commitment_number;
+    let secret = SecretKey::from_slice(&revoke_and_ack.per_commitment_secret)
+        .map_err(|_| ChannelError::InvalidRevocationSecret { commitment_number })?;
+    if pubkey_from_secret(secret) != counterparty.per_commitment_point {
+        return Err(ChannelError::InvalidRevocationSecret { commitment_number });
+    }
+    counterparty
+        .revoked_secrets
+        .insert(MAX_SHACHAIN_INDEX - commitment_number, revoke_and_ack.per_commitment_secret)?;
+
+    counterparty.commitment_number += 1;
+    counterparty.per_commitment_point = counterparty.next_per_commitment_point;
+    counterparty.next_per_commitment_point = revoke_and_ack.next_per_commitment_point;
+    Ok(())
+}
+
+//
+// Exercise 43
+//
+
+/// Hand a watchtower the penalty transaction for a commitment the
+/// counterparty just revoked, without telling it which channel it is for.
+/// File it under the commitment's breach hint and encrypt it with its
+/// breach key, so the tower can only read it once that commitment is
+/// on-chain.
+pub fn create_justice_blob(revoked_commitment_txid: &Txid, justice_tx: &Transaction) -> JusticeBlob {
+    JusticeBlob {
+        hint: BreachHint::from_txid(revoked_commitment_txid),
+        encrypted_justice_tx: encrypt_blob(&breach_key(revoked_commitment_txid), &serialize(justice_tx)),
+    }
+}
+
+//
+// Exercise 44
+//
+
+/// The tower saw `breach_tx` in a block, under the hint of `blob`. Decrypt
+/// the justice transaction with the key of `breach_tx` and check that every
+/// input spends the breach: the tower shouldn't broadcast anything else for
+/// a client.
+pub fn open_justice_blob(blob: &JusticeBlob, breach_tx: &Transaction) -> Result<Transaction, WatchtowerError> {
+    let breach_txid = breach_tx.compute_txid();
+    let plaintext = decrypt_blob(&breach_key(&breach_txid), &blob.encrypted_justice_tx)?;
+    let justice_tx: Transaction = deserialize(&plaintext).map_err(|_| WatchtowerError::InvalidJusticeTransaction)?;
+
+    if justice_tx.input.is_empty() || justice_tx.input.iter().any(|input| input.previous_output.txid != breach_txid) {
+        return Err(WatchtowerError::NotABreachSpend);
+    }
+    Ok(justice_tx)
+}
+
+//
+// Exercise 45
+//
+
+/// Look through a new block for the transaction spending our channel's
+/// funding output. Only one can ever spend it.
+pub fn scan_block_for_channel_spends(block: &Block, funding_outpoint: OutPoint) -> Option<Transaction> {
+    block.txdata.iter().find(|tx| tx.input.iter().any(|input| input.previous_output == funding_outpoint)).cloned()
+}
+
+//
+// Exercise 46
+//
+
+/// Work out what kind of transaction spent the funding output. Commitments
+/// carry their obscured commitment number in the locktime and the funding
+/// input's sequence; closing transactions don't. Any commitment number
+/// below the counterparty's current one was revoked in `revoke_and_ack`.
+pub fn classify_funding_spend(
+    spend_tx: &Transaction,
+    obscure_factor: u64,
+    counterparty: &CounterpartyCommitments,
+) -> FundingSpend {
+    match recover_commitment_number(spend_tx, obscure_factor) {
+        None => FundingSpend::CooperativeClose,
+        Some(commitment_number) if commitment_number < counterparty.commitment_number => {
+            FundingSpend::RevokedCommitment { commitment_number }
+        }
+        Some(commitment_number) => FundingSpend::LatestCommitment { commitment_number },
+    }
+}
+
+//
+// Exercise 47
+//
+
+/// Check that `branch` proves its transaction is in the block with
+/// `merkle_root`. Start from the txid and hash in one sibling per level:
+/// the lowest bit of the index says whether we are the right child at the
+/// bottom level, the next bit at the level above, and so on.
+pub fn verify_merkle_branch(branch: &MerkleBranch, merkle_root: &TxMerkleNode) -> bool {
+    let mut node = TxMerkleNode::from_byte_array(branch.txid.to_byte_array());
+    for (level, sibling) in branch.hashes.iter().enumerate() {
+        node = if (branch.index >> level) & 1 == 1 {
+            merkle_parent(sibling, &node)
+        } else {
+            merkle_parent(&node, sibling)
+        };
+    }
+    node == *merkle_root
+}
+
+//
+// Exercise 48
+//
+
+/// How many confirmations the funding transaction has, if it is in `block`
+/// at `block_height` and the best block is at `best_height`. Check its
+/// merkle branch against the block header rather than trusting the list
+/// of transactions: a light client only has the header and the branch.
+pub fn funding_confirmations(block: &Block, block_height: u32, funding_txid: &Txid, best_height: u32) -> Option<u32> {
+    let branch = merkle_branch(block, funding_txid)?;
+    if !verify_merkle_branch(&branch, &block.header.merkle_root) {
+        return None;
+    }
+    Some(confirmations(block_height, best_height))
+}
+
+//
+// Exercise 49
+//
+
+/// A zero-fee HTLC transaction can't confirm on its own. Append a wallet
+/// input to pay its fee and an output for the change, leaving the HTLC
+/// input and its output at index 0: the counterparty's
+/// SIGHASH_SINGLE|ANYONECANPAY signature only covers that pair, so it
+/// stays valid.
+///
+/// `htlc_tx_weight` is the weight of the signed HTLC transaction on its
+/// own. Sign the HTLC input with SIGHASH_ALL only after bumping, since that
+/// signature covers every input and output.
+pub fn bump_zero_fee_htlc_transaction(
+    htlc_tx: Transaction,
+    htlc_tx_weight: u64,
+    fee_input: OutPoint,
+    fee_input_value: Amount,
+    change_script: ScriptBuf,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ExerciseError> {
+    let fee = fee_for_weight(feerate_per_kw, htlc_tx_weight + P2WPKH_INPUT_WEIGHT + P2WPKH_OUTPUT_WEIGHT);
+    let change = fee_input_value
+        .checked_sub(fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: fee_input_value.to_sat() })?;
+
+    let mut tx = htlc_tx;
+    tx.input.push(TxIn {
+        previous_output: fee_input,
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    });
+    tx.output.push(build_output(change, change_script));
+    Ok(tx)
+}
+
+//
+// Exercise 50
+//
+
+/// A commitment stuck in the mempool below `target_feerate_per_kw` can be
+/// pulled in by a child spending our anchor: miners take the two together
+/// if the pair pays enough. The child pays what the commitment's own
+/// `commitment_fee` doesn't, for the weight of both, and sends the anchor
+/// and the wallet UTXO, less that fee, to `change_script`.
+///
+/// The anchor is input 0, the wallet UTXO input 1. Both are left unsigned.
+pub fn build_anchor_cpfp_transaction(
+    anchor_outpoint: OutPoint,
+    wallet_utxo: &ChainUtxo,
+    target_feerate_per_kw: u64,
+    commitment_weight: u64,
+    commitment_fee: Amount,
+    change_script: ScriptBuf,
+) -> Result<Transaction, ExerciseError> {
+    let package_fee = fee_for_weight(target_feerate_per_kw, commitment_weight + ANCHOR_CPFP_TX_WEIGHT);
+    // the child pays for itself even if the commitment needs no help
+    let child_fee = std::cmp::max(
+        package_fee.checked_sub(commitment_fee).unwrap_or(Amount::ZERO),
+        fee_for_weight(target_feerate_per_kw, ANCHOR_CPFP_TX_WEIGHT),
+    );
+
+    let available = ANCHOR_OUTPUT_VALUE + wallet_utxo.value;
+    let change = available
+        .checked_sub(child_fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: child_fee.to_sat(), available: available.to_sat() })?;
+
+    let inputs = [anchor_outpoint, wallet_utxo.outpoint]
+        .into_iter()
+        .map(|previous_output| TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            // signal replaceability, to bump again if the feerate keeps rising
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        })
+        .collect();
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, inputs, vec![build_output(change, change_script)]))
+}
+
+//
+// Exercise 51
+//
+
+/// Replace a funding transaction that is stuck in the mempool with one
+/// paying `new_feerate_per_kw`. `original_tx` is signed, and pays
+/// `original_fee`. The replacement spends its inputs and `additional_input`,
+/// a P2WPKH wallet UTXO, into the same funding output and a change output,
+/// which takes the fee increase out of `additional_input`.
+///
+/// Every input signals replaceability, so the funding transaction can be
+/// bumped again. The inputs are left unsigned: the old signatures commit to
+/// the old outputs.
+pub fn bump_funding_fee(
+    original_tx: &Transaction,
+    original_fee: Amount,
+    additional_input: &ChainUtxo,
+    change_script: ScriptBuf,
+    new_feerate_per_kw: u64,
+) -> Result<Transaction, ExerciseError> {
+    let weight = original_tx.weight().to_wu() + P2WPKH_INPUT_WEIGHT + P2WPKH_OUTPUT_WEIGHT;
+    let new_fee = fee_for_weight(new_feerate_per_kw, weight);
+    let required = min_replacement_fee(original_fee, weight);
+    if new_fee < required {
+        return Err(ExerciseError::InsufficientReplacementFee { fee: new_fee.to_sat(), required: required.to_sat() });
+    }
+
+    let available = original_fee + additional_input.value;
+    let change = available
+        .checked_sub(new_fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: new_fee.to_sat(), available: available.to_sat() })?;
+
+    let previous_outputs = original_tx.input.iter().map(|input| input.previous_output);
+    let inputs = previous_outputs
+        .chain([additional_input.outpoint])
+        .map(|previous_output| TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        })
+        .collect();
+    let mut outputs = original_tx.output.clone();
+    outputs.push(build_output(change, change_script));
+    Ok(build_transaction(original_tx.version, original_tx.lock_time, inputs, outputs))
+}
+
+//
+// Exercise 52
+//
+
+/// The fee to answer `closing_signed` with, given the range we would agree
+/// to and the fee we proposed last, if any. Answering with the fee the peer
+/// proposed agrees to it.
+///
+/// The fee ranges have to overlap. The funder proposes first, and the
+/// fundee either agrees or counters with the fee in the overlap closest to
+/// the funder's. The funder then has to agree to the counter, since it is
+/// in its own range: the negotiation never takes more than two rounds.
+pub fn closing_signed_fee(
+    is_funder: bool,
+    our_fee_range: &FeeRange,
+    our_last_fee: Option<Amount>,
+    closing_signed: &ClosingSigned,
+) -> Result<Amount, ClosingError> {
+    let their_fee_range = closing_signed.fee_range.ok_or(ClosingError::MissingFeeRange)?;
+    let fee = closing_signed.fee_satoshis;
+
+    let overlap = FeeRange {
+        min_fee_satoshis: our_fee_range.min_fee_satoshis.max(their_fee_range.min_fee_satoshis),
+        max_fee_satoshis: our_fee_range.max_fee_satoshis.min(their_fee_range.max_fee_satoshis),
+    };
+    if overlap.min_fee_satoshis > overlap.max_fee_satoshis {
+        return Err(ClosingError::NoFeeRangeOverlap { ours: *our_fee_range, theirs: their_fee_range });
+    }
+    if !their_fee_range.contains(fee) {
+        return Err(ClosingError::FeeOutsideRange(fee.to_sat()));
+    }
+
+    if is_funder {
+        // the fundee's counter, which we can only agree to
+        if !overlap.contains(fee) {
+            return Err(ClosingError::FeeOutsideRange(fee.to_sat()));
+        }
+        return Ok(fee);
+    }
+
+    // the funder answers our counter by agreeing to it
+    if let Some(sent) = our_last_fee {
+        if sent != fee {
+            return Err(ClosingError::FeeChanged { sent: sent.to_sat(), received: fee.to_sat() });
+        }
+    }
+    Ok(fee.clamp(overlap.min_fee_satoshis, overlap.max_fee_satoshis))
+}
+
+//
+// Exercise 53
+//
+
+/// Check the script the peer's `shutdown` asks to be paid to. If it set an
+/// `upfront_shutdown_script` when the channel opened, it can't close to any
+/// other: whoever steals its node key can't redirect its funds. An empty
+/// upfront script means it didn't commit to one.
+pub fn check_counterparty_shutdown(
+    shutdown: &Shutdown,
+    upfront_shutdown_script: Option<&ScriptBuf>,
+    anysegwit: bool,
+) -> Result<(), ShutdownScriptError> {
+    if let Some(upfront_script) = upfront_shutdown_script.filter(|script| !script.is_empty()) {
+        if *upfront_script != shutdown.scriptpubkey {
+            return Err(ShutdownScriptError::UpfrontScriptMismatch);
+        }
+    }
+    validate_shutdown_script(&shutdown.scriptpubkey, anysegwit)
+}
+
+//
+// Exercise 54
+//
+
+/// Serialize `backup`, version byte first and every field in the order of
+/// `StaticChannelBackup`, and encrypt it under the node seed's backup key.
+/// Integers are big-endian, like on the wire, and the funder flag is one
+/// byte.
+pub fn encrypt_channel_backup(node_seed: &[u8; 32], backup: &StaticChannelBackup) -> Vec<u8> {
+    let mut plaintext = vec![STATIC_CHANNEL_BACKUP_VERSION];
+    plaintext.extend_from_slice(&backup.channel_id);
+    plaintext.extend_from_slice(backup.funding_outpoint.txid.as_byte_array());
+    plaintext.extend_from_slice(&backup.funding_outpoint.vout.to_be_bytes());
+    plaintext.extend_from_slice(&backup.peer_node_id.serialize());
+    plaintext.extend_from_slice(&backup.channel_key_index.to_be_bytes());
+    plaintext.push(backup.is_funder as u8);
+    plaintext.extend_from_slice(&backup.to_self_delay.to_be_bytes());
+    let keys = &backup.counterparty_keys;
+    for key in [
+        keys.funding_pubkey,
+        keys.payment_basepoint,
+        keys.delayed_payment_basepoint,
+        keys.htlc_basepoint,
+        keys.revocation_basepoint,
+    ] {
+        plaintext.extend_from_slice(&key.serialize());
+    }
+    encrypt_blob(&backup_key(node_seed), &plaintext)
+}
+
+//
+// Exercise 55
+//
+
+/// Decrypt and parse a backup written by `encrypt_channel_backup`, and
+/// derive the channel's keys again from the node seed.
+///
+/// We have lost every commitment, so we can't close the channel ourselves.
+/// Instead, reestablish it as if it had just opened: with zero commitment
+/// numbers and an all-zero secret. A peer whose channel has moved on since
+/// can't reconcile that with its own state, so it closes the channel with
+/// its latest commitment.
+pub fn restore_channel_backup(node_seed: &[u8; 32], blob: &[u8]) -> Result<RestoredChannel, BackupError> {
+    let plaintext = decrypt_blob(&backup_key(node_seed), blob).map_err(|_| BackupError::DecryptionFailed)?;
+    let mut reader = Reader::new(&plaintext);
+    let version = reader.read_bytes(1)?[0];
+    if version != STATIC_CHANNEL_BACKUP_VERSION {
+        return Err(BackupError::UnknownVersion(version));
+    }
+
+    let channel_id = reader.read_array()?;
+    let txid = Txid::from_byte_array(reader.read_array()?);
+    let funding_outpoint = OutPoint::new(txid, reader.read_u32()?);
+    let peer_node_id = reader.read_pubkey()?;
+    let channel_key_index = reader.read_u64()?;
+    let is_funder = reader.read_bytes(1)?[0] != 0;
+    let to_self_delay = reader.read_u16()?;
+    let counterparty_keys = ChannelPublicKeys {
+        funding_pubkey: reader.read_pubkey()?,
+        payment_basepoint: reader.read_pubkey()?,
+        delayed_payment_basepoint: reader.read_pubkey()?,
+        htlc_basepoint: reader.read_pubkey()?,
+        revocation_basepoint: reader.read_pubkey()?,
+    };
+    let trailing = reader.read_remaining().len();
+    if trailing > 0 {
+        return Err(BackupError::TrailingBytes(trailing));
+    }
+
+    let backup = StaticChannelBackup {
+        channel_id,
+        funding_outpoint,
+        peer_node_id,
+        channel_key_index,
+        is_funder,
+        to_self_delay,
+        counterparty_keys,
+    };
+    let keys = ChannelKeyManager::from_seed(node_seed, channel_key_index);
+    let channel_reestablish = ChannelReestablish {
+        channel_id,
+        next_commitment_number: 0,
+        next_revocation_number: 0,
+        your_last_per_commitment_secret: [0; 32],
+        my_current_per_commitment_point: keys.per_commitment_point(0),
+    };
+    Ok(RestoredChannel { backup, keys, channel_reestablish })
+}
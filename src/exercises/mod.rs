@@ -1,3 +1,5 @@
 pub mod exercises;
+pub mod onion;
 pub mod solutions;
-pub mod test;
\ No newline at end of file
+pub mod test;
+pub mod transport;
@@ -0,0 +1,380 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Scalar, Secp256k1, SecretKey};
+use internal::errors::OnionError;
+use internal::noise_utils::hmac_sha256;
+use internal::onion_utils::{
+    blinding_factor, frame_hop_payload, generate_cipher_stream, generate_key, packet_hmac, shared_secret,
+    xor_in_place, OnionPacket, AMT_TO_FORWARD_TLV, FAILURE_MESSAGE_MIN_LEN, HMAC_LEN, HOP_PAYLOADS_LEN,
+    ONION_VERSION, OUTGOING_CLTV_VALUE_TLV, PAYMENT_DATA_TLV, SHORT_CHANNEL_ID_TLV,
+};
+use internal::payments::{PaymentHash, PaymentSecret};
+use internal::routing::Route;
+use internal::tlv::{encode_tu64, read_bigsize, write_tlv_stream, TlvRecord};
+use internal::units::MilliSat;
+use internal::wire::{Reader, ShortChannelId};
+
+/// Everything the sender derives for one hop of the route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopKeys {
+    pub shared_secret: [u8; 32],
+    pub blinding_factor: [u8; 32],
+    /// The ephemeral key this hop sees in the packet it receives.
+    pub ephemeral_pubkey: secp256k1PublicKey,
+    /// Keys the ChaCha20 stream that encrypts this hop's layer.
+    pub rho: [u8; 32],
+    /// Keys the HMAC this hop checks.
+    pub mu: [u8; 32],
+}
+
+/// What a hop learns from peeling its layer of the onion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeeledOnion {
+    /// Forward `next_packet` along the channel named in `payload`.
+    Forward { payload: Vec<u8>, next_packet: Box<OnionPacket> },
+    /// We are the final hop; `payload` says what we should be paid.
+    Receive { payload: Vec<u8> },
+}
+
+//
+// Shared secrets
+//
+
+/// Walk the route from the first hop, deriving each hop's shared secret from
+/// the current ephemeral key, then blinding that key so the next hop sees a
+/// different one. Blinding the secret key by the same factor keeps the pair
+/// in step.
+pub fn compute_hop_keys(session_key: &SecretKey, hops: &[secp256k1PublicKey]) -> Vec<HopKeys> {
+    let secp = Secp256k1::new();
+    let mut ephemeral_key = *session_key;
+    let mut keys = Vec::with_capacity(hops.len());
+
+    for hop in hops {
+        let ephemeral_pubkey = secp256k1PublicKey::from_secret_key(&secp, &ephemeral_key);
+        let shared_secret = shared_secret(&ephemeral_key, hop);
+        let blinding_factor = blinding_factor(&ephemeral_pubkey, &shared_secret);
+
+        keys.push(HopKeys {
+            shared_secret,
+            blinding_factor,
+            ephemeral_pubkey,
+            rho: generate_key(b"rho", &shared_secret),
+            mu: generate_key(b"mu", &shared_secret),
+        });
+
+        // A SHA256 output is a valid scalar with overwhelming probability.
+        ephemeral_key = ephemeral_key
+            .mul_tweak(&Scalar::from_be_bytes(blinding_factor).unwrap())
+            .expect("blinded ephemeral key is not zero");
+    }
+
+    keys
+}
+
+//
+// Filler
+//
+
+/// Each hop shifts its payload out of the front of the packet and pads the
+/// end with its rho stream before passing it on. The filler is what those
+/// pads look like by the time the packet reaches the last hop, so the sender
+/// can write it into the packet up front and every HMAC still checks out.
+///
+/// `framed_payloads` are the length-prefixed payloads; each one also takes a
+/// 32-byte HMAC in the packet.
+pub fn generate_filler(hop_keys: &[HopKeys], framed_payloads: &[Vec<u8>]) -> Vec<u8> {
+    let mut filler: Vec<u8> = Vec::new();
+
+    for (keys, payload) in hop_keys.iter().zip(framed_payloads).take(hop_keys.len().saturating_sub(1)) {
+        let start = HOP_PAYLOADS_LEN - filler.len();
+        filler.resize(filler.len() + payload.len() + HMAC_LEN, 0);
+
+        let stream = generate_cipher_stream(&keys.rho, 2 * HOP_PAYLOADS_LEN);
+        xor_in_place(&mut filler, &stream[start..]);
+    }
+
+    filler
+}
+
+//
+// Packet construction
+//
+
+/// Build the onion from the inside out: starting at the final hop, shift the
+/// packet right, write the hop's payload and the HMAC of the layer inside it,
+/// then encrypt with the hop's rho stream and HMAC the result with its mu
+/// key. The packet starts as random-looking padding derived from the session
+/// key, and the final hop's layer ends with the filler.
+///
+/// `payloads` are each hop's TLV stream, without the length prefix, and
+/// `associated_data` is the payment hash.
+pub fn construct_onion_packet(
+    session_key: &SecretKey,
+    hops: &[secp256k1PublicKey],
+    payloads: &[Vec<u8>],
+    associated_data: &[u8; 32],
+) -> Result<OnionPacket, OnionError> {
+    if hops.len() != payloads.len() || hops.is_empty() {
+        return Err(OnionError::HopCountMismatch { hops: hops.len(), payloads: payloads.len() });
+    }
+
+    let framed_payloads: Vec<Vec<u8>> = payloads.iter().map(|payload| frame_hop_payload(payload)).collect();
+    let length = framed_payloads.iter().map(|payload| payload.len() + HMAC_LEN).sum();
+    if length > HOP_PAYLOADS_LEN {
+        return Err(OnionError::PayloadsTooLong { length });
+    }
+
+    let hop_keys = compute_hop_keys(session_key, hops);
+    let filler = generate_filler(&hop_keys, &framed_payloads);
+
+    let pad_key = generate_key(b"pad", &session_key.secret_bytes());
+    let mut hop_payloads: [u8; HOP_PAYLOADS_LEN] =
+        generate_cipher_stream(&pad_key, HOP_PAYLOADS_LEN).try_into().unwrap();
+    let mut next_hmac = [0u8; HMAC_LEN];
+
+    for (i, (keys, payload)) in hop_keys.iter().zip(&framed_payloads).enumerate().rev() {
+        let shift = payload.len() + HMAC_LEN;
+        hop_payloads.copy_within(..HOP_PAYLOADS_LEN - shift, shift);
+        hop_payloads[..payload.len()].copy_from_slice(payload);
+        hop_payloads[payload.len()..shift].copy_from_slice(&next_hmac);
+
+        xor_in_place(&mut hop_payloads, &generate_cipher_stream(&keys.rho, HOP_PAYLOADS_LEN));
+
+        if i == hop_keys.len() - 1 {
+            hop_payloads[HOP_PAYLOADS_LEN - filler.len()..].copy_from_slice(&filler);
+        }
+
+        next_hmac = packet_hmac(&keys.mu, &hop_payloads, associated_data);
+    }
+
+    Ok(OnionPacket {
+        version: ONION_VERSION,
+        public_key: hop_keys[0].ephemeral_pubkey,
+        hop_payloads,
+        hmac: next_hmac,
+    })
+}
+
+//
+// Packet processing
+//
+
+/// What a forwarding node does with an incoming onion: check the HMAC,
+/// decrypt the hop payloads with a rho stream twice their length so 1300
+/// fresh bytes are left after our payload is cut off the front, then blind
+/// the ephemeral key for the next hop. An all-zero next HMAC means we are
+/// the final hop.
+pub fn peel_onion(
+    packet: &OnionPacket,
+    node_key: &SecretKey,
+    associated_data: &[u8; 32],
+) -> Result<PeeledOnion, OnionError> {
+    if packet.version != ONION_VERSION {
+        return Err(OnionError::UnknownVersion(packet.version));
+    }
+
+    let shared_secret = shared_secret(node_key, &packet.public_key);
+    let mu = generate_key(b"mu", &shared_secret);
+    if packet_hmac(&mu, &packet.hop_payloads, associated_data) != packet.hmac {
+        return Err(OnionError::InvalidHmac);
+    }
+
+    let rho = generate_key(b"rho", &shared_secret);
+    let mut bytes = packet.hop_payloads.to_vec();
+    bytes.resize(2 * HOP_PAYLOADS_LEN, 0);
+    xor_in_place(&mut bytes, &generate_cipher_stream(&rho, 2 * HOP_PAYLOADS_LEN));
+
+    let mut reader = Reader::new(&bytes);
+    let length = read_bigsize(&mut reader).map_err(|_| OnionError::InvalidPayloadLength)?;
+    // Any length that could come close to the limit takes a 3-byte BigSize.
+    if length > (HOP_PAYLOADS_LEN - HMAC_LEN - 3) as u64 {
+        return Err(OnionError::InvalidPayloadLength);
+    }
+    let payload = reader.read_bytes(length as usize).unwrap().to_vec();
+    let hmac = reader.read_array::<HMAC_LEN>().unwrap();
+    let hop_payloads = reader.read_array::<HOP_PAYLOADS_LEN>().unwrap();
+
+    if hmac == [0; HMAC_LEN] {
+        return Ok(PeeledOnion::Receive { payload });
+    }
+
+    let secp = Secp256k1::new();
+    let blinding_factor = blinding_factor(&packet.public_key, &shared_secret);
+    let public_key = packet
+        .public_key
+        .mul_tweak(&secp, &Scalar::from_be_bytes(blinding_factor).map_err(|_| OnionError::InvalidPublicKey)?)
+        .map_err(|_| OnionError::InvalidPublicKey)?;
+
+    Ok(PeeledOnion::Forward {
+        payload,
+        next_packet: Box::new(OnionPacket { version: ONION_VERSION, public_key, hop_payloads, hmac }),
+    })
+}
+
+//
+// Failure messages
+//
+
+/// A failure the sender has decrypted and traced back to a hop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnionFailure {
+    /// Position of the failing node in the route, starting from 0.
+    pub hop_index: usize,
+    pub node_id: secp256k1PublicKey,
+    pub failure_code: u16,
+    pub failure_data: Vec<u8>,
+}
+
+/// The failing node builds `HMAC(um, rest) || rest`, where `rest` is the
+/// length-prefixed failure message followed by length-prefixed zero padding.
+pub fn build_failure_packet(shared_secret: &[u8; 32], failure_code: u16, failure_data: &[u8]) -> Vec<u8> {
+    let mut failure_message = failure_code.to_be_bytes().to_vec();
+    failure_message.extend_from_slice(failure_data);
+    let pad_len = FAILURE_MESSAGE_MIN_LEN.saturating_sub(failure_message.len());
+
+    let mut rest = Vec::with_capacity(4 + failure_message.len() + pad_len);
+    rest.extend_from_slice(&(failure_message.len() as u16).to_be_bytes());
+    rest.extend_from_slice(&failure_message);
+    rest.extend_from_slice(&(pad_len as u16).to_be_bytes());
+    rest.resize(rest.len() + pad_len, 0);
+
+    let um = generate_key(b"um", shared_secret);
+    let mut packet = hmac_sha256(&um, &[&rest]).to_vec();
+    packet.extend_from_slice(&rest);
+    packet
+}
+
+/// Every node on the way back, starting with the failing node itself, XORs
+/// the packet with its ammag stream. Only the sender, who knows every shared
+/// secret, can take the layers off again.
+pub fn encrypt_failure_packet(shared_secret: &[u8; 32], packet: &[u8]) -> Vec<u8> {
+    let ammag = generate_key(b"ammag", shared_secret);
+    let mut encrypted = packet.to_vec();
+    xor_in_place(&mut encrypted, &generate_cipher_stream(&ammag, packet.len()));
+    encrypted
+}
+
+/// Strip one ammag layer per hop, starting from the first, until the `um`
+/// HMAC of a hop matches. That hop built the failure.
+pub fn decrypt_failure_onion(
+    session_key: &SecretKey,
+    hops: &[secp256k1PublicKey],
+    packet: &[u8],
+) -> Result<OnionFailure, OnionError> {
+    if packet.len() < HMAC_LEN {
+        return Err(OnionError::InvalidFailureMessage);
+    }
+
+    let mut packet = packet.to_vec();
+    for (hop_index, (keys, node_id)) in compute_hop_keys(session_key, hops).iter().zip(hops).enumerate() {
+        packet = encrypt_failure_packet(&keys.shared_secret, &packet);
+
+        let um = generate_key(b"um", &keys.shared_secret);
+        let (hmac, rest) = packet.split_at(HMAC_LEN);
+        if hmac_sha256(&um, &[rest]) != hmac {
+            continue;
+        }
+
+        let mut reader = Reader::new(rest);
+        let failure_message = reader.read_u16_prefixed().map_err(|_| OnionError::InvalidFailureMessage)?;
+        if failure_message.len() < 2 {
+            return Err(OnionError::InvalidFailureMessage);
+        }
+        reader.read_u16_prefixed().map_err(|_| OnionError::InvalidFailureMessage)?;
+
+        return Ok(OnionFailure {
+            hop_index,
+            node_id: *node_id,
+            failure_code: u16::from_be_bytes([failure_message[0], failure_message[1]]),
+            failure_data: failure_message[2..].to_vec(),
+        });
+    }
+
+    Err(OnionError::UnattributableFailure)
+}
+
+//
+// Hop payloads
+//
+
+/// An onion for a route, with the HTLC the sender offers the first hop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentOnion {
+    pub packet: OnionPacket,
+    pub amount_msat: MilliSat,
+    pub cltv_expiry: u32,
+}
+
+/// An intermediate hop is told what HTLC to offer and on which channel.
+/// Amounts and expiries are truncated integers; the channel id is a full
+/// eight bytes.
+pub fn build_hop_payload(short_channel_id: ShortChannelId, amt_to_forward: MilliSat, outgoing_cltv: u32) -> Vec<u8> {
+    let records = vec![
+        TlvRecord::new(AMT_TO_FORWARD_TLV, encode_tu64(amt_to_forward.to_msat())),
+        TlvRecord::new(OUTGOING_CLTV_VALUE_TLV, encode_tu64(outgoing_cltv as u64)),
+        TlvRecord::new(SHORT_CHANNEL_ID_TLV, short_channel_id.0.to_be_bytes().to_vec()),
+    ];
+    write_tlv_stream(&records).unwrap()
+}
+
+/// The final hop gets no channel. Instead it gets the invoice's
+/// `payment_secret`, so a node along the way cannot probe it, and
+/// `total_msat`, so it can wait for every part of a multi-part payment
+/// before settling.
+pub fn build_final_hop_payload(
+    amt_to_forward: MilliSat,
+    outgoing_cltv: u32,
+    payment_secret: &PaymentSecret,
+    total_msat: MilliSat,
+) -> Vec<u8> {
+    let mut payment_data = payment_secret.0.to_vec();
+    payment_data.extend_from_slice(&encode_tu64(total_msat.to_msat()));
+
+    let records = vec![
+        TlvRecord::new(AMT_TO_FORWARD_TLV, encode_tu64(amt_to_forward.to_msat())),
+        TlvRecord::new(OUTGOING_CLTV_VALUE_TLV, encode_tu64(outgoing_cltv as u64)),
+        TlvRecord::new(PAYMENT_DATA_TLV, payment_data),
+    ];
+    write_tlv_stream(&records).unwrap()
+}
+
+/// The payloads of every hop but the last. Each describes the HTLC on the
+/// *next* channel of the route, which is why the amounts and deltas are
+/// shifted by one; the HTLC on the first channel is the one the sender
+/// offers itself.
+pub fn build_forwarding_payloads(route: &Route, final_cltv_expiry: u32) -> Vec<Vec<u8>> {
+    route
+        .hops
+        .windows(2)
+        .map(|pair| {
+            let next = &pair[1];
+            build_hop_payload(next.short_channel_id, next.amount_msat, final_cltv_expiry + next.cltv_expiry_delta)
+        })
+        .collect()
+}
+
+/// Turn a route from `find_route` into an onion. This is a single-part
+/// payment, so the final hop's `total_msat` is the amount it receives.
+pub fn build_payment_onion(
+    route: &Route,
+    final_cltv_expiry: u32,
+    payment_secret: &PaymentSecret,
+    payment_hash: &PaymentHash,
+    session_key: &SecretKey,
+) -> Result<PaymentOnion, OnionError> {
+    let Some(last) = route.hops.last() else {
+        return Err(OnionError::HopCountMismatch { hops: 0, payloads: 0 });
+    };
+
+    let mut payloads = build_forwarding_payloads(route, final_cltv_expiry);
+    payloads.push(build_final_hop_payload(last.amount_msat, final_cltv_expiry, payment_secret, last.amount_msat));
+
+    let hops: Vec<secp256k1PublicKey> = route.hops.iter().map(|hop| hop.node_id).collect();
+    let packet = construct_onion_packet(session_key, &hops, &payloads, &payment_hash.0)?;
+
+    Ok(PaymentOnion {
+        packet,
+        amount_msat: route.total_amount(),
+        cltv_expiry: final_cltv_expiry + route.total_cltv_expiry_delta(),
+    })
+}
@@ -8,11 +8,19 @@
 use crate::internal;
 use bitcoin::script::{Builder, ScriptBuf, ScriptHash};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys};
-use internal::tx_utils::{build_output, build_transaction};
-use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
+use internal::fee_utils::{balances_after_funder_fee, commit_tx_fee, HtlcFeeMode};
+use internal::htlc_utils::{is_htlc_trimmed, Htlc};
+use internal::payments::PaymentHash160;
+use internal::errors::ExerciseError;
+use internal::key_utils::check_compressed;
+use internal::keys::{DelayedPaymentPubkey, HtlcPubkey, RevocationPubkey};
+use internal::tx_utils::{build_output, build_transaction, check_output_amount, check_to_self_delay, obscured_commitment_transaction_numbers,
+    sort_outputs_bolt3, strip_cltv_expiries};
+use internal::script_utils::{build_htlc_offerer_witness_script, build_htlc_receiver_witness_script, p2wpkh_output_script};
 use bitcoin::blockdata::opcodes::all as opcodes;
 use bitcoin::secp256k1::{SecretKey, PublicKey as secp256k1PublicKey, Scalar};
 use bitcoin::PublicKey;
+use bitcoin::Amount;
 use bitcoin::hashes::Hash;
 use bitcoin::{Block, OutPoint, PubkeyHash, Sequence, Transaction, TxIn, TxOut, Witness};
 use bitcoin::transaction::Version;
@@ -43,7 +51,7 @@ pub fn build_funding_transaction(
     txins: Vec<TxIn>,
     alice_pubkey: &PublicKey,
     bob_pubkey: &PublicKey,
-    amount: u64,
+    amount: Amount,
 ) -> Transaction {
 
     let witness_script = two_of_two_multisig_witness_script(alice_pubkey, bob_pubkey);
@@ -53,14 +61,12 @@ pub fn build_funding_transaction(
     let version = Version::TWO;
     let locktime = LockTime::ZERO;
 
-    let tx = build_transaction(
+    build_transaction(
         version,
         locktime,
         txins,
         vec![txout],
-    );
-
-    tx
+    )
 }
 
 //
@@ -71,8 +77,8 @@ pub fn build_refund_transaction(
     funding_txin: TxIn,
     alice_pubkey: PublicKey,
     bob_pubkey: PublicKey,
-    alice_balance: u64,
-    bob_balance: u64
+    alice_balance: Amount,
+    bob_balance: Amount
 ) -> Transaction {
 
     let alice_script = p2wpkh_output_script(alice_pubkey);
@@ -86,11 +92,12 @@ pub fn build_refund_transaction(
     let version = Version::TWO;
     let locktime = LockTime::ZERO;
 
-    let tx = build_transaction(version,
+    let outputs = sort_outputs_bolt3(vec![(alice_output, None), (bob_output, None)]);
+
+    build_transaction(version,
                       locktime,
                       vec![funding_txin],
-                      vec![bob_output, alice_output]);
-    tx
+                      strip_cltv_expiries(outputs))
 }
 
 //
@@ -113,9 +120,7 @@ pub fn generate_revocation_pubkey(
     let P =
         pubkey_multipication_tweak(per_commitment_point, h2);
 
-    let revocation_pubkey = add_pubkeys(R, P);
-
-    revocation_pubkey
+    add_pubkeys(R, P)
 }
 
 //
@@ -144,21 +149,25 @@ pub fn generate_revocation_privkey(countersignatory_per_commitment_secret: Secre
 //
 
 pub fn to_local(
-    revocation_key: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_key: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     to_self_delay: i64,
-) -> ScriptBuf {
-    Builder::new()
+) -> Result<ScriptBuf, ExerciseError> {
+    check_to_self_delay(to_self_delay)?;
+    check_compressed("revocation key", &revocation_key.0)?;
+    check_compressed("to_local delayed pubkey", &to_local_delayed_pubkey.0)?;
+
+    Ok(Builder::new()
         .push_opcode(opcodes::OP_IF)
-        .push_key(revocation_key)
+        .push_key(&revocation_key.0)
         .push_opcode(opcodes::OP_ELSE)
         .push_int(to_self_delay)
         .push_opcode(opcodes::OP_CSV)
         .push_opcode(opcodes::OP_DROP)
-        .push_key(to_local_delayed_pubkey)
+        .push_key(&to_local_delayed_pubkey.0)
         .push_opcode(opcodes::OP_ENDIF)
         .push_opcode(opcodes::OP_CHECKSIG)
-        .into_script()
+        .into_script())
 }
 
 //
@@ -167,16 +176,27 @@ pub fn to_local(
 
 pub fn build_commitment_transaction(
     funding_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     remote_pubkey: PublicKey,
     to_self_delay: i64,
-    local_amount: u64,
-    remote_amount: u64,
-) -> Transaction {
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    feerate_per_kw: u64,
+    obscure_factor: u64,
+    commitment_number: u64,
+) -> Result<Transaction, ExerciseError> {
+    let fee = commit_tx_fee(feerate_per_kw, 0, false);
+    let (local_amount, remote_amount) =
+        balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    check_output_amount("to_local", local_amount)?;
+    check_output_amount("to_remote", remote_amount)?;
+    check_compressed("remote pubkey", &remote_pubkey)?;
 
     let to_local_script =
-        to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
+        to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
 
     let to_remote_script = p2wpkh_output_script(remote_pubkey);
 
@@ -185,13 +205,17 @@ pub fn build_commitment_transaction(
     let remote_output = build_output(remote_amount, to_remote_script);
 
     let version = Version::TWO;
-    let locktime = LockTime::ZERO;
 
-    let tx = build_transaction(version,
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+    let mut funding_txin = funding_txin;
+    funding_txin.sequence = sequence;
+
+    let outputs = sort_outputs_bolt3(vec![(local_output, None), (remote_output, None)]);
+
+    Ok(build_transaction(version,
                       locktime,
                       vec![funding_txin],
-                      vec![remote_output, local_output]);
-    tx
+                      strip_cltv_expiries(outputs)))
 }
 
 //
@@ -200,44 +224,81 @@ pub fn build_commitment_transaction(
 
 pub fn build_htlc_commitment_transaction(
     funding_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    remote_htlc_pubkey: &PublicKey,
-    local_htlc_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     remote_pubkey: PublicKey,
     to_self_delay: i64,
-    payment_hash160: &[u8; 20],
-    htlc_amount: u64,
-    local_amount: u64,
-    remote_amount: u64,
-) -> Transaction {
-    let htlc_offerer_script = build_htlc_offerer_witness_script(
-        revocation_pubkey,
-        remote_htlc_pubkey,
-        local_htlc_pubkey,
-        payment_hash160,
-    );
+    htlcs: &[Htlc],
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+    obscure_factor: u64,
+    commitment_number: u64,
+) -> Result<Transaction, ExerciseError> {
+    let untrimmed_htlcs: Vec<&Htlc> = htlcs
+        .iter()
+        .filter(|htlc| {
+            !is_htlc_trimmed(htlc.amount_msat.to_amount_floor(), dust_limit, feerate_per_kw, htlc.offered, HtlcFeeMode::Legacy)
+        })
+        .collect();
+
+    let fee = commit_tx_fee(feerate_per_kw, untrimmed_htlcs.len(), false);
+    let (local_amount, remote_amount) =
+        balances_after_funder_fee(local_amount, remote_amount, local_is_funder, fee)?;
+
+    check_output_amount("to_local", local_amount)?;
+    check_output_amount("to_remote", remote_amount)?;
+    check_compressed("remote pubkey", &remote_pubkey)?;
 
     let to_local_script =
-        to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay);
+        to_local(revocation_pubkey, to_local_delayed_pubkey, to_self_delay)?;
 
     let to_remote_script = p2wpkh_output_script(remote_pubkey);
 
-    let htlc_output = build_output(htlc_amount, htlc_offerer_script.to_p2wsh());
-
     let local_output = build_output(local_amount, to_local_script.to_p2wsh());
 
     let remote_output = build_output(remote_amount, to_remote_script);
 
+    let mut outputs = vec![(local_output, None), (remote_output, None)];
+
+    for htlc in untrimmed_htlcs {
+        let htlc_amount = htlc.amount_msat.to_amount_floor();
+        check_output_amount("HTLC", htlc_amount)?;
+
+        let htlc_script = if htlc.offered {
+            build_htlc_offerer_witness_script(
+                revocation_pubkey,
+                remote_htlc_pubkey,
+                local_htlc_pubkey,
+                &htlc.payment_hash,
+            )
+        } else {
+            build_htlc_receiver_witness_script(
+                revocation_pubkey,
+                remote_htlc_pubkey,
+                local_htlc_pubkey,
+                &htlc.payment_hash,
+                htlc.cltv_expiry,
+            )
+        };
+
+        outputs.push((build_output(htlc_amount, htlc_script.to_p2wsh()), Some(htlc.cltv_expiry)));
+    }
+
     let version = Version::TWO;
-    let locktime = LockTime::ZERO;
 
-    let tx = build_transaction(version,
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+    let mut funding_txin = funding_txin;
+    funding_txin.sequence = sequence;
+
+    Ok(build_transaction(version,
                                locktime,
                                vec![funding_txin],
-                            vec![htlc_output, remote_output, local_output]);
-
-    tx
+                               strip_cltv_expiries(sort_outputs_bolt3(outputs))))
 }
 
 //
@@ -246,28 +307,34 @@ pub fn build_htlc_commitment_transaction(
 
 pub fn build_htlc_timeout_transaction(
     htlc_txin: TxIn,
-    revocation_pubkey: &PublicKey,
-    to_local_delayed_pubkey: &PublicKey,
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
     to_self_delay: i64,
     cltv_expiry: u32,
-    htlc_amount: u64,
-) -> Transaction {
+    htlc_amount: Amount,
+    htlc_fee_mode: HtlcFeeMode,
+) -> Result<Transaction, ExerciseError> {
+    check_output_amount("HTLC", htlc_amount)?;
+
     let htlc_timeout_script = to_local(
         revocation_pubkey,
         to_local_delayed_pubkey,
         to_self_delay,
-    );
+    )?;
 
     let htlc_output = build_output(htlc_amount, htlc_timeout_script.to_p2wsh());
 
     let version = Version::TWO;
     let locktime = LockTime::from_consensus(cltv_expiry);
 
-    let tx = build_transaction(
+    let mut htlc_txin = htlc_txin;
+    if htlc_fee_mode.anchors() {
+        htlc_txin.sequence = Sequence(1);
+    }
+
+    Ok(build_transaction(
                 version,
                 locktime,
                 vec![htlc_txin],
-                vec![htlc_output]);
-
-    tx
+                vec![htlc_output]))
 }
\ No newline at end of file
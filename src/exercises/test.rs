@@ -1,308 +1,4181 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::exercises::exercises::{
-    build_commitment_transaction, build_funding_transaction, build_htlc_commitment_transaction,
-    build_htlc_timeout_transaction, build_refund_transaction, generate_revocation_privkey,
-    generate_revocation_pubkey, to_local, two_of_two_multisig_witness_script,
+    build_commitment_transaction, build_commitment_transaction_with_anchors,
+    build_funding_psbt, build_funding_transaction, build_funding_transaction_with_script,
+    build_all_htlc_transactions, build_htlc_commitment_transaction, build_htlc_success_transaction,
+    build_htlc_timeout_transaction, build_htlc_timeout_transaction_with_fee, build_refund_transaction,
+    build_closing_transaction, build_trimmed_htlc_commitment_transaction, obscure_commitment_transaction,
+    build_funding_witness, can_derive, compute_commitment_sighash, sign_commitment_transaction,
+    build_penalty_transaction, build_delayed_sweep_transaction, finalize_htlc_timeout_transaction,
+    htlc_success_witness, offered_htlc_preimage_witness, channel_id_for_funding_transaction,
+    cosign_funding_psbt, encode_open_channel_tlvs, build_open_channel, handle_channel_reestablish, verify_channel_announcement, apply_channel_update, find_route, htlc_payment_hash, create_invoice, build_keysend_onion, settle_keysend, next_htlc_state, handle_revoke_and_ack, create_justice_blob, open_justice_blob, scan_block_for_channel_spends, classify_funding_spend, verify_merkle_branch, funding_confirmations, bump_zero_fee_htlc_transaction, build_anchor_cpfp_transaction, bump_funding_fee, closing_signed_fee, check_counterparty_shutdown, encrypt_channel_backup, restore_channel_backup, derive_commitment_keys, generate_per_commitment_secret, received_htlc_witness_script, generate_revocation_privkey,
+    generate_revocation_pubkey, to_local, two_of_two_multisig_witness_script, validate_htlc_outputs, validate_htlc_tx_set,
 };
 use crate::internal;
 use bitcoin::hash_types::Txid;
+use bitcoin::hashes::Hash;
 use bitcoin::script::ScriptBuf;
 use bitcoin::secp256k1::{self, Secp256k1};
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Scalar, SecretKey};
 use bitcoin::PublicKey;
-use bitcoin::{OutPoint, Sequence, Transaction, TxIn, Witness};
+use bitcoin::{Amount, OutPoint, Sequence, Transaction, TxIn, Witness};
 use internal::key_utils::{
-    add_privkeys, add_pubkeys, hash_pubkeys, privkey_multipication_tweak, pubkey_from_private_key,
+    add_privkeys, add_pubkeys, derive_private_key, derive_public_key, hash_pubkeys, privkey_multipication_tweak, pubkey_from_private_key,
     pubkey_from_secret, pubkey_multipication_tweak, secp256k1_private_key,
     secp256k1pubkey_from_private_key,
 };
-use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
-use internal::tx_utils::{build_output, build_transaction};
+use internal::script_utils::{
+    build_anchor_htlc_offerer_witness_script, build_anchor_output_script, build_htlc_offerer_witness_script,
+    build_anchor_to_remote_script, build_htlc_receiver_witness_script, p2wpkh_output_script,
+};
+use internal::sign_utils::{
+    counterparty_htlc_sighash_type, generate_p2wsh_signature, sign_p2wpkh_input, verify_p2wsh_signature,
+};
+use internal::channel_utils::channel_id_from_funding;
+use internal::psbt_utils::{finalize_funding_psbt, sign_funding_psbt};
+use internal::channel::build_commitment_full;
+use internal::errors::{ChannelError, ExerciseError};
+use internal::chain_source::ChainUtxo;
+use internal::fee_utils::{
+    commit_tx_fee, fee_for_weight, HtlcFeeMode, ANCHOR_CPFP_TX_WEIGHT, ANCHOR_OUTPUT_VALUE, DELAYED_SWEEP_TX_WEIGHT,
+    HTLC_TIMEOUT_ANCHOR_TX_WEIGHT, P2WPKH_INPUT_WEIGHT, P2WPKH_OUTPUT_WEIGHT,
+};
+use crate::exercises::onion::{peel_onion, PeeledOnion};
+use internal::onion_utils::HopPayload;
+use internal::hints::ExerciseCheck;
+use internal::htlc_utils::{max_feerate_for_htlc, Htlc, HtlcOutput};
+use internal::payments::{PaymentHash, PaymentHash160, PaymentPreimage, PaymentSecret};
+use internal::keys::{ChannelKeys, ChannelPublicKeys, DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey};
+use internal::shachain::{generate_from_seed, ShachainStore, MAX_SHACHAIN_INDEX};
+use internal::tx_utils::{
+    build_output, build_transaction, commitment_number_obscure_factor, dummy_funding_txin, get_funding_input,
+    recover_commitment_number, tx_to_hex, DUMMY_FUNDING_TXID,
+};
+use internal::units::MilliSat;
+use internal::errors::WireError;
+use internal::tlv::{find_record, read_tlv_stream};
+use internal::wire::{
+    encode_features, has_feature, Message as WireMessage, ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED, STATIC_REMOTE_KEY_REQUIRED,
+};
+use internal::channel::{
+    ChannelParams, CommitmentEvent, CounterpartyCommitments, FundingSpend, HtlcState, ReestablishAction,
+};
+use internal::wire::{
+    ChannelAnnouncement, ChannelReestablish, ChannelUpdate, RevokeAndAck, ShortChannelId, CHANNEL_FLAGS_DIRECTION,
+    CHANNEL_FLAGS_DISABLE, MESSAGE_FLAGS_MUST_BE_ONE,
+};
+use internal::graph::{ChannelPolicy, GraphChannel, NetworkGraph};
+use internal::routing::{Route, RouteHop};
+use internal::invoice::{decode_invoice, Invoice, DEFAULT_EXPIRY, DESCRIPTION_TAG};
+use internal::errors::InvoiceError;
+use internal::errors::{ClosingError, ShutdownScriptError};
+use internal::wire::{ClosingSigned, FeeRange, Shutdown};
+use internal::backup::{backup_key, StaticChannelBackup, STATIC_CHANNEL_BACKUP_VERSION};
+use internal::errors::BackupError;
+use internal::errors::GossipError;
+use internal::errors::WatchtowerError;
+use internal::chain_utils::{merkle_branch, MerkleBranch};
+use bitcoin::Block;
+use internal::watchtower::{breach_key, decrypt_blob, encrypt_blob, BreachHint, JusticeBlob};
+use bitcoin::consensus::encode::serialize;
+use bitcoin::TxOut;
+use internal::keys::ChannelKeyManager;
+use bitcoin::constants::ChainHash;
+use bitcoin::Network;
+use bitcoin::sighash::EcdsaSighashType;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
 
 /// hash160 of the empty string
-const HASH160_DUMMY: [u8; 20] = [
+const HASH160_DUMMY: PaymentHash160 = PaymentHash160([
     0xb4, 0x72, 0xa2, 0x66, 0xd0, 0xbd, 0x89, 0xc1, 0x37, 0x06, 0xa4, 0x13, 0x2c, 0xcf, 0xb1, 0x6f,
     0x7c, 0x3b, 0x9f, 0xcb,
-];
+]);
+
+/// The single 405,000 sat HTLC the Exercise 8 commitments offer.
+fn dummy_offered_htlc() -> Htlc {
+    Htlc {
+        offered: true,
+        amount_msat: MilliSat::from_msat(405_000_000),
+        payment_hash: HASH160_DUMMY,
+        cltv_expiry: 300,
+    }
+}
+
+/// Basepoints for the Exercise 12 and 14 channels, whose commitment keys are
+/// given directly: only the funding key and payment basepoint matter.
+fn dummy_channel_pubkeys(funding_byte: u8, payment_byte: u8) -> ChannelPublicKeys {
+    let payment_basepoint = pubkey_from_private_key(&[payment_byte; 32]).inner;
+    ChannelPublicKeys {
+        funding_pubkey: pubkey_from_private_key(&[funding_byte; 32]).inner,
+        payment_basepoint,
+        delayed_payment_basepoint: payment_basepoint,
+        htlc_basepoint: payment_basepoint,
+        revocation_basepoint: payment_basepoint,
+    }
+}
+
+/// Print the hex of a transaction built by an exercise, to paste into
+/// `bitcoin-cli decoderawtransaction` or mempool.space.
+fn print_transaction(tx: &Transaction) {
+    println!("their transaction: {}", tx_to_hex(tx));
+}
+
+/// What a learner stuck on an exercise is shown, with `HINT_LEVEL` set,
+/// when its test fails.
+const EXERCISE_1: ExerciseCheck = ExerciseCheck {
+    exercise: 1,
+    concept: "A 2-of-2 multisig script needs both keys to sign: it pushes the number of signatures required, both \
+              keys, the number of keys, then checks the signatures.",
+    api: "bitcoin::script::Builder has push_int, push_key and push_opcode, and opcodes::OP_CHECKMULTISIG.",
+    solution: "Builder::new().push_int(2).push_key(pubkey1).push_key(pubkey2).push_int(2)\
+               .push_opcode(OP_CHECKMULTISIG).into_script()",
+};
+
+const EXERCISE_2: ExerciseCheck = ExerciseCheck {
+    exercise: 2,
+    concept: "The funding transaction pays the channel amount to a P2WSH output locked by the 2-of-2 multisig \
+              script of Exercise 1.",
+    api: "two_of_two_multisig_witness_script, ScriptBuf::new_p2wsh with the script's wscript_hash, then build_output \
+          and build_transaction.",
+    solution: "build_transaction(Version::TWO, LockTime::ZERO, txins, vec![build_output(amount, \
+               ScriptBuf::new_p2wsh(&witness_script.wscript_hash()))])",
+};
+
+const EXERCISE_3: ExerciseCheck = ExerciseCheck {
+    exercise: 3,
+    concept: "The refund transaction spends the funding output and pays each side its balance, so nobody's funds \
+              are stuck if the other side disappears.",
+    api: "p2wpkh_output_script for each side's output, build_output, and sort_outputs_bolt3 so both sides build \
+          the same transaction.",
+    solution: "Build Alice's and Bob's P2WPKH outputs, sort them with sort_outputs_bolt3(vec![(alice_output, None), \
+               (bob_output, None)]), drop the expiries with strip_cltv_expiries, and build_transaction with the funding txin.",
+};
+
+const EXERCISE_4: ExerciseCheck = ExerciseCheck {
+    exercise: 4,
+    concept: "The revocation pubkey combines the countersignatory's basepoint and our per-commitment point, so \
+              neither side alone knows its private key until we reveal the per-commitment secret.",
+    api: "hash_pubkeys for the two tweaks, pubkey_multipication_tweak to multiply each point by its tweak, and \
+          add_pubkeys.",
+    solution: "basepoint * SHA256(basepoint || point) + point * SHA256(point || basepoint)",
+};
+
+const EXERCISE_5: ExerciseCheck = ExerciseCheck {
+    exercise: 5,
+    concept: "The revocation private key is the same sum as Exercise 4, with secrets in place of the points.",
+    api: "pubkey_from_secret for both points, hash_pubkeys for the tweaks, privkey_multipication_tweak and \
+          add_privkeys.",
+    solution: "revocation_base_secret * SHA256(basepoint || point) + per_commitment_secret * \
+               SHA256(point || basepoint)",
+};
+
+const EXERCISE_6: ExerciseCheck = ExerciseCheck {
+    exercise: 6,
+    concept: "to_local pays the revocation key right away, or our delayed key after to_self_delay blocks, so the \
+              counterparty can take it if we broadcast a revoked commitment.",
+    api: "check_to_self_delay and check_compressed for the arguments, then Builder with OP_IF, OP_ELSE, OP_CSV, \
+          OP_DROP, OP_ENDIF and OP_CHECKSIG.",
+    solution: "OP_IF <revocation key> OP_ELSE <to_self_delay> OP_CSV OP_DROP <delayed key> OP_ENDIF OP_CHECKSIG",
+};
+
+const EXERCISE_7: ExerciseCheck = ExerciseCheck {
+    exercise: 7,
+    concept: "The commitment transaction spends the funding output into to_local, our balance behind the script of \
+              Exercise 6, and to_remote, theirs to a P2WPKH. The funder pays the fee, and the commitment number is \
+              hidden in the locktime and the funding input's sequence.",
+    api: "commit_tx_fee and balances_after_funder_fee for the fee, check_output_amount and check_compressed for the \
+          arguments, to_local, p2wpkh_output_script, obscured_commitment_transaction_numbers and sort_outputs_bolt3.",
+    solution: "Take commit_tx_fee(feerate_per_kw, 0, false) from the funder with balances_after_funder_fee, set the \
+               locktime and sequence from obscured_commitment_transaction_numbers(obscure_factor, commitment_number), \
+               and build_transaction with the sorted to_local P2WSH and to_remote P2WPKH outputs.",
+};
+
+const EXERCISE_8: ExerciseCheck = ExerciseCheck {
+    exercise: 8,
+    concept: "Every HTLC that isn't trimmed gets its own P2WSH output: the offerer script for the HTLCs we offered, \
+              the receiver script with its cltv_expiry for the ones we received. Each of them adds to the fee.",
+    api: "is_htlc_trimmed with HtlcFeeMode::Legacy, commit_tx_fee with the number of untrimmed HTLCs, \
+          build_htlc_offerer_witness_script, build_htlc_receiver_witness_script, and sort_outputs_bolt3 with each \
+          HTLC's Some(cltv_expiry).",
+    solution: "Exercise 7 with commit_tx_fee(feerate_per_kw, untrimmed_htlcs.len(), false), and \
+               (build_output(htlc_amount, htlc_script.to_p2wsh()), Some(htlc.cltv_expiry)) pushed for each untrimmed \
+               HTLC before sorting.",
+};
+
+const EXERCISE_9: ExerciseCheck = ExerciseCheck {
+    exercise: 9,
+    concept: "The HTLC-timeout transaction spends an HTLC we offered once cltv_expiry has passed, into an output with \
+              the same revocation and delay as to_local.",
+    api: "to_local for the output script, LockTime::from_consensus, and htlc_fee_mode.anchors() for the input's \
+          sequence.",
+    solution: "build_transaction(Version::TWO, LockTime::from_consensus(cltv_expiry), vec![htlc_txin], \
+               vec![build_output(htlc_amount, to_local_script.to_p2wsh())]), with the sequence set to 1 with anchors.",
+};
+
+const EXERCISE_10: ExerciseCheck = ExerciseCheck {
+    exercise: 10,
+    concept: "With anchors, each side whose balance is above dust also gets an anchor output, which it can spend to \
+              bump the commitment's fee. The funder pays the fee and both anchors.",
+    api: "commit_tx_fee(feerate_per_kw, 0, true), ANCHOR_OUTPUT_VALUE, build_anchor_output_script with each funding \
+          pubkey, build_anchor_to_remote_script, balances_after_funder_fee and sort_outputs_bolt3.",
+    solution: "Take commit_tx_fee(feerate_per_kw, 0, true) + ANCHOR_OUTPUT_VALUE * 2 from the funder, then for each \
+               side with at least dust_limit push its output and its anchor, sort them and build_transaction.",
+};
+
+const EXERCISE_11: ExerciseCheck = ExerciseCheck {
+    exercise: 11,
+    concept: "Every HTLC transaction must spend an output of this commitment, and no two of them the same one.",
+    api: "commitment_tx.compute_txid(), the previous_output of each HTLC transaction's input, and the \
+          ChannelError::HtlcTx variants.",
+    solution: "For each HTLC transaction in order, check it has an input, that it spends the commitment's txid, that \
+               its vout is below the number of outputs, and that no earlier one spent the same vout.",
+};
+
+const EXERCISE_12: ExerciseCheck = ExerciseCheck {
+    exercise: 12,
+    concept: "Each expected HTLC must match its own output by script and amount, and every other output must be one \
+              the channel could have built: to_local, to_remote or an anchor.",
+    api: "htlc_witness_script(keys, &htlc, params.anchors()), HtlcOutput::amount_sat, to_local, p2wpkh_output_script \
+          or build_anchor_to_remote_script, build_anchor_output_script, and ChannelError::MissingHtlcOutput and \
+          UnexpectedOutput.",
+    solution: "Mark the outputs as they match: for each expected HTLC find an unmarked output with its P2WSH script \
+               and amount, then reject any unmarked output whose script isn't one of the others.",
+};
+
+const EXERCISE_13: ExerciseCheck = ExerciseCheck {
+    exercise: 13,
+    concept: "Without anchors the HTLC-timeout transaction pays its fee out of the HTLC, at the feerate agreed when \
+              the HTLC was added, and it can't be bumped later. An output below dust won't relay.",
+    api: "htlc_timeout_fee(feerate_per_kw, false), ChannelError::DustOutput, and build_htlc_timeout_transaction with \
+          HtlcFeeMode::Legacy.",
+    solution: "Subtract htlc_timeout_fee(feerate_per_kw, false) from htlc_amount, down to zero at most; return \
+               DustOutput if that is below dust_limit, else Exercise 9 with what is left.",
+};
+
+const EXERCISE_14: ExerciseCheck = ExerciseCheck {
+    exercise: 14,
+    concept: "Every HTLC output of the commitment needs its second-level transaction: HTLC-timeout for the HTLCs we \
+              offered, HTLC-success for the ones we received, each paying its fee out of the HTLC.",
+    api: "HtlcOutput::output_index, which is None for a trimmed HTLC, htlc_witness_script, \
+          params.htlc_fee_mode.htlc_timeout_fee and htlc_success_fee, and Exercises 9 and 16.",
+    solution: "For each HTLC with an output_index, spend OutPoint { txid: commitment txid, vout } with Sequence::ZERO, \
+               subtract its fee or return InsufficientFunds, and return the timeout or success transaction with the \
+               HTLC's witness script.",
+};
+
+const EXERCISE_15: ExerciseCheck = ExerciseCheck {
+    exercise: 15,
+    concept: "An HTLC we received pays the revocation key, or both HTLC keys with the preimage, or the remote HTLC \
+              key alone once cltv_expiry has passed.",
+    api: "Builder with revocation_pubkey.0.pubkey_hash(), OP_DUP, OP_HASH160, OP_SIZE, OP_SWAP, OP_CHECKMULTISIG and \
+          OP_CLTV.",
+    solution: "OP_DUP OP_HASH160 <revocation pubkey hash> OP_EQUAL OP_IF OP_CHECKSIG OP_ELSE <remote htlc key> OP_SWAP \
+               OP_SIZE 32 OP_EQUAL OP_IF OP_HASH160 <payment hash> OP_EQUALVERIFY 2 OP_SWAP <local htlc key> 2 \
+               OP_CHECKMULTISIG OP_ELSE OP_DROP <cltv_expiry> OP_CLTV OP_DROP OP_CHECKSIG OP_ENDIF OP_ENDIF",
+};
+
+const EXERCISE_16: ExerciseCheck = ExerciseCheck {
+    exercise: 16,
+    concept: "The HTLC-success transaction claims an HTLC we received with its preimage, which can be used right \
+              away, into an output with the same revocation and delay as to_local.",
+    api: "check_output_amount, to_local for the output script, and htlc_fee_mode.anchors() for the input's sequence.",
+    solution: "Exercise 9 with LockTime::ZERO: only the timeout path waits for a block height.",
+};
+
+const EXERCISE_17: ExerciseCheck = ExerciseCheck {
+    exercise: 17,
+    concept: "A mutual close pays each side to its shutdown script, with no delay or revocation. The funder pays the \
+              fee for the signed transaction, and outputs below dust are left out.",
+    api: "fee_for_weight with the unsigned weight plus FUNDING_WITNESS_WEIGHT, balances_after_funder_fee, \
+          sort_outputs_bolt3, and Sequence::MAX on the funding input.",
+    solution: "Build it once with both outputs to weigh it, take fee_for_weight(feerate_per_kw, weight + \
+               FUNDING_WITNESS_WEIGHT) from the funder, drop the outputs below dust_limit, and build_transaction with \
+               the rest sorted.",
+};
+
+const EXERCISE_18: ExerciseCheck = ExerciseCheck {
+    exercise: 18,
+    concept: "The commitment number is XORed with the lower 48 bits of SHA256(opener payment basepoint || accepter \
+              payment basepoint), so only the peers can read it, then split between the locktime and the sequence.",
+    api: "commitment_number_obscure_factor and obscured_commitment_transaction_numbers.",
+    solution: "Set tx.lock_time and tx.input[0].sequence from obscured_commitment_transaction_numbers(\
+               commitment_number_obscure_factor(opener, accepter), commitment_number).",
+};
+
+const EXERCISE_19: ExerciseCheck = ExerciseCheck {
+    exercise: 19,
+    concept: "An HTLC whose second-level transaction would leave less than dust is trimmed: it gets no output, and \
+              its value goes to the fee. A to_local or to_remote below dust is left out too.",
+    api: "is_htlc_trimmed with HtlcFeeMode::Legacy, commit_tx_fee with the number of untrimmed HTLCs, \
+          balances_after_funder_fee and sort_outputs_bolt3.",
+    solution: "The HTLC outputs of Exercise 8 for the untrimmed HTLCs only, to_local and to_remote only if they are \
+               at least dust_limit, sorted and built with LockTime::ZERO.",
+};
+
+const EXERCISE_20: ExerciseCheck = ExerciseCheck {
+    exercise: 20,
+    concept: "Every per-commitment secret comes from one seed: for each set bit of the 48-bit index, from the \
+              highest, flip that bit of the secret and hash it.",
+    api: "bitcoin::hashes::sha256::Hash::hash and to_byte_array.",
+    solution: "For bitpos from 47 down to 0, if the index has that bit set: secret[bitpos / 8] ^= 1 << (bitpos % 8), \
+               then secret = SHA256(secret).",
+};
+
+const EXERCISE_21: ExerciseCheck = ExerciseCheck {
+    exercise: 21,
+    concept: "The secret of an index has flipped all of the index's set bits already, so it can only go on to flip \
+              the bits below its lowest set bit.",
+    api: "u64::trailing_zeros, at most 48, and a mask of the bits above them.",
+    solution: "let bits = from_index.trailing_zeros().min(48); to_index & !((1 << bits) - 1) == from_index",
+};
+
+const EXERCISE_22: ExerciseCheck = ExerciseCheck {
+    exercise: 22,
+    concept: "Each commitment's keys are the basepoints tweaked by its per-commitment point, basepoint + \
+              SHA256(per_commitment_point || basepoint) * G, so no two commitments share a key.",
+    api: "derive_public_key(basepoint, per_commitment_point).",
+    solution: "(derive_public_key(payment_basepoint, point), derive_public_key(delayed_payment_basepoint, point), \
+               derive_public_key(htlc_basepoint, point))",
+};
+
+const EXERCISE_23: ExerciseCheck = ExerciseCheck {
+    exercise: 23,
+    concept: "Spending the 2-of-2 takes an empty element for the extra item OP_CHECKMULTISIG pops, both signatures in \
+              the order of their keys in the script, then the script.",
+    api: "serialize_der, EcdsaSighashType::All for the byte after each signature, sort_funding_keys and Witness::push.",
+    solution: "[] [signature of the lesser key] [signature of the other key] [funding_script], each signature DER \
+               encoded with the SIGHASH_ALL byte appended.",
+};
+
+const EXERCISE_24: ExerciseCheck = ExerciseCheck {
+    exercise: 24,
+    concept: "Both sides sign the BIP 143 digest of the commitment's funding input, which commits to the funding \
+              script and the channel value.",
+    api: "sort_funding_keys, two_of_two_multisig_witness_script and commitment_sighash_to_sign.",
+    solution: "commitment_sighash_to_sign(commitment_tx, &two_of_two_multisig_witness_script(&first, &second), \
+               channel_value.to_sat()), with the funding keys sorted.",
+};
+
+const EXERCISE_25: ExerciseCheck = ExerciseCheck {
+    exercise: 25,
+    concept: "The commitment is signed with SIGHASH_ALL over its only input, the funding output, with the funding \
+              script and the channel value.",
+    api: "generate_p2wsh_signature.",
+    solution: "generate_p2wsh_signature(tx.clone(), 0, funding_witness_script, channel_value.to_sat(), \
+               EcdsaSighashType::All, *funding_secret_key)",
+};
+
+const EXERCISE_26: ExerciseCheck = ExerciseCheck {
+    exercise: 26,
+    concept: "A revoked to_local can be taken right away through the OP_IF branch, with a signature of the \
+              revocation key and a non-empty element to pick that branch.",
+    api: "penalty_tx_fee(1, 0, feerate), generate_p2wsh_signature and Witness::push.",
+    solution: "One input with Sequence::MAX, an output of to_local_amount less penalty_tx_fee or InsufficientFunds, \
+               then the witness [signature + SIGHASH_ALL byte] [0x01] [to_local_script].",
+};
+
+const EXERCISE_27: ExerciseCheck = ExerciseCheck {
+    exercise: 27,
+    concept: "Our own to_local can only be spent through the OP_ELSE branch after to_self_delay blocks, and OP_CSV \
+              checks that against the input's sequence.",
+    api: "to_self_delay_sequence, fee_for_weight with DELAYED_SWEEP_TX_WEIGHT, and generate_p2wsh_signature.",
+    solution: "Exercise 26 with the sequence to_self_delay_sequence(to_self_delay), a fee of fee_for_weight(feerate, \
+               DELAYED_SWEEP_TX_WEIGHT), the delayed payment key, and an empty element instead of 0x01.",
+};
+
+const EXERCISE_28: ExerciseCheck = ExerciseCheck {
+    exercise: 28,
+    concept: "The timeout path of an offered HTLC takes both HTLC signatures: theirs, which is SIGHASH_SINGLE | \
+              ANYONECANPAY with anchors, and ours with SIGHASH_ALL. An empty element instead of a preimage picks it.",
+    api: "generate_p2wsh_signature, counterparty_htlc_sighash_type(anchors), and Sequence(1) with anchors.",
+    solution: "Set the sequence before signing, 1 with anchors and 0 without, then the witness [] [remote signature + \
+               its sighash byte] [local signature + SIGHASH_ALL byte] [] [offered_htlc_script].",
+};
+
+const EXERCISE_29: ExerciseCheck = ExerciseCheck {
+    exercise: 29,
+    concept: "An HTLC is claimed with its preimage: from the offerer's commitment with one signature, or through the \
+              HTLC-success transaction from our own commitment with both HTLC signatures.",
+    api: "serialize_der, counterparty_htlc_sighash_type(anchors), and payment_preimage.0.",
+    solution: "Offered: [remote signature + SIGHASH_ALL byte] [preimage] [script]. Success: [] [remote signature + its \
+               sighash byte] [local signature + SIGHASH_ALL byte] [preimage] [script].",
+};
+
+const EXERCISE_30: ExerciseCheck = ExerciseCheck {
+    exercise: 30,
+    concept: "The channel_id is the funding txid with the index of the funding output XORed into its last two bytes.",
+    api: "funding_script.to_p2wsh() to find the funding output, and channel_id_from_funding.",
+    solution: "Find the output paying to funding_script.to_p2wsh(), or return None, then \
+               channel_id_from_funding(funding_tx.compute_txid(), index as u16).",
+};
+
+const EXERCISE_31: ExerciseCheck = ExerciseCheck {
+    exercise: 31,
+    concept: "A PSBT collects the signatures: Bob adds his to Alice's, then the input is finalized into its witness \
+              and the transaction extracted.",
+    api: "sign_funding_psbt, finalize_funding_psbt and Psbt::extract_tx.",
+    solution: "Ok(finalize_funding_psbt(sign_funding_psbt(alice_signed_psbt, bob_secret_key))?.extract_tx()?)",
+};
+
+const EXERCISE_32: ExerciseCheck = ExerciseCheck {
+    exercise: 32,
+    concept: "A TLV stream is type, length and value for each record, in increasing type order, and a record that \
+              isn't set is left out.",
+    api: "TlvRecord::new, UPFRONT_SHUTDOWN_SCRIPT_TLV, CHANNEL_TYPE_TLV and write_tlv_stream.",
+    solution: "Push TlvRecord::new(UPFRONT_SHUTDOWN_SCRIPT_TLV, script.to_bytes()) and \
+               TlvRecord::new(CHANNEL_TYPE_TLV, channel_type.to_vec()) for the ones given, then \
+               write_tlv_stream(&records).",
+};
+
+const EXERCISE_33: ExerciseCheck = ExerciseCheck {
+    exercise: 33,
+    concept: "open_channel proposes the channel: its funding amount, the limits we set, our basepoints and first \
+              per-commitment point, and the channel type.",
+    api: "ChainHash::using_genesis_block, params.local_keys, STATIC_REMOTE_KEY_REQUIRED and the anchor bit of \
+          params.htlc_fee_mode, encode_features and MAX_ACCEPTED_HTLCS.",
+    solution: "Fill OpenChannel from params, with channel_reserve_satoshis = max(funding_amount / 100, \
+               params.dust_limit), to_self_delay = counterparty_to_self_delay, and channel_type = \
+               Some(encode_features(&bits)).",
+};
+
+const EXERCISE_34: ExerciseCheck = ExerciseCheck {
+    exercise: 34,
+    concept: "channel_reestablish says what the peer last received. next_revocation_number and its secret tell \
+              whether it missed our revoke_and_ack or we fell behind; next_commitment_number, whether it missed our \
+              commitment_signed.",
+    api: "keys.per_commitment_secret(n - 1), the secret expected with next_revocation_number n, or all zeros for 0, \
+          and the ReestablishAction variants.",
+    solution: "A next_revocation_number above local_commitment_number is FallenBehind if its secret is right. \
+               Otherwise the secret must be right, one commitment behind retransmits revoke_and_ack, \
+               next_commitment_number == remote_commitment_number retransmits commitment_signed, and anything else \
+               is FailChannel.",
+};
+
+const EXERCISE_35: ExerciseCheck = ExerciseCheck {
+    exercise: 35,
+    concept: "A channel_announcement proves the channel exists: both nodes and both funding keys sign it, and the \
+              funding keys are the 2-of-2 of the funding output.",
+    api: "signing_hash with Message::from_digest, verify_ecdsa, sort_funding_keys and \
+          two_of_two_multisig_witness_script.",
+    solution: "Reject node ids out of order, verify the four signatures, and compare the P2WSH of the 2-of-2 of the \
+               sorted bitcoin keys with funding_output.script_pubkey.",
+};
+
+const EXERCISE_36: ExerciseCheck = ExerciseCheck {
+    exercise: 36,
+    concept: "A channel_update sets the policy of one direction of the channel, signed by the node on that side, and \
+              only a newer timestamp replaces it.",
+    api: "verify_channel_update, channel.policy_mut(update.direction()), ChannelPolicy and update.is_disabled().",
+    solution: "Check the short_channel_id and the signature, reject an htlc_maximum_msat above the capacity, return \
+               Ok(false) if the policy's last_update is at least the timestamp, else store the new policy and return \
+               Ok(true).",
+};
+
+const EXERCISE_37: ExerciseCheck = ExerciseCheck {
+    exercise: 37,
+    concept: "Dijkstra from the destination back to the source: each node must receive the amount plus the fees of \
+              the hops after it, so fees build up going backwards.",
+    api: "graph.incoming_channels, channel.policy(), can_carry and fee_for on the policy, and a BinaryHeap of \
+          Reverse((amount, cltv, node)).",
+    solution: "Pop the cheapest node, and for each channel into it that can carry the amount add its fee and \
+               cltv_expiry_delta, none for the source's own channel, skipping any over max_cltv. Keep the best amount \
+               and next hop of each node, then follow the next hops from the source.",
+};
+
+const EXERCISE_38: ExerciseCheck = ExerciseCheck {
+    exercise: 38,
+    concept: "The invoice has SHA256(preimage), but the HTLC scripts hash the preimage with OP_HASH160, which is \
+              RIPEMD160(SHA256(preimage)).",
+    api: "Ripemd160::hash and PaymentHash160.",
+    solution: "PaymentHash160(Ripemd160::hash(&invoice.payment_hash.0).to_byte_array())",
+};
+
+const EXERCISE_39: ExerciseCheck = ExerciseCheck {
+    exercise: 39,
+    concept: "A BOLT 11 invoice is bech32: a prefix with the network and amount, the timestamp, the tagged fields, \
+              and a recoverable signature of all of it.",
+    api: "invoice_hrp, timestamp_to_words, write_tagged_field, bytes_to_words, u64_to_words, features_to_words, \
+          signing_hash, sign_ecdsa_recoverable and encode_bech32.",
+    solution: "Tagged fields s, p, d, x only if expiry isn't DEFAULT_EXPIRY, and 9 with VAR_ONION_OPTIN_REQUIRED and \
+               PAYMENT_SECRET_REQUIRED, then the compact signature and recovery id as words and \
+               encode_bech32(&hrp, &data).",
+};
+
+const EXERCISE_40: ExerciseCheck = ExerciseCheck {
+    exercise: 40,
+    concept: "For a keysend the sender picks the preimage and puts it in the final hop's payload, and the recipient \
+              settles only if it hashes to the payment hash.",
+    api: "preimage.hash(), build_forwarding_payloads, TlvRecord with AMT_TO_FORWARD_TLV, OUTGOING_CLTV_VALUE_TLV and \
+          KEYSEND_PREIMAGE_TLV, encode_tu64 and construct_onion_packet.",
+    solution: "Append the final payload with the amount, final_cltv_expiry and the preimage, construct_onion_packet \
+               with the payment hash, and send route.total_amount() at final_cltv_expiry + \
+               route.total_cltv_expiry_delta(). settle_keysend checks the preimage's hash.",
+};
+
+const EXERCISE_41: ExerciseCheck = ExerciseCheck {
+    exercise: 41,
+    concept: "An HTLC update is final once it is in both commitments and the ones before are revoked. Ours goes into \
+              their commitment first, theirs into ours first.",
+    api: "HtlcState and CommitmentEvent, matched as a (state, event) pair.",
+    solution: "LocalAddProposed, SendCommitmentSigned => LocalAddSigned; LocalAddSigned, ReceiveRevokeAndAck => \
+               LocalAddAcked; LocalAddAcked, ReceiveCommitmentSigned => Committed; the mirror image for RemoteAdd, the \
+               same for the fulfills, and (state, _) => state.",
+};
+
+const EXERCISE_42: ExerciseCheck = ExerciseCheck {
+    exercise: 42,
+    concept: "revoke_and_ack reveals the secret of the counterparty's current commitment. If it isn't the secret of \
+              the point that commitment was built with, we couldn't punish a breach of it.",
+    api: "SecretKey::from_slice, pubkey_from_secret, revoked_secrets.insert at MAX_SHACHAIN_INDEX - \
+          commitment_number, and ChannelError::InvalidRevocationSecret.",
+    solution: "Check pubkey_from_secret(secret) == counterparty.per_commitment_point and store the secret, then \
+               increment commitment_number and move next_per_commitment_point into per_commitment_point, taking the \
+               new next point from the message.",
+};
+
+const EXERCISE_43: ExerciseCheck = ExerciseCheck {
+    exercise: 43,
+    concept: "The tower files a blob under a hint of the revoked commitment's txid, and can only decrypt it with a \
+              key from the whole txid, which it learns when the commitment is on-chain.",
+    api: "BreachHint::from_txid, breach_key, encrypt_blob and bitcoin::consensus::serialize.",
+    solution: "JusticeBlob { hint: BreachHint::from_txid(txid), encrypted_justice_tx: encrypt_blob(&breach_key(txid), \
+               &serialize(justice_tx)) }",
+};
+
+const EXERCISE_44: ExerciseCheck = ExerciseCheck {
+    exercise: 44,
+    concept: "Anyone can upload a blob under any hint, so after decrypting it the tower only trusts a transaction \
+              whose every input spends the breach.",
+    api: "breach_key, decrypt_blob, bitcoin::consensus::deserialize, and WatchtowerError::InvalidJusticeTransaction \
+          and NotABreachSpend.",
+    solution: "decrypt_blob(&breach_key(&breach_tx.compute_txid()), ...)?, deserialize it or return \
+               InvalidJusticeTransaction, and return NotABreachSpend if it has no inputs or any spends another txid.",
+};
+
+const EXERCISE_45: ExerciseCheck = ExerciseCheck {
+    exercise: 45,
+    concept: "Only one transaction can ever spend an outpoint, so the first transaction in the block with an input \
+              spending the funding outpoint is the one.",
+    api: "block.txdata and the previous_output of each input.",
+    solution: "block.txdata.iter().find(|tx| tx.input.iter().any(|input| input.previous_output == \
+               funding_outpoint)).cloned()",
+};
+
+const EXERCISE_46: ExerciseCheck = ExerciseCheck {
+    exercise: 46,
+    concept: "Commitments carry their obscured commitment number and closing transactions don't. A commitment below \
+              the counterparty's current number has been revoked.",
+    api: "recover_commitment_number(spend_tx, obscure_factor) and counterparty.commitment_number.",
+    solution: "None is CooperativeClose, a number below counterparty.commitment_number is RevokedCommitment, and any \
+               other is LatestCommitment.",
+};
+
+const EXERCISE_47: ExerciseCheck = ExerciseCheck {
+    exercise: 47,
+    concept: "Hash from the txid up to the root, one sibling per level. Each bit of the index says which side the \
+              sibling goes on at that level.",
+    api: "TxMerkleNode::from_byte_array and merkle_parent(left, right).",
+    solution: "Start from the txid; at each level take merkle_parent(sibling, node) if that bit of the index is set, \
+               else merkle_parent(node, sibling), and compare the result with merkle_root.",
+};
+
+const EXERCISE_48: ExerciseCheck = ExerciseCheck {
+    exercise: 48,
+    concept: "A light client counts confirmations from the header and a merkle branch, and the block the funding \
+              transaction is in counts as one.",
+    api: "merkle_branch, verify_merkle_branch from Exercise 47, block.header.merkle_root and confirmations.",
+    solution: "let branch = merkle_branch(block, funding_txid)?; return None unless verify_merkle_branch(&branch, \
+               &block.header.merkle_root), else Some(confirmations(block_height, best_height)).",
+};
+
+const EXERCISE_49: ExerciseCheck = ExerciseCheck {
+    exercise: 49,
+    concept: "SIGHASH_SINGLE | ANYONECANPAY only covers the HTLC input and its output, so we can add an input and a \
+              change output after them to pay the fee.",
+    api: "fee_for_weight with htlc_tx_weight + P2WPKH_INPUT_WEIGHT + P2WPKH_OUTPUT_WEIGHT, and build_output.",
+    solution: "The change is fee_input_value less the fee, or InsufficientFunds; push the fee input with \
+               Sequence::MAX and the change output after the HTLC's.",
+};
+
+const EXERCISE_50: ExerciseCheck = ExerciseCheck {
+    exercise: 50,
+    concept: "Child pays for parent: the child pays the fee for the weight of both, less what the commitment pays, \
+              but never less than the fee for its own weight.",
+    api: "fee_for_weight, ANCHOR_CPFP_TX_WEIGHT, ANCHOR_OUTPUT_VALUE and Sequence::ENABLE_RBF_NO_LOCKTIME.",
+    solution: "child_fee = max(fee_for_weight(target, commitment_weight + ANCHOR_CPFP_TX_WEIGHT) - commitment_fee, \
+               fee_for_weight(target, ANCHOR_CPFP_TX_WEIGHT)); spend the anchor, then the wallet UTXO, into one \
+               change output of both values less child_fee.",
+};
+
+const EXERCISE_51: ExerciseCheck = ExerciseCheck {
+    exercise: 51,
+    concept: "A replacement must pay at least the original's fee plus the relay fee for its own weight. It keeps the \
+              funding output and takes the extra fee from a new wallet input, with change.",
+    api: "fee_for_weight with the original weight plus P2WPKH_INPUT_WEIGHT and P2WPKH_OUTPUT_WEIGHT, \
+          min_replacement_fee, ExerciseError::InsufficientReplacementFee and Sequence::ENABLE_RBF_NO_LOCKTIME.",
+    solution: "Return InsufficientReplacementFee if the new fee is below min_replacement_fee(original_fee, weight); \
+               spend the original inputs then additional_input into the original outputs plus change of \
+               original_fee + additional_input.value - new_fee, with the same version and locktime.",
+};
+
+const EXERCISE_52: ExerciseCheck = ExerciseCheck {
+    exercise: 52,
+    concept: "The fee ranges must overlap. The funder proposes, the fundee agrees or counters with the fee in the \
+              overlap closest to the funder's, and the funder agrees to that.",
+    api: "FeeRange::contains, the overlap of the two ranges, Amount::clamp, and the ClosingError variants.",
+    solution: "Reject a missing range, no overlap, or a fee outside their range. The funder accepts a fee in the \
+               overlap; the fundee requires its own last fee back if it sent one, else answers \
+               fee.clamp(overlap.min_fee_satoshis, overlap.max_fee_satoshis).",
+};
+
+const EXERCISE_53: ExerciseCheck = ExerciseCheck {
+    exercise: 53,
+    concept: "A peer that set an upfront_shutdown_script can only close to that script, and any shutdown script must \
+              be one of the standard kinds.",
+    api: "validate_shutdown_script and ShutdownScriptError::UpfrontScriptMismatch.",
+    solution: "If the upfront script is set and not empty, return UpfrontScriptMismatch unless it equals \
+               shutdown.scriptpubkey, then validate_shutdown_script(&shutdown.scriptpubkey, anysegwit).",
+};
+
+const EXERCISE_54: ExerciseCheck = ExerciseCheck {
+    exercise: 54,
+    concept: "A static channel backup keeps what survives losing the channel state: which channel, with whom, and \
+              how to derive our keys again.",
+    api: "STATIC_CHANNEL_BACKUP_VERSION, to_be_bytes, serialize on the keys, backup_key and encrypt_blob.",
+    solution: "Version, channel_id, funding txid, vout, peer node id, channel_key_index, is_funder as a byte, \
+               to_self_delay and the five counterparty keys, then encrypt_blob(&backup_key(node_seed), &plaintext).",
+};
+
+const EXERCISE_55: ExerciseCheck = ExerciseCheck {
+    exercise: 55,
+    concept: "With every commitment lost we can't close the channel ourselves, so we reestablish it as if it had just \
+              opened, and the peer closes it with its latest commitment.",
+    api: "decrypt_blob with backup_key, Reader for the fields, ChannelKeyManager::from_seed and the BackupError \
+          variants.",
+    solution: "Read the fields in the order of Exercise 54, rejecting an unknown version or trailing bytes, derive \
+               the keys with from_seed(node_seed, channel_key_index), and a ChannelReestablish with zero commitment \
+               numbers, an all-zero secret and keys.per_commitment_point(0).",
+};
 
 #[test]
 fn test_01_two_of_two_multisig_witness_script() {
-    let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
-    let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
-    let result = two_of_two_multisig_witness_script(&pubkey1, &pubkey2);
+    EXERCISE_1.run(|| {
+        let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
+        let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+        let result = two_of_two_multisig_witness_script(&pubkey1, &pubkey2);
+
+        EXERCISE_1.expect_eq(
+            "the witness script",
+            hex::encode(result.as_bytes()).as_str(),
+            "5221031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f21024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d076652ae",
+        );
 
-    let their_solution = format!("{}", result.script_hash());
-    println!("their solution: {}", their_solution);
-    let acceptable_solutions = ["d8fecda80c30e89a9e7f0964ee79ce055288bc1c".to_string()];
+        let their_solution = format!("{}", result.script_hash());
+        println!("their solution: {}", their_solution);
+        let acceptable_solutions = ["d8fecda80c30e89a9e7f0964ee79ce055288bc1c".to_string()];
 
-    assert!(acceptable_solutions.contains(&their_solution))
+        assert!(acceptable_solutions.contains(&their_solution))
+    })
 }
 
 #[test]
 fn test_02_build_funding_transaction() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
+    EXERCISE_2.run(|| {
+        let txin = vec![dummy_funding_txin()];
+
+        let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
+        let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+
+        let amount = Amount::from_sat(100000);
+
+        let transaction = build_funding_transaction(txin, &pubkey1, &pubkey2, amount);
+        EXERCISE_2.expect_eq("the number of outputs", transaction.output.len(), 1);
+        EXERCISE_2.expect_eq("the funding output amount", transaction.output[0].value, amount);
+        EXERCISE_2.expect_eq(
+            "the funding output script",
+            hex::encode(transaction.output[0].script_pubkey.as_bytes()).as_str(),
+            "0020657760ca015175e42ff5b4470563b23adcf0d2973a0506a176a5569690d64437",
+        );
+
+        let their_solution = transaction.compute_txid().to_string();
 
-    let txin = vec![TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    }];
+        println!("their solution: {}", their_solution);
+        print_transaction(&transaction);
 
-    let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
-    let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+        let acceptable_solutions =
+            ["8f28cec85c8d986559c7bf5760d57d57446e26f27ac3ed623d591e4579b7bc9c".to_string()];
 
-    let amount: u64 = 100000;
+        assert!(acceptable_solutions.contains(&their_solution));
+    })
+}
+
+#[test]
+fn test_02_build_funding_transaction_with_script() {
+    EXERCISE_2.run(|| {
+        let txin = vec![dummy_funding_txin()];
+
+        let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
+        let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+
+        let mut sorted = [pubkey1, pubkey2];
+        sorted.sort_by_key(|key| key.inner.serialize());
+        let expected_script = two_of_two_multisig_witness_script(&sorted[0], &sorted[1]);
+
+        // the argument order must not change the result
+        for (first, second) in [(&pubkey1, &pubkey2), (&pubkey2, &pubkey1)] {
+            let (transaction, witness_script) =
+                build_funding_transaction_with_script(txin.clone(), first, second, Amount::from_sat(100000));
+
+            assert_eq!(witness_script, expected_script);
+            print_transaction(&transaction);
+            assert_eq!(transaction.output[0].script_pubkey, witness_script.to_p2wsh());
+        }
+    })
+}
 
-    let transaction = build_funding_transaction(txin, &pubkey1, &pubkey2, amount);
+#[test]
+fn test_02_build_funding_psbt() {
+    EXERCISE_2.run(|| {
+        let txin = vec![dummy_funding_txin()];
+
+        let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
+        let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+
+        let amount = Amount::from_sat(100000);
+        let utxo = build_output(Amount::from_sat(150_000), p2wpkh_output_script(pubkey1));
+
+        let key_source = |fingerprint: &str, path: &str| {
+            (fingerprint.parse::<bitcoin::bip32::Fingerprint>().unwrap(), path.parse::<bitcoin::bip32::DerivationPath>().unwrap())
+        };
+        let alice_key_source = key_source("d34db33f", "m/1017'/0'/0'");
+        let bob_key_source = key_source("c0ffee00", "m/1017'/0'/1'");
 
-    let their_solution = transaction.compute_txid().to_string();
+        let psbt = build_funding_psbt(
+            txin.clone(),
+            vec![utxo.clone()],
+            &pubkey1,
+            alice_key_source.clone(),
+            &pubkey2,
+            bob_key_source.clone(),
+            amount,
+        )
+        .unwrap();
 
-    println!("their solution: {}", their_solution);
+        let (expected_tx, witness_script) =
+            build_funding_transaction_with_script(txin.clone(), &pubkey1, &pubkey2, amount);
+        assert_eq!(psbt.unsigned_tx, expected_tx);
 
-    let acceptable_solutions =
-        ["8f28cec85c8d986559c7bf5760d57d57446e26f27ac3ed623d591e4579b7bc9c".to_string()];
+        assert_eq!(psbt.inputs[0].witness_utxo, Some(utxo.clone()));
 
-    assert!(acceptable_solutions.contains(&their_solution));
+        let funding_output = &psbt.outputs[0];
+        assert_eq!(funding_output.witness_script, Some(witness_script));
+        assert_eq!(funding_output.bip32_derivation.get(&pubkey1.inner), Some(&alice_key_source));
+        assert_eq!(funding_output.bip32_derivation.get(&pubkey2.inner), Some(&bob_key_source));
+
+        // survives the round trip to and from a wallet
+        let decoded = bitcoin::psbt::Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(decoded, psbt);
+        assert_eq!(decoded.fee().unwrap().to_sat(), 50_000);
+
+        // an already signed input can't go in a PSBT
+        let mut signed_txin = txin.clone();
+        signed_txin[0].witness.push([0x01]);
+        assert_eq!(
+            build_funding_psbt(
+                signed_txin,
+                vec![utxo.clone()],
+                &pubkey1,
+                alice_key_source.clone(),
+                &pubkey2,
+                bob_key_source.clone(),
+                amount,
+            ),
+            Err(ChannelError::SignedInput { input: 0 })
+        );
+        assert_eq!(
+            build_funding_psbt(txin, vec![], &pubkey1, alice_key_source, &pubkey2, bob_key_source, amount),
+            Err(ChannelError::InputValuesMismatch { inputs: 1, values: 0 })
+        );
+    })
 }
 
 #[test]
 fn test_03_build_refund_transaction() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
+    EXERCISE_3.run(|| {
+        let txin = dummy_funding_txin();
 
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
+        let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
+        let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
 
-    let pubkey1 = pubkey_from_private_key(&[0x01; 32]);
-    let pubkey2 = pubkey_from_private_key(&[0x02; 32]);
+        let alice_amount = Amount::from_sat(4_998_500);
+        let bob_amount = Amount::from_sat(500);
 
-    let alice_amount: u64 = 4_998_500;
-    let bob_amount: u64 = 500;
+        let transaction = build_refund_transaction(txin, pubkey1, pubkey2, alice_amount, bob_amount);
 
-    let transaction = build_refund_transaction(txin, pubkey1, pubkey2, alice_amount, bob_amount);
+        // BOLT 3 orders outputs by amount first
+        let outputs: Vec<_> = transaction
+            .output
+            .iter()
+            .map(|output| (output.value, hex::encode(output.script_pubkey.as_bytes())))
+            .collect();
+        EXERCISE_3.expect_eq(
+            "the outputs",
+            outputs,
+            vec![
+                (bob_amount, "0014ebc0ee0b2ab9e8277a600c251475e22a3241a1c1".to_string()),
+                (alice_amount, "001479b000887626b294a914501a4cd226b58b235983".to_string()),
+            ],
+        );
 
-    let their_solution = transaction.compute_txid().to_string();
+        let their_solution = transaction.compute_txid().to_string();
 
-    println!("their solution: {}", their_solution);
+        println!("their solution: {}", their_solution);
+        print_transaction(&transaction);
 
-    let acceptable_solutions =
-        ["e66414f0d4dca7df235b9e2cf4855f0cf64b9d4164412fd03c8670621bd398ff".to_string()];
+        let acceptable_solutions =
+            ["e66414f0d4dca7df235b9e2cf4855f0cf64b9d4164412fd03c8670621bd398ff".to_string()];
 
-    assert!(acceptable_solutions.contains(&their_solution));
+        assert!(acceptable_solutions.contains(&their_solution));
+    })
 }
 
 #[test]
 fn test_04_generate_revocation_pubkey() {
-    let countersignatory_basepoint = secp256k1pubkey_from_private_key(&[0x01; 32]);
-    let per_commitment_point = secp256k1pubkey_from_private_key(&[0x02; 32]);
+    EXERCISE_4.run(|| {
+        let countersignatory_basepoint = secp256k1pubkey_from_private_key(&[0x01; 32]);
+        let per_commitment_point = secp256k1pubkey_from_private_key(&[0x02; 32]);
 
-    let revocation_pubkey =
-        generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point);
+        let revocation_pubkey =
+            generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point);
 
-    let key_str = "0c8415f91d9df28009f87442dfc897d3c22a534032d4795467206db994a3c539";
-    let key_bytes = hex::decode(key_str).expect("Invalid hex string");
-    let key = SecretKey::from_slice(&key_bytes).expect("Invalid 32-byte private key");
-    let secp = Secp256k1::new();
-    let derived_pubkey = secp256k1PublicKey::from_secret_key(&secp, &key);
+        let key_str = "0c8415f91d9df28009f87442dfc897d3c22a534032d4795467206db994a3c539";
+        let key_bytes = hex::decode(key_str).expect("Invalid hex string");
+        let key = SecretKey::from_slice(&key_bytes).expect("Invalid 32-byte private key");
+        let secp = Secp256k1::new();
+        let derived_pubkey = secp256k1PublicKey::from_secret_key(&secp, &key);
 
-    let actual = revocation_pubkey.to_string();
+        let actual = revocation_pubkey.to_string();
 
-    let expected = "02f406b2b2ef2372c08d003e5062e4b34929b86f107a36bfbd406f5644419e9ff6";
+        let expected = "02f406b2b2ef2372c08d003e5062e4b34929b86f107a36bfbd406f5644419e9ff6";
 
-    assert_eq!(
-        actual, expected,
-        "Revocation pubkey doesn't match expected value"
-    );
+        assert_eq!(
+            actual, expected,
+            "Revocation pubkey doesn't match expected value"
+        );
 
-    assert_eq!(
-        derived_pubkey, revocation_pubkey,
-        "Secret does not work for pubkey"
-    );
+        assert_eq!(
+            derived_pubkey, revocation_pubkey,
+            "Secret does not work for pubkey"
+        );
+    })
 }
 
 #[test]
 fn test_05_generate_revocation_privkey() {
-    let countersignatory_secret = secp256k1_private_key(&[0x01; 32]);
-    let per_commitment_secret = secp256k1_private_key(&[0x02; 32]);
+    EXERCISE_5.run(|| {
+        let countersignatory_secret = secp256k1_private_key(&[0x01; 32]);
+        let per_commitment_secret = secp256k1_private_key(&[0x02; 32]);
 
-    let revocation_privkey =
-        generate_revocation_privkey(countersignatory_secret, per_commitment_secret);
+        let revocation_privkey =
+            generate_revocation_privkey(countersignatory_secret, per_commitment_secret);
 
-    let actual = revocation_privkey.display_secret().to_string();
+        let actual = revocation_privkey.display_secret().to_string();
 
-    let expected = "0c8415f91d9df28009f87442dfc897d3c22a534032d4795467206db994a3c539";
+        let expected = "0c8415f91d9df28009f87442dfc897d3c22a534032d4795467206db994a3c539";
 
-    assert_eq!(
-        actual, expected,
-        "Revocation pubkey doesn't match expected value"
-    );
+        assert_eq!(
+            actual, expected,
+            "Revocation pubkey doesn't match expected value"
+        );
+    })
 }
 
 #[test]
 fn test_06_to_local() {
-    let revocation_key = pubkey_from_private_key(&[0x01; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
-    let to_self_delay: i64 = 144;
-    let result = to_local(&revocation_key, &to_local_delayed_pubkey, to_self_delay);
+    EXERCISE_6.run(|| {
+        let revocation_key = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let to_self_delay: i64 = 144;
+        let result = to_local(&revocation_key, &to_local_delayed_pubkey, to_self_delay).unwrap();
+
+        EXERCISE_6.expect_eq(
+            "the witness script",
+            hex::encode(result.as_bytes()).as_str(),
+            "6321031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f67029000b27521024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d076668ac",
+        );
 
-    let their_solution = format!("{}", result.script_hash());
-    println!("their solution: {}", their_solution);
-    let acceptable_solutions = ["6c61fd62fe96fc6aa5485a820ce87d662329f4ab".to_string()];
+        let their_solution = format!("{}", result.script_hash());
+        println!("their solution: {}", their_solution);
+        let acceptable_solutions = ["6c61fd62fe96fc6aa5485a820ce87d662329f4ab".to_string()];
 
-    assert!(acceptable_solutions.contains(&their_solution))
+        assert!(acceptable_solutions.contains(&their_solution))
+    })
+}
+
+#[test]
+fn test_06_to_local_rejects_invalid_arguments() {
+    EXERCISE_6.run(|| {
+        let revocation_key = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        assert_eq!(
+            to_local(&revocation_key, &to_local_delayed_pubkey, 0),
+            Err(ExerciseError::InvalidToSelfDelay(0))
+        );
+        assert_eq!(
+            to_local(&revocation_key, &to_local_delayed_pubkey, 70_000),
+            Err(ExerciseError::InvalidToSelfDelay(70_000))
+        );
+
+        let uncompressed = DelayedPaymentPubkey(PublicKey::new_uncompressed(to_local_delayed_pubkey.0.inner));
+        assert_eq!(
+            to_local(&revocation_key, &uncompressed, 144),
+            Err(ExerciseError::UncompressedKey("to_local delayed pubkey"))
+        );
+    })
 }
 
 #[test]
 fn test_07_build_commitment_transaction() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
-
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
-
-    let revocation_pubkey = pubkey_from_private_key(&[0x01; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
-    let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
-
-    let to_self_delay: i64 = 144;
-    let alice_amount: u64 = 3_998_500;
-    let bob_amount: u64 = 1_000_500;
-
-    let transaction = build_commitment_transaction(
-        txin,
-        &revocation_pubkey,
-        &to_local_delayed_pubkey,
-        remote_pubkey,
-        to_self_delay,
-        alice_amount,
-        bob_amount,
-    );
+    EXERCISE_7.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+        let to_self_delay: i64 = 144;
+        let alice_amount = Amount::from_sat(3_999_500);
+        let bob_amount = Amount::from_sat(1_000_500);
+
+        let transaction = build_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            to_self_delay,
+            alice_amount,
+            bob_amount,
+            true,
+            1_000,
+            239765233721135,
+            1,
+        ).unwrap();
+
+        // whatever the outputs don't claim is the fee, paid out of Alice's balance
+        let output_total: Amount = transaction.output.iter().map(|output| output.value).sum();
+        assert_eq!((alice_amount + bob_amount) - output_total, commit_tx_fee(1_000, 0, false));
+        assert!(transaction.output.iter().any(|output| output.value == bob_amount));
+
+        let their_solution = transaction.compute_txid().to_string();
+
+        println!("their solution: {}", their_solution);
+        print_transaction(&transaction);
+
+        let acceptable_solutions =
+            ["043f8a96976cd39805d098ea4b75277291ff8ef633729a282af6ea6a849d6b8f".to_string()];
+
+        assert!(acceptable_solutions.contains(&their_solution));
+    })
+}
 
-    let their_solution = transaction.compute_txid().to_string();
+#[test]
+fn test_07_build_commitment_transaction_obscures_the_whole_commitment_number() {
+    EXERCISE_7.run(|| {
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+        let obscure_factor = 0x2bb0_3852_1914;
+        let txin = TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+
+        // both halves are set, so a number wider than 24 bits survives the round trip
+        for commitment_number in [0, 1, 0xab_cdef, 0x12_3456_789a, (1 << 48) - 1] {
+            let transaction = build_commitment_transaction(
+                txin.clone(),
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                remote_pubkey,
+                144,
+                Amount::from_sat(3_998_500),
+                Amount::from_sat(1_000_500),
+                true,
+                253,
+                obscure_factor,
+                commitment_number,
+            )
+            .unwrap();
+
+            assert_eq!(transaction.lock_time.to_consensus_u32() >> 24, 0x20);
+            assert_eq!(transaction.input[0].sequence.0 >> 24, 0x80);
+            assert_eq!(recover_commitment_number(&transaction, obscure_factor), Some(commitment_number));
+        }
+    })
+}
+
+#[test]
+fn test_07_build_commitment_transaction_rejects_invalid_arguments() {
+    EXERCISE_7.run(|| {
+        let txin = TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+        let build = |remote_pubkey, to_self_delay, local_amount, remote_amount| {
+            build_commitment_transaction(
+                txin.clone(),
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                remote_pubkey,
+                to_self_delay,
+                local_amount,
+                remote_amount,
+                true,
+                0,
+                0,
+                0,
+            )
+        };
 
-    println!("their solution: {}", their_solution);
+        assert_eq!(
+            build(remote_pubkey, 144, Amount::ZERO, Amount::from_sat(1_000_500)),
+            Err(ExerciseError::ZeroValueOutput("to_local"))
+        );
+        assert_eq!(
+            build(remote_pubkey, 144, Amount::from_sat(3_998_500), Amount::from_sat(21_000_001 * 100_000_000)),
+            Err(ExerciseError::AmountOutOfRange { name: "to_remote", amount: 21_000_001 * 100_000_000 })
+        );
+        assert_eq!(
+            build(PublicKey::new_uncompressed(remote_pubkey.inner), 144, Amount::from_sat(3_998_500), Amount::from_sat(1_000_500)),
+            Err(ExerciseError::UncompressedKey("remote pubkey"))
+        );
+        assert_eq!(
+            build(remote_pubkey, 0, Amount::from_sat(3_998_500), Amount::from_sat(1_000_500)),
+            Err(ExerciseError::InvalidToSelfDelay(0))
+        );
 
-    let acceptable_solutions =
-        ["83aef4d9008ac14c71967a7944f2f0b8bcb30ef58ae2946e0c550de6bb908cba".to_string()];
+        // the funder's balance has to cover the 183 sat fee at 253 sat/kw
+        assert_eq!(
+            build_commitment_transaction(
+                txin.clone(),
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                remote_pubkey,
+                144,
+                Amount::from_sat(100),
+                Amount::from_sat(1_000_500),
+                true,
+                253,
+                0,
+                0,
+            ),
+            Err(ExerciseError::InsufficientFunds { needed: 183, available: 100 })
+        );
 
-    assert!(acceptable_solutions.contains(&their_solution));
+        let err: ChannelError = build(remote_pubkey, 0, Amount::from_sat(3_998_500), Amount::from_sat(1_000_500)).unwrap_err().into();
+        assert_eq!(err, ChannelError::InvalidArguments(ExerciseError::InvalidToSelfDelay(0)));
+        assert_eq!(err.to_string(), "to_self_delay of 0 blocks is outside the BIP 68 range of 1 to 65535");
+    })
 }
 
 #[test]
 fn test_08_build_htlc_commitment_transaction() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
-
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
-
-    let revocation_pubkey = pubkey_from_private_key(&[0x01; 32]);
-    let remote_htlc_pubkey = pubkey_from_private_key(&[0x02; 32]);
-    let local_htlc_pubkey = pubkey_from_private_key(&[0x03; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
-    let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
-
-    let to_self_delay: i64 = 144;
-    let payment_hash160 = HASH160_DUMMY;
-    let htlc_amount: u64 = 405_000;
-    let local_amount: u64 = 3_593_500;
-    let remote_amount: u64 = 1_000_500;
-
-    let transaction = build_htlc_commitment_transaction(
-        txin,
-        &revocation_pubkey,
-        &remote_htlc_pubkey,
-        &local_htlc_pubkey,
-        &to_local_delayed_pubkey,
-        remote_pubkey,
-        to_self_delay,
-        &payment_hash160,
-        htlc_amount,
-        local_amount,
-        remote_amount,
-    );
+    EXERCISE_8.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let to_self_delay: i64 = 144;
+        let local_amount = Amount::from_sat(3_594_500);
+        let remote_amount = Amount::from_sat(1_000_500);
+
+        let transaction = build_htlc_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            to_self_delay,
+            &[dummy_offered_htlc()],
+            local_amount,
+            remote_amount,
+            true,
+            Amount::from_sat(546),
+            1_000,
+            239765233721135,
+            1,
+        ).unwrap();
+
+        // the HTLC output adds its weight to the fee the funder pays
+        let output_total: Amount = transaction.output.iter().map(|output| output.value).sum();
+        let input_total = local_amount + remote_amount + dummy_offered_htlc().amount_msat.to_amount_floor();
+        assert_eq!(input_total - output_total, commit_tx_fee(1_000, 1, false));
+        // the commitment number is obscured just like in Exercise 7
+        assert_eq!(recover_commitment_number(&transaction, 239765233721135), Some(1));
+
+        let their_solution = transaction.compute_txid().to_string();
+
+        println!("their solution: {}", their_solution);
+        print_transaction(&transaction);
 
-    let their_solution = transaction.compute_txid().to_string();
+        let acceptable_solutions =
+            ["6b2650c8b418466953f565f7e3a4cf652c65dd263e92b2fc3dab99504939d6a5".to_string()];
 
-    println!("their solution: {}", their_solution);
+        assert!(acceptable_solutions.contains(&their_solution));
+    })
+}
+
+#[test]
+fn test_08_build_htlc_commitment_transaction_multiple_htlcs() {
+    EXERCISE_8.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x05; 32]);
 
-    let acceptable_solutions =
-        ["cecd7f4c7bebfddbdd563fd96bab55a1d5d72b672518104aaa68e9bbf99a4acb".to_string()];
+        let received = Htlc {
+            offered: false,
+            amount_msat: MilliSat::from_msat(200_000_000),
+            payment_hash: PaymentHash160([0x42; 20]),
+            cltv_expiry: 310,
+        };
+        // identical but for the expiry, so only cltv_expiry can order the two
+        let received_later = Htlc { cltv_expiry: 320, ..received };
+        let htlcs = [received_later, dummy_offered_htlc(), received];
 
-    assert!(acceptable_solutions.contains(&their_solution));
+        let transaction = build_htlc_commitment_transaction(
+            txin.clone(),
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            &htlcs,
+            Amount::from_sat(3_193_500),
+            Amount::from_sat(1_000_500),
+            true,
+            Amount::from_sat(546),
+            253,
+            0,
+            0,
+        )
+        .unwrap();
+        print_transaction(&transaction);
+
+        let received_script = |htlc: &Htlc| {
+            build_htlc_receiver_witness_script(
+                &revocation_pubkey,
+                &remote_htlc_pubkey,
+                &local_htlc_pubkey,
+                &htlc.payment_hash,
+                htlc.cltv_expiry,
+            )
+            .to_p2wsh()
+        };
+        let offered_script =
+            build_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &HASH160_DUMMY)
+                .to_p2wsh();
+
+        let values: Vec<u64> = transaction.output.iter().map(|output| output.value.to_sat()).collect();
+        // 253 sat/kw on a commitment with 3 HTLCs is a 313 sat fee, paid by the local funder
+        assert_eq!(values, vec![200_000, 200_000, 405_000, 1_000_500, 3_193_500 - 313]);
+        assert_eq!(transaction.output[0].script_pubkey, received_script(&received));
+        assert_eq!(transaction.output[1].script_pubkey, received_script(&received_later));
+        assert_eq!(transaction.output[2].script_pubkey, offered_script);
+
+        // below the dust limit an HTLC is trimmed: no output and no fee weight
+        let dust_htlc = Htlc { amount_msat: MilliSat::from_msat(500_000), ..received };
+        let transaction = build_htlc_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            &[dust_htlc, received],
+            Amount::from_sat(3_193_500),
+            Amount::from_sat(1_000_500),
+            true,
+            Amount::from_sat(546),
+            253,
+            0,
+            0,
+        )
+        .unwrap();
+        let values: Vec<u64> = transaction.output.iter().map(|output| output.value.to_sat()).collect();
+        assert_eq!(values, vec![200_000, 1_000_500, 3_193_500 - commit_tx_fee(253, 1, false).to_sat()]);
+    })
 }
 
 #[test]
 fn test_09_build_htlc_timeout_transaction() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
-
-    let txin = TxIn {
-        previous_output: outpoint,
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::MAX,
-        witness: Witness::new(),
-    };
-
-    let revocation_pubkey = pubkey_from_private_key(&[0x01; 32]);
-    let broadcaster_delayed_payment_key = pubkey_from_private_key(&[0x02; 32]);
-    let local_htlc_pubkey = pubkey_from_private_key(&[0x03; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
-    let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
-
-    let contest_delay: i64 = 144;
-    let cltv_expiry: u32 = 300;
-    let htlc_amount: u64 = 404_000;
-
-    let transaction = build_htlc_timeout_transaction(
-        txin,
-        &revocation_pubkey,
-        &broadcaster_delayed_payment_key,
-        contest_delay,
-        cltv_expiry,
-        htlc_amount,
-    );
+    EXERCISE_9.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let broadcaster_delayed_payment_key = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = pubkey_from_private_key(&[0x03; 32]);
+        let to_local_delayed_pubkey = pubkey_from_private_key(&[0x02; 32]);
+        let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let contest_delay: i64 = 144;
+        let cltv_expiry: u32 = 300;
+        let htlc_amount = Amount::from_sat(404_000);
+
+        let transaction = build_htlc_timeout_transaction(
+            txin,
+            &revocation_pubkey,
+            &broadcaster_delayed_payment_key,
+            contest_delay,
+            cltv_expiry,
+            htlc_amount,
+            HtlcFeeMode::Legacy,
+        ).unwrap();
+
+        let their_solution = transaction.compute_txid().to_string();
+
+        println!("their solution: {}", their_solution);
+        print_transaction(&transaction);
+
+        let acceptable_solutions =
+            ["5899cdd0e418b516afa2552611aad22f974ac17e9892f5828e2e55f18b2d7899".to_string()];
+
+        assert!(acceptable_solutions.contains(&their_solution));
+    })
+}
+
+#[test]
+fn test_09_build_htlc_timeout_transaction_anchors() {
+    EXERCISE_9.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_htlc_privkey = secp256k1_private_key(&[0x02; 32]);
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let broadcaster_delayed_payment_key = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let htlc_amount = Amount::from_sat(404_000);
+
+        let transaction = build_htlc_timeout_transaction(
+            txin,
+            &revocation_pubkey,
+            &broadcaster_delayed_payment_key,
+            144,
+            300,
+            htlc_amount,
+            HtlcFeeMode::Anchors,
+        ).unwrap();
+        print_transaction(&transaction);
+
+        assert_eq!(transaction.input[0].sequence, Sequence(1));
+
+        let sighash_type = counterparty_htlc_sighash_type(true);
+        assert_eq!(sighash_type, EcdsaSighashType::SinglePlusAnyoneCanPay);
+        assert_eq!(counterparty_htlc_sighash_type(false), EcdsaSighashType::All);
+
+        // the counterparty signature must stay valid once fee inputs are attached
+        let htlc_script = build_anchor_htlc_offerer_witness_script(
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &HASH160_DUMMY,
+        );
+        let signature = generate_p2wsh_signature(
+            transaction.clone(),
+            0,
+            &htlc_script,
+            405_000,
+            sighash_type,
+            remote_htlc_privkey,
+        );
+
+        let mut bumped = transaction.clone();
+        bumped.input.push(get_funding_input(DUMMY_FUNDING_TXID.to_string(), 2));
+        bumped.output.push(build_output(Amount::from_sat(10_000), p2wpkh_output_script(local_htlc_pubkey.0)));
+
+        let bumped_signature = generate_p2wsh_signature(
+            bumped,
+            0,
+            &htlc_script,
+            405_000,
+            sighash_type,
+            remote_htlc_privkey,
+        );
+
+        assert_eq!(signature, bumped_signature);
+    })
+}
+
+#[test]
+fn test_10_build_commitment_transaction_with_anchors() {
+    EXERCISE_10.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+        let local_funding_pubkey = FundingPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let remote_funding_pubkey = FundingPubkey(pubkey_from_private_key(&[0x05; 32]));
+
+        let transaction = build_commitment_transaction_with_anchors(
+            txin,
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            &local_funding_pubkey,
+            &remote_funding_pubkey,
+            144,
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(2_000_000),
+            true,
+            Amount::from_sat(546),
+            253,
+        ).unwrap();
+        print_transaction(&transaction);
+
+        let value_of = |script: &ScriptBuf| {
+            transaction
+                .output
+                .iter()
+                .find(|output| output.script_pubkey == *script)
+                .map(|output| output.value.to_sat())
+        };
+
+        let local_anchor = build_anchor_output_script(&local_funding_pubkey).to_p2wsh();
+        let remote_anchor = build_anchor_output_script(&remote_funding_pubkey).to_p2wsh();
+        assert_eq!(value_of(&local_anchor), Some(330));
+        assert_eq!(value_of(&remote_anchor), Some(330));
+
+        // to_remote is delayed by `1 OP_CSV` instead of being a plain P2WPKH
+        let to_remote = build_anchor_to_remote_script(&remote_pubkey).to_p2wsh();
+        assert_eq!(value_of(&to_remote), Some(2_000_000));
+        assert_eq!(value_of(&p2wpkh_output_script(remote_pubkey)), None);
+
+        // the funder pays the 1124 weight commitment fee and both anchors
+        let to_local = to_local(&revocation_pubkey, &to_local_delayed_pubkey, 144).unwrap().to_p2wsh();
+        assert_eq!(value_of(&to_local), Some(3_000_000 - 1124 * 253 / 1000 - 2 * 330));
+
+        let values: Vec<u64> = transaction.output.iter().map(|output| output.value.to_sat()).collect();
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(values, sorted);
+    })
+}
+
+#[test]
+fn test_10_build_commitment_transaction_with_anchors_prunes_anchor() {
+    EXERCISE_10.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+        let local_funding_pubkey = FundingPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let remote_funding_pubkey = FundingPubkey(pubkey_from_private_key(&[0x05; 32]));
+
+        let local_anchor = build_anchor_output_script(&local_funding_pubkey).to_p2wsh();
+        let remote_anchor = build_anchor_output_script(&remote_funding_pubkey).to_p2wsh();
+
+        let try_build = |feerate_per_kw: u64| {
+            build_commitment_transaction_with_anchors(
+                txin.clone(),
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                remote_pubkey,
+                &local_funding_pubkey,
+                &remote_funding_pubkey,
+                144,
+                Amount::from_sat(4_990_000),
+                Amount::from_sat(10_000),
+                false,
+                Amount::from_sat(546),
+                feerate_per_kw,
+            )
+        };
+        let build = |feerate_per_kw: u64| try_build(feerate_per_kw).unwrap();
+
+        let has_output = |tx: &Transaction, script: &ScriptBuf| {
+            tx.output.iter().any(|output| output.script_pubkey == *script)
+        };
+
+        // at a low feerate the remote funder can pay the fee and keeps its output
+        let transaction = build(253);
+        assert_eq!(transaction.output.len(), 4);
+        assert!(has_output(&transaction, &local_anchor));
+        assert!(has_output(&transaction, &remote_anchor));
+
+        // at 8_000 sat/kw the remote funder is left with less than the dust limit
+        let transaction = build(8_000);
+        assert_eq!(transaction.output.len(), 2);
+        assert!(has_output(&transaction, &local_anchor));
+        assert!(!has_output(&transaction, &remote_anchor));
+        assert!(transaction.output.iter().any(|output| output.value.to_sat() == 4_990_000));
+
+        // at 10_000 sat/kw the fee and anchors exceed the remote balance
+        assert_eq!(
+            try_build(10_000),
+            Err(ChannelError::InvalidArguments(ExerciseError::InsufficientFunds {
+                needed: 1124 * 10_000 / 1000 + 2 * 330,
+                available: 10_000,
+            }))
+        );
+    })
+}
+
+#[test]
+fn test_11_validate_htlc_tx_set() {
+    EXERCISE_11.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let commitment_tx = build_htlc_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            &[dummy_offered_htlc()],
+            Amount::from_sat(3_593_500),
+            Amount::from_sat(1_000_500),
+            true,
+            Amount::from_sat(546),
+            253,
+            0,
+            0,
+        ).unwrap();
+        print_transaction(&commitment_tx);
+
+        let htlc_tx_spending = |vout: u32| {
+            let htlc_txin = TxIn {
+                previous_output: OutPoint::new(commitment_tx.compute_txid(), vout),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            };
+
+            build_htlc_timeout_transaction(
+                htlc_txin,
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                144,
+                300 + vout,
+                Amount::from_sat(404_000),
+                HtlcFeeMode::Legacy,
+            ).unwrap()
+        };
+
+        assert_eq!(validate_htlc_tx_set(&commitment_tx, &[htlc_tx_spending(0), htlc_tx_spending(1)]), Ok(()));
+
+        assert_eq!(
+            validate_htlc_tx_set(&commitment_tx, &[htlc_tx_spending(0), htlc_tx_spending(0)]),
+            Err(ChannelError::HtlcTxDuplicateVout { index: 1, vout: 0 })
+        );
+        assert_eq!(
+            validate_htlc_tx_set(&commitment_tx, &[htlc_tx_spending(3)]),
+            Err(ChannelError::HtlcTxUnknownVout { index: 0, vout: 3 })
+        );
+    })
+}
+
+#[test]
+fn test_12_validate_htlc_outputs() {
+    EXERCISE_12.run(|| {
+        let txin = dummy_funding_txin();
+
+        let keys = ChannelKeys {
+            revocation_pubkey: RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+            remote_htlc_pubkey: HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+            local_htlc_pubkey: HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+            to_local_delayed_pubkey: DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32])),
+            remote_pubkey: pubkey_from_private_key(&[0x05; 32]),
+        };
+
+        let commitment_tx = build_htlc_commitment_transaction(
+            txin,
+            &keys.revocation_pubkey,
+            &keys.remote_htlc_pubkey,
+            &keys.local_htlc_pubkey,
+            &keys.to_local_delayed_pubkey,
+            keys.remote_pubkey,
+            144,
+            &[dummy_offered_htlc()],
+            Amount::from_sat(3_593_500),
+            Amount::from_sat(1_000_500),
+            true,
+            Amount::from_sat(546),
+            253,
+            0,
+            0,
+        ).unwrap();
+        print_transaction(&commitment_tx);
+
+        let agreed_htlc = HtlcOutput {
+            htlc: Htlc {
+                offered: true,
+                amount_msat: MilliSat::from_msat(405_000_000),
+                payment_hash: HASH160_DUMMY,
+                cltv_expiry: 300,
+            },
+            output_index: None,
+        };
+
+        let other_htlc = HtlcOutput {
+            htlc: Htlc {
+                offered: false,
+                amount_msat: MilliSat::from_msat(200_000_000),
+                payment_hash: PaymentHash160([0x42; 20]),
+                cltv_expiry: 310,
+            },
+            output_index: None,
+        };
 
-    let their_solution = transaction.compute_txid().to_string();
+        let mut params = ChannelParams {
+            funding_outpoint: dummy_funding_txin().previous_output,
+            to_self_delay: 144,
+            dust_limit: Amount::from_sat(546),
+            feerate_per_kw: 253,
+            htlc_fee_mode: HtlcFeeMode::Legacy,
+            is_funder: false,
+            local_keys: dummy_channel_pubkeys(0x06, 0x09),
+            remote_keys: dummy_channel_pubkeys(0x07, 0x08),
+        };
 
-    println!("their solution: {}", their_solution);
+        assert_eq!(validate_htlc_outputs(&commitment_tx, &[agreed_htlc], &keys, &params), Ok(()));
+
+        // an agreed HTLC the commitment is missing
+        assert_eq!(
+            validate_htlc_outputs(&commitment_tx, &[agreed_htlc, other_htlc], &keys, &params),
+            Err(ChannelError::MissingHtlcOutput { offered: false, amount_sat: 200_000 })
+        );
+
+        // an HTLC output nobody agreed on
+        assert!(matches!(
+            validate_htlc_outputs(&commitment_tx, &[], &keys, &params),
+            Err(ChannelError::UnexpectedOutput { .. })
+        ));
+
+        // with anchors, the anchor outputs are not HTLCs, and to_local may be
+        // trimmed away
+        params.htlc_fee_mode = HtlcFeeMode::Anchors;
+        let (anchor_tx, anchor_htlcs) = build_commitment_full(
+            &keys,
+            &params,
+            MilliSat::from_msat(0),
+            MilliSat::from_msat(4_595_000_000),
+            &[agreed_htlc.htlc, other_htlc.htlc],
+            1,
+        )
+        .unwrap();
+        assert_eq!(anchor_tx.output.len(), 5);
+        assert_eq!(validate_htlc_outputs(&anchor_tx, &anchor_htlcs, &keys, &params), Ok(()));
+        assert!(validate_htlc_outputs(&anchor_tx, &anchor_htlcs[..1], &keys, &params).is_err());
+
+        // the pre-anchor HTLC scripts are not the ones an anchor channel agreed on
+        params.htlc_fee_mode = HtlcFeeMode::Legacy;
+        assert!(validate_htlc_outputs(&anchor_tx, &anchor_htlcs, &keys, &params).is_err());
+    })
+}
+
+#[test]
+fn test_13_build_htlc_timeout_transaction_with_fee() {
+    EXERCISE_13.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let htlc_amount = Amount::from_sat(1_000);
+        let dust_limit = Amount::from_sat(546);
+
+        // the largest feerate with 663 * feerate / 1000 <= 1_000 - 546
+        let max_feerate = max_feerate_for_htlc(htlc_amount, dust_limit, true, false).unwrap();
+        assert_eq!(max_feerate, 686);
+
+        let build = |feerate_per_kw: u64| {
+            build_htlc_timeout_transaction_with_fee(
+                txin.clone(),
+                &revocation_pubkey,
+                &to_local_delayed_pubkey,
+                144,
+                300,
+                htlc_amount,
+                dust_limit,
+                feerate_per_kw,
+            )
+        };
+
+        let transaction = build(max_feerate).unwrap();
+        print_transaction(&transaction);
+        assert_eq!(transaction.output[0].value, htlc_amount - Amount::from_sat(663 * max_feerate / 1000));
+        assert!(transaction.output[0].value >= dust_limit);
+
+        assert!(matches!(
+            build(max_feerate + 1),
+            Err(ChannelError::DustOutput { index: 0, .. })
+        ));
+    })
+}
+
+#[test]
+fn test_14_build_all_htlc_transactions() {
+    EXERCISE_14.run(|| {
+        let outpoint = dummy_funding_txin().previous_output;
+
+        let keys = ChannelKeys {
+            revocation_pubkey: RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+            remote_htlc_pubkey: HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+            local_htlc_pubkey: HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+            to_local_delayed_pubkey: DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32])),
+            remote_pubkey: pubkey_from_private_key(&[0x05; 32]),
+        };
+        let params = ChannelParams {
+            funding_outpoint: outpoint,
+            to_self_delay: 144,
+            dust_limit: Amount::from_sat(546),
+            feerate_per_kw: 253,
+            htlc_fee_mode: HtlcFeeMode::Legacy,
+            is_funder: true,
+            local_keys: dummy_channel_pubkeys(0x06, 0x08),
+            remote_keys: dummy_channel_pubkeys(0x07, 0x09),
+        };
+
+        let offered = Htlc {
+            offered: true,
+            amount_msat: MilliSat::from_msat(405_000_000),
+            payment_hash: HASH160_DUMMY,
+            cltv_expiry: 300,
+        };
+        let received = Htlc {
+            offered: false,
+            amount_msat: MilliSat::from_msat(200_000_000),
+            payment_hash: PaymentHash160([0x42; 20]),
+            cltv_expiry: 310,
+        };
+
+        let (commitment_tx, htlc_outputs) = build_commitment_full(
+            &keys,
+            &params,
+            MilliSat::from_msat(3_000_000_000),
+            MilliSat::from_msat(1_395_000_000),
+            &[offered, received],
+            1,
+        )
+        .unwrap();
+
+        let htlc_txs = build_all_htlc_transactions(&commitment_tx, &htlc_outputs, &keys, &params).unwrap();
+        assert_eq!(htlc_txs.len(), 2);
+
+        let (timeout_tx, timeout_script) = &htlc_txs[0];
+        let timeout_vout = htlc_outputs[0].output_index.unwrap();
+        assert_eq!(timeout_tx.input[0].previous_output.txid, commitment_tx.compute_txid());
+        assert_eq!(timeout_tx.input[0].previous_output.vout, timeout_vout);
+        assert_eq!(timeout_tx.lock_time.to_consensus_u32(), 300);
+        assert_eq!(timeout_tx.output[0].value.to_sat(), 405_000 - 663 * 253 / 1000);
+        assert_eq!(
+            timeout_script.to_p2wsh(),
+            commitment_tx.output[timeout_vout as usize].script_pubkey
+        );
+
+        let (success_tx, success_script) = &htlc_txs[1];
+        let success_vout = htlc_outputs[1].output_index.unwrap();
+        assert_eq!(success_tx.input[0].previous_output.txid, commitment_tx.compute_txid());
+        assert_eq!(success_tx.input[0].previous_output.vout, success_vout);
+        assert_eq!(success_tx.lock_time.to_consensus_u32(), 0);
+        assert_eq!(success_tx.output[0].value.to_sat(), 200_000 - 703 * 253 / 1000);
+        assert_eq!(
+            success_script.to_p2wsh(),
+            commitment_tx.output[success_vout as usize].script_pubkey
+        );
+
+        // at a feerate the offered HTLC can't pay its HTLC-timeout fee from, the
+        // transaction can't be built
+        let high_feerate_per_kw = 700_000;
+        let high_fee_params = ChannelParams { feerate_per_kw: high_feerate_per_kw, ..params };
+        assert_eq!(
+            build_all_htlc_transactions(&commitment_tx, &htlc_outputs, &keys, &high_fee_params),
+            Err(ExerciseError::InsufficientFunds { needed: 663 * high_feerate_per_kw / 1000, available: 405_000 })
+        );
+
+        // zero-fee HTLC transactions spend the whole HTLC, at any feerate
+        let zero_fee_params =
+            ChannelParams { htlc_fee_mode: HtlcFeeMode::ZeroFeeAnchors, feerate_per_kw: high_feerate_per_kw, ..params };
+        let (commitment_tx, htlc_outputs) = build_commitment_full(
+            &keys,
+            &zero_fee_params,
+            MilliSat::from_msat(3_000_000_000),
+            MilliSat::from_msat(1_395_000_000),
+            &[offered, received],
+            1,
+        )
+        .unwrap();
+        let htlc_txs = build_all_htlc_transactions(&commitment_tx, &htlc_outputs, &keys, &zero_fee_params).unwrap();
+        assert_eq!(htlc_txs.len(), 2);
+        for ((htlc_tx, _), htlc_output) in htlc_txs.iter().zip(&htlc_outputs) {
+            assert_eq!(htlc_tx.input[0].sequence, Sequence(1));
+            assert_eq!(htlc_tx.output[0].value, htlc_output.amount_sat());
+        }
+    })
+}
+
+#[test]
+fn test_15_received_htlc_witness_script() {
+    EXERCISE_15.run(|| {
+        // BOLT 3 appendix C keys
+        let revocation_pubkey: PublicKey = "0212a140cd0c6539d07cd08dfe09984dec3251ea808b892efeac3ede9402bf2b19"
+            .parse()
+            .unwrap();
+        let local_htlc_pubkey: PublicKey = "030d417a46946384f88d5f3337267c5e579765875dc4daca813e21734b140639e7"
+            .parse()
+            .unwrap();
+        let remote_htlc_pubkey: PublicKey = "0394854aa6eab5b2a8122cc726e9dded053a2184d88256816826d6231c068d4a5b"
+            .parse()
+            .unwrap();
+
+        // the received HTLCs #0, #1 and #4 and their P2WSH outputs
+        let vectors = [
+            (0x00, 500, "002052bfef0479d7b293c27e0f1eb294bea154c63a3294ef092c19af51409bce0e2a"),
+            (0x01, 501, "0020748eba944fedc8827f6b06bc44678f93c0f9e6078b35c6331ed31e75f8ce0c2d"),
+            (0x04, 504, "00208c48d15160397c9731df9bc3b236656efb6665fbfe92b4a6878e88a499f741c4"),
+        ];
+
+        for (preimage_byte, cltv_expiry, expected_script_pubkey) in vectors {
+            let payment_hash = PaymentPreimage([preimage_byte; 32]).hash160();
+
+            let script = received_htlc_witness_script(
+                &RevocationPubkey(revocation_pubkey),
+                &HtlcPubkey(remote_htlc_pubkey),
+                &HtlcPubkey(local_htlc_pubkey),
+                &payment_hash,
+                cltv_expiry,
+            );
+
+            assert_eq!(script.to_p2wsh().to_hex_string(), expected_script_pubkey);
+            assert_eq!(
+                script,
+                build_htlc_receiver_witness_script(
+                    &RevocationPubkey(revocation_pubkey),
+                    &HtlcPubkey(remote_htlc_pubkey),
+                    &HtlcPubkey(local_htlc_pubkey),
+                    &payment_hash,
+                    cltv_expiry,
+                )
+            );
+        }
+
+        let htlc_0_script = received_htlc_witness_script(
+            &RevocationPubkey(revocation_pubkey),
+            &HtlcPubkey(remote_htlc_pubkey),
+            &HtlcPubkey(local_htlc_pubkey),
+            &PaymentPreimage([0x00; 32]).hash160(),
+            500,
+        );
+        assert_eq!(
+            htlc_0_script.to_hex_string(),
+            "76a91414011f7254d96b819c76986c277d115efce6f7b58763ac67210394854aa6eab5b2a8122cc726e9dded053a2184d88256816826d6231c068d4a5b7c8201208763a914b8bcb07f6344b42ab04250c86a6e8b75d3fdbbc688527c21030d417a46946384f88d5f3337267c5e579765875dc4daca813e21734b140639e752ae677502f401b175ac6868"
+        );
+    })
+}
+
+#[test]
+fn test_16_build_htlc_success_transaction() {
+    EXERCISE_16.run(|| {
+        let txin = TxIn { sequence: Sequence::ZERO, ..dummy_funding_txin() };
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let contest_delay: i64 = 144;
+        let htlc_amount = Amount::from_sat(404_000);
+
+        let success_tx = build_htlc_success_transaction(
+            txin.clone(),
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            contest_delay,
+            htlc_amount,
+            HtlcFeeMode::Legacy,
+        ).unwrap();
+        print_transaction(&success_tx);
+
+        let timeout_tx = build_htlc_timeout_transaction(
+            txin.clone(),
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            contest_delay,
+            300,
+            htlc_amount,
+            HtlcFeeMode::Legacy,
+        ).unwrap();
+        print_transaction(&timeout_tx);
+
+        // both second-stage transactions pay to the same revocable to_local script
+        assert_eq!(success_tx.output, timeout_tx.output);
+        assert_eq!(
+            success_tx.output[0].script_pubkey,
+            to_local(&revocation_pubkey, &to_local_delayed_pubkey, contest_delay).unwrap().to_p2wsh()
+        );
+
+        assert_eq!(success_tx.lock_time.to_consensus_u32(), 0);
+        assert_eq!(timeout_tx.lock_time.to_consensus_u32(), 300);
+        assert_eq!(success_tx.input, vec![txin]);
+
+        // the anchor HTLC scripts need `1 OP_CSV` on the success path too
+        let anchor_success_tx = build_htlc_success_transaction(
+            success_tx.input[0].clone(),
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            contest_delay,
+            htlc_amount,
+            HtlcFeeMode::ZeroFeeAnchors,
+        )
+        .unwrap();
+        assert_eq!(anchor_success_tx.input[0].sequence, Sequence(1));
+        assert_eq!(anchor_success_tx.output, success_tx.output);
+    })
+}
+
+#[test]
+fn test_17_build_closing_transaction() {
+    EXERCISE_17.run(|| {
+        let txin = TxIn { sequence: Sequence::ZERO, ..dummy_funding_txin() };
+
+        let local_script = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+        let remote_script = p2wpkh_output_script(pubkey_from_private_key(&[0x02; 32]));
+
+        let closing_tx = build_closing_transaction(
+            txin.clone(),
+            local_script.clone(),
+            remote_script.clone(),
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(2_000_000),
+            true,
+            Amount::from_sat(546),
+            253,
+        )
+        .unwrap();
+        print_transaction(&closing_tx);
+
+        assert_eq!(closing_tx.lock_time.to_consensus_u32(), 0);
+        assert_eq!(closing_tx.input[0].sequence, Sequence::MAX);
+        assert_eq!(closing_tx.output.len(), 2);
+
+        // sorted by value: remote first, and only the funder pays the fee
+        assert_eq!(closing_tx.output[0].script_pubkey, remote_script);
+        assert_eq!(closing_tx.output[0].value.to_sat(), 2_000_000);
+        assert_eq!(closing_tx.output[1].script_pubkey, local_script);
+        let fee = 3_000_000 - closing_tx.output[1].value.to_sat();
+        assert!(fee > 0);
+
+        // the non-funder's dust output is dropped
+        let closing_tx = build_closing_transaction(
+            txin.clone(),
+            local_script.clone(),
+            remote_script.clone(),
+            Amount::from_sat(4_999_500),
+            Amount::from_sat(500),
+            true,
+            Amount::from_sat(546),
+            253,
+        )
+        .unwrap();
+        print_transaction(&closing_tx);
+        assert_eq!(closing_tx.output.len(), 1);
+        assert_eq!(closing_tx.output[0].script_pubkey, local_script);
+        assert_eq!(closing_tx.output[0].value.to_sat(), 4_999_500 - fee);
+
+        // a funder that can't pay the fee is an error, not a dropped output
+        assert_eq!(
+            build_closing_transaction(
+                txin.clone(),
+                local_script.clone(),
+                remote_script.clone(),
+                Amount::from_sat(100),
+                Amount::from_sat(4_999_900),
+                true,
+                Amount::from_sat(546),
+                253,
+            ),
+            Err(ChannelError::InvalidArguments(ExerciseError::InsufficientFunds { needed: fee, available: 100 }))
+        );
+    })
+}
+
+#[test]
+fn test_18_obscure_commitment_transaction() {
+    EXERCISE_18.run(|| {
+        let txin = dummy_funding_txin();
+
+        // BOLT 3 appendix C payment basepoints
+        let opener_payment_basepoint: PublicKey = "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa"
+            .parse()
+            .unwrap();
+        let accepter_payment_basepoint: PublicKey = "032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            commitment_number_obscure_factor(&opener_payment_basepoint, &accepter_payment_basepoint),
+            0x2bb038521914
+        );
+
+        let commitment_tx = build_transaction(
+            bitcoin::transaction::Version::TWO,
+            bitcoin::absolute::LockTime::ZERO,
+            vec![txin],
+            vec![build_output(Amount::from_sat(4_990_000), p2wpkh_output_script(opener_payment_basepoint))],
+        );
+
+        let obscured = obscure_commitment_transaction(
+            commitment_tx,
+            &opener_payment_basepoint,
+            &accepter_payment_basepoint,
+            42,
+        );
+        print_transaction(&obscured);
+
+        // 42 ^ 0x2bb038521914 = 0x2bb03852193e
+        assert_eq!(obscured.lock_time.to_consensus_u32(), 0x2052193e);
+        assert_eq!(obscured.input[0].sequence.0, 0x802bb038);
+    })
+}
+
+#[test]
+fn test_19_build_trimmed_htlc_commitment_transaction() {
+    EXERCISE_19.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x05; 32]);
+
+        let htlc = |offered: bool, amount_msat: u64, preimage_byte: u8| Htlc {
+            offered,
+            amount_msat: MilliSat::from_msat(amount_msat),
+            payment_hash: PaymentPreimage([preimage_byte; 32]).hash160(),
+            cltv_expiry: 500,
+        };
+
+        // at 1000 sat/kw an offered HTLC needs 546 + 663 sats and a received one 546 + 703
+        let htlcs = [
+            htlc(true, 1_209_000, 0x01),
+            htlc(true, 1_208_000, 0x02),
+            htlc(false, 1_249_000, 0x03),
+            htlc(false, 1_248_000, 0x04),
+        ];
+
+        let transaction = build_trimmed_htlc_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            &htlcs,
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(500),
+            true,
+            Amount::from_sat(546),
+            1_000,
+        ).unwrap();
+        print_transaction(&transaction);
+
+        let values: Vec<u64> = transaction.output.iter().map(|output| output.value.to_sat()).collect();
+
+        // the two HTLCs just below their threshold and the dust to_remote are
+        // dropped, and the funder pays for a commitment with 2 HTLCs
+        assert_eq!(values, vec![1_209, 1_249, 3_000_000 - (724 + 2 * 172)]);
+
+        let offered_script = build_htlc_offerer_witness_script(
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &htlcs[0].payment_hash,
+        );
+        assert_eq!(transaction.output[0].script_pubkey, offered_script.to_p2wsh());
+    })
+}
+
+#[test]
+fn test_19_build_trimmed_htlc_commitment_transaction_remote_funder() {
+    EXERCISE_19.run(|| {
+        let txin = dummy_funding_txin();
+
+        let build = |local_is_funder: bool, remote_amount: u64| {
+            build_trimmed_htlc_commitment_transaction(
+                txin.clone(),
+                &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+                &HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+                &HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+                &DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32])),
+                pubkey_from_private_key(&[0x05; 32]),
+                144,
+                &[],
+                Amount::from_sat(3_000_000),
+                Amount::from_sat(remote_amount),
+                local_is_funder,
+                Amount::from_sat(546),
+                253,
+            )
+        };
+
+        // 724 * 253 / 1000 = 183 sats of fee, paid by whoever funded the channel
+        let values = |tx: Transaction| -> Vec<u64> { tx.output.iter().map(|output| output.value.to_sat()).collect() };
+        assert_eq!(values(build(true, 2_000_000).unwrap()), vec![2_000_000, 3_000_000 - 183]);
+        assert_eq!(values(build(false, 2_000_000).unwrap()), vec![2_000_000 - 183, 3_000_000]);
+
+        // a funder that can't cover the fee is an error, not an output clamped to zero
+        assert_eq!(build(false, 100), Err(ExerciseError::InsufficientFunds { needed: 183, available: 100 }));
+    })
+}
+
+#[test]
+fn test_20_generate_per_commitment_secret() {
+    EXERCISE_20.run(|| {
+        // BOLT 3 appendix D, generate_from_seed test vectors
+        let vectors: [([u8; 32], u64, &str); 5] = [
+            ([0x00; 32], 281474976710655, "02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148"),
+            ([0xff; 32], 281474976710655, "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc"),
+            ([0xff; 32], 0xaaaaaaaaaaa, "56f4008fb007ca9acf0e15b054d5c9fd12ee06cea347914ddbaed70d1c13a528"),
+            ([0xff; 32], 0x555555555555, "9015daaeb06dba4ccc05b91b2f73bd54405f2be9f217fbacd3c5ac2e62327d31"),
+            ([0x01; 32], 1, "915c75942a26bb3a433a8ce2cb0427c29ec6c1775cfc78328b57f6ba7bfeaa9c"),
+        ];
+
+        for (seed, index, expected) in vectors {
+            assert_eq!(hex::encode(generate_per_commitment_secret(&seed, index)), expected);
+            assert_eq!(hex::encode(generate_from_seed(&seed, index)), expected);
+        }
+    })
+}
+
+#[test]
+fn test_21_can_derive() {
+    EXERCISE_21.run(|| {
+        // a secret with N trailing zero bits derives the 2^N indices below it
+        assert!(can_derive(0b1000, 0b1000));
+        assert!(can_derive(0b1000, 0b1111));
+        assert!(can_derive(0b1000, 0b1001));
+        assert!(!can_derive(0b1000, 0b0111));
+        assert!(!can_derive(0b1000, 0b10000));
+
+        // odd indices only derive themselves
+        assert!(can_derive(0b1011, 0b1011));
+        assert!(!can_derive(0b1011, 0b1010));
+
+        assert!(can_derive(MAX_SHACHAIN_INDEX - 1, MAX_SHACHAIN_INDEX));
+        assert!(!can_derive(MAX_SHACHAIN_INDEX, MAX_SHACHAIN_INDEX - 1));
+    })
+}
+
+#[test]
+fn test_21_shachain_store() {
+    EXERCISE_21.run(|| {
+        let seed = [0x42; 32];
+        let mut store = ShachainStore::new();
+
+        // receive the secrets of the first 20 commitments, newest last
+        for n in 0..20 {
+            let index = MAX_SHACHAIN_INDEX - n;
+            store.insert(index, generate_from_seed(&seed, index)).unwrap();
+        }
+
+        // every secret received so far can be recovered from the compact store
+        for n in 0..20 {
+            let index = MAX_SHACHAIN_INDEX - n;
+            assert_eq!(store.derive(index), Some(generate_from_seed(&seed, index)));
+        }
+        assert_eq!(store.derive(MAX_SHACHAIN_INDEX - 20), None);
+
+        // a secret from another seed can't derive the secrets already received
+        let index = MAX_SHACHAIN_INDEX - 21;
+        assert!(store.insert(MAX_SHACHAIN_INDEX - 20, generate_from_seed(&seed, MAX_SHACHAIN_INDEX - 20)).is_ok());
+        assert_eq!(
+            store.insert(index, generate_from_seed(&[0x43; 32], index)),
+            Err(ChannelError::InconsistentSecret { index })
+        );
+    })
+}
+
+#[test]
+fn test_22_derive_commitment_keys() {
+    EXERCISE_22.run(|| {
+        // BOLT 3 appendix E, key derivation test vectors
+        let base_secret = SecretKey::from_slice(
+            &hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap(),
+        )
+        .unwrap();
+        let per_commitment_secret = SecretKey::from_slice(
+            &hex::decode("1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100").unwrap(),
+        )
+        .unwrap();
+
+        let basepoint = pubkey_from_secret(base_secret);
+        let per_commitment_point = pubkey_from_secret(per_commitment_secret);
+        assert_eq!(
+            basepoint.to_string(),
+            "036d6caac248af96f6afa7f904f550253a0f3ef3f5aa2fe6838a95b216691468e2"
+        );
+        assert_eq!(
+            per_commitment_point.to_string(),
+            "025f7117a78150fe2ef97db7cfc83bd57b2e2c0d0dd25eaf467a4a1c2a45ce1486"
+        );
+
+        let localpubkey = derive_public_key(basepoint, per_commitment_point);
+        assert_eq!(
+            localpubkey.to_string(),
+            "0235f2dbfaa89b57ec7b055afe29849ef7ddfeb1cefdb9ebdc43f5494984db29e5"
+        );
+
+        let localprivkey = derive_private_key(base_secret, per_commitment_point);
+        assert_eq!(
+            localprivkey.display_secret().to_string(),
+            "cbced912d3b21bf196a766651e436aff192362621ce317704ea2f75d87e7be0f"
+        );
+        assert_eq!(pubkey_from_secret(localprivkey), localpubkey);
+
+        // the same derivation applies to every basepoint
+        let delayed_basepoint = secp256k1pubkey_from_private_key(&[0x02; 32]);
+        let htlc_basepoint = secp256k1pubkey_from_private_key(&[0x03; 32]);
+        let (local, delayed, htlc) =
+            derive_commitment_keys(basepoint, delayed_basepoint, htlc_basepoint, per_commitment_point);
+
+        assert_eq!(local, localpubkey);
+        assert_eq!(delayed, derive_public_key(delayed_basepoint, per_commitment_point));
+        assert_eq!(htlc, derive_public_key(htlc_basepoint, per_commitment_point));
+        assert_eq!(
+            pubkey_from_secret(derive_private_key(secp256k1_private_key(&[0x03; 32]), per_commitment_point)),
+            htlc
+        );
+    })
+}
+
+#[test]
+fn test_23_build_funding_witness() {
+    EXERCISE_23.run(|| {
+        let txin = dummy_funding_txin();
+
+        let alice_secret = secp256k1_private_key(&[0x01; 32]);
+        let bob_secret = secp256k1_private_key(&[0x02; 32]);
+        let alice_pubkey = pubkey_from_private_key(&[0x01; 32]);
+        let bob_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let funding_amount = 5_000_000;
+        let (funding_tx, funding_script) =
+            build_funding_transaction_with_script(vec![txin], &alice_pubkey, &bob_pubkey, Amount::from_sat(funding_amount));
+
+        let spend_txin = TxIn {
+            previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let spend_tx = build_transaction(
+            bitcoin::transaction::Version::TWO,
+            bitcoin::absolute::LockTime::ZERO,
+            vec![spend_txin],
+            vec![build_output(Amount::from_sat(4_990_000), p2wpkh_output_script(alice_pubkey))],
+        );
+
+        let sign = |secret| {
+            generate_p2wsh_signature(spend_tx.clone(), 0, &funding_script, funding_amount, EcdsaSighashType::All, secret)
+        };
+        let alice_signature = sign(alice_secret);
+        let bob_signature = sign(bob_secret);
+
+        let spent_output = |_: &OutPoint| Some(funding_tx.output[0].clone());
+
+        // the result doesn't depend on which party is passed first
+        for witness in [
+            build_funding_witness(&funding_script, &alice_pubkey, &alice_signature, &bob_pubkey, &bob_signature),
+            build_funding_witness(&funding_script, &bob_pubkey, &bob_signature, &alice_pubkey, &alice_signature),
+        ] {
+            assert_eq!(witness.len(), 4);
+            assert!(witness.nth(0).unwrap().is_empty());
+            assert_eq!(witness.last().unwrap(), funding_script.as_bytes());
+
+            let mut signed_tx = spend_tx.clone();
+            signed_tx.input[0].witness = witness;
+            print_transaction(&signed_tx);
+            assert!(signed_tx.verify(spent_output).is_ok());
+        }
+
+        // the signatures in the wrong order don't satisfy OP_CHECKMULTISIG
+        let witness = build_funding_witness(&funding_script, &alice_pubkey, &alice_signature, &bob_pubkey, &bob_signature);
+        let swapped = Witness::from_slice(&[
+            witness.nth(0).unwrap(),
+            witness.nth(2).unwrap(),
+            witness.nth(1).unwrap(),
+            witness.nth(3).unwrap(),
+        ]);
+        let mut signed_tx = spend_tx.clone();
+        signed_tx.input[0].witness = swapped;
+        assert!(signed_tx.verify(spent_output).is_err());
+    })
+}
+
+#[test]
+fn test_24_compute_commitment_sighash() {
+    EXERCISE_24.run(|| {
+        let txin = dummy_funding_txin();
+
+        let alice_secret = secp256k1_private_key(&[0x01; 32]);
+        let bob_secret = secp256k1_private_key(&[0x02; 32]);
+        let alice_pubkey = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let bob_pubkey = FundingPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let funding_amount = 5_000_000;
+        let (funding_tx, funding_script) =
+            build_funding_transaction_with_script(vec![txin], &alice_pubkey.0, &bob_pubkey.0, Amount::from_sat(funding_amount));
+
+        let commitment_txin = TxIn {
+            previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let commitment_tx = build_commitment_transaction(
+            commitment_txin,
+            &RevocationPubkey(pubkey_from_private_key(&[0x03; 32])),
+            &DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32])),
+            bob_pubkey.0,
+            144,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(1_000_500),
+            true,
+            253,
+            239765233721135,
+            1,
+        ).unwrap();
+
+        let sighash = compute_commitment_sighash(&commitment_tx, &alice_pubkey, &bob_pubkey, Amount::from_sat(funding_amount));
+
+        // both parties compute the same digest
+        assert_eq!(
+            sighash,
+            compute_commitment_sighash(&commitment_tx, &bob_pubkey, &alice_pubkey, Amount::from_sat(funding_amount))
+        );
+
+        // the channel value is committed to
+        assert_ne!(
+            sighash,
+            compute_commitment_sighash(&commitment_tx, &alice_pubkey, &bob_pubkey, Amount::from_sat(funding_amount - 1))
+        );
+
+        let secp = Secp256k1::new();
+        let message = secp256k1::Message::from_digest(sighash);
+        let alice_signature = secp.sign_ecdsa(&message, &alice_secret);
+        let bob_signature = secp.sign_ecdsa(&message, &bob_secret);
+
+        let mut signed_tx = commitment_tx.clone();
+        signed_tx.input[0].witness =
+            build_funding_witness(&funding_script, &alice_pubkey.0, &alice_signature, &bob_pubkey.0, &bob_signature);
+
+        print_transaction(&signed_tx);
+        assert!(signed_tx.verify(|_| Some(funding_tx.output[0].clone())).is_ok());
+    })
+}
+
+#[test]
+fn test_25_sign_commitment_transaction() {
+    EXERCISE_25.run(|| {
+        let txin = dummy_funding_txin();
+
+        let alice_secret = secp256k1_private_key(&[0x01; 32]);
+        let bob_secret = secp256k1_private_key(&[0x02; 32]);
+        let alice_pubkey = pubkey_from_private_key(&[0x01; 32]);
+        let bob_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let funding_amount = 5_000_000;
+        let (funding_tx, funding_script) =
+            build_funding_transaction_with_script(vec![txin], &alice_pubkey, &bob_pubkey, Amount::from_sat(funding_amount));
+
+        let commitment_txin = TxIn {
+            previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let commitment_tx = build_commitment_transaction(
+            commitment_txin,
+            &RevocationPubkey(pubkey_from_private_key(&[0x03; 32])),
+            &DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32])),
+            bob_pubkey,
+            144,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(1_000_500),
+            true,
+            253,
+            239765233721135,
+            1,
+        ).unwrap();
+
+        let alice_signature = sign_commitment_transaction(&commitment_tx, &funding_script, Amount::from_sat(funding_amount), &alice_secret);
+        let bob_signature = sign_commitment_transaction(&commitment_tx, &funding_script, Amount::from_sat(funding_amount), &bob_secret);
+
+        assert!(verify_p2wsh_signature(&commitment_tx, 0, &funding_script, funding_amount, &alice_signature, &alice_pubkey));
+        assert!(verify_p2wsh_signature(&commitment_tx, 0, &funding_script, funding_amount, &bob_signature, &bob_pubkey));
+        assert!(!verify_p2wsh_signature(&commitment_tx, 0, &funding_script, funding_amount, &alice_signature, &bob_pubkey));
+
+        // signatures must be low-S to be relayed
+        let mut normalized = alice_signature;
+        normalized.normalize_s();
+        assert_eq!(normalized, alice_signature);
+
+        let mut signed_tx = commitment_tx.clone();
+        signed_tx.input[0].witness =
+            build_funding_witness(&funding_script, &alice_pubkey, &alice_signature, &bob_pubkey, &bob_signature);
+
+        print_transaction(&signed_tx);
+        assert!(signed_tx.verify(|_| Some(funding_tx.output[0].clone())).is_ok());
+    })
+}
+
+#[test]
+fn test_26_build_penalty_transaction() {
+    EXERCISE_26.run(|| {
+        let txin = dummy_funding_txin();
+
+        // Bob revealed this commitment's secret, so Alice holds its revocation key
+        let per_commitment_secret = secp256k1_private_key(&[0x05; 32]);
+        let alice_revocation_base_secret = secp256k1_private_key(&[0x06; 32]);
+        let revocation_privkey = generate_revocation_privkey(per_commitment_secret, alice_revocation_base_secret);
+        let revocation_pubkey = RevocationPubkey(PublicKey::new(revocation_privkey.public_key(&Secp256k1::new())));
+
+        let bob_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x07; 32]));
+        let alice_pubkey = pubkey_from_private_key(&[0x08; 32]);
+        let to_self_delay: i64 = 144;
+
+        let revoked_commitment = build_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &bob_delayed_pubkey,
+            alice_pubkey,
+            to_self_delay,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(1_000_500),
+            true,
+            253,
+            0,
+            0,
+        ).unwrap();
+
+        let to_local_script = to_local(&revocation_pubkey, &bob_delayed_pubkey, to_self_delay).unwrap();
+        let to_local_index = revoked_commitment
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == to_local_script.to_p2wsh())
+            .unwrap();
+        let to_local_output = revoked_commitment.output[to_local_index].clone();
+
+        let feerate = 253;
+        let penalty_tx = build_penalty_transaction(
+            OutPoint::new(revoked_commitment.compute_txid(), to_local_index as u32),
+            to_local_output.value,
+            &revocation_privkey,
+            &to_local_script,
+            p2wpkh_output_script(alice_pubkey),
+            feerate,
+        )
+        .unwrap();
+        print_transaction(&penalty_tx);
+
+        let witness = &penalty_tx.input[0].witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness.nth(1).unwrap(), [1u8]);
+        assert_eq!(witness.last().unwrap(), to_local_script.as_bytes());
+
+        // no delay is needed to use the revocation branch
+        assert_eq!(penalty_tx.input[0].sequence, Sequence::MAX);
+        assert_eq!(
+            penalty_tx.output[0].value,
+            to_local_output.value - internal::watchtower::penalty_tx_fee(1, 0, feerate)
+        );
+
+        assert!(penalty_tx.verify(|_| Some(to_local_output.clone())).is_ok());
+
+        // a to_local output too small to pay for its own sweep
+        let fee = internal::watchtower::penalty_tx_fee(1, 0, feerate);
+        assert_eq!(
+            build_penalty_transaction(
+                OutPoint::new(revoked_commitment.compute_txid(), to_local_index as u32),
+                fee - Amount::from_sat(1),
+                &revocation_privkey,
+                &to_local_script,
+                p2wpkh_output_script(alice_pubkey),
+                feerate,
+            ),
+            Err(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: fee.to_sat() - 1 })
+        );
+    })
+}
+
+#[test]
+fn test_27_build_delayed_sweep_transaction() {
+    EXERCISE_27.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let delayed_privkey = secp256k1_private_key(&[0x02; 32]);
+        let delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+        let to_self_delay: u16 = 144;
+
+        let commitment = build_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &delayed_pubkey,
+            remote_pubkey,
+            to_self_delay as i64,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(1_000_500),
+            true,
+            253,
+            0,
+            0,
+        ).unwrap();
+
+        let to_local_script = to_local(&revocation_pubkey, &delayed_pubkey, to_self_delay as i64).unwrap();
+        let to_local_index = commitment
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == to_local_script.to_p2wsh())
+            .unwrap();
+        let to_local_output = commitment.output[to_local_index].clone();
+        let to_local_outpoint = OutPoint::new(commitment.compute_txid(), to_local_index as u32);
+
+        let sweep = |delay, amount| {
+            build_delayed_sweep_transaction(
+                to_local_outpoint,
+                amount,
+                &delayed_privkey,
+                &to_local_script,
+                delay,
+                p2wpkh_output_script(delayed_pubkey.0),
+                253,
+            )
+        };
+
+        let sweep_tx = sweep(to_self_delay, to_local_output.value).unwrap();
+        assert_eq!(sweep_tx.input[0].sequence, Sequence::from_height(to_self_delay));
+        assert!(sweep_tx.input[0].witness.nth(1).unwrap().is_empty());
+        print_transaction(&sweep_tx);
+        assert!(sweep_tx.verify(|_| Some(to_local_output.clone())).is_ok());
+
+        // a correctly signed sweep that doesn't wait long enough fails OP_CSV
+        let early_sweep_tx = sweep(to_self_delay - 1, to_local_output.value).unwrap();
+        assert!(early_sweep_tx.verify(|_| Some(to_local_output.clone())).is_err());
+
+        // an output worth less than the sweep's fee can't be swept
+        let fee = fee_for_weight(253, DELAYED_SWEEP_TX_WEIGHT);
+        assert_eq!(
+            sweep(to_self_delay, fee - Amount::from_sat(1)),
+            Err(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: fee.to_sat() - 1 })
+        );
+    })
+}
+
+#[test]
+fn test_28_finalize_htlc_timeout_transaction() {
+    EXERCISE_28.run(|| {
+        let txin = dummy_funding_txin();
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_privkey = secp256k1_private_key(&[0x02; 32]);
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_privkey = secp256k1_private_key(&[0x03; 32]);
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+
+        let htlc_output_amount = Amount::from_sat(405_000);
+        let htlc_amount = Amount::from_sat(404_000);
+
+        for htlc_fee_mode in [HtlcFeeMode::Legacy, HtlcFeeMode::Anchors] {
+            let anchors = htlc_fee_mode.anchors();
+            let offered_htlc_script = if anchors {
+                build_anchor_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &HASH160_DUMMY)
+            } else {
+                build_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &HASH160_DUMMY)
+            };
+            let htlc_output = build_output(htlc_output_amount, offered_htlc_script.to_p2wsh());
+
+            let unsigned_tx = build_htlc_timeout_transaction(
+                txin.clone(),
+                &revocation_pubkey,
+                &delayed_pubkey,
+                144,
+                300,
+                htlc_amount,
+                htlc_fee_mode,
+            ).unwrap();
+
+            // the counterparty signs the transaction with its BOLT 3 sequence
+            let mut remote_view = unsigned_tx.clone();
+            remote_view.input[0].sequence = if anchors { Sequence(1) } else { Sequence::ZERO };
+            let remote_signature = generate_p2wsh_signature(
+                remote_view,
+                0,
+                &offered_htlc_script,
+                htlc_output_amount.to_sat(),
+                counterparty_htlc_sighash_type(anchors),
+                remote_htlc_privkey,
+            );
+
+            let signed_tx = finalize_htlc_timeout_transaction(
+                unsigned_tx,
+                &offered_htlc_script,
+                htlc_output_amount,
+                &remote_signature,
+                &local_htlc_privkey,
+                anchors,
+            );
+            print_transaction(&signed_tx);
+
+            let witness = &signed_tx.input[0].witness;
+            assert_eq!(witness.len(), 5);
+            assert!(witness.nth(0).unwrap().is_empty());
+            assert!(witness.nth(3).unwrap().is_empty());
+            assert_eq!(witness.last().unwrap(), offered_htlc_script.as_bytes());
+            assert_eq!(signed_tx.lock_time.to_consensus_u32(), 300);
+
+            assert!(signed_tx.verify(|_| Some(htlc_output.clone())).is_ok());
+        }
+    })
+}
+
+/// The 20-byte payment hash an HTLC script checks with OP_EQUALVERIFY.
+fn embedded_payment_hash(script: &ScriptBuf) -> [u8; 20] {
+    let instructions: Vec<_> = script.instructions().map(|instruction| instruction.unwrap()).collect();
+    instructions
+        .windows(2)
+        .find_map(|pair| match (&pair[0], &pair[1]) {
+            (bitcoin::script::Instruction::PushBytes(bytes), bitcoin::script::Instruction::Op(op))
+                if *op == bitcoin::opcodes::all::OP_EQUALVERIFY && bytes.len() == 20 =>
+            {
+                Some(bytes.as_bytes().try_into().unwrap())
+            }
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_29_htlc_preimage_witnesses() {
+    EXERCISE_29.run(|| {
+        let txin = TxIn { sequence: Sequence::ZERO, ..dummy_funding_txin() };
+
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_privkey = secp256k1_private_key(&[0x02; 32]);
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_privkey = secp256k1_private_key(&[0x03; 32]);
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+
+        let payment_preimage = PaymentPreimage([0x42; 32]);
+        let payment_hash = payment_preimage.hash160();
+        let htlc_output_amount = Amount::from_sat(405_000);
+
+        // the scripts lock to RIPEMD160(SHA256(preimage))
+        let expected_hash: [u8; 20] = Ripemd160::digest(Sha256::digest(payment_preimage.0)).into();
+
+        // the receiver claims an offered HTLC on the offerer's commitment
+        let offered_htlc_script =
+            build_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &payment_hash);
+        assert_eq!(embedded_payment_hash(&offered_htlc_script), expected_hash);
+
+        let offered_output = build_output(htlc_output_amount, offered_htlc_script.to_p2wsh());
+        let claim_tx = build_transaction(
+            bitcoin::transaction::Version::TWO,
+            bitcoin::absolute::LockTime::ZERO,
+            vec![txin.clone()],
+            vec![build_output(Amount::from_sat(400_000), p2wpkh_output_script(remote_htlc_pubkey.0))],
+        );
+        let remote_signature = generate_p2wsh_signature(
+            claim_tx.clone(),
+            0,
+            &offered_htlc_script,
+            htlc_output_amount.to_sat(),
+            EcdsaSighashType::All,
+            remote_htlc_privkey,
+        );
+
+        let mut signed_claim_tx = claim_tx.clone();
+        signed_claim_tx.input[0].witness =
+            offered_htlc_preimage_witness(&remote_signature, &payment_preimage, &offered_htlc_script);
+        print_transaction(&signed_claim_tx);
+        assert!(signed_claim_tx.verify(|_| Some(offered_output.clone())).is_ok());
+
+        let mut wrong_preimage_tx = claim_tx.clone();
+        wrong_preimage_tx.input[0].witness =
+            offered_htlc_preimage_witness(&remote_signature, &PaymentPreimage([0x43; 32]), &offered_htlc_script);
+        assert!(wrong_preimage_tx.verify(|_| Some(offered_output.clone())).is_err());
+
+        // the HTLC-success transaction spends a received HTLC on our commitment
+        let cltv_expiry = 500;
+        let received_htlc_script = build_htlc_receiver_witness_script(
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &payment_hash,
+            cltv_expiry,
+        );
+        assert_eq!(embedded_payment_hash(&received_htlc_script), expected_hash);
+
+        let received_output = build_output(htlc_output_amount, received_htlc_script.to_p2wsh());
+        let success_tx = build_htlc_success_transaction(
+            txin,
+            &revocation_pubkey,
+            &delayed_pubkey,
+            144,
+            Amount::from_sat(400_000),
+            HtlcFeeMode::Legacy,
+        )
+        .unwrap();
+
+        let sign = |secret| {
+            generate_p2wsh_signature(
+                success_tx.clone(),
+                0,
+                &received_htlc_script,
+                htlc_output_amount.to_sat(),
+                EcdsaSighashType::All,
+                secret,
+            )
+        };
+        let remote_signature = sign(remote_htlc_privkey);
+        let local_signature = sign(local_htlc_privkey);
+
+        let mut signed_success_tx = success_tx.clone();
+        signed_success_tx.input[0].witness =
+            htlc_success_witness(&remote_signature, &local_signature, &payment_preimage, &received_htlc_script, false);
+        assert_eq!(signed_success_tx.input[0].witness.len(), 5);
+        print_transaction(&signed_success_tx);
+        assert!(signed_success_tx.verify(|_| Some(received_output.clone())).is_ok());
+
+        // swapping the signatures breaks OP_CHECKMULTISIG
+        let mut swapped_tx = success_tx.clone();
+        swapped_tx.input[0].witness =
+            htlc_success_witness(&local_signature, &remote_signature, &payment_preimage, &received_htlc_script, false);
+        assert!(swapped_tx.verify(|_| Some(received_output.clone())).is_err());
+    })
+}
+
+#[test]
+fn test_30_channel_id_for_funding_transaction() {
+    EXERCISE_30.run(|| {
+        let txin = dummy_funding_txin();
+
+        let alice_pubkey = pubkey_from_private_key(&[0x01; 32]);
+        let bob_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let (funding_tx, funding_script) =
+            build_funding_transaction_with_script(vec![txin.clone()], &alice_pubkey, &bob_pubkey, Amount::from_sat(5_000_000));
+
+        // the funding output comes first, so the channel_id is the txid itself
+        assert_eq!(
+            channel_id_for_funding_transaction(&funding_tx, &funding_script),
+            Some(funding_tx.compute_txid().to_byte_array())
+        );
+
+        // put a change output in front of the funding output
+        let mut reordered_tx = funding_tx.clone();
+        reordered_tx.output.insert(0, build_output(Amount::from_sat(10_000), p2wpkh_output_script(alice_pubkey)));
+        let channel_id = channel_id_for_funding_transaction(&reordered_tx, &funding_script).unwrap();
+        assert_eq!(channel_id, channel_id_from_funding(reordered_tx.compute_txid(), 1));
+        assert_eq!(channel_id[31], reordered_tx.compute_txid().to_byte_array()[31] ^ 1);
+
+        let unrelated_script = two_of_two_multisig_witness_script(&alice_pubkey, &pubkey_from_private_key(&[0x03; 32]));
+        assert_eq!(channel_id_for_funding_transaction(&funding_tx, &unrelated_script), None);
+    })
+}
+
+#[test]
+fn test_31_cosign_funding_psbt() {
+    EXERCISE_31.run(|| {
+        let txin = dummy_funding_txin();
+
+        let alice_secret = secp256k1_private_key(&[0x01; 32]);
+        let bob_secret = secp256k1_private_key(&[0x02; 32]);
+        let alice_pubkey = pubkey_from_private_key(&[0x01; 32]);
+        let bob_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let funding_amount = 5_000_000;
+        let (funding_tx, funding_script) =
+            build_funding_transaction_with_script(vec![txin], &alice_pubkey, &bob_pubkey, Amount::from_sat(funding_amount));
+
+        let refund_txin = TxIn {
+            previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let refund_psbt = |alice_amount: u64, bob_amount: u64| {
+            let refund_tx = build_refund_transaction(
+                refund_txin.clone(),
+                alice_pubkey,
+                bob_pubkey,
+                Amount::from_sat(alice_amount),
+                Amount::from_sat(bob_amount),
+            );
+            let mut psbt = bitcoin::psbt::Psbt::from_unsigned_tx(refund_tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(funding_tx.output[0].clone());
+            psbt.inputs[0].witness_script = Some(funding_script.clone());
+            psbt
+        };
+
+        let alice_signed_psbt = sign_funding_psbt(refund_psbt(2_995_000, 2_000_000), &alice_secret);
+        assert_eq!(alice_signed_psbt.inputs[0].partial_sigs.len(), 1);
+
+        // one signature isn't enough to finalize
+        assert_eq!(
+            finalize_funding_psbt(alice_signed_psbt.clone()).unwrap_err(),
+            ChannelError::MissingSignature { input: 0 }
+        );
+
+        // keys that aren't in the funding script don't sign
+        let stranger_signed_psbt = sign_funding_psbt(alice_signed_psbt.clone(), &secp256k1_private_key(&[0x03; 32]));
+        assert_eq!(stranger_signed_psbt.inputs[0].partial_sigs.len(), 1);
+
+        let signed_tx = cosign_funding_psbt(alice_signed_psbt, &bob_secret).unwrap();
+        print_transaction(&signed_tx);
+
+        let witness = &signed_tx.input[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness.last().unwrap(), funding_script.as_bytes());
+        assert!(signed_tx.verify(|_| Some(funding_tx.output[0].clone())).is_ok());
+
+        // a refund leaving almost all of the funding output to fees is refused, not a panic
+        let absurd_psbt = sign_funding_psbt(refund_psbt(1_000, 1_000), &alice_secret);
+        match cosign_funding_psbt(absurd_psbt, &bob_secret) {
+            Err(ChannelError::ExtractTx(e)) => assert!(matches!(*e, bitcoin::psbt::ExtractTxError::AbsurdFeeRate { .. })),
+            other => panic!("expected an ExtractTx error, got {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn test_32_encode_open_channel_tlvs() {
+    EXERCISE_32.run(|| {
+        let shutdown_script = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+        let channel_type = encode_features(&[STATIC_REMOTE_KEY_REQUIRED, ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED]);
+        assert_eq!(channel_type, vec![0x40, 0x10, 0x00]);
+
+        let tlvs = encode_open_channel_tlvs(Some(&shutdown_script), Some(&channel_type));
+        let mut expected = vec![0x00, 0x16];
+        expected.extend_from_slice(shutdown_script.as_bytes());
+        expected.extend_from_slice(&[0x01, 0x03, 0x40, 0x10, 0x00]);
+        assert_eq!(tlvs, expected);
+
+        let records = read_tlv_stream(&tlvs, &[0]).unwrap();
+        assert_eq!(find_record(&records, 0), Some(shutdown_script.as_bytes()));
+        assert_eq!(find_record(&records, 1), Some(channel_type.as_slice()));
+
+        // An empty script still gets a record, telling the peer we opt out.
+        assert_eq!(encode_open_channel_tlvs(Some(&ScriptBuf::new()), None), vec![0x00, 0x00]);
+        assert!(encode_open_channel_tlvs(None, None).is_empty());
+
+        // A peer that doesn't know type 0 must refuse the stream.
+        assert_eq!(read_tlv_stream(&tlvs, &[]), Err(WireError::UnknownEvenTlvType(0)));
+    })
+}
+
+#[test]
+fn test_33_build_open_channel() {
+    EXERCISE_33.run(|| {
+        let key_manager = |byte: u8| ChannelKeyManager {
+            funding_key: secp256k1_private_key(&[byte; 32]),
+            payment_base_key: secp256k1_private_key(&[byte + 1; 32]),
+            delayed_payment_base_key: secp256k1_private_key(&[byte + 2; 32]),
+            htlc_base_key: secp256k1_private_key(&[byte + 3; 32]),
+            revocation_base_key: secp256k1_private_key(&[byte + 4; 32]),
+            commitment_seed: [byte + 5; 32],
+        };
+        let alice = key_manager(0x10);
+        let bob = key_manager(0x20);
+
+        let params = ChannelParams {
+            funding_outpoint: OutPoint::null(),
+            to_self_delay: 144,
+            dust_limit: Amount::from_sat(546),
+            feerate_per_kw: 2_500,
+            htlc_fee_mode: HtlcFeeMode::ZeroFeeAnchors,
+            is_funder: true,
+            local_keys: alice.pubkeys(),
+            remote_keys: bob.pubkeys(),
+        };
+
+        let open_channel = build_open_channel(
+            Network::Regtest,
+            [0x42; 32],
+            &params,
+            Amount::from_sat(5_000_000),
+            MilliSat::from_msat(1_000_000),
+            720,
+            alice.per_commitment_point(0),
+        );
+
+        assert_eq!(open_channel.chain_hash, ChainHash::REGTEST);
+        assert_eq!(open_channel.to_self_delay, 720);
+        assert_eq!(open_channel.channel_reserve_satoshis, Amount::from_sat(50_000));
+        assert_eq!(open_channel.max_htlc_value_in_flight_msat, MilliSat::from_msat(5_000_000_000));
+        assert_eq!(open_channel.feerate_per_kw, 2_500);
+        let channel_type = open_channel.channel_type.as_ref().unwrap();
+        assert!(has_feature(channel_type, STATIC_REMOTE_KEY_REQUIRED));
+        assert!(has_feature(channel_type, ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED));
+
+        // Bob can build the funding script from Exercise 1 out of the message alone.
+        let funding_script = two_of_two_multisig_witness_script(
+            &PublicKey::new(open_channel.funding_pubkey),
+            &PublicKey::new(bob.pubkeys().funding_pubkey),
+        );
+        let expected_script = two_of_two_multisig_witness_script(
+            &PublicKey::new(alice.pubkeys().funding_pubkey),
+            &PublicKey::new(bob.pubkeys().funding_pubkey),
+        );
+        assert_eq!(funding_script, expected_script);
+
+        // 319 bytes of fixed fields after the type, then the channel_type record.
+        let bytes = WireMessage::OpenChannel(open_channel.clone()).encode();
+        assert_eq!(bytes.len(), 2 + 319 + 5);
+        assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::OpenChannel(open_channel.clone()));
+
+        // A small channel still keeps a reserve above dust.
+        let small = build_open_channel(
+            Network::Regtest,
+            [0x42; 32],
+            &ChannelParams { htlc_fee_mode: HtlcFeeMode::Legacy, ..params },
+            Amount::from_sat(20_000),
+            MilliSat::ZERO,
+            720,
+            alice.per_commitment_point(0),
+        );
+        assert_eq!(small.channel_reserve_satoshis, Amount::from_sat(546));
+        assert_eq!(small.channel_type, Some(encode_features(&[STATIC_REMOTE_KEY_REQUIRED])));
+    })
+}
+
+#[test]
+fn test_34_handle_channel_reestablish() {
+    EXERCISE_34.run(|| {
+        let keys = ChannelKeyManager {
+            funding_key: secp256k1_private_key(&[0x10; 32]),
+            payment_base_key: secp256k1_private_key(&[0x11; 32]),
+            delayed_payment_base_key: secp256k1_private_key(&[0x12; 32]),
+            htlc_base_key: secp256k1_private_key(&[0x13; 32]),
+            revocation_base_key: secp256k1_private_key(&[0x14; 32]),
+            commitment_seed: [0x15; 32],
+        };
+        let reestablish = |next_commitment_number: u64, next_revocation_number: u64, secret: [u8; 32]| ChannelReestablish {
+            channel_id: [0x42; 32],
+            next_commitment_number,
+            next_revocation_number,
+            your_last_per_commitment_secret: secret,
+            my_current_per_commitment_point: pubkey_from_private_key(&[0x20; 32]).inner,
+        };
+
+        // Straight after funding nothing has been revoked yet.
+        assert_eq!(handle_channel_reestablish(&keys, 0, 0, &reestablish(1, 0, [0; 32])), ReestablishAction::Proceed);
+
+        // Both sides are at commitment 5.
+        let secret_4 = keys.per_commitment_secret(4);
+        assert_eq!(handle_channel_reestablish(&keys, 5, 5, &reestablish(6, 5, secret_4)), ReestablishAction::Proceed);
+
+        // The peer never got our commitment_signed for its commitment 5.
+        assert_eq!(
+            handle_channel_reestablish(&keys, 5, 5, &reestablish(5, 5, secret_4)),
+            ReestablishAction::Retransmit { commitment_signed: true, revoke_and_ack: false }
+        );
+
+        // The peer never got the revoke_and_ack for our commitment 4.
+        let secret_3 = keys.per_commitment_secret(3);
+        assert_eq!(
+            handle_channel_reestablish(&keys, 5, 5, &reestablish(6, 4, secret_3)),
+            ReestablishAction::Retransmit { commitment_signed: false, revoke_and_ack: true }
+        );
+        assert_eq!(
+            handle_channel_reestablish(&keys, 5, 5, &reestablish(5, 4, secret_3)),
+            ReestablishAction::Retransmit { commitment_signed: true, revoke_and_ack: true }
+        );
+
+        // We restored a backup from commitment 3, but the peer holds our secret
+        // for commitment 4: our commitment is revoked and must not be broadcast.
+        assert_eq!(handle_channel_reestablish(&keys, 3, 3, &reestablish(6, 5, secret_4)), ReestablishAction::FallenBehind);
+
+        // Claiming we are behind without the secret to prove it.
+        assert_eq!(handle_channel_reestablish(&keys, 3, 3, &reestablish(6, 5, [0xab; 32])), ReestablishAction::FailChannel);
+
+        // A wrong secret, or a peer that is more than one step behind.
+        assert_eq!(handle_channel_reestablish(&keys, 5, 5, &reestablish(6, 5, secret_3)), ReestablishAction::FailChannel);
+        let secret_2 = keys.per_commitment_secret(2);
+        assert_eq!(handle_channel_reestablish(&keys, 5, 5, &reestablish(6, 3, secret_2)), ReestablishAction::FailChannel);
+        assert_eq!(handle_channel_reestablish(&keys, 5, 5, &reestablish(3, 5, secret_4)), ReestablishAction::FailChannel);
+
+        let msg = reestablish(6, 5, secret_4);
+        let bytes = WireMessage::ChannelReestablish(msg.clone()).encode();
+        assert_eq!(bytes.len(), 2 + 32 + 8 + 8 + 32 + 33);
+        assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ChannelReestablish(msg));
+    })
+}
+
+#[test]
+fn test_35_verify_channel_announcement() {
+    EXERCISE_35.run(|| {
+        let secp = Secp256k1::new();
+        let node_key_a = secp256k1_private_key(&[0x31; 32]);
+        let node_key_b = secp256k1_private_key(&[0x32; 32]);
+        let funding_key_a = secp256k1_private_key(&[0x01; 32]);
+        let funding_key_b = secp256k1_private_key(&[0x02; 32]);
+
+        // node_id_1 is whichever node id sorts first, and brings its funding key.
+        let mut nodes = [(node_key_a, funding_key_a), (node_key_b, funding_key_b)];
+        nodes.sort_by_key(|(node_key, _)| pubkey_from_secret(*node_key).serialize());
+        let [(node_key_1, funding_key_1), (node_key_2, funding_key_2)] = nodes;
+
+        let (funding_tx, _) = build_funding_transaction_with_script(
+            vec![],
+            &PublicKey::new(pubkey_from_secret(funding_key_a)),
+            &PublicKey::new(pubkey_from_secret(funding_key_b)),
+            Amount::from_sat(5_000_000),
+        );
+        let funding_output = funding_tx.output[0].clone();
+
+        let dummy_signature = secp.sign_ecdsa(&secp256k1::Message::from_digest([0; 32]), &node_key_1);
+        let mut announcement = ChannelAnnouncement {
+            node_signature_1: dummy_signature,
+            node_signature_2: dummy_signature,
+            bitcoin_signature_1: dummy_signature,
+            bitcoin_signature_2: dummy_signature,
+            features: vec![],
+            chain_hash: ChainHash::REGTEST,
+            short_channel_id: ShortChannelId::new(700_000, 42, 0),
+            node_id_1: pubkey_from_secret(node_key_1),
+            node_id_2: pubkey_from_secret(node_key_2),
+            bitcoin_key_1: pubkey_from_secret(funding_key_1),
+            bitcoin_key_2: pubkey_from_secret(funding_key_2),
+        };
+        let sign = |announcement: &mut ChannelAnnouncement| {
+            let message = secp256k1::Message::from_digest(announcement.signing_hash());
+            announcement.node_signature_1 = secp.sign_ecdsa(&message, &node_key_1);
+            announcement.node_signature_2 = secp.sign_ecdsa(&message, &node_key_2);
+            announcement.bitcoin_signature_1 = secp.sign_ecdsa(&message, &funding_key_1);
+            announcement.bitcoin_signature_2 = secp.sign_ecdsa(&message, &funding_key_2);
+        };
+        sign(&mut announcement);
+
+        assert_eq!(verify_channel_announcement(&announcement, &funding_output), Ok(()));
+        assert_eq!(announcement.short_channel_id.to_string(), "700000x42x0");
+
+        let bytes = WireMessage::ChannelAnnouncement(announcement.clone()).encode();
+        assert_eq!(bytes.len(), 2 + 4 * 64 + 2 + 32 + 8 + 4 * 33);
+        assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ChannelAnnouncement(announcement.clone()));
+
+        // Changing a field after signing breaks every signature.
+        let mut tampered = announcement.clone();
+        tampered.short_channel_id = ShortChannelId::new(700_000, 43, 0);
+        assert_eq!(
+            verify_channel_announcement(&tampered, &funding_output),
+            Err(GossipError::InvalidSignature("node_id_1"))
+        );
+
+        // Valid signatures, but not over the keys of this funding output.
+        let mut other = announcement.clone();
+        other.bitcoin_key_2 = pubkey_from_secret(secp256k1_private_key(&[0x03; 32]));
+        sign(&mut other);
+        assert_eq!(verify_channel_announcement(&other, &funding_output), Err(GossipError::InvalidSignature("bitcoin_key_2")));
+
+        let mut swapped = announcement.clone();
+        std::mem::swap(&mut swapped.node_id_1, &mut swapped.node_id_2);
+        assert_eq!(verify_channel_announcement(&swapped, &funding_output), Err(GossipError::NodeIdsNotOrdered));
+
+        let unrelated_output = bitcoin::TxOut { value: funding_output.value, script_pubkey: ScriptBuf::new_op_return([]) };
+        assert_eq!(verify_channel_announcement(&announcement, &unrelated_output), Err(GossipError::FundingScriptMismatch));
+    })
+}
+
+#[test]
+fn test_36_apply_channel_update() {
+    EXERCISE_36.run(|| {
+        let secp = Secp256k1::new();
+        let mut node_keys = [secp256k1_private_key(&[0x31; 32]), secp256k1_private_key(&[0x32; 32])];
+        node_keys.sort_by_key(|key| pubkey_from_secret(*key).serialize());
+        let [node_key_1, node_key_2] = node_keys;
+
+        let short_channel_id = ShortChannelId::new(700_000, 42, 0);
+        let mut channel = GraphChannel::new(
+            short_channel_id,
+            pubkey_from_secret(node_key_1),
+            pubkey_from_secret(node_key_2),
+            Amount::from_sat(1_000_000),
+        );
+
+        let update = |timestamp: u32, channel_flags: u8, fee_base_msat: u32, signer: &SecretKey| {
+            let mut update = ChannelUpdate {
+                signature: secp.sign_ecdsa(&secp256k1::Message::from_digest([0; 32]), signer),
+                chain_hash: ChainHash::REGTEST,
+                short_channel_id,
+                timestamp,
+                message_flags: MESSAGE_FLAGS_MUST_BE_ONE,
+                channel_flags,
+                cltv_expiry_delta: 40,
+                htlc_minimum_msat: MilliSat::from_msat(1_000),
+                fee_base_msat,
+                fee_proportional_millionths: 100,
+                htlc_maximum_msat: MilliSat::from_msat(500_000_000),
+            };
+            update.signature = secp.sign_ecdsa(&secp256k1::Message::from_digest(update.signing_hash()), signer);
+            update
+        };
+
+        // node_id_1 sets its policy for payments it forwards to node_id_2.
+        assert_eq!(apply_channel_update(&mut channel, &update(100, 0, 1_000, &node_key_1)), Ok(true));
+        let policy = channel.one_to_two.unwrap();
+        assert!(policy.enabled);
+        assert_eq!(policy.fee_base_msat, 1_000);
+        assert_eq!(policy.last_update, 100);
+        assert_eq!(channel.two_to_one, None);
+
+        // An older or repeated update changes nothing.
+        assert_eq!(apply_channel_update(&mut channel, &update(100, 0, 2_000, &node_key_1)), Ok(false));
+        assert_eq!(apply_channel_update(&mut channel, &update(99, 0, 2_000, &node_key_1)), Ok(false));
+        assert_eq!(channel.one_to_two.unwrap().fee_base_msat, 1_000);
+
+        // node_id_2 disables the other direction.
+        let disable = update(50, CHANNEL_FLAGS_DIRECTION | CHANNEL_FLAGS_DISABLE, 0, &node_key_2);
+        assert_eq!(apply_channel_update(&mut channel, &disable), Ok(true));
+        assert!(!channel.two_to_one.unwrap().enabled);
+
+        // Only the node at the start of a direction may update it.
+        assert_eq!(
+            apply_channel_update(&mut channel, &update(200, 0, 0, &node_key_2)),
+            Err(GossipError::InvalidSignature("node_id_1"))
+        );
+
+        let mut other_channel = update(200, 0, 0, &node_key_1);
+        other_channel.short_channel_id = ShortChannelId::new(700_000, 43, 0);
+        assert_eq!(
+            apply_channel_update(&mut channel, &other_channel),
+            Err(GossipError::UnknownChannel(other_channel.short_channel_id.0))
+        );
+
+        let mut too_large = update(200, 0, 0, &node_key_1);
+        too_large.htlc_maximum_msat = MilliSat::from_msat(1_000_000_001);
+        too_large.signature = secp.sign_ecdsa(&secp256k1::Message::from_digest(too_large.signing_hash()), &node_key_1);
+        assert_eq!(
+            apply_channel_update(&mut channel, &too_large),
+            Err(GossipError::HtlcMaximumAboveCapacity { htlc_maximum_msat: 1_000_000_001, capacity_msat: 1_000_000_000 })
+        );
+
+        let bytes = WireMessage::ChannelUpdate(disable.clone()).encode();
+        assert_eq!(bytes.len(), 2 + 64 + 32 + 8 + 4 + 1 + 1 + 2 + 8 + 4 + 4 + 8);
+        assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ChannelUpdate(disable));
+    })
+}
+
+fn routing_policy(fee_base_msat: u32, fee_proportional_millionths: u32, cltv_expiry_delta: u16) -> ChannelPolicy {
+    ChannelPolicy {
+        enabled: true,
+        cltv_expiry_delta,
+        htlc_minimum_msat: MilliSat::from_msat(1_000),
+        htlc_maximum_msat: MilliSat::from_msat(100_000_000),
+        fee_base_msat,
+        fee_proportional_millionths,
+        last_update: 0,
+    }
+}
+
+/// Add a channel with `forward` as the policy from `from` to `to` and
+/// `backward` as the policy the other way.
+fn add_routing_channel(
+    graph: &mut NetworkGraph,
+    short_channel_id: ShortChannelId,
+    from: secp256k1PublicKey,
+    to: secp256k1PublicKey,
+    forward: ChannelPolicy,
+    backward: ChannelPolicy,
+) {
+    let (node_one, node_two, one_to_two, two_to_one) =
+        if from.serialize() < to.serialize() { (from, to, forward, backward) } else { (to, from, backward, forward) };
+    let mut channel = GraphChannel::new(short_channel_id, node_one, node_two, Amount::from_sat(1_000_000));
+    channel.one_to_two = Some(one_to_two);
+    channel.two_to_one = Some(two_to_one);
+    graph.add_channel_unchecked(channel);
+}
+
+#[test]
+fn test_37_find_route() {
+    EXERCISE_37.run(|| {
+        let node = |byte: u8| pubkey_from_secret(secp256k1_private_key(&[byte; 32]));
+        let (sender, a, b, c, payee) = (node(0x40), node(0x41), node(0x42), node(0x43), node(0x44));
+        let scid = |n: u32| ShortChannelId::new(700_000, n, 0);
+
+        //   sender -- a ---------- payee     a: 5000 msat + 0.1%, 30 blocks
+        //      \                  /
+        //       b ------------- c            b and c: 1000 msat + 0.01%, 40 blocks each
+        let mut graph = NetworkGraph::new(ChainHash::REGTEST);
+        let expensive_own_channel = routing_policy(99_999, 0, 500);
+        add_routing_channel(&mut graph, scid(1), sender, a, expensive_own_channel, routing_policy(0, 0, 40));
+        add_routing_channel(&mut graph, scid(2), a, payee, routing_policy(5_000, 1_000, 30), routing_policy(0, 0, 40));
+        add_routing_channel(&mut graph, scid(3), sender, b, expensive_own_channel, routing_policy(0, 0, 40));
+        add_routing_channel(&mut graph, scid(4), b, c, routing_policy(1_000, 100, 40), routing_policy(0, 0, 40));
+        add_routing_channel(&mut graph, scid(5), c, payee, routing_policy(1_000, 100, 40), routing_policy(0, 0, 40));
+
+        let amount = MilliSat::from_msat(10_000_000);
+
+        // Through a costs 15000 msat; through b and c only 2000 + 2000, with b
+        // charging on the 10_002_000 msat it has to forward to c.
+        let route = find_route(&graph, &sender, &payee, amount, 1_000).unwrap();
+        assert_eq!(route.hops.iter().map(|hop| hop.node_id).collect::<Vec<_>>(), vec![b, c, payee]);
+        assert_eq!(route.hops.iter().map(|hop| hop.short_channel_id).collect::<Vec<_>>(), vec![scid(3), scid(4), scid(5)]);
+        assert_eq!(
+            route.hops.iter().map(|hop| hop.amount_msat.to_msat()).collect::<Vec<_>>(),
+            vec![10_004_000, 10_002_000, 10_000_000]
+        );
+        assert_eq!(route.hops.iter().map(|hop| hop.cltv_expiry_delta).collect::<Vec<_>>(), vec![80, 40, 0]);
+        assert_eq!(route.total_fees(), MilliSat::from_msat(4_000));
+        assert_eq!(route.total_cltv_expiry_delta(), 80);
+
+        // With only 60 blocks to spare, the short path through a is the only one.
+        let route = find_route(&graph, &sender, &payee, amount, 60).unwrap();
+        assert_eq!(route.hops.iter().map(|hop| hop.node_id).collect::<Vec<_>>(), vec![a, payee]);
+        assert_eq!(route.total_fees(), MilliSat::from_msat(15_000));
+        assert_eq!(find_route(&graph, &sender, &payee, amount, 20), None);
+
+        // b can't forward 10_002_000 msat, but smaller payments still fit.
+        let mut limited = graph.clone();
+        let channel = limited.channels.get_mut(&scid(4)).unwrap();
+        let direction = if channel.node_one == b { 0 } else { 1 };
+        channel.policy_mut(direction).as_mut().unwrap().htlc_maximum_msat = MilliSat::from_msat(5_000_000);
+        assert_eq!(find_route(&limited, &sender, &payee, amount, 1_000).unwrap().hops[0].node_id, a);
+        assert_eq!(find_route(&limited, &sender, &payee, MilliSat::from_msat(1_000_000), 1_000).unwrap().hops[0].node_id, b);
+
+        // Too small for a's htlc_minimum_msat as well as b's.
+        assert_eq!(find_route(&graph, &sender, &payee, MilliSat::from_msat(999), 1_000), None);
+
+        // A disabled channel is skipped.
+        let mut disabled = graph.clone();
+        let channel = disabled.channels.get_mut(&scid(5)).unwrap();
+        let direction = if channel.node_one == c { 0 } else { 1 };
+        channel.policy_mut(direction).as_mut().unwrap().enabled = false;
+        assert_eq!(find_route(&disabled, &sender, &payee, amount, 1_000).unwrap().hops[0].node_id, a);
+
+        assert_eq!(find_route(&graph, &sender, &node(0x45), amount, 1_000), None);
+    })
+}
+
+/// The BOLT 11 spec's invoice for "1 cup coffee": 2500u, so 250,000 sats.
+const COFFEE_INVOICE: &str = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+
+#[test]
+fn test_38_htlc_payment_hash() {
+    EXERCISE_38.run(|| {
+        let invoice = decode_invoice(COFFEE_INVOICE).unwrap();
+        let payment_hash = htlc_payment_hash(&invoice);
+        let expected_hash: [u8; 20] = Ripemd160::digest(invoice.payment_hash.0).into();
+        assert_eq!(payment_hash, PaymentHash160(expected_hash));
+
+        // Whoever holds the preimage behind an invoice can claim the HTLC.
+        let preimage = PaymentPreimage([0x42; 32]);
+        let invoice_for_preimage = Invoice { payment_hash: preimage.hash(), ..invoice.clone() };
+        assert_eq!(htlc_payment_hash(&invoice_for_preimage), preimage.hash160());
+
+        // Offer an HTLC paying the invoice on our commitment.
+        let txin = dummy_funding_txin();
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let remote_pubkey = pubkey_from_private_key(&[0x05; 32]);
+
+        let htlc = Htlc {
+            offered: true,
+            amount_msat: invoice.amount.unwrap(),
+            payment_hash,
+            cltv_expiry: 500 + invoice.min_final_cltv_expiry_delta as u32,
+        };
+        let transaction = build_htlc_commitment_transaction(
+            txin,
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            &[htlc],
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(1_000_000),
+            true,
+            Amount::from_sat(546),
+            253,
+            0,
+            0,
+        )
+        .unwrap();
+        print_transaction(&transaction);
+
+        let htlc_script =
+            build_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &payment_hash);
+        assert_eq!(embedded_payment_hash(&htlc_script), expected_hash);
+        let htlc_output = transaction.output.iter().find(|output| output.script_pubkey == htlc_script.to_p2wsh()).unwrap();
+        assert_eq!(htlc_output.value, Amount::from_sat(250_000));
+    })
+}
+
+#[test]
+fn test_39_create_invoice() {
+    EXERCISE_39.run(|| {
+        // The key and fields behind the spec's "1 cup coffee" invoice.
+        let payee_key = secp256k1_private_key(
+            &hex::decode("e126f68f7eafcc8b74f54d269fe206be715000f94dac067d1c04a8ca3b2db734").unwrap().try_into().unwrap(),
+        );
+        let payment_hash = PaymentHash::from_slice(
+            &hex::decode("0001020304050607080900010203040506070809000102030405060708090102").unwrap(),
+        )
+        .unwrap();
+
+        let invoice = create_invoice(
+            &payee_key,
+            Network::Bitcoin,
+            Some(MilliSat::from_msat(250_000_000)),
+            &payment_hash,
+            &PaymentSecret([0x11; 32]),
+            "1 cup coffee",
+            60,
+            1496314658,
+        )
+        .unwrap();
+        assert_eq!(invoice, COFFEE_INVOICE);
+
+        // Signed with whichever recovery id the signature needs, the decoder
+        // recovers our key.
+        let preimage = PaymentPreimage([0x42; 32]);
+        let payment_hash = preimage.hash();
+        for timestamp in 1_700_000_000..1_700_000_008 {
+            let encoded = create_invoice(
+                &payee_key,
+                Network::Regtest,
+                None,
+                &payment_hash,
+                &PaymentSecret([0x22; 32]),
+                "donation",
+                DEFAULT_EXPIRY,
+                timestamp,
+            )
+            .unwrap();
+            assert!(encoded.starts_with("lnbcrt1"));
+
+            let decoded = decode_invoice(&encoded).unwrap();
+            assert_eq!(decoded.payee_pubkey, pubkey_from_secret(payee_key));
+            assert_eq!(decoded.timestamp, timestamp);
+            assert_eq!(decoded.amount, None);
+            assert_eq!(decoded.payment_secret, Some(PaymentSecret([0x22; 32])));
+            assert_eq!(decoded.description.as_deref(), Some("donation"));
+            assert_eq!(decoded.expiry, DEFAULT_EXPIRY);
+            assert_eq!(htlc_payment_hash(&decoded), preimage.hash160());
+        }
+
+        assert_eq!(
+            create_invoice(
+                &payee_key,
+                Network::Regtest,
+                None,
+                &payment_hash,
+                &PaymentSecret([0x22; 32]),
+                &"a".repeat(640),
+                60,
+                0,
+            ),
+            Err(InvoiceError::FieldTooLong { tag: DESCRIPTION_TAG, words: 1024 })
+        );
+    })
+}
+
+#[test]
+fn test_40_keysend() {
+    EXERCISE_40.run(|| {
+        let node_key = |byte: u8| secp256k1_private_key(&[byte; 32]);
+        let (bob, carol) = (node_key(0x42), node_key(0x43));
+        let route = Route {
+            hops: vec![
+                RouteHop {
+                    node_id: pubkey_from_secret(bob),
+                    short_channel_id: ShortChannelId::new(700_000, 1, 0),
+                    amount_msat: MilliSat(101_000),
+                    cltv_expiry_delta: 40,
+                },
+                RouteHop {
+                    node_id: pubkey_from_secret(carol),
+                    short_channel_id: ShortChannelId::new(700_000, 2, 0),
+                    amount_msat: MilliSat(100_000),
+                    cltv_expiry_delta: 0,
+                },
+            ],
+        };
+
+        let preimage = PaymentPreimage::random();
+        let (onion, payment_hash) = build_keysend_onion(&route, 800_000, &preimage, &node_key(0x01)).unwrap();
+        assert_eq!(payment_hash, PaymentHash(Sha256::digest(preimage.0).into()));
+        assert_eq!((onion.amount_msat, onion.cltv_expiry), (MilliSat(101_000), 800_040));
+
+        // Bob forwards without learning the preimage.
+        let PeeledOnion::Forward { payload, next_packet } = peel_onion(&onion.packet, &bob, &payment_hash.0).unwrap() else {
+            panic!("Bob should forward");
+        };
+        let payload = HopPayload::decode(&payload).unwrap();
+        assert_eq!(payload.keysend_preimage, None);
+        assert_eq!(payload.short_channel_id, Some(ShortChannelId::new(700_000, 2, 0)));
+
+        // Carol finds it in her payload and can claim the HTLC with it.
+        let PeeledOnion::Receive { payload } = peel_onion(&next_packet, &carol, &payment_hash.0).unwrap() else {
+            panic!("Carol should receive");
+        };
+        let payload = HopPayload::decode(&payload).unwrap();
+        assert_eq!((payload.amt_to_forward, payload.outgoing_cltv_value), (MilliSat(100_000), 800_000));
+        assert_eq!(payload.payment_data, None);
+        let settled = settle_keysend(&payload, &payment_hash).unwrap();
+        assert_eq!(settled, preimage);
+        assert_eq!(settled.hash160(), payment_hash.hash160());
+
+        // A preimage for some other HTLC is no use to her.
+        assert_eq!(settle_keysend(&payload, &PaymentHash([0x42; 32])), None);
+
+        // Nor is an ordinary payment, which carries no preimage.
+        let ordinary = HopPayload { keysend_preimage: None, ..payload };
+        assert_eq!(settle_keysend(&ordinary, &payment_hash), None);
+    })
+}
+
+#[test]
+fn test_41_next_htlc_state() {
+    EXERCISE_41.run(|| {
+        use CommitmentEvent::*;
+        use HtlcState::*;
+
+        // Each message moves the sender's view of the HTLC and the receiver's.
+        // Sending revoke_and_ack isn't an event: it always answers a
+        // commitment_signed.
+        let alice_signs = (Some(SendCommitmentSigned), Some(ReceiveCommitmentSigned));
+        let bob_revokes = (Some(ReceiveRevokeAndAck), None);
+        let bob_signs = (Some(ReceiveCommitmentSigned), Some(SendCommitmentSigned));
+        let alice_revokes = (None, Some(ReceiveRevokeAndAck));
+
+        type Step = ((Option<CommitmentEvent>, Option<CommitmentEvent>), HtlcState, HtlcState);
+        let run = |mut alice: HtlcState, mut bob: HtlcState, steps: &[Step]| {
+            for &((alice_event, bob_event), alice_state, bob_state) in steps {
+                alice = alice_event.map_or(alice, |event| next_htlc_state(alice, event));
+                bob = bob_event.map_or(bob, |event| next_htlc_state(bob, event));
+                assert_eq!((alice, bob), (alice_state, bob_state));
+
+                // Both sides always agree on what each commitment holds.
+                assert_eq!(alice.in_local_commitment(), bob.in_remote_commitment());
+                assert_eq!(alice.in_remote_commitment(), bob.in_local_commitment());
+            }
+        };
+
+        // Alice offers an HTLC and signs first.
+        run(
+            LocalAddProposed,
+            RemoteAddProposed,
+            &[
+                (alice_signs, LocalAddSigned, RemoteAddAcked),
+                (bob_revokes, LocalAddAcked, RemoteAddAcked),
+                (bob_signs, Committed, RemoteAddSigned),
+                (alice_revokes, Committed, Committed),
+            ],
+        );
+
+        // Bob fulfills it and signs first.
+        run(
+            RemoteFulfillProposed,
+            LocalFulfillProposed,
+            &[
+                (bob_signs, RemoteFulfillAcked, LocalFulfillSigned),
+                (alice_revokes, RemoteFulfillAcked, LocalFulfillAcked),
+                (alice_signs, RemoteFulfillSigned, Fulfilled),
+                (bob_revokes, Fulfilled, Fulfilled),
+            ],
+        );
+
+        // Signing an update before it is in the right commitment does nothing.
+        assert_eq!(next_htlc_state(LocalAddProposed, ReceiveCommitmentSigned), LocalAddProposed);
+        assert_eq!(next_htlc_state(LocalAddSigned, ReceiveCommitmentSigned), LocalAddSigned);
+        assert_eq!(next_htlc_state(RemoteAddProposed, SendCommitmentSigned), RemoteAddProposed);
+        for event in [SendCommitmentSigned, ReceiveCommitmentSigned, ReceiveRevokeAndAck] {
+            assert_eq!(next_htlc_state(Committed, event), Committed);
+            assert_eq!(next_htlc_state(Fulfilled, event), Fulfilled);
+        }
+    })
+}
+
+#[test]
+fn test_42_handle_revoke_and_ack() {
+    EXERCISE_42.run(|| {
+        let bob = ChannelKeyManager {
+            funding_key: secp256k1_private_key(&[0x20; 32]),
+            payment_base_key: secp256k1_private_key(&[0x21; 32]),
+            delayed_payment_base_key: secp256k1_private_key(&[0x22; 32]),
+            htlc_base_key: secp256k1_private_key(&[0x23; 32]),
+            revocation_base_key: secp256k1_private_key(&[0x24; 32]),
+            commitment_seed: [0x25; 32],
+        };
+        let revoke = |n: u64| RevokeAndAck {
+            channel_id: [0x43; 32],
+            per_commitment_secret: bob.per_commitment_secret(n),
+            next_per_commitment_point: bob.per_commitment_point(n + 2),
+        };
+
+        // Alice has Bob's first two points from accept_channel and channel_ready.
+        let mut counterparty = CounterpartyCommitments {
+            commitment_number: 0,
+            per_commitment_point: bob.per_commitment_point(0),
+            next_per_commitment_point: bob.per_commitment_point(1),
+            revoked_secrets: ShachainStore::new(),
+        };
+        for n in 0..5 {
+            handle_revoke_and_ack(&mut counterparty, &revoke(n)).unwrap();
+            assert_eq!(counterparty.commitment_number, n + 1);
+            assert_eq!(counterparty.per_commitment_point, bob.per_commitment_point(n + 1));
+            assert_eq!(counterparty.next_per_commitment_point, bob.per_commitment_point(n + 2));
+        }
+        for n in 0..5 {
+            assert_eq!(counterparty.revoked_secrets.derive(MAX_SHACHAIN_INDEX - n), Some(bob.per_commitment_secret(n)));
+        }
+        assert_eq!(counterparty.revoked_secrets.derive(MAX_SHACHAIN_INDEX - 5), None);
+
+        // With a revealed secret, Alice can spend the revocation path of Bob's
+        // old commitment.
+        let alice_revocation_base_key = secp256k1_private_key(&[0x14; 32]);
+        let secret = SecretKey::from_slice(&counterparty.revoked_secrets.derive(MAX_SHACHAIN_INDEX - 2).unwrap()).unwrap();
+        assert_eq!(
+            pubkey_from_secret(generate_revocation_privkey(secret, alice_revocation_base_key)),
+            generate_revocation_pubkey(pubkey_from_secret(alice_revocation_base_key), bob.per_commitment_point(2))
+        );
+
+        // Revealing an old secret again, or one for another point, revokes
+        // nothing, and Alice stays where she was.
+        let before = counterparty.clone();
+        assert_eq!(
+            handle_revoke_and_ack(&mut counterparty, &revoke(4)),
+            Err(ChannelError::InvalidRevocationSecret { commitment_number: 5 })
+        );
+        let not_a_key = RevokeAndAck { per_commitment_secret: [0xff; 32], ..revoke(5) };
+        assert_eq!(
+            handle_revoke_and_ack(&mut counterparty, &not_a_key),
+            Err(ChannelError::InvalidRevocationSecret { commitment_number: 5 })
+        );
+        assert_eq!(counterparty, before);
+
+        // A secret from another seed matches its own point, but not the secrets
+        // Bob revealed before.
+        let mallory = ChannelKeyManager { commitment_seed: [0x26; 32], ..bob.clone() };
+        counterparty.per_commitment_point = mallory.per_commitment_point(5);
+        let forged = RevokeAndAck { per_commitment_secret: mallory.per_commitment_secret(5), ..revoke(5) };
+        assert_eq!(
+            handle_revoke_and_ack(&mut counterparty, &forged),
+            Err(ChannelError::InconsistentSecret { index: MAX_SHACHAIN_INDEX - 5 })
+        );
+        assert_eq!(counterparty.commitment_number, 5);
+    })
+}
+
+/// A commitment Bob revoked, and Alice's signed penalty transaction sweeping
+/// its `to_local` output, as in Exercise 26.
+fn revoked_commitment_with_penalty() -> (Transaction, Transaction, TxOut) {
+    let txin = dummy_funding_txin();
+
+    let per_commitment_secret = secp256k1_private_key(&[0x05; 32]);
+    let revocation_privkey = generate_revocation_privkey(per_commitment_secret, secp256k1_private_key(&[0x06; 32]));
+    let revocation_pubkey = RevocationPubkey(PublicKey::new(revocation_privkey.public_key(&Secp256k1::new())));
+    let bob_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x07; 32]));
+    let alice_pubkey = pubkey_from_private_key(&[0x08; 32]);
+
+    let revoked_commitment = build_commitment_transaction(
+        txin,
+        &revocation_pubkey,
+        &bob_delayed_pubkey,
+        alice_pubkey,
+        144,
+        Amount::from_sat(3_998_500),
+        Amount::from_sat(1_000_500),
+        true,
+        253,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let to_local_script = to_local(&revocation_pubkey, &bob_delayed_pubkey, 144).unwrap();
+    let to_local_index =
+        revoked_commitment.output.iter().position(|output| output.script_pubkey == to_local_script.to_p2wsh()).unwrap();
+    let to_local_output = revoked_commitment.output[to_local_index].clone();
+
+    let penalty_tx = build_penalty_transaction(
+        OutPoint::new(revoked_commitment.compute_txid(), to_local_index as u32),
+        to_local_output.value,
+        &revocation_privkey,
+        &to_local_script,
+        p2wpkh_output_script(alice_pubkey),
+        253,
+    )
+    .unwrap();
+    (revoked_commitment, penalty_tx, to_local_output)
+}
+
+#[test]
+fn test_43_create_justice_blob() {
+    EXERCISE_43.run(|| {
+        let (revoked_commitment, penalty_tx, _) = revoked_commitment_with_penalty();
+        let txid = revoked_commitment.compute_txid();
+
+        let blob = create_justice_blob(&txid, &penalty_tx);
+        assert_eq!(blob.hint, BreachHint::from_txid(&txid));
+
+        // the hint is not the txid, and the penalty can't be read off the blob
+        assert_ne!(blob.hint.0, txid.to_byte_array()[..16]);
+        let penalty_bytes = serialize(&penalty_tx);
+        assert_ne!(blob.encrypted_justice_tx[12..12 + penalty_bytes.len()], penalty_bytes[..]);
+
+        assert_eq!(decrypt_blob(&breach_key(&txid), &blob.encrypted_justice_tx), Ok(penalty_bytes));
+
+        // uploading the same penalty twice gives the tower two different blobs
+        assert_ne!(create_justice_blob(&txid, &penalty_tx), blob);
+    })
+}
+
+#[test]
+fn test_44_open_justice_blob() {
+    EXERCISE_44.run(|| {
+        let (revoked_commitment, penalty_tx, to_local_output) = revoked_commitment_with_penalty();
+        let blob = create_justice_blob(&revoked_commitment.compute_txid(), &penalty_tx);
+
+        let justice_tx = open_justice_blob(&blob, &revoked_commitment).unwrap();
+        assert_eq!(justice_tx, penalty_tx);
+        assert!(justice_tx.verify(|_| Some(to_local_output.clone())).is_ok());
+
+        // Any other transaction has another key, so the blob stays closed.
+        let (mut other_commitment, _, _) = revoked_commitment_with_penalty();
+        other_commitment.lock_time = bitcoin::absolute::LockTime::from_consensus(0x2000_0001);
+        assert_eq!(open_justice_blob(&blob, &other_commitment), Err(WatchtowerError::DecryptionFailed));
+
+        // A blob that opens under the breach's key still has to spend the breach.
+        let key = breach_key(&other_commitment.compute_txid());
+        let unrelated = JusticeBlob {
+            hint: BreachHint::from_txid(&other_commitment.compute_txid()),
+            encrypted_justice_tx: encrypt_blob(&key, &serialize(&penalty_tx)),
+        };
+        assert_eq!(open_justice_blob(&unrelated, &other_commitment), Err(WatchtowerError::NotABreachSpend));
+
+        let garbage = JusticeBlob { encrypted_justice_tx: encrypt_blob(&key, b"not a transaction"), ..unrelated.clone() };
+        assert_eq!(open_justice_blob(&garbage, &other_commitment), Err(WatchtowerError::InvalidJusticeTransaction));
+
+        let truncated = JusticeBlob { encrypted_justice_tx: vec![0; 27], ..unrelated };
+        assert_eq!(open_justice_blob(&truncated, &other_commitment), Err(WatchtowerError::BlobTooShort));
+    })
+}
+
+#[test]
+fn test_45_scan_block_for_channel_spends() {
+    EXERCISE_45.run(|| {
+        let (commitment, penalty_tx, _) = revoked_commitment_with_penalty();
+        let funding_outpoint = commitment.input[0].previous_output;
+
+        let mut block = bitcoin::constants::genesis_block(Network::Regtest);
+        assert_eq!(scan_block_for_channel_spends(&block, funding_outpoint), None);
+
+        // the penalty spends the commitment, not the funding output
+        block.txdata.push(penalty_tx);
+        assert_eq!(scan_block_for_channel_spends(&block, funding_outpoint), None);
+
+        // nor does a transaction spending another output of the funding transaction
+        let mut other_output = commitment.clone();
+        other_output.input[0].previous_output.vout = 0;
+        block.txdata.push(other_output);
+        assert_eq!(scan_block_for_channel_spends(&block, funding_outpoint), None);
+
+        block.txdata.push(commitment.clone());
+        assert_eq!(scan_block_for_channel_spends(&block, funding_outpoint), Some(commitment));
+    })
+}
+
+#[test]
+fn test_46_classify_funding_spend() {
+    EXERCISE_46.run(|| {
+        let (commitment, _, _) = revoked_commitment_with_penalty();
+
+        // BOLT 3 appendix C payment basepoints
+        let opener_payment_basepoint: PublicKey =
+            "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa".parse().unwrap();
+        let accepter_payment_basepoint: PublicKey =
+            "032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991".parse().unwrap();
+        let obscure_factor = commitment_number_obscure_factor(&opener_payment_basepoint, &accepter_payment_basepoint);
+        let commitment_number = |n: u64| {
+            obscure_commitment_transaction(commitment.clone(), &opener_payment_basepoint, &accepter_payment_basepoint, n)
+        };
+
+        // Bob has revoked his commitments 0 to 4.
+        let counterparty = CounterpartyCommitments {
+            commitment_number: 5,
+            per_commitment_point: pubkey_from_secret(secp256k1_private_key(&[0x05; 32])),
+            next_per_commitment_point: pubkey_from_secret(secp256k1_private_key(&[0x06; 32])),
+            revoked_secrets: ShachainStore::new(),
+        };
+
+        assert_eq!(
+            classify_funding_spend(&commitment_number(0), obscure_factor, &counterparty),
+            FundingSpend::RevokedCommitment { commitment_number: 0 }
+        );
+        assert_eq!(
+            classify_funding_spend(&commitment_number(4), obscure_factor, &counterparty),
+            FundingSpend::RevokedCommitment { commitment_number: 4 }
+        );
+        assert_eq!(
+            classify_funding_spend(&commitment_number(5), obscure_factor, &counterparty),
+            FundingSpend::LatestCommitment { commitment_number: 5 }
+        );
+
+        let closing_tx = build_closing_transaction(
+            commitment.input[0].clone(),
+            p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32])),
+            p2wpkh_output_script(pubkey_from_private_key(&[0x02; 32])),
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(2_000_000),
+            true,
+            Amount::from_sat(546),
+            253,
+        )
+        .unwrap();
+        assert_eq!(classify_funding_spend(&closing_tx, obscure_factor, &counterparty), FundingSpend::CooperativeClose);
+    })
+}
+
+/// A regtest block with `num_txs` different transactions and a merkle root
+/// that commits to them.
+fn block_with_txs(num_txs: u32) -> Block {
+    let (commitment, _, _) = revoked_commitment_with_penalty();
+    let mut block = bitcoin::constants::genesis_block(Network::Regtest);
+    block.txdata = (0..num_txs)
+        .map(|n| Transaction { lock_time: bitcoin::absolute::LockTime::from_consensus(n + 1), ..commitment.clone() })
+        .collect();
+    block.header.merkle_root = block.compute_merkle_root().unwrap();
+    block
+}
+
+#[test]
+fn test_47_verify_merkle_branch() {
+    EXERCISE_47.run(|| {
+        // an odd number of transactions, so some levels pair a node with itself
+        let block = block_with_txs(5);
+        let merkle_root = block.header.merkle_root;
+
+        for tx in &block.txdata {
+            let branch = merkle_branch(&block, &tx.compute_txid()).unwrap();
+            assert_eq!(branch.hashes.len(), 3);
+            assert!(verify_merkle_branch(&branch, &merkle_root));
+        }
+
+        let branch = merkle_branch(&block, &block.txdata[2].compute_txid()).unwrap();
+        assert!(!verify_merkle_branch(&MerkleBranch { index: 3, ..branch.clone() }, &merkle_root));
+        let other_txid = block.txdata[1].compute_txid();
+        assert!(!verify_merkle_branch(&MerkleBranch { txid: other_txid, ..branch.clone() }, &merkle_root));
+        assert!(!verify_merkle_branch(&branch, &block_with_txs(4).header.merkle_root));
+
+        // in a block with a single transaction, the root is the txid
+        let block = block_with_txs(1);
+        let branch = merkle_branch(&block, &block.txdata[0].compute_txid()).unwrap();
+        assert!(branch.hashes.is_empty());
+        assert!(verify_merkle_branch(&branch, &block.header.merkle_root));
+    })
+}
+
+#[test]
+fn test_48_funding_confirmations() {
+    EXERCISE_48.run(|| {
+        let mut block = block_with_txs(6);
+        let funding_txid = block.txdata[3].compute_txid();
+
+        assert_eq!(funding_confirmations(&block, 100, &funding_txid, 100), Some(1));
+        assert_eq!(funding_confirmations(&block, 100, &funding_txid, 105), Some(6));
+        // the best chain doesn't reach our block yet
+        assert_eq!(funding_confirmations(&block, 100, &funding_txid, 99), Some(0));
+
+        let (unrelated, _, _) = revoked_commitment_with_penalty();
+        assert_eq!(funding_confirmations(&block, 100, &unrelated.compute_txid(), 105), None);
+
+        // a header that doesn't commit to the transactions proves nothing
+        block.header.merkle_root = block_with_txs(5).header.merkle_root;
+        assert_eq!(funding_confirmations(&block, 100, &funding_txid, 105), None);
+    })
+}
+
+#[test]
+fn test_49_bump_zero_fee_htlc_transaction() {
+    EXERCISE_49.run(|| {
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let remote_htlc_privkey = secp256k1_private_key(&[0x02; 32]);
+        let remote_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let local_htlc_privkey = secp256k1_private_key(&[0x03; 32]);
+        let local_htlc_pubkey = HtlcPubkey(pubkey_from_private_key(&[0x03; 32]));
+        let delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+        let wallet_privkey = secp256k1_private_key(&[0x05; 32]);
+        let wallet_pubkey = pubkey_from_private_key(&[0x05; 32]);
+
+        let mode = HtlcFeeMode::ZeroFeeAnchors;
+        let offered_htlc_script =
+            build_anchor_htlc_offerer_witness_script(&revocation_pubkey, &remote_htlc_pubkey, &local_htlc_pubkey, &HASH160_DUMMY);
+        let htlc_amount = Amount::from_sat(405_000);
+        let htlc_output = build_output(htlc_amount, offered_htlc_script.to_p2wsh());
+
+        let htlc_txin = TxIn {
+            previous_output: OutPoint::new(OutPoint::null().txid, 1),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        // the HTLC transaction pays no fee at all
+        let unsigned_tx = build_htlc_timeout_transaction(
+            htlc_txin,
+            &revocation_pubkey,
+            &delayed_pubkey,
+            144,
+            300,
+            htlc_amount - mode.htlc_timeout_fee(10_000),
+            mode,
+        )
+        .unwrap();
+        assert_eq!(unsigned_tx.output[0].value, htlc_amount);
+
+        let remote_signature = generate_p2wsh_signature(
+            unsigned_tx.clone(),
+            0,
+            &offered_htlc_script,
+            htlc_amount.to_sat(),
+            mode.counterparty_sighash_type(),
+            remote_htlc_privkey,
+        );
+
+        let fee_input = OutPoint::new(OutPoint::null().txid, 7);
+        let fee_input_value = Amount::from_sat(20_000);
+        let fee_output = build_output(fee_input_value, p2wpkh_output_script(wallet_pubkey));
+        let change_script = p2wpkh_output_script(wallet_pubkey);
+
+        let bumped_tx = bump_zero_fee_htlc_transaction(
+            unsigned_tx.clone(),
+            HTLC_TIMEOUT_ANCHOR_TX_WEIGHT,
+            fee_input,
+            fee_input_value,
+            change_script.clone(),
+            10_000,
+        )
+        .unwrap();
+        assert_eq!(bumped_tx.input.len(), 2);
+        assert_eq!(bumped_tx.input[0], unsigned_tx.input[0]);
+        assert_eq!(bumped_tx.input[1].previous_output, fee_input);
+        assert_eq!(bumped_tx.output.len(), 2);
+        assert_eq!(bumped_tx.output[0], unsigned_tx.output[0]);
+        assert_eq!(bumped_tx.output[1].script_pubkey, change_script);
+        // (666 + 273 + 124) weight at 10_000 sat/kw
+        assert_eq!(bumped_tx.output[1].value, Amount::from_sat(20_000 - 10_630));
+
+        // the counterparty's signature survives the extra input and output
+        let mut signed_tx = finalize_htlc_timeout_transaction(
+            bumped_tx.clone(),
+            &offered_htlc_script,
+            htlc_amount,
+            &remote_signature,
+            &local_htlc_privkey,
+            mode.anchors(),
+        );
+        sign_p2wpkh_input(&mut signed_tx, 1, fee_input_value, &wallet_privkey);
+        print_transaction(&signed_tx);
+
+        assert!(signed_tx.weight().to_wu() <= HTLC_TIMEOUT_ANCHOR_TX_WEIGHT + 273 + 124);
+        let prevout = |outpoint: &OutPoint| {
+            if *outpoint == fee_input {
+                Some(fee_output.clone())
+            } else {
+                Some(htlc_output.clone())
+            }
+        };
+        assert!(signed_tx.verify(prevout).is_ok());
+
+        assert_eq!(
+            bump_zero_fee_htlc_transaction(
+                unsigned_tx,
+                HTLC_TIMEOUT_ANCHOR_TX_WEIGHT,
+                fee_input,
+                Amount::from_sat(10_000),
+                change_script,
+                10_000
+            ),
+            Err(ExerciseError::InsufficientFunds { needed: 10_630, available: 10_000 })
+        );
+    })
+}
+
+#[test]
+fn test_50_build_anchor_cpfp_transaction() {
+    EXERCISE_50.run(|| {
+        let funding_privkey = secp256k1_private_key(&[0x01; 32]);
+        let funding_pubkey = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let wallet_privkey = secp256k1_private_key(&[0x02; 32]);
+        let wallet_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        let anchor_script = build_anchor_output_script(&funding_pubkey);
+        let anchor_outpoint = OutPoint::new(OutPoint::null().txid, 0);
+        let anchor_output = build_output(ANCHOR_OUTPUT_VALUE, anchor_script.to_p2wsh());
+        let wallet_utxo =
+            ChainUtxo { outpoint: OutPoint::new(OutPoint::null().txid, 9), value: Amount::from_sat(50_000), confirmation_height: Some(100) };
+        let wallet_output = build_output(wallet_utxo.value, p2wpkh_output_script(wallet_pubkey));
+        let change_script = p2wpkh_output_script(wallet_pubkey);
+
+        // a commitment signed at 253 sat/kw, which now needs 5_000 sat/kw
+        let commitment_weight = 1_124;
+        let commitment_fee = fee_for_weight(253, commitment_weight);
+        let cpfp_tx = build_anchor_cpfp_transaction(
+            anchor_outpoint,
+            &wallet_utxo,
+            5_000,
+            commitment_weight,
+            commitment_fee,
+            change_script.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(cpfp_tx.input.len(), 2);
+        assert_eq!(cpfp_tx.input[0].previous_output, anchor_outpoint);
+        assert_eq!(cpfp_tx.input[1].previous_output, wallet_utxo.outpoint);
+        assert_eq!(cpfp_tx.output.len(), 1);
+        assert_eq!(cpfp_tx.output[0].script_pubkey, change_script);
+        // (1_124 + 719) weight at 5_000 sat/kw, less the 284 sats the commitment pays
+        let child_fee = ANCHOR_OUTPUT_VALUE + wallet_utxo.value - cpfp_tx.output[0].value;
+        assert_eq!(child_fee, Amount::from_sat(9_215 - 284));
+
+        let mut signed_tx = cpfp_tx.clone();
+        let anchor_signature = generate_p2wsh_signature(
+            cpfp_tx.clone(),
+            0,
+            &anchor_script,
+            ANCHOR_OUTPUT_VALUE.to_sat(),
+            bitcoin::sighash::EcdsaSighashType::All,
+            funding_privkey,
+        );
+        let mut anchor_signature = anchor_signature.serialize_der().to_vec();
+        anchor_signature.push(bitcoin::sighash::EcdsaSighashType::All as u8);
+        signed_tx.input[0].witness.push(anchor_signature);
+        signed_tx.input[0].witness.push(anchor_script.as_bytes());
+
+        sign_p2wpkh_input(&mut signed_tx, 1, wallet_utxo.value, &wallet_privkey);
+        print_transaction(&signed_tx);
+
+        assert!(signed_tx.weight().to_wu() <= ANCHOR_CPFP_TX_WEIGHT);
+        let prevout =
+            |outpoint: &OutPoint| if *outpoint == anchor_outpoint { Some(anchor_output.clone()) } else { Some(wallet_output.clone()) };
+        assert!(signed_tx.verify(prevout).is_ok());
+
+        // the package reaches the target feerate
+        let package_weight = commitment_weight + signed_tx.weight().to_wu();
+        assert!((commitment_fee + child_fee).to_sat() * 1000 / package_weight >= 5_000);
+
+        // a commitment that already pays enough still needs a child that pays for itself
+        let cpfp_tx =
+            build_anchor_cpfp_transaction(anchor_outpoint, &wallet_utxo, 253, commitment_weight, Amount::from_sat(10_000), change_script.clone())
+                .unwrap();
+        assert_eq!(ANCHOR_OUTPUT_VALUE + wallet_utxo.value - cpfp_tx.output[0].value, fee_for_weight(253, ANCHOR_CPFP_TX_WEIGHT));
+
+        let poor_utxo = ChainUtxo { value: Amount::from_sat(1_000), ..wallet_utxo };
+        assert_eq!(
+            build_anchor_cpfp_transaction(anchor_outpoint, &poor_utxo, 5_000, commitment_weight, commitment_fee, change_script),
+            Err(ExerciseError::InsufficientFunds { needed: 9_215 - 284, available: 1_330 })
+        );
+    })
+}
+
+#[test]
+fn test_51_bump_funding_fee() {
+    EXERCISE_51.run(|| {
+        let wallet_privkey = secp256k1_private_key(&[0x05; 32]);
+        let wallet_pubkey = pubkey_from_private_key(&[0x05; 32]);
+        let wallet_output = |value| build_output(Amount::from_sat(value), p2wpkh_output_script(wallet_pubkey));
+        let alice_pubkey = pubkey_from_private_key(&[0x01; 32]);
+        let bob_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+        // a funding transaction paying 1_000 sat/kw
+        let original_input = OutPoint::new(OutPoint::null().txid, 0);
+        let txin =
+            TxIn { previous_output: original_input, script_sig: ScriptBuf::new(), sequence: Sequence::MAX, witness: Witness::new() };
+        let mut original_tx = build_funding_transaction(vec![txin], &alice_pubkey, &bob_pubkey, Amount::from_sat(5_000_000));
+        // sign once for the weight, and again once the fee is taken out
+        sign_p2wpkh_input(&mut original_tx, 0, Amount::from_sat(5_000_000), &wallet_privkey);
+        let original_fee = fee_for_weight(1_000, original_tx.weight().to_wu());
+        original_tx.output[0].value -= original_fee;
+        sign_p2wpkh_input(&mut original_tx, 0, Amount::from_sat(5_000_000), &wallet_privkey);
+
+        let additional_input =
+            ChainUtxo { outpoint: OutPoint::new(OutPoint::null().txid, 1), value: Amount::from_sat(20_000), confirmation_height: Some(100) };
+        let change_script = p2wpkh_output_script(wallet_pubkey);
+
+        let replacement =
+            bump_funding_fee(&original_tx, original_fee, &additional_input, change_script.clone(), 5_000).unwrap();
+        assert_eq!(replacement.output[0], original_tx.output[0]);
+        assert_eq!(replacement.output[1].script_pubkey, change_script);
+        assert_eq!(replacement.input[0].previous_output, original_input);
+        assert_eq!(replacement.input[1].previous_output, additional_input.outpoint);
+        assert!(replacement.input.iter().all(|input| input.sequence.to_consensus_u32() < 0xfffffffe));
+        assert!(replacement.input.iter().all(|input| input.witness.is_empty()));
+
+        let mut signed = replacement.clone();
+        sign_p2wpkh_input(&mut signed, 0, Amount::from_sat(5_000_000), &wallet_privkey);
+        sign_p2wpkh_input(&mut signed, 1, additional_input.value, &wallet_privkey);
+        print_transaction(&signed);
+        let prevout = |outpoint: &OutPoint| Some(wallet_output(if *outpoint == original_input { 5_000_000 } else { 20_000 }));
+        assert!(signed.verify(prevout).is_ok());
+
+        let new_fee = Amount::from_sat(5_000_000 + 20_000) - replacement.output[0].value - replacement.output[1].value;
+        assert!(new_fee.to_sat() * 1000 / signed.weight().to_wu() >= 5_000);
+
+        // BIP 125 rule 3: the replacement pays less than the original, as the
+        // extra input and output don't make up for the lower feerate
+        assert!(matches!(
+            bump_funding_fee(&original_tx, original_fee, &additional_input, change_script.clone(), 500),
+            Err(ExerciseError::InsufficientReplacementFee { .. })
+        ));
+
+        // rule 4: the replacement pays more than the original, but less than
+        // 1 sat/vB more for its own size
+        let weight = original_tx.weight().to_wu() + P2WPKH_INPUT_WEIGHT + P2WPKH_OUTPUT_WEIGHT;
+        assert!(fee_for_weight(700, weight) > original_fee);
+        assert_eq!(
+            bump_funding_fee(&original_tx, original_fee, &additional_input, change_script.clone(), 700),
+            Err(ExerciseError::InsufficientReplacementFee {
+                fee: fee_for_weight(700, weight).to_sat(),
+                required: (original_fee + fee_for_weight(250, weight)).to_sat(),
+            })
+        );
+
+        let poor_input = ChainUtxo { value: Amount::from_sat(1_000), ..additional_input };
+        assert!(matches!(
+            bump_funding_fee(&original_tx, original_fee, &poor_input, change_script, 5_000),
+            Err(ExerciseError::InsufficientFunds { .. })
+        ));
+    })
+}
+
+fn fee_range(min: u64, max: u64) -> FeeRange {
+    FeeRange { min_fee_satoshis: Amount::from_sat(min), max_fee_satoshis: Amount::from_sat(max) }
+}
+
+fn closing_signed(fee: u64, fee_range: Option<FeeRange>) -> ClosingSigned {
+    let secp = Secp256k1::new();
+    let signature = secp.sign_ecdsa(&secp256k1::Message::from_digest([0x01; 32]), &secp256k1_private_key(&[0x02; 32]));
+    ClosingSigned { channel_id: [0x42; 32], fee_satoshis: Amount::from_sat(fee), signature, fee_range }
+}
+
+#[test]
+fn test_52_closing_signed_fee() {
+    EXERCISE_52.run(|| {
+        let fundee_range = fee_range(1_000, 3_000);
+        let fee = |closing_signed: &ClosingSigned| closing_signed_fee(false, &fundee_range, None, closing_signed);
+
+        // a fee in our range is agreed to as it is
+        assert_eq!(fee(&closing_signed(2_000, Some(fee_range(500, 5_000)))), Ok(Amount::from_sat(2_000)));
+        // otherwise the fundee counters with the closest fee it would agree to
+        assert_eq!(fee(&closing_signed(4_000, Some(fee_range(500, 5_000)))), Ok(Amount::from_sat(3_000)));
+        assert_eq!(fee(&closing_signed(600, Some(fee_range(500, 5_000)))), Ok(Amount::from_sat(1_000)));
+        assert_eq!(
+            fee(&closing_signed(600, Some(fee_range(500, 900)))),
+            Err(ClosingError::NoFeeRangeOverlap { ours: fundee_range, theirs: fee_range(500, 900) })
+        );
+        assert_eq!(fee(&closing_signed(2_000, None)), Err(ClosingError::MissingFeeRange));
+        assert_eq!(fee(&closing_signed(6_000, Some(fee_range(500, 5_000)))), Err(ClosingError::FeeOutsideRange(6_000)));
+
+        // the funder has to agree to the fundee's counter
+        let funder_range = fee_range(500, 5_000);
+        assert_eq!(
+            closing_signed_fee(
+                true,
+                &funder_range,
+                Some(Amount::from_sat(4_000)),
+                &closing_signed(3_000, Some(fundee_range))
+            ),
+            Ok(Amount::from_sat(3_000))
+        );
+        assert_eq!(
+            closing_signed_fee(
+                true,
+                &fee_range(500, 2_000),
+                Some(Amount::from_sat(1_500)),
+                &closing_signed(2_500, Some(fundee_range))
+            ),
+            Err(ClosingError::FeeOutsideRange(2_500))
+        );
+        // which the fundee's reply must not move away from
+        assert_eq!(
+            closing_signed_fee(
+                false,
+                &fundee_range,
+                Some(Amount::from_sat(3_000)),
+                &closing_signed(3_000, Some(funder_range))
+            ),
+            Ok(Amount::from_sat(3_000))
+        );
+        assert_eq!(
+            closing_signed_fee(
+                false,
+                &fundee_range,
+                Some(Amount::from_sat(3_000)),
+                &closing_signed(2_500, Some(funder_range))
+            ),
+            Err(ClosingError::FeeChanged { sent: 3_000, received: 2_500 })
+        );
+    })
+}
+
+#[test]
+fn test_53_check_counterparty_shutdown() {
+    EXERCISE_53.run(|| {
+        let shutdown = |scriptpubkey: ScriptBuf| Shutdown { channel_id: [0x42; 32], scriptpubkey };
+        let p2wpkh = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+        let other_p2wpkh = p2wpkh_output_script(pubkey_from_private_key(&[0x02; 32]));
+        let p2tr = ScriptBuf::from_hex(&format!("5120{}", "22".repeat(32))).unwrap();
+
+        assert_eq!(check_counterparty_shutdown(&shutdown(p2wpkh.clone()), None, false), Ok(()));
+        assert_eq!(check_counterparty_shutdown(&shutdown(p2wpkh.clone()), Some(&p2wpkh), false), Ok(()));
+        // an empty upfront script doesn't commit to anything
+        assert_eq!(check_counterparty_shutdown(&shutdown(p2wpkh.clone()), Some(&ScriptBuf::new()), false), Ok(()));
+        assert_eq!(
+            check_counterparty_shutdown(&shutdown(other_p2wpkh), Some(&p2wpkh), false),
+            Err(ShutdownScriptError::UpfrontScriptMismatch)
+        );
+
+        assert_eq!(check_counterparty_shutdown(&shutdown(p2tr.clone()), None, true), Ok(()));
+        assert_eq!(
+            check_counterparty_shutdown(&shutdown(p2tr.clone()), Some(&p2tr), false),
+            Err(ShutdownScriptError::AnySegwitNotNegotiated(1))
+        );
+
+        // committing to a script upfront doesn't make it valid
+        let op_return = ScriptBuf::from_hex("6a0401020304").unwrap();
+        assert_eq!(
+            check_counterparty_shutdown(&shutdown(op_return.clone()), Some(&op_return), true),
+            Err(ShutdownScriptError::Unsupported(op_return))
+        );
+    })
+}
+
+const NODE_SEED: [u8; 32] = [0x5e; 32];
+
+fn channel_backup() -> StaticChannelBackup {
+    StaticChannelBackup {
+        channel_id: [0x42; 32],
+        funding_outpoint: OutPoint::new(OutPoint::null().txid, 1),
+        peer_node_id: pubkey_from_private_key(&[0x02; 32]).inner,
+        channel_key_index: 7,
+        is_funder: true,
+        to_self_delay: 144,
+        counterparty_keys: ChannelKeyManager::from_seed(&[0x6f; 32], 0).pubkeys(),
+    }
+}
+
+#[test]
+fn test_54_encrypt_channel_backup() {
+    EXERCISE_54.run(|| {
+        let backup = channel_backup();
+        let blob = encrypt_channel_backup(&NODE_SEED, &backup);
+        let plaintext = decrypt_blob(&backup_key(&NODE_SEED), &blob).unwrap();
+
+        // version, channel id, outpoint, node id, key index, funder flag,
+        // to_self_delay and five basepoints
+        assert_eq!(plaintext.len(), 1 + 32 + 32 + 4 + 33 + 8 + 1 + 2 + 5 * 33);
+        assert_eq!(plaintext[0], STATIC_CHANNEL_BACKUP_VERSION);
+        assert_eq!(&plaintext[1..33], &[0x42; 32]);
+        assert_eq!(&plaintext[65..69], &[0, 0, 0, 1]);
+        assert_eq!(&plaintext[69..102], &backup.peer_node_id.serialize());
+        assert_eq!(&plaintext[102..113], &[0, 0, 0, 0, 0, 0, 0, 7, 1, 0, 144]);
+        assert_eq!(&plaintext[113..146], &backup.counterparty_keys.funding_pubkey.serialize());
+        assert_eq!(&plaintext[245..], &backup.counterparty_keys.revocation_basepoint.serialize());
+
+        // a fresh nonce every time, so two backups of the same channel don't
+        // look alike
+        assert_ne!(encrypt_channel_backup(&NODE_SEED, &backup), blob);
+        assert!(decrypt_blob(&backup_key(&[0x00; 32]), &blob).is_err());
+    })
+}
+
+#[test]
+fn test_55_restore_channel_backup() {
+    EXERCISE_55.run(|| {
+        let backup = channel_backup();
+        let restored = restore_channel_backup(&NODE_SEED, &encrypt_channel_backup(&NODE_SEED, &backup)).unwrap();
+        assert_eq!(restored.backup, backup);
+        let keys = ChannelKeyManager::from_seed(&NODE_SEED, 7);
+        assert_eq!(restored.keys, keys);
+        assert_eq!(restored.channel_reestablish.channel_id, backup.channel_id);
 
-    let acceptable_solutions =
-        ["5899cdd0e418b516afa2552611aad22f974ac17e9892f5828e2e55f18b2d7899".to_string()];
+        // The peer is at commitment 5 with us, and can't make sense of a
+        // channel_reestablish from a channel that just opened: it closes the
+        // channel with its latest commitment.
+        let peer_keys = ChannelKeyManager::from_seed(&[0x6f; 32], 0);
+        assert_eq!(
+            handle_channel_reestablish(&peer_keys, 5, 5, &restored.channel_reestablish),
+            ReestablishAction::FailChannel
+        );
+        // Its own channel_reestablish proves we revoked commitment 4, so we know
+        // not to broadcast anything and to wait for its close.
+        let peer_reestablish = ChannelReestablish {
+            channel_id: backup.channel_id,
+            next_commitment_number: 6,
+            next_revocation_number: 5,
+            your_last_per_commitment_secret: keys.per_commitment_secret(4),
+            my_current_per_commitment_point: peer_keys.per_commitment_point(5),
+        };
+        assert_eq!(handle_channel_reestablish(&restored.keys, 0, 0, &peer_reestablish), ReestablishAction::FallenBehind);
 
-    assert!(acceptable_solutions.contains(&their_solution));
+        let blob = encrypt_channel_backup(&NODE_SEED, &backup);
+        assert_eq!(restore_channel_backup(&[0x00; 32], &blob), Err(BackupError::DecryptionFailed));
+        let mut plaintext = decrypt_blob(&backup_key(&NODE_SEED), &blob).unwrap();
+        plaintext.push(0x00);
+        assert_eq!(
+            restore_channel_backup(&NODE_SEED, &encrypt_blob(&backup_key(&NODE_SEED), &plaintext)),
+            Err(BackupError::TrailingBytes(1))
+        );
+        plaintext.truncate(100);
+        assert_eq!(
+            restore_channel_backup(&NODE_SEED, &encrypt_blob(&backup_key(&NODE_SEED), &plaintext)),
+            Err(BackupError::Invalid(WireError::ShortRead))
+        );
+        plaintext[0] = 2;
+        assert_eq!(
+            restore_channel_backup(&NODE_SEED, &encrypt_blob(&backup_key(&NODE_SEED), &plaintext)),
+            Err(BackupError::UnknownVersion(2))
+        );
+    })
 }
@@ -0,0 +1,262 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use internal::errors::TransportError;
+use internal::noise_utils::{
+    decrypt_with_ad, ecdh, encrypt_with_ad, hkdf, sha256, HANDSHAKE_VERSION, PROLOGUE, PROTOCOL_NAME, TAG_LEN,
+};
+
+pub const ACT_ONE_LEN: usize = 50;
+pub const ACT_TWO_LEN: usize = 50;
+pub const ACT_THREE_LEN: usize = 66;
+
+/// Each key is rotated once it has encrypted this many times, or every 500
+/// messages since the length prefix and body each use a nonce.
+pub const KEY_ROTATION_INTERVAL: u64 = 1000;
+
+/// An encrypted length prefix: two bytes plus a tag.
+pub const ENCRYPTED_LENGTH_LEN: usize = 2 + TAG_LEN;
+
+/// What each side carries from one act to the next: the chaining key `ck`,
+/// the handshake hash `h` and the last temporary key derived from `ck`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeState {
+    pub ck: [u8; 32],
+    pub h: [u8; 32],
+    pub temp_k: [u8; 32],
+}
+
+/// The keys a finished handshake hands to the transport, plus the chaining
+/// key they are rotated from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportKeys {
+    pub sending_key: [u8; 32],
+    pub receiving_key: [u8; 32],
+    pub chaining_key: [u8; 32],
+}
+
+/// One direction of a connection after the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CipherState {
+    pub key: [u8; 32],
+    pub nonce: u64,
+    pub chaining_key: [u8; 32],
+}
+
+impl TransportKeys {
+    pub fn sending_cipher(&self) -> CipherState {
+        CipherState { key: self.sending_key, nonce: 0, chaining_key: self.chaining_key }
+    }
+
+    pub fn receiving_cipher(&self) -> CipherState {
+        CipherState { key: self.receiving_key, nonce: 0, chaining_key: self.chaining_key }
+    }
+}
+
+/// Both sides start from the protocol name and prologue, then mix in the
+/// responder's static key, which the initiator has to know up front.
+pub fn initialize_handshake_state(responder_static_pubkey: &secp256k1PublicKey) -> HandshakeState {
+    let h = sha256(&[PROTOCOL_NAME]);
+    let ck = h;
+    let h = sha256(&[&h, PROLOGUE]);
+    let h = sha256(&[&h, &responder_static_pubkey.serialize()]);
+    HandshakeState { ck, h, temp_k: [0; 32] }
+}
+
+fn parse_act(act: &[u8], pubkey_len: usize) -> Result<(secp256k1PublicKey, &[u8]), TransportError> {
+    if act[0] != HANDSHAKE_VERSION {
+        return Err(TransportError::UnknownVersion(act[0]));
+    }
+    let pubkey =
+        secp256k1PublicKey::from_slice(&act[1..1 + pubkey_len]).map_err(|_| TransportError::InvalidPublicKey)?;
+    Ok((pubkey, &act[1 + pubkey_len..]))
+}
+
+fn write_act(payload: &[u8], tag: &[u8]) -> Vec<u8> {
+    let mut act = vec![HANDSHAKE_VERSION];
+    act.extend_from_slice(payload);
+    act.extend_from_slice(tag);
+    act
+}
+
+//
+// Act One
+//
+
+/// Initiator: send our ephemeral key and prove we know the responder's
+/// static key with an empty ciphertext keyed from `ECDH(e, rs)`.
+pub fn act_one(
+    state: &mut HandshakeState,
+    ephemeral_key: &SecretKey,
+    responder_static_pubkey: &secp256k1PublicKey,
+) -> [u8; ACT_ONE_LEN] {
+    let secp = Secp256k1::new();
+    let e_pub = secp256k1PublicKey::from_secret_key(&secp, ephemeral_key);
+
+    state.h = sha256(&[&state.h, &e_pub.serialize()]);
+    let es = ecdh(ephemeral_key, responder_static_pubkey);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &es);
+    let c = encrypt_with_ad(&state.temp_k, 0, &state.h, &[]);
+    state.h = sha256(&[&state.h, &c]);
+
+    write_act(&e_pub.serialize(), &c).try_into().unwrap()
+}
+
+/// Responder: check act one and return the initiator's ephemeral key.
+pub fn process_act_one(
+    state: &mut HandshakeState,
+    static_key: &SecretKey,
+    act: &[u8; ACT_ONE_LEN],
+) -> Result<secp256k1PublicKey, TransportError> {
+    let (re, c) = parse_act(act, 33)?;
+
+    state.h = sha256(&[&state.h, &re.serialize()]);
+    let es = ecdh(static_key, &re);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &es);
+    decrypt_with_ad(&state.temp_k, 0, &state.h, c)?;
+    state.h = sha256(&[&state.h, c]);
+
+    Ok(re)
+}
+
+//
+// Act Two
+//
+
+/// Responder: answer with our own ephemeral key, keyed from `ECDH(e, re)`.
+pub fn act_two(
+    state: &mut HandshakeState,
+    ephemeral_key: &SecretKey,
+    initiator_ephemeral_pubkey: &secp256k1PublicKey,
+) -> [u8; ACT_TWO_LEN] {
+    let secp = Secp256k1::new();
+    let e_pub = secp256k1PublicKey::from_secret_key(&secp, ephemeral_key);
+
+    state.h = sha256(&[&state.h, &e_pub.serialize()]);
+    let ee = ecdh(ephemeral_key, initiator_ephemeral_pubkey);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &ee);
+    let c = encrypt_with_ad(&state.temp_k, 0, &state.h, &[]);
+    state.h = sha256(&[&state.h, &c]);
+
+    write_act(&e_pub.serialize(), &c).try_into().unwrap()
+}
+
+/// Initiator: check act two and return the responder's ephemeral key.
+pub fn process_act_two(
+    state: &mut HandshakeState,
+    ephemeral_key: &SecretKey,
+    act: &[u8; ACT_TWO_LEN],
+) -> Result<secp256k1PublicKey, TransportError> {
+    let (re, c) = parse_act(act, 33)?;
+
+    state.h = sha256(&[&state.h, &re.serialize()]);
+    let ee = ecdh(ephemeral_key, &re);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &ee);
+    decrypt_with_ad(&state.temp_k, 0, &state.h, c)?;
+    state.h = sha256(&[&state.h, c]);
+
+    Ok(re)
+}
+
+//
+// Act Three
+//
+
+/// Initiator: reveal our static key under the act two key, then finish
+/// with a tag keyed from `ECDH(s, re)` and split off the transport keys.
+pub fn act_three(
+    state: &mut HandshakeState,
+    static_key: &SecretKey,
+    responder_ephemeral_pubkey: &secp256k1PublicKey,
+) -> ([u8; ACT_THREE_LEN], TransportKeys) {
+    let secp = Secp256k1::new();
+    let s_pub = secp256k1PublicKey::from_secret_key(&secp, static_key);
+
+    // The nonce is 1: temp_k2 already encrypted the empty act two payload.
+    let c = encrypt_with_ad(&state.temp_k, 1, &state.h, &s_pub.serialize());
+    state.h = sha256(&[&state.h, &c]);
+    let se = ecdh(static_key, responder_ephemeral_pubkey);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &se);
+    let t = encrypt_with_ad(&state.temp_k, 0, &state.h, &[]);
+
+    let (sending_key, receiving_key) = hkdf(&state.ck, &[]);
+    let keys = TransportKeys { sending_key, receiving_key, chaining_key: state.ck };
+
+    (write_act(&c, &t).try_into().unwrap(), keys)
+}
+
+/// Responder: learn and authenticate the initiator's static key. The
+/// transport keys come out mirrored, since we receive what they send.
+pub fn process_act_three(
+    state: &mut HandshakeState,
+    ephemeral_key: &SecretKey,
+    act: &[u8; ACT_THREE_LEN],
+) -> Result<(secp256k1PublicKey, TransportKeys), TransportError> {
+    if act[0] != HANDSHAKE_VERSION {
+        return Err(TransportError::UnknownVersion(act[0]));
+    }
+    let (c, t) = act[1..].split_at(33 + TAG_LEN);
+
+    let rs_bytes = decrypt_with_ad(&state.temp_k, 1, &state.h, c)?;
+    let rs = secp256k1PublicKey::from_slice(&rs_bytes).map_err(|_| TransportError::InvalidPublicKey)?;
+    state.h = sha256(&[&state.h, c]);
+    let se = ecdh(ephemeral_key, &rs);
+    (state.ck, state.temp_k) = hkdf(&state.ck, &se);
+    decrypt_with_ad(&state.temp_k, 0, &state.h, t)?;
+
+    let (receiving_key, sending_key) = hkdf(&state.ck, &[]);
+    let keys = TransportKeys { sending_key, receiving_key, chaining_key: state.ck };
+
+    Ok((rs, keys))
+}
+
+//
+// Lightning Message Encryption
+//
+
+/// Move to the next nonce, rotating to `HKDF(ck, k)` once the key is spent.
+fn advance_nonce(cipher: &mut CipherState) {
+    cipher.nonce += 1;
+    if cipher.nonce == KEY_ROTATION_INTERVAL {
+        (cipher.chaining_key, cipher.key) = hkdf(&cipher.chaining_key, &cipher.key);
+        cipher.nonce = 0;
+    }
+}
+
+/// Encrypt the 2-byte length of `payload`, then `payload` itself, each with
+/// its own nonce and empty associated data.
+pub fn encrypt_message(cipher: &mut CipherState, payload: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let len = u16::try_from(payload.len()).map_err(|_| TransportError::MessageTooLong(payload.len()))?;
+
+    let mut message = encrypt_with_ad(&cipher.key, cipher.nonce, &[], &len.to_be_bytes());
+    advance_nonce(cipher);
+    message.extend(encrypt_with_ad(&cipher.key, cipher.nonce, &[], payload));
+    advance_nonce(cipher);
+
+    Ok(message)
+}
+
+/// Decrypt just the length prefix, so a reader knows how many more bytes to
+/// wait for. The result doesn't count the body's tag.
+pub fn decrypt_length(cipher: &mut CipherState, header: &[u8; ENCRYPTED_LENGTH_LEN]) -> Result<usize, TransportError> {
+    let len = decrypt_with_ad(&cipher.key, cipher.nonce, &[], header).map_err(|_| TransportError::InvalidMessageMac)?;
+    advance_nonce(cipher);
+    Ok(u16::from_be_bytes([len[0], len[1]]) as usize)
+}
+
+/// Decrypt one whole message as produced by `encrypt_message`.
+pub fn decrypt_message(cipher: &mut CipherState, message: &[u8]) -> Result<Vec<u8>, TransportError> {
+    if message.len() < ENCRYPTED_LENGTH_LEN + TAG_LEN {
+        return Err(TransportError::LengthMismatch { expected: ENCRYPTED_LENGTH_LEN + TAG_LEN, actual: message.len() });
+    }
+    let (header, body) = message.split_at(ENCRYPTED_LENGTH_LEN);
+
+    let len = decrypt_length(cipher, header.try_into().unwrap())?;
+    if body.len() != len + TAG_LEN {
+        return Err(TransportError::LengthMismatch { expected: ENCRYPTED_LENGTH_LEN + len + TAG_LEN, actual: message.len() });
+    }
+    let payload = decrypt_with_ad(&cipher.key, cipher.nonce, &[], body).map_err(|_| TransportError::InvalidMessageMac)?;
+    advance_nonce(cipher);
+
+    Ok(payload)
+}
@@ -0,0 +1,578 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::Hash;
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::script::ScriptBuf;
+use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::secp256k1::{Scalar, Secp256k1, SecretKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::constants::ChainHash;
+use bitcoin::transaction::Version;
+use bitcoin::{taproot, Amount, Network, OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+use internal::blinded_path::{decrypt_recipient_data, encrypt_recipient_data, BlindedHop, BlindedHopData, BlindedPath};
+use internal::channel::{Channel, ChannelParams, CommitmentTransactionBuilder};
+use internal::errors::{ChannelError, ExerciseError, InteractiveTxError, MusigError, OfferError, OnionError};
+use internal::fee_utils::{fee_for_weight, SPLICE_IN_TX_WEIGHT};
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+use bitcoin::secp256k1::{Keypair, Message, PublicKey as secp256k1PublicKey};
+use internal::key_utils::{
+    complete_adaptor_signature, extract_adaptor_secret, sort_funding_keys, verify_adaptor_signature, AdaptorSignature,
+};
+use internal::sighash_utils::compute_tapscript_sighash;
+use internal::interactive_tx::{
+    is_initiator_serial_id, InteractiveTxConstructor, NegotiatedInput, MAX_RECEIVED_TX_ADDITIONS,
+};
+use internal::keys::{DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey};
+use internal::offers::{
+    read_invoice_request, signature_digest, verify_signature, Bolt12Invoice, Offer, INVOICE_REQUEST_TYPES,
+    INVREQ_AMOUNT_TLV, INVREQ_CHAIN_TLV, INVREQ_METADATA_TLV, INVREQ_PAYER_ID_TLV, INVREQ_QUANTITY_TLV, SIGNATURE_TLV,
+};
+use internal::onion_utils::{blinding_factor, generate_key, shared_secret};
+use internal::musig_utils::{
+    aggregate_nonces, aggregate_partial_signatures, generate_nonce, partial_sign, verify_partial_signature,
+    KeyAggContext,
+};
+use internal::script_utils::{
+    build_ptlc_witness_script, build_taproot_revoke_script, build_taproot_spend_info, build_taproot_to_delay_script,
+    taproot_nums_point,
+};
+use internal::tlv::{decode_tu64, encode_tu64, find_record, write_tlv_stream, TlvRecord};
+use internal::tx_utils::{build_transaction, check_to_self_delay};
+use internal::wire::{TxAddInput, TxAddOutput};
+use internal::units::MilliSat;
+
+//
+// Exercise 1
+//
+
+/// The simple taproot channels `to_local` output. The NUMS internal key rules
+/// out a key path spend, so the output can only be claimed through the
+/// delayed leaf or, once revoked, the revocation leaf.
+pub fn taproot_to_local(
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    to_self_delay: i64,
+) -> Result<ScriptBuf, ExerciseError> {
+    check_to_self_delay(to_self_delay)?;
+
+    let spend_info = taproot_to_local_spend_info(revocation_pubkey, to_local_delayed_pubkey, to_self_delay as u16);
+
+    Ok(ScriptBuf::new_p2tr_tweaked(spend_info.output_key()))
+}
+
+/// The script tree behind `taproot_to_local`, needed to build the control
+/// block of either leaf when the output is spent.
+pub fn taproot_to_local_spend_info(
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+    to_self_delay: u16,
+) -> TaprootSpendInfo {
+    let to_delay_script = build_taproot_to_delay_script(to_local_delayed_pubkey, to_self_delay);
+    let revoke_script = build_taproot_revoke_script(revocation_pubkey, to_local_delayed_pubkey);
+
+    build_taproot_spend_info(taproot_nums_point(), vec![to_delay_script, revoke_script])
+}
+
+//
+// Exercise 2
+//
+
+/// The MuSig2 aggregate of both funding keys, in BOLT 3 order.
+pub fn musig_funding_key_agg(
+    local_funding_pubkey: &FundingPubkey,
+    remote_funding_pubkey: &FundingPubkey,
+) -> Result<KeyAggContext, MusigError> {
+    let (first, second) = sort_funding_keys(local_funding_pubkey.0, remote_funding_pubkey.0);
+
+    KeyAggContext::new(vec![first.inner, second.inner])
+}
+
+/// A funding output both sides spend together with one key path signature,
+/// so a cooperative spend looks like any other single-key P2TR spend.
+pub fn musig_funding_output(
+    local_funding_pubkey: &FundingPubkey,
+    remote_funding_pubkey: &FundingPubkey,
+) -> Result<ScriptBuf, MusigError> {
+    let secp = Secp256k1::verification_only();
+    let key_agg = musig_funding_key_agg(local_funding_pubkey, remote_funding_pubkey)?;
+
+    Ok(ScriptBuf::new_p2tr(&secp, key_agg.aggregated_pubkey(), None))
+}
+
+//
+// Exercise 3
+//
+
+/// Run both MuSig2 rounds between Alice and Bob to sign the key path spend of
+/// `funding_output` by input 0 of `tx`, and attach the aggregate signature.
+pub fn sign_musig_funding_spend(
+    mut tx: Transaction,
+    funding_output: &TxOut,
+    alice_funding_key: &SecretKey,
+    bob_funding_key: &SecretKey,
+    alice_session_rand: [u8; 32],
+    bob_session_rand: [u8; 32],
+) -> Result<Transaction, MusigError> {
+    let secp = Secp256k1::new();
+    let alice_pubkey = FundingPubkey(bitcoin::PublicKey::new(alice_funding_key.public_key(&secp)));
+    let bob_pubkey = FundingPubkey(bitcoin::PublicKey::new(bob_funding_key.public_key(&secp)));
+
+    // the output key commits to an empty script tree, so sign for the tweaked key
+    let key_agg = musig_funding_key_agg(&alice_pubkey, &bob_pubkey)?.with_taproot_tweak(None);
+
+    let sighash = SighashCache::new(&tx)
+        .taproot_key_spend_signature_hash(0, &Prevouts::All(std::slice::from_ref(funding_output)), TapSighashType::Default)
+        .unwrap();
+    let msg = sighash.to_byte_array();
+
+    // round 1: swap public nonces
+    let aggregated_pubkey = key_agg.aggregated_pubkey();
+    let (alice_secnonce, alice_pubnonce) = generate_nonce(alice_session_rand, alice_funding_key, &aggregated_pubkey, &msg);
+    let (bob_secnonce, bob_pubnonce) = generate_nonce(bob_session_rand, bob_funding_key, &aggregated_pubkey, &msg);
+    let aggnonce = aggregate_nonces(&[alice_pubnonce, bob_pubnonce])?;
+
+    // round 2: swap partial signatures, checking the one received
+    let alice_partial = partial_sign(alice_secnonce, alice_funding_key, &key_agg, &aggnonce, &msg)?;
+    let bob_partial = partial_sign(bob_secnonce, bob_funding_key, &key_agg, &aggnonce, &msg)?;
+    if !verify_partial_signature(&bob_partial, &bob_pubnonce, &bob_pubkey.0.inner, &key_agg, &aggnonce, &msg) {
+        return Err(MusigError::InvalidPartialSignature);
+    }
+
+    let signature = aggregate_partial_signatures(&[alice_partial, bob_partial], &key_agg, &aggnonce, &msg);
+    tx.input[0].witness = Witness::p2tr_key_spend(&taproot::Signature {
+        signature,
+        sighash_type: TapSighashType::Default,
+    });
+
+    Ok(tx)
+}
+
+//
+// Exercise 4
+//
+
+/// The P2TR output of a PTLC. Its only leaf is the PTLC script, under the
+/// NUMS internal key.
+pub fn ptlc_output(offerer_htlc_pubkey: &HtlcPubkey, receiver_htlc_pubkey: &HtlcPubkey, cltv_expiry: u32) -> ScriptBuf {
+    let ptlc_script = build_ptlc_witness_script(offerer_htlc_pubkey, receiver_htlc_pubkey, cltv_expiry);
+    let spend_info = build_taproot_spend_info(taproot_nums_point(), vec![ptlc_script]);
+
+    ScriptBuf::new_p2tr_tweaked(spend_info.output_key())
+}
+
+/// The receiver's claim of a PTLC: check the offerer's adaptor signature,
+/// complete it with the payment secret and co-sign input 0 of `claim_tx`.
+pub fn claim_ptlc(
+    mut claim_tx: Transaction,
+    ptlc_txout: &TxOut,
+    offerer_htlc_pubkey: &HtlcPubkey,
+    receiver_htlc_key: &SecretKey,
+    cltv_expiry: u32,
+    offerer_adaptor_signature: &AdaptorSignature,
+    payment_secret: &SecretKey,
+) -> Result<Transaction, ExerciseError> {
+    let secp = Secp256k1::new();
+    let receiver_htlc_pubkey = HtlcPubkey(bitcoin::PublicKey::new(receiver_htlc_key.public_key(&secp)));
+    let ptlc_script = build_ptlc_witness_script(offerer_htlc_pubkey, &receiver_htlc_pubkey, cltv_expiry);
+
+    let sighash = compute_tapscript_sighash(&claim_tx, 0, std::slice::from_ref(ptlc_txout), &ptlc_script);
+    let payment_point = payment_secret.public_key(&secp);
+    let offerer_xonly = offerer_htlc_pubkey.0.inner.x_only_public_key().0;
+    if !verify_adaptor_signature(offerer_adaptor_signature, &offerer_xonly, &sighash, &payment_point) {
+        return Err(ExerciseError::InvalidAdaptorSignature);
+    }
+
+    let offerer_signature = complete_adaptor_signature(offerer_adaptor_signature, payment_secret);
+    let receiver_keypair = Keypair::from_secret_key(&secp, receiver_htlc_key);
+    let receiver_signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest(sighash), &receiver_keypair);
+
+    let spend_info = build_taproot_spend_info(taproot_nums_point(), vec![ptlc_script.clone()]);
+    let control_block = spend_info.control_block(&(ptlc_script.clone(), taproot::LeafVersion::TapScript)).unwrap();
+
+    let mut witness = Witness::new();
+    witness.push(offerer_signature.as_ref());
+    witness.push(receiver_signature.as_ref());
+    // take the OP_IF branch
+    witness.push([1u8]);
+    witness.push(ptlc_script.as_bytes());
+    witness.push(control_block.serialize());
+    claim_tx.input[0].witness = witness;
+
+    Ok(claim_tx)
+}
+
+//
+// Exercise 5
+//
+
+/// What the offerer learns once the claim is on chain: the payment secret,
+/// which lets it claim the PTLC it received upstream.
+pub fn extract_payment_secret(
+    claim_tx: &Transaction,
+    offerer_adaptor_signature: &AdaptorSignature,
+    payment_point: &secp256k1PublicKey,
+) -> Option<SecretKey> {
+    let offerer_signature = SchnorrSignature::from_slice(claim_tx.input[0].witness.nth(0)?).ok()?;
+
+    extract_adaptor_secret(offerer_adaptor_signature, &offerer_signature, payment_point)
+}
+
+//
+// Exercise 6
+//
+
+/// The recipient blinds a path from `introduction_node` through `path` to
+/// itself. For each hop, the shared secret `ss_i = SHA256(e_i * N_i)` gives
+/// the blinded node id `HMAC("blinded_node_id", ss_i) * N_i` and the `rho`
+/// key its data is encrypted under; the ephemeral key is then blinded just
+/// like in the onion, `e_{i+1} = SHA256(E_i || ss_i) * e_i`.
+///
+/// `recipient_data` holds the plaintext TLV stream for every hop, the
+/// introduction node first.
+pub fn build_blinded_path(
+    session_key: &SecretKey,
+    introduction_node: &secp256k1PublicKey,
+    path: &[secp256k1PublicKey],
+    recipient_data: &[Vec<u8>],
+) -> Result<BlindedPath, OnionError> {
+    if recipient_data.len() != path.len() + 1 {
+        return Err(OnionError::HopCountMismatch { hops: path.len() + 1, payloads: recipient_data.len() });
+    }
+
+    let secp = Secp256k1::new();
+    let blinding_point = secp256k1PublicKey::from_secret_key(&secp, session_key);
+    let mut ephemeral_key = *session_key;
+    let mut blinded_hops = Vec::with_capacity(recipient_data.len());
+
+    for (node_id, data) in std::iter::once(introduction_node).chain(path).zip(recipient_data) {
+        let ephemeral_pubkey = secp256k1PublicKey::from_secret_key(&secp, &ephemeral_key);
+        let shared_secret = shared_secret(&ephemeral_key, node_id);
+
+        let tweak = Scalar::from_be_bytes(generate_key(b"blinded_node_id", &shared_secret)).unwrap();
+        blinded_hops.push(BlindedHop {
+            blinded_node_id: node_id.mul_tweak(&secp, &tweak).map_err(|_| OnionError::InvalidPublicKey)?,
+            encrypted_recipient_data: encrypt_recipient_data(&generate_key(b"rho", &shared_secret), data),
+        });
+
+        let blinding_factor = Scalar::from_be_bytes(blinding_factor(&ephemeral_pubkey, &shared_secret)).unwrap();
+        ephemeral_key = ephemeral_key.mul_tweak(&blinding_factor).map_err(|_| OnionError::InvalidPublicKey)?;
+    }
+
+    Ok(BlindedPath { introduction_node_id: *introduction_node, blinding_point, blinded_hops })
+}
+
+//
+// Exercise 7
+//
+
+/// A node inside the path gets the blinding point `E_i` in `update_add_htlc`
+/// and computes the same shared secret as `k_i * E_i`. The sender encrypted
+/// its onion layer to the blinded node id, so it needs the matching private
+/// key, `HMAC("blinded_node_id", ss_i) * k_i`, to peel it.
+pub fn blinded_node_key(node_key: &SecretKey, blinding_point: &secp256k1PublicKey) -> Result<SecretKey, OnionError> {
+    let shared_secret = shared_secret(node_key, blinding_point);
+    let tweak = Scalar::from_be_bytes(generate_key(b"blinded_node_id", &shared_secret)).unwrap();
+    node_key.mul_tweak(&tweak).map_err(|_| OnionError::InvalidPublicKey)
+}
+
+/// With the onion peeled, decrypt the `encrypted_recipient_data` from the
+/// payload and derive the blinding point to pass on. The introduction node
+/// takes `E_0` from its payload instead of from `update_add_htlc`.
+pub fn decrypt_blinded_hop(
+    node_key: &SecretKey,
+    blinding_point: &secp256k1PublicKey,
+    encrypted_recipient_data: &[u8],
+) -> Result<BlindedHopData, OnionError> {
+    let secp = Secp256k1::new();
+    let shared_secret = shared_secret(node_key, blinding_point);
+
+    let recipient_data = decrypt_recipient_data(&generate_key(b"rho", &shared_secret), encrypted_recipient_data)?;
+
+    let blinding_factor = Scalar::from_be_bytes(blinding_factor(blinding_point, &shared_secret)).unwrap();
+    let next_blinding_point =
+        blinding_point.mul_tweak(&secp, &blinding_factor).map_err(|_| OnionError::InvalidPublicKey)?;
+
+    Ok(BlindedHopData { recipient_data, next_blinding_point })
+}
+
+//
+// Exercise 8
+//
+
+/// The payer answers an offer with an `invoice_request`: every record of the
+/// offer, untouched, plus its own. `invreq_metadata` makes each request
+/// unique and `invreq_payer_id` is the key it is signed with. A chain is only
+/// named if it isn't bitcoin, and an amount only set if the offer leaves it
+/// to the payer or the payer wants to pay more.
+///
+/// The signature is `sign_schnorr_no_aux_rand` over the `signature_digest`
+/// of "invoice_request", appended as record 240.
+pub fn create_invoice_request(
+    offer: &Offer,
+    metadata: &[u8],
+    payer_key: &Keypair,
+    network: Network,
+    amount_msat: Option<MilliSat>,
+) -> Result<Vec<u8>, OfferError> {
+    if !offer.supports_chain(network) {
+        return Err(OfferError::UnsupportedChain);
+    }
+    if offer.amount.is_none() && amount_msat.is_none() {
+        return Err(OfferError::MissingField("invreq_amount"));
+    }
+
+    let mut records = offer.records.clone();
+    records.push(TlvRecord::new(INVREQ_METADATA_TLV, metadata.to_vec()));
+    if network != Network::Bitcoin {
+        records.push(TlvRecord::new(INVREQ_CHAIN_TLV, ChainHash::using_genesis_block(network).as_bytes().to_vec()));
+    }
+    if let Some(amount_msat) = amount_msat {
+        records.push(TlvRecord::new(INVREQ_AMOUNT_TLV, encode_tu64(amount_msat.to_msat())));
+    }
+    records.push(TlvRecord::new(INVREQ_PAYER_ID_TLV, payer_key.public_key().serialize().to_vec()));
+    records.sort_by_key(|record| record.tlv_type);
+
+    let secp = Secp256k1::new();
+    let signature = secp.sign_schnorr_no_aux_rand(&signature_digest("invoice_request", &records), payer_key);
+    records.push(TlvRecord::new(SIGNATURE_TLV, signature.serialize().to_vec()));
+
+    Ok(write_tlv_stream(&records)?)
+}
+
+//
+// Exercise 9
+//
+
+/// The payee replies with an invoice that repeats the `invoice_request` and
+/// adds its own fields. Before paying it, the payer checks that:
+///
+/// - the records in `INVOICE_REQUEST_TYPES` are exactly the request's, so
+///   nothing it asked for was changed,
+/// - `invoice_node_id` is the offer's `offer_issuer_id`, or for an offer
+///   without one, the last blinded node id of one of its paths,
+/// - `invoice_amount` is `invreq_amount` if the request set one, or the
+///   offer's amount times `invreq_quantity` if the offer is priced in msat,
+/// - the "invoice" signature is from `invoice_node_id`.
+pub fn verify_bolt12_invoice(
+    invoice: &[u8],
+    invoice_request: &[u8],
+    offer: &Offer,
+) -> Result<Bolt12Invoice, OfferError> {
+    let invoice = Bolt12Invoice::decode(invoice)?;
+    let request = read_invoice_request(invoice_request)?;
+
+    let mirrored = |records: &[TlvRecord]| -> Vec<TlvRecord> {
+        records
+            .iter()
+            .filter(|record| INVOICE_REQUEST_TYPES.iter().any(|range| range.contains(&record.tlv_type)))
+            .cloned()
+            .collect()
+    };
+    if mirrored(&invoice.records) != mirrored(&request) {
+        return Err(OfferError::InvoiceRequestMismatch);
+    }
+
+    let expected_node_id = match offer.issuer_id {
+        Some(issuer_id) => issuer_id == invoice.node_id,
+        None => offer
+            .paths
+            .iter()
+            .any(|path| path.blinded_hops.last().map(|hop| hop.blinded_node_id) == Some(invoice.node_id)),
+    };
+    if !expected_node_id {
+        return Err(OfferError::UnexpectedNodeId);
+    }
+
+    let quantity = find_record(&request, INVREQ_QUANTITY_TLV).map(|bytes| decode_tu64(bytes, 8)).transpose()?;
+    let expected_amount = match find_record(&request, INVREQ_AMOUNT_TLV) {
+        Some(bytes) => Some(decode_tu64(bytes, 8)?),
+        None if offer.currency.is_none() => offer.amount.map(|amount| amount * quantity.unwrap_or(1)),
+        None => None,
+    };
+    if expected_amount.is_some_and(|amount| amount != invoice.amount.to_msat()) {
+        return Err(OfferError::AmountMismatch);
+    }
+
+    verify_signature("invoice", &invoice.records, &invoice.signature, &invoice.node_id)?;
+    Ok(invoice)
+}
+
+//
+// Exercise 10
+//
+
+/// Record the peer's `tx_add_input`, or fail the negotiation if it breaks
+/// the BOLT 2 rules: the serial id must have the peer's parity and be
+/// unused, `prevtx_out` must be an output of `prevtx`, that output must be
+/// segwit so the txid can't be malleated, no other input may spend it, and
+/// the peer may not add more than `MAX_RECEIVED_TX_ADDITIONS` inputs.
+pub fn receive_tx_add_input(
+    constructor: &mut InteractiveTxConstructor,
+    message: &TxAddInput,
+) -> Result<(), InteractiveTxError> {
+    if is_initiator_serial_id(message.serial_id) == constructor.is_initiator {
+        return Err(InteractiveTxError::WrongSerialIdParity(message.serial_id));
+    }
+    if constructor.inputs.contains_key(&message.serial_id) {
+        return Err(InteractiveTxError::DuplicateSerialId(message.serial_id));
+    }
+    let prevout = message
+        .prevtx
+        .output
+        .get(message.prevtx_out as usize)
+        .ok_or(InteractiveTxError::PrevoutOutOfRange(message.prevtx_out))?;
+    if !prevout.script_pubkey.is_witness_program() {
+        return Err(InteractiveTxError::NonSegwitInput);
+    }
+    let previous_output = OutPoint::new(message.prevtx.compute_txid(), message.prevtx_out);
+    if constructor.inputs.values().any(|input| input.txin.previous_output == previous_output) {
+        return Err(InteractiveTxError::DuplicateInput(previous_output));
+    }
+    if constructor.received_inputs == MAX_RECEIVED_TX_ADDITIONS {
+        return Err(InteractiveTxError::TooManyAdditions);
+    }
+
+    let txin = TxIn {
+        previous_output,
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence(message.sequence),
+        witness: Witness::new(),
+    };
+    constructor.inputs.insert(message.serial_id, NegotiatedInput { txin, prevout: prevout.clone() });
+    constructor.received_inputs += 1;
+    Ok(())
+}
+
+//
+// Exercise 11
+//
+
+/// Record the peer's `tx_add_output`. The serial id follows the same rules
+/// as for inputs, and the amount must be at least our dust limit and at
+/// most `Amount::MAX_MONEY`.
+pub fn receive_tx_add_output(
+    constructor: &mut InteractiveTxConstructor,
+    message: &TxAddOutput,
+) -> Result<(), InteractiveTxError> {
+    if is_initiator_serial_id(message.serial_id) == constructor.is_initiator {
+        return Err(InteractiveTxError::WrongSerialIdParity(message.serial_id));
+    }
+    if constructor.outputs.contains_key(&message.serial_id) {
+        return Err(InteractiveTxError::DuplicateSerialId(message.serial_id));
+    }
+    if message.sats < constructor.dust_limit {
+        return Err(InteractiveTxError::DustOutput {
+            value: message.sats.to_sat(),
+            dust_limit: constructor.dust_limit.to_sat(),
+        });
+    }
+    if message.sats > Amount::MAX_MONEY {
+        return Err(InteractiveTxError::OutputAmountOutOfRange(message.sats.to_sat()));
+    }
+    if constructor.received_outputs == MAX_RECEIVED_TX_ADDITIONS {
+        return Err(InteractiveTxError::TooManyAdditions);
+    }
+
+    constructor.outputs.insert(message.serial_id, TxOut { value: message.sats, script_pubkey: message.script.clone() });
+    constructor.received_outputs += 1;
+    Ok(())
+}
+
+//
+// Exercise 12
+//
+
+/// The funding transaction both sides end up with: every input and every
+/// output in serial id order, whoever added it.
+pub fn build_interactive_funding_transaction(
+    constructor: &InteractiveTxConstructor,
+) -> Result<Transaction, InteractiveTxError> {
+    if !constructor.is_complete() {
+        return Err(InteractiveTxError::NotComplete);
+    }
+    if !constructor.outputs.values().any(|output| output.script_pubkey == constructor.funding_script_pubkey) {
+        return Err(InteractiveTxError::MissingFundingOutput);
+    }
+
+    let inputs = constructor.inputs.values().map(|input| input.txin.clone()).collect();
+    let outputs = constructor.outputs.values().cloned().collect();
+    Ok(build_transaction(Version::TWO, constructor.locktime, inputs, outputs))
+}
+
+//
+// Exercise 13
+//
+
+/// Our contributions to a splice-in, added to the interactive construction
+/// of the splice transaction: the old funding output, spent as input 0 so
+/// the 2-of-2 signs it like a commitment, and a wallet UTXO paying into a
+/// new funding output `splice_in_amount` larger than the old one. The wallet
+/// UTXO also pays the fee at `feerate_per_kw`, and the rest goes to
+/// `change_script`.
+pub fn contribute_splice_in(
+    constructor: &mut InteractiveTxConstructor,
+    funding_tx: Transaction,
+    funding_vout: u32,
+    wallet_prevtx: Transaction,
+    wallet_vout: u32,
+    splice_in_amount: Amount,
+    change_script: ScriptBuf,
+    feerate_per_kw: u64,
+) -> Result<(), ExerciseError> {
+    let old_funding_value = funding_tx.output[funding_vout as usize].value;
+    let wallet_value = wallet_prevtx.output[wallet_vout as usize].value;
+    let needed = splice_in_amount + fee_for_weight(feerate_per_kw, SPLICE_IN_TX_WEIGHT);
+    let change = wallet_value
+        .checked_sub(needed)
+        .ok_or(ExerciseError::InsufficientFunds { needed: needed.to_sat(), available: wallet_value.to_sat() })?;
+
+    constructor.contribute_input(funding_tx, funding_vout, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    constructor.contribute_input(wallet_prevtx, wallet_vout, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    let new_funding_script = constructor.funding_script_pubkey.clone();
+    constructor
+        .contribute_output(TxOut { value: old_funding_value + splice_in_amount, script_pubkey: new_funding_script });
+    constructor.contribute_output(TxOut { value: change, script_pubkey: change_script });
+    Ok(())
+}
+
+//
+// Exercise 14
+//
+
+/// Once the splice transaction is agreed, the next commitment spends its
+/// funding output instead of the old one, and each side's balance grows by
+/// what it spliced in. Return the channel parameters for the new funding
+/// outpoint, and our commitment for `per_commitment_point` with the new
+/// balances.
+pub fn splice_commitment(
+    params: &ChannelParams,
+    channel: &Channel,
+    splice_tx: &Transaction,
+    funding_script: &ScriptBuf,
+    local_splice_in: Amount,
+    remote_splice_in: Amount,
+    per_commitment_point: secp256k1PublicKey,
+) -> Result<(ChannelParams, Transaction), ChannelError> {
+    let vout = splice_tx
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == funding_script.to_p2wsh())
+        .ok_or(ChannelError::MissingFundingOutput)?;
+    let spliced_params =
+        ChannelParams { funding_outpoint: OutPoint::new(splice_tx.compute_txid(), vout as u32), ..*params };
+
+    let add = |balance: MilliSat, amount: Amount| {
+        let out_of_range = ExerciseError::AmountOutOfRange { name: "splice_in", amount: amount.to_sat() };
+        MilliSat::from_amount(amount).and_then(|amount| balance.checked_add(amount)).ok_or(out_of_range)
+    };
+    let spliced_channel = Channel {
+        to_local_msat: add(channel.to_local_msat, local_splice_in)?,
+        to_remote_msat: add(channel.to_remote_msat, remote_splice_in)?,
+        ..channel.clone()
+    };
+
+    let keys = spliced_params.local_commitment_keys(per_commitment_point);
+    let (commitment_tx, _) =
+        CommitmentTransactionBuilder::from_channel(&spliced_params, &spliced_channel, keys).build()?;
+    Ok((spliced_params, commitment_tx))
+}
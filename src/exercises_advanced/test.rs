@@ -0,0 +1,971 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{build_funding_transaction_with_script, sign_commitment_transaction};
+use crate::exercises::onion::{construct_onion_packet, peel_onion, PeeledOnion};
+use crate::exercises_advanced::exercises::{
+    blinded_node_key, build_blinded_path, claim_ptlc, create_invoice_request, decrypt_blinded_hop,
+    extract_payment_secret, musig_funding_key_agg, musig_funding_output, ptlc_output, sign_musig_funding_spend,
+    taproot_to_local, taproot_to_local_spend_info, verify_bolt12_invoice, receive_tx_add_input, receive_tx_add_output,
+    build_interactive_funding_transaction, contribute_splice_in, splice_commitment,
+};
+use crate::internal;
+use crate::simulation::dual_funding::negotiate_funding_transaction;
+use bitcoin::hash_types::Txid;
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{Keypair, Message, PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{self, LeafVersion};
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, Network, OutPoint, PublicKey, Sequence, Transaction, TxIn, TxOut, Witness};
+use internal::blinded_path::{BlindedPath, PATH_ID_TLV, SHORT_CHANNEL_ID_TLV};
+use internal::channel::{Channel, ChannelParams};
+use internal::errors::{ChannelError, ExerciseError, InteractiveTxError, OfferError, OnionError};
+use internal::fee_utils::HtlcFeeMode;
+use internal::hints::ExerciseCheck;
+use internal::keys::ChannelKeyManager;
+use internal::script_utils::p2wpkh_output_script;
+use internal::sign_utils::{finalize_commitment, sign_p2wpkh_input};
+use internal::interactive_tx::InteractiveTxConstructor;
+use internal::wire::{Message as WireMessage, TxAddInput, TxAddOutput, TxComplete};
+use internal::key_utils::{create_adaptor_signature, pubkey_from_private_key, secp256k1_private_key};
+use internal::keys::{DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey};
+use internal::script_utils::{
+    build_ptlc_witness_script, build_taproot_revoke_script, build_taproot_to_delay_script, taproot_nums_point,
+};
+use internal::offers::{
+    decode_offer, encode_bolt12, read_invoice_request, signature_digest, BlindedPayInfo, Bolt12Invoice,
+    DEFAULT_RELATIVE_EXPIRY, INVOICE_AMOUNT_TLV, INVOICE_BLINDEDPAY_TLV, INVOICE_CREATED_AT_TLV, INVOICE_NODE_ID_TLV,
+    INVOICE_PATHS_TLV, INVOICE_PAYMENT_HASH_TLV, INVOICE_REQUEST_HRP, INVREQ_AMOUNT_TLV, SIGNATURE_TLV,
+};
+use internal::onion_utils::{
+    AMT_TO_FORWARD_TLV, CURRENT_BLINDING_POINT_TLV, ENCRYPTED_RECIPIENT_DATA_TLV, OUTGOING_CLTV_VALUE_TLV,
+    TOTAL_AMOUNT_MSAT_TLV,
+};
+use internal::payments::PaymentHash;
+use internal::sighash_utils::compute_tapscript_sighash;
+use internal::tlv::{decode_tu64, encode_tu64, find_record, read_tlv_stream, write_tlv_stream, TlvRecord};
+use internal::units::MilliSat;
+use internal::tx_utils::{build_output, build_transaction, get_funding_input, tx_to_hex, DUMMY_FUNDING_TXID};
+
+/// Print the hex of a transaction built by an exercise, to paste into
+/// `bitcoin-cli decoderawtransaction` or mempool.space.
+fn print_transaction(tx: &Transaction) {
+    println!("their transaction: {}", tx_to_hex(tx));
+}
+
+/// What a learner stuck on an advanced exercise is shown, with `HINT_LEVEL`
+/// set, when its test fails.
+const EXERCISE_1: ExerciseCheck = ExerciseCheck {
+    exercise: 1,
+    concept: "A taproot to_local has no key path: its internal key is a NUMS point nobody knows the key of, and its \
+              two leaves are the delayed spend and the revocation spend.",
+    api: "check_to_self_delay, build_taproot_to_delay_script, build_taproot_revoke_script, taproot_nums_point, \
+          build_taproot_spend_info and ScriptBuf::new_p2tr_tweaked.",
+    solution: "build_taproot_spend_info(taproot_nums_point(), vec![to_delay_script, revoke_script]), and \
+               ScriptBuf::new_p2tr_tweaked(spend_info.output_key()) for the output.",
+};
+
+const EXERCISE_2: ExerciseCheck = ExerciseCheck {
+    exercise: 2,
+    concept: "MuSig2 aggregates the two funding keys into one, so the funding output is a plain P2TR key path output \
+              and a cooperative spend looks like any single-key spend.",
+    api: "sort_funding_keys, KeyAggContext::new, aggregated_pubkey and ScriptBuf::new_p2tr.",
+    solution: "KeyAggContext::new(vec![first.inner, second.inner]) with the sorted keys, then \
+               ScriptBuf::new_p2tr(&secp, key_agg.aggregated_pubkey(), None).",
+};
+
+const EXERCISE_3: ExerciseCheck = ExerciseCheck {
+    exercise: 3,
+    concept: "Signing with MuSig2 takes two rounds: both sides swap public nonces, then partial signatures, which \
+              add up to one Schnorr signature for the tweaked aggregate key.",
+    api: "with_taproot_tweak(None), taproot_key_spend_signature_hash, generate_nonce, aggregate_nonces, \
+          partial_sign, verify_partial_signature, aggregate_partial_signatures and Witness::p2tr_key_spend.",
+    solution: "Sign the key spend sighash of input 0: a nonce from each side, aggregate_nonces, a partial signature \
+               from each, reject Bob's if it doesn't verify, then aggregate them into the key spend witness.",
+};
+
+const EXERCISE_4: ExerciseCheck = ExerciseCheck {
+    exercise: 4,
+    concept: "A PTLC locks the payment to a point rather than a hash. The offerer's adaptor signature only becomes a \
+              valid signature with the payment secret, the private key of that point.",
+    api: "build_ptlc_witness_script, build_taproot_spend_info, compute_tapscript_sighash, verify_adaptor_signature, \
+          complete_adaptor_signature, sign_schnorr_no_aux_rand and control_block.",
+    solution: "Check the adaptor signature against the offerer's x-only key and the payment point, complete it, sign \
+               with the receiver's key, and witness [offerer signature] [receiver signature] [0x01] [ptlc_script] \
+               [control block].",
+};
+
+const EXERCISE_5: ExerciseCheck = ExerciseCheck {
+    exercise: 5,
+    concept: "The completed signature on chain and the adaptor signature differ by exactly the payment secret, so \
+              the offerer learns it from the claim.",
+    api: "claim_tx.input[0].witness.nth(0), SchnorrSignature::from_slice and extract_adaptor_secret.",
+    solution: "extract_adaptor_secret(offerer_adaptor_signature, &SchnorrSignature::from_slice(witness.nth(0)?).ok()?, \
+               payment_point)",
+};
+
+const EXERCISE_6: ExerciseCheck = ExerciseCheck {
+    exercise: 6,
+    concept: "Each hop of a blinded path gets a blinded node id and its data encrypted under a key from the shared \
+              secret with it, and the ephemeral key is blinded from hop to hop like in the onion.",
+    api: "shared_secret, generate_key with \"blinded_node_id\" and \"rho\", mul_tweak, encrypt_recipient_data and \
+          blinding_factor.",
+    solution: "Check there is one payload per hop; for each node, blinded_node_id = HMAC(\"blinded_node_id\", ss) * N, \
+               encrypt its data under generate_key(b\"rho\", &ss), then e = blinding_factor(E, ss) * e.",
+};
+
+const EXERCISE_7: ExerciseCheck = ExerciseCheck {
+    exercise: 7,
+    concept: "A node in the path finds the same shared secret from the blinding point and its own key, and tweaks \
+              its key the way the recipient tweaked its node id.",
+    api: "shared_secret, generate_key, SecretKey::mul_tweak, decrypt_recipient_data, blinding_factor and \
+          PublicKey::mul_tweak.",
+    solution: "blinded_node_key: node_key * HMAC(\"blinded_node_id\", ss). decrypt_blinded_hop: decrypt under \
+               generate_key(b\"rho\", &ss) and pass on blinding_point * blinding_factor(blinding_point, ss).",
+};
+
+const EXERCISE_8: ExerciseCheck = ExerciseCheck {
+    exercise: 8,
+    concept: "An invoice_request repeats every record of the offer and adds the payer's own, signed by the payer id.",
+    api: "offer.supports_chain, TlvRecord::new with the INVREQ_ types, encode_tu64, signature_digest, \
+          sign_schnorr_no_aux_rand, SIGNATURE_TLV and write_tlv_stream.",
+    solution: "Check the chain and that an amount is known, add the metadata, the chain if not bitcoin, the amount if \
+               given and the payer id, sort by type, sign signature_digest(\"invoice_request\", &records) and append \
+               the signature.",
+};
+
+const EXERCISE_9: ExerciseCheck = ExerciseCheck {
+    exercise: 9,
+    concept: "The payer only pays an invoice that repeats its request unchanged, comes from the offer's node, asks \
+              for the amount agreed, and is signed by that node.",
+    api: "Bolt12Invoice::decode, read_invoice_request, INVOICE_REQUEST_TYPES, find_record, decode_tu64 and \
+          verify_signature.",
+    solution: "Compare the records in INVOICE_REQUEST_TYPES, check node_id against offer.issuer_id or the last \
+               blinded node id of a path, check the amount against invreq_amount or the offer's amount times the \
+               quantity, then verify_signature(\"invoice\", ...).",
+};
+
+const EXERCISE_10: ExerciseCheck = ExerciseCheck {
+    exercise: 10,
+    concept: "Each side adds its own inputs with serial ids of its own parity, and each input must be a segwit \
+              output nobody else spends, so the txid can't change under either side.",
+    api: "is_initiator_serial_id, constructor.inputs, is_witness_program, MAX_RECEIVED_TX_ADDITIONS, \
+          NegotiatedInput and the InteractiveTxError variants.",
+    solution: "Reject the wrong parity, a used serial id, a prevtx_out out of range, a non-segwit prevout, an input \
+               already spent and too many additions, then insert the TxIn with the message's sequence and count it.",
+};
+
+const EXERCISE_11: ExerciseCheck = ExerciseCheck {
+    exercise: 11,
+    concept: "Outputs follow the same serial id rules as inputs, and their amount must be at least our dust limit and \
+              at most all the bitcoin there is.",
+    api: "is_initiator_serial_id, constructor.outputs, constructor.dust_limit, Amount::MAX_MONEY and \
+          MAX_RECEIVED_TX_ADDITIONS.",
+    solution: "Reject the wrong parity, a used serial id, an amount below dust_limit or above MAX_MONEY and too many \
+               additions, then insert the TxOut and count it.",
+};
+
+const EXERCISE_12: ExerciseCheck = ExerciseCheck {
+    exercise: 12,
+    concept: "Both sides build the same transaction from what was negotiated: every input and output in serial id \
+              order, whoever added it.",
+    api: "constructor.is_complete(), funding_script_pubkey, the inputs and outputs maps, which are sorted by serial \
+          id, and build_transaction.",
+    solution: "Return NotComplete or MissingFundingOutput if needed, then build_transaction(Version::TWO, \
+               constructor.locktime, inputs, outputs) from the values of both maps.",
+};
+
+const EXERCISE_13: ExerciseCheck = ExerciseCheck {
+    exercise: 13,
+    concept: "A splice-in spends the old funding output and a wallet UTXO into a bigger funding output, and the \
+              wallet UTXO pays for the fee.",
+    api: "fee_for_weight with SPLICE_IN_TX_WEIGHT, contribute_input and contribute_output on the constructor, and \
+          Sequence::ENABLE_RBF_NO_LOCKTIME.",
+    solution: "change = wallet value - splice_in_amount - fee, or InsufficientFunds; contribute the old funding \
+               output first, then the wallet UTXO, then the new funding output of old value + splice_in_amount and \
+               the change.",
+};
+
+const EXERCISE_14: ExerciseCheck = ExerciseCheck {
+    exercise: 14,
+    concept: "After the splice, commitments spend the new funding output, and each balance grows by what that side \
+              spliced in.",
+    api: "funding_script.to_p2wsh() to find the output, ChannelParams with a new funding_outpoint, \
+          MilliSat::from_amount, checked_add, local_commitment_keys and CommitmentTransactionBuilder::from_channel.",
+    solution: "Find the new funding vout or return MissingFundingOutput, copy params with the new outpoint, add the \
+               splice amounts to the balances, and build the commitment for per_commitment_point from them.",
+};
+
+#[test]
+fn test_01_taproot_to_local() {
+    EXERCISE_1.run(|| {
+        let secp = Secp256k1::new();
+        let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let script_pubkey = taproot_to_local(&revocation_pubkey, &to_local_delayed_pubkey, 144).unwrap();
+        assert!(script_pubkey.is_p2tr());
+
+        let spend_info = taproot_to_local_spend_info(&revocation_pubkey, &to_local_delayed_pubkey, 144);
+        assert_eq!(spend_info.internal_key(), taproot_nums_point());
+        assert_eq!(&script_pubkey.as_bytes()[2..], &spend_info.output_key().serialize()[..]);
+
+        // both leaves can be proven to be committed to by the output key
+        let to_delay_script = build_taproot_to_delay_script(&to_local_delayed_pubkey, 144);
+        let revoke_script = build_taproot_revoke_script(&revocation_pubkey, &to_local_delayed_pubkey);
+        for leaf in [to_delay_script, revoke_script] {
+            let control_block = spend_info.control_block(&(leaf.clone(), LeafVersion::TapScript)).unwrap();
+            assert!(control_block.verify_taproot_commitment(&secp, spend_info.output_key().to_inner(), &leaf));
+        }
+
+        assert_ne!(taproot_to_local(&revocation_pubkey, &to_local_delayed_pubkey, 145).unwrap(), script_pubkey);
+        assert_eq!(
+            taproot_to_local(&revocation_pubkey, &to_local_delayed_pubkey, 0),
+            Err(ExerciseError::InvalidToSelfDelay(0))
+        );
+    })
+}
+
+#[test]
+fn test_02_musig_funding_output() {
+    EXERCISE_2.run(|| {
+        let secp = Secp256k1::new();
+        let alice = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let bob = FundingPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let funding_script = musig_funding_output(&alice, &bob).unwrap();
+        assert!(funding_script.is_p2tr());
+
+        // the keys are sorted first, so both sides get the same output
+        assert_eq!(musig_funding_output(&bob, &alice).unwrap(), funding_script);
+
+        let key_agg = musig_funding_key_agg(&alice, &bob).unwrap();
+        assert_eq!(funding_script, ScriptBuf::new_p2tr(&secp, key_agg.aggregated_pubkey(), None));
+        assert_ne!(key_agg.aggregated_pubkey(), alice.0.inner.x_only_public_key().0);
+        assert_ne!(key_agg.aggregated_pubkey(), bob.0.inner.x_only_public_key().0);
+    })
+}
+
+#[test]
+fn test_03_sign_musig_funding_spend() {
+    EXERCISE_3.run(|| {
+        let secp = Secp256k1::new();
+        let alice_key = secp256k1_private_key(&[0x01; 32]);
+        let bob_key = secp256k1_private_key(&[0x02; 32]);
+        let alice = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let bob = FundingPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+        let funding_output = build_output(Amount::from_sat(5_000_000), musig_funding_output(&alice, &bob).unwrap());
+        let txin = get_funding_input(DUMMY_FUNDING_TXID.to_string(), 0);
+        let payout = build_output(Amount::from_sat(4_999_000), ScriptBuf::new_p2wpkh(&alice.0.wpubkey_hash().unwrap()));
+        let tx = build_transaction(Version::TWO, LockTime::ZERO, vec![txin], vec![payout]);
+
+        let signed = sign_musig_funding_spend(tx.clone(), &funding_output, &alice_key, &bob_key, [0xaa; 32], [0xbb; 32])
+            .unwrap();
+        print_transaction(&signed);
+
+        // a single 64-byte signature, valid for the funding output's tweaked key
+        assert_eq!(signed.input[0].witness.len(), 1);
+        let signature = taproot::Signature::from_slice(&signed.input[0].witness[0]).unwrap();
+        assert_eq!(signed.input[0].witness[0].len(), 64);
+
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(std::slice::from_ref(&funding_output)), TapSighashType::Default)
+            .unwrap();
+        let output_key = musig_funding_key_agg(&alice, &bob).unwrap().aggregated_pubkey().tap_tweak(&secp, None).0;
+        let msg = Message::from_digest(sighash.to_byte_array());
+        assert!(secp.verify_schnorr(&signature.signature, &msg, &output_key.to_inner()).is_ok());
+
+        // fresh session randomness gives a different, equally valid signature
+        let resigned = sign_musig_funding_spend(tx, &funding_output, &alice_key, &bob_key, [0xcc; 32], [0xbb; 32]).unwrap();
+        assert_ne!(resigned.input[0].witness, signed.input[0].witness);
+        let signature = taproot::Signature::from_slice(&resigned.input[0].witness[0]).unwrap();
+        assert!(secp.verify_schnorr(&signature.signature, &msg, &output_key.to_inner()).is_ok());
+    })
+}
+
+/// Bob claims a PTLC Alice offered him, spending it to his own key.
+fn ptlc_claim_setup() -> (Transaction, TxOut) {
+    let alice_htlc = HtlcPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let bob_htlc = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+
+    let ptlc_txout = build_output(Amount::from_sat(400_000), ptlc_output(&alice_htlc, &bob_htlc, 500));
+    let txin = TxIn { sequence: Sequence::ZERO, ..get_funding_input(DUMMY_FUNDING_TXID.to_string(), 2) };
+    let payout = build_output(Amount::from_sat(399_000), ScriptBuf::new_p2wpkh(&bob_htlc.0.wpubkey_hash().unwrap()));
+
+    (build_transaction(Version::TWO, LockTime::ZERO, vec![txin], vec![payout]), ptlc_txout)
+}
+
+#[test]
+fn test_04_claim_ptlc() {
+    EXERCISE_4.run(|| {
+        let secp = Secp256k1::new();
+        let alice_htlc_key = secp256k1_private_key(&[0x01; 32]);
+        let bob_htlc_key = secp256k1_private_key(&[0x02; 32]);
+        let alice_htlc = HtlcPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let bob_htlc = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let payment_secret = secp256k1_private_key(&[0x07; 32]);
+        let payment_point = payment_secret.public_key(&secp);
+
+        let (claim_tx, ptlc_txout) = ptlc_claim_setup();
+        assert!(ptlc_txout.script_pubkey.is_p2tr());
+
+        // Alice only hands Bob an adaptor signature for the payment point
+        let ptlc_script = build_ptlc_witness_script(&alice_htlc, &bob_htlc, 500);
+        let sighash = compute_tapscript_sighash(&claim_tx, 0, std::slice::from_ref(&ptlc_txout), &ptlc_script);
+        let adaptor = create_adaptor_signature(&alice_htlc_key, &sighash, &payment_point, [0x99; 32]);
+
+        let claimed = claim_ptlc(claim_tx.clone(), &ptlc_txout, &alice_htlc, &bob_htlc_key, 500, &adaptor, &payment_secret)
+            .unwrap();
+        print_transaction(&claimed);
+
+        let witness: Vec<&[u8]> = claimed.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 5);
+        assert_eq!(witness[2], &[1u8][..]);
+        assert_eq!(witness[3], ptlc_script.as_bytes());
+
+        let msg = Message::from_digest(sighash);
+        let alice_xonly = alice_htlc.0.inner.x_only_public_key().0;
+        let bob_xonly = bob_htlc.0.inner.x_only_public_key().0;
+        let alice_signature = bitcoin::secp256k1::schnorr::Signature::from_slice(witness[0]).unwrap();
+        let bob_signature = bitcoin::secp256k1::schnorr::Signature::from_slice(witness[1]).unwrap();
+        assert!(secp.verify_schnorr(&alice_signature, &msg, &alice_xonly).is_ok());
+        assert!(secp.verify_schnorr(&bob_signature, &msg, &bob_xonly).is_ok());
+
+        let control_block = taproot::ControlBlock::decode(witness[4]).unwrap();
+        let output_key = bitcoin::key::XOnlyPublicKey::from_slice(&ptlc_txout.script_pubkey.as_bytes()[2..]).unwrap();
+        assert!(control_block.verify_taproot_commitment(&secp, output_key, &ptlc_script));
+
+        // the adaptor signature is worthless with the wrong secret
+        let wrong_secret = secp256k1_private_key(&[0x08; 32]);
+        assert_eq!(
+            claim_ptlc(claim_tx, &ptlc_txout, &alice_htlc, &bob_htlc_key, 500, &adaptor, &wrong_secret),
+            Err(ExerciseError::InvalidAdaptorSignature)
+        );
+    })
+}
+
+#[test]
+fn test_05_extract_payment_secret() {
+    EXERCISE_5.run(|| {
+        let secp = Secp256k1::new();
+        let alice_htlc_key = secp256k1_private_key(&[0x01; 32]);
+        let bob_htlc_key = secp256k1_private_key(&[0x02; 32]);
+        let alice_htlc = HtlcPubkey(pubkey_from_private_key(&[0x01; 32]));
+        let bob_htlc = HtlcPubkey(pubkey_from_private_key(&[0x02; 32]));
+        let payment_secret = secp256k1_private_key(&[0x07; 32]);
+        let payment_point = payment_secret.public_key(&secp);
+
+        let (claim_tx, ptlc_txout) = ptlc_claim_setup();
+        let ptlc_script = build_ptlc_witness_script(&alice_htlc, &bob_htlc, 500);
+        let sighash = compute_tapscript_sighash(&claim_tx, 0, std::slice::from_ref(&ptlc_txout), &ptlc_script);
+        let adaptor = create_adaptor_signature(&alice_htlc_key, &sighash, &payment_point, [0x99; 32]);
+
+        assert_eq!(extract_payment_secret(&claim_tx, &adaptor, &payment_point), None);
+
+        let claimed = claim_ptlc(claim_tx, &ptlc_txout, &alice_htlc, &bob_htlc_key, 500, &adaptor, &payment_secret).unwrap();
+        assert_eq!(extract_payment_secret(&claimed, &adaptor, &payment_point), Some(payment_secret));
+    })
+}
+
+/// The BOLT 4 route blinding test vector: Eve blinds a path to herself
+/// through Dave, and Bob's path to Carol is put in front of it.
+fn route_blinding_node(byte: u8) -> (SecretKey, secp256k1PublicKey) {
+    let secret = secp256k1_private_key(&[byte; 32]);
+    (secret, secret.public_key(&Secp256k1::new()))
+}
+
+const BOB_RECIPIENT_DATA: &str = "011a0000000000000000000000000000000000000000000000000000020800000000000006c10a0800240000009627100c06000b69e505dc0e00fd023103123456";
+const CAROL_RECIPIENT_DATA: &str = "020800000000000004510821031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f0a0800300000006401f40c06000b69c105dc0e00";
+const DAVE_RECIPIENT_DATA: &str = "01230000000000000000000000000000000000000000000000000000000000000000000000020800000000000002310a060090000000fa0c06000b699105dc0e00";
+const EVE_RECIPIENT_DATA: &str = "011a00000000000000000000000000000000000000000000000000000604deadbeef0c06000b690105dc0e0f020000000000000000000000000000fdffff0206c1";
+
+const BLINDED_NODE_IDS: [&str; 4] = [
+    "03da173ad2aee2f701f17e59fbd16cb708906d69838a5f088e8123fb36e89a2c25",
+    "02e466727716f044290abf91a14a6d90e87487da160c2a3cbd0d465d7a78eb83a7",
+    "036861b366f284f0a11738ffbf7eda46241a8977592878fe3175ae1d1e4754eccf",
+    "021982a48086cb8984427d3727fe35a03d396b234f0701f5249daa12e8105c8dae",
+];
+
+const ENCRYPTED_RECIPIENT_DATA: [&str; 4] = [
+    "cd4100ff9c09ed28102b210ac73aa12d63e90852cebc496c49f57c49982088b49f2e70b99287fdee0aa58aa39913ab405813b999f66783aa2fe637b3cda91ffc0913c30324e2c6ce327e045183e4bffecb",
+    "cc0f16524fd7f8bb0b1d8d40ad71709ef140174c76faa574cac401bb8992fef76c4d004aa485dd599ed1cf2715f57ff62da5aaec5d7b10d59b04d8a9d77e472b9b3ecc2179334e411be22fa4c02b467c7e",
+    "0fa0a72cff3b64a3d6e1e4903cf8c8b0a17144aeb249dcb86561adee1f679ee8db3e561d9c43815fd4bcebf6f58c546da0cd8a9bf5cebd0d554802f6c0255e28e4a27343f761fe518cd897463187991105",
+    "da1a7e5f7881219884beae6ae68971de73bab4c3055d9865b1afb60724a2e4d3f0489ad884f7f3f77149209f0df51efd6b276294a02e3949c7254fbc8b5cab58212d9a78983e1cf86fe218b30c4ca8f6d8",
+];
+
+/// Bob to Carol under session key 0x02.., then Dave to Eve under 0x01...
+fn route_blinding_paths() -> (BlindedPath, BlindedPath) {
+    let (_, bob) = route_blinding_node(0x42);
+    let (_, carol) = route_blinding_node(0x43);
+    let (_, dave) = route_blinding_node(0x44);
+    let (_, eve) = route_blinding_node(0x45);
+
+    let bob_carol = build_blinded_path(
+        &secp256k1_private_key(&[0x02; 32]),
+        &bob,
+        &[carol],
+        &[hex::decode(BOB_RECIPIENT_DATA).unwrap(), hex::decode(CAROL_RECIPIENT_DATA).unwrap()],
+    )
+    .unwrap();
+    let dave_eve = build_blinded_path(
+        &secp256k1_private_key(&[0x01; 32]),
+        &dave,
+        &[eve],
+        &[hex::decode(DAVE_RECIPIENT_DATA).unwrap(), hex::decode(EVE_RECIPIENT_DATA).unwrap()],
+    )
+    .unwrap();
+    (bob_carol, dave_eve)
+}
+
+#[test]
+fn test_06_build_blinded_path() {
+    EXERCISE_6.run(|| {
+        let (bob_carol, dave_eve) = route_blinding_paths();
+        assert_eq!(bob_carol.introduction_node_id, route_blinding_node(0x42).1);
+        assert_eq!(bob_carol.blinding_point, route_blinding_node(0x02).1);
+        assert_eq!(dave_eve.blinding_point, route_blinding_node(0x01).1);
+
+        let blinded_hops: Vec<_> = bob_carol.blinded_hops.iter().chain(&dave_eve.blinded_hops).collect();
+        for (i, hop) in blinded_hops.iter().enumerate() {
+            assert_eq!(hex::encode(hop.blinded_node_id.serialize()), BLINDED_NODE_IDS[i], "blinded node id {}", i);
+            assert_eq!(hex::encode(&hop.encrypted_recipient_data), ENCRYPTED_RECIPIENT_DATA[i], "encrypted data {}", i);
+        }
+
+        assert_eq!(
+            build_blinded_path(&secp256k1_private_key(&[0x02; 32]), &bob_carol.introduction_node_id, &[], &[]),
+            Err(OnionError::HopCountMismatch { hops: 1, payloads: 0 })
+        );
+    })
+}
+
+#[test]
+fn test_07_pay_through_blinded_path() {
+    EXERCISE_7.run(|| {
+        let (bob_secret, bob) = route_blinding_node(0x42);
+        let (carol_secret, carol) = route_blinding_node(0x43);
+        let (dave_secret, dave) = route_blinding_node(0x44);
+
+        // Dave hides himself behind Bob and Carol. Each hop is told which
+        // channel to forward over; Dave gets a path_id to recognise the payment.
+        let scid =
+            |value: u64| write_tlv_stream(&[TlvRecord::new(SHORT_CHANNEL_ID_TLV, value.to_be_bytes().to_vec())]).unwrap();
+        let recipient_data =
+            vec![scid(1), scid(2), write_tlv_stream(&[TlvRecord::new(PATH_ID_TLV, vec![0xd1; 32])]).unwrap()];
+        let path = build_blinded_path(&secp256k1_private_key(&[0x02; 32]), &bob, &[carol, dave], &recipient_data).unwrap();
+
+        // The sender never learns Carol's or Dave's node ids.
+        let hops = path.onion_hops();
+        assert_eq!(hops[0], bob);
+        assert!(!hops.contains(&carol) && !hops.contains(&dave));
+
+        let payment_hash = [0x42; 32];
+        let payloads = path.onion_payloads(MilliSat(100_000), 747_000, MilliSat(100_000));
+        let onion = construct_onion_packet(&secp256k1_private_key(&[0x03; 32]), &hops, &payloads, &payment_hash).unwrap();
+        let known_types = [
+            AMT_TO_FORWARD_TLV,
+            OUTGOING_CLTV_VALUE_TLV,
+            ENCRYPTED_RECIPIENT_DATA_TLV,
+            CURRENT_BLINDING_POINT_TLV,
+            TOTAL_AMOUNT_MSAT_TLV,
+        ];
+
+        // Bob peels with his real key and finds the blinding point in his payload.
+        let PeeledOnion::Forward { payload, next_packet } = peel_onion(&onion, &bob_secret, &payment_hash).unwrap() else {
+            panic!("Bob should forward");
+        };
+        let records = read_tlv_stream(&payload, &known_types).unwrap();
+        let blinding_point =
+            secp256k1PublicKey::from_slice(find_record(&records, CURRENT_BLINDING_POINT_TLV).unwrap()).unwrap();
+        let encrypted = find_record(&records, ENCRYPTED_RECIPIENT_DATA_TLV).unwrap();
+        let bob_data = decrypt_blinded_hop(&bob_secret, &blinding_point, encrypted).unwrap();
+        assert_eq!(bob_data.recipient_data, recipient_data[0]);
+
+        // Carol's layer is encrypted to her blinded id; her real key can't open it.
+        assert_eq!(peel_onion(&next_packet, &carol_secret, &payment_hash), Err(OnionError::InvalidHmac));
+        let carol_key = blinded_node_key(&carol_secret, &bob_data.next_blinding_point).unwrap();
+        assert_eq!(carol_key.public_key(&Secp256k1::new()), path.blinded_hops[1].blinded_node_id);
+        let PeeledOnion::Forward { payload, next_packet } = peel_onion(&next_packet, &carol_key, &payment_hash).unwrap()
+        else {
+            panic!("Carol should forward");
+        };
+        let records = read_tlv_stream(&payload, &known_types).unwrap();
+        let encrypted = find_record(&records, ENCRYPTED_RECIPIENT_DATA_TLV).unwrap();
+        let carol_data = decrypt_blinded_hop(&carol_secret, &bob_data.next_blinding_point, encrypted).unwrap();
+        assert_eq!(carol_data.recipient_data, recipient_data[1]);
+
+        let dave_key = blinded_node_key(&dave_secret, &carol_data.next_blinding_point).unwrap();
+        let PeeledOnion::Receive { payload } = peel_onion(&next_packet, &dave_key, &payment_hash).unwrap() else {
+            panic!("Dave should receive");
+        };
+        let records = read_tlv_stream(&payload, &known_types).unwrap();
+        assert_eq!(decode_tu64(find_record(&records, AMT_TO_FORWARD_TLV).unwrap(), 8), Ok(100_000));
+        assert_eq!(decode_tu64(find_record(&records, TOTAL_AMOUNT_MSAT_TLV).unwrap(), 8), Ok(100_000));
+        let encrypted = find_record(&records, ENCRYPTED_RECIPIENT_DATA_TLV).unwrap();
+        let dave_data = decrypt_blinded_hop(&dave_secret, &carol_data.next_blinding_point, encrypted).unwrap();
+        assert_eq!(dave_data.recipient_data, recipient_data[2]);
+
+        // Data meant for Dave doesn't decrypt under Carol's blinding point.
+        assert_eq!(
+            decrypt_blinded_hop(&dave_secret, &bob_data.next_blinding_point, encrypted),
+            Err(OnionError::InvalidRecipientData)
+        );
+    })
+}
+
+/// An offer of 100 USD cents from `0x41...`, and the `invoice_request` the
+/// BOLT 12 test vectors make for it with the payer key `0x42...`.
+const MATHEMATICAL_TREATISE_OFFER: &str =
+    "lno1qcp4256ypqqkgzshgysy6ct5dpjk6ct5d93kzmpq23ex2ct5d9ek293pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const MATHEMATICAL_TREATISE_REQUEST: &str = "lnr1qqyqqqqqqqqqqqqqqcp4256ypqqkgzshgysy6ct5dpjk6ct5d93kzmpq23ex2ct5d9ek293pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpjkppqvjx204vgdzgsqpvcp4mldl3plscny0rt707gvpdh6ndydfacz43euzqhrurageg3n7kafgsek6gz3e9w52parv8gs2hlxzk95tzeswywffxlkeyhml0hh46kndmwf4m6xma3tkq2lu04qz3slje2rfthc89vss";
+
+/// A 10,000 msat offer from `0x41...`, and one that leaves the amount open.
+const PRICED_OFFER: &str = "lno1pqpzwyq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const DONATION_OFFER: &str = "lno1pgx9getnwss8vetrw3hhyuckyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg";
+
+fn offer_keypair(byte: u8) -> Keypair {
+    Keypair::from_secret_key(&Secp256k1::new(), &secp256k1_private_key(&[byte; 32]))
+}
+
+#[test]
+fn test_08_create_invoice_request() {
+    EXERCISE_8.run(|| {
+        let offer = decode_offer(MATHEMATICAL_TREATISE_OFFER).unwrap();
+        let payer = offer_keypair(0x42);
+
+        let request = create_invoice_request(&offer, &[0; 8], &payer, Network::Bitcoin, None).unwrap();
+        assert_eq!(encode_bolt12(INVOICE_REQUEST_HRP, &request), MATHEMATICAL_TREATISE_REQUEST);
+
+        // The offer lists no chains, so it can only be paid on bitcoin.
+        assert_eq!(
+            create_invoice_request(&offer, &[0; 8], &payer, Network::Regtest, None),
+            Err(OfferError::UnsupportedChain)
+        );
+
+        // A donation has no price, so the payer has to say how much it sends.
+        let donation = decode_offer(DONATION_OFFER).unwrap();
+        assert_eq!(
+            create_invoice_request(&donation, &[0; 8], &payer, Network::Bitcoin, None),
+            Err(OfferError::MissingField("invreq_amount"))
+        );
+        let request = create_invoice_request(&donation, &[0; 8], &payer, Network::Bitcoin, Some(MilliSat(5_000))).unwrap();
+        let records = read_invoice_request(&request).unwrap();
+        assert_eq!(decode_tu64(find_record(&records, INVREQ_AMOUNT_TLV).unwrap(), 8), Ok(5_000));
+    })
+}
+
+/// The payee's side: copy the request, add a payment hash, a blinded path
+/// to itself and an amount, and sign it all.
+fn sign_bolt12_invoice(
+    invoice_request: &[u8],
+    node_key: &Keypair,
+    path: &BlindedPath,
+    payment_hash: PaymentHash,
+    amount_msat: u64,
+) -> Vec<u8> {
+    let pay_info = BlindedPayInfo {
+        fee_base_msat: 1_000,
+        fee_proportional_millionths: 100,
+        cltv_expiry_delta: 144,
+        htlc_minimum_msat: MilliSat(1_000),
+        htlc_maximum_msat: MilliSat(100_000_000),
+        features: Vec::new(),
+    };
+    let mut records: Vec<TlvRecord> = read_invoice_request(invoice_request)
+        .unwrap()
+        .into_iter()
+        .filter(|record| record.tlv_type != SIGNATURE_TLV)
+        .collect();
+    records.extend([
+        TlvRecord::new(INVOICE_PATHS_TLV, path.encode()),
+        TlvRecord::new(INVOICE_BLINDEDPAY_TLV, pay_info.encode()),
+        TlvRecord::new(INVOICE_CREATED_AT_TLV, encode_tu64(1_700_000_000)),
+        TlvRecord::new(INVOICE_PAYMENT_HASH_TLV, payment_hash.0.to_vec()),
+        TlvRecord::new(INVOICE_AMOUNT_TLV, encode_tu64(amount_msat)),
+        TlvRecord::new(INVOICE_NODE_ID_TLV, node_key.public_key().serialize().to_vec()),
+    ]);
+    records.sort_by_key(|record| record.tlv_type);
+
+    let signature = Secp256k1::new().sign_schnorr_no_aux_rand(&signature_digest("invoice", &records), node_key);
+    records.push(TlvRecord::new(SIGNATURE_TLV, signature.serialize().to_vec()));
+    write_tlv_stream(&records).unwrap()
+}
+
+#[test]
+fn test_09_verify_bolt12_invoice() {
+    EXERCISE_9.run(|| {
+        let offer = decode_offer(PRICED_OFFER).unwrap();
+        let alice = offer_keypair(0x41);
+        let request = create_invoice_request(&offer, &[0; 8], &offer_keypair(0x42), Network::Bitcoin, None).unwrap();
+
+        // Alice hides behind Bob, who the payer will route to.
+        let (_, bob) = route_blinding_node(0x43);
+        let path = build_blinded_path(&secp256k1_private_key(&[0x03; 32]), &bob, &[alice.public_key()], &[vec![], vec![]])
+            .unwrap();
+        let payment_hash = PaymentHash([0x2a; 32]);
+
+        let invoice = sign_bolt12_invoice(&request, &alice, &path, payment_hash, 10_000);
+        let verified = verify_bolt12_invoice(&invoice, &request, &offer).unwrap();
+        assert_eq!(verified.payment_hash, payment_hash);
+        assert_eq!(verified.amount, MilliSat(10_000));
+        assert_eq!(verified.relative_expiry, DEFAULT_RELATIVE_EXPIRY);
+        assert_eq!(verified.paths[0].0, path);
+        assert_eq!(verified.paths[0].1.cltv_expiry_delta, 144);
+
+        // The offer set the price.
+        let invoice = sign_bolt12_invoice(&request, &alice, &path, payment_hash, 20_000);
+        assert_eq!(verify_bolt12_invoice(&invoice, &request, &offer).map(|_| ()), Err(OfferError::AmountMismatch));
+
+        // Only the offer's issuer may answer.
+        let invoice = sign_bolt12_invoice(&request, &offer_keypair(0x43), &path, payment_hash, 10_000);
+        assert_eq!(verify_bolt12_invoice(&invoice, &request, &offer).map(|_| ()), Err(OfferError::UnexpectedNodeId));
+
+        // An invoice for somebody else's request doesn't pay for this one.
+        let other_request = create_invoice_request(&offer, &[1; 8], &offer_keypair(0x44), Network::Bitcoin, None).unwrap();
+        let invoice = sign_bolt12_invoice(&other_request, &alice, &path, payment_hash, 10_000);
+        assert_eq!(verify_bolt12_invoice(&invoice, &request, &offer).map(|_| ()), Err(OfferError::InvoiceRequestMismatch));
+
+        // Changing any field after signing breaks the signature.
+        let mut records =
+            Bolt12Invoice::decode(&sign_bolt12_invoice(&request, &alice, &path, payment_hash, 10_000)).unwrap().records;
+        records.iter_mut().find(|record| record.tlv_type == INVOICE_CREATED_AT_TLV).unwrap().value =
+            encode_tu64(1_800_000_000);
+        let invoice = write_tlv_stream(&records).unwrap();
+        assert_eq!(verify_bolt12_invoice(&invoice, &request, &offer).map(|_| ()), Err(OfferError::InvalidSignature));
+    })
+}
+
+/// A confirmed transaction paying `value` to `script_pubkey`, for a side
+/// of a dual-funded open to spend from.
+fn wallet_prevtx(value: u64, script_pubkey: ScriptBuf) -> Transaction {
+    let txin = TxIn {
+        previous_output: OutPoint::new(Txid::all_zeros(), value as u32),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    };
+    build_transaction(
+        Version::TWO,
+        LockTime::ZERO,
+        vec![txin],
+        vec![build_output(Amount::from_sat(value), script_pubkey)],
+    )
+}
+
+fn segwit_script(byte: u8) -> ScriptBuf {
+    ScriptBuf::new_p2wsh(&ScriptBuf::from(vec![byte]).wscript_hash())
+}
+
+/// Bob's side of the negotiation. Alice, the initiator, uses even serial ids.
+fn acceptor() -> InteractiveTxConstructor {
+    InteractiveTxConstructor::new([0x42; 32], false, segwit_script(0xff), Amount::from_sat(354), LockTime::ZERO)
+}
+
+fn tx_add_input(serial_id: u64, prevtx: Transaction, prevtx_out: u32) -> TxAddInput {
+    TxAddInput { channel_id: [0x42; 32], serial_id, prevtx, prevtx_out, sequence: 0xfffffffd }
+}
+
+#[test]
+fn test_10_receive_tx_add_input() {
+    EXERCISE_10.run(|| {
+        let mut bob = acceptor();
+        let prevtx = wallet_prevtx(100_000, segwit_script(0x01));
+
+        receive_tx_add_input(&mut bob, &tx_add_input(0, prevtx.clone(), 0)).unwrap();
+        let input = &bob.inputs[&0];
+        assert_eq!(input.txin.previous_output, OutPoint::new(prevtx.compute_txid(), 0));
+        assert_eq!(input.txin.sequence, Sequence(0xfffffffd));
+        assert_eq!(input.prevout, prevtx.output[0]);
+        assert_eq!(bob.received_inputs, 1);
+
+        // odd serial ids are Bob's own
+        assert_eq!(
+            receive_tx_add_input(&mut bob, &tx_add_input(1, wallet_prevtx(1, segwit_script(0x01)), 0)),
+            Err(InteractiveTxError::WrongSerialIdParity(1))
+        );
+        assert_eq!(
+            receive_tx_add_input(&mut bob, &tx_add_input(0, wallet_prevtx(2, segwit_script(0x01)), 0)),
+            Err(InteractiveTxError::DuplicateSerialId(0))
+        );
+        assert_eq!(
+            receive_tx_add_input(&mut bob, &tx_add_input(2, prevtx.clone(), 1)),
+            Err(InteractiveTxError::PrevoutOutOfRange(1))
+        );
+        assert_eq!(
+            receive_tx_add_input(&mut bob, &tx_add_input(2, prevtx.clone(), 0)),
+            Err(InteractiveTxError::DuplicateInput(OutPoint::new(prevtx.compute_txid(), 0)))
+        );
+        let legacy = wallet_prevtx(3, ScriptBuf::new_p2pkh(&pubkey_from_private_key(&[0x01; 32]).pubkey_hash()));
+        assert_eq!(receive_tx_add_input(&mut bob, &tx_add_input(2, legacy, 0)), Err(InteractiveTxError::NonSegwitInput));
+
+        bob.received_inputs = 4096;
+        assert_eq!(
+            receive_tx_add_input(&mut bob, &tx_add_input(2, wallet_prevtx(4, segwit_script(0x01)), 0)),
+            Err(InteractiveTxError::TooManyAdditions)
+        );
+        assert_eq!(bob.inputs.len(), 1);
+    })
+}
+
+#[test]
+fn test_11_receive_tx_add_output() {
+    EXERCISE_11.run(|| {
+        let mut bob = acceptor();
+        let tx_add_output = |serial_id, sats| TxAddOutput {
+            channel_id: [0x42; 32],
+            serial_id,
+            sats: Amount::from_sat(sats),
+            script: segwit_script(0x02),
+        };
+
+        receive_tx_add_output(&mut bob, &tx_add_output(4, 50_000)).unwrap();
+        assert_eq!(bob.outputs[&4], build_output(Amount::from_sat(50_000), segwit_script(0x02)));
+        assert_eq!(bob.received_outputs, 1);
+
+        assert_eq!(
+            receive_tx_add_output(&mut bob, &tx_add_output(5, 50_000)),
+            Err(InteractiveTxError::WrongSerialIdParity(5))
+        );
+        assert_eq!(
+            receive_tx_add_output(&mut bob, &tx_add_output(4, 50_000)),
+            Err(InteractiveTxError::DuplicateSerialId(4))
+        );
+        assert_eq!(
+            receive_tx_add_output(&mut bob, &tx_add_output(6, 353)),
+            Err(InteractiveTxError::DustOutput { value: 353, dust_limit: 354 })
+        );
+        assert_eq!(
+            receive_tx_add_output(&mut bob, &tx_add_output(6, Amount::MAX_MONEY.to_sat() + 1)),
+            Err(InteractiveTxError::OutputAmountOutOfRange(Amount::MAX_MONEY.to_sat() + 1))
+        );
+        assert_eq!(bob.outputs.len(), 1);
+    })
+}
+
+#[test]
+fn test_12_build_interactive_funding_transaction() {
+    EXERCISE_12.run(|| {
+        let mut bob = acceptor();
+        let alice_prevtx = wallet_prevtx(300_000, segwit_script(0x01));
+        let bob_prevtx = wallet_prevtx(200_000, segwit_script(0x02));
+
+        // Bob's input and change come between Alice's by serial id, whatever
+        // order they were added in
+        bob.contribute_input(bob_prevtx.clone(), 0, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        bob.contribute_output(build_output(Amount::from_sat(99_000), segwit_script(0x02)));
+        bob.handle_message(&WireMessage::TxAddInput(tx_add_input(4, alice_prevtx.clone(), 0))).unwrap();
+        bob.handle_message(&WireMessage::TxAddOutput(TxAddOutput {
+            channel_id: [0x42; 32],
+            serial_id: 0,
+            sats: Amount::from_sat(400_000),
+            script: segwit_script(0xff),
+        }))
+        .unwrap();
+        assert_eq!(build_interactive_funding_transaction(&bob), Err(InteractiveTxError::NotComplete));
+
+        // Bob sends his two additions, and then has nothing more to add
+        assert!(matches!(bob.next_message(), Some(WireMessage::TxAddInput(_))));
+        assert!(matches!(bob.next_message(), Some(WireMessage::TxAddOutput(_))));
+        assert!(matches!(bob.next_message(), Some(WireMessage::TxComplete(_))));
+        bob.handle_message(&WireMessage::TxComplete(TxComplete { channel_id: [0x42; 32] })).unwrap();
+        assert!(bob.is_complete());
+
+        let funding_tx = build_interactive_funding_transaction(&bob).unwrap();
+        print_transaction(&funding_tx);
+        let spent: Vec<_> = funding_tx.input.iter().map(|input| input.previous_output.txid).collect();
+        assert_eq!(spent, vec![bob_prevtx.compute_txid(), alice_prevtx.compute_txid()]);
+        assert_eq!(funding_tx.output[0].script_pubkey, segwit_script(0xff));
+        assert_eq!(funding_tx.output[1].script_pubkey, segwit_script(0x02));
+        assert_eq!(funding_tx.input[0].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+
+        // a negotiation that ends without the funding output opens nothing
+        let mut bob = acceptor();
+        bob.next_message();
+        bob.handle_message(&WireMessage::TxComplete(TxComplete { channel_id: [0x42; 32] })).unwrap();
+        assert_eq!(build_interactive_funding_transaction(&bob), Err(InteractiveTxError::MissingFundingOutput));
+    })
+}
+
+fn key_manager(byte: u8) -> ChannelKeyManager {
+    ChannelKeyManager {
+        funding_key: secp256k1_private_key(&[byte; 32]),
+        payment_base_key: secp256k1_private_key(&[byte + 1; 32]),
+        delayed_payment_base_key: secp256k1_private_key(&[byte + 2; 32]),
+        htlc_base_key: secp256k1_private_key(&[byte + 3; 32]),
+        revocation_base_key: secp256k1_private_key(&[byte + 4; 32]),
+        commitment_seed: [byte + 5; 32],
+    }
+}
+
+/// A 1_000_000 sat channel between Alice and Bob, its funding transaction
+/// and its 2-of-2 funding script.
+fn spliceable_channel() -> (ChannelParams, Transaction, ScriptBuf) {
+    let (alice, bob) = (key_manager(0x10), key_manager(0x20));
+    let txin = TxIn {
+        previous_output: OutPoint::new(Txid::all_zeros(), 0),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    };
+    let (funding_tx, funding_script) = build_funding_transaction_with_script(
+        vec![txin],
+        &PublicKey::new(alice.pubkeys().funding_pubkey),
+        &PublicKey::new(bob.pubkeys().funding_pubkey),
+        Amount::from_sat(1_000_000),
+    );
+    let params = ChannelParams {
+        funding_outpoint: OutPoint::new(funding_tx.compute_txid(), 0),
+        to_self_delay: 144,
+        dust_limit: Amount::from_sat(354),
+        feerate_per_kw: 253,
+        htlc_fee_mode: HtlcFeeMode::Legacy,
+        is_funder: true,
+        local_keys: alice.pubkeys(),
+        remote_keys: bob.pubkeys(),
+    };
+    (params, funding_tx, funding_script)
+}
+
+fn splice_constructor(is_initiator: bool, funding_script: &ScriptBuf) -> InteractiveTxConstructor {
+    InteractiveTxConstructor::new([0x42; 32], is_initiator, funding_script.to_p2wsh(), Amount::from_sat(354), LockTime::ZERO)
+}
+
+#[test]
+fn test_13_contribute_splice_in() {
+    EXERCISE_13.run(|| {
+        let (_, funding_tx, funding_script) = spliceable_channel();
+        let wallet_script = p2wpkh_output_script(pubkey_from_private_key(&[0x05; 32]));
+        let wallet_prevtx = wallet_prevtx(600_000, wallet_script.clone());
+        let mut alice = splice_constructor(true, &funding_script);
+
+        contribute_splice_in(
+            &mut alice,
+            funding_tx.clone(),
+            0,
+            wallet_prevtx.clone(),
+            0,
+            Amount::from_sat(500_000),
+            wallet_script.clone(),
+            1_000,
+        )
+        .unwrap();
+
+        // the old funding output comes first, so it is input 0
+        assert_eq!(alice.inputs[&0].txin.previous_output, OutPoint::new(funding_tx.compute_txid(), 0));
+        assert_eq!(alice.inputs[&2].txin.previous_output, OutPoint::new(wallet_prevtx.compute_txid(), 0));
+        assert_eq!(alice.outputs[&4], build_output(Amount::from_sat(1_500_000), funding_script.to_p2wsh()));
+        // 600_000 in, 500_000 spliced in, and 997 weight at 1_000 sat/kw
+        assert_eq!(alice.outputs[&6], build_output(Amount::from_sat(100_000 - 997), wallet_script.clone()));
+
+        let mut alice = splice_constructor(true, &funding_script);
+        assert_eq!(
+            contribute_splice_in(&mut alice, funding_tx, 0, wallet_prevtx, 0, Amount::from_sat(600_000), wallet_script, 1_000),
+            Err(ExerciseError::InsufficientFunds { needed: 600_997, available: 600_000 })
+        );
+        assert!(alice.inputs.is_empty());
+    })
+}
+
+#[test]
+fn test_14_splice_commitment() {
+    EXERCISE_14.run(|| {
+        let (alice, bob) = (key_manager(0x10), key_manager(0x20));
+        let (params, funding_tx, funding_script) = spliceable_channel();
+        let wallet_key = secp256k1_private_key(&[0x05; 32]);
+        let wallet_script = p2wpkh_output_script(pubkey_from_private_key(&[0x05; 32]));
+        let wallet_prevtx = wallet_prevtx(600_000, wallet_script.clone());
+
+        // Alice splices 500_000 sats in, and Bob agrees to the transaction
+        let mut alice_constructor = splice_constructor(true, &funding_script);
+        let mut bob_constructor = splice_constructor(false, &funding_script);
+        contribute_splice_in(
+            &mut alice_constructor,
+            funding_tx.clone(),
+            0,
+            wallet_prevtx.clone(),
+            0,
+            Amount::from_sat(500_000),
+            wallet_script,
+            1_000,
+        )
+        .unwrap();
+        let (splice_tx, bob_splice_tx) = negotiate_funding_transaction(&mut alice_constructor, &mut bob_constructor).unwrap();
+        assert_eq!(splice_tx, bob_splice_tx);
+
+        // the old funding output is spent with the same 2-of-2 signatures as a commitment
+        let alice_funding_pubkey = PublicKey::new(alice.pubkeys().funding_pubkey);
+        let bob_funding_pubkey = PublicKey::new(bob.pubkeys().funding_pubkey);
+        let old_value = Amount::from_sat(1_000_000);
+        let alice_signature = sign_commitment_transaction(&splice_tx, &funding_script, old_value, &alice.funding_key);
+        let bob_signature = sign_commitment_transaction(&splice_tx, &funding_script, old_value, &bob.funding_key);
+        let mut signed_splice_tx = finalize_commitment(
+            splice_tx.clone(),
+            &funding_script,
+            &alice_funding_pubkey,
+            &alice_signature,
+            &bob_funding_pubkey,
+            &bob_signature,
+        )
+        .unwrap();
+        sign_p2wpkh_input(&mut signed_splice_tx, 1, Amount::from_sat(600_000), &wallet_key);
+        print_transaction(&signed_splice_tx);
+        let prevout = |outpoint: &OutPoint| {
+            [&funding_tx, &wallet_prevtx]
+                .into_iter()
+                .find(|tx| tx.compute_txid() == outpoint.txid)
+                .map(|tx| tx.output[outpoint.vout as usize].clone())
+        };
+        assert!(signed_splice_tx.verify(prevout).is_ok());
+
+        let channel = Channel::new(MilliSat::from_msat(700_000_000), MilliSat::from_msat(300_000_000));
+        let (spliced_params, commitment_tx) = splice_commitment(
+            &params,
+            &channel,
+            &signed_splice_tx,
+            &funding_script,
+            Amount::from_sat(500_000),
+            Amount::ZERO,
+            alice.per_commitment_point(0),
+        )
+        .unwrap();
+        assert_eq!(spliced_params.funding_outpoint, OutPoint::new(signed_splice_tx.compute_txid(), 0));
+        assert_eq!(commitment_tx.input[0].previous_output, spliced_params.funding_outpoint);
+        // Alice has the 500_000 sats she spliced in, less the commitment fee she pays as funder
+        let outputs: Vec<u64> = commitment_tx.output.iter().map(|output| output.value.to_sat()).collect();
+        assert_eq!(outputs, vec![300_000, 1_200_000 - 724 * 253 / 1000]);
+
+        // and the 2-of-2 signs the new commitment against the new funding output
+        let new_value = Amount::from_sat(1_500_000);
+        let alice_signature = sign_commitment_transaction(&commitment_tx, &funding_script, new_value, &alice.funding_key);
+        let bob_signature = sign_commitment_transaction(&commitment_tx, &funding_script, new_value, &bob.funding_key);
+        let signed_commitment = finalize_commitment(
+            commitment_tx,
+            &funding_script,
+            &alice_funding_pubkey,
+            &alice_signature,
+            &bob_funding_pubkey,
+            &bob_signature,
+        )
+        .unwrap();
+        assert!(signed_commitment.verify(|outpoint| prevout_of(&signed_splice_tx, outpoint)).is_ok());
+
+        assert_eq!(
+            splice_commitment(
+                &params,
+                &channel,
+                &wallet_prevtx,
+                &funding_script,
+                Amount::from_sat(500_000),
+                Amount::ZERO,
+                alice.per_commitment_point(0)
+            ),
+            Err(ChannelError::MissingFundingOutput)
+        );
+    })
+}
+
+fn prevout_of(tx: &Transaction, outpoint: &OutPoint) -> Option<TxOut> {
+    (tx.compute_txid() == outpoint.txid).then(|| tx.output[outpoint.vout as usize].clone())
+}
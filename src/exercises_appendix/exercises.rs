@@ -57,12 +57,12 @@ impl NodeKeysManager {
             .expect("Your RNG is busted");
 
         NodeKeysManager {
-            secp_ctx: secp_ctx,
-            node_secret: node_secret,
-            node_id: node_id,
-            shutdown_xpub: shutdown_xpub,
-            channel_master_key: channel_master_key,
-            seed: seed,
+            secp_ctx,
+            node_secret,
+            node_id,
+            shutdown_xpub,
+            channel_master_key,
+            seed,
         }
     }
 
@@ -117,7 +117,7 @@ pub enum Basepoint {
 /// Build the commitment secret from the seed and the commitment number
 impl ChannelKeysManager {
     pub fn build_commitment_secret(&self, idx: u64) -> [u8; 32] {
-        let mut res: [u8; 32] = self.commitment_seed.clone();
+        let mut res: [u8; 32] = self.commitment_seed;
         for i in 0..48 {
             let bitpos = 47 - i;
             if idx & (1 << bitpos) == (1 << bitpos) {
@@ -148,7 +148,7 @@ impl ChannelKeysManager {
         }
 
         // Second, convert basepoint to public key
-        let basepoint = PublicKey::from_secret_key(&secp_ctx, &basepoint_secret);
+        let basepoint = PublicKey::from_secret_key(secp_ctx, &basepoint_secret);
 
         // Third, get per-commitment-point with index
         let per_commitment_secret = self.build_commitment_secret(commitment_index);
@@ -179,9 +179,8 @@ impl ChannelKeysManager {
         
         let per_commitment_point = PublicKey::from_secret_key(secp_ctx, &per_commitment_private_key);
         
-        let revocation_pubkey = generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point);
         
-        revocation_pubkey
+        generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point)
 
       }
 }
@@ -189,8 +188,8 @@ impl ChannelKeysManager {
 fn key_step_derivation(seed: &[u8; 32], bytes: &[u8], previous_key: &[u8]) -> SecretKey {
     let mut sha = Sha256::engine();
     sha.input(seed);
-    sha.input(&previous_key[..]);
-    sha.input(&bytes[..]);
+    sha.input(previous_key);
+    sha.input(bytes);
     SecretKey::from_slice(&Sha256::from_engine(sha).to_byte_array())
         .expect("SHA-256 is busted")
 }
@@ -205,8 +204,7 @@ fn get_master_key(seed: [u8; 32]) -> Xpriv {
 
 fn get_hardened_extended_child_private_key(master_key: Xpriv, idx: u32) -> Xpriv {
     let secp_ctx = Secp256k1::new();
-    let hardened_extended_child = master_key
+    master_key
         .derive_priv(&secp_ctx, &ChildNumber::from_hardened_idx(idx).unwrap())
-        .expect("Your RNG is busted");
-    hardened_extended_child
+        .expect("Your RNG is busted")
 }
@@ -17,12 +17,12 @@ use bitcoin::script::{ScriptBuf};
 use bitcoin::{OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
 
 fn extract_lower_48_bits(input: [u8; 32]) -> u64 {
-  ((input[26] as u64) << 5 * 8)
-    | ((input[27] as u64) << 4 * 8)
-    | ((input[28] as u64) << 3 * 8)
-    | ((input[29] as u64) << 2 * 8)
-    | ((input[30] as u64) << 1 * 8)
-    | ((input[31] as u64) << 0 * 8)
+  ((input[26] as u64) << (5 * 8))
+    | ((input[27] as u64) << (4 * 8))
+    | ((input[28] as u64) << (3 * 8))
+    | ((input[29] as u64) << (2 * 8))
+    | ((input[30] as u64) << 8)
+    | (input[31] as u64)
 }
 
 pub fn get_commitment_transaction_number_obscure_factor(
@@ -50,8 +50,8 @@ pub fn build_commitment_input(
   TxIn {
     previous_output: funding_outpoint,
     script_sig: ScriptBuf::new(),
-    sequence: Sequence(((0x80 as u32) << 8 * 3)
-      | ((obscured_commitment_transaction_number >> 3 * 8) as u32)),
+    sequence: Sequence((0x80_u32 << (8 * 3))
+      | ((obscured_commitment_transaction_number >> (3 * 8)) as u32)),
     witness: Witness::new(),
   }
 
@@ -65,7 +65,7 @@ pub fn build_commitment_locktime(
   let obscured_commitment_transaction_number = 
     commitment_transaction_number_obscure_factor ^ commitment_number;
 
-  LockTime::from_consensus(((0x20 as u32) << 8 * 3) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
+  LockTime::from_consensus((0x20_u32 << (8 * 3)) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
 
 }
 
@@ -102,11 +102,11 @@ impl NodeKeysManager {
         let channel_master_key = get_hardened_extended_child_private_key(master_key, 3);
 
         NodeKeysManager {
-            secp_ctx: secp_ctx,
-            channel_master_key: channel_master_key,
-            node_secret: node_secret,
-            node_id: node_id,
-            seed: seed,
+            secp_ctx,
+            channel_master_key,
+            node_secret,
+            node_id,
+            seed,
         }
     }
 
@@ -164,7 +164,7 @@ pub enum Basepoint {
 /// Build the commitment secret from the seed and the commitment number
 impl ChannelKeysManager {
     pub fn build_commitment_secret(&self, idx: u64) -> [u8; 32] {
-        let mut res: [u8; 32] = self.commitment_seed.clone();
+        let mut res: [u8; 32] = self.commitment_seed;
         for i in 0..48 {
             let bitpos = 47 - i;
             if idx & (1 << bitpos) == (1 << bitpos) {
@@ -191,7 +191,7 @@ impl ChannelKeysManager {
         };
 
         // Second, convert basepoint to public key
-        let basepoint = PublicKey::from_secret_key(&secp_ctx, &basepoint_secret);
+        let basepoint = PublicKey::from_secret_key(secp_ctx, basepoint_secret);
 
         // Third, get per-commitment-point with index
         let per_commitment_secret = self.build_commitment_secret(commitment_index);
@@ -222,9 +222,8 @@ impl ChannelKeysManager {
         
         let per_commitment_point = PublicKey::from_secret_key(secp_ctx, &per_commitment_private_key);
         
-        let revocation_pubkey = generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point);
         
-        revocation_pubkey
+        generate_revocation_pubkey(countersignatory_basepoint, per_commitment_point)
 
       }
 }
@@ -232,8 +231,8 @@ impl ChannelKeysManager {
 fn key_step_derivation(seed: &[u8; 32], bytes: &[u8], previous_key: &[u8]) -> SecretKey {
     let mut sha = Sha256::engine();
     sha.input(seed);
-    sha.input(&previous_key[..]);
-    sha.input(&bytes[..]);
+    sha.input(previous_key);
+    sha.input(bytes);
     SecretKey::from_slice(&Sha256::from_engine(sha).to_byte_array())
         .expect("SHA-256 is busted")
 }
@@ -248,8 +247,7 @@ fn get_master_key(seed: [u8; 32]) -> Xpriv {
 
 fn get_hardened_extended_child_private_key(master_key: Xpriv, idx: u32) -> Xpriv {
     let secp_ctx = Secp256k1::new();
-    let hardened_extended_child = master_key
+    master_key
         .derive_priv(&secp_ctx, &ChildNumber::from_hardened_idx(idx).unwrap())
-        .expect("Your RNG is busted");
-    hardened_extended_child
+        .expect("Your RNG is busted")
 }
@@ -13,7 +13,7 @@ use bitcoin::{OutPoint, Sequence, Transaction, TxIn, Witness};
 use bitcoin::hash_types::Txid;
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, pubkey_from_private_key, privkey_multipication_tweak, hash_pubkeys,
                          secp256k1pubkey_from_private_key};
-use internal::tx_utils::{build_output, build_transaction};
+use internal::tx_utils::{build_output, build_transaction, dummy_funding_txin};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use bitcoin::locktime::absolute::LockTime;
 
@@ -39,12 +39,7 @@ fn test_get_commitment_transaction_number_obscure_factor() {
 
 #[test]
 fn test_build_commitment_input() {
-    let outpoint = OutPoint::new(
-        "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721"
-            .parse::<Txid>()
-            .unwrap(),
-        1,
-    );
+    let outpoint = dummy_funding_txin().previous_output;
 
     let obscure_factor: u64 = 239765233721135;
     let commitment_number = INITIAL_COMMITMENT_NUMBER - 10;
@@ -75,7 +70,7 @@ fn test_build_commitment_locktime() {
     let obscured_commitment_transaction_number = 
         obscure_factor ^ commitment_number;
 
-    let answer = LockTime::from_consensus(((0x20 as u32) << 8 * 3) |
+    let answer = LockTime::from_consensus((0x20_u32 << (8 * 3)) |
                 ((obscured_commitment_transaction_number & 0xffffffu64) as u32));
 
     let locktime = build_commitment_locktime(
@@ -8,12 +8,12 @@ use bitcoin::script::{ScriptBuf};
 use bitcoin::{OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
 
 fn extract_lower_48_bits(input: [u8; 32]) -> u64 {
-  ((input[26] as u64) << 5 * 8)
-    | ((input[27] as u64) << 4 * 8)
-    | ((input[28] as u64) << 3 * 8)
-    | ((input[29] as u64) << 2 * 8)
-    | ((input[30] as u64) << 1 * 8)
-    | ((input[31] as u64) << 0 * 8)
+  ((input[26] as u64) << (5 * 8))
+    | ((input[27] as u64) << (4 * 8))
+    | ((input[28] as u64) << (3 * 8))
+    | ((input[29] as u64) << (2 * 8))
+    | ((input[30] as u64) << 8)
+    | (input[31] as u64)
 }
 
 pub fn get_commitment_transaction_number_obscure_factor(
@@ -41,8 +41,8 @@ pub fn build_commitment_input(
   TxIn {
     previous_output: funding_outpoint,
     script_sig: ScriptBuf::new(),
-    sequence: Sequence(((0x80 as u32) << 8 * 3)
-      | ((obscured_commitment_transaction_number >> 3 * 8) as u32)),
+    sequence: Sequence((0x80_u32 << (8 * 3))
+      | ((obscured_commitment_transaction_number >> (3 * 8)) as u32)),
     witness: Witness::new(),
   }
   
@@ -56,6 +56,6 @@ pub fn build_commitment_locktime(
   let obscured_commitment_transaction_number = 
     commitment_transaction_number_obscure_factor ^ commitment_number;
 
-  LockTime::from_consensus(((0x20 as u32) << 8 * 3) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
+  LockTime::from_consensus((0x20_u32 << (8 * 3)) | ((obscured_commitment_transaction_number & 0xffffffu64) as u32))
 
 }
\ No newline at end of file
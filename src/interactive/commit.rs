@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
 use bitcoin::PublicKey;
@@ -11,6 +12,7 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
 use internal::tx_utils::{build_output, build_transaction, get_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_funding_transaction};
+use internal::keys::{DelayedPaymentPubkey, RevocationPubkey};
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -27,21 +29,32 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                         our_key_manager: KeyManager,
                                         counterparty_key_manager: KeyManager,
                                         funding_amount: u64,
-                                        our_balance: u64,
-                                        counterparty_balance: u64) {
+                                        our_balance: Amount,
+                                        counterparty_balance: Amount) {
 
     let txid_index = 0;
     let to_self_delay = 144;
+    let feerate_per_kw = 253;
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
 
-    let tx = build_commitment_transaction(
+    let tx = match build_commitment_transaction(
         funding_txin,
-        &our_key_manager.revocation_pubkey,
-        &our_key_manager.delayed_pubkey,
+        &RevocationPubkey(our_key_manager.revocation_pubkey),
+        &DelayedPaymentPubkey(our_key_manager.delayed_pubkey),
         counterparty_key_manager.commitment_pubkey,
         to_self_delay,
         our_balance,
-        counterparty_balance);
+        counterparty_balance,
+        true,
+        feerate_per_kw,
+        0,
+        0) {
+        Ok(tx) => tx,
+        Err(e) => {
+            println!("Could not build the commitment transaction: {}", e);
+            return;
+        }
+    };
 
     let signed_tx = sign_funding_transaction(tx,
         our_key_manager.funding_public_key,
@@ -97,8 +110,8 @@ pub async fn run(funding_txid: String) {
         };
     
     let funding_amount = 5_000_000;
-    let our_balance = 3_998_500;
-    let counterparty_balance = 1_000_500;
+    let our_balance = Amount::from_sat(3_999_500);
+    let counterparty_balance = Amount::from_sat(1_000_500);
     
     create_broadcast_funding_tx(bitcoind, txid.clone(), our_key_manager, counterparty_key_manager, funding_amount,
                                our_balance, counterparty_balance).await;
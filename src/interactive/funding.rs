@@ -1,10 +1,11 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::{TxIn};
 use bitcoin::PublicKey;
-use exercises::exercises::build_funding_transaction;
+use exercises::exercises::build_funding_transaction_with_script;
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
@@ -16,7 +17,7 @@ use tokio::time::sleep;
 
 pub async fn build_funding_tx(bitcoind: BitcoindClient,
                                         tx_input: TxIn,
-                                        tx_in_amount: u64) {
+                                        tx_in_amount: Amount) {
 
     // we're locking to a 2-of-2 multisig, so we need two public keys
     // normally, we would generate our own public key
@@ -25,7 +26,7 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
     let counterparty_pubkey = pubkey_from_private_key(&[0x02; 32]);
 
     // build funding transaction using the function we created
-    let tx = build_funding_transaction(
+    let (tx, _) = build_funding_transaction_with_script(
             vec![tx_input],
             &our_public_key,
             &counterparty_pubkey,
@@ -48,7 +49,7 @@ pub async fn run() {
     // get an unspent output for funding transaction
     let tx_input = get_unspent_output(bitcoind.clone()).await;
 
-    let tx_in_amount = 5_000_000;
+    let tx_in_amount = Amount::from_sat(5_000_000);
     
         build_funding_tx(bitcoind, tx_input, tx_in_amount).await;
 
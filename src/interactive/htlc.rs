@@ -1,6 +1,7 @@
 #![allow(dead_code,unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
 use bitcoin::PublicKey;
@@ -10,17 +11,21 @@ use exercises::exercises::{
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
+use internal::htlc_utils::Htlc;
+use internal::payments::PaymentHash160;
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::keys::{DelayedPaymentPubkey, HtlcPubkey, RevocationPubkey};
+use internal::units::MilliSat;
 use std::time::Duration;
 use tokio::time::sleep;
 
 /// hash160 of the empty string
-const HASH160_DUMMY: [u8; 20] = [
+const HASH160_DUMMY: PaymentHash160 = PaymentHash160([
     0xb4, 0x72, 0xa2, 0x66, 0xd0, 0xbd, 0x89, 0xc1, 0x37, 0x06, 0xa4, 0x13, 0x2c, 0xcf, 0xb1, 0x6f,
     0x7c, 0x3b, 0x9f, 0xcb,
-];
+]);
 
 
 pub struct KeyManager{
@@ -41,26 +46,43 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
     let funding_amount = 5_000_000;
 
-    let payment_hash160 = HASH160_DUMMY;
     let to_self_delay: i64 = 144;
+    let dust_limit = Amount::from_sat(546);
+    let feerate_per_kw = 253;
 
-    let htlc_amount = 405_000;
-    let local_amount = 3_593_500;
-    let remote_amount = 1_000_500;
+    // the HTLC we offer, timing out at block 300
+    let htlc = Htlc {
+        offered: true,
+        amount_msat: MilliSat::from_msat(405_000_000),
+        payment_hash: HASH160_DUMMY,
+        cltv_expiry: 300,
+    };
+    let local_amount = Amount::from_sat(3_594_500);
+    let remote_amount = Amount::from_sat(1_000_500);
 
 
-    let tx = build_htlc_commitment_transaction(
+    let tx = match build_htlc_commitment_transaction(
         funding_txin,
-        &our_key_manager.revocation_pubkey,
-        &counterparty_key_manager.htlc_pubkey,
-        &our_key_manager.htlc_pubkey,
-        &our_key_manager.delayed_pubkey,
+        &RevocationPubkey(our_key_manager.revocation_pubkey),
+        &HtlcPubkey(counterparty_key_manager.htlc_pubkey),
+        &HtlcPubkey(our_key_manager.htlc_pubkey),
+        &DelayedPaymentPubkey(our_key_manager.delayed_pubkey),
         counterparty_key_manager.pubkey,
         to_self_delay,
-        &payment_hash160,
-        htlc_amount,
+        &[htlc],
         local_amount,
-        remote_amount);
+        remote_amount,
+        true,
+        dust_limit,
+        feerate_per_kw,
+        0,
+        0) {
+        Ok(tx) => tx,
+        Err(e) => {
+            println!("Could not build the HTLC commitment transaction: {}", e);
+            return;
+        }
+    };
 
     let signed_tx = sign_funding_transaction(tx,
         our_key_manager.funding_public_key,
@@ -98,7 +120,7 @@ pub async fn run(funding_txid: String) {
             htlc_pubkey: local_htlc_pubkey,
             delayed_pubkey: to_local_delayed_pubkey,
             pubkey: local_pubkey,
-            revocation_pubkey: revocation_pubkey,
+            revocation_pubkey,
         };
 
     // Get our Counterparty Pubkey
@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_variables,unused_imports, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::blockdata::opcodes::all as opcodes;
 use bitcoin::blockdata::script::ScriptBuf;
@@ -17,6 +18,7 @@ use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_s
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction};
+use internal::keys::{DelayedPaymentPubkey, RevocationPubkey};
 use std::time::Duration;
 use tokio::time::sleep;
 use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
@@ -31,8 +33,8 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
     // normally, we would generate our own public key
     //   and the counterparty would send us theirs
     let our_public_key = pubkey_from_private_key(&[0x01; 32]);
-    let revocation_key = pubkey_from_private_key(&[0x02; 32]);
-    let to_local_delayed_pubkey = pubkey_from_private_key(&[0x03; 32]);
+    let revocation_key = RevocationPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x03; 32]));
     let counterparty_public_key = pubkey_from_private_key(&[0x04; 32]);
 
     let to_self_delay = 144;
@@ -43,13 +45,19 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
     let payment_hash = Sha256::hash(secret_bytes).to_byte_array();
     let payment_hash160 = Ripemd160::hash(&payment_hash).to_byte_array();
 
-    let local_output_script = to_local(&revocation_key, &to_local_delayed_pubkey,
-            to_self_delay);
+    let local_output_script = match to_local(&revocation_key, &to_local_delayed_pubkey,
+            to_self_delay) {
+        Ok(script) => script,
+        Err(e) => {
+            println!("Could not build the to_local script: {}", e);
+            return;
+        }
+    };
 
     let remote_output_script = p2wpkh_output_script(counterparty_public_key);
 
-    let local_output = build_output(3_593_500, local_output_script.to_p2wsh());
-    let remote_output = build_output(1_000_500, remote_output_script);
+    let local_output = build_output(Amount::from_sat(3_593_500), local_output_script.to_p2wsh());
+    let remote_output = build_output(Amount::from_sat(1_000_500), remote_output_script);
 
     // build funding transaction using the function we created
     let output_script = build_hash_locked_script(&our_public_key,
@@ -57,7 +65,7 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
     println!("Witness Script (hex): {}", output_script.to_hex_string());
     
 
-    let htlc_output = build_output(405_000, output_script.to_p2wsh());
+    let htlc_output = build_output(Amount::from_sat(405_000), output_script.to_p2wsh());
 
     let version = Version::TWO;
     let locktime = LockTime::ZERO;
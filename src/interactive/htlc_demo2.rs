@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
+use bitcoin::Amount;
 use bitcoin::blockdata::opcodes::all as opcodes;
 use bitcoin::blockdata::script::ScriptBuf;
 use bitcoin::blockdata::transaction::Transaction;
@@ -122,16 +123,15 @@ pub fn sign_transaction(tx: Transaction)-> Transaction {
 
 fn build_p2wpkh_tx(txin: TxIn, pubkey: PublicKey) -> Transaction {
     let output_script = p2wpkh_output_script(pubkey);
-    let output = build_output(404_000, output_script);
+    let output = build_output(Amount::from_sat(404_000), output_script);
     
     let version = Version::TWO;
     let locktime = LockTime::ZERO;
 
-    let tx = build_transaction(version,
+    build_transaction(version,
                       locktime,
                       vec![txin],
-                      vec![output]);
-    tx
+                      vec![output])
     
 }
 
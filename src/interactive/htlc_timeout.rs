@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
 use exercises::exercises::{build_htlc_timeout_transaction,
@@ -10,17 +11,20 @@ use bitcoin::PublicKey;
 use internal::bitcoind_client::{BitcoindClient, get_bitcoind_client};
 use internal::key_utils::{add_pubkeys, pubkey_multipication_tweak, pubkey_from_secret, add_privkeys, privkey_multipication_tweak, hash_pubkeys,
       pubkey_from_private_key, secp256k1_private_key};
+use internal::fee_utils::HtlcFeeMode;
+use internal::payments::PaymentHash160;
 use internal::tx_utils::{build_output,get_unspent_output, build_transaction, get_funding_input, get_htlc_funding_input};
 use internal::script_utils::{build_htlc_offerer_witness_script, p2wpkh_output_script};
 use internal::sign_utils::{sign_raw_transaction, sign_funding_transaction, generate_p2wsh_signature};
+use internal::keys::{DelayedPaymentPubkey, HtlcPubkey, RevocationPubkey};
 use std::time::Duration;
 use tokio::time::sleep;
 
 /// hash160 of the empty string
-const HASH160_DUMMY: [u8; 20] = [
+const HASH160_DUMMY: PaymentHash160 = PaymentHash160([
     0xb4, 0x72, 0xa2, 0x66, 0xd0, 0xbd, 0x89, 0xc1, 0x37, 0x06, 0xa4, 0x13, 0x2c, 0xcf, 0xb1, 0x6f,
     0x7c, 0x3b, 0x9f, 0xcb,
-];
+]);
 
 pub struct KeyManager{
     pub funding_private_key: SecretKey,
@@ -41,28 +45,35 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
     let funding_txin = get_htlc_funding_input(txid.to_string(), txid_index);
     let funding_amount = 405_000;
 
-    let payment_hash160 = HASH160_DUMMY;
+    let payment_hash = HASH160_DUMMY;
     let to_self_delay: i64 = 144;
     let cltv_expiry: u32 = 300;
-    let htlc_amount = 404_000;
+    let htlc_amount = Amount::from_sat(404_000);
 
 
-    let tx = build_htlc_timeout_transaction(
+    let tx = match build_htlc_timeout_transaction(
         funding_txin,
-        &our_key_manager.revocation_pubkey,
-        &our_key_manager.delayed_pubkey,
+        &RevocationPubkey(our_key_manager.revocation_pubkey),
+        &DelayedPaymentPubkey(our_key_manager.delayed_pubkey),
         to_self_delay,
         cltv_expiry,
-        htlc_amount
-        );
+        htlc_amount,
+        HtlcFeeMode::Legacy
+        ) {
+        Ok(tx) => tx,
+        Err(e) => {
+            println!("Could not build the HTLC-timeout transaction: {}", e);
+            return;
+        }
+    };
 
     // Prepare the redeem script for signing (e.g., P2PKH or P2WPKH)
     let redeem_script =
         build_htlc_offerer_witness_script(
-            &our_key_manager.revocation_pubkey,
-            &counterparty_key_manager.htlc_pubkey,
-            &our_key_manager.htlc_pubkey,
-            &payment_hash160);
+            &RevocationPubkey(our_key_manager.revocation_pubkey),
+            &HtlcPubkey(counterparty_key_manager.htlc_pubkey),
+            &HtlcPubkey(our_key_manager.htlc_pubkey),
+            &payment_hash);
 
     let our_signature = generate_p2wsh_signature(
          tx.clone(), 
@@ -139,7 +150,7 @@ pub async fn run(htlc_txid: String) {
             htlc_private_key: local_htlc_private_key,
             delayed_pubkey: to_local_delayed_pubkey,
             pubkey: local_pubkey,
-            revocation_pubkey: revocation_pubkey,
+            revocation_pubkey,
         };
 
     // Get our Counterparty Pubkey
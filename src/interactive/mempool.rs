@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
+use bitcoin::Amount;
 use bitcoin::blockdata::script::ScriptBuf;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::locktime::absolute::LockTime;
@@ -41,7 +42,7 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
 
             let output_script = build_non_standard_output2();
 
-            let output1 = build_output(5_000_000, output_script);
+            let output1 = build_output(Amount::from_sat(5_000_000), output_script);
 
             vec![output1]
             
@@ -50,7 +51,7 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
         MempoolCommand::Consensus => {
             let output_script = p2wpkh_output_script(our_public_key);
 
-            let output1 = build_output(5_500_000, output_script);
+            let output1 = build_output(Amount::from_sat(5_500_000), output_script);
             
             vec![output1]
         },
@@ -58,8 +59,8 @@ pub async fn build_funding_tx(bitcoind: BitcoindClient,
         MempoolCommand::Policy => {
             let output_script = p2wpkh_output_script(our_public_key);
 
-            let output1 = build_output(4_900_000, output_script.clone());
-            let output2 = build_output(100, output_script.clone());
+            let output1 = build_output(Amount::from_sat(4_900_000), output_script.clone());
+            let output2 = build_output(Amount::from_sat(100), output_script.clone());
 
             vec![output1, output2]
         }
@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+use bitcoin::Amount;
 use bitcoin::consensus::encode::serialize_hex;
 use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
 use bitcoin::PublicKey;
@@ -27,8 +28,8 @@ pub async fn create_broadcast_funding_tx(bitcoind: BitcoindClient,
                                         our_key_manager: KeyManager,
                                         counterparty_key_manager: KeyManager,
                                         funding_amount: u64,
-                                        our_balance: u64,
-                                        counterparty_balance: u64) {
+                                        our_balance: Amount,
+                                        counterparty_balance: Amount) {
 
     let txid_index = 0;
     let funding_txin = get_funding_input(txid.to_string(), txid_index);
@@ -88,8 +89,8 @@ pub async fn run(funding_txid: String) {
         };
     
     let funding_amount = 5_000_000;
-    let our_balance = 4_998_500;
-    let counterparty_balance = 500;
+    let our_balance = Amount::from_sat(4_998_500);
+    let counterparty_balance = Amount::from_sat(500);
     
     create_broadcast_funding_tx(bitcoind, txid.clone(), our_key_manager, counterparty_key_manager, funding_amount,
                                our_balance, counterparty_balance).await;
@@ -0,0 +1,55 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! Static channel backups: what a node needs to get its funds back after
+//! losing its channel state. They are static because they only change when
+//! a channel opens, so one taken at the open is as good as a fresh one.
+//!
+//! A backup can't hold the latest commitment, so it can't be used to close
+//! the channel. Restoring one asks the peer to close instead, with its own
+//! latest commitment, and with `option_static_remotekey` our output on it
+//! pays our payment basepoint, which the node seed derives again.
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::OutPoint;
+use internal::keys::{ChannelKeyManager, ChannelPublicKeys};
+use internal::wire::ChannelReestablish;
+
+/// The first byte of every backup, so later versions can add fields.
+pub const STATIC_CHANNEL_BACKUP_VERSION: u8 = 1;
+
+/// Everything about a channel that doesn't change once it is open, and that
+/// recovering its funds needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticChannelBackup {
+    pub channel_id: [u8; 32],
+    pub funding_outpoint: OutPoint,
+    /// Who to reconnect to, to ask for the close.
+    pub peer_node_id: secp256k1PublicKey,
+    /// Which of `ChannelKeyManager::from_seed`'s keys the channel uses.
+    pub channel_key_index: u64,
+    pub is_funder: bool,
+    /// The delay on our `to_local`, in case the peer's commitment isn't the
+    /// one that ends the channel.
+    pub to_self_delay: u16,
+    pub counterparty_keys: ChannelPublicKeys,
+}
+
+/// A channel brought back from its backup, and the `channel_reestablish`
+/// that starts its recovery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoredChannel {
+    pub backup: StaticChannelBackup,
+    pub keys: ChannelKeyManager,
+    pub channel_reestablish: ChannelReestablish,
+}
+
+/// The key backups are encrypted with, derived from the node seed so that
+/// keeping the seed is enough to read them, and a backup stored with a
+/// third party tells it nothing.
+pub fn backup_key(node_seed: &[u8; 32]) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    engine.input(b"static channel backup");
+    engine.input(node_seed);
+    Sha256::from_engine(engine).to_byte_array()
+}
@@ -59,7 +59,7 @@ impl BitcoindClient {
             base64::encode(format!("{}:{}", rpc_user.clone(), rpc_password.clone()));
         let bitcoind_rpc_client = RpcClient::new(&rpc_credentials, http_endpoint)?;
         let _dummy = bitcoind_rpc_client
-            .call_method::<BlockchainInfo>("getblockchaininfo", &vec![])
+            .call_method::<BlockchainInfo>("getblockchaininfo", &[])
             .await
             .map_err(|_| {
                 std::io::Error::new(std::io::ErrorKind::PermissionDenied,
@@ -76,7 +76,7 @@ impl BitcoindClient {
 
     pub async fn list_unspent(&self) -> ListUnspentResponse {
         self.bitcoind_rpc_client
-            .call_method::<ListUnspentResponse>("listunspent", &vec![])
+            .call_method::<ListUnspentResponse>("listunspent", &[])
             .await
             .unwrap()
     }
@@ -98,13 +98,13 @@ impl BitcoindClient {
             .call_method::<AddressPubkey>("getaddressinfo", &addr_args)
             .await
             .unwrap();
-        pubkey.0.into()
+        pubkey.0
         }
 
     pub async fn sign_raw_transaction_with_wallet(&self, tx_hex: String) -> SignedTx {
         let tx_hex_json = serde_json::json!(tx_hex);
         let signed_tx: SignedTx = self.bitcoind_rpc_client
-            .call_method("signrawtransactionwithwallet", &vec![tx_hex_json])
+            .call_method("signrawtransactionwithwallet", &[tx_hex_json])
             .await
             .unwrap();
         //println!("Signed Tx: {}", &signed_tx.hex);
@@ -121,7 +121,7 @@ impl BitcoindClient {
 
 impl BroadcasterInterface for BitcoindClient {
     fn broadcast_transactions(&self, txs: &[&Transaction]) {
-        let txn = txs.iter().map(|tx| encode::serialize_hex(tx)).collect::<Vec<_>>();
+        let txn = txs.iter().map(encode::serialize_hex).collect::<Vec<_>>();
         
         //println!("txn: {:?}", txn);
         let bitcoind_rpc_client = Arc::clone(&self.bitcoind_rpc_client);
@@ -0,0 +1,129 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use internal::errors::{OnionError, WireError};
+use internal::noise_utils::{decrypt_with_ad, encrypt_with_ad};
+use internal::onion_utils::{
+    AMT_TO_FORWARD_TLV, CURRENT_BLINDING_POINT_TLV, ENCRYPTED_RECIPIENT_DATA_TLV, OUTGOING_CLTV_VALUE_TLV,
+    TOTAL_AMOUNT_MSAT_TLV,
+};
+use internal::tlv::{encode_tu64, write_tlv_stream, TlvRecord};
+use internal::units::MilliSat;
+use internal::wire::{write_u16_prefixed, Reader};
+
+/// TLV types inside `encrypted_recipient_data`.
+pub const PADDING_TLV: u64 = 1;
+pub const SHORT_CHANNEL_ID_TLV: u64 = 2;
+pub const NEXT_NODE_ID_TLV: u64 = 4;
+pub const PATH_ID_TLV: u64 = 6;
+pub const NEXT_BLINDING_OVERRIDE_TLV: u64 = 8;
+pub const PAYMENT_RELAY_TLV: u64 = 10;
+pub const PAYMENT_CONSTRAINTS_TLV: u64 = 12;
+
+/// One hop of a blinded path, as the sender sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedHop {
+    pub blinded_node_id: secp256k1PublicKey,
+    /// What the recipient wants this hop to know, readable only by the hop.
+    pub encrypted_recipient_data: Vec<u8>,
+}
+
+/// A route to the recipient that hides every node after the introduction
+/// node. The recipient builds it and hands it to the sender, for example in
+/// a BOLT 12 invoice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedPath {
+    pub introduction_node_id: secp256k1PublicKey,
+    /// `E_0`, which the introduction node needs to decrypt its data.
+    pub blinding_point: secp256k1PublicKey,
+    /// Every hop from the introduction node to the recipient.
+    pub blinded_hops: Vec<BlindedHop>,
+}
+
+/// What a node on a blinded path learns from its `encrypted_recipient_data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedHopData {
+    pub recipient_data: Vec<u8>,
+    /// `E_{i+1}`, passed to the next hop in `update_add_htlc`.
+    pub next_blinding_point: secp256k1PublicKey,
+}
+
+impl BlindedPath {
+    /// The keys to build the onion with. The sender knows the introduction
+    /// node, so its layer is encrypted to its real key.
+    pub fn onion_hops(&self) -> Vec<secp256k1PublicKey> {
+        let mut hops = vec![self.introduction_node_id];
+        hops.extend(self.blinded_hops.iter().skip(1).map(|hop| hop.blinded_node_id));
+        hops
+    }
+
+    /// A payload for every hop of the path. Inside the path the recipient
+    /// decides where the HTLC goes, so hops only get their encrypted data; the
+    /// introduction node also gets the blinding point, and the recipient the
+    /// amount, expiry and total it should check.
+    pub fn onion_payloads(&self, amount_msat: MilliSat, cltv_expiry: u32, total_msat: MilliSat) -> Vec<Vec<u8>> {
+        let last = self.blinded_hops.len() - 1;
+        self.blinded_hops
+            .iter()
+            .enumerate()
+            .map(|(i, hop)| {
+                let mut records =
+                    vec![TlvRecord::new(ENCRYPTED_RECIPIENT_DATA_TLV, hop.encrypted_recipient_data.clone())];
+                if i == 0 {
+                    records.push(TlvRecord::new(CURRENT_BLINDING_POINT_TLV, self.blinding_point.serialize().to_vec()));
+                }
+                if i == last {
+                    records.push(TlvRecord::new(AMT_TO_FORWARD_TLV, encode_tu64(amount_msat.to_msat())));
+                    records.push(TlvRecord::new(OUTGOING_CLTV_VALUE_TLV, encode_tu64(cltv_expiry as u64)));
+                    records.push(TlvRecord::new(TOTAL_AMOUNT_MSAT_TLV, encode_tu64(total_msat.to_msat())));
+                }
+                write_tlv_stream(&records).unwrap()
+            })
+            .collect()
+    }
+
+    /// The `blinded_path` encoding offers and BOLT 12 invoices use: the
+    /// introduction node, `E_0`, then each hop's blinded id and its data
+    /// behind a `u16` length.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.introduction_node_id.serialize());
+        out.extend_from_slice(&self.blinding_point.serialize());
+        out.push(self.blinded_hops.len() as u8);
+        for hop in &self.blinded_hops {
+            out.extend_from_slice(&hop.blinded_node_id.serialize());
+            write_u16_prefixed(&mut out, &hop.encrypted_recipient_data);
+        }
+        out
+    }
+
+    /// Reads one path. An introduction node can also be named by a short
+    /// channel id, but finding its node id needs the graph, so only paths
+    /// that start at a node id are accepted.
+    pub fn decode(reader: &mut Reader) -> Result<BlindedPath, WireError> {
+        let introduction_node_id = reader.read_pubkey().map_err(|_| WireError::InvalidValue("first_node_id"))?;
+        let blinding_point = reader.read_pubkey()?;
+        let num_hops = reader.read_bytes(1)?[0];
+        if num_hops == 0 {
+            return Err(WireError::InvalidValue("num_hops"));
+        }
+
+        let mut blinded_hops = Vec::with_capacity(num_hops as usize);
+        for _ in 0..num_hops {
+            let blinded_node_id = reader.read_pubkey()?;
+            let encrypted_recipient_data = reader.read_u16_prefixed()?.to_vec();
+            blinded_hops.push(BlindedHop { blinded_node_id, encrypted_recipient_data });
+        }
+        Ok(BlindedPath { introduction_node_id, blinding_point, blinded_hops })
+    }
+}
+
+/// ChaCha20-Poly1305 under the hop's `rho` key, with a zero nonce and no
+/// associated data. Each key is only ever used once.
+pub fn encrypt_recipient_data(rho: &[u8; 32], recipient_data: &[u8]) -> Vec<u8> {
+    encrypt_with_ad(rho, 0, &[], recipient_data)
+}
+
+pub fn decrypt_recipient_data(rho: &[u8; 32], encrypted_recipient_data: &[u8]) -> Result<Vec<u8>, OnionError> {
+    decrypt_with_ad(rho, 0, &[], encrypted_recipient_data).map_err(|_| OnionError::InvalidRecipientData)
+}
@@ -0,0 +1,183 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::hash_types::Txid;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::script::Script;
+use bitcoin::{Amount, OutPoint, Transaction};
+use internal::chain_utils::confirmations;
+use internal::errors::ChainSourceError;
+use serde_json::Value;
+use std::str::FromStr;
+
+/// The public Esplora API of Mutinynet, the signet with 30 second blocks
+/// the workshop uses.
+pub const MUTINYNET_ESPLORA_URL: &str = "https://mutinynet.com/api";
+
+pub const SIGNET_ESPLORA_URL: &str = "https://mempool.space/signet/api";
+
+/// An output a chain backend says is unspent, and the height of the block
+/// that confirmed it, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainUtxo {
+    pub outpoint: OutPoint,
+    pub value: Amount,
+    pub confirmation_height: Option<u32>,
+}
+
+/// What the exercises need to know about the chain, from whichever backend
+/// is at hand.
+pub trait ChainSource {
+    fn best_height(&self) -> Result<u32, ChainSourceError>;
+
+    /// `None` if the backend has never seen the transaction.
+    fn get_transaction(&self, txid: &Txid) -> Result<Option<Transaction>, ChainSourceError>;
+
+    /// The height of the block that confirmed `txid`, or `None` while it is
+    /// unconfirmed.
+    fn confirmation_height(&self, txid: &Txid) -> Result<Option<u32>, ChainSourceError>;
+
+    fn unspent_outputs(&self, script_pubkey: &Script) -> Result<Vec<ChainUtxo>, ChainSourceError>;
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainSourceError>;
+
+    fn confirmations(&self, txid: &Txid) -> Result<u32, ChainSourceError> {
+        match self.confirmation_height(txid)? {
+            Some(height) => Ok(confirmations(height, self.best_height()?)),
+            None => Ok(0),
+        }
+    }
+
+    /// The `cltv_expiry` for an HTLC that should time out `delta` blocks
+    /// from now.
+    fn cltv_expiry_from_now(&self, delta: u32) -> Result<u32, ChainSourceError> {
+        Ok(self.best_height()? + delta)
+    }
+}
+
+/// A chain backend over the Esplora HTTP API, for machines without a
+/// bitcoind of their own. Its requests block, so don't call it from inside
+/// a tokio runtime.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct EsploraClient {
+    base_url: String,
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl EsploraClient {
+    pub fn new(base_url: &str) -> EsploraClient {
+        EsploraClient { base_url: base_url.trim_end_matches('/').to_string(), client: reqwest::blocking::Client::new() }
+    }
+
+    pub fn mutinynet() -> EsploraClient {
+        EsploraClient::new(MUTINYNET_ESPLORA_URL)
+    }
+
+    /// GET `path`, or `None` on a 404.
+    fn get(&self, path: &str) -> Result<Option<String>, ChainSourceError> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .map_err(|err| ChainSourceError::Http(err.to_string()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status().map_err(|err| ChainSourceError::Http(err.to_string()))?;
+        response.text().map(Some).map_err(|err| ChainSourceError::Http(err.to_string()))
+    }
+
+    fn get_json(&self, path: &str) -> Result<Option<Value>, ChainSourceError> {
+        match self.get(path)? {
+            Some(body) => serde_json::from_str(&body).map(Some).map_err(invalid_response),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ChainSource for EsploraClient {
+    fn best_height(&self) -> Result<u32, ChainSourceError> {
+        let body = self.get("/blocks/tip/height")?.ok_or_else(|| invalid_response("no tip"))?;
+        body.trim().parse().map_err(invalid_response)
+    }
+
+    fn get_transaction(&self, txid: &Txid) -> Result<Option<Transaction>, ChainSourceError> {
+        let Some(tx_hex) = self.get(&format!("/tx/{}/hex", txid))? else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(tx_hex.trim()).map_err(invalid_response)?;
+        deserialize(&bytes).map(Some).map_err(invalid_response)
+    }
+
+    fn confirmation_height(&self, txid: &Txid) -> Result<Option<u32>, ChainSourceError> {
+        match self.get_json(&format!("/tx/{}/status", txid))? {
+            Some(status) => parse_tx_status(&status),
+            None => Ok(None),
+        }
+    }
+
+    fn unspent_outputs(&self, script_pubkey: &Script) -> Result<Vec<ChainUtxo>, ChainSourceError> {
+        match self.get_json(&format!("/scripthash/{}/utxo", script_hash(script_pubkey)))? {
+            Some(utxos) => parse_utxos(&utxos),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainSourceError> {
+        let response = self
+            .client
+            .post(format!("{}/tx", self.base_url))
+            .body(serialize_hex(tx))
+            .send()
+            .map_err(|err| ChainSourceError::Http(err.to_string()))?;
+        let status = response.status();
+        let body = response.text().map_err(|err| ChainSourceError::Http(err.to_string()))?;
+        if !status.is_success() {
+            return Err(ChainSourceError::Rejected(body));
+        }
+        Txid::from_str(body.trim()).map_err(invalid_response)
+    }
+}
+
+fn invalid_response(err: impl std::fmt::Display) -> ChainSourceError {
+    ChainSourceError::InvalidResponse(err.to_string())
+}
+
+/// Esplora and Electrum index outputs by the SHA256 of their script, in
+/// reverse byte order.
+pub fn script_hash(script_pubkey: &Script) -> String {
+    let mut hash = Sha256::hash(script_pubkey.as_bytes()).to_byte_array();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// `{"confirmed": true, "block_height": 1234, ...}`
+pub(crate) fn parse_tx_status(status: &Value) -> Result<Option<u32>, ChainSourceError> {
+    if !status["confirmed"].as_bool().ok_or_else(|| invalid_response("status without confirmed"))? {
+        return Ok(None);
+    }
+    let height = status["block_height"].as_u64().ok_or_else(|| invalid_response("status without block_height"))?;
+    Ok(Some(height as u32))
+}
+
+/// `[{"txid": "..", "vout": 0, "value": 5000, "status": {..}}, ..]`
+pub(crate) fn parse_utxos(utxos: &Value) -> Result<Vec<ChainUtxo>, ChainSourceError> {
+    let utxos = utxos.as_array().ok_or_else(|| invalid_response("utxos are not an array"))?;
+    utxos
+        .iter()
+        .map(|utxo| {
+            let txid = utxo["txid"].as_str().ok_or_else(|| invalid_response("utxo without txid"))?;
+            let vout = utxo["vout"].as_u64().ok_or_else(|| invalid_response("utxo without vout"))?;
+            let value = utxo["value"].as_u64().ok_or_else(|| invalid_response("utxo without value"))?;
+            Ok(ChainUtxo {
+                outpoint: OutPoint::new(Txid::from_str(txid).map_err(invalid_response)?, vout as u32),
+                value: Amount::from_sat(value),
+                confirmation_height: parse_tx_status(&utxo["status"])?,
+            })
+        })
+        .collect()
+}
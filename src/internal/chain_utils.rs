@@ -0,0 +1,54 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::hash_types::{TxMerkleNode, Txid};
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::Block;
+
+/// The hashes that prove a transaction is in a block: one sibling for each
+/// level of the merkle tree, from the leaves up. `index` is the position of
+/// the transaction in the block, and its bits say on which side each
+/// sibling goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBranch {
+    pub txid: Txid,
+    pub index: usize,
+    pub hashes: Vec<TxMerkleNode>,
+}
+
+/// SHA256d(left || right), the parent of two nodes of the merkle tree.
+pub fn merkle_parent(left: &TxMerkleNode, right: &TxMerkleNode) -> TxMerkleNode {
+    let mut engine = TxMerkleNode::engine();
+    engine.input(left.as_byte_array());
+    engine.input(right.as_byte_array());
+    TxMerkleNode::from_engine(engine)
+}
+
+/// The merkle branch of `txid` in `block`, or `None` if the block doesn't
+/// have it. Levels with an odd number of nodes pair the last one with
+/// itself, as Bitcoin does.
+pub fn merkle_branch(block: &Block, txid: &Txid) -> Option<MerkleBranch> {
+    let index = block.txdata.iter().position(|tx| tx.compute_txid() == *txid)?;
+
+    let mut level: Vec<TxMerkleNode> =
+        block.txdata.iter().map(|tx| TxMerkleNode::from_byte_array(tx.compute_txid().to_byte_array())).collect();
+    let mut position = index;
+    let mut hashes = Vec::new();
+    while level.len() > 1 {
+        let sibling = (position ^ 1).min(level.len() - 1);
+        hashes.push(level[sibling]);
+
+        level = level.chunks(2).map(|pair| merkle_parent(&pair[0], pair.last().unwrap())).collect();
+        position /= 2;
+    }
+
+    Some(MerkleBranch { txid: *txid, index, hashes })
+}
+
+/// How deep a block at `block_height` is when the best block is at
+/// `best_height`: 1 for the best block itself, 0 if it isn't in the chain
+/// up to there.
+pub fn confirmations(block_height: u32, best_height: u32) -> u32 {
+    if block_height > best_height {
+        return 0;
+    }
+    best_height - block_height + 1
+}
@@ -0,0 +1,460 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::to_local;
+use crate::internal;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::{Amount, OutPoint, PublicKey, ScriptBuf, Transaction, TxIn, TxOut, Witness};
+use internal::errors::ChannelError;
+use internal::fee_utils::{balances_after_funder_fee, commit_tx_fee, HtlcFeeMode, ANCHOR_OUTPUT_VALUE};
+use internal::htlc_utils::{htlc_witness_script, is_htlc_trimmed, Htlc, HtlcOutput};
+use internal::keys::{ChannelKeys, ChannelPublicKeys, FundingPubkey, TxCreationKeys};
+use internal::shachain::ShachainStore;
+use internal::script_utils::{build_anchor_output_script, build_anchor_to_remote_script, p2wpkh_output_script};
+use internal::tx_utils::{
+    build_output, build_transaction, commitment_number_obscure_factor, obscured_commitment_transaction_numbers,
+    sort_outputs_bolt3, strip_cltv_expiries,
+};
+use internal::units::MilliSat;
+
+/// The off-chain state of a channel, from the local node's point of view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub commitment_number: u64,
+    pub to_local_msat: MilliSat,
+    pub to_remote_msat: MilliSat,
+    pub htlcs: Vec<Htlc>,
+    pub revealed_secrets: Vec<[u8; 32]>,
+}
+
+/// Everything `Channel::restore` rolls back. Revealed per-commitment secrets
+/// are left out: once a secret is revealed it can't be taken back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelSnapshot {
+    pub commitment_number: u64,
+    pub to_local_msat: MilliSat,
+    pub to_remote_msat: MilliSat,
+    pub htlcs: Vec<Htlc>,
+}
+
+impl Channel {
+    pub fn new(to_local_msat: MilliSat, to_remote_msat: MilliSat) -> Channel {
+        Channel {
+            commitment_number: 0,
+            to_local_msat,
+            to_remote_msat,
+            htlcs: vec![],
+            revealed_secrets: vec![],
+        }
+    }
+
+    /// Add an HTLC, taking its amount from the balance of the side offering it.
+    pub fn add_htlc(&mut self, htlc: Htlc) -> Result<(), ChannelError> {
+        let balance = if htlc.offered {
+            &mut self.to_local_msat
+        } else {
+            &mut self.to_remote_msat
+        };
+
+        *balance = balance.checked_sub(htlc.amount_msat).ok_or(ChannelError::InsufficientBalance {
+            available_msat: balance.to_msat(),
+            required_msat: htlc.amount_msat.to_msat(),
+        })?;
+        self.htlcs.push(htlc);
+        Ok(())
+    }
+
+    /// Move to the next commitment, revoking the current one with its secret.
+    pub fn advance_commitment(&mut self, revoked_secret: [u8; 32]) {
+        self.revealed_secrets.push(revoked_secret);
+        self.commitment_number += 1;
+    }
+
+    pub fn snapshot(&self) -> ChannelSnapshot {
+        ChannelSnapshot {
+            commitment_number: self.commitment_number,
+            to_local_msat: self.to_local_msat,
+            to_remote_msat: self.to_remote_msat,
+            htlcs: self.htlcs.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: ChannelSnapshot) {
+        self.commitment_number = snapshot.commitment_number;
+        self.to_local_msat = snapshot.to_local_msat;
+        self.to_remote_msat = snapshot.to_remote_msat;
+        self.htlcs = snapshot.htlcs;
+    }
+}
+
+/// The payment basepoints the commitment number is obscured with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentBasepoints {
+    pub opener_payment_basepoint: PublicKey,
+    pub accepter_payment_basepoint: PublicKey,
+}
+
+/// Build the local commitment transaction from msat balances: trim HTLCs,
+/// take the fee (and anchors) from the funder, drop dust outputs, sort the
+/// outputs and obscure the commitment number.
+///
+/// The returned `HtlcOutput`s follow the order of `htlcs`.
+pub fn build_commitment_full(
+    keys: &ChannelKeys,
+    params: &ChannelParams,
+    local_msat: MilliSat,
+    remote_msat: MilliSat,
+    htlcs: &[Htlc],
+    commitment_number: u64,
+) -> Result<(Transaction, Vec<HtlcOutput>), ChannelError> {
+    let untrimmed: Vec<bool> = htlcs
+        .iter()
+        .map(|htlc| {
+            !is_htlc_trimmed(
+                htlc.amount_msat.to_amount_floor(),
+                params.dust_limit,
+                params.feerate_per_kw,
+                htlc.offered,
+                params.htlc_fee_mode,
+            )
+        })
+        .collect();
+    let num_untrimmed = untrimmed.iter().filter(|untrimmed| **untrimmed).count();
+
+    let mut fee = commit_tx_fee(params.feerate_per_kw, num_untrimmed, params.anchors());
+    if params.anchors() {
+        fee += ANCHOR_OUTPUT_VALUE * 2;
+    }
+
+    let (local_sat, remote_sat) = balances_after_funder_fee(
+        local_msat.to_amount_floor(),
+        remote_msat.to_amount_floor(),
+        params.is_funder,
+        fee,
+    )?;
+
+    // the output of each untrimmed HTLC, in the order of `htlcs`
+    let htlc_txouts: Vec<Option<TxOut>> = htlcs
+        .iter()
+        .zip(&untrimmed)
+        .map(|(htlc, untrimmed)| {
+            untrimmed.then(|| {
+                let htlc_script = htlc_witness_script(keys, htlc, params.anchors());
+                build_output(htlc.amount_msat.to_amount_floor(), htlc_script.to_p2wsh())
+            })
+        })
+        .collect();
+
+    // HTLC outputs carry their cltv_expiry, which breaks ties between
+    // otherwise identical HTLC outputs
+    let mut outputs: Vec<(TxOut, Option<u32>)> = htlcs
+        .iter()
+        .zip(&htlc_txouts)
+        .filter_map(|(htlc, txout)| Some((txout.clone()?, Some(htlc.cltv_expiry))))
+        .collect();
+
+    let has_to_local = local_sat >= params.dust_limit;
+    let has_to_remote = remote_sat >= params.dust_limit;
+
+    if has_to_local {
+        let to_local_script = to_local(
+            &keys.revocation_pubkey,
+            &keys.to_local_delayed_pubkey,
+            params.to_self_delay as i64,
+        )?;
+        outputs.push((build_output(local_sat, to_local_script.to_p2wsh()), None));
+    }
+
+    if has_to_remote {
+        let to_remote_script = if params.anchors() {
+            build_anchor_to_remote_script(&keys.remote_pubkey).to_p2wsh()
+        } else {
+            p2wpkh_output_script(keys.remote_pubkey)
+        };
+        outputs.push((build_output(remote_sat, to_remote_script), None));
+    }
+
+    if params.anchors() {
+        // an anchor is added for each side with something to fee-bump
+        if has_to_local || num_untrimmed > 0 {
+            let anchor_script = build_anchor_output_script(&params.local_funding_pubkey());
+            outputs.push((build_output(ANCHOR_OUTPUT_VALUE, anchor_script.to_p2wsh()), None));
+        }
+        if has_to_remote || num_untrimmed > 0 {
+            let anchor_script = build_anchor_output_script(&params.remote_funding_pubkey());
+            outputs.push((build_output(ANCHOR_OUTPUT_VALUE, anchor_script.to_p2wsh()), None));
+        }
+    }
+
+    let outputs = sort_outputs_bolt3(outputs);
+
+    // find where each HTLC's output was sorted to. Outputs that are equal
+    // down to the expiry are interchangeable, so each takes the first one left.
+    let mut claimed = vec![false; outputs.len()];
+    let mut htlc_outputs: Vec<HtlcOutput> = vec![];
+    for (htlc, txout) in htlcs.iter().zip(&htlc_txouts) {
+        let output_index = txout.as_ref().and_then(|txout| {
+            let index = outputs.iter().enumerate().position(|(index, (output, cltv))| {
+                !claimed[index] && output == txout && *cltv == Some(htlc.cltv_expiry)
+            })?;
+            claimed[index] = true;
+            Some(index as u32)
+        });
+        htlc_outputs.push(HtlcOutput { htlc: *htlc, output_index });
+    }
+
+    let basepoints = params.basepoints();
+    let obscure_factor =
+        commitment_number_obscure_factor(&basepoints.opener_payment_basepoint, &basepoints.accepter_payment_basepoint);
+    let (locktime, sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+    let funding_txin = TxIn {
+        previous_output: params.funding_outpoint,
+        script_sig: ScriptBuf::new(),
+        sequence,
+        witness: Witness::new(),
+    };
+
+    let tx = build_transaction(Version::TWO, locktime, vec![funding_txin], strip_cltv_expiries(outputs));
+
+    Ok((tx, htlc_outputs))
+}
+
+/// What to do after comparing a peer's `channel_reestablish` with our state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReestablishAction {
+    /// Both sides already have everything; carry on.
+    Proceed,
+    /// The peer missed our last messages before disconnecting.
+    Retransmit { commitment_signed: bool, revoke_and_ack: bool },
+    /// The peer proved it has seen a revocation we don't remember sending, so
+    /// our commitment is stale. Broadcasting it would let the peer take
+    /// everything; wait for it to close the channel instead.
+    FallenBehind,
+    /// The peer's numbers or secret make no sense for this channel. Close it
+    /// with our latest commitment.
+    FailChannel,
+}
+
+/// What spent a channel's funding output, told apart by the spending
+/// transaction and the commitments the counterparty has revoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FundingSpend {
+    /// A commitment the counterparty may still broadcast. Our output on it
+    /// is ours right away; theirs waits out `to_self_delay`.
+    LatestCommitment { commitment_number: u64 },
+    /// A commitment the counterparty revoked. We hold its secret, so we can
+    /// take every output with the revocation key.
+    RevokedCommitment { commitment_number: u64 },
+    /// A closing transaction both sides signed. There is nothing to punish
+    /// or wait for.
+    CooperativeClose,
+}
+
+/// Where an HTLC is in the BOLT 2 update dance, from the local node's point
+/// of view. `Local` states are for updates we proposed, `Remote` ones for
+/// updates the counterparty proposed, whichever side offered the HTLC.
+///
+/// An update is only safe once it is in both commitments and both of the
+/// commitments without it have been revoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HtlcState {
+    /// We sent `update_add_htlc`, but it is in no commitment yet.
+    LocalAddProposed,
+    /// We signed a remote commitment with the HTLC in it.
+    LocalAddSigned,
+    /// The counterparty revoked its commitment without the HTLC.
+    LocalAddAcked,
+    /// We received `update_add_htlc`, but it is in no commitment yet.
+    RemoteAddProposed,
+    /// The counterparty signed a local commitment with the HTLC in it.
+    RemoteAddAcked,
+    /// We signed a remote commitment with the HTLC in it too.
+    RemoteAddSigned,
+    /// The HTLC is in both commitments, and neither side can go back to one
+    /// without it.
+    Committed,
+    /// We sent `update_fulfill_htlc` for an HTLC we were offered.
+    LocalFulfillProposed,
+    /// We signed a remote commitment without the HTLC.
+    LocalFulfillSigned,
+    /// The counterparty revoked its commitment with the HTLC.
+    LocalFulfillAcked,
+    /// We received `update_fulfill_htlc` for an HTLC we offered.
+    RemoteFulfillProposed,
+    /// The counterparty signed a local commitment without the HTLC.
+    RemoteFulfillAcked,
+    /// We signed a remote commitment without the HTLC too.
+    RemoteFulfillSigned,
+    /// The HTLC is gone from both commitments, and its amount belongs to the
+    /// side it was offered to.
+    Fulfilled,
+}
+
+impl HtlcState {
+    /// Whether our latest commitment has an output for the HTLC.
+    pub fn in_local_commitment(&self) -> bool {
+        matches!(
+            self,
+            HtlcState::RemoteAddAcked
+                | HtlcState::RemoteAddSigned
+                | HtlcState::Committed
+                | HtlcState::LocalFulfillProposed
+                | HtlcState::LocalFulfillSigned
+                | HtlcState::LocalFulfillAcked
+                | HtlcState::RemoteFulfillProposed
+        )
+    }
+
+    /// Whether the counterparty's latest commitment has an output for the HTLC.
+    pub fn in_remote_commitment(&self) -> bool {
+        matches!(
+            self,
+            HtlcState::LocalAddSigned
+                | HtlcState::LocalAddAcked
+                | HtlcState::RemoteAddSigned
+                | HtlcState::Committed
+                | HtlcState::LocalFulfillProposed
+                | HtlcState::RemoteFulfillProposed
+                | HtlcState::RemoteFulfillAcked
+        )
+    }
+
+    /// Whether the HTLC is being, or has been, settled with its preimage.
+    pub fn is_fulfill(&self) -> bool {
+        matches!(
+            self,
+            HtlcState::LocalFulfillProposed
+                | HtlcState::LocalFulfillSigned
+                | HtlcState::LocalFulfillAcked
+                | HtlcState::RemoteFulfillProposed
+                | HtlcState::RemoteFulfillAcked
+                | HtlcState::RemoteFulfillSigned
+                | HtlcState::Fulfilled
+        )
+    }
+}
+
+/// The messages that move HTLCs from one `HtlcState` to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommitmentEvent {
+    SendCommitmentSigned,
+    ReceiveCommitmentSigned,
+    ReceiveRevokeAndAck,
+}
+
+/// Where the counterparty is in its chain of commitments: the points of its
+/// current and next commitments, and the secrets of the ones it revoked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CounterpartyCommitments {
+    pub commitment_number: u64,
+    pub per_commitment_point: secp256k1PublicKey,
+    pub next_per_commitment_point: secp256k1PublicKey,
+    pub revoked_secrets: ShachainStore,
+}
+
+/// Everything about a channel that is agreed when it is opened, from the
+/// local node's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelParams {
+    pub funding_outpoint: OutPoint,
+    /// The delay the remote node asks us to wait before spending `to_local`.
+    pub to_self_delay: u16,
+    pub dust_limit: Amount,
+    pub feerate_per_kw: u64,
+    pub htlc_fee_mode: HtlcFeeMode,
+    pub is_funder: bool,
+    pub local_keys: ChannelPublicKeys,
+    pub remote_keys: ChannelPublicKeys,
+}
+
+impl ChannelParams {
+    /// The keys of our own commitment for `per_commitment_point`.
+    pub fn local_commitment_keys(&self, per_commitment_point: secp256k1PublicKey) -> ChannelKeys {
+        TxCreationKeys::derive(per_commitment_point, &self.local_keys, &self.remote_keys).to_channel_keys()
+    }
+
+    pub fn anchors(&self) -> bool {
+        self.htlc_fee_mode.anchors()
+    }
+
+    pub fn local_funding_pubkey(&self) -> FundingPubkey {
+        FundingPubkey(PublicKey::new(self.local_keys.funding_pubkey))
+    }
+
+    pub fn remote_funding_pubkey(&self) -> FundingPubkey {
+        FundingPubkey(PublicKey::new(self.remote_keys.funding_pubkey))
+    }
+
+    pub fn basepoints(&self) -> CommitmentBasepoints {
+        let (opener, accepter) = if self.is_funder {
+            (&self.local_keys, &self.remote_keys)
+        } else {
+            (&self.remote_keys, &self.local_keys)
+        };
+        CommitmentBasepoints {
+            opener_payment_basepoint: PublicKey::new(opener.payment_basepoint),
+            accepter_payment_basepoint: PublicKey::new(accepter.payment_basepoint),
+        }
+    }
+}
+
+/// Builds one commitment transaction of a channel, adding its balances and
+/// HTLCs one piece at a time before calling `build`.
+#[derive(Debug, Clone)]
+pub struct CommitmentTransactionBuilder<'a> {
+    params: &'a ChannelParams,
+    keys: ChannelKeys,
+    commitment_number: u64,
+    to_local_msat: MilliSat,
+    to_remote_msat: MilliSat,
+    htlcs: Vec<Htlc>,
+}
+
+impl<'a> CommitmentTransactionBuilder<'a> {
+    pub fn new(params: &'a ChannelParams, commitment_number: u64, keys: ChannelKeys) -> Self {
+        CommitmentTransactionBuilder {
+            params,
+            keys,
+            commitment_number,
+            to_local_msat: MilliSat::ZERO,
+            to_remote_msat: MilliSat::ZERO,
+            htlcs: vec![],
+        }
+    }
+
+    /// Start from the current state of `channel`.
+    pub fn from_channel(params: &'a ChannelParams, channel: &Channel, keys: ChannelKeys) -> Self {
+        CommitmentTransactionBuilder::new(params, channel.commitment_number, keys)
+            .balances(channel.to_local_msat, channel.to_remote_msat)
+            .htlcs(&channel.htlcs)
+    }
+
+    pub fn balances(mut self, to_local_msat: MilliSat, to_remote_msat: MilliSat) -> Self {
+        self.to_local_msat = to_local_msat;
+        self.to_remote_msat = to_remote_msat;
+        self
+    }
+
+    pub fn add_htlc(mut self, htlc: Htlc) -> Self {
+        self.htlcs.push(htlc);
+        self
+    }
+
+    pub fn htlcs(mut self, htlcs: &[Htlc]) -> Self {
+        self.htlcs.extend_from_slice(htlcs);
+        self
+    }
+
+    /// The commitment transaction and where each HTLC ended up in it, in the
+    /// order the HTLCs were added.
+    pub fn build(&self) -> Result<(Transaction, Vec<HtlcOutput>), ChannelError> {
+        build_commitment_full(
+            &self.keys,
+            self.params,
+            self.to_local_msat,
+            self.to_remote_msat,
+            &self.htlcs,
+            self.commitment_number,
+        )
+    }
+}
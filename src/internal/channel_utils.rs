@@ -0,0 +1,30 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::hash_types::Txid;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+
+/// The BOLT 2 `channel_id` of a channel opened with `open_channel`: the
+/// funding txid with its last two bytes XORed with the funding output index.
+pub fn channel_id_from_funding(txid: Txid, output_index: u16) -> [u8; 32] {
+    let mut channel_id = txid.to_byte_array();
+    channel_id[30] ^= (output_index >> 8) as u8;
+    channel_id[31] ^= (output_index & 0xff) as u8;
+    channel_id
+}
+
+/// The BOLT 2 `channel_id` of a dual-funded channel:
+/// `SHA256(lesser-revocation-basepoint || greater-revocation-basepoint)`.
+pub fn channel_id_v2(
+    revocation_basepoint_a: &secp256k1PublicKey,
+    revocation_basepoint_b: &secp256k1PublicKey,
+) -> [u8; 32] {
+    let a = revocation_basepoint_a.serialize();
+    let b = revocation_basepoint_b.serialize();
+    let (lesser, greater) = if a <= b { (a, b) } else { (b, a) };
+
+    let mut engine = Sha256::engine();
+    engine.input(&lesser);
+    engine.input(&greater);
+    Sha256::from_engine(engine).to_byte_array()
+}
@@ -117,12 +117,12 @@ impl TryInto<ListUnspentResponse> for JsonResponse {
       .unwrap()
       .iter()
       .map(|utxo| ListUnspentUtxo {
-        txid: Txid::from_str(&utxo["txid"].as_str().unwrap().to_string()).unwrap(),
+        txid: Txid::from_str(utxo["txid"].as_str().unwrap()).unwrap(),
         vout: utxo["vout"].as_u64().unwrap() as u32,
         amount: bitcoin::Amount::from_btc(utxo["amount"].as_f64().unwrap())
           .unwrap()
           .to_sat(),
-        address: Address::from_str(&utxo["address"].as_str().unwrap().to_string())
+        address: Address::from_str(utxo["address"].as_str().unwrap())
           .unwrap()
           .assume_checked(), // the expected network is not known at this point
       })
@@ -0,0 +1,130 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::Transaction;
+use internal::channel::Channel;
+use internal::htlc_utils::Htlc;
+use internal::units::MilliSat;
+use serde_json::{json, Value};
+
+/// Check whether `tx` carries an obscured commitment number the way BOLT 3
+/// encodes it: upper byte 0x20 in the locktime and 0x80 in the funding input's
+/// sequence. A commitment built with `LockTime::ZERO` fails this check.
+pub fn has_obscured_commitment_number(tx: &Transaction) -> bool {
+    let locktime_marker = tx.lock_time.to_consensus_u32() >> 24;
+
+    let sequence_marker = match tx.input.first() {
+        Some(txin) => txin.sequence.0 >> 24,
+        None => return false,
+    };
+
+    locktime_marker == 0x20 && sequence_marker == 0x80
+}
+
+/// Describe every output of the channel's current local commitment and the
+/// ways each one can be spent, as a JSON tree for the teaching front-end.
+///
+/// `Channel` doesn't hold keys, so keys are named by their BOLT 3 role.
+pub fn channel_spend_tree_json(channel: &Channel) -> String {
+    let mut outputs = Vec::new();
+
+    if channel.to_local_msat > MilliSat::ZERO {
+        outputs.push(json!({
+            "output": "to_local",
+            "amount_msat": channel.to_local_msat.to_msat(),
+            "spend_paths": [
+                {
+                    "path": "revocation",
+                    "condition": "counterparty knows the revocation secret",
+                    "keys": ["revocation_pubkey"],
+                    "timelock": null,
+                },
+                {
+                    "path": "delayed",
+                    "condition": "local node after the relative delay",
+                    "keys": ["local_delayedpubkey"],
+                    "timelock": { "type": "relative", "value": "to_self_delay" },
+                },
+            ],
+        }));
+    }
+
+    if channel.to_remote_msat > MilliSat::ZERO {
+        outputs.push(json!({
+            "output": "to_remote",
+            "amount_msat": channel.to_remote_msat.to_msat(),
+            "spend_paths": [
+                {
+                    "path": "remote",
+                    "condition": "counterparty signature",
+                    "keys": ["remote_pubkey"],
+                    "timelock": null,
+                },
+            ],
+        }));
+    }
+
+    outputs.extend(channel.htlcs.iter().map(htlc_spend_tree));
+
+    json!({
+        "commitment_number": channel.commitment_number,
+        "outputs": outputs,
+    })
+    .to_string()
+}
+
+fn htlc_spend_tree(htlc: &Htlc) -> Value {
+    let revocation = json!({
+        "path": "revocation",
+        "condition": "counterparty knows the revocation secret",
+        "keys": ["revocation_pubkey"],
+        "timelock": null,
+    });
+    let cltv = json!({ "type": "absolute", "value": htlc.cltv_expiry });
+
+    let (output, spend_paths) = if htlc.offered {
+        (
+            "offered_htlc",
+            vec![
+                revocation,
+                json!({
+                    "path": "success",
+                    "condition": "counterparty reveals the payment preimage",
+                    "keys": ["remote_htlcpubkey"],
+                    "timelock": null,
+                }),
+                json!({
+                    "path": "timeout",
+                    "condition": "local node via the HTLC-timeout transaction",
+                    "keys": ["local_htlcpubkey", "remote_htlcpubkey"],
+                    "timelock": cltv,
+                }),
+            ],
+        )
+    } else {
+        (
+            "received_htlc",
+            vec![
+                revocation,
+                json!({
+                    "path": "success",
+                    "condition": "local node reveals the payment preimage via the HTLC-success transaction",
+                    "keys": ["local_htlcpubkey", "remote_htlcpubkey"],
+                    "timelock": null,
+                }),
+                json!({
+                    "path": "timeout",
+                    "condition": "counterparty after the HTLC expires",
+                    "keys": ["remote_htlcpubkey"],
+                    "timelock": cltv,
+                }),
+            ],
+        )
+    };
+
+    json!({
+        "output": output,
+        "amount_msat": htlc.amount_msat.to_msat(),
+        "payment_hash": hex::encode(htlc.payment_hash.0),
+        "spend_paths": spend_paths,
+    })
+}
@@ -0,0 +1,433 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+
+/// Invalid arguments passed to an exercise builder, caught before they reach
+/// rust-bitcoin and panic there.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExerciseError {
+    #[error("to_self_delay of {0} blocks is outside the BIP 68 range of 1 to 65535")]
+    InvalidToSelfDelay(i64),
+    #[error("{0} amount is zero; leave the output out instead")]
+    ZeroValueOutput(&'static str),
+    #[error("{name} amount of {amount} sats is more than will ever exist")]
+    AmountOutOfRange { name: &'static str, amount: u64 },
+    #[error("{0} is not a compressed public key")]
+    UncompressedKey(&'static str),
+    #[error("adaptor signature does not verify for this payment point")]
+    InvalidAdaptorSignature,
+    #[error("{available} sats can't pay a fee of {needed} sats")]
+    InsufficientFunds { needed: u64, available: u64 },
+    #[error("replacement fee of {fee} sats is below the {required} sats BIP 125 requires")]
+    InsufficientReplacementFee { fee: u64, required: u64 },
+}
+
+/// Failures while aggregating keys, nonces or signatures with MuSig2.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MusigError {
+    #[error("no public keys to aggregate")]
+    NoKeys,
+    #[error("aggregate is the point at infinity")]
+    InfiniteAggregate,
+    #[error("secret key does not belong to any of the aggregated public keys")]
+    UnknownSigner,
+    #[error("partial signature does not verify against the signer's key and nonce")]
+    InvalidPartialSignature,
+}
+
+/// Failures while running the BOLT 8 handshake.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TransportError {
+    #[error("unknown handshake version {0}")]
+    UnknownVersion(u8),
+    #[error("handshake act carries an invalid public key")]
+    InvalidPublicKey,
+    #[error("handshake act failed to decrypt")]
+    DecryptionFailed,
+    #[error("message ciphertext failed to decrypt")]
+    InvalidMessageMac,
+    #[error("message of {0} bytes is longer than the 65535 bytes a length prefix can carry")]
+    MessageTooLong(usize),
+    #[error("message is {actual} bytes but its length prefix says {expected}")]
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+/// Failures while decoding a Lightning wire message.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WireError {
+    #[error("message ended before all of its fields were read")]
+    ShortRead,
+    #[error("unknown even message type {0}")]
+    UnknownEvenType(u16),
+    #[error("BigSize is not minimally encoded")]
+    NonCanonicalBigSize,
+    #[error("TLV type {current} does not come after type {previous}")]
+    TlvTypesNotAscending { previous: u64, current: u64 },
+    #[error("unknown even TLV type {0}")]
+    UnknownEvenTlvType(u64),
+    #[error("{0} field does not hold a valid value")]
+    InvalidValue(&'static str),
+}
+
+/// Gossip messages that fail BOLT 7 validation.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GossipError {
+    #[error("{0} does not sign the announcement")]
+    InvalidSignature(&'static str),
+    #[error("node_id_1 must sort before node_id_2")]
+    NodeIdsNotOrdered,
+    #[error("bitcoin keys do not match the funding output")]
+    FundingScriptMismatch,
+    #[error("no channel {0} in the graph")]
+    UnknownChannel(u64),
+    #[error("node has no announced channels")]
+    UnknownNode,
+    #[error("announcement is for a different chain")]
+    WrongChain,
+    #[error("htlc_maximum_msat of {htlc_maximum_msat} is more than the channel capacity of {capacity_msat} msat")]
+    HtlcMaximumAboveCapacity { htlc_maximum_msat: u64, capacity_msat: u64 },
+}
+
+/// Failures while building or peeling a BOLT 4 onion.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OnionError {
+    #[error("{hops} hops but {payloads} payloads")]
+    HopCountMismatch { hops: usize, payloads: usize },
+    #[error("hop payloads take {length} bytes, more than the 1300 an onion can carry")]
+    PayloadsTooLong { length: usize },
+    #[error("unknown onion version {0}")]
+    UnknownVersion(u8),
+    #[error("onion carries an invalid ephemeral public key")]
+    InvalidPublicKey,
+    #[error("onion HMAC does not match")]
+    InvalidHmac,
+    #[error("hop payload length runs past the end of the onion")]
+    InvalidPayloadLength,
+    #[error("failure onion does not authenticate under any hop's shared secret")]
+    UnattributableFailure,
+    #[error("failure message lengths run past the end of the packet")]
+    InvalidFailureMessage,
+    #[error("encrypted_recipient_data failed to decrypt")]
+    InvalidRecipientData,
+}
+
+/// Reasons a node refuses to forward an HTLC it was offered. Most of them
+/// match a BOLT 4 failure code.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ForwardError {
+    #[error("onion can't be peeled: {0}")]
+    InvalidOnion(#[from] OnionError),
+    #[error("hop payload is not valid: {0}")]
+    InvalidPayload(#[from] WireError),
+    #[error("we are the final hop, there is nowhere to forward to")]
+    FinalHop,
+    #[error("no channel {0} to forward over")]
+    UnknownNextPeer(u64),
+    #[error("{incoming_msat} msat in does not cover {amt_to_forward_msat} msat out plus the {fee_msat} msat fee")]
+    FeeInsufficient { incoming_msat: u64, amt_to_forward_msat: u64, fee_msat: u64 },
+    #[error("incoming expiry {incoming} is not {cltv_expiry_delta} blocks above the outgoing {outgoing}")]
+    IncorrectCltvExpiry { incoming: u32, outgoing: u32, cltv_expiry_delta: u16 },
+    #[error("{0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// Justice blobs a watchtower can't turn into a penalty transaction for the
+/// breach it saw.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WatchtowerError {
+    #[error("blob is too short to hold a nonce and a tag")]
+    BlobTooShort,
+    #[error("blob was not encrypted for this breach")]
+    DecryptionFailed,
+    #[error("blob does not hold a valid transaction")]
+    InvalidJusticeTransaction,
+    #[error("justice transaction does not spend the breach")]
+    NotABreachSpend,
+}
+
+/// A static channel backup we can't restore.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BackupError {
+    #[error("backup was not encrypted with this node's seed")]
+    DecryptionFailed,
+    #[error("unknown backup version {0}")]
+    UnknownVersion(u8),
+    #[error("backup has {0} bytes left over")]
+    TrailingBytes(usize),
+    #[error("invalid backup: {0}")]
+    Invalid(#[from] WireError),
+}
+
+/// Channels a `Persister` could not save or load.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PersistError {
+    #[error("could not access the channel store: {0}")]
+    Io(String),
+    #[error("channel store is corrupt: {0}")]
+    InvalidStore(String),
+    #[error("invalid persisted channel: {0}")]
+    Invalid(#[from] WireError),
+}
+
+/// A peer breaking the BOLT 2 interactive transaction construction rules.
+/// Any of these fails the negotiation.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InteractiveTxError {
+    #[error("message for channel {}, not ours", hex::encode(.0))]
+    UnknownChannelId([u8; 32]),
+    #[error("unexpected message type {0} during transaction construction")]
+    UnexpectedMessage(u16),
+    #[error("serial_id {0} has the parity of our own serial ids")]
+    WrongSerialIdParity(u64),
+    #[error("serial_id {0} is already in use")]
+    DuplicateSerialId(u64),
+    #[error("prevtx has no output {0}")]
+    PrevoutOutOfRange(u32),
+    #[error("input does not spend a segwit output")]
+    NonSegwitInput,
+    #[error("input {0} is already in the transaction")]
+    DuplicateInput(bitcoin::OutPoint),
+    #[error("output of {value} sats is below the dust limit of {dust_limit} sats")]
+    DustOutput { value: u64, dust_limit: u64 },
+    #[error("output of {0} sats is more than will ever exist")]
+    OutputAmountOutOfRange(u64),
+    #[error("peer sent more than 4096 tx_add_input or tx_add_output")]
+    TooManyAdditions,
+    #[error("negotiation is not complete yet")]
+    NotComplete,
+    #[error("transaction has no funding output")]
+    MissingFundingOutput,
+}
+
+/// A `shutdown` script BOLT 2 doesn't let the peer close to.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ShutdownScriptError {
+    #[error("shutdown script {} is not one BOLT 2 allows", hex::encode(.0.as_bytes()))]
+    Unsupported(bitcoin::ScriptBuf),
+    #[error("witness v{0} shutdown script without option_shutdown_anysegwit")]
+    AnySegwitNotNegotiated(u8),
+    #[error("shutdown script is not the upfront_shutdown_script")]
+    UpfrontScriptMismatch,
+}
+
+/// A peer breaking the BOLT 2 `closing_signed` rules. Any of these fails
+/// the cooperative close.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ClosingError {
+    #[error("message for channel {}, not ours", hex::encode(.0))]
+    UnknownChannelId([u8; 32]),
+    #[error("{0} HTLCs are still on the channel")]
+    PendingHtlcs(usize),
+    #[error("closing_signed has no fee_range")]
+    MissingFeeRange,
+    #[error("fee range {theirs:?} does not overlap ours, {ours:?}")]
+    NoFeeRangeOverlap { ours: crate::internal::wire::FeeRange, theirs: crate::internal::wire::FeeRange },
+    #[error("fee of {0} sats is outside the agreed fee range")]
+    FeeOutsideRange(u64),
+    #[error("we proposed a fee of {sent} sats, and the funder answered {received} sats")]
+    FeeChanged { sent: u64, received: u64 },
+    #[error("invalid signature on the closing transaction")]
+    InvalidSignature,
+    #[error(transparent)]
+    InvalidShutdownScript(#[from] ShutdownScriptError),
+    #[error("{0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// A chain backend that couldn't answer, or answered something we can't
+/// read.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChainSourceError {
+    #[error("request failed: {0}")]
+    Http(String),
+    #[error("backend refused the transaction: {0}")]
+    Rejected(String),
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Payment hashes, preimages and secrets built from bytes of the wrong
+/// length.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PaymentError {
+    #[error("expected {expected} bytes but got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+/// Failures while decoding or encoding a BOLT 11 invoice.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvoiceError {
+    #[error("invoice is not valid bech32: {0}")]
+    Bech32(#[from] bech32::primitives::decode::CheckedHrpstringError),
+    #[error("invoice is not for a known currency")]
+    UnknownCurrency,
+    #[error("invoice amount is malformed or not a whole number of millisatoshis")]
+    InvalidAmount,
+    #[error("invoice is too short to hold a timestamp and a signature")]
+    TooShort,
+    #[error("{0} field does not hold a valid value")]
+    InvalidField(&'static str),
+    #[error("invoice has no payment hash")]
+    MissingPaymentHash,
+    #[error("invoice has neither a description nor a description hash")]
+    MissingDescription,
+    #[error("invoice signature is not valid")]
+    InvalidSignature,
+    #[error("tagged field {tag} of {words} words is longer than the 1023 its length can carry")]
+    FieldTooLong { tag: u8, words: usize },
+}
+
+/// Failures while decoding, building or checking BOLT 12 messages.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OfferError {
+    #[error("string is not valid bech32: {0}")]
+    Bech32(#[from] bech32::primitives::decode::UncheckedHrpstringError),
+    #[error("'+' must join two non-empty parts of the string")]
+    InvalidContinuation,
+    #[error("expected a string starting with {0}1")]
+    WrongHrp(&'static str),
+    #[error("invalid TLV stream: {0}")]
+    Wire(#[from] WireError),
+    #[error("TLV type {0} does not belong in this message")]
+    UnexpectedType(u64),
+    #[error("{0} field does not hold a valid value")]
+    InvalidField(&'static str),
+    #[error("message has no {0}")]
+    MissingField(&'static str),
+    #[error("offer is not for this chain")]
+    UnsupportedChain,
+    #[error("invoice does not repeat the invoice_request's fields")]
+    InvoiceRequestMismatch,
+    #[error("invoice_node_id is not the offer's node")]
+    UnexpectedNodeId,
+    #[error("invoice_amount is not the amount requested")]
+    AmountMismatch,
+    #[error("signature is not valid")]
+    InvalidSignature,
+}
+
+/// Errors reported when validating the transactions built in the workshop.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ChannelError {
+    #[error("transaction version {0} is not standard")]
+    NonStandardVersion(i32),
+    #[error("transaction weight {weight} exceeds the standard maximum")]
+    WeightTooHigh { weight: u64 },
+    #[error("output {index} pays {value} sats, below its dust limit of {dust_limit} sats")]
+    DustOutput { index: usize, value: u64, dust_limit: u64 },
+    #[error("output {index} does not use a standard script type")]
+    NonStandardOutputScript { index: usize },
+    #[error("witness script of input {input} is {size} bytes, too large to relay")]
+    ScriptTooLarge { input: usize, size: usize },
+    #[error("witness script of input {input} contains a non-minimal push")]
+    NonMinimalPush { input: usize },
+    #[error("witness element {element} of input {input} is a signature with a high S value")]
+    HighSSignature { input: usize, element: usize },
+    #[error("output script is not P2WSH")]
+    NotP2wsh,
+    #[error("witness is empty")]
+    EmptyWitness,
+    #[error("witness script does not hash to the output's witness program")]
+    WitnessScriptMismatch,
+    #[error("balance of {available_msat} msat cannot cover {required_msat} msat")]
+    InsufficientBalance { available_msat: u64, required_msat: u64 },
+    #[error("feerate of {feerate_per_kw} sat/kw is below the minimum of {min_feerate_per_kw} sat/kw")]
+    FeerateTooLow { feerate_per_kw: u64, min_feerate_per_kw: u64 },
+    #[error("transaction has {inputs} inputs but {values} input values were given")]
+    InputValuesMismatch { inputs: usize, values: usize },
+    #[error("outputs pay {output_value} sats but the inputs only hold {input_value} sats")]
+    OutputsExceedInputs { input_value: u64, output_value: u64 },
+    #[error("per-commitment secret {index} does not derive the secrets received before it")]
+    InconsistentSecret { index: u64 },
+    #[error("revealed secret is not the secret of commitment {commitment_number}'s per-commitment point")]
+    InvalidRevocationSecret { commitment_number: u64 },
+    #[error("counterparty signature does not sign the commitment transaction")]
+    InvalidCommitmentSignature,
+    #[error("input {input} is missing a signature from one of the funding keys")]
+    MissingSignature { input: usize },
+    #[error("input {input} is already signed, but a PSBT takes unsigned inputs")]
+    SignedInput { input: usize },
+    #[error("HTLC id {actual} does not follow the previous one, expected {expected}")]
+    UnexpectedHtlcId { expected: u64, actual: u64 },
+    #[error("no HTLC {0} on the channel")]
+    UnknownHtlc(u64),
+    #[error("HTLC {0} is not irrevocably committed on both sides yet")]
+    HtlcNotCommitted(u64),
+    #[error("preimage does not hash to the payment hash of HTLC {0}")]
+    InvalidPaymentPreimage(u64),
+    #[error("commitment has {expected} HTLC outputs but {actual} HTLC signatures were given")]
+    HtlcSignatureCountMismatch { expected: usize, actual: usize },
+    #[error("HTLC signature {index} does not sign its second-stage transaction")]
+    InvalidHtlcSignature { index: usize },
+    #[error("the counterparty has not revoked its previous commitment yet")]
+    AwaitingRevokeAndAck,
+    #[error("revoke_and_ack received without a new commitment to revoke for")]
+    UnexpectedRevokeAndAck,
+    #[error("transaction has no output paying the funding script")]
+    MissingFundingOutput,
+    #[error("funding pubkey {0} is not in the funding script")]
+    FundingKeyNotInScript(bitcoin::PublicKey),
+    #[error("commitment is missing the {} HTLC of {amount_sat} sats", if *.offered { "offered" } else { "received" })]
+    MissingHtlcOutput { offered: bool, amount_sat: u64 },
+    #[error("output {index} is not to_local, to_remote, an anchor or an agreed HTLC")]
+    UnexpectedOutput { index: usize },
+    #[error("HTLC transaction {index} has no input")]
+    HtlcTxMissingInput { index: usize },
+    #[error("HTLC transaction {index} does not spend the commitment")]
+    HtlcTxSpendsOtherTx { index: usize },
+    #[error("HTLC transaction {index} spends vout {vout}, which the commitment does not have")]
+    HtlcTxUnknownVout { index: usize, vout: u32 },
+    #[error("HTLC transaction {index} spends vout {vout}, already spent by another HTLC transaction")]
+    HtlcTxDuplicateVout { index: usize, vout: u32 },
+    // boxed, as the error can carry the whole PSBT
+    #[error("could not extract the transaction from the PSBT: {0}")]
+    ExtractTx(Box<bitcoin::psbt::ExtractTxError>),
+    #[error(transparent)]
+    InvalidArguments(#[from] ExerciseError),
+}
+
+impl From<bitcoin::psbt::ExtractTxError> for ChannelError {
+    fn from(e: bitcoin::psbt::ExtractTxError) -> Self {
+        ChannelError::ExtractTx(Box::new(e))
+    }
+}
+
+/// Any error the crate returns. Code that calls into several chapters can
+/// `?` each chapter's error into this one instead of juggling them.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Exercise(#[from] ExerciseError),
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+    #[error(transparent)]
+    Musig(#[from] MusigError),
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+    #[error(transparent)]
+    Wire(#[from] WireError),
+    #[error(transparent)]
+    Gossip(#[from] GossipError),
+    #[error(transparent)]
+    Onion(#[from] OnionError),
+    #[error(transparent)]
+    Forward(#[from] ForwardError),
+    #[error(transparent)]
+    Watchtower(#[from] WatchtowerError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+    #[error(transparent)]
+    Persist(#[from] PersistError),
+    #[error(transparent)]
+    InteractiveTx(#[from] InteractiveTxError),
+    #[error(transparent)]
+    ShutdownScript(#[from] ShutdownScriptError),
+    #[error(transparent)]
+    Closing(#[from] ClosingError),
+    #[error(transparent)]
+    ChainSource(#[from] ChainSourceError),
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+    #[error(transparent)]
+    Invoice(#[from] InvoiceError),
+    #[error(transparent)]
+    Offer(#[from] OfferError),
+}
@@ -0,0 +1,266 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::{Amount, FeeRate};
+use internal::errors::{ChainSourceError, ExerciseError};
+use internal::sign_utils::counterparty_htlc_sighash_type;
+use internal::wire::FeeRange;
+use serde_json::Value;
+
+/// Value of each `option_anchors` anchor output.
+pub const ANCHOR_OUTPUT_VALUE: Amount = Amount::from_sat(330);
+
+/// BOLT 3 expected weights used to compute commitment and HTLC fees.
+pub const COMMITMENT_TX_BASE_WEIGHT: u64 = 724;
+pub const COMMITMENT_TX_BASE_ANCHOR_WEIGHT: u64 = 1124;
+pub const COMMITMENT_TX_WEIGHT_PER_HTLC: u64 = 172;
+pub const HTLC_TIMEOUT_TX_WEIGHT: u64 = 663;
+pub const HTLC_TIMEOUT_ANCHOR_TX_WEIGHT: u64 = 666;
+pub const HTLC_SUCCESS_TX_WEIGHT: u64 = 703;
+pub const HTLC_SUCCESS_ANCHOR_TX_WEIGHT: u64 = 706;
+
+/// Weight of the witness spending the 2-of-2 funding output: the item count,
+/// the empty NULLDUMMY element, two 73-byte signatures and the 71-byte script.
+pub const FUNDING_WITNESS_WEIGHT: u64 = 1 + 1 + 2 * (1 + 73) + (1 + 71);
+
+/// Weight of a one-input, one-P2WPKH-output transaction sweeping a `to_local`
+/// output through its delayed branch, with the `<sig> <> <to_local_script>`
+/// witness.
+pub const DELAYED_SWEEP_TX_WEIGHT: u64 = 4 * (4 + 4 + 1 + 1) + 2 + 4 * (32 + 4 + 1 + 4) + 4 * (8 + 1 + 22)
+    + (1 + 1 + 73 + 1 + 1 + 77);
+
+/// Weight of a transaction spending our anchor output and a P2WPKH wallet
+/// input into one P2WPKH change output. The anchor input's witness is
+/// `<sig> <anchor_script>`, with the 40-byte anchor script.
+pub const ANCHOR_CPFP_TX_WEIGHT: u64 = 4 * (4 + 1 + 1 + 4) + 2 + 2 * 4 * (32 + 4 + 1 + 4) + 4 * (8 + 1 + 22)
+    + (1 + 1 + 73 + 1 + 40) + (1 + 1 + 73 + 1 + 33);
+
+/// Weight of a splice-in transaction: the old funding output and a P2WPKH
+/// wallet input, spent into the new funding output and P2WPKH change.
+pub const SPLICE_IN_TX_WEIGHT: u64 = 4 * (4 + 1 + 1 + 4) + 2 + 2 * 4 * (32 + 4 + 1 + 4) + FUNDING_WITNESS_WEIGHT
+    + (1 + 1 + 73 + 1 + 33) + 4 * (8 + 1 + 34) + 4 * (8 + 1 + 22);
+
+/// A P2WPKH wallet input added to pay fees: outpoint, empty script_sig and
+/// sequence, then the `<sig> <pubkey>` witness.
+pub const P2WPKH_INPUT_WEIGHT: u64 = 4 * (32 + 4 + 1 + 4) + (1 + 1 + 73 + 1 + 33);
+
+/// A P2WPKH change output.
+pub const P2WPKH_OUTPUT_WEIGHT: u64 = 4 * (8 + 1 + 22);
+
+/// The fee for a transaction of `weight` at `feerate_per_kw`, rounded down.
+pub fn fee_for_weight(feerate_per_kw: u64, weight: u64) -> Amount {
+    Amount::from_sat(feerate_per_kw * weight / 1000)
+}
+
+/// Bitcoin Core's default `-incrementalrelayfee` of 1 sat/vB, which a
+/// replacement has to pay on top of the fee of what it replaces.
+pub const INCREMENTAL_RELAY_FEERATE_PER_KW: u64 = 250;
+
+/// The lowest fee BIP 125 lets a transaction of `replacement_weight` pay to
+/// replace one paying `original_fee`: all of it (rule 3), and then enough
+/// for its own relay at the incremental feerate (rule 4).
+pub fn min_replacement_fee(original_fee: Amount, replacement_weight: u64) -> Amount {
+    original_fee + fee_for_weight(INCREMENTAL_RELAY_FEERATE_PER_KW, replacement_weight)
+}
+
+pub fn commit_tx_fee(feerate_per_kw: u64, num_untrimmed_htlcs: usize, anchors: bool) -> Amount {
+    let base_weight = if anchors {
+        COMMITMENT_TX_BASE_ANCHOR_WEIGHT
+    } else {
+        COMMITMENT_TX_BASE_WEIGHT
+    };
+    let weight = base_weight + COMMITMENT_TX_WEIGHT_PER_HTLC * num_untrimmed_htlcs as u64;
+
+    fee_for_weight(feerate_per_kw, weight)
+}
+
+/// The local and remote balances once the funder has paid `fee`, the fee of
+/// a commitment or closing transaction (BOLT 3). The non-funder's balance is
+/// left untouched, and a funder that can't afford the fee is an error, not
+/// an empty output.
+pub fn balances_after_funder_fee(
+    local_amount: Amount,
+    remote_amount: Amount,
+    local_is_funder: bool,
+    fee: Amount,
+) -> Result<(Amount, Amount), ExerciseError> {
+    let funder_amount = if local_is_funder { local_amount } else { remote_amount };
+    let funder_amount = funder_amount
+        .checked_sub(fee)
+        .ok_or(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: funder_amount.to_sat() })?;
+
+    Ok(if local_is_funder { (funder_amount, remote_amount) } else { (local_amount, funder_amount) })
+}
+
+/// Weight of the second-stage transaction claiming an HTLC: HTLC-timeout
+/// for one we offered, HTLC-success for one we received.
+pub fn htlc_tx_weight(offered: bool, anchors: bool) -> u64 {
+    match (offered, anchors) {
+        (true, false) => HTLC_TIMEOUT_TX_WEIGHT,
+        (true, true) => HTLC_TIMEOUT_ANCHOR_TX_WEIGHT,
+        (false, false) => HTLC_SUCCESS_TX_WEIGHT,
+        (false, true) => HTLC_SUCCESS_ANCHOR_TX_WEIGHT,
+    }
+}
+
+pub fn htlc_timeout_fee(feerate_per_kw: u64, anchors: bool) -> Amount {
+    fee_for_weight(feerate_per_kw, htlc_tx_weight(true, anchors))
+}
+
+pub fn htlc_success_fee(feerate_per_kw: u64, anchors: bool) -> Amount {
+    fee_for_weight(feerate_per_kw, htlc_tx_weight(false, anchors))
+}
+
+/// How a channel's second-stage HTLC transactions pay their fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtlcFeeMode {
+    /// Out of the HTLC at the commitment's feerate, and signed with
+    /// SIGHASH_ALL by both sides.
+    Legacy,
+    /// `option_anchor_outputs`: out of the HTLC at the commitment's feerate,
+    /// but the counterparty signs with SIGHASH_SINGLE|ANYONECANPAY so more
+    /// fee can be added.
+    Anchors,
+    /// `option_anchors_zero_fee_htlc_tx`: no fee at all. Whoever broadcasts
+    /// the transaction adds an input to pay for it at the feerate of the
+    /// day.
+    ZeroFeeAnchors,
+}
+
+impl HtlcFeeMode {
+    pub fn anchors(self) -> bool {
+        self != HtlcFeeMode::Legacy
+    }
+
+    pub fn htlc_timeout_fee(self, feerate_per_kw: u64) -> Amount {
+        match self {
+            HtlcFeeMode::ZeroFeeAnchors => Amount::ZERO,
+            _ => htlc_timeout_fee(feerate_per_kw, self.anchors()),
+        }
+    }
+
+    pub fn htlc_success_fee(self, feerate_per_kw: u64) -> Amount {
+        match self {
+            HtlcFeeMode::ZeroFeeAnchors => Amount::ZERO,
+            _ => htlc_success_fee(feerate_per_kw, self.anchors()),
+        }
+    }
+
+    /// The sighash type the counterparty signs our HTLC transactions with.
+    pub fn counterparty_sighash_type(self) -> EcdsaSighashType {
+        counterparty_htlc_sighash_type(self.anchors())
+    }
+}
+
+/// The lowest `feerate_per_kw` worth using. 1 sat/vB is 250 sat per 1000
+/// weight units, but bitcoind rounds weight up to whole vbytes, so 250
+/// can fall just short of its minimum relay fee.
+pub const FEERATE_FLOOR_PER_KW: u64 = 253;
+
+/// Lightning's `feerate_per_kw` is in satoshis per 1000 weight units, the
+/// same unit as rust-bitcoin's sat/kWU.
+pub fn feerate_per_kw(fee_rate: FeeRate) -> u64 {
+    fee_rate.to_sat_per_kwu()
+}
+
+pub fn fee_rate_from_per_kw(feerate_per_kw: u64) -> FeeRate {
+    FeeRate::from_sat_per_kwu(feerate_per_kw)
+}
+
+/// Wallets and block explorers quote feerates in sat/vB, often with a
+/// fraction. A vbyte is 4 weight units, so 1 sat/vB is 250 per kw. Rounds
+/// up, so the result never pays less than what was quoted.
+pub fn sat_per_vb_to_per_kw(sat_per_vb: f64) -> u64 {
+    (sat_per_vb * 250.0).ceil() as u64
+}
+
+pub fn per_kw_to_sat_per_vb(feerate_per_kw: u64) -> f64 {
+    feerate_per_kw as f64 / 250.0
+}
+
+/// How soon a transaction needs to confirm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfirmationTarget {
+    /// In the next block or two: penalties, HTLC claims near their expiry,
+    /// and anchor CPFPs for them.
+    Urgent,
+    /// Within about an hour: funding and cooperative closes.
+    Normal,
+    /// Within a day or so: sweeping outputs nobody else can spend.
+    Background,
+}
+
+/// Where exercises get their feerates, so every transaction they build
+/// uses the same ones.
+pub trait FeeEstimator {
+    /// Never below `FEERATE_FLOOR_PER_KW`.
+    fn feerate_per_kw(&self, target: ConfirmationTarget) -> u64;
+}
+
+/// The same feerate for every target, for tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedFeeEstimator(pub u64);
+
+impl FeeEstimator for FixedFeeEstimator {
+    fn feerate_per_kw(&self, target: ConfirmationTarget) -> u64 {
+        self.0.max(FEERATE_FLOOR_PER_KW)
+    }
+}
+
+/// The fee a side proposes in `closing_signed` for a closing transaction of
+/// `closing_tx_weight`, and the range it would agree to: from a close that
+/// confirms within a day to one that confirms right away.
+pub fn closing_fee_proposal(fee_estimator: &dyn FeeEstimator, closing_tx_weight: u64) -> (Amount, FeeRange) {
+    let fee = |target| fee_for_weight(fee_estimator.feerate_per_kw(target), closing_tx_weight);
+    let fee_range = FeeRange {
+        min_fee_satoshis: fee(ConfirmationTarget::Background),
+        max_fee_satoshis: fee(ConfirmationTarget::Urgent),
+    };
+    (fee(ConfirmationTarget::Normal), fee_range)
+}
+
+pub const MEMPOOL_SPACE_FEES_URL: &str = "https://mempool.space/api/v1/fees/recommended";
+
+/// The feerates mempool.space recommended when `fetch` was called, in
+/// sat/vB. Fetch again for fresh ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MempoolSpaceFeeEstimator {
+    pub fastest_fee: f64,
+    pub half_hour_fee: f64,
+    pub economy_fee: f64,
+}
+
+impl MempoolSpaceFeeEstimator {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fetch(url: &str) -> Result<MempoolSpaceFeeEstimator, ChainSourceError> {
+        let body = reqwest::blocking::get(url)
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+            .map_err(|err| ChainSourceError::Http(err.to_string()))?;
+        let fees = serde_json::from_str(&body).map_err(|err| ChainSourceError::InvalidResponse(err.to_string()))?;
+        MempoolSpaceFeeEstimator::from_json(&fees)
+    }
+
+    /// `{"fastestFee": 12, "halfHourFee": 8, "hourFee": 6, "economyFee": 3, "minimumFee": 1}`
+    pub fn from_json(fees: &Value) -> Result<MempoolSpaceFeeEstimator, ChainSourceError> {
+        let fee = |name: &str| {
+            fees[name].as_f64().ok_or_else(|| ChainSourceError::InvalidResponse(format!("no {} in fees", name)))
+        };
+        Ok(MempoolSpaceFeeEstimator {
+            fastest_fee: fee("fastestFee")?,
+            half_hour_fee: fee("halfHourFee")?,
+            economy_fee: fee("economyFee")?,
+        })
+    }
+}
+
+impl FeeEstimator for MempoolSpaceFeeEstimator {
+    fn feerate_per_kw(&self, target: ConfirmationTarget) -> u64 {
+        let sat_per_vb = match target {
+            ConfirmationTarget::Urgent => self.fastest_fee,
+            ConfirmationTarget::Normal => self.half_hour_fee,
+            ConfirmationTarget::Background => self.economy_fee,
+        };
+        sat_per_vb_to_per_kw(sat_per_vb).max(FEERATE_FLOOR_PER_KW)
+    }
+}
@@ -0,0 +1,64 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! The fuzz targets, so the `cargo fuzz` crate in `fuzz/` and a plain
+//! `cargo test --features fuzz` run the same checks. Each takes any bytes,
+//! and panics only on a bug: a parser crashing, or accepting something it
+//! can't write back.
+use crate::exercises::onion::{peel_onion, PeeledOnion};
+use crate::internal;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use internal::invoice::decode_invoice;
+use internal::onion_utils::{
+    generate_cipher_stream, generate_key, packet_hmac, shared_secret, xor_in_place, HopPayload, OnionPacket,
+    HOP_PAYLOADS_LEN, ONION_VERSION,
+};
+use internal::tlv::{read_tlv_stream, write_tlv_stream};
+
+/// The even types `fuzz_tlv_roundtrip` accepts, so streams with known even
+/// records get past the "it's OK to be odd" check.
+pub const FUZZ_KNOWN_TLV_TYPES: [u64; 4] = [0, 2, 4, 6];
+
+/// The node peeling the fuzzed onions, and the ephemeral key they come with.
+const FUZZ_NODE_KEY: [u8; 32] = [0x41; 32];
+const FUZZ_EPHEMERAL_KEY: [u8; 32] = [0x42; 32];
+
+/// A stream that reads must write back to the same bytes: BigSizes are
+/// minimal and types ascending, so there is only one encoding.
+pub fn fuzz_tlv_roundtrip(data: &[u8]) {
+    let Ok(records) = read_tlv_stream(data, &FUZZ_KNOWN_TLV_TYPES) else {
+        return;
+    };
+    let encoded = write_tlv_stream(&records).expect("records read from a stream can be written");
+    assert_eq!(encoded, data, "TLV stream did not round trip");
+    assert_eq!(read_tlv_stream(&encoded, &FUZZ_KNOWN_TLV_TYPES).unwrap(), records);
+}
+
+/// Any string, valid or not, decodes without panicking.
+pub fn fuzz_invoice_decode(data: &[u8]) {
+    if let Ok(invoice) = std::str::from_utf8(data) {
+        let _ = decode_invoice(invoice);
+    }
+}
+
+/// `data` is what the node sees once its layer is decrypted: a hop payload,
+/// the next HMAC and whatever follows. It is encrypted and given a valid
+/// HMAC, so peeling gets past the HMAC check to the payload parsing.
+pub fn fuzz_onion_peel(data: &[u8]) {
+    let mut hop_payloads = [0u8; HOP_PAYLOADS_LEN];
+    let len = data.len().min(HOP_PAYLOADS_LEN);
+    hop_payloads[..len].copy_from_slice(&data[..len]);
+
+    let node_key = SecretKey::from_slice(&FUZZ_NODE_KEY).unwrap();
+    let ephemeral_key = SecretKey::from_slice(&FUZZ_EPHEMERAL_KEY).unwrap();
+    let public_key = secp256k1PublicKey::from_secret_key(&Secp256k1::new(), &ephemeral_key);
+    let secret = shared_secret(&ephemeral_key, &secp256k1PublicKey::from_secret_key(&Secp256k1::new(), &node_key));
+    xor_in_place(&mut hop_payloads, &generate_cipher_stream(&generate_key(b"rho", &secret), HOP_PAYLOADS_LEN));
+    let associated_data = [0x43; 32];
+    let hmac = packet_hmac(&generate_key(b"mu", &secret), &hop_payloads, &associated_data);
+    let packet = OnionPacket { version: ONION_VERSION, public_key, hop_payloads, hmac };
+
+    let payload = match peel_onion(&packet, &node_key, &associated_data) {
+        Ok(PeeledOnion::Forward { payload, .. }) | Ok(PeeledOnion::Receive { payload }) => payload,
+        Err(_) => return,
+    };
+    let _ = HopPayload::decode(&payload);
+}
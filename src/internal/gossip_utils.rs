@@ -0,0 +1,36 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::{Message, PublicKey as secp256k1PublicKey, Secp256k1};
+use internal::errors::GossipError;
+use internal::wire::{ChannelUpdate, NodeAnnouncement};
+
+fn verify_signature(
+    hash: [u8; 32],
+    signature: &Signature,
+    key: &secp256k1PublicKey,
+    name: &'static str,
+) -> Result<(), GossipError> {
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(hash), signature, key)
+        .map_err(|_| GossipError::InvalidSignature(name))
+}
+
+/// A node announcement has to be signed by the node it announces.
+pub fn verify_node_announcement(announcement: &NodeAnnouncement) -> Result<(), GossipError> {
+    verify_signature(announcement.signing_hash(), &announcement.signature, &announcement.node_id, "node_id")
+}
+
+/// A channel update has to be signed by the node its direction starts from:
+/// `node_id_1` for direction 0 and `node_id_2` for direction 1.
+pub fn verify_channel_update(
+    update: &ChannelUpdate,
+    node_id_1: &secp256k1PublicKey,
+    node_id_2: &secp256k1PublicKey,
+) -> Result<(), GossipError> {
+    let (key, name) = match update.direction() {
+        0 => (node_id_1, "node_id_1"),
+        _ => (node_id_2, "node_id_2"),
+    };
+    verify_signature(update.signing_hash(), &update.signature, key, name)
+}
@@ -0,0 +1,76 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! Turns `cargo test` output into a pass or fail for each exercise, for the
+//! `grader` binary. Every test of exercise N is named `test_NN_...`, so the
+//! number tells which exercise a test checks.
+use std::collections::BTreeMap;
+
+/// The test modules of the core and the advanced exercises.
+pub const EXERCISES_TEST_MODULE: &str = "exercises::test";
+pub const EXERCISES_ADVANCED_TEST_MODULE: &str = "exercises_advanced::test";
+
+/// How the tests of one exercise went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExerciseResult {
+    pub exercise: u32,
+    pub passed: usize,
+    /// The names of the tests that failed, without the module.
+    pub failed: Vec<String>,
+}
+
+impl ExerciseResult {
+    pub fn is_pass(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn total(&self) -> usize {
+        self.passed + self.failed.len()
+    }
+}
+
+/// The `cargo test` filter for exercise `exercise` of `module`, or for all
+/// of them.
+pub fn test_filter(module: &str, exercise: Option<u32>) -> String {
+    match exercise {
+        Some(exercise) => format!("{}::test_{:02}_", module, exercise),
+        None => format!("{}::test_", module),
+    }
+}
+
+/// `test_07_build_commitment_transaction` is a test of exercise 7.
+pub fn exercise_number(test_name: &str) -> Option<u32> {
+    let number = test_name.strip_prefix("test_")?.split('_').next()?;
+    number.parse().ok()
+}
+
+/// Reads the `test <name> ... ok` and `test <name> ... FAILED` lines of
+/// `module`'s tests, and groups them by exercise.
+pub fn parse_test_output(output: &str, module: &str) -> Vec<ExerciseResult> {
+    let mut results: BTreeMap<u32, ExerciseResult> = BTreeMap::new();
+    for line in output.lines() {
+        let Some((name, outcome)) = line.strip_prefix("test ").and_then(|line| line.split_once(" ... ")) else {
+            continue;
+        };
+        let Some(name) = name.strip_prefix(module).and_then(|name| name.strip_prefix("::")) else {
+            continue;
+        };
+        let Some(exercise) = exercise_number(name) else {
+            continue;
+        };
+        let result = results.entry(exercise).or_insert_with(|| ExerciseResult { exercise, passed: 0, failed: vec![] });
+        match outcome.trim() {
+            "ok" => result.passed += 1,
+            "FAILED" => result.failed.push(name.to_string()),
+            _ => {}
+        }
+    }
+    results.into_values().filter(|result| result.total() > 0).collect()
+}
+
+/// The share of the exercises that pass, as a percentage.
+pub fn completion_percent(results: &[ExerciseResult]) -> u32 {
+    if results.is_empty() {
+        return 0;
+    }
+    let passed = results.iter().filter(|result| result.is_pass()).count();
+    (passed * 100 / results.len()) as u32
+}
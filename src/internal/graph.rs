@@ -0,0 +1,238 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{apply_channel_update, verify_channel_announcement};
+use crate::internal;
+use bitcoin::constants::ChainHash;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::{Amount, TxOut};
+use internal::errors::GossipError;
+use internal::gossip_utils::verify_node_announcement;
+use internal::units::MilliSat;
+use internal::wire::{ChannelAnnouncement, ChannelUpdate, NodeAnnouncement, ShortChannelId};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// BOLT 7 lets nodes forget channels that haven't been updated in two weeks.
+pub const STALE_CHANNEL_AGE: u32 = 14 * 24 * 60 * 60;
+
+/// How one direction of a channel forwards payments, as last announced in a
+/// `channel_update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPolicy {
+    pub enabled: bool,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: MilliSat,
+    pub htlc_maximum_msat: MilliSat,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub last_update: u32,
+}
+
+impl ChannelPolicy {
+    /// What the node at the start of this direction charges to forward
+    /// `amount` over it.
+    pub fn fee_for(&self, amount: MilliSat) -> MilliSat {
+        let proportional = amount.to_msat() as u128 * self.fee_proportional_millionths as u128 / 1_000_000;
+        MilliSat::from_msat(self.fee_base_msat as u64 + proportional as u64)
+    }
+
+    /// Whether an HTLC of `amount` may use this direction at all.
+    pub fn can_carry(&self, amount: MilliSat) -> bool {
+        self.enabled && amount >= self.htlc_minimum_msat && amount <= self.htlc_maximum_msat
+    }
+}
+
+/// A public channel between `node_one` and `node_two`, where `node_one` is
+/// the announcement's `node_id_1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphChannel {
+    pub short_channel_id: ShortChannelId,
+    pub node_one: secp256k1PublicKey,
+    pub node_two: secp256k1PublicKey,
+    pub capacity: Amount,
+    pub one_to_two: Option<ChannelPolicy>,
+    pub two_to_one: Option<ChannelPolicy>,
+}
+
+impl GraphChannel {
+    pub fn new(
+        short_channel_id: ShortChannelId,
+        node_one: secp256k1PublicKey,
+        node_two: secp256k1PublicKey,
+        capacity: Amount,
+    ) -> GraphChannel {
+        GraphChannel { short_channel_id, node_one, node_two, capacity, one_to_two: None, two_to_one: None }
+    }
+
+    /// The policy for channel_update direction `direction`.
+    pub fn policy(&self, direction: u8) -> Option<&ChannelPolicy> {
+        match direction {
+            0 => self.one_to_two.as_ref(),
+            _ => self.two_to_one.as_ref(),
+        }
+    }
+
+    pub fn policy_mut(&mut self, direction: u8) -> &mut Option<ChannelPolicy> {
+        match direction {
+            0 => &mut self.one_to_two,
+            _ => &mut self.two_to_one,
+        }
+    }
+}
+
+/// One direction of a channel: payments flow from `source` to `target`
+/// under `policy`.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectedChannel<'a> {
+    pub channel: &'a GraphChannel,
+    pub direction: u8,
+}
+
+impl<'a> DirectedChannel<'a> {
+    pub fn source(&self) -> secp256k1PublicKey {
+        match self.direction {
+            0 => self.channel.node_one,
+            _ => self.channel.node_two,
+        }
+    }
+
+    pub fn target(&self) -> secp256k1PublicKey {
+        match self.direction {
+            0 => self.channel.node_two,
+            _ => self.channel.node_one,
+        }
+    }
+
+    pub fn policy(&self) -> Option<&'a ChannelPolicy> {
+        self.channel.policy(self.direction)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphNode {
+    pub channels: BTreeSet<ShortChannelId>,
+    pub announcement: Option<NodeAnnouncement>,
+}
+
+/// The public network as learned from gossip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkGraph {
+    pub chain_hash: ChainHash,
+    pub channels: BTreeMap<ShortChannelId, GraphChannel>,
+    pub nodes: BTreeMap<secp256k1PublicKey, GraphNode>,
+}
+
+impl NetworkGraph {
+    pub fn new(chain_hash: ChainHash) -> NetworkGraph {
+        NetworkGraph { chain_hash, channels: BTreeMap::new(), nodes: BTreeMap::new() }
+    }
+
+    /// Add an announced channel once it checks out against the funding
+    /// output at its `short_channel_id`. Returns `false` if we already know it.
+    pub fn add_channel_announcement(
+        &mut self,
+        announcement: &ChannelAnnouncement,
+        funding_output: &TxOut,
+    ) -> Result<bool, GossipError> {
+        if announcement.chain_hash != self.chain_hash {
+            return Err(GossipError::WrongChain);
+        }
+        if self.channels.contains_key(&announcement.short_channel_id) {
+            return Ok(false);
+        }
+        verify_channel_announcement(announcement, funding_output)?;
+
+        self.add_channel_unchecked(GraphChannel::new(
+            announcement.short_channel_id,
+            announcement.node_id_1,
+            announcement.node_id_2,
+            funding_output.value,
+        ));
+        Ok(true)
+    }
+
+    /// Add a channel without any checks, for building graphs where there is
+    /// no chain to check announcements against.
+    pub fn add_channel_unchecked(&mut self, channel: GraphChannel) {
+        for node_id in [channel.node_one, channel.node_two] {
+            self.nodes.entry(node_id).or_default().channels.insert(channel.short_channel_id);
+        }
+        self.channels.insert(channel.short_channel_id, channel);
+    }
+
+    /// Returns `false` if the update was no newer than what we have.
+    pub fn update_channel(&mut self, update: &ChannelUpdate) -> Result<bool, GossipError> {
+        if update.chain_hash != self.chain_hash {
+            return Err(GossipError::WrongChain);
+        }
+        let channel = self
+            .channels
+            .get_mut(&update.short_channel_id)
+            .ok_or(GossipError::UnknownChannel(update.short_channel_id.0))?;
+        apply_channel_update(channel, update)
+    }
+
+    /// Nodes only get a place in the graph through their channels, so an
+    /// announcement from a node without one is refused.
+    pub fn update_node(&mut self, announcement: &NodeAnnouncement) -> Result<bool, GossipError> {
+        let node = self.nodes.get_mut(&announcement.node_id).ok_or(GossipError::UnknownNode)?;
+        verify_node_announcement(announcement)?;
+
+        if node.announcement.as_ref().is_some_and(|known| known.timestamp >= announcement.timestamp) {
+            return Ok(false);
+        }
+        node.announcement = Some(announcement.clone());
+        Ok(true)
+    }
+
+    /// Every direction, starting from another node, that pays into `node_id`.
+    pub fn incoming_channels(&self, node_id: &secp256k1PublicKey) -> Vec<DirectedChannel<'_>> {
+        self.outgoing_channels(node_id)
+            .into_iter()
+            .map(|outgoing| DirectedChannel { channel: outgoing.channel, direction: 1 - outgoing.direction })
+            .collect()
+    }
+
+    /// Both directions of every channel we know, starting from `node_id`.
+    pub fn outgoing_channels(&self, node_id: &secp256k1PublicKey) -> Vec<DirectedChannel<'_>> {
+        let Some(node) = self.nodes.get(node_id) else { return Vec::new() };
+        node.channels
+            .iter()
+            .map(|short_channel_id| {
+                let channel = &self.channels[short_channel_id];
+                let direction = if channel.node_one == *node_id { 0 } else { 1 };
+                DirectedChannel { channel, direction }
+            })
+            .collect()
+    }
+
+    /// Forget channels whose newest update is older than `STALE_CHANNEL_AGE`
+    /// at time `now`, and any nodes left without channels. A channel nobody
+    /// has sent an update for is useless for routing, so it goes too.
+    pub fn prune_stale_channels(&mut self, now: u32) -> Vec<ShortChannelId> {
+        let cutoff = now.saturating_sub(STALE_CHANNEL_AGE);
+        let stale: Vec<ShortChannelId> = self
+            .channels
+            .values()
+            .filter(|channel| {
+                let newest = [channel.one_to_two, channel.two_to_one]
+                    .iter()
+                    .flatten()
+                    .map(|policy| policy.last_update)
+                    .max();
+                newest.is_none_or(|newest| newest < cutoff)
+            })
+            .map(|channel| channel.short_channel_id)
+            .collect();
+
+        for short_channel_id in &stale {
+            let channel = self.channels.remove(short_channel_id).unwrap();
+            for node_id in [channel.node_one, channel.node_two] {
+                let node = self.nodes.get_mut(&node_id).unwrap();
+                node.channels.remove(short_channel_id);
+                if node.channels.is_empty() {
+                    self.nodes.remove(&node_id);
+                }
+            }
+        }
+        stale
+    }
+}
@@ -0,0 +1,105 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! Broadcast transactions to a local regtest bitcoind and mine them, so
+//! tests can check that consensus really accepts what the exercises build.
+//! It talks to bitcoind through the same `BitcoindClient` as the
+//! interactive exercises, and needs a node with a loaded wallet on
+//! `0.0.0.0:18443`.
+use crate::internal;
+use bitcoin::address::Address;
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::hash_types::{BlockHash, Txid};
+use bitcoin::{Amount, Block, Network, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness};
+use internal::bitcoind_client::{get_bitcoind_client, BitcoindClient};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// Coinbase outputs can only be spent after 100 blocks.
+const COINBASE_MATURITY: u64 = 100;
+
+pub struct RegtestHarness {
+    pub bitcoind: BitcoindClient,
+    /// Where the blocks we mine pay their coinbase.
+    mining_address: Address,
+}
+
+impl RegtestHarness {
+    /// Connect to bitcoind, and mine enough blocks for the wallet to have
+    /// mature coins to fund tests with.
+    pub async fn new() -> RegtestHarness {
+        let bitcoind = get_bitcoind_client().await;
+        let mining_address = bitcoind.get_new_address().await;
+        let harness = RegtestHarness { bitcoind, mining_address };
+
+        let balance = harness.call("getbalance", &[]).await.unwrap().as_f64().unwrap_or(0.0);
+        if balance < 1.0 {
+            harness.mine_blocks(COINBASE_MATURITY + 1).await;
+        }
+        harness
+    }
+
+    async fn call(&self, method: &str, params: &[Value]) -> std::io::Result<Value> {
+        self.bitcoind.bitcoind_rpc_client.call_method::<Value>(method, params).await
+    }
+
+    pub async fn best_height(&self) -> u32 {
+        self.call("getblockcount", &[]).await.unwrap().as_u64().unwrap() as u32
+    }
+
+    pub async fn mine_blocks(&self, num_blocks: u64) -> Vec<BlockHash> {
+        let hashes =
+            self.call("generatetoaddress", &[json!(num_blocks), json!(self.mining_address.to_string())]).await.unwrap();
+        hashes.as_array().unwrap().iter().map(|hash| BlockHash::from_str(hash.as_str().unwrap()).unwrap()).collect()
+    }
+
+    pub async fn get_block(&self, hash: &BlockHash) -> Block {
+        let hex = self.call("getblock", &[json!(hash.to_string()), json!(0)]).await.unwrap();
+        deserialize(&hex::decode(hex.as_str().unwrap()).unwrap()).unwrap()
+    }
+
+    /// A confirmed wallet output of exactly `amount`, and the input that
+    /// spends it. Build a transaction on it, then sign it with
+    /// `sign_with_wallet`.
+    pub async fn fund(&self, amount: Amount) -> (TxIn, TxOut) {
+        let address = self.bitcoind.get_new_address().await;
+        let txid = self.call("sendtoaddress", &[json!(address.to_string()), json!(amount.to_btc())]).await.unwrap();
+        let wallet_tx = self.call("gettransaction", &[txid]).await.unwrap();
+        let tx: Transaction = deserialize(&hex::decode(wallet_tx["hex"].as_str().unwrap()).unwrap()).unwrap();
+        self.mine_blocks(1).await;
+
+        let vout = tx.output.iter().position(|output| output.script_pubkey == address.script_pubkey()).unwrap();
+        let txin = TxIn {
+            previous_output: bitcoin::OutPoint::new(tx.compute_txid(), vout as u32),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        (txin, tx.output[vout].clone())
+    }
+
+    /// Have the wallet sign the inputs of `tx` it owns. They are all segwit,
+    /// so the txid doesn't change and transactions built on `tx` stay valid.
+    pub async fn sign_with_wallet(&self, tx: &Transaction) -> Transaction {
+        let signed = self.bitcoind.sign_raw_transaction_with_wallet(serialize_hex(tx)).await;
+        assert!(signed.complete, "wallet could not sign every input");
+        deserialize(&hex::decode(signed.hex).unwrap()).unwrap()
+    }
+
+    /// Submit `tx` to bitcoind's mempool. Unlike `BroadcasterInterface`,
+    /// this waits for the answer, so a rejected transaction is an error with
+    /// bitcoind's reason in it.
+    pub async fn broadcast(&self, tx: &Transaction) -> std::io::Result<Txid> {
+        let txid = self.call("sendrawtransaction", &[json!(serialize_hex(tx))]).await?;
+        Ok(Txid::from_str(txid.as_str().unwrap()).unwrap())
+    }
+
+    /// Broadcast `tx`, mine a block, and check the block has it. Returns
+    /// the block and its height.
+    pub async fn assert_confirms(&self, tx: &Transaction) -> (Block, u32) {
+        let txid =
+            self.broadcast(tx).await.unwrap_or_else(|err| panic!("bitcoind rejected {}: {}", tx.compute_txid(), err));
+        let hash = self.mine_blocks(1).await[0];
+        let block = self.get_block(&hash).await;
+        assert!(block.txdata.iter().any(|block_tx| block_tx.compute_txid() == txid), "{} was not mined", txid);
+        (block, self.best_height().await)
+    }
+}
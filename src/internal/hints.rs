@@ -0,0 +1,72 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! Hints for learners stuck on an exercise. A failing exercise test prints
+//! as many as `HINT_LEVEL` asks for, from the idea behind the exercise to
+//! most of the answer, so `HINT_LEVEL=1 cargo test test_01` gives the first.
+use std::fmt::Debug;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+pub const HINT_LEVEL_VAR: &str = "HINT_LEVEL";
+
+/// One hint for each level, from 1 to 3.
+pub const MAX_HINT_LEVEL: usize = 3;
+
+/// What `grader --help` says about hints.
+pub fn hints_help() -> String {
+    format!(
+        "Hints: the exercise tests print hints when they fail with {} set to 1, 2 or 3, \
+         e.g. `{}=1 cargo test test_01`.",
+        HINT_LEVEL_VAR,
+        HINT_LEVEL_VAR
+    )
+}
+
+/// The tests of one exercise, and the hints for when they fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExerciseCheck {
+    pub exercise: u32,
+    /// The concept the exercise is about.
+    pub concept: &'static str,
+    /// The helpers and types that do most of the work.
+    pub api: &'static str,
+    /// Close to the answer itself.
+    pub solution: &'static str,
+}
+
+impl ExerciseCheck {
+    /// `HINT_LEVEL`, or 0 when it isn't set. Anything above 3 shows all
+    /// three hints.
+    pub fn hint_level() -> usize {
+        std::env::var(HINT_LEVEL_VAR).ok().and_then(|level| level.trim().parse().ok()).unwrap_or(0).min(MAX_HINT_LEVEL)
+    }
+
+    /// The hints up to `level`, and how to see more.
+    pub fn hints(&self, level: usize) -> String {
+        let hints = [("concept", self.concept), ("API", self.api), ("solution", self.solution)];
+        let mut out = format!("Exercise {} failed.\n", self.exercise);
+        for (i, (kind, hint)) in hints.iter().take(level).enumerate() {
+            out.push_str(&format!("Hint {} ({}): {}\n", i + 1, kind, hint));
+        }
+        if level < MAX_HINT_LEVEL {
+            out.push_str(&format!("Run with {}={} for a hint.\n", HINT_LEVEL_VAR, level + 1));
+        }
+        out
+    }
+
+    /// Run a test of the exercise. If it fails, including when the exercise
+    /// panics with `todo!()`, print the hints before failing.
+    pub fn run<R>(&self, test: impl FnOnce() -> R) -> R {
+        match catch_unwind(AssertUnwindSafe(test)) {
+            Ok(result) => result,
+            Err(panic) => {
+                eprint!("{}", self.hints(ExerciseCheck::hint_level()));
+                resume_unwind(panic)
+            }
+        }
+    }
+
+    /// Check a value on the way to the answer, like the witness script a
+    /// funding output hashes, so a wrong answer says which step went wrong.
+    pub fn expect_eq<T: PartialEq + Debug>(&self, what: &str, actual: T, expected: T) {
+        assert!(actual == expected, "Exercise {}: {} is {:?}, expected {:?}", self.exercise, what, actual, expected);
+    }
+}
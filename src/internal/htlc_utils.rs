@@ -0,0 +1,104 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::{Amount, ScriptBuf, Witness};
+use internal::fee_utils::{fee_for_weight, htlc_tx_weight, HtlcFeeMode};
+use internal::keys::ChannelKeys;
+use internal::script_utils::{
+    build_anchor_htlc_offerer_witness_script, build_anchor_htlc_receiver_witness_script,
+    build_htlc_offerer_witness_script, build_htlc_receiver_witness_script,
+};
+use internal::units::MilliSat;
+use internal::payments::{PaymentHash160, PaymentPreimage};
+
+/// An HTLC is trimmed when its value can't cover the dust limit plus the fee
+/// of the second-stage transaction that would claim it. With zero-fee HTLC
+/// transactions that fee is 0, so only the dust limit counts.
+pub fn is_htlc_trimmed(
+    htlc_amount: Amount,
+    dust_limit: Amount,
+    feerate_per_kw: u64,
+    offered: bool,
+    htlc_fee_mode: HtlcFeeMode,
+) -> bool {
+    let second_stage_fee = if offered {
+        htlc_fee_mode.htlc_timeout_fee(feerate_per_kw)
+    } else {
+        htlc_fee_mode.htlc_success_fee(feerate_per_kw)
+    };
+
+    htlc_amount < dust_limit + second_stage_fee
+}
+
+/// The highest `feerate_per_kw` at which the HTLC is still not trimmed, or
+/// `None` if it is below the dust limit and so trimmed at every feerate.
+pub fn max_feerate_for_htlc(htlc_amount: Amount, dust_limit: Amount, offered: bool, anchors: bool) -> Option<u64> {
+    let above_dust = htlc_amount.checked_sub(dust_limit)?;
+
+    // largest feerate with feerate * weight / 1000 <= htlc_amount - dust_limit
+    Some(((above_dust.to_sat() + 1) * 1000 - 1) / htlc_tx_weight(offered, anchors))
+}
+
+/// An HTLC on the channel, from the local node's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Htlc {
+    pub offered: bool,
+    pub amount_msat: MilliSat,
+    pub payment_hash: PaymentHash160,
+    pub cltv_expiry: u32,
+}
+
+/// An HTLC as it appears on a commitment transaction, with the index of its
+/// output once the commitment has been built (`None` if it was trimmed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtlcOutput {
+    pub htlc: Htlc,
+    pub output_index: Option<u32>,
+}
+
+impl HtlcOutput {
+    pub fn amount_sat(&self) -> Amount {
+        self.htlc.amount_msat.to_amount_floor()
+    }
+}
+
+/// The witness script of `htlc`'s commitment output. The anchor variants
+/// add a `1 OP_CSV` to every branch but the revocation one.
+pub fn htlc_witness_script(keys: &ChannelKeys, htlc: &Htlc, anchors: bool) -> ScriptBuf {
+    match (htlc.offered, anchors) {
+        (true, false) => build_htlc_offerer_witness_script(
+            &keys.revocation_pubkey,
+            &keys.remote_htlc_pubkey,
+            &keys.local_htlc_pubkey,
+            &htlc.payment_hash,
+        ),
+        (true, true) => build_anchor_htlc_offerer_witness_script(
+            &keys.revocation_pubkey,
+            &keys.remote_htlc_pubkey,
+            &keys.local_htlc_pubkey,
+            &htlc.payment_hash,
+        ),
+        (false, false) => build_htlc_receiver_witness_script(
+            &keys.revocation_pubkey,
+            &keys.remote_htlc_pubkey,
+            &keys.local_htlc_pubkey,
+            &htlc.payment_hash,
+            htlc.cltv_expiry,
+        ),
+        (false, true) => build_anchor_htlc_receiver_witness_script(
+            &keys.revocation_pubkey,
+            &keys.remote_htlc_pubkey,
+            &keys.local_htlc_pubkey,
+            &htlc.payment_hash,
+            htlc.cltv_expiry,
+        ),
+    }
+}
+
+/// Scan a witness stack for a 32-byte element hashing to `payment_hash`,
+/// as revealed when the counterparty claims an offered HTLC on-chain.
+pub fn extract_preimage_from_witness(witness: &Witness, payment_hash: &PaymentHash160) -> Option<PaymentPreimage> {
+    witness
+        .iter()
+        .filter_map(|element| PaymentPreimage::from_slice(element).ok())
+        .find(|candidate| candidate.hash160() == *payment_hash)
+}
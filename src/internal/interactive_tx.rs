@@ -0,0 +1,168 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! The BOLT 2 interactive transaction construction protocol, which lets
+//! both sides of a dual-funded channel add inputs and outputs to the
+//! funding transaction. The sides take turns sending one `tx_add_input`,
+//! `tx_add_output` or `tx_complete` each, until both send `tx_complete` in
+//! a row.
+use crate::exercises_advanced::exercises::{receive_tx_add_input, receive_tx_add_output};
+use crate::internal;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::script::ScriptBuf;
+use bitcoin::{Amount, OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+use internal::errors::InteractiveTxError;
+use internal::wire::{Message, TxAddInput, TxAddOutput, TxComplete};
+use std::collections::{BTreeMap, VecDeque};
+
+/// The most `tx_add_input`, and separately `tx_add_output`, we accept from
+/// the peer.
+pub const MAX_RECEIVED_TX_ADDITIONS: usize = 4096;
+
+/// The initiator's serial ids are even, and the other side's odd, so the
+/// two can never pick the same one.
+pub fn is_initiator_serial_id(serial_id: u64) -> bool {
+    serial_id.is_multiple_of(2)
+}
+
+/// An input of the transaction being built, and the output it spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiatedInput {
+    pub txin: TxIn,
+    pub prevout: TxOut,
+}
+
+/// One side's view of a funding transaction being built with its peer.
+#[derive(Debug, Clone)]
+pub struct InteractiveTxConstructor {
+    pub channel_id: [u8; 32],
+    pub is_initiator: bool,
+    /// The 2-of-2 output both sides agreed on. The initiator adds it.
+    pub funding_script_pubkey: ScriptBuf,
+    pub dust_limit: Amount,
+    pub locktime: LockTime,
+    /// Both sides' inputs and outputs, by serial id: the order they take in
+    /// the transaction.
+    pub inputs: BTreeMap<u64, NegotiatedInput>,
+    pub outputs: BTreeMap<u64, TxOut>,
+    /// How many `tx_add_input` and `tx_add_output` the peer sent.
+    pub received_inputs: usize,
+    pub received_outputs: usize,
+    to_send: VecDeque<Message>,
+    next_serial_id: u64,
+    sent_complete: bool,
+    received_complete: bool,
+}
+
+impl InteractiveTxConstructor {
+    pub fn new(
+        channel_id: [u8; 32],
+        is_initiator: bool,
+        funding_script_pubkey: ScriptBuf,
+        dust_limit: Amount,
+        locktime: LockTime,
+    ) -> InteractiveTxConstructor {
+        InteractiveTxConstructor {
+            channel_id,
+            is_initiator,
+            funding_script_pubkey,
+            dust_limit,
+            locktime,
+            inputs: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+            received_inputs: 0,
+            received_outputs: 0,
+            to_send: VecDeque::new(),
+            next_serial_id: if is_initiator { 0 } else { 1 },
+            sent_complete: false,
+            received_complete: false,
+        }
+    }
+
+    fn take_serial_id(&mut self) -> u64 {
+        let serial_id = self.next_serial_id;
+        self.next_serial_id += 2;
+        serial_id
+    }
+
+    /// Queue an input spending output `prevtx_out` of `prevtx`.
+    pub fn contribute_input(&mut self, prevtx: Transaction, prevtx_out: u32, sequence: Sequence) {
+        let serial_id = self.take_serial_id();
+        let txin = TxIn {
+            previous_output: OutPoint::new(prevtx.compute_txid(), prevtx_out),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        };
+        let prevout = prevtx.output[prevtx_out as usize].clone();
+        self.inputs.insert(serial_id, NegotiatedInput { txin, prevout });
+        self.to_send.push_back(Message::TxAddInput(TxAddInput {
+            channel_id: self.channel_id,
+            serial_id,
+            prevtx,
+            prevtx_out,
+            sequence: sequence.to_consensus_u32(),
+        }));
+    }
+
+    pub fn contribute_output(&mut self, output: TxOut) {
+        let serial_id = self.take_serial_id();
+        self.outputs.insert(serial_id, output.clone());
+        self.to_send.push_back(Message::TxAddOutput(TxAddOutput {
+            channel_id: self.channel_id,
+            serial_id,
+            sats: output.value,
+            script: output.script_pubkey,
+        }));
+    }
+
+    /// Our message for this turn: the next of our contributions, or
+    /// `tx_complete` once they have all been sent. `None` once the
+    /// negotiation is complete.
+    pub fn next_message(&mut self) -> Option<Message> {
+        if self.is_complete() {
+            return None;
+        }
+        match self.to_send.pop_front() {
+            Some(message) => {
+                self.sent_complete = false;
+                Some(message)
+            }
+            None => {
+                self.sent_complete = true;
+                Some(Message::TxComplete(TxComplete { channel_id: self.channel_id }))
+            }
+        }
+    }
+
+    pub fn handle_message(&mut self, message: &Message) -> Result<(), InteractiveTxError> {
+        let channel_id = match message {
+            Message::TxAddInput(add) => add.channel_id,
+            Message::TxAddOutput(add) => add.channel_id,
+            Message::TxComplete(complete) => complete.channel_id,
+            other => return Err(InteractiveTxError::UnexpectedMessage(other.msg_type())),
+        };
+        if channel_id != self.channel_id {
+            return Err(InteractiveTxError::UnknownChannelId(channel_id));
+        }
+
+        match message {
+            Message::TxAddInput(add) => {
+                self.received_complete = false;
+                receive_tx_add_input(self, add)
+            }
+            Message::TxAddOutput(add) => {
+                self.received_complete = false;
+                receive_tx_add_output(self, add)
+            }
+            _ => {
+                self.received_complete = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Both sides sent `tx_complete` in a row, so neither can add anything
+    /// more.
+    pub fn is_complete(&self) -> bool {
+        self.sent_complete && self.received_complete
+    }
+}
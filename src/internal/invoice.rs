@@ -0,0 +1,368 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::primitives::iter::Fe32IterExt;
+use bech32::{Bech32, Checksum, Fe32, Hrp};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, PublicKey as secp256k1PublicKey, Secp256k1};
+use bitcoin::Network;
+use internal::errors::InvoiceError;
+use internal::payments::{PaymentHash, PaymentSecret};
+use internal::units::MilliSat;
+use internal::wire::{encode_features, ShortChannelId};
+
+/// The bech32 checksum with the longer code length BOLT 11 needs: a few route
+/// hints push an invoice well past the 1023 characters of a bech32 address.
+pub enum Bolt11Checksum {}
+
+impl Checksum for Bolt11Checksum {
+    type MidstateRepr = u32;
+    const CODE_LENGTH: usize = 7089;
+    const CHECKSUM_LENGTH: usize = 6;
+    const GENERATOR_SH: [u32; 5] = Bech32::GENERATOR_SH;
+    const TARGET_RESIDUE: u32 = 1;
+}
+
+/// Tagged field types, as the 5-bit value of the field's bech32 character.
+pub const PAYMENT_HASH_TAG: u8 = 1; // p
+pub const ROUTE_HINT_TAG: u8 = 3; // r
+pub const FEATURES_TAG: u8 = 5; // 9
+pub const EXPIRY_TAG: u8 = 6; // x
+pub const FALLBACK_ADDRESS_TAG: u8 = 9; // f
+pub const DESCRIPTION_TAG: u8 = 13; // d
+pub const PAYMENT_SECRET_TAG: u8 = 16; // s
+pub const PAYEE_PUBKEY_TAG: u8 = 19; // n
+pub const DESCRIPTION_HASH_TAG: u8 = 23; // h
+pub const MIN_FINAL_CLTV_EXPIRY_DELTA_TAG: u8 = 24; // c
+
+/// What a missing `x` and `c` field mean.
+pub const DEFAULT_EXPIRY: u64 = 3600;
+pub const DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA: u64 = 18;
+
+/// The data part starts with a 35-bit timestamp and ends with a 65-byte
+/// recoverable signature, both counted in 5-bit words.
+pub const TIMESTAMP_WORDS: usize = 7;
+pub const SIGNATURE_WORDS: usize = 104;
+
+/// Public key, short channel id, fee base, fee rate and CLTV delta.
+const ROUTE_HINT_HOP_LEN: usize = 33 + 8 + 4 + 4 + 2;
+
+/// One hop of a private route the payee suggests to reach it: the channel
+/// from `node_id` towards the payee, and what `node_id` charges to use it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHintHop {
+    pub node_id: secp256k1PublicKey,
+    pub short_channel_id: ShortChannelId,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+}
+
+/// A decoded BOLT 11 invoice whose signature has been checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invoice {
+    pub network: Network,
+    /// `None` if the payer gets to choose how much to pay.
+    pub amount: Option<MilliSat>,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The hash of the preimage the payee reveals once it is paid.
+    pub payment_hash: PaymentHash,
+    pub payment_secret: Option<PaymentSecret>,
+    pub description: Option<String>,
+    pub description_hash: Option<[u8; 32]>,
+    pub payee_pubkey: secp256k1PublicKey,
+    /// Seconds after `timestamp` the invoice stops being payable.
+    pub expiry: u64,
+    pub min_final_cltv_expiry_delta: u64,
+    pub route_hints: Vec<Vec<RouteHintHop>>,
+    /// Feature bits laid out as in `init`, so `has_feature` reads them.
+    pub features: Vec<u8>,
+}
+
+/// Packs 5-bit words into bytes. The bits left over at the end are dropped,
+/// or with `pad` filled out to one more byte with zero bits.
+pub fn words_to_bytes(words: &[u8], pad: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(words.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &word in words {
+        buffer = (buffer << 5) | word as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if pad && bits > 0 {
+        bytes.push((buffer << (8 - bits)) as u8);
+    }
+    bytes
+}
+
+/// Splits bytes into 5-bit words, padding the last word with zero bits.
+pub fn bytes_to_words(bytes: &[u8]) -> Vec<u8> {
+    let mut words = Vec::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            words.push((buffer >> bits) as u8 & 31);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        words.push((buffer << (5 - bits)) as u8);
+    }
+    words
+}
+
+/// `value` in as few big-endian 5-bit words as it takes.
+pub fn u64_to_words(value: u64) -> Vec<u8> {
+    let len = (64 - value.leading_zeros() as usize).div_ceil(5);
+    (0..len).rev().map(|i| (value >> (5 * i)) as u8 & 31).collect()
+}
+
+/// The timestamp always takes its full 7 words, leading zeros and all.
+pub fn timestamp_to_words(timestamp: u64) -> Vec<u8> {
+    (0..TIMESTAMP_WORDS).rev().map(|i| (timestamp >> (5 * i)) as u8 & 31).collect()
+}
+
+/// Feature bits laid out for the `9` field, numbered from the lowest bit of
+/// the last word.
+pub fn features_to_words(bits: &[usize]) -> Vec<u8> {
+    let Some(&highest) = bits.iter().max() else { return Vec::new() };
+    let mut words = vec![0u8; highest / 5 + 1];
+    let len = words.len();
+    for bit in bits {
+        words[len - 1 - bit / 5] |= 1 << (bit % 5);
+    }
+    words
+}
+
+/// Appends a tagged field: its type, its 10-bit length in words, then the
+/// value.
+pub fn write_tagged_field(data: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<(), InvoiceError> {
+    if value.len() >= 1 << 10 {
+        return Err(InvoiceError::FieldTooLong { tag, words: value.len() });
+    }
+    data.extend_from_slice(&[tag, (value.len() >> 5) as u8, value.len() as u8 & 31]);
+    data.extend_from_slice(value);
+    Ok(())
+}
+
+/// The human-readable part for an invoice on `network`, with the amount in
+/// its shortest form.
+pub fn invoice_hrp(network: Network, amount: Option<MilliSat>) -> String {
+    let currency = match network {
+        Network::Bitcoin => "bc",
+        Network::Testnet => "tb",
+        Network::Signet => "tbs",
+        _ => "bcrt",
+    };
+    let amount = match amount {
+        None => String::new(),
+        Some(MilliSat(msat)) if msat.is_multiple_of(100_000_000_000) => format!("{}", msat / 100_000_000_000),
+        Some(MilliSat(msat)) if msat.is_multiple_of(100_000_000) => format!("{}m", msat / 100_000_000),
+        Some(MilliSat(msat)) if msat.is_multiple_of(100_000) => format!("{}u", msat / 100_000),
+        Some(MilliSat(msat)) if msat.is_multiple_of(100) => format!("{}n", msat / 100),
+        Some(MilliSat(msat)) => format!("{}p", msat * 10),
+    };
+    format!("ln{}{}", currency, amount)
+}
+
+/// The bech32 string for `hrp` and the 5-bit `words` of the data part,
+/// checksum included.
+pub fn encode_bech32(hrp: &str, words: &[u8]) -> String {
+    let hrp = Hrp::parse(hrp).expect("invoice HRPs are valid bech32");
+    words.iter().map(|&word| Fe32::try_from(word).unwrap()).with_checksum::<Bolt11Checksum>(&hrp).chars().collect()
+}
+
+/// A big-endian number spread over `words`, or `None` if it doesn't fit in a
+/// `u64`.
+fn words_to_u64(words: &[u8]) -> Option<u64> {
+    if words.len() > 12 {
+        return None;
+    }
+    Some(words.iter().fold(0, |value, &word| (value << 5) | word as u64))
+}
+
+/// The hash the payee signs: the HRP's ASCII bytes followed by the data part
+/// up to the signature, padded with zero bits to a whole number of bytes.
+pub fn signing_hash(hrp: &str, data: &[u8]) -> [u8; 32] {
+    let mut message = hrp.as_bytes().to_vec();
+    message.extend_from_slice(&words_to_bytes(data, true));
+    Sha256::hash(&message).to_byte_array()
+}
+
+/// `amount` is the part of the HRP after the currency prefix: digits followed
+/// by an optional multiplier of the bitcoin amount.
+fn parse_amount(amount: &str) -> Result<Option<MilliSat>, InvoiceError> {
+    if amount.is_empty() {
+        return Ok(None);
+    }
+    let (digits, multiplier) = match amount.as_bytes()[amount.len() - 1] {
+        b'm' | b'u' | b'n' | b'p' => amount.split_at(amount.len() - 1),
+        _ => (amount, ""),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(InvoiceError::InvalidAmount);
+    }
+    let value: u64 = digits.parse().map_err(|_| InvoiceError::InvalidAmount)?;
+
+    let msat = match multiplier {
+        "" => value.checked_mul(100_000_000_000),
+        "m" => value.checked_mul(100_000_000),
+        "u" => value.checked_mul(100_000),
+        "n" => value.checked_mul(100),
+        // A pico-bitcoin is a tenth of a millisatoshi.
+        _ if value.is_multiple_of(10) => Some(value / 10),
+        _ => None,
+    };
+    msat.map(|msat| Some(MilliSat(msat))).ok_or(InvoiceError::InvalidAmount)
+}
+
+fn parse_hrp(hrp: &str) -> Result<(Network, Option<MilliSat>), InvoiceError> {
+    let currency = hrp.strip_prefix("ln").ok_or(InvoiceError::UnknownCurrency)?;
+    // Longest prefixes first, so `bcrt` isn't read as `bc` and an amount.
+    let currencies =
+        [("bcrt", Network::Regtest), ("bc", Network::Bitcoin), ("tbs", Network::Signet), ("tb", Network::Testnet)];
+    let (prefix, network) =
+        currencies.into_iter().find(|(prefix, _)| currency.starts_with(prefix)).ok_or(InvoiceError::UnknownCurrency)?;
+    Ok((network, parse_amount(&currency[prefix.len()..])?))
+}
+
+fn parse_route_hint(bytes: &[u8]) -> Result<Vec<RouteHintHop>, InvoiceError> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(ROUTE_HINT_HOP_LEN) {
+        return Err(InvoiceError::InvalidField("route hint"));
+    }
+    bytes
+        .chunks(ROUTE_HINT_HOP_LEN)
+        .map(|hop| {
+            Ok(RouteHintHop {
+                node_id: secp256k1PublicKey::from_slice(&hop[..33])
+                    .map_err(|_| InvoiceError::InvalidField("route hint"))?,
+                short_channel_id: ShortChannelId(u64::from_be_bytes(hop[33..41].try_into().unwrap())),
+                fee_base_msat: u32::from_be_bytes(hop[41..45].try_into().unwrap()),
+                fee_proportional_millionths: u32::from_be_bytes(hop[45..49].try_into().unwrap()),
+                cltv_expiry_delta: u16::from_be_bytes(hop[49..51].try_into().unwrap()),
+            })
+        })
+        .collect()
+}
+
+/// Feature bits are numbered from the lowest bit of the last word.
+fn parse_features(words: &[u8]) -> Vec<u8> {
+    let bits: Vec<usize> =
+        (0..words.len() * 5).filter(|bit| words[words.len() - 1 - bit / 5] >> (bit % 5) & 1 == 1).collect();
+    encode_features(&bits)
+}
+
+/// Decodes a BOLT 11 invoice and checks that the payee signed it, recovering
+/// the payee's key from the signature if the invoice has no `n` field.
+///
+/// Fields of the wrong length and field types we don't know are skipped, so
+/// an invoice from a newer version of the spec still decodes.
+pub fn decode_invoice(invoice: &str) -> Result<Invoice, InvoiceError> {
+    let checked = CheckedHrpstring::new::<Bolt11Checksum>(invoice)?;
+    let hrp = checked.hrp().to_lowercase();
+    let (network, amount) = parse_hrp(&hrp)?;
+
+    let words: Vec<u8> =
+        checked.data_part_ascii_no_checksum().iter().map(|&c| Fe32::from_char_unchecked(c).to_u8()).collect();
+    if words.len() < TIMESTAMP_WORDS + SIGNATURE_WORDS {
+        return Err(InvoiceError::TooShort);
+    }
+    let (data, signature) = words.split_at(words.len() - SIGNATURE_WORDS);
+    let timestamp = words_to_u64(&data[..TIMESTAMP_WORDS]).unwrap();
+
+    let mut payment_hash = None;
+    let mut payment_secret = None;
+    let mut description = None;
+    let mut description_hash = None;
+    let mut payee_pubkey = None;
+    let mut expiry = DEFAULT_EXPIRY;
+    let mut min_final_cltv_expiry_delta = DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA;
+    let mut route_hints = Vec::new();
+    let mut features = Vec::new();
+
+    // Each field is a 5-bit type, a 10-bit length in words, then the value.
+    let mut fields = &data[TIMESTAMP_WORDS..];
+    while !fields.is_empty() {
+        if fields.len() < 3 {
+            return Err(InvoiceError::InvalidField("tagged field"));
+        }
+        let length = ((fields[1] as usize) << 5) | fields[2] as usize;
+        let value = fields.get(3..3 + length).ok_or(InvoiceError::InvalidField("tagged field"))?;
+        let hash_field = || -> [u8; 32] { words_to_bytes(value, false)[..32].try_into().unwrap() };
+
+        match (fields[0], length) {
+            (PAYMENT_HASH_TAG, 52) => payment_hash = Some(PaymentHash(hash_field())),
+            (PAYMENT_SECRET_TAG, 52) => payment_secret = Some(PaymentSecret(hash_field())),
+            (DESCRIPTION_HASH_TAG, 52) => description_hash = Some(hash_field()),
+            (PAYEE_PUBKEY_TAG, 53) => {
+                let pubkey = secp256k1PublicKey::from_slice(&words_to_bytes(value, false)[..33])
+                    .map_err(|_| InvoiceError::InvalidField("payee pubkey"))?;
+                payee_pubkey = Some(pubkey);
+            }
+            (DESCRIPTION_TAG, _) => {
+                let text = String::from_utf8(words_to_bytes(value, false))
+                    .map_err(|_| InvoiceError::InvalidField("description"))?;
+                description = Some(text);
+            }
+            (EXPIRY_TAG, _) => expiry = words_to_u64(value).ok_or(InvoiceError::InvalidField("expiry"))?,
+            (MIN_FINAL_CLTV_EXPIRY_DELTA_TAG, _) => {
+                min_final_cltv_expiry_delta =
+                    words_to_u64(value).ok_or(InvoiceError::InvalidField("min_final_cltv_expiry_delta"))?;
+            }
+            (ROUTE_HINT_TAG, _) => route_hints.push(parse_route_hint(&words_to_bytes(value, false))?),
+            (FEATURES_TAG, _) => features = parse_features(value),
+            _ => {}
+        }
+        fields = &fields[3 + length..];
+    }
+
+    let payment_hash = payment_hash.ok_or(InvoiceError::MissingPaymentHash)?;
+    if description.is_none() && description_hash.is_none() {
+        return Err(InvoiceError::MissingDescription);
+    }
+
+    let signature_bytes = words_to_bytes(signature, false);
+    let recovery_id = RecoveryId::from_i32(signature_bytes[64] as i32).map_err(|_| InvoiceError::InvalidSignature)?;
+    let signature = RecoverableSignature::from_compact(&signature_bytes[..64], recovery_id)
+        .map_err(|_| InvoiceError::InvalidSignature)?;
+    let message = Message::from_digest(signing_hash(&hrp, data));
+    let secp = Secp256k1::verification_only();
+
+    let payee_pubkey = match payee_pubkey {
+        Some(pubkey) => {
+            // Recovery doesn't care whether S is low, so neither do we.
+            let mut standard = signature.to_standard();
+            standard.normalize_s();
+            secp.verify_ecdsa(&message, &standard, &pubkey).map_err(|_| InvoiceError::InvalidSignature)?;
+            pubkey
+        }
+        None => secp.recover_ecdsa(&message, &signature).map_err(|_| InvoiceError::InvalidSignature)?,
+    };
+
+    Ok(Invoice {
+        network,
+        amount,
+        timestamp,
+        payment_hash,
+        payment_secret,
+        description,
+        description_hash,
+        payee_pubkey,
+        expiry,
+        min_final_cltv_expiry_delta,
+        route_hints,
+        features,
+    })
+}
@@ -1,7 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
-use internal::bitcoind_client::BitcoindClient;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::Hash;
 use bitcoin::hashes::HashEngine;
@@ -27,6 +26,22 @@ use bitcoin::secp256k1::Message;
 use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::sighash::SighashCache;
 use exercises::exercises::{ two_of_two_multisig_witness_script};
+use internal::errors::{ChannelError, ExerciseError};
+use internal::sighash_utils::compute_p2wsh_sighash;
+use internal::musig_utils::tagged_hash;
+use bitcoin::key::{Parity, XOnlyPublicKey};
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+
+/// The two funding keys in BOLT 3 order: lexicographically by their
+/// compressed serialization. Everything that depends on the order of the
+/// funding keys goes through this.
+pub fn sort_funding_keys(key1: PublicKey, key2: PublicKey) -> (PublicKey, PublicKey) {
+    if key1.inner.serialize() <= key2.inner.serialize() {
+        (key1, key2)
+    } else {
+        (key2, key1)
+    }
+}
 
 pub fn secp256k1_private_key(private_key_bytes: &[u8; 32]) -> SecretKey {
     let secp = Secp256k1::new();
@@ -60,6 +75,10 @@ pub fn privkey_multipication_tweak(secret: SecretKey, sha_bytes: [u8; 32]) -> Se
     secret.mul_tweak(&Scalar::from_be_bytes(sha_bytes).unwrap()).unwrap()
 }
 
+/// SHA256(serialize(key1) || serialize(key2)) over the compressed keys.
+///
+/// The order matters: `hash_pubkeys(a, b) != hash_pubkeys(b, a)`, and the
+/// revocation key derivation depends on it by hashing both ways round.
 pub fn hash_pubkeys(key1: secp256k1PublicKey, key2: secp256k1PublicKey) -> [u8; 32] {
     let mut sha = Sha256::engine();
 
@@ -70,12 +89,169 @@ pub fn hash_pubkeys(key1: secp256k1PublicKey, key2: secp256k1PublicKey) -> [u8;
 }
 
 pub fn add_pubkeys(key1: secp256k1PublicKey, key2: secp256k1PublicKey) -> secp256k1PublicKey {
-    let pk = key1.combine(&key2).unwrap();
 
-    pk
+    key1.combine(&key2).unwrap()
 }
 
 pub fn add_privkeys(key1: SecretKey, key2: SecretKey) -> SecretKey {
     let tweak = Scalar::from_be_bytes(key2.secret_bytes()).unwrap();
     key1.add_tweak(&tweak).unwrap()
-}
\ No newline at end of file
+}
+/// BOLT 3 key derivation: `basepoint + SHA256(per_commitment_point || basepoint) * G`.
+/// Used for localpubkey, remotepubkey, local_delayedpubkey and the HTLC keys.
+pub fn derive_public_key(basepoint: secp256k1PublicKey, per_commitment_point: secp256k1PublicKey) -> secp256k1PublicKey {
+    let tweak = hash_pubkeys(per_commitment_point, basepoint);
+    let tweak_point = pubkey_from_secret(SecretKey::from_slice(&tweak).unwrap());
+
+    basepoint.combine(&tweak_point).unwrap()
+}
+
+/// The private key of `derive_public_key`: `base_secret + SHA256(per_commitment_point || basepoint)`.
+pub fn derive_private_key(base_secret: SecretKey, per_commitment_point: secp256k1PublicKey) -> SecretKey {
+    let basepoint = pubkey_from_secret(base_secret);
+    let tweak = hash_pubkeys(per_commitment_point, basepoint);
+
+    base_secret.add_tweak(&Scalar::from_be_bytes(tweak).unwrap()).unwrap()
+}
+
+pub fn verify_commitment_signature(sig: &Signature, pubkey: &PublicKey, sighash: &[u8; 32]) -> bool {
+    let secp = Secp256k1::verification_only();
+    let message = Message::from_digest(*sighash);
+
+    secp.verify_ecdsa(&message, sig, &pubkey.inner).is_ok()
+}
+
+/// Check the signature the counterparty sent for our commitment transaction
+/// before we rely on being able to broadcast it.
+pub fn verify_counterparty_commitment(
+    tx: &Transaction,
+    sig: &Signature,
+    remote_funding_pubkey: &PublicKey,
+    witness_script: &ScriptBuf,
+    amount: u64,
+) -> Result<(), ChannelError> {
+    let sighash = compute_p2wsh_sighash(tx, 0, witness_script, amount);
+
+    if verify_commitment_signature(sig, remote_funding_pubkey, &sighash) {
+        Ok(())
+    } else {
+        Err(ChannelError::InvalidCommitmentSignature)
+    }
+}
+
+/// Lightning only uses compressed keys, and P2WPKH can't commit to any other.
+pub fn check_compressed(name: &'static str, key: &PublicKey) -> Result<(), ExerciseError> {
+    if key.compressed {
+        Ok(())
+    } else {
+        Err(ExerciseError::UncompressedKey(name))
+    }
+}
+
+/// A Schnorr signature that only becomes valid once the secret of
+/// `adaptor_point` is added to it, and that reveals the secret when it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptorSignature {
+    /// `R + T`, the nonce the completed signature will carry.
+    pub nonce: secp256k1PublicKey,
+    pub s: SecretKey,
+}
+
+impl AdaptorSignature {
+    /// BIP 340 needs the final nonce to have an even y. When `R + T` doesn't,
+    /// the signer negates its nonce and the adaptor secret is subtracted
+    /// instead of added.
+    fn nonce_negated(&self) -> bool {
+        self.nonce.x_only_public_key().1 == Parity::Odd
+    }
+}
+
+fn bip340_challenge(nonce: &secp256k1PublicKey, pubkey: &XOnlyPublicKey, msg: &[u8; 32]) -> Scalar {
+    let hash = tagged_hash(
+        "BIP0340/challenge",
+        &[&nonce.x_only_public_key().0.serialize(), &pubkey.serialize(), msg],
+    );
+    Scalar::from_be_bytes(hash).unwrap()
+}
+
+/// Sign `msg` so the signature only completes with the discrete log of
+/// `adaptor_point`. `aux_rand` should be fresh randomness.
+pub fn create_adaptor_signature(
+    secret_key: &SecretKey,
+    msg: &[u8; 32],
+    adaptor_point: &secp256k1PublicKey,
+    aux_rand: [u8; 32],
+) -> AdaptorSignature {
+    let secp = Secp256k1::new();
+    let (pubkey, parity) = secret_key.public_key(&secp).x_only_public_key();
+    let x = if parity == Parity::Odd { secret_key.negate() } else { *secret_key };
+
+    let k = tagged_hash(
+        "PTLC/adaptor_nonce",
+        &[&aux_rand, &x.secret_bytes(), &adaptor_point.serialize(), msg],
+    );
+    let k = SecretKey::from_slice(&k).unwrap();
+    let nonce = pubkey_from_secret(k).combine(adaptor_point).unwrap();
+    let k = if nonce.x_only_public_key().1 == Parity::Odd { k.negate() } else { k };
+
+    // s' = k + e * x
+    let e = bip340_challenge(&nonce, &pubkey, msg);
+    let s = x.mul_tweak(&e).unwrap().add_tweak(&Scalar::from(k)).unwrap();
+
+    AdaptorSignature { nonce, s }
+}
+
+/// Check that completing `adaptor_signature` with the secret of
+/// `adaptor_point` gives a valid signature of `msg` by `pubkey`.
+pub fn verify_adaptor_signature(
+    adaptor_signature: &AdaptorSignature,
+    pubkey: &XOnlyPublicKey,
+    msg: &[u8; 32],
+    adaptor_point: &secp256k1PublicKey,
+) -> bool {
+    let secp = Secp256k1::verification_only();
+
+    // take the signer's own nonce R back out of R + T, with the parity the signer used
+    let (nonce, adaptor_point) = if adaptor_signature.nonce_negated() {
+        (adaptor_signature.nonce.negate(&secp), adaptor_point.negate(&secp))
+    } else {
+        (adaptor_signature.nonce, *adaptor_point)
+    };
+    let Ok(r) = nonce.combine(&adaptor_point.negate(&secp)) else {
+        return false;
+    };
+
+    // s' * G == R + e * P
+    let e = bip340_challenge(&adaptor_signature.nonce, pubkey, msg);
+    let p = pubkey.public_key(Parity::Even);
+    let expected = r.combine(&p.mul_tweak(&secp, &e).unwrap());
+
+    expected == Ok(pubkey_from_secret(adaptor_signature.s))
+}
+
+/// Add the adaptor secret to get the final BIP 340 signature.
+pub fn complete_adaptor_signature(adaptor_signature: &AdaptorSignature, adaptor_secret: &SecretKey) -> SchnorrSignature {
+    let t = if adaptor_signature.nonce_negated() { adaptor_secret.negate() } else { *adaptor_secret };
+    let s = adaptor_signature.s.add_tweak(&Scalar::from(t)).unwrap();
+
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&adaptor_signature.nonce.x_only_public_key().0.serialize());
+    bytes[32..].copy_from_slice(&s.secret_bytes());
+    SchnorrSignature::from_slice(&bytes).unwrap()
+}
+
+/// Recover the adaptor secret from the completed `signature`, or `None` if it
+/// wasn't completed from `adaptor_signature`.
+pub fn extract_adaptor_secret(
+    adaptor_signature: &AdaptorSignature,
+    signature: &SchnorrSignature,
+    adaptor_point: &secp256k1PublicKey,
+) -> Option<SecretKey> {
+    let s = SecretKey::from_slice(&signature.as_ref()[32..]).ok()?;
+
+    // t = s - s', negated back if the signer negated its nonce
+    let t = s.add_tweak(&Scalar::from(adaptor_signature.s.negate())).ok()?;
+    let t = if adaptor_signature.nonce_negated() { t.negate() } else { t };
+
+    (pubkey_from_secret(t) == *adaptor_point).then_some(t)
+}
@@ -0,0 +1,153 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::generate_revocation_pubkey;
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use bitcoin::PublicKey;
+use internal::key_utils::{derive_public_key, pubkey_from_secret};
+use internal::shachain::{generate_from_seed, MAX_SHACHAIN_INDEX};
+
+/// The key the counterparty can sweep a revoked commitment's outputs with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RevocationPubkey(pub PublicKey);
+
+/// The key that claims `to_local` once `to_self_delay` has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DelayedPaymentPubkey(pub PublicKey);
+
+/// The per-commitment key one side signs its HTLC spends with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HtlcPubkey(pub PublicKey);
+
+/// One side's key in the 2-of-2 funding output, which also owns its anchor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FundingPubkey(pub PublicKey);
+
+/// The keys used to build one side's commitment transaction, from the point
+/// of view of the commitment's broadcaster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelKeys {
+    pub revocation_pubkey: RevocationPubkey,
+    pub local_htlc_pubkey: HtlcPubkey,
+    pub remote_htlc_pubkey: HtlcPubkey,
+    pub to_local_delayed_pubkey: DelayedPaymentPubkey,
+    pub remote_pubkey: PublicKey,
+}
+
+/// The basepoints a node sends its counterparty in `open_channel` or
+/// `accept_channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPublicKeys {
+    pub funding_pubkey: secp256k1PublicKey,
+    pub payment_basepoint: secp256k1PublicKey,
+    pub delayed_payment_basepoint: secp256k1PublicKey,
+    pub htlc_basepoint: secp256k1PublicKey,
+    pub revocation_basepoint: secp256k1PublicKey,
+}
+
+/// The keys of one commitment transaction, derived from both sides'
+/// basepoints and the broadcaster's per-commitment point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxCreationKeys {
+    pub per_commitment_point: secp256k1PublicKey,
+    pub revocation_key: secp256k1PublicKey,
+    pub broadcaster_htlc_key: secp256k1PublicKey,
+    pub countersignatory_htlc_key: secp256k1PublicKey,
+    pub broadcaster_delayed_payment_key: secp256k1PublicKey,
+    /// With `option_static_remotekey` this is just the counterparty's payment basepoint.
+    pub countersignatory_payment_key: secp256k1PublicKey,
+}
+
+impl TxCreationKeys {
+    /// Derive the keys of `broadcaster`'s commitment for one per-commitment point.
+    pub fn derive(
+        per_commitment_point: secp256k1PublicKey,
+        broadcaster: &ChannelPublicKeys,
+        countersignatory: &ChannelPublicKeys,
+    ) -> TxCreationKeys {
+        TxCreationKeys {
+            per_commitment_point,
+            revocation_key: generate_revocation_pubkey(countersignatory.revocation_basepoint, per_commitment_point),
+            broadcaster_htlc_key: derive_public_key(broadcaster.htlc_basepoint, per_commitment_point),
+            countersignatory_htlc_key: derive_public_key(countersignatory.htlc_basepoint, per_commitment_point),
+            broadcaster_delayed_payment_key: derive_public_key(
+                broadcaster.delayed_payment_basepoint,
+                per_commitment_point,
+            ),
+            countersignatory_payment_key: countersignatory.payment_basepoint,
+        }
+    }
+
+    /// The same keys in the shape the commitment builders take.
+    pub fn to_channel_keys(&self) -> ChannelKeys {
+        ChannelKeys {
+            revocation_pubkey: RevocationPubkey(PublicKey::new(self.revocation_key)),
+            local_htlc_pubkey: HtlcPubkey(PublicKey::new(self.broadcaster_htlc_key)),
+            remote_htlc_pubkey: HtlcPubkey(PublicKey::new(self.countersignatory_htlc_key)),
+            to_local_delayed_pubkey: DelayedPaymentPubkey(PublicKey::new(self.broadcaster_delayed_payment_key)),
+            remote_pubkey: PublicKey::new(self.countersignatory_payment_key),
+        }
+    }
+}
+
+/// All of a node's secret key material for one channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelKeyManager {
+    pub funding_key: SecretKey,
+    pub payment_base_key: SecretKey,
+    pub delayed_payment_base_key: SecretKey,
+    pub htlc_base_key: SecretKey,
+    pub revocation_base_key: SecretKey,
+    pub commitment_seed: [u8; 32],
+}
+
+impl ChannelKeyManager {
+    /// The keys of the node's `channel_index`th channel, each derived from
+    /// `node_seed` as SHA256(seed || channel_index || key number). A node
+    /// that only kept its seed can derive them all again.
+    pub fn from_seed(node_seed: &[u8; 32], channel_index: u64) -> ChannelKeyManager {
+        let derive = |key_number: u8| {
+            let mut engine = Sha256::engine();
+            engine.input(node_seed);
+            engine.input(&channel_index.to_be_bytes());
+            engine.input(&[key_number]);
+            Sha256::from_engine(engine).to_byte_array()
+        };
+        let secret_key = |key_number: u8| SecretKey::from_slice(&derive(key_number)).unwrap();
+        ChannelKeyManager {
+            funding_key: secret_key(0),
+            payment_base_key: secret_key(1),
+            delayed_payment_base_key: secret_key(2),
+            htlc_base_key: secret_key(3),
+            revocation_base_key: secret_key(4),
+            commitment_seed: derive(5),
+        }
+    }
+
+    pub fn pubkeys(&self) -> ChannelPublicKeys {
+        ChannelPublicKeys {
+            funding_pubkey: pubkey_from_secret(self.funding_key),
+            payment_basepoint: pubkey_from_secret(self.payment_base_key),
+            delayed_payment_basepoint: pubkey_from_secret(self.delayed_payment_base_key),
+            htlc_basepoint: pubkey_from_secret(self.htlc_base_key),
+            revocation_basepoint: pubkey_from_secret(self.revocation_base_key),
+        }
+    }
+
+    /// The per-commitment secret of commitment `commitment_number`. The first
+    /// commitment takes the highest shachain index so later ones can be
+    /// stored compactly by the counterparty.
+    pub fn per_commitment_secret(&self, commitment_number: u64) -> [u8; 32] {
+        generate_from_seed(&self.commitment_seed, MAX_SHACHAIN_INDEX - commitment_number)
+    }
+
+    pub fn per_commitment_point(&self, commitment_number: u64) -> secp256k1PublicKey {
+        pubkey_from_secret(SecretKey::from_slice(&self.per_commitment_secret(commitment_number)).unwrap())
+    }
+
+    /// The keys of our own commitment `commitment_number`.
+    pub fn keys_for_commitment(&self, commitment_number: u64, counterparty: &ChannelPublicKeys) -> TxCreationKeys {
+        TxCreationKeys::derive(self.per_commitment_point(commitment_number), &self.pubkeys(), counterparty)
+    }
+}
@@ -1,8 +1,48 @@
-
+pub mod backup;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod bitcoind_client;
+pub mod blinded_path;
+pub mod chain_source;
+pub mod chain_utils;
+pub mod channel;
+pub mod channel_utils;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod convert;
+pub mod debug_utils;
+pub mod errors;
+pub mod fee_utils;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod gossip_utils;
+pub mod grading;
+pub mod graph;
+#[cfg(feature = "regtest")]
+pub mod harness;
 pub mod hex_utils;
+pub mod hints;
+pub mod htlc_utils;
+pub mod interactive_tx;
+pub mod invoice;
 pub mod key_utils;
+pub mod keys;
+pub mod musig_utils;
+pub mod noise_utils;
+pub mod offers;
+pub mod onion_utils;
+pub mod payments;
 pub mod tx_utils;
 pub mod script_utils;
-pub mod sign_utils;
\ No newline at end of file
+pub mod psbt_utils;
+pub mod routing;
+pub mod shachain;
+pub mod sighash_utils;
+pub mod sign_utils;
+pub mod standardness;
+pub mod test;
+pub mod tlv;
+pub mod units;
+pub mod watchtower;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+pub mod witness_utils;
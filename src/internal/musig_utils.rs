@@ -0,0 +1,286 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! A two-round MuSig2 (BIP 327) for the funding key, written out on top of
+//! secp256k1's point and scalar arithmetic so each step can be followed.
+//!
+//! Scalars that should only be zero with negligible probability (hash
+//! outputs, sums of secret values) are unwrapped.
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::key::{Parity, XOnlyPublicKey};
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::taproot::TapNodeHash;
+use internal::errors::MusigError;
+
+/// BIP 340 style tagged hash: `sha256(sha256(tag) || sha256(tag) || data)`.
+pub fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::hash(tag.as_bytes());
+    let mut engine = Sha256::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for bytes in data {
+        engine.input(bytes);
+    }
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+fn hash_to_scalar(tag: &str, data: &[&[u8]]) -> Scalar {
+    // a hash is at least n with probability about 2^-128
+    Scalar::from_be_bytes(tagged_hash(tag, data)).unwrap()
+}
+
+fn scalar_mul(scalar: Scalar, other: Scalar) -> Scalar {
+    Scalar::from(SecretKey::from_slice(&scalar.to_be_bytes()).unwrap().mul_tweak(&other).unwrap())
+}
+
+/// The aggregate of several public keys, plus the tweaks applied to it since.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    pub pubkeys: Vec<secp256k1PublicKey>,
+    aggregate: secp256k1PublicKey,
+    /// `gacc` of BIP 327, which is only ever 1 or -1.
+    negated: bool,
+    /// `tacc` of BIP 327, `None` while no tweak has been added.
+    tweak: Option<SecretKey>,
+}
+
+impl KeyAggContext {
+    /// Aggregate `pubkeys` in the order given. Sort them first (see
+    /// `sort_funding_keys`) if the signers don't agree on an order.
+    pub fn new(pubkeys: Vec<secp256k1PublicKey>) -> Result<KeyAggContext, MusigError> {
+        if pubkeys.is_empty() {
+            return Err(MusigError::NoKeys);
+        }
+
+        let secp = Secp256k1::verification_only();
+        let mut terms = vec![];
+        for pubkey in &pubkeys {
+            let coefficient = key_agg_coefficient(&pubkeys, pubkey);
+            terms.push(pubkey.mul_tweak(&secp, &coefficient).unwrap());
+        }
+        let aggregate = secp256k1PublicKey::combine_keys(&terms.iter().collect::<Vec<_>>())
+            .map_err(|_| MusigError::InfiniteAggregate)?;
+
+        Ok(KeyAggContext { pubkeys, aggregate, negated: false, tweak: None })
+    }
+
+    /// The aggregate key as it appears in a P2TR output.
+    pub fn aggregated_pubkey(&self) -> XOnlyPublicKey {
+        self.aggregate.x_only_public_key().0
+    }
+
+    /// Apply the BIP 341 key path tweak for a tree with `merkle_root` (`None`
+    /// for an output with no scripts), so the context signs for the output key.
+    pub fn with_taproot_tweak(self, merkle_root: Option<TapNodeHash>) -> KeyAggContext {
+        let internal_key = self.aggregated_pubkey();
+
+        let mut data = internal_key.serialize().to_vec();
+        if let Some(root) = merkle_root {
+            data.extend_from_slice(root.as_ref());
+        }
+        let t = hash_to_scalar("TapTweak", &[&data]);
+        self.with_xonly_tweak(t)
+    }
+
+    /// Add `t` to the aggregate as an x-only key.
+    fn with_xonly_tweak(self, t: Scalar) -> KeyAggContext {
+        let secp = Secp256k1::verification_only();
+        let odd = self.aggregate.x_only_public_key().1 == Parity::Odd;
+
+        let even_aggregate = if odd { self.aggregate.negate(&secp) } else { self.aggregate };
+        let aggregate = even_aggregate.add_exp_tweak(&secp, &t).unwrap();
+
+        // tacc' = t + g * tacc
+        let tweak = match self.tweak {
+            Some(tacc) => {
+                let tacc = if odd { tacc.negate() } else { tacc };
+                tacc.add_tweak(&t).ok()
+            }
+            None => SecretKey::from_slice(&t.to_be_bytes()).ok(),
+        };
+
+        KeyAggContext { pubkeys: self.pubkeys, aggregate, negated: self.negated ^ odd, tweak }
+    }
+
+    fn has_even_y(&self) -> bool {
+        self.aggregate.x_only_public_key().1 == Parity::Even
+    }
+}
+
+/// `KeyAgg coefficient` of BIP 327. The second distinct key gets 1, which
+/// saves a multiplication and is still secure.
+fn key_agg_coefficient(pubkeys: &[secp256k1PublicKey], pubkey: &secp256k1PublicKey) -> Scalar {
+    let second_key = pubkeys.iter().find(|key| *key != &pubkeys[0]);
+    if second_key == Some(pubkey) {
+        return Scalar::ONE;
+    }
+
+    let list: Vec<u8> = pubkeys.iter().flat_map(|key| key.serialize()).collect();
+    let list_hash = tagged_hash("KeyAgg list", &[&list]);
+    hash_to_scalar("KeyAgg coefficient", &[&list_hash, &pubkey.serialize()])
+}
+
+/// The two secret nonces of one signing session. Not `Clone`, so that signing
+/// uses them up: signing twice with the same nonces leaks the secret key.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SecretNonce(SecretKey, SecretKey);
+
+/// The two public nonces a signer sends in the first round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicNonce(pub secp256k1PublicKey, pub secp256k1PublicKey);
+
+impl PublicNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        bytes[..33].copy_from_slice(&self.0.serialize());
+        bytes[33..].copy_from_slice(&self.1.serialize());
+        bytes
+    }
+}
+
+/// Derive a signer's nonces from `session_rand`, which must be fresh
+/// randomness for every signing session. Also hashing in the key and message
+/// keeps a weak random source from repeating nonces across sessions.
+pub fn generate_nonce(
+    session_rand: [u8; 32],
+    secret_key: &SecretKey,
+    aggregated_pubkey: &XOnlyPublicKey,
+    msg: &[u8; 32],
+) -> (SecretNonce, PublicNonce) {
+    let secp = Secp256k1::new();
+    let pubkey = secret_key.public_key(&secp).serialize();
+    let nonce = |i: u8| {
+        let k = tagged_hash("MuSig/nonce", &[&session_rand, &pubkey, &aggregated_pubkey.serialize(), msg, &[i]]);
+        SecretKey::from_slice(&k).unwrap()
+    };
+
+    let (k1, k2) = (nonce(0), nonce(1));
+    let public_nonce = PublicNonce(k1.public_key(&secp), k2.public_key(&secp));
+    (SecretNonce(k1, k2), public_nonce)
+}
+
+/// Sum the public nonces of every signer, once all of them have been received.
+pub fn aggregate_nonces(nonces: &[PublicNonce]) -> Result<PublicNonce, MusigError> {
+    let first: Vec<&secp256k1PublicKey> = nonces.iter().map(|nonce| &nonce.0).collect();
+    let second: Vec<&secp256k1PublicKey> = nonces.iter().map(|nonce| &nonce.1).collect();
+
+    Ok(PublicNonce(
+        secp256k1PublicKey::combine_keys(&first).map_err(|_| MusigError::InfiniteAggregate)?,
+        secp256k1PublicKey::combine_keys(&second).map_err(|_| MusigError::InfiniteAggregate)?,
+    ))
+}
+
+/// What every signer derives from the aggregate nonce and the message.
+struct Session {
+    /// The nonce coefficient `b`.
+    b: Scalar,
+    /// The final nonce `R = R1 + b * R2`.
+    r: secp256k1PublicKey,
+    /// The BIP 340 challenge `e`.
+    e: Scalar,
+}
+
+impl Session {
+    fn new(ctx: &KeyAggContext, aggnonce: &PublicNonce, msg: &[u8; 32]) -> Session {
+        let secp = Secp256k1::verification_only();
+        let q = ctx.aggregated_pubkey().serialize();
+
+        let b = hash_to_scalar("MuSig/noncecoef", &[&aggnonce.serialize(), &q, msg]);
+        let r = aggnonce.0.combine(&aggnonce.1.mul_tweak(&secp, &b).unwrap()).unwrap();
+        let e = hash_to_scalar("BIP0340/challenge", &[&r.x_only_public_key().0.serialize(), &q, msg]);
+
+        Session { b, r, e }
+    }
+
+    fn r_has_even_y(&self) -> bool {
+        self.r.x_only_public_key().1 == Parity::Even
+    }
+}
+
+/// One signer's share `s_i` of the final signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature(pub SecretKey);
+
+/// Sign `msg` for the aggregate in `ctx`, using up `secnonce`.
+pub fn partial_sign(
+    secnonce: SecretNonce,
+    secret_key: &SecretKey,
+    ctx: &KeyAggContext,
+    aggnonce: &PublicNonce,
+    msg: &[u8; 32],
+) -> Result<PartialSignature, MusigError> {
+    let secp = Secp256k1::new();
+    let pubkey = secret_key.public_key(&secp);
+    if !ctx.pubkeys.contains(&pubkey) {
+        return Err(MusigError::UnknownSigner);
+    }
+
+    let session = Session::new(ctx, aggnonce, msg);
+    let SecretNonce(k1, k2) = secnonce;
+    let (k1, k2) = if session.r_has_even_y() { (k1, k2) } else { (k1.negate(), k2.negate()) };
+
+    // d = g * gacc * d_i, where g flips the key if the aggregate has an odd y
+    let negate_key = ctx.has_even_y() == ctx.negated;
+    let d = if negate_key { secret_key.negate() } else { *secret_key };
+
+    // s_i = k1 + b * k2 + e * a_i * d
+    let a = key_agg_coefficient(&ctx.pubkeys, &pubkey);
+    let ead = d.mul_tweak(&a).unwrap().mul_tweak(&session.e).unwrap();
+    let s = k2.mul_tweak(&session.b).unwrap().add_tweak(&Scalar::from(k1)).unwrap();
+    let s = s.add_tweak(&Scalar::from(ead)).unwrap();
+
+    Ok(PartialSignature(s))
+}
+
+/// Check a counterparty's partial signature before combining it, so a bad
+/// share can be blamed on whoever sent it.
+pub fn verify_partial_signature(
+    partial_signature: &PartialSignature,
+    pubnonce: &PublicNonce,
+    pubkey: &secp256k1PublicKey,
+    ctx: &KeyAggContext,
+    aggnonce: &PublicNonce,
+    msg: &[u8; 32],
+) -> bool {
+    let secp = Secp256k1::new();
+    let session = Session::new(ctx, aggnonce, msg);
+
+    let r = pubnonce.0.combine(&pubnonce.1.mul_tweak(&secp, &session.b).unwrap()).unwrap();
+    let r = if session.r_has_even_y() { r } else { r.negate(&secp) };
+
+    let negate_key = ctx.has_even_y() == ctx.negated;
+    let pubkey_term = if negate_key { pubkey.negate(&secp) } else { *pubkey };
+    let a = key_agg_coefficient(&ctx.pubkeys, pubkey);
+    let ea = scalar_mul(a, session.e);
+    let expected = r.combine(&pubkey_term.mul_tweak(&secp, &ea).unwrap());
+
+    expected == Ok(partial_signature.0.public_key(&secp))
+}
+
+/// Combine every signer's partial signature into a BIP 340 signature for the
+/// aggregate key in `ctx`.
+pub fn aggregate_partial_signatures(
+    partial_signatures: &[PartialSignature],
+    ctx: &KeyAggContext,
+    aggnonce: &PublicNonce,
+    msg: &[u8; 32],
+) -> SchnorrSignature {
+    let session = Session::new(ctx, aggnonce, msg);
+
+    let mut s = partial_signatures[0].0;
+    for partial_signature in &partial_signatures[1..] {
+        s = s.add_tweak(&Scalar::from(partial_signature.0)).unwrap();
+    }
+
+    // s += e * g * tacc
+    if let Some(tacc) = ctx.tweak {
+        let tacc = if ctx.has_even_y() { tacc } else { tacc.negate() };
+        s = s.add_tweak(&Scalar::from(tacc.mul_tweak(&session.e).unwrap())).unwrap();
+    }
+
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&session.r.x_only_public_key().0.serialize());
+    bytes[32..].copy_from_slice(&s.secret_bytes());
+    SchnorrSignature::from_slice(&bytes).unwrap()
+}
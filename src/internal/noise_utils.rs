@@ -0,0 +1,73 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use internal::errors::TransportError;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+
+pub const PROTOCOL_NAME: &[u8] = b"Noise_XK_secp256k1_ChaChaPoly_SHA256";
+pub const PROLOGUE: &[u8] = b"lightning";
+
+/// The only handshake version BOLT 8 defines.
+pub const HANDSHAKE_VERSION: u8 = 0;
+
+/// Length of a Poly1305 authentication tag.
+pub const TAG_LEN: usize = 16;
+
+/// SHA256 over the concatenation of `data`, for mixing into the handshake hash.
+pub fn sha256(data: &[&[u8]]) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    for bytes in data {
+        engine.input(bytes);
+    }
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+/// BOLT 8 ECDH: the SHA256 of the compressed shared point.
+pub fn ecdh(secret_key: &SecretKey, pubkey: &secp256k1PublicKey) -> [u8; 32] {
+    SharedSecret::new(pubkey, secret_key).secret_bytes()
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut engine = HmacEngine::<Sha256>::new(key);
+    for bytes in data {
+        engine.input(bytes);
+    }
+    Hmac::<Sha256>::from_engine(engine).to_byte_array()
+}
+
+/// RFC 5869 HKDF with SHA256, salted with the chaining key and cut to the two
+/// 32-byte outputs BOLT 8 uses.
+pub fn hkdf(salt: &[u8; 32], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let prk = hmac_sha256(salt, &[ikm]);
+    let t1 = hmac_sha256(&prk, &[&[1]]);
+    let t2 = hmac_sha256(&prk, &[&t1, &[2]]);
+    (t1, t2)
+}
+
+/// The 96-bit ChaCha20-Poly1305 nonce: 32 zero bits then the 64-bit counter
+/// in little endian.
+fn nonce(n: u64) -> Nonce {
+    let mut bytes = [0u8; 12];
+    bytes[4..].copy_from_slice(&n.to_le_bytes());
+    Nonce::assume_unique_for_key(bytes)
+}
+
+pub fn encrypt_with_ad(key: &[u8; 32], n: u64, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(nonce(n), Aad::from(ad), &mut in_out).unwrap();
+    in_out
+}
+
+pub fn decrypt_with_ad(key: &[u8; 32], n: u64, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(nonce(n), Aad::from(ad), &mut in_out)
+        .map_err(|_| TransportError::DecryptionFailed)?;
+    Ok(plaintext.to_vec())
+}
@@ -0,0 +1,445 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! BOLT 12 messages: offers, invoice requests and invoices. Each is a TLV
+//! stream, written as bech32 without a checksum, and signed with BIP 340
+//! over a merkle tree of its records.
+use crate::internal;
+use bech32::primitives::decode::UncheckedHrpstring;
+use bech32::{Hrp, NoChecksum};
+use bitcoin::constants::ChainHash;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+use bitcoin::secp256k1::{Message, PublicKey as secp256k1PublicKey, Secp256k1};
+use bitcoin::Network;
+use internal::blinded_path::BlindedPath;
+use internal::errors::{OfferError, WireError};
+use internal::payments::PaymentHash;
+use internal::tlv::{decode_tu64, find_record, read_tlv_stream, write_bigsize, TlvRecord};
+use internal::units::MilliSat;
+use internal::wire::{write_u16_prefixed, Reader};
+use std::ops::RangeInclusive;
+
+pub const OFFER_HRP: &str = "lno";
+pub const INVOICE_REQUEST_HRP: &str = "lnr";
+pub const INVOICE_HRP: &str = "lni";
+
+/// TLV types in an offer.
+pub const OFFER_CHAINS_TLV: u64 = 2;
+pub const OFFER_METADATA_TLV: u64 = 4;
+pub const OFFER_CURRENCY_TLV: u64 = 6;
+pub const OFFER_AMOUNT_TLV: u64 = 8;
+pub const OFFER_DESCRIPTION_TLV: u64 = 10;
+pub const OFFER_FEATURES_TLV: u64 = 12;
+pub const OFFER_ABSOLUTE_EXPIRY_TLV: u64 = 14;
+pub const OFFER_PATHS_TLV: u64 = 16;
+pub const OFFER_ISSUER_TLV: u64 = 18;
+pub const OFFER_QUANTITY_MAX_TLV: u64 = 20;
+pub const OFFER_ISSUER_ID_TLV: u64 = 22;
+
+/// TLV types an `invoice_request` adds to the offer it copies.
+pub const INVREQ_METADATA_TLV: u64 = 0;
+pub const INVREQ_CHAIN_TLV: u64 = 80;
+pub const INVREQ_AMOUNT_TLV: u64 = 82;
+pub const INVREQ_FEATURES_TLV: u64 = 84;
+pub const INVREQ_QUANTITY_TLV: u64 = 86;
+pub const INVREQ_PAYER_ID_TLV: u64 = 88;
+pub const INVREQ_PAYER_NOTE_TLV: u64 = 89;
+
+/// TLV types an `invoice` adds to the `invoice_request` it copies.
+pub const INVOICE_PATHS_TLV: u64 = 160;
+pub const INVOICE_BLINDEDPAY_TLV: u64 = 162;
+pub const INVOICE_CREATED_AT_TLV: u64 = 164;
+pub const INVOICE_RELATIVE_EXPIRY_TLV: u64 = 166;
+pub const INVOICE_PAYMENT_HASH_TLV: u64 = 168;
+pub const INVOICE_AMOUNT_TLV: u64 = 170;
+pub const INVOICE_FALLBACKS_TLV: u64 = 172;
+pub const INVOICE_FEATURES_TLV: u64 = 174;
+pub const INVOICE_NODE_ID_TLV: u64 = 176;
+
+pub const SIGNATURE_TLV: u64 = 240;
+
+/// Records in this range sign the message and are left out of its merkle
+/// tree.
+pub const SIGNATURE_TYPES: RangeInclusive<u64> = 240..=1000;
+
+/// Where each message's own fields live. Types from `1_000_000_000` up are
+/// left for experiments, split the same way.
+pub const OFFER_TYPES: [RangeInclusive<u64>; 2] = [1..=79, 1_000_000_000..=1_999_999_999];
+pub const INVOICE_REQUEST_TYPES: [RangeInclusive<u64>; 2] = [0..=159, 1_000_000_000..=2_999_999_999];
+pub const INVOICE_TYPES: [RangeInclusive<u64>; 3] = [0..=239, SIGNATURE_TYPES, 1_000_000_000..=2_999_999_999];
+
+/// What a missing `invoice_relative_expiry` means, in seconds.
+pub const DEFAULT_RELATIVE_EXPIRY: u64 = 7200;
+
+const OFFER_KNOWN_TYPES: [u64; 11] = [
+    OFFER_CHAINS_TLV,
+    OFFER_METADATA_TLV,
+    OFFER_CURRENCY_TLV,
+    OFFER_AMOUNT_TLV,
+    OFFER_DESCRIPTION_TLV,
+    OFFER_FEATURES_TLV,
+    OFFER_ABSOLUTE_EXPIRY_TLV,
+    OFFER_PATHS_TLV,
+    OFFER_ISSUER_TLV,
+    OFFER_QUANTITY_MAX_TLV,
+    OFFER_ISSUER_ID_TLV,
+];
+
+const INVOICE_REQUEST_KNOWN_TYPES: [u64; 6] = [
+    INVREQ_METADATA_TLV,
+    INVREQ_CHAIN_TLV,
+    INVREQ_AMOUNT_TLV,
+    INVREQ_FEATURES_TLV,
+    INVREQ_QUANTITY_TLV,
+    INVREQ_PAYER_ID_TLV,
+];
+
+const INVOICE_KNOWN_TYPES: [u64; 9] = [
+    INVOICE_PATHS_TLV,
+    INVOICE_BLINDEDPAY_TLV,
+    INVOICE_CREATED_AT_TLV,
+    INVOICE_RELATIVE_EXPIRY_TLV,
+    INVOICE_PAYMENT_HASH_TLV,
+    INVOICE_AMOUNT_TLV,
+    INVOICE_FALLBACKS_TLV,
+    INVOICE_FEATURES_TLV,
+    INVOICE_NODE_ID_TLV,
+];
+
+/// A decoded offer: a reusable request for payment the payee can print on a
+/// sign. Paying it starts with an `invoice_request`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    /// Genesis hashes of the chains the offer can be paid on. Empty means
+    /// bitcoin only.
+    pub chains: Vec<ChainHash>,
+    pub metadata: Option<Vec<u8>>,
+    /// ISO 4217 code the amount is in. `None` means the amount is in msat.
+    pub currency: Option<String>,
+    /// `None` if the payer gets to choose how much to pay.
+    pub amount: Option<u64>,
+    pub description: Option<String>,
+    pub features: Vec<u8>,
+    /// Seconds since the Unix epoch after which the offer can't be paid.
+    pub absolute_expiry: Option<u64>,
+    /// Blinded paths to send the `invoice_request` along.
+    pub paths: Vec<BlindedPath>,
+    pub issuer: Option<String>,
+    pub quantity_max: Option<u64>,
+    /// The key the invoice is signed with, if the offer names one.
+    pub issuer_id: Option<secp256k1PublicKey>,
+    /// Every record as it was read. An `invoice_request` repeats them all,
+    /// including the odd ones nobody here understands.
+    pub records: Vec<TlvRecord>,
+}
+
+/// What the payee charges to use a blinded path from a BOLT 12 invoice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedPayInfo {
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: MilliSat,
+    pub htlc_maximum_msat: MilliSat,
+    pub features: Vec<u8>,
+}
+
+/// A decoded BOLT 12 invoice. The payer still has to check it against the
+/// `invoice_request` it answers, including its signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bolt12Invoice {
+    /// Blinded paths to the payee, each with what it costs to use.
+    pub paths: Vec<(BlindedPath, BlindedPayInfo)>,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Seconds after `created_at` the invoice stops being payable.
+    pub relative_expiry: u64,
+    pub payment_hash: PaymentHash,
+    pub amount: MilliSat,
+    pub features: Vec<u8>,
+    pub node_id: secp256k1PublicKey,
+    pub signature: SchnorrSignature,
+    pub records: Vec<TlvRecord>,
+}
+
+/// Removes the `+` BOLT 12 strings may be split with, along with any
+/// whitespace after it, then reads the bech32 data part without a checksum.
+pub fn decode_bolt12(encoded: &str, hrp: &'static str) -> Result<Vec<u8>, OfferError> {
+    let joined: String = if encoded.contains('+') {
+        for part in encoded.split('+') {
+            let part = part.trim_start();
+            if part.is_empty() || part.contains(char::is_whitespace) {
+                return Err(OfferError::InvalidContinuation);
+            }
+        }
+        encoded.chars().filter(|c| *c != '+' && !c.is_whitespace()).collect()
+    } else {
+        encoded.to_string()
+    };
+
+    let unchecked = UncheckedHrpstring::new(&joined)?;
+    if !unchecked.hrp().as_str().eq_ignore_ascii_case(hrp) {
+        return Err(OfferError::WrongHrp(hrp));
+    }
+    Ok(unchecked.remove_checksum::<NoChecksum>().byte_iter().collect())
+}
+
+pub fn encode_bolt12(hrp: &str, bytes: &[u8]) -> String {
+    bech32::encode_lower::<NoChecksum>(Hrp::parse(hrp).expect("BOLT 12 HRPs are valid bech32"), bytes).unwrap()
+}
+
+/// A record exactly as it appears in the stream: type, length and value.
+pub fn record_bytes(record: &TlvRecord) -> Vec<u8> {
+    let mut out = Vec::with_capacity(record.value.len() + 2);
+    write_bigsize(&mut out, record.tlv_type);
+    write_bigsize(&mut out, record.value.len() as u64);
+    out.extend_from_slice(&record.value);
+    out
+}
+
+/// `sha256(sha256(tag) || sha256(tag) || data)`. Unlike the BIP 340 tags,
+/// the nonce tag holds a whole TLV record, so it is bytes rather than text.
+fn bolt12_tagged_hash(tag: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::hash(tag);
+    let mut engine = Sha256::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for bytes in data {
+        engine.input(bytes);
+    }
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+/// The root of the tree the signature commits to. Every record outside
+/// `SIGNATURE_TYPES` gives two leaves, `H("LnLeaf", record)` and
+/// `H("LnNonce" || first_record, type)`, which stop anyone guessing the
+/// hidden records of a partly revealed message. Branches hash the smaller
+/// child first; an odd node out is carried up a level unchanged.
+pub fn merkle_root(records: &[TlvRecord]) -> [u8; 32] {
+    let mut nonce_tag = b"LnNonce".to_vec();
+    nonce_tag.extend_from_slice(&record_bytes(&records[0]));
+
+    let mut leaves = Vec::new();
+    for record in records.iter().filter(|record| !SIGNATURE_TYPES.contains(&record.tlv_type)) {
+        let mut type_bytes = Vec::new();
+        write_bigsize(&mut type_bytes, record.tlv_type);
+        leaves.push(bolt12_tagged_hash(b"LnLeaf", &[&record_bytes(record)]));
+        leaves.push(bolt12_tagged_hash(&nonce_tag, &[&type_bytes]));
+    }
+
+    let mut step = 1;
+    while step < leaves.len() {
+        for i in (0..leaves.len() - step).step_by(2 * step) {
+            let (lesser, greater) = if leaves[i] < leaves[i + step] {
+                (leaves[i], leaves[i + step])
+            } else {
+                (leaves[i + step], leaves[i])
+            };
+            leaves[i] = bolt12_tagged_hash(b"LnBranch", &[&lesser, &greater]);
+        }
+        step *= 2;
+    }
+    leaves[0]
+}
+
+/// What gets signed: the merkle root under the tag
+/// `"lightning" || message_name || "signature"`.
+pub fn signature_digest(message_name: &str, records: &[TlvRecord]) -> Message {
+    let tag = format!("lightning{}signature", message_name);
+    Message::from_digest(bolt12_tagged_hash(tag.as_bytes(), &[&merkle_root(records)]))
+}
+
+pub fn verify_signature(
+    message_name: &str,
+    records: &[TlvRecord],
+    signature: &SchnorrSignature,
+    pubkey: &secp256k1PublicKey,
+) -> Result<(), OfferError> {
+    let digest = signature_digest(message_name, records);
+    Secp256k1::verification_only()
+        .verify_schnorr(signature, &digest, &pubkey.x_only_public_key().0)
+        .map_err(|_| OfferError::InvalidSignature)
+}
+
+/// Types outside `ranges` may not appear at all, odd or even.
+fn check_types(records: &[TlvRecord], ranges: &[RangeInclusive<u64>]) -> Result<(), OfferError> {
+    match records.iter().find(|record| !ranges.iter().any(|range| range.contains(&record.tlv_type))) {
+        Some(record) => Err(OfferError::UnexpectedType(record.tlv_type)),
+        None => Ok(()),
+    }
+}
+
+fn read_string(bytes: &[u8], field: &'static str) -> Result<String, OfferError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| OfferError::InvalidField(field))
+}
+
+fn read_paths(bytes: &[u8]) -> Result<Vec<BlindedPath>, WireError> {
+    let mut reader = Reader::new(bytes);
+    let mut paths = Vec::new();
+    while !reader.is_empty() {
+        paths.push(BlindedPath::decode(&mut reader)?);
+    }
+    Ok(paths)
+}
+
+impl BlindedPayInfo {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.fee_base_msat.to_be_bytes());
+        out.extend_from_slice(&self.fee_proportional_millionths.to_be_bytes());
+        out.extend_from_slice(&self.cltv_expiry_delta.to_be_bytes());
+        out.extend_from_slice(&self.htlc_minimum_msat.to_msat().to_be_bytes());
+        out.extend_from_slice(&self.htlc_maximum_msat.to_msat().to_be_bytes());
+        write_u16_prefixed(&mut out, &self.features);
+        out
+    }
+
+    pub fn decode(reader: &mut Reader) -> Result<BlindedPayInfo, WireError> {
+        Ok(BlindedPayInfo {
+            fee_base_msat: reader.read_u32()?,
+            fee_proportional_millionths: reader.read_u32()?,
+            cltv_expiry_delta: reader.read_u16()?,
+            htlc_minimum_msat: reader.read_msat()?,
+            htlc_maximum_msat: reader.read_msat()?,
+            features: reader.read_u16_prefixed()?.to_vec(),
+        })
+    }
+}
+
+pub fn decode_offer(offer: &str) -> Result<Offer, OfferError> {
+    Offer::decode(&decode_bolt12(offer, OFFER_HRP)?)
+}
+
+impl Offer {
+    pub fn decode(bytes: &[u8]) -> Result<Offer, OfferError> {
+        let records = read_tlv_stream(bytes, &OFFER_KNOWN_TYPES)?;
+        check_types(&records, &OFFER_TYPES)?;
+
+        let chains = match find_record(&records, OFFER_CHAINS_TLV) {
+            Some(bytes) if bytes.len().is_multiple_of(32) => {
+                bytes.chunks(32).map(|chain| ChainHash::from(<[u8; 32]>::try_from(chain).unwrap())).collect()
+            }
+            Some(_) => return Err(OfferError::InvalidField("offer_chains")),
+            None => Vec::new(),
+        };
+        let currency = match find_record(&records, OFFER_CURRENCY_TLV) {
+            Some(bytes) if bytes.len() == 3 => Some(read_string(bytes, "offer_currency")?),
+            Some(_) => return Err(OfferError::InvalidField("offer_currency")),
+            None => None,
+        };
+        let amount = find_record(&records, OFFER_AMOUNT_TLV).map(|bytes| decode_tu64(bytes, 8)).transpose()?;
+        let description = find_record(&records, OFFER_DESCRIPTION_TLV)
+            .map(|bytes| read_string(bytes, "offer_description"))
+            .transpose()?;
+        let absolute_expiry =
+            find_record(&records, OFFER_ABSOLUTE_EXPIRY_TLV).map(|bytes| decode_tu64(bytes, 8)).transpose()?;
+        let paths = match find_record(&records, OFFER_PATHS_TLV) {
+            Some([]) => return Err(OfferError::InvalidField("offer_paths")),
+            Some(bytes) => read_paths(bytes)?,
+            None => Vec::new(),
+        };
+        let issuer =
+            find_record(&records, OFFER_ISSUER_TLV).map(|bytes| read_string(bytes, "offer_issuer")).transpose()?;
+        let quantity_max =
+            find_record(&records, OFFER_QUANTITY_MAX_TLV).map(|bytes| decode_tu64(bytes, 8)).transpose()?;
+        let issuer_id = find_record(&records, OFFER_ISSUER_ID_TLV)
+            .map(|bytes| Reader::new(bytes).read_pubkey().map_err(|_| OfferError::InvalidField("offer_issuer_id")))
+            .transpose()?;
+
+        if amount.is_some() && description.is_none() {
+            return Err(OfferError::MissingField("offer_description"));
+        }
+        if currency.is_some() && amount.is_none() {
+            return Err(OfferError::MissingField("offer_amount"));
+        }
+        if issuer_id.is_none() && paths.is_empty() {
+            return Err(OfferError::MissingField("offer_issuer_id"));
+        }
+
+        Ok(Offer {
+            chains,
+            metadata: find_record(&records, OFFER_METADATA_TLV).map(<[u8]>::to_vec),
+            currency,
+            amount,
+            description,
+            features: find_record(&records, OFFER_FEATURES_TLV).map(<[u8]>::to_vec).unwrap_or_default(),
+            absolute_expiry,
+            paths,
+            issuer,
+            quantity_max,
+            issuer_id,
+            records,
+        })
+    }
+
+    /// Offers that list no chains are for bitcoin.
+    pub fn supports_chain(&self, network: Network) -> bool {
+        let chain = ChainHash::using_genesis_block(network);
+        if self.chains.is_empty() {
+            chain == ChainHash::BITCOIN
+        } else {
+            self.chains.contains(&chain)
+        }
+    }
+}
+
+/// Reads the records of an `invoice_request`, checking only that each is one
+/// an `invoice_request` may carry.
+pub fn read_invoice_request(bytes: &[u8]) -> Result<Vec<TlvRecord>, OfferError> {
+    let known_types: Vec<u64> =
+        OFFER_KNOWN_TYPES.iter().chain(&INVOICE_REQUEST_KNOWN_TYPES).chain(&[SIGNATURE_TLV]).copied().collect();
+    let records = read_tlv_stream(bytes, &known_types)?;
+    check_types(&records, &[INVOICE_REQUEST_TYPES[0].clone(), SIGNATURE_TYPES, INVOICE_REQUEST_TYPES[1].clone()])?;
+    Ok(records)
+}
+
+impl Bolt12Invoice {
+    /// Parses the invoice's own fields. The fields it copies from the
+    /// `invoice_request` are kept in `records`, unread.
+    pub fn decode(bytes: &[u8]) -> Result<Bolt12Invoice, OfferError> {
+        let known_types: Vec<u64> = OFFER_KNOWN_TYPES
+            .iter()
+            .chain(&INVOICE_REQUEST_KNOWN_TYPES)
+            .chain(&INVOICE_KNOWN_TYPES)
+            .chain(&[SIGNATURE_TLV])
+            .copied()
+            .collect();
+        let records = read_tlv_stream(bytes, &known_types)?;
+        check_types(&records, &INVOICE_TYPES)?;
+
+        let required = |tlv_type, field| find_record(&records, tlv_type).ok_or(OfferError::MissingField(field));
+
+        let paths = read_paths(required(INVOICE_PATHS_TLV, "invoice_paths")?)?;
+        let mut reader = Reader::new(required(INVOICE_BLINDEDPAY_TLV, "invoice_blindedpay")?);
+        let mut pay_info = Vec::with_capacity(paths.len());
+        while !reader.is_empty() {
+            pay_info.push(BlindedPayInfo::decode(&mut reader)?);
+        }
+        if paths.is_empty() || pay_info.len() != paths.len() {
+            return Err(OfferError::InvalidField("invoice_blindedpay"));
+        }
+
+        let payment_hash = PaymentHash::from_slice(required(INVOICE_PAYMENT_HASH_TLV, "invoice_payment_hash")?)
+            .map_err(|_| OfferError::InvalidField("invoice_payment_hash"))?;
+        let node_id = Reader::new(required(INVOICE_NODE_ID_TLV, "invoice_node_id")?)
+            .read_pubkey()
+            .map_err(|_| OfferError::InvalidField("invoice_node_id"))?;
+        let signature = SchnorrSignature::from_slice(required(SIGNATURE_TLV, "signature")?)
+            .map_err(|_| OfferError::InvalidField("signature"))?;
+
+        Ok(Bolt12Invoice {
+            paths: paths.into_iter().zip(pay_info).collect(),
+            created_at: decode_tu64(required(INVOICE_CREATED_AT_TLV, "invoice_created_at")?, 8)?,
+            relative_expiry: find_record(&records, INVOICE_RELATIVE_EXPIRY_TLV)
+                .map(|bytes| decode_tu64(bytes, 4))
+                .transpose()?
+                .unwrap_or(DEFAULT_RELATIVE_EXPIRY),
+            payment_hash,
+            amount: MilliSat(decode_tu64(required(INVOICE_AMOUNT_TLV, "invoice_amount")?, 8)?),
+            features: find_record(&records, INVOICE_FEATURES_TLV).map(<[u8]>::to_vec).unwrap_or_default(),
+            node_id,
+            signature,
+            records,
+        })
+    }
+}
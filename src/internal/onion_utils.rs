@@ -0,0 +1,242 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::ecdh::SharedSecret;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use internal::errors::{OnionError, WireError};
+use internal::noise_utils::hmac_sha256;
+use internal::payments::{PaymentPreimage, PaymentSecret};
+use internal::tlv::{decode_tu64, find_record, read_tlv_stream, write_bigsize};
+use internal::units::MilliSat;
+use internal::wire::{Reader, ShortChannelId};
+
+/// The only onion version BOLT 4 defines.
+pub const ONION_VERSION: u8 = 0;
+
+/// Every payment onion carries exactly this many bytes of hop payloads, so a
+/// forwarding node cannot tell how far along the route it sits.
+pub const HOP_PAYLOADS_LEN: usize = 1300;
+
+pub const HMAC_LEN: usize = 32;
+
+/// Version, ephemeral key, hop payloads and HMAC.
+pub const ONION_PACKET_LEN: usize = 1 + 33 + HOP_PAYLOADS_LEN + HMAC_LEN;
+
+/// TLV types in a hop payload.
+pub const AMT_TO_FORWARD_TLV: u64 = 2;
+pub const OUTGOING_CLTV_VALUE_TLV: u64 = 4;
+pub const SHORT_CHANNEL_ID_TLV: u64 = 6;
+pub const PAYMENT_DATA_TLV: u64 = 8;
+pub const ENCRYPTED_RECIPIENT_DATA_TLV: u64 = 10;
+pub const CURRENT_BLINDING_POINT_TLV: u64 = 12;
+pub const TOTAL_AMOUNT_MSAT_TLV: u64 = 18;
+
+/// Where a keysend sender puts the preimage in the final hop's payload. The
+/// large type comes from bLIP 3: keysend was deployed before there was a
+/// BOLT for it.
+pub const KEYSEND_PREIMAGE_TLV: u64 = 5482373484;
+
+/// Failure code flags. A failure code is one or more flags ORed with a number.
+pub const BADONION: u16 = 0x8000;
+pub const PERM: u16 = 0x4000;
+pub const NODE: u16 = 0x2000;
+pub const UPDATE: u16 = 0x1000;
+
+pub const TEMPORARY_NODE_FAILURE: u16 = NODE | 2;
+pub const PERMANENT_NODE_FAILURE: u16 = PERM | NODE | 2;
+pub const TEMPORARY_CHANNEL_FAILURE: u16 = UPDATE | 7;
+pub const UNKNOWN_NEXT_PEER: u16 = PERM | 10;
+pub const INCORRECT_OR_UNKNOWN_PAYMENT_DETAILS: u16 = PERM | 15;
+
+/// Failure messages are padded to at least this length so their size does
+/// not give away which failure it is.
+pub const FAILURE_MESSAGE_MIN_LEN: usize = 256;
+
+/// The version 0 onion sent in `update_add_htlc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnionPacket {
+    pub version: u8,
+    pub public_key: secp256k1PublicKey,
+    pub hop_payloads: [u8; HOP_PAYLOADS_LEN],
+    pub hmac: [u8; HMAC_LEN],
+}
+
+impl OnionPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(ONION_PACKET_LEN);
+        out.push(self.version);
+        out.extend_from_slice(&self.public_key.serialize());
+        out.extend_from_slice(&self.hop_payloads);
+        out.extend_from_slice(&self.hmac);
+        out
+    }
+
+    /// Parses a packet without checking its HMAC; that needs the shared
+    /// secret, which only the node it is addressed to can compute.
+    pub fn decode(bytes: &[u8; ONION_PACKET_LEN]) -> Result<OnionPacket, OnionError> {
+        if bytes[0] != ONION_VERSION {
+            return Err(OnionError::UnknownVersion(bytes[0]));
+        }
+        let public_key = secp256k1PublicKey::from_slice(&bytes[1..34]).map_err(|_| OnionError::InvalidPublicKey)?;
+        Ok(OnionPacket {
+            version: bytes[0],
+            public_key,
+            hop_payloads: bytes[34..34 + HOP_PAYLOADS_LEN].try_into().unwrap(),
+            hmac: bytes[34 + HOP_PAYLOADS_LEN..].try_into().unwrap(),
+        })
+    }
+}
+
+/// The SHA256 of the compressed point `secret * pubkey`, computed by the
+/// sender with its ephemeral key and by the hop with its node key.
+pub fn shared_secret(secret_key: &SecretKey, pubkey: &secp256k1PublicKey) -> [u8; 32] {
+    SharedSecret::new(pubkey, secret_key).secret_bytes()
+}
+
+/// `SHA256(ephemeral_pubkey || shared_secret)`, the scalar each hop multiplies
+/// the ephemeral key by before passing the onion on.
+pub fn blinding_factor(ephemeral_pubkey: &secp256k1PublicKey, shared_secret: &[u8; 32]) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    engine.input(&ephemeral_pubkey.serialize());
+    engine.input(shared_secret);
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+/// Derives one of the per-hop keys (`rho`, `mu`, `um`, `ammag` or `pad`) as an
+/// HMAC keyed with the key type's ASCII name.
+pub fn generate_key(key_type: &[u8], shared_secret: &[u8; 32]) -> [u8; 32] {
+    hmac_sha256(key_type, &[shared_secret])
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// One 64-byte RFC 8439 ChaCha20 block with an all-zero nonce.
+fn chacha20_block(key: &[u8; 32], counter: u32) -> [u8; 64] {
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&[0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574]);
+    for (word, bytes) in initial[4..12].iter_mut().zip(key.chunks(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    initial[12] = counter;
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0u8; 64];
+    for (i, bytes) in block.chunks_mut(4).enumerate() {
+        bytes.copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    block
+}
+
+/// `len` bytes of ChaCha20 keystream: the encryption of `len` zero bytes
+/// under `key` with a zero nonce.
+pub fn generate_cipher_stream(key: &[u8; 32], len: usize) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(len + 64);
+    let mut counter = 0;
+    while stream.len() < len {
+        stream.extend_from_slice(&chacha20_block(key, counter));
+        counter += 1;
+    }
+    stream.truncate(len);
+    stream
+}
+
+/// XORs `stream` into the start of `data`.
+pub fn xor_in_place(data: &mut [u8], stream: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(stream) {
+        *byte ^= key;
+    }
+}
+
+/// The HMAC a hop checks before peeling its layer: keyed with `mu`, over the
+/// hop payloads and the payment hash the onion is bound to.
+pub fn packet_hmac(mu: &[u8; 32], hop_payloads: &[u8], associated_data: &[u8]) -> [u8; HMAC_LEN] {
+    hmac_sha256(mu, &[hop_payloads, associated_data])
+}
+
+/// Prefixes a hop's TLV payload with its BigSize length, the framing every
+/// hop reads before it knows where its payload ends.
+pub fn frame_hop_payload(payload: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(payload.len() + 3);
+    write_bigsize(&mut framed, payload.len() as u64);
+    framed.extend_from_slice(payload);
+    framed
+}
+
+/// What a hop reads out of its payload once it has peeled its layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopPayload {
+    pub amt_to_forward: MilliSat,
+    pub outgoing_cltv_value: u32,
+    /// Where to forward the HTLC. Only the final hop has none.
+    pub short_channel_id: Option<ShortChannelId>,
+    /// The invoice's `payment_secret` and the total of every part of the
+    /// payment. Only the final hop has this.
+    pub payment_data: Option<(PaymentSecret, MilliSat)>,
+    /// The preimage of a keysend payment, which only the final hop gets.
+    pub keysend_preimage: Option<PaymentPreimage>,
+}
+
+impl HopPayload {
+    /// Parses a hop's TLV stream, without its length prefix.
+    pub fn decode(payload: &[u8]) -> Result<HopPayload, WireError> {
+        let known_types =
+            [AMT_TO_FORWARD_TLV, OUTGOING_CLTV_VALUE_TLV, SHORT_CHANNEL_ID_TLV, PAYMENT_DATA_TLV, KEYSEND_PREIMAGE_TLV];
+        let records = read_tlv_stream(payload, &known_types)?;
+
+        let amt_to_forward =
+            find_record(&records, AMT_TO_FORWARD_TLV).ok_or(WireError::InvalidValue("amt_to_forward"))?;
+        let outgoing_cltv_value =
+            find_record(&records, OUTGOING_CLTV_VALUE_TLV).ok_or(WireError::InvalidValue("outgoing_cltv_value"))?;
+
+        let short_channel_id = match find_record(&records, SHORT_CHANNEL_ID_TLV) {
+            Some(bytes) => Some(ShortChannelId(Reader::new(bytes).read_u64()?)),
+            None => None,
+        };
+
+        let payment_data = match find_record(&records, PAYMENT_DATA_TLV) {
+            Some(bytes) => {
+                let mut reader = Reader::new(bytes);
+                let payment_secret = PaymentSecret(reader.read_array::<32>()?);
+                let total_msat = decode_tu64(reader.read_remaining(), 8)?;
+                Some((payment_secret, MilliSat(total_msat)))
+            }
+            None => None,
+        };
+
+        let keysend_preimage = match find_record(&records, KEYSEND_PREIMAGE_TLV) {
+            Some(bytes) => {
+                Some(PaymentPreimage::from_slice(bytes).map_err(|_| WireError::InvalidValue("keysend_preimage"))?)
+            }
+            None => None,
+        };
+
+        Ok(HopPayload {
+            amt_to_forward: MilliSat(decode_tu64(amt_to_forward, 8)?),
+            outgoing_cltv_value: decode_tu64(outgoing_cltv_value, 4)? as u32,
+            short_channel_id,
+            payment_data,
+            keysend_preimage,
+        })
+    }
+}
@@ -0,0 +1,84 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::hashes::hash160::Hash as Hash160;
+use bitcoin::hashes::ripemd160::Hash as Ripemd160;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use internal::errors::PaymentError;
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// The secret that settles an HTLC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentPreimage(pub [u8; 32]);
+
+/// The SHA256 of a payment's preimage. This is the `payment_hash` of
+/// invoices, the `update_add_htlc` message and the onion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaymentHash(pub [u8; 32]);
+
+/// The RIPEMD160(SHA256(preimage)) that HTLC scripts lock to, so they can
+/// check a preimage with a single `OP_HASH160`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaymentHash160(pub [u8; 20]);
+
+/// The secret an invoice hands the payer to put in the final hop's onion
+/// payload. It stops nodes along the route from probing the payee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaymentSecret(pub [u8; 32]);
+
+fn array_from_slice<const N: usize>(bytes: &[u8]) -> Result<[u8; N], PaymentError> {
+    bytes.try_into().map_err(|_| PaymentError::InvalidLength { expected: N, actual: bytes.len() })
+}
+
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new().fill(&mut bytes).expect("system randomness is available");
+    bytes
+}
+
+impl PaymentPreimage {
+    /// A fresh preimage from the system's random number generator. Anyone
+    /// who can guess it can settle the payment.
+    pub fn random() -> PaymentPreimage {
+        PaymentPreimage(random_bytes())
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<PaymentPreimage, PaymentError> {
+        array_from_slice(bytes).map(PaymentPreimage)
+    }
+
+    pub fn hash(&self) -> PaymentHash {
+        PaymentHash(Sha256::hash(&self.0).to_byte_array())
+    }
+
+    pub fn hash160(&self) -> PaymentHash160 {
+        PaymentHash160(Hash160::hash(&self.0).to_byte_array())
+    }
+}
+
+impl PaymentHash {
+    pub fn from_slice(bytes: &[u8]) -> Result<PaymentHash, PaymentError> {
+        array_from_slice(bytes).map(PaymentHash)
+    }
+
+    /// Only the RIPEMD160 is left to do, since this is already the SHA256.
+    pub fn hash160(&self) -> PaymentHash160 {
+        PaymentHash160(Ripemd160::hash(&self.0).to_byte_array())
+    }
+}
+
+impl PaymentHash160 {
+    pub fn from_slice(bytes: &[u8]) -> Result<PaymentHash160, PaymentError> {
+        array_from_slice(bytes).map(PaymentHash160)
+    }
+}
+
+impl PaymentSecret {
+    pub fn random() -> PaymentSecret {
+        PaymentSecret(random_bytes())
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<PaymentSecret, PaymentError> {
+        array_from_slice(bytes).map(PaymentSecret)
+    }
+}
@@ -0,0 +1,90 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::ecdsa::Signature as BitcoinSignature;
+use bitcoin::psbt::Psbt;
+use bitcoin::script::{Instruction, Script};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bitcoin::sighash::EcdsaSighashType;
+use bitcoin::{PublicKey, Witness};
+use internal::errors::ChannelError;
+use internal::sighash_utils::compute_p2wsh_sighash;
+
+/// The keys of a 2-of-2 funding script, in the order they appear in it.
+fn funding_script_keys(witness_script: &Script) -> Option<(PublicKey, PublicKey)> {
+    let keys: Vec<PublicKey> = witness_script
+        .instructions()
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => PublicKey::from_slice(bytes.as_bytes()).ok(),
+            _ => None,
+        })
+        .collect();
+
+    match keys[..] {
+        [first, second] => Some((first, second)),
+        _ => None,
+    }
+}
+
+/// Add a SIGHASH_ALL partial signature made with `secret_key` to every input
+/// spending a funding output that `secret_key` is one of the keys of.
+pub fn sign_funding_psbt(psbt: Psbt, secret_key: &SecretKey) -> Psbt {
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::new(secret_key.public_key(&secp));
+
+    let mut psbt = psbt;
+    for input_index in 0..psbt.inputs.len() {
+        let input = &psbt.inputs[input_index];
+        let (Some(witness_script), Some(utxo)) = (&input.witness_script, &input.witness_utxo) else {
+            continue;
+        };
+        let Some((first, second)) = funding_script_keys(witness_script) else {
+            continue;
+        };
+        if public_key != first && public_key != second {
+            continue;
+        }
+
+        let sighash = compute_p2wsh_sighash(&psbt.unsigned_tx, input_index, witness_script, utxo.value.to_sat());
+        let signature = secp.sign_ecdsa(&Message::from_digest(sighash), secret_key);
+
+        psbt.inputs[input_index]
+            .partial_sigs
+            .insert(public_key, BitcoinSignature::sighash_all(signature));
+    }
+
+    psbt
+}
+
+/// Turn the partial signatures of every funding input into its final
+/// `0 <sig1> <sig2> <funding_script>` witness, as the BIP 174 finalizer does.
+pub fn finalize_funding_psbt(psbt: Psbt) -> Result<Psbt, ChannelError> {
+    let mut psbt = psbt;
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        let Some(witness_script) = input.witness_script.clone() else {
+            continue;
+        };
+        let Some((first, second)) = funding_script_keys(&witness_script) else {
+            continue;
+        };
+
+        let (Some(first_sig), Some(second_sig)) = (input.partial_sigs.get(&first), input.partial_sigs.get(&second))
+        else {
+            return Err(ChannelError::MissingSignature { input: index });
+        };
+
+        let mut witness = Witness::new();
+        // OP_CHECKMULTISIG pops one element too many
+        witness.push(Vec::new());
+        witness.push(first_sig.to_vec());
+        witness.push(second_sig.to_vec());
+        witness.push(witness_script.as_bytes());
+
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+        input.sighash_type = None;
+        input.witness_script = None;
+        input.bip32_derivation.clear();
+    }
+
+    Ok(psbt)
+}
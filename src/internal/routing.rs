@@ -0,0 +1,40 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use internal::units::MilliSat;
+use internal::wire::ShortChannelId;
+
+/// One channel of a route, and the HTLC that goes over it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteHop {
+    /// The node this channel leads to.
+    pub node_id: secp256k1PublicKey,
+    pub short_channel_id: ShortChannelId,
+    /// The HTLC amount on this channel: what the payee gets plus the fees of
+    /// every node after this channel.
+    pub amount_msat: MilliSat,
+    /// How many blocks this HTLC's expiry sits above the final hop's.
+    pub cltv_expiry_delta: u32,
+}
+
+/// A path from the sender to the payee, first hop first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub hops: Vec<RouteHop>,
+}
+
+impl Route {
+    /// What the sender puts into the first HTLC.
+    pub fn total_amount(&self) -> MilliSat {
+        self.hops.first().map_or(MilliSat::ZERO, |hop| hop.amount_msat)
+    }
+
+    pub fn total_fees(&self) -> MilliSat {
+        let delivered = self.hops.last().map_or(MilliSat::ZERO, |hop| hop.amount_msat);
+        self.total_amount().checked_sub(delivered).unwrap()
+    }
+
+    pub fn total_cltv_expiry_delta(&self) -> u32 {
+        self.hops.first().map_or(0, |hop| hop.cltv_expiry_delta)
+    }
+}
@@ -1,7 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
-use internal::bitcoind_client::BitcoindClient;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::Hash;
 use bitcoin::hashes::HashEngine;
@@ -28,26 +27,91 @@ use bitcoin::sighash::EcdsaSighashType;
 use bitcoin::sighash::SighashCache;
 use exercises::exercises::{ two_of_two_multisig_witness_script};
 use bitcoin::script::{Builder};
+use internal::payments::PaymentHash160;
+use internal::key_utils::sort_funding_keys;
+use internal::keys::{DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::taproot::{TaprootBuilder, TaprootSpendInfo};
+use std::str::FromStr;
+use internal::errors::ShutdownScriptError;
 
 pub fn p2wpkh_output_script(public_key: PublicKey) -> ScriptBuf {
     ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash().unwrap())
 }
 
+/// The output descriptor of the funding output, with the keys in BOLT 3 order.
+pub fn funding_output_descriptor(pubkey1: &PublicKey, pubkey2: &PublicKey) -> String {
+    let (first, second) = sort_funding_keys(*pubkey1, *pubkey2);
+    format!("wsh(multi(2,{},{}))", first, second)
+}
+
+/// The `option_anchors` anchor script: spendable by its owner right away, or
+/// by anyone once it has 16 confirmations.
+pub fn build_anchor_output_script(funding_pubkey: &FundingPubkey) -> ScriptBuf {
+    Builder::new()
+        .push_key(&funding_pubkey.0)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_IFDUP)
+        .push_opcode(opcodes::OP_NOTIF)
+        .push_int(16)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+/// The `option_anchors` to_remote script, delayed by one block so it can't be
+/// used to fee-bump the commitment.
+pub fn build_anchor_to_remote_script(remote_pubkey: &PublicKey) -> ScriptBuf {
+    Builder::new()
+        .push_key(remote_pubkey)
+        .push_opcode(opcodes::OP_CHECKSIGVERIFY)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .into_script()
+}
+
 pub fn build_htlc_offerer_witness_script(
-    revocation_pubkey: &PublicKey,
-    remote_htlc_pubkey: &PublicKey,
-    local_htlc_pubkey: &PublicKey,
-    payment_hash160: &[u8; 20],
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+) -> ScriptBuf {
+    htlc_offerer_script_body(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, payment_hash)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+/// The `option_anchors` offered HTLC script, which adds `1 OP_CSV` to the
+/// non-revocation paths so the second-stage transactions can be fee-bumped.
+pub fn build_anchor_htlc_offerer_witness_script(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
 ) -> ScriptBuf {
+    htlc_offerer_script_body(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, payment_hash)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+fn htlc_offerer_script_body(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+) -> Builder {
     Builder::new()
         .push_opcode(opcodes::OP_DUP)
         .push_opcode(opcodes::OP_HASH160)
-        .push_slice(revocation_pubkey.pubkey_hash())
+        .push_slice(revocation_pubkey.0.pubkey_hash())
         .push_opcode(opcodes::OP_EQUAL)
         .push_opcode(opcodes::OP_IF)
         .push_opcode(opcodes::OP_CHECKSIG)
         .push_opcode(opcodes::OP_ELSE)
-        .push_key(&remote_htlc_pubkey)
+        .push_key(&remote_htlc_pubkey.0)
         .push_opcode(opcodes::OP_SWAP)
         .push_opcode(opcodes::OP_SIZE)
         .push_int(32)
@@ -56,15 +120,173 @@ pub fn build_htlc_offerer_witness_script(
         .push_opcode(opcodes::OP_DROP)
         .push_int(2)
         .push_opcode(opcodes::OP_SWAP)
-        .push_key(&local_htlc_pubkey)
+        .push_key(&local_htlc_pubkey.0)
         .push_int(2)
         .push_opcode(opcodes::OP_CHECKMULTISIG)
         .push_opcode(opcodes::OP_ELSE)
         .push_opcode(opcodes::OP_HASH160)
-        .push_slice(payment_hash160)
+        .push_slice(payment_hash.0)
         .push_opcode(opcodes::OP_EQUALVERIFY)
         .push_opcode(opcodes::OP_CHECKSIG)
         .push_opcode(opcodes::OP_ENDIF)
+}
+
+pub fn build_htlc_receiver_witness_script(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+    cltv_expiry: u32,
+) -> ScriptBuf {
+    htlc_receiver_script_body(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, payment_hash, cltv_expiry)
         .push_opcode(opcodes::OP_ENDIF)
         .into_script()
 }
+
+/// The `option_anchors` received HTLC script, which adds `1 OP_CSV` to the
+/// non-revocation paths so the second-stage transactions can be fee-bumped.
+pub fn build_anchor_htlc_receiver_witness_script(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+    cltv_expiry: u32,
+) -> ScriptBuf {
+    htlc_receiver_script_body(revocation_pubkey, remote_htlc_pubkey, local_htlc_pubkey, payment_hash, cltv_expiry)
+        .push_int(1)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+fn htlc_receiver_script_body(
+    revocation_pubkey: &RevocationPubkey,
+    remote_htlc_pubkey: &HtlcPubkey,
+    local_htlc_pubkey: &HtlcPubkey,
+    payment_hash: &PaymentHash160,
+    cltv_expiry: u32,
+) -> Builder {
+    Builder::new()
+        .push_opcode(opcodes::OP_DUP)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(revocation_pubkey.0.pubkey_hash())
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_key(&remote_htlc_pubkey.0)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_opcode(opcodes::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::OP_EQUAL)
+        .push_opcode(opcodes::OP_IF)
+        .push_opcode(opcodes::OP_HASH160)
+        .push_slice(payment_hash.0)
+        .push_opcode(opcodes::OP_EQUALVERIFY)
+        .push_int(2)
+        .push_opcode(opcodes::OP_SWAP)
+        .push_key(&local_htlc_pubkey.0)
+        .push_int(2)
+        .push_opcode(opcodes::OP_CHECKMULTISIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_opcode(opcodes::OP_DROP)
+        .push_int(cltv_expiry as i64)
+        .push_opcode(opcodes::OP_CLTV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ENDIF)
+}
+
+/// The x-coordinate of BIP 341's NUMS point `H`. Nobody knows its private key,
+/// so an output with it as internal key can only be spent through its scripts.
+pub const TAPROOT_NUMS_POINT: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+pub fn taproot_nums_point() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_str(TAPROOT_NUMS_POINT).unwrap()
+}
+
+/// The simple taproot channels `to_delay_script` leaf: the owner of `to_local`
+/// can spend it once `to_self_delay` blocks have passed.
+pub fn build_taproot_to_delay_script(to_local_delayed_pubkey: &DelayedPaymentPubkey, to_self_delay: u16) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&to_local_delayed_pubkey.0.inner.x_only_public_key().0)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_int(to_self_delay as i64)
+        .push_opcode(opcodes::OP_CSV)
+        .push_opcode(opcodes::OP_DROP)
+        .into_script()
+}
+
+/// The simple taproot channels `revoke_script` leaf. The delayed key is pushed
+/// and dropped so that the leaf commits to this channel's `to_local` output.
+pub fn build_taproot_revoke_script(
+    revocation_pubkey: &RevocationPubkey,
+    to_local_delayed_pubkey: &DelayedPaymentPubkey,
+) -> ScriptBuf {
+    Builder::new()
+        .push_x_only_key(&to_local_delayed_pubkey.0.inner.x_only_public_key().0)
+        .push_opcode(opcodes::OP_DROP)
+        .push_x_only_key(&revocation_pubkey.0.inner.x_only_public_key().0)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Commit to `leaves` in a script tree under `internal_key`. Every leaf gets
+/// the same weight, so none ends up deeper in the tree than it has to.
+pub fn build_taproot_spend_info(internal_key: XOnlyPublicKey, leaves: Vec<ScriptBuf>) -> TaprootSpendInfo {
+    let secp = Secp256k1::verification_only();
+    TaprootBuilder::with_huffman_tree(leaves.into_iter().map(|leaf| (1, leaf)))
+        .unwrap()
+        .finalize(&secp, internal_key)
+        .unwrap()
+}
+
+/// A PTLC locked to a point instead of a hash. Claiming it needs both HTLC
+/// keys, and the offerer's signature is only handed over as an adaptor
+/// signature for the payment point, so claiming reveals the payment secret.
+/// After `cltv_expiry` the offerer can take it back alone.
+pub fn build_ptlc_witness_script(
+    offerer_htlc_pubkey: &HtlcPubkey,
+    receiver_htlc_pubkey: &HtlcPubkey,
+    cltv_expiry: u32,
+) -> ScriptBuf {
+    let offerer = offerer_htlc_pubkey.0.inner.x_only_public_key().0;
+    let receiver = receiver_htlc_pubkey.0.inner.x_only_public_key().0;
+
+    Builder::new()
+        .push_opcode(opcodes::OP_IF)
+        .push_x_only_key(&receiver)
+        .push_opcode(opcodes::OP_CHECKSIGVERIFY)
+        .push_x_only_key(&offerer)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ELSE)
+        .push_int(cltv_expiry as i64)
+        .push_opcode(opcodes::OP_CLTV)
+        .push_opcode(opcodes::OP_DROP)
+        .push_x_only_key(&offerer)
+        .push_opcode(opcodes::OP_CHECKSIG)
+        .push_opcode(opcodes::OP_ENDIF)
+        .into_script()
+}
+
+/// Whether the peer may close to `script`. BOLT 2 allows P2PKH, P2SH,
+/// P2WPKH and P2WSH, and with `option_shutdown_anysegwit` any witness
+/// program of version 1 to 16, like P2TR. Anything else might not relay, and
+/// the closing transaction could never confirm.
+pub fn validate_shutdown_script(script: &ScriptBuf, anysegwit: bool) -> Result<(), ShutdownScriptError> {
+    if script.is_p2pkh() || script.is_p2sh() || script.is_p2wpkh() || script.is_p2wsh() {
+        return Ok(());
+    }
+    match script.witness_version() {
+        // v0 programs are only valid with 20 or 32 bytes
+        Some(version) if version.to_num() > 0 && script.is_witness_program() => {
+            if anysegwit {
+                Ok(())
+            } else {
+                Err(ShutdownScriptError::AnySegwitNotNegotiated(version.to_num()))
+            }
+        }
+        _ => Err(ShutdownScriptError::Unsupported(script.clone())),
+    }
+}
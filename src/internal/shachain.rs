@@ -0,0 +1,96 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::can_derive;
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use internal::errors::ChannelError;
+
+/// The highest index a per-commitment secret can have. The first commitment
+/// uses this index and each later one counts down by one.
+pub const MAX_SHACHAIN_INDEX: u64 = (1 << 48) - 1;
+
+/// BOLT 3 `generate_from_seed`: for each of the lower 48 bits of `index` that
+/// is set, from the highest down, flip that bit of the value and hash it.
+pub fn generate_from_seed(seed: &[u8; 32], index: u64) -> [u8; 32] {
+    let mut value = *seed;
+
+    for bit in (0..48).rev() {
+        if index & (1 << bit) != 0 {
+            value[bit / 8] ^= 1 << (bit % 8);
+            value = Sha256::hash(&value).to_byte_array();
+        }
+    }
+
+    value
+}
+
+/// Like `generate_from_seed`, but only flips the lower `bits` bits, starting
+/// from a secret that was itself derived for the upper bits.
+fn derive_secret(base: &[u8; 32], bits: u32, index: u64) -> [u8; 32] {
+    let mut value = *base;
+
+    for bit in (0..bits as usize).rev() {
+        if index & (1 << bit) != 0 {
+            value[bit / 8] ^= 1 << (bit % 8);
+            value = Sha256::hash(&value).to_byte_array();
+        }
+    }
+
+    value
+}
+
+/// The slot a secret is stored in: the number of trailing zeros of its index.
+fn where_to_put_secret(index: u64) -> usize {
+    index.trailing_zeros().min(48) as usize
+}
+
+/// The counterparty's per-commitment secrets, kept in the 49 slots of the
+/// BOLT 3 compact storage: every secret received so far can be derived from one
+/// of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShachainStore {
+    known: [Option<([u8; 32], u64)>; 49],
+}
+
+impl Default for ShachainStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShachainStore {
+    pub fn new() -> ShachainStore {
+        ShachainStore { known: [None; 49] }
+    }
+
+    /// Store the secret for `index`, checking it derives every secret held
+    /// in the slots below its own.
+    pub fn insert(&mut self, index: u64, secret: [u8; 32]) -> Result<(), ChannelError> {
+        let slot = where_to_put_secret(index);
+
+        for (known_secret, known_index) in self.known[..slot].iter().flatten() {
+            if derive_secret(&secret, slot as u32, *known_index) != *known_secret {
+                return Err(ChannelError::InconsistentSecret { index });
+            }
+        }
+
+        self.known[slot] = Some((secret, index));
+        Ok(())
+    }
+
+    /// The secrets held, with their indexes, from the lowest slot up.
+    /// Inserting them again in this order rebuilds the store.
+    pub fn known_secrets(&self) -> impl Iterator<Item = (u64, [u8; 32])> + '_ {
+        self.known.iter().flatten().map(|(secret, index)| (*index, *secret))
+    }
+
+    /// Recover the secret for `index`, if it was received already.
+    pub fn derive(&self, index: u64) -> Option<[u8; 32]> {
+        self.known.iter().enumerate().find_map(|(slot, known)| match known {
+            Some((secret, known_index)) if can_derive(*known_index, index) => {
+                Some(derive_secret(secret, slot as u32, index))
+            }
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::amount::Amount;
+use bitcoin::hashes::Hash;
+use bitcoin::script::Script;
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{Transaction, TxOut};
+
+/// The BIP 143 SIGHASH_ALL digest for spending the P2WSH output locked to
+/// `witness_script` and holding `amount` sats from input `input_index`.
+pub fn compute_p2wsh_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    witness_script: &Script,
+    amount: u64,
+) -> [u8; 32] {
+    compute_p2wsh_sighash_with_type(tx, input_index, witness_script, amount, EcdsaSighashType::All)
+}
+
+pub fn compute_p2wsh_sighash_with_type(
+    tx: &Transaction,
+    input_index: usize,
+    witness_script: &Script,
+    amount: u64,
+    sighash_type: EcdsaSighashType,
+) -> [u8; 32] {
+    let mut cache = SighashCache::new(tx);
+
+    let sighash = cache
+        .p2wsh_signature_hash(input_index, witness_script, Amount::from_sat(amount), sighash_type)
+        .unwrap();
+
+    sighash.to_byte_array()
+}
+
+/// The BIP 341 SIGHASH_DEFAULT digest for spending input `input_index`
+/// through the tapscript leaf `leaf_script`. Taproot signs every spent
+/// output, so `prevouts` lists them all in input order.
+pub fn compute_tapscript_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    leaf_script: &Script,
+) -> [u8; 32] {
+    let mut cache = SighashCache::new(tx);
+    let leaf_hash = TapLeafHash::from_script(leaf_script, LeafVersion::TapScript);
+
+    let sighash = cache
+        .taproot_script_spend_signature_hash(input_index, &Prevouts::All(prevouts), leaf_hash, TapSighashType::Default)
+        .unwrap();
+
+    sighash.to_byte_array()
+}
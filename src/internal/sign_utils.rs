@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+#[cfg(not(target_arch = "wasm32"))]
 use internal::bitcoind_client::BitcoindClient;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::Hash;
@@ -18,7 +19,10 @@ use bitcoin::blockdata::opcodes::all as opcodes;
 use bitcoin::{PubkeyHash};
 use bitcoin::{Network};
 use bitcoin::consensus::encode::serialize_hex;
+use internal::errors::ChannelError;
 use internal::hex_utils;
+use internal::key_utils::{sort_funding_keys, verify_commitment_signature};
+use internal::sighash_utils::{compute_p2wsh_sighash, compute_p2wsh_sighash_with_type};
 use bitcoin::consensus::{encode};
 use bitcoin::hash_types::Txid;
 use std::env;
@@ -38,11 +42,9 @@ pub fn sign_funding_transaction(tx: Transaction,
     let funding_amount = 5_000_000;
     let txid_index = 0;
 
-    // Prepare the redeem script for signing (e.g., P2PKH or P2WPKH)
-    let redeem_script =
-        two_of_two_multisig_witness_script(
-            &our_funding_public_key,
-            &counterparty_funding_public_key);
+    // Prepare the redeem script for signing, with the keys in BOLT 3 order
+    let (first_key, second_key) = sort_funding_keys(our_funding_public_key, counterparty_funding_public_key);
+    let redeem_script = two_of_two_multisig_witness_script(&first_key, &second_key);
 
     let our_signature = generate_p2wsh_signature(
          tx.clone(), 
@@ -67,8 +69,8 @@ pub fn sign_funding_transaction(tx: Transaction,
     let mut counterparty_signature_der = counterparty_signature.serialize_der().to_vec();
     counterparty_signature_der.push(EcdsaSighashType::All as u8);
 
-    // Determine signature order based on pubkey comparison
-    let our_sig_first = our_funding_public_key.inner.serialize()[..] > counterparty_funding_public_key.inner.serialize()[..];
+    // Signatures go in the same order as the keys in the script
+    let our_sig_first = first_key == our_funding_public_key;
 
     // Add the signature and public key to the witness
     let mut signed_tx = tx.clone();
@@ -104,11 +106,26 @@ pub fn generate_p2wsh_signature(
 
     let message =
         generate_p2wsh_message(transaction, input_idx, witness_script, value, sighash_type);
-    let signature = secp.sign_ecdsa(&message, &private_key);
 
-    signature
+    secp.sign_ecdsa(&message, &private_key)
 }
 
+/// Check `signature` against the SIGHASH_ALL digest for spending the P2WSH
+/// output locked to `witness_script` from input `input_idx`.
+pub fn verify_p2wsh_signature(
+    transaction: &Transaction,
+    input_idx: usize,
+    witness_script: &ScriptBuf,
+    value: u64,
+    signature: &Signature,
+    public_key: &PublicKey,
+) -> bool {
+    let sighash = compute_p2wsh_sighash(transaction, input_idx, witness_script, value);
+
+    verify_commitment_signature(signature, public_key, &sighash)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn sign_raw_transaction(bitcoind: BitcoindClient,
                                 tx: Transaction) -> Transaction {
 
@@ -133,17 +150,93 @@ fn generate_p2wsh_message(
     value: u64,
     sighash_type: EcdsaSighashType,
 ) -> Message {
+    let sighash =
+        compute_p2wsh_sighash_with_type(&transaction, input_idx, witness_script, value, sighash_type);
+
+    Message::from_digest(sighash)
+}
+
+/// Sign input `index` of `tx` as a P2WPKH wallet input worth `value`, and
+/// attach the `<sig> <pubkey>` witness.
+pub fn sign_p2wpkh_input(tx: &mut Transaction, index: usize, value: Amount, private_key: &SecretKey) {
     let secp = Secp256k1::new();
+    let public_key = PublicKey::new(private_key.public_key(&secp));
+    // BIP 143 signs P2WPKH spends with the P2PKH script as the script code
+    let script_code = ScriptBuf::new_p2pkh(&public_key.pubkey_hash());
+    let signature =
+        generate_p2wsh_signature(tx.clone(), index, &script_code, value.to_sat(), EcdsaSighashType::All, *private_key);
+
+    let mut signature_der = signature.serialize_der().to_vec();
+    signature_der.push(EcdsaSighashType::All as u8);
+    tx.input[index].witness = Witness::from_slice(&[signature_der, public_key.to_bytes()]);
+}
+
+/// Sighash type the counterparty signs second-stage HTLC transactions with.
+///
+/// With `option_anchors` the signature only commits to its own input and
+/// output, so the broadcaster can attach extra inputs and outputs to pay fees.
+pub fn counterparty_htlc_sighash_type(anchors: bool) -> EcdsaSighashType {
+    if anchors {
+        EcdsaSighashType::SinglePlusAnyoneCanPay
+    } else {
+        EcdsaSighashType::All
+    }
+}
+
+/// The SIGHASH_ALL digest each funding key must sign for the commitment's
+/// funding input, for signing the commitment outside this crate.
+pub fn commitment_sighash_to_sign(
+    commitment_tx: &Transaction,
+    funding_script: &ScriptBuf,
+    funding_amount: u64,
+) -> [u8; 32] {
+    compute_p2wsh_sighash(commitment_tx, 0, funding_script, funding_amount)
+}
+
+/// Attach the 2-of-2 funding witness to a commitment transaction, ordering the
+/// signatures to match the order of the keys in `funding_script`. Fails if
+/// either key is not in the script.
+pub fn finalize_commitment(
+    commitment_tx: Transaction,
+    funding_script: &ScriptBuf,
+    our_funding_pubkey: &PublicKey,
+    our_signature: &Signature,
+    counterparty_funding_pubkey: &PublicKey,
+    counterparty_signature: &Signature,
+) -> Result<Transaction, ChannelError> {
+    let mut our_signature_der = our_signature.serialize_der().to_vec();
+    our_signature_der.push(EcdsaSighashType::All as u8);
 
-    let mut cache = SighashCache::new(&transaction);
+    let mut counterparty_signature_der = counterparty_signature.serialize_der().to_vec();
+    counterparty_signature_der.push(EcdsaSighashType::All as u8);
 
-    let amount = Amount::from_sat(value);
+    let funding_keys: Vec<Vec<u8>> = funding_script
+        .instructions()
+        .filter_map(|instruction| instruction.ok()?.push_bytes().map(|bytes| bytes.as_bytes().to_vec()))
+        .collect();
+    let position = |pubkey: &PublicKey| {
+        funding_keys
+            .iter()
+            .position(|key| *key == pubkey.to_bytes())
+            .ok_or(ChannelError::FundingKeyNotInScript(*pubkey))
+    };
+    let our_position = position(our_funding_pubkey)?;
+    let counterparty_position = position(counterparty_funding_pubkey)?;
+
+    let mut signed_tx = commitment_tx;
 
-    let sighash = cache
-        .p2wsh_signature_hash(input_idx, &witness_script, amount, sighash_type)
-        .unwrap();
+    // First push empty element for NULLDUMMY compliance
+    signed_tx.input[0].witness.push(Vec::new());
+
+    if our_position < counterparty_position {
+        signed_tx.input[0].witness.push(our_signature_der);
+        signed_tx.input[0].witness.push(counterparty_signature_der);
+    } else {
+        signed_tx.input[0].witness.push(counterparty_signature_der);
+        signed_tx.input[0].witness.push(our_signature_der);
+    }
 
-    let message = Message::from_digest_slice(&sighash[..]).unwrap();
+    signed_tx.input[0].witness.push(funding_script.as_bytes());
 
-    message
+    Ok(signed_tx)
 }
@@ -0,0 +1,131 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::ecdsa::Signature as BitcoinSignature;
+use bitcoin::script::{Script, ScriptBuf};
+use bitcoin::{Transaction, Witness};
+use internal::errors::ChannelError;
+
+/// Largest transaction weight Bitcoin Core will relay.
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// Largest P2WSH witness script Bitcoin Core will relay.
+pub const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+
+/// Run every standardness check against `tx` and return all the violations found.
+///
+/// `witness_stacks` holds the witness for each input, so a transaction can be
+/// checked before its witnesses have been attached.
+pub fn check_standardness(tx: &Transaction, witness_stacks: &[Witness]) -> Result<(), Vec<ChannelError>> {
+    let mut errors = Vec::new();
+
+    if let Err(e) = check_version(tx) {
+        errors.push(e);
+    }
+
+    if let Err(e) = check_weight(tx, witness_stacks) {
+        errors.push(e);
+    }
+
+    errors.extend(check_outputs(tx));
+
+    for (input, witness) in witness_stacks.iter().enumerate() {
+        errors.extend(check_witness(input, witness));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn check_version(tx: &Transaction) -> Result<(), ChannelError> {
+    match tx.version.0 {
+        1 | 2 => Ok(()),
+        version => Err(ChannelError::NonStandardVersion(version)),
+    }
+}
+
+pub fn check_weight(tx: &Transaction, witness_stacks: &[Witness]) -> Result<(), ChannelError> {
+    let mut tx = tx.clone();
+    for (txin, witness) in tx.input.iter_mut().zip(witness_stacks) {
+        txin.witness = witness.clone();
+    }
+
+    let weight = tx.weight().to_wu();
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        return Err(ChannelError::WeightTooHigh { weight });
+    }
+    Ok(())
+}
+
+pub fn check_outputs(tx: &Transaction) -> Vec<ChannelError> {
+    let mut errors = Vec::new();
+
+    for (index, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+
+        if script.is_op_return() {
+            continue;
+        }
+
+        if !is_standard_output_script(script) {
+            errors.push(ChannelError::NonStandardOutputScript { index });
+        }
+
+        let dust_limit = script.minimal_non_dust().to_sat();
+        if output.value.to_sat() < dust_limit {
+            errors.push(ChannelError::DustOutput {
+                index,
+                value: output.value.to_sat(),
+                dust_limit,
+            });
+        }
+    }
+
+    errors
+}
+
+pub fn check_witness(input: usize, witness: &Witness) -> Vec<ChannelError> {
+    let mut errors = Vec::new();
+    let elements: Vec<&[u8]> = witness.iter().collect();
+
+    // A P2WPKH spend is just <signature> <pubkey>; anything else ends with
+    // the witness script.
+    let is_p2wpkh_spend = elements.len() == 2 && elements[1].len() == 33;
+    let (stack, witness_script) = match elements.split_last() {
+        Some((last, rest)) if !is_p2wpkh_spend => (rest, Some(Script::from_bytes(last))),
+        _ => (&elements[..], None),
+    };
+
+    if let Some(script) = witness_script {
+        if script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            errors.push(ChannelError::ScriptTooLarge { input, size: script.len() });
+        }
+
+        if script.instructions_minimal().any(|instruction| instruction.is_err()) {
+            errors.push(ChannelError::NonMinimalPush { input });
+        }
+    }
+
+    for (element, bytes) in stack.iter().enumerate() {
+        if let Ok(sig) = BitcoinSignature::from_slice(bytes) {
+            let mut normalized = sig.signature;
+            normalized.normalize_s();
+            if normalized != sig.signature {
+                errors.push(ChannelError::HighSSignature { input, element });
+            }
+        }
+    }
+
+    errors
+}
+
+fn is_standard_output_script(script: &Script) -> bool {
+    script.is_p2pkh()
+        || script.is_p2sh()
+        || script.is_p2wpkh()
+        || script.is_p2wsh()
+        || script.is_p2tr()
+        || script.is_multisig()
+}
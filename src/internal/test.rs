@@ -0,0 +1,1878 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{build_commitment_transaction, two_of_two_multisig_witness_script};
+use crate::internal;
+use bitcoin::hash_types::Txid;
+use bitcoin::script::{Builder, ScriptBuf};
+use bitcoin::blockdata::opcodes::all as opcodes;
+use bitcoin::{Amount, OutPoint, Sequence, Transaction, TxIn, Witness};
+use crate::exercises_appendix::tx_exercises::{build_commitment_input, build_commitment_locktime};
+use internal::channel_utils::{channel_id_from_funding, channel_id_v2};
+use internal::channel::{
+    build_commitment_full, Channel, ChannelParams, CommitmentBasepoints, CommitmentTransactionBuilder,
+};
+use internal::debug_utils::{channel_spend_tree_json, has_obscured_commitment_number};
+use bitcoin::sighash::EcdsaSighashType;
+use internal::errors::{ChannelError, Error, ExerciseError};
+use internal::fee_utils::{
+    commit_tx_fee, fee_for_weight, fee_rate_from_per_kw, feerate_per_kw, htlc_success_fee, htlc_timeout_fee, per_kw_to_sat_per_vb,
+    sat_per_vb_to_per_kw, ConfirmationTarget, FeeEstimator, FixedFeeEstimator, HtlcFeeMode, MempoolSpaceFeeEstimator,
+    COMMITMENT_TX_BASE_WEIGHT, FEERATE_FLOOR_PER_KW,
+};
+use internal::keys::{
+    ChannelKeyManager, ChannelKeys, ChannelPublicKeys, DelayedPaymentPubkey, FundingPubkey, HtlcPubkey, RevocationPubkey,
+};
+use internal::shachain::{generate_from_seed, MAX_SHACHAIN_INDEX};
+use crate::exercises::exercises::generate_revocation_pubkey;
+use internal::htlc_utils::{extract_preimage_from_witness, is_htlc_trimmed, Htlc, max_feerate_for_htlc};
+use internal::payments::{PaymentHash, PaymentHash160, PaymentPreimage, PaymentSecret};
+use internal::script_utils::{build_anchor_output_script, build_anchor_to_remote_script, build_htlc_offerer_witness_script};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+use internal::key_utils::{
+    complete_adaptor_signature, create_adaptor_signature, extract_adaptor_secret, verify_adaptor_signature,
+    derive_public_key, hash_pubkeys, pubkey_from_private_key, pubkey_from_secret, sort_funding_keys, secp256k1_private_key, secp256k1pubkey_from_private_key,
+    verify_commitment_signature, verify_counterparty_commitment,
+};
+use internal::sign_utils::{commitment_sighash_to_sign, finalize_commitment};
+use internal::script_utils::{p2wpkh_output_script, validate_shutdown_script};
+use internal::errors::ShutdownScriptError;
+use internal::tx_utils::{
+    build_output, commitment_number_obscure_factor, dummy_funding_txin, obscured_commitment_transaction_numbers,
+    recover_commitment_number, sort_outputs_bolt3, strip_cltv_expiries, to_self_delay_sequence, tx_from_hex, tx_to_hex,
+    verify_min_feerate, DUMMY_FUNDING_TXID,
+};
+use internal::units::MilliSat;
+use bitcoin::relative;
+use internal::witness_utils::validate_p2wsh_witness;
+use internal::watchtower::{build_breach_remedy, penalty_tx_fee, RevokedOutput, Watchtower};
+use crate::exercises::exercises::create_justice_blob;
+use lightning::chain::chaininterface::BroadcasterInterface;
+use std::cell::RefCell;
+use internal::chain_source::{parse_tx_status, parse_utxos, script_hash, ChainSource, ChainUtxo};
+use internal::errors::ChainSourceError;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use internal::standardness::{check_standardness, MAX_STANDARD_P2WSH_SCRIPT_SIZE};
+use internal::errors::MusigError;
+use internal::errors::WireError;
+use internal::tlv::{
+    decode_tu64, encode_tu64, read_bigsize, read_tlv_stream, write_bigsize, write_tlv_stream, TlvRecord,
+};
+use internal::wire::{
+    AcceptChannel, ChannelReady, CommitmentSigned, FundingCreated, FundingSigned, Message as WireMessage, NetAddress,
+    NodeAnnouncement, Ping, Reader, RevokeAndAck, TxAddInput, TxAddOutput, TxComplete, UpdateAddHtlc, UpdateFulfillHtlc,
+};
+use internal::wire::{ClosingSigned, FeeRange, Shutdown};
+use internal::onion_utils::{OnionPacket, ONION_PACKET_LEN};
+use internal::gossip_utils::verify_node_announcement;
+use internal::wire::{encode_features, STATIC_REMOTE_KEY_REQUIRED};
+use internal::errors::GossipError;
+use internal::hints::{hints_help, ExerciseCheck};
+use internal::grading::{completion_percent, parse_test_output, test_filter, ExerciseResult, EXERCISES_TEST_MODULE};
+use internal::errors::PaymentError;
+use internal::graph::{NetworkGraph, STALE_CHANNEL_AGE};
+use internal::wire::{ChannelAnnouncement, ChannelUpdate, ShortChannelId, CHANNEL_FLAGS_DIRECTION, MESSAGE_FLAGS_MUST_BE_ONE};
+use bitcoin::constants::ChainHash;
+use bitcoin::secp256k1::SecretKey;
+use internal::musig_utils::{
+    aggregate_nonces, aggregate_partial_signatures, generate_nonce, partial_sign, verify_partial_signature,
+    KeyAggContext,
+};
+
+#[test]
+fn test_check_standardness_reports_all_violations() {
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+    // 100 sats is below the 294 sat dust limit of the P2WPKH to_remote output
+    let tx = build_commitment_transaction(
+        dummy_funding_txin(),
+        &revocation_pubkey,
+        &to_local_delayed_pubkey,
+        remote_pubkey,
+        144,
+        Amount::from_sat(4_998_900),
+        Amount::from_sat(100),
+        true,
+        253,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let mut builder = Builder::new();
+    for _ in 0..=MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+        builder = builder.push_opcode(opcodes::OP_NOP);
+    }
+    let oversized_script = builder.into_script();
+
+    let mut witness = Witness::new();
+    witness.push(Vec::new());
+    witness.push(oversized_script.as_bytes());
+
+    let errors = check_standardness(&tx, &[witness]).unwrap_err();
+
+    assert_eq!(errors.len(), 2, "unexpected violations: {:?}", errors);
+    assert!(errors.iter().any(|e| matches!(e, ChannelError::DustOutput { value: 100, dust_limit: 294, .. })));
+    assert!(errors.iter().any(|e| matches!(
+        e,
+        ChannelError::ScriptTooLarge { input: 0, size } if *size == MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1
+    )));
+}
+
+#[test]
+fn test_check_standardness_accepts_commitment() {
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+    let tx = build_commitment_transaction(
+        dummy_funding_txin(),
+        &revocation_pubkey,
+        &to_local_delayed_pubkey,
+        remote_pubkey,
+        144,
+        Amount::from_sat(3_998_500),
+        Amount::from_sat(1_000_500),
+        true,
+        253,
+        0,
+        0,
+    )
+    .unwrap();
+
+    assert_eq!(check_standardness(&tx, &[]), Ok(()));
+}
+
+#[test]
+fn test_errors_convert_into_the_crate_error() {
+    fn commitment_then_standardness(to_self_delay: i64, remote_amount: u64) -> Result<(), Error> {
+        let tx = build_commitment_transaction(
+            dummy_funding_txin(),
+            &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+            &DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32])),
+            pubkey_from_private_key(&[0x03; 32]),
+            to_self_delay,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(remote_amount),
+            true,
+            253,
+            0,
+            0,
+        )?;
+        check_standardness(&tx, &[]).map_err(|mut errors| errors.remove(0))?;
+        Ok(())
+    }
+
+    assert_eq!(commitment_then_standardness(144, 1_000_500), Ok(()));
+
+    let err = commitment_then_standardness(0, 1_000_500).unwrap_err();
+    assert_eq!(err, Error::Exercise(ExerciseError::InvalidToSelfDelay(0)));
+    assert_eq!(err.to_string(), ExerciseError::InvalidToSelfDelay(0).to_string());
+
+    let err = commitment_then_standardness(144, 100).unwrap_err();
+    assert!(matches!(err, Error::Channel(ChannelError::DustOutput { value: 100, .. })));
+    assert_eq!(err.to_string(), "output 0 pays 100 sats, below its dust limit of 294 sats");
+}
+
+#[test]
+fn test_has_obscured_commitment_number() {
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+    let obscure_factor: u64 = 239765233721135;
+    let commitment_number: u64 = (1 << 48) - 11;
+
+    let commitment = build_commitment_transaction(
+        dummy_funding_txin(),
+        &revocation_pubkey,
+        &to_local_delayed_pubkey,
+        remote_pubkey,
+        144,
+        Amount::from_sat(3_998_500),
+        Amount::from_sat(1_000_500),
+        true,
+        253,
+        obscure_factor,
+        commitment_number,
+    )
+    .unwrap();
+
+    // Exercise 7 obscures both halves the way the appendix exercises do
+    let obscured_input =
+        build_commitment_input(dummy_funding_txin().previous_output, &obscure_factor, &commitment_number);
+    assert_eq!(commitment.input[0].sequence, obscured_input.sequence);
+    assert_eq!(commitment.lock_time, build_commitment_locktime(&obscure_factor, &commitment_number));
+    assert!(has_obscured_commitment_number(&commitment));
+
+    // the funding input's sequence is obscured, but the locktime is still zero
+    let mut unobscured = commitment.clone();
+    unobscured.lock_time = bitcoin::absolute::LockTime::ZERO;
+    assert!(!has_obscured_commitment_number(&unobscured));
+}
+
+#[test]
+fn test_commitment_sighash_to_sign_external_signing() {
+    let secp = Secp256k1::new();
+
+    let alice_funding_privkey = secp256k1_private_key(&[0x01; 32]);
+    let alice_funding_pubkey = pubkey_from_private_key(&[0x01; 32]);
+    let bob_funding_privkey = secp256k1_private_key(&[0x02; 32]);
+    let bob_funding_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x11; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x12; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x13; 32]);
+
+    let funding_amount = Amount::from_sat(5_000_000);
+    let funding_script = two_of_two_multisig_witness_script(&alice_funding_pubkey, &bob_funding_pubkey);
+
+    let commitment_tx = build_commitment_transaction(
+        dummy_funding_txin(),
+        &revocation_pubkey,
+        &to_local_delayed_pubkey,
+        remote_pubkey,
+        144,
+        Amount::from_sat(3_998_500),
+        Amount::from_sat(1_000_500),
+        true,
+        253,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let sighash = commitment_sighash_to_sign(&commitment_tx, &funding_script, funding_amount.to_sat());
+
+    // sign "elsewhere", with nothing but the digest
+    let message = Message::from_digest(sighash);
+    let alice_signature = secp.sign_ecdsa(&message, &alice_funding_privkey);
+    let bob_signature = secp.sign_ecdsa(&message, &bob_funding_privkey);
+
+    // a key the script doesn't have can't be given a position in the witness
+    let stranger_pubkey = pubkey_from_private_key(&[0x03; 32]);
+    assert_eq!(
+        finalize_commitment(
+            commitment_tx.clone(),
+            &funding_script,
+            &stranger_pubkey,
+            &bob_signature,
+            &alice_funding_pubkey,
+            &alice_signature,
+        ),
+        Err(ChannelError::FundingKeyNotInScript(stranger_pubkey))
+    );
+
+    // the argument order must not matter: signatures follow the script's key order
+    let signed_tx = finalize_commitment(
+        commitment_tx,
+        &funding_script,
+        &bob_funding_pubkey,
+        &bob_signature,
+        &alice_funding_pubkey,
+        &alice_signature,
+    )
+    .unwrap();
+
+    let funding_output = build_output(funding_amount, funding_script.to_p2wsh());
+    signed_tx
+        .verify(|_| Some(funding_output.clone()))
+        .expect("finalized commitment should satisfy the funding script");
+}
+
+#[test]
+fn test_payment_preimage_hash() {
+    let preimage = PaymentPreimage([0x42; 32]);
+
+    let sha: [u8; 32] = Sha256::digest(preimage.0).into();
+    let expected: [u8; 20] = Ripemd160::digest(sha).into();
+
+    assert_eq!(preimage.hash(), PaymentHash(sha));
+    assert_eq!(preimage.hash().hash160(), PaymentHash160(expected));
+
+    let payment_hash = preimage.hash160();
+    assert_eq!(payment_hash, PaymentHash160(expected));
+
+    let htlc_script = build_htlc_offerer_witness_script(
+        &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+        &payment_hash,
+    );
+
+    assert!(htlc_script
+        .as_bytes()
+        .windows(20)
+        .any(|window| window == expected));
+}
+
+#[test]
+fn test_payment_types_from_slice() {
+    let preimage = PaymentPreimage::from_slice(&[0x42; 32]).unwrap();
+    assert_eq!(PaymentHash::from_slice(&preimage.hash().0), Ok(preimage.hash()));
+    assert_eq!(PaymentHash160::from_slice(&preimage.hash160().0), Ok(preimage.hash160()));
+    assert_eq!(PaymentSecret::from_slice(&[0x11; 32]), Ok(PaymentSecret([0x11; 32])));
+
+    // A hash160 is not a payment hash, and a hash is not a preimage.
+    assert_eq!(
+        PaymentHash::from_slice(&preimage.hash160().0),
+        Err(PaymentError::InvalidLength { expected: 32, actual: 20 })
+    );
+    assert_eq!(
+        PaymentHash160::from_slice(&preimage.hash().0),
+        Err(PaymentError::InvalidLength { expected: 20, actual: 32 })
+    );
+    assert_eq!(PaymentPreimage::from_slice(&[0x42; 33]), Err(PaymentError::InvalidLength { expected: 32, actual: 33 }));
+
+    assert_ne!(PaymentPreimage::random(), PaymentPreimage::random());
+}
+
+#[test]
+fn test_penalty_tx_fee_scales_with_swept_inputs() {
+    let feerate_per_kw = 2_500;
+
+    let to_local_only = penalty_tx_fee(1, 0, feerate_per_kw);
+    let one_htlc = penalty_tx_fee(1, 1, feerate_per_kw);
+    let two_htlcs = penalty_tx_fee(1, 2, feerate_per_kw);
+
+    assert!(to_local_only < one_htlc && one_htlc < two_htlcs);
+
+    // every extra HTLC adds the same input + witness weight
+    let per_htlc = two_htlcs - one_htlc;
+    assert!((per_htlc.to_sat() as i64 - (one_htlc - to_local_only).to_sat() as i64).abs() <= 1);
+
+    let txid = dummy_funding_txin().previous_output.txid;
+    let revoked_outputs = [
+        RevokedOutput { outpoint: OutPoint::new(txid, 0), value: Amount::from_sat(3_000_000), is_htlc: false },
+        RevokedOutput { outpoint: OutPoint::new(txid, 1), value: Amount::from_sat(400_000), is_htlc: true },
+        RevokedOutput { outpoint: OutPoint::new(txid, 2), value: Amount::from_sat(300_000), is_htlc: true },
+    ];
+
+    let destination = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+    let penalty_tx = build_breach_remedy(&revoked_outputs, destination.clone(), feerate_per_kw).unwrap();
+
+    assert_eq!(penalty_tx.input.len(), 3);
+    assert_eq!(penalty_tx.output[0].value, Amount::from_sat(3_700_000) - two_htlcs);
+
+    // a revoked output worth less than the fee to sweep it can't be swept
+    let dust = [RevokedOutput { outpoint: OutPoint::new(txid, 0), value: Amount::from_sat(500), is_htlc: false }];
+    assert_eq!(
+        build_breach_remedy(&dust, destination, feerate_per_kw),
+        Err(ExerciseError::InsufficientFunds { needed: to_local_only.to_sat(), available: 500 })
+    );
+}
+
+/// Keeps what it was asked to broadcast instead of sending it anywhere.
+#[derive(Default)]
+struct RecordingBroadcaster(RefCell<Vec<Transaction>>);
+
+impl BroadcasterInterface for RecordingBroadcaster {
+    fn broadcast_transactions(&self, txs: &[&Transaction]) {
+        self.0.borrow_mut().extend(txs.iter().map(|tx| (*tx).clone()));
+    }
+}
+
+#[test]
+fn test_watchtower_broadcasts_justice_for_breaches_only() {
+    let breach = |commitment_number: u32| {
+        let mut commitment = build_commitment_transaction(
+            dummy_funding_txin(),
+            &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+            &DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32])),
+            pubkey_from_private_key(&[0x03; 32]),
+            144,
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(2_000_000),
+            true,
+            253,
+            0,
+            0,
+        )
+        .unwrap();
+        commitment.lock_time = bitcoin::absolute::LockTime::from_consensus(0x2000_0000 | commitment_number);
+        commitment
+    };
+    let justice = |breach: &Transaction| {
+        let revoked_outputs = [RevokedOutput {
+            outpoint: OutPoint::new(breach.compute_txid(), 0),
+            value: breach.output[0].value,
+            is_htlc: false,
+        }];
+        let destination = p2wpkh_output_script(pubkey_from_private_key(&[0x04; 32]));
+        build_breach_remedy(&revoked_outputs, destination, 253).unwrap()
+    };
+
+    let mut tower = Watchtower::new();
+    for commitment_number in 0..3 {
+        let revoked = breach(commitment_number);
+        tower.upload(create_justice_blob(&revoked.compute_txid(), &justice(&revoked)));
+    }
+    assert_eq!(tower.len(), 3);
+
+    // a block with the latest commitment, which was never revoked
+    let broadcaster = RecordingBroadcaster::default();
+    let mut block = bitcoin::constants::genesis_block(bitcoin::Network::Regtest);
+    block.txdata = vec![breach(3)];
+    assert!(tower.block_connected(&block, &broadcaster).is_empty());
+    assert!(broadcaster.0.borrow().is_empty());
+
+    block.txdata = vec![breach(3), breach(1)];
+    assert_eq!(tower.block_connected(&block, &broadcaster), vec![justice(&breach(1))]);
+    assert_eq!(*broadcaster.0.borrow(), vec![justice(&breach(1))]);
+    assert_eq!(tower.len(), 2);
+}
+
+#[test]
+fn test_esplora_responses_parse() {
+    // the example from the Electrum protocol docs, for the genesis block's address
+    let script = ScriptBuf::from_hex("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+    assert_eq!(script_hash(&script), "8b01df4e368ea28f8dc0423bcf7a4923e3a12d307c875e47a0cfbf90b5c39161");
+
+    let txid = dummy_funding_txin().previous_output.txid;
+    let utxos = serde_json::json!([
+        { "txid": txid.to_string(), "vout": 1, "value": 5_000_000,
+          "status": { "confirmed": true, "block_height": 1_500_000, "block_hash": "00" } },
+        { "txid": txid.to_string(), "vout": 2, "value": 21_000, "status": { "confirmed": false } },
+    ]);
+    assert_eq!(
+        parse_utxos(&utxos),
+        Ok(vec![
+            ChainUtxo {
+                outpoint: OutPoint::new(txid, 1),
+                value: Amount::from_sat(5_000_000),
+                confirmation_height: Some(1_500_000),
+            },
+            ChainUtxo { outpoint: OutPoint::new(txid, 2), value: Amount::from_sat(21_000), confirmation_height: None },
+        ])
+    );
+
+    assert!(parse_tx_status(&serde_json::json!({ "confirmed": true })).is_err());
+    assert!(parse_utxos(&serde_json::json!({ "error": "not found" })).is_err());
+}
+
+/// A chain with one transaction, confirmed at height 100.
+struct FixedChain {
+    best_height: u32,
+    txid: Txid,
+}
+
+impl ChainSource for FixedChain {
+    fn best_height(&self) -> Result<u32, ChainSourceError> {
+        Ok(self.best_height)
+    }
+
+    fn get_transaction(&self, txid: &Txid) -> Result<Option<Transaction>, ChainSourceError> {
+        Ok(None)
+    }
+
+    fn confirmation_height(&self, txid: &Txid) -> Result<Option<u32>, ChainSourceError> {
+        Ok((*txid == self.txid).then_some(100))
+    }
+
+    fn unspent_outputs(&self, script_pubkey: &bitcoin::Script) -> Result<Vec<ChainUtxo>, ChainSourceError> {
+        Ok(Vec::new())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, ChainSourceError> {
+        Err(ChainSourceError::Rejected("read-only".to_string()))
+    }
+}
+
+#[test]
+fn test_chain_source_counts_confirmations() {
+    let txid = dummy_funding_txin().previous_output.txid;
+    let chain = FixedChain { best_height: 102, txid };
+
+    assert_eq!(chain.confirmations(&txid), Ok(3));
+    assert_eq!(chain.confirmations(&OutPoint::null().txid), Ok(0));
+    assert_eq!(chain.cltv_expiry_from_now(40), Ok(142));
+}
+
+#[test]
+fn test_feerate_conversions() {
+    assert_eq!(sat_per_vb_to_per_kw(1.0), 250);
+    assert_eq!(sat_per_vb_to_per_kw(10.5), 2_625);
+    // rounds up, never under the quoted rate
+    assert_eq!(sat_per_vb_to_per_kw(1.001), 251);
+    assert_eq!(per_kw_to_sat_per_vb(2_500), 10.0);
+
+    let fee_rate = bitcoin::FeeRate::from_sat_per_vb(4).unwrap();
+    assert_eq!(feerate_per_kw(fee_rate), 1_000);
+    assert_eq!(fee_rate_from_per_kw(1_000), fee_rate);
+
+    // the same feerate gives the same fee in every unit
+    let weight = bitcoin::Weight::from_wu(COMMITMENT_TX_BASE_WEIGHT);
+    assert_eq!(fee_rate.fee_wu(weight), Some(fee_for_weight(feerate_per_kw(fee_rate), weight.to_wu())));
+}
+
+#[test]
+fn test_fee_estimators() {
+    assert_eq!(FixedFeeEstimator(2_500).feerate_per_kw(ConfirmationTarget::Urgent), 2_500);
+    assert_eq!(FixedFeeEstimator(100).feerate_per_kw(ConfirmationTarget::Background), FEERATE_FLOOR_PER_KW);
+
+    let fees = serde_json::json!({
+        "fastestFee": 12, "halfHourFee": 8.5, "hourFee": 6, "economyFee": 1, "minimumFee": 1
+    });
+    let estimator = MempoolSpaceFeeEstimator::from_json(&fees).unwrap();
+    assert_eq!(estimator.feerate_per_kw(ConfirmationTarget::Urgent), 3_000);
+    assert_eq!(estimator.feerate_per_kw(ConfirmationTarget::Normal), 2_125);
+    assert_eq!(estimator.feerate_per_kw(ConfirmationTarget::Background), FEERATE_FLOOR_PER_KW);
+
+    assert!(MempoolSpaceFeeEstimator::from_json(&serde_json::json!({ "fastestFee": 12 })).is_err());
+}
+
+#[test]
+fn test_max_feerate_for_htlc() {
+    let htlc_amount = 5_000;
+    let dust_limit = 546;
+
+    for (offered, mode) in [
+        (true, HtlcFeeMode::Legacy),
+        (true, HtlcFeeMode::Anchors),
+        (false, HtlcFeeMode::Legacy),
+        (false, HtlcFeeMode::Anchors),
+    ] {
+        let (htlc_amount, dust_limit) = (Amount::from_sat(htlc_amount), Amount::from_sat(dust_limit));
+        let feerate = max_feerate_for_htlc(htlc_amount, dust_limit, offered, mode.anchors()).unwrap();
+
+        assert!(!is_htlc_trimmed(htlc_amount, dust_limit, feerate, offered, mode));
+        assert!(is_htlc_trimmed(htlc_amount, dust_limit, feerate + 1, offered, mode));
+    }
+
+    // zero-fee HTLC transactions leave only the dust limit to clear
+    for offered in [true, false] {
+        let mode = HtlcFeeMode::ZeroFeeAnchors;
+        assert!(!is_htlc_trimmed(Amount::from_sat(dust_limit), Amount::from_sat(dust_limit), 100_000, offered, mode));
+        assert!(is_htlc_trimmed(Amount::from_sat(dust_limit - 1), Amount::from_sat(dust_limit), 0, offered, mode));
+    }
+
+    // below the dust limit no feerate is low enough, not even 0
+    let (htlc_amount, dust_limit) = (Amount::from_sat(dust_limit - 1), Amount::from_sat(dust_limit));
+    for offered in [true, false] {
+        assert_eq!(max_feerate_for_htlc(htlc_amount, dust_limit, offered, false), None);
+        assert!(is_htlc_trimmed(htlc_amount, dust_limit, 0, offered, HtlcFeeMode::Legacy));
+    }
+
+    // right at the dust limit it's kept while the fee rounds down to 0
+    let feerate = max_feerate_for_htlc(dust_limit, dust_limit, true, false);
+    assert_eq!(feerate, Some(1));
+    assert!(!is_htlc_trimmed(dust_limit, dust_limit, 1, true, HtlcFeeMode::Legacy));
+    assert!(is_htlc_trimmed(dust_limit, dust_limit, 2, true, HtlcFeeMode::Legacy));
+}
+
+#[test]
+fn test_validate_p2wsh_witness() {
+    let alice_funding_pubkey = pubkey_from_private_key(&[0x01; 32]);
+    let bob_funding_pubkey = pubkey_from_private_key(&[0x02; 32]);
+    let funding_script = two_of_two_multisig_witness_script(&alice_funding_pubkey, &bob_funding_pubkey);
+    let output_script = funding_script.to_p2wsh();
+
+    let mut witness = Witness::new();
+    witness.push(Vec::new());
+    witness.push(funding_script.as_bytes());
+    assert_eq!(validate_p2wsh_witness(&witness, &output_script), Ok(()));
+
+    // keys swapped: a valid-looking script, but not the one the output commits to
+    let swapped_script = two_of_two_multisig_witness_script(&bob_funding_pubkey, &alice_funding_pubkey);
+    let mut witness = Witness::new();
+    witness.push(Vec::new());
+    witness.push(swapped_script.as_bytes());
+    assert_eq!(
+        validate_p2wsh_witness(&witness, &output_script),
+        Err(ChannelError::WitnessScriptMismatch)
+    );
+
+    assert_eq!(
+        validate_p2wsh_witness(&Witness::new(), &output_script),
+        Err(ChannelError::EmptyWitness)
+    );
+}
+
+#[test]
+fn test_channel_snapshot_restore() {
+    let mut channel = Channel::new(MilliSat::from_msat(3_000_000_000), MilliSat::from_msat(2_000_000_000));
+    channel.advance_commitment([0x01; 32]);
+
+    let snapshot = channel.snapshot();
+    let before = channel.clone();
+
+    channel
+        .add_htlc(Htlc {
+            offered: true,
+            amount_msat: MilliSat::from_msat(400_000_000),
+            payment_hash: PaymentPreimage([0x42; 32]).hash160(),
+            cltv_expiry: 500_000,
+        })
+        .unwrap();
+    channel.advance_commitment([0x02; 32]);
+    assert_eq!(channel.commitment_number, 2);
+
+    channel.restore(snapshot);
+
+    assert_eq!(channel.commitment_number, before.commitment_number);
+    assert_eq!(channel.to_local_msat, before.to_local_msat);
+    assert_eq!(channel.to_remote_msat, before.to_remote_msat);
+    assert!(channel.htlcs.is_empty());
+
+    // revealed secrets stay revealed
+    assert_eq!(channel.revealed_secrets, vec![[0x01; 32], [0x02; 32]]);
+}
+
+#[test]
+fn test_to_self_delay_sequence() {
+    let sequence = to_self_delay_sequence(144);
+
+    assert!(sequence.is_relative_lock_time());
+    assert_eq!(
+        sequence.to_relative_lock_time(),
+        Some(relative::LockTime::from_height(144))
+    );
+}
+
+#[test]
+fn test_channel_spend_tree_json() {
+    let channel = Channel::new(MilliSat::from_msat(3_000_000_000), MilliSat::from_msat(2_000_000_000));
+
+    let tree: serde_json::Value = serde_json::from_str(&channel_spend_tree_json(&channel)).unwrap();
+
+    let to_local = tree["outputs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|output| output["output"] == "to_local")
+        .unwrap();
+    let paths: Vec<&str> = to_local["spend_paths"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|path| path["path"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(paths, vec!["revocation", "delayed"]);
+    assert_eq!(to_local["spend_paths"][1]["timelock"]["type"], "relative");
+}
+
+#[test]
+fn test_extract_preimage_from_witness() {
+    let preimage = PaymentPreimage([0x42; 32]);
+    let payment_hash = preimage.hash160();
+    let script = build_htlc_offerer_witness_script(
+        &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+        &payment_hash,
+    );
+    let dummy_sig = vec![0x30; 71];
+
+    let success_witness = Witness::from_slice(&[
+        dummy_sig.clone(),
+        preimage.0.to_vec(),
+        script.to_bytes(),
+    ]);
+    assert_eq!(
+        extract_preimage_from_witness(&success_witness, &payment_hash),
+        Some(preimage)
+    );
+
+    let timeout_witness = Witness::from_slice(&[
+        vec![],
+        dummy_sig.clone(),
+        dummy_sig,
+        vec![],
+        script.to_bytes(),
+    ]);
+    assert_eq!(extract_preimage_from_witness(&timeout_witness, &payment_hash), None);
+}
+
+fn bolt3_pubkey(hex_str: &str) -> bitcoin::PublicKey {
+    hex_str.parse().unwrap()
+}
+
+/// One side's keys in BOLT 3 appendix C. The appendix gives the commitment
+/// keys directly, so only the funding key and payment basepoint are used.
+fn bolt3_channel_pubkeys(funding_pubkey: &str, payment_basepoint: &str) -> ChannelPublicKeys {
+    let payment_basepoint = bolt3_pubkey(payment_basepoint).inner;
+    ChannelPublicKeys {
+        funding_pubkey: bolt3_pubkey(funding_pubkey).inner,
+        payment_basepoint,
+        delayed_payment_basepoint: payment_basepoint,
+        htlc_basepoint: payment_basepoint,
+        revocation_basepoint: payment_basepoint,
+    }
+}
+
+#[test]
+fn test_build_commitment_full_bolt3_five_htlcs() {
+    // BOLT 3 appendix C, "commitment tx with seven outputs untrimmed (maximum feerate)"
+    let funding_outpoint = OutPoint::new(
+        "8984484a580b825b9972d7adb15050b3ab624ccd731946b3eeddb92f4e7ef6be"
+            .parse::<Txid>()
+            .unwrap(),
+        0,
+    );
+    let funding_amount = 10_000_000;
+
+    let localpubkey = HtlcPubkey(bolt3_pubkey("030d417a46946384f88d5f3337267c5e579765875dc4daca813e21734b140639e7"));
+    let remotepubkey = HtlcPubkey(bolt3_pubkey("0394854aa6eab5b2a8122cc726e9dded053a2184d88256816826d6231c068d4a5b"));
+    let keys = ChannelKeys {
+        revocation_pubkey: RevocationPubkey(bolt3_pubkey("0212a140cd0c6539d07cd08dfe09984dec3251ea808b892efeac3ede9402bf2b19")),
+        local_htlc_pubkey: localpubkey,
+        remote_htlc_pubkey: remotepubkey,
+        to_local_delayed_pubkey: DelayedPaymentPubkey(bolt3_pubkey("03fd5960528dc152014952efdb702a88f71e3c1653b2314431701ec77e57fde83c")),
+        remote_pubkey: remotepubkey.0,
+    };
+    let params = ChannelParams {
+        funding_outpoint,
+        to_self_delay: 144,
+        dust_limit: Amount::from_sat(546),
+        feerate_per_kw: 647,
+        htlc_fee_mode: HtlcFeeMode::Legacy,
+        is_funder: true,
+        local_keys: bolt3_channel_pubkeys(
+            "023da092f6980e58d2c037173180e9a465476026ee50f96695963e8efe436f54eb",
+            "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa",
+        ),
+        remote_keys: bolt3_channel_pubkeys(
+            "030e9f7b623d2ccc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c39fa711c1",
+            "032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991",
+        ),
+    };
+
+    let htlc = |offered: bool, amount_msat: u64, cltv_expiry: u32, preimage_byte: u8| Htlc {
+        offered,
+        amount_msat: MilliSat::from_msat(amount_msat),
+        payment_hash: PaymentPreimage([preimage_byte; 32]).hash160(),
+        cltv_expiry,
+    };
+    let htlcs = vec![
+        htlc(false, 1_000_000, 500, 0x00),
+        htlc(false, 2_000_000, 501, 0x01),
+        htlc(true, 2_000_000, 502, 0x02),
+        htlc(true, 3_000_000, 503, 0x03),
+        htlc(false, 4_000_000, 504, 0x04),
+    ];
+
+    let (tx, htlc_outputs) = build_commitment_full(
+        &keys,
+        &params,
+        MilliSat::from_msat(6_988_000_000),
+        MilliSat::from_msat(3_000_000_000),
+        &htlcs,
+        42,
+    )
+    .unwrap();
+
+    let values: Vec<u64> = tx.output.iter().map(|output| output.value.to_sat()).collect();
+    assert_eq!(values, vec![1000, 2000, 2000, 3000, 4000, 3_000_000, 6_986_976]);
+
+    let fee = Amount::from_sat(funding_amount - values.iter().sum::<u64>());
+    assert_eq!(fee, Amount::from_sat(1024));
+
+    assert_eq!(
+        tx.output[5].script_pubkey.to_hex_string(),
+        "0014ccf1af2f2aabee14bb40fa3851ab2301de843110"
+    );
+    assert_eq!(
+        tx.output[6].script_pubkey.to_hex_string(),
+        "00204adb4e2f00643db396dd120d4e7dc17625f5f2c11a40d857accc862d6b7dd80e"
+    );
+
+    // obscured commitment number 42 ^ 0x2bb038521914
+    assert_eq!(tx.lock_time.to_consensus_u32(), 0x2052193e);
+    assert_eq!(tx.input[0].sequence.0, 0x802bb038);
+
+    assert!(htlc_outputs.iter().all(|htlc_output| htlc_output.output_index.is_some()));
+    assert_eq!(htlc_outputs[0].output_index, Some(0));
+    assert_eq!(htlc_outputs[4].output_index, Some(4));
+
+    // a funder that can't pay the fee is short in sats, not msat
+    assert_eq!(
+        build_commitment_full(&keys, &params, MilliSat::from_msat(1_000_999), MilliSat::from_msat(3_000_000_000), &htlcs, 42),
+        Err(ChannelError::InvalidArguments(ExerciseError::InsufficientFunds { needed: 1024, available: 1000 }))
+    );
+}
+
+#[test]
+fn test_verify_min_feerate() {
+    let local_pubkey = pubkey_from_private_key(&[0x01; 32]);
+    let remote_pubkey = pubkey_from_private_key(&[0x02; 32]);
+    let funding_script = two_of_two_multisig_witness_script(&local_pubkey, &remote_pubkey);
+    let funding_amount = Amount::from_sat(5_000_000);
+
+    let build = |fee: u64| {
+        let mut tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![dummy_funding_txin()],
+            output: vec![build_output(funding_amount - Amount::from_sat(fee), p2wpkh_output_script(remote_pubkey))],
+        };
+        // a finalized 2-of-2 witness: empty element, two signatures and the script
+        tx.input[0].witness = Witness::from_slice(&[vec![], vec![0x30; 72], vec![0x30; 72], funding_script.to_bytes()]);
+        tx
+    };
+
+    let weight = build(0).weight().to_wu();
+
+    // pays 252 sat/kw at most
+    let underpaying = build(252 * weight / 1000);
+    assert!(matches!(
+        verify_min_feerate(&underpaying, &[funding_amount], 253),
+        Err(ChannelError::FeerateTooLow { min_feerate_per_kw: 253, .. })
+    ));
+
+    let paying = build((253 * weight).div_ceil(1000));
+    assert_eq!(verify_min_feerate(&paying, &[funding_amount], 253), Ok(()));
+}
+
+#[test]
+fn test_build_commitment_transaction_overwrites_sequence() {
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let to_local_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x03; 32]);
+
+    let obscure_factor: u64 = 0x2bb038521914;
+    let commitment_number: u64 = 42;
+    let (_, expected_sequence) = obscured_commitment_transaction_numbers(obscure_factor, commitment_number);
+
+    for original_sequence in [Sequence::MAX, Sequence::ZERO, Sequence::ENABLE_RBF_NO_LOCKTIME] {
+        let mut funding_txin = dummy_funding_txin();
+        funding_txin.sequence = original_sequence;
+
+        let tx = build_commitment_transaction(
+            funding_txin,
+            &revocation_pubkey,
+            &to_local_delayed_pubkey,
+            remote_pubkey,
+            144,
+            Amount::from_sat(3_998_500),
+            Amount::from_sat(1_000_500),
+            true,
+            253,
+            obscure_factor,
+            commitment_number,
+        ).unwrap();
+
+        assert_eq!(tx.input[0].sequence.0 >> 24, 0x80);
+        assert_eq!(tx.input[0].sequence, expected_sequence);
+        assert_eq!(tx.input[0].sequence.0, 0x802bb038);
+    }
+}
+
+/// A commitment at `commitment_number`, with both its sequence and its
+/// locktime obscured.
+fn build_commitment_at(commitment_number: u64, obscure_factor: u64) -> Transaction {
+    let mut tx = build_commitment_transaction(
+        dummy_funding_txin(),
+        &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+        &DelayedPaymentPubkey(pubkey_from_private_key(&[0x02; 32])),
+        pubkey_from_private_key(&[0x03; 32]),
+        144,
+        Amount::from_sat(3_998_500),
+        Amount::from_sat(1_000_500),
+        true,
+        253,
+        obscure_factor,
+        commitment_number,
+    )
+    .unwrap();
+    tx.lock_time = obscured_commitment_transaction_numbers(obscure_factor, commitment_number).0;
+    tx
+}
+
+#[test]
+fn test_recover_commitment_number_round_trip() {
+    // xorshift64, so the run is reproducible
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let max_commitment_number = (1 << 48) - 1;
+
+    for i in 0..64 {
+        let mut opener_secret = [0u8; 32];
+        let mut accepter_secret = [0u8; 32];
+        opener_secret[..8].copy_from_slice(&next().to_be_bytes());
+        accepter_secret[..8].copy_from_slice(&next().to_be_bytes());
+        opener_secret[31] = 1;
+        accepter_secret[31] = 1;
+
+        let obscure_factor = commitment_number_obscure_factor(
+            &pubkey_from_private_key(&opener_secret),
+            &pubkey_from_private_key(&accepter_secret),
+        );
+
+        let commitment_number = match i {
+            0 => 0,
+            1 => max_commitment_number,
+            _ => next() & max_commitment_number,
+        };
+
+        let tx = build_commitment_at(commitment_number, obscure_factor);
+        assert!(has_obscured_commitment_number(&tx));
+        assert_eq!(recover_commitment_number(&tx, obscure_factor), Some(commitment_number));
+    }
+}
+
+#[test]
+fn test_hash_pubkeys_is_order_dependent() {
+    let a = secp256k1pubkey_from_private_key(&[0x01; 32]);
+    let b = secp256k1pubkey_from_private_key(&[0x02; 32]);
+
+    assert_ne!(hash_pubkeys(a, b), hash_pubkeys(b, a));
+}
+
+#[test]
+fn test_hash_pubkeys_is_sha256_of_concatenation() {
+    let a = secp256k1pubkey_from_private_key(&[0x01; 32]);
+    let b = secp256k1pubkey_from_private_key(&[0x02; 32]);
+
+    let mut concatenated = a.serialize().to_vec();
+    concatenated.extend_from_slice(&b.serialize());
+    let expected: [u8; 32] = Sha256::digest(&concatenated).into();
+
+    assert_eq!(hash_pubkeys(a, b), expected);
+}
+
+#[test]
+fn test_anchor_scripts() {
+    let pubkey = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let pubkey_push = format!("21{}", hex::encode(pubkey.0.to_bytes()));
+
+    // <pubkey> OP_CHECKSIG OP_IFDUP OP_NOTIF OP_16 OP_CSV OP_ENDIF
+    assert_eq!(
+        build_anchor_output_script(&pubkey).to_hex_string(),
+        format!("{}ac736460b268", pubkey_push)
+    );
+
+    // <pubkey> OP_CHECKSIGVERIFY 1 OP_CSV
+    assert_eq!(
+        build_anchor_to_remote_script(&pubkey.0).to_hex_string(),
+        format!("{}ad51b2", pubkey_push)
+    );
+}
+
+#[test]
+fn test_validate_shutdown_script() {
+    let script = |hex: &str| ScriptBuf::from_hex(hex).unwrap();
+    let hash20 = "11".repeat(20);
+    let hash32 = "22".repeat(32);
+
+    // P2PKH, P2SH, P2WPKH and P2WSH, with or without anysegwit
+    for allowed in [
+        format!("76a914{}88ac", hash20),
+        format!("a914{}87", hash20),
+        format!("0014{}", hash20),
+        format!("0020{}", hash32),
+    ] {
+        assert_eq!(validate_shutdown_script(&script(&allowed), false), Ok(()));
+        assert_eq!(validate_shutdown_script(&script(&allowed), true), Ok(()));
+    }
+
+    // P2TR, and the shortest and longest future witness programs
+    for (future, version) in
+        [(format!("5120{}", hash32), 1), ("60021234".to_string(), 16), (format!("5228{}", "33".repeat(40)), 2)]
+    {
+        assert_eq!(validate_shutdown_script(&script(&future), true), Ok(()));
+        assert_eq!(
+            validate_shutdown_script(&script(&future), false),
+            Err(ShutdownScriptError::AnySegwitNotNegotiated(version))
+        );
+    }
+
+    for rejected in [
+        // empty, and OP_RETURN
+        String::new(),
+        "6a0401020304".to_string(),
+        // a v0 program that is neither 20 nor 32 bytes
+        format!("0015{}", "11".repeat(21)),
+        // witness programs of 1 and 41 bytes
+        "510101".to_string(),
+        format!("5129{}", "33".repeat(41)),
+        // P2WPKH pushed with OP_PUSHDATA1, and with a trailing OP_NOP
+        format!("004c14{}", hash20),
+        format!("0014{}61", hash20),
+        // a bare 1-of-1 multisig
+        format!("5121{}51ae", hex::encode(pubkey_from_private_key(&[0x01; 32]).to_bytes())),
+    ] {
+        let rejected = script(&rejected);
+        assert_eq!(validate_shutdown_script(&rejected, true), Err(ShutdownScriptError::Unsupported(rejected.clone())));
+    }
+}
+
+#[test]
+fn test_sort_outputs_bolt3() {
+    let htlc_script = build_htlc_offerer_witness_script(
+        &RevocationPubkey(pubkey_from_private_key(&[0x01; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x02; 32])),
+        &HtlcPubkey(pubkey_from_private_key(&[0x03; 32])),
+        &PaymentPreimage([0x42; 32]).hash160(),
+    )
+    .to_p2wsh();
+    let to_remote_script = p2wpkh_output_script(pubkey_from_private_key(&[0x04; 32]));
+
+    let htlc_output = build_output(Amount::from_sat(5_000), htlc_script);
+    let to_remote_output = build_output(Amount::from_sat(5_000), to_remote_script);
+    let small_output = build_output(Amount::from_sat(1_000), htlc_output.script_pubkey.clone());
+
+    let sorted = sort_outputs_bolt3(vec![
+        (htlc_output.clone(), Some(600)),
+        (to_remote_output.clone(), None),
+        (htlc_output.clone(), Some(500)),
+        (small_output.clone(), Some(700)),
+    ]);
+
+    // P2WPKH (0x0014...) sorts before P2WSH (0x0020...) at the same value
+    assert_eq!(
+        strip_cltv_expiries(sorted),
+        vec![small_output.clone(), to_remote_output.clone(), htlc_output.clone(), htlc_output.clone()]
+    );
+
+    // identical HTLC outputs only differ by expiry, and keep it through the sort
+    let sorted = sort_outputs_bolt3(vec![
+        (htlc_output.clone(), Some(600)),
+        (htlc_output.clone(), Some(500)),
+        (to_remote_output.clone(), None),
+    ]);
+    assert_eq!(sorted, vec![(to_remote_output, None), (htlc_output.clone(), Some(500)), (htlc_output, Some(600))]);
+}
+
+#[test]
+fn test_fee_utils_bolt3_weights() {
+    // BOLT 3 appendix C: 5 untrimmed HTLCs at 647 sat/kw
+    assert_eq!(commit_tx_fee(647, 5, false), Amount::from_sat(1024));
+    assert_eq!(commit_tx_fee(253, 0, false), Amount::from_sat(724 * 253 / 1000));
+    assert_eq!(commit_tx_fee(253, 0, true), Amount::from_sat(1124 * 253 / 1000));
+
+    assert_eq!(htlc_timeout_fee(1_000, false), Amount::from_sat(663));
+    assert_eq!(htlc_timeout_fee(1_000, true), Amount::from_sat(666));
+    assert_eq!(htlc_success_fee(1_000, false), Amount::from_sat(703));
+    assert_eq!(htlc_success_fee(1_000, true), Amount::from_sat(706));
+
+    assert_eq!(HtlcFeeMode::Anchors.htlc_timeout_fee(1_000), Amount::from_sat(666));
+    assert_eq!(HtlcFeeMode::ZeroFeeAnchors.htlc_timeout_fee(1_000), Amount::ZERO);
+    assert_eq!(HtlcFeeMode::ZeroFeeAnchors.htlc_success_fee(1_000), Amount::ZERO);
+    assert_eq!(HtlcFeeMode::Legacy.counterparty_sighash_type(), EcdsaSighashType::All);
+    assert_eq!(HtlcFeeMode::ZeroFeeAnchors.counterparty_sighash_type(), EcdsaSighashType::SinglePlusAnyoneCanPay);
+}
+
+fn dummy_key_manager(byte: u8) -> ChannelKeyManager {
+    ChannelKeyManager {
+        funding_key: secp256k1_private_key(&[byte; 32]),
+        payment_base_key: secp256k1_private_key(&[byte + 1; 32]),
+        delayed_payment_base_key: secp256k1_private_key(&[byte + 2; 32]),
+        htlc_base_key: secp256k1_private_key(&[byte + 3; 32]),
+        revocation_base_key: secp256k1_private_key(&[byte + 4; 32]),
+        commitment_seed: [byte + 5; 32],
+    }
+}
+
+#[test]
+fn test_channel_key_manager_from_seed() {
+    let keys = ChannelKeyManager::from_seed(&[0x01; 32], 0);
+    assert_eq!(ChannelKeyManager::from_seed(&[0x01; 32], 0), keys);
+
+    // every key is different, and so is every channel's set of keys
+    let secrets = [
+        keys.funding_key.secret_bytes(),
+        keys.payment_base_key.secret_bytes(),
+        keys.delayed_payment_base_key.secret_bytes(),
+        keys.htlc_base_key.secret_bytes(),
+        keys.revocation_base_key.secret_bytes(),
+        keys.commitment_seed,
+    ];
+    assert_eq!(secrets.iter().collect::<std::collections::HashSet<_>>().len(), 6);
+    assert_ne!(ChannelKeyManager::from_seed(&[0x01; 32], 1).funding_key, keys.funding_key);
+    assert_ne!(ChannelKeyManager::from_seed(&[0x02; 32], 0).funding_key, keys.funding_key);
+}
+
+#[test]
+fn test_channel_key_manager_keys_for_commitment() {
+    let alice = dummy_key_manager(0x10);
+    let bob = dummy_key_manager(0x20);
+
+    let keys = alice.keys_for_commitment(3, &bob.pubkeys());
+
+    let secret = generate_from_seed(&alice.commitment_seed, MAX_SHACHAIN_INDEX - 3);
+    assert_eq!(alice.per_commitment_secret(3), secret);
+    assert_eq!(keys.per_commitment_point, alice.per_commitment_point(3));
+
+    assert_eq!(
+        keys.revocation_key,
+        generate_revocation_pubkey(bob.pubkeys().revocation_basepoint, keys.per_commitment_point)
+    );
+    assert_eq!(
+        keys.broadcaster_delayed_payment_key,
+        derive_public_key(alice.pubkeys().delayed_payment_basepoint, keys.per_commitment_point)
+    );
+    assert_eq!(
+        keys.countersignatory_htlc_key,
+        derive_public_key(bob.pubkeys().htlc_basepoint, keys.per_commitment_point)
+    );
+    assert_eq!(keys.countersignatory_payment_key, bob.pubkeys().payment_basepoint);
+
+    // every commitment gets fresh keys
+    assert_ne!(alice.keys_for_commitment(4, &bob.pubkeys()), keys);
+
+    let channel_keys = keys.to_channel_keys();
+    assert_eq!(channel_keys.revocation_pubkey.0.inner, keys.revocation_key);
+    assert_eq!(channel_keys.local_htlc_pubkey.0.inner, keys.broadcaster_htlc_key);
+}
+
+#[test]
+fn test_commitment_transaction_builder() {
+    let alice = dummy_key_manager(0x10);
+    let bob = dummy_key_manager(0x20);
+
+    let params = ChannelParams {
+        funding_outpoint: dummy_funding_txin().previous_output,
+        to_self_delay: 144,
+        dust_limit: Amount::from_sat(546),
+        feerate_per_kw: 2_500,
+        htlc_fee_mode: HtlcFeeMode::Anchors,
+        is_funder: true,
+        local_keys: alice.pubkeys(),
+        remote_keys: bob.pubkeys(),
+    };
+
+    let keys = params.local_commitment_keys(alice.per_commitment_point(7));
+    assert_eq!(keys, alice.keys_for_commitment(7, &bob.pubkeys()).to_channel_keys());
+
+    let htlc = |offered: bool, amount_msat: u64, cltv_expiry: u32| Htlc {
+        offered,
+        amount_msat: MilliSat::from_msat(amount_msat),
+        payment_hash: PaymentPreimage([cltv_expiry as u8; 32]).hash160(),
+        cltv_expiry,
+    };
+
+    let mut channel = Channel::new(MilliSat::from_msat(700_000_000), MilliSat::from_msat(300_000_000));
+    channel.commitment_number = 7;
+    channel.add_htlc(htlc(true, 5_000_000, 500)).unwrap();
+    channel.add_htlc(htlc(false, 7_000_000, 510)).unwrap();
+
+    let (tx, htlc_outputs) = CommitmentTransactionBuilder::from_channel(&params, &channel, keys).build().unwrap();
+
+    let expected =
+        build_commitment_full(&keys, &params, channel.to_local_msat, channel.to_remote_msat, &channel.htlcs, 7)
+            .unwrap();
+    assert_eq!((tx.clone(), htlc_outputs), expected);
+
+    // adding the same pieces by hand builds the same transaction
+    let (manual_tx, _) = CommitmentTransactionBuilder::new(&params, 7, keys)
+        .balances(channel.to_local_msat, channel.to_remote_msat)
+        .add_htlc(channel.htlcs[0])
+        .add_htlc(channel.htlcs[1])
+        .build()
+        .unwrap();
+    assert_eq!(manual_tx, tx);
+
+    // the opener's payment basepoint is always listed first
+    let accepter = ChannelParams { is_funder: false, ..params };
+    assert_eq!(accepter.basepoints().opener_payment_basepoint, params.basepoints().accepter_payment_basepoint);
+}
+
+#[test]
+fn test_verify_counterparty_commitment() {
+    let secp = Secp256k1::new();
+
+    let alice_funding_pubkey = pubkey_from_private_key(&[0x01; 32]);
+    let bob_funding_privkey = secp256k1_private_key(&[0x02; 32]);
+    let bob_funding_pubkey = pubkey_from_private_key(&[0x02; 32]);
+
+    let funding_amount: u64 = 5_000_000;
+    let funding_script = two_of_two_multisig_witness_script(&alice_funding_pubkey, &bob_funding_pubkey);
+
+    let commitment_tx = build_commitment_at(42, 0x2bb038521914);
+
+    // Bob signs Alice's commitment and sends her the signature
+    let sighash = commitment_sighash_to_sign(&commitment_tx, &funding_script, funding_amount);
+    let bob_signature = secp.sign_ecdsa(&Message::from_digest(sighash), &bob_funding_privkey);
+
+    assert!(verify_commitment_signature(&bob_signature, &bob_funding_pubkey, &sighash));
+    assert!(!verify_commitment_signature(&bob_signature, &alice_funding_pubkey, &sighash));
+
+    assert_eq!(
+        verify_counterparty_commitment(&commitment_tx, &bob_signature, &bob_funding_pubkey, &funding_script, funding_amount),
+        Ok(())
+    );
+
+    // a signature for a different channel value, or for another commitment, is rejected
+    assert_eq!(
+        verify_counterparty_commitment(&commitment_tx, &bob_signature, &bob_funding_pubkey, &funding_script, funding_amount - 1),
+        Err(ChannelError::InvalidCommitmentSignature)
+    );
+    assert_eq!(
+        verify_counterparty_commitment(
+            &build_commitment_at(43, 0x2bb038521914),
+            &bob_signature,
+            &bob_funding_pubkey,
+            &funding_script,
+            funding_amount
+        ),
+        Err(ChannelError::InvalidCommitmentSignature)
+    );
+}
+
+#[test]
+fn test_channel_id_helpers() {
+    let txid = DUMMY_FUNDING_TXID.parse::<Txid>().unwrap();
+    let txid_bytes = bitcoin::hashes::Hash::to_byte_array(txid);
+
+    assert_eq!(channel_id_from_funding(txid, 0), txid_bytes);
+
+    // the index is XORed big-endian into the last two bytes
+    let channel_id = channel_id_from_funding(txid, 0x0102);
+    assert_eq!(channel_id[..30], txid_bytes[..30]);
+    assert_eq!(channel_id[30], txid_bytes[30] ^ 0x01);
+    assert_eq!(channel_id[31], txid_bytes[31] ^ 0x02);
+
+    let basepoint_a = secp256k1pubkey_from_private_key(&[0x01; 32]);
+    let basepoint_b = secp256k1pubkey_from_private_key(&[0x02; 32]);
+    let (lesser, greater) = if basepoint_a.serialize() < basepoint_b.serialize() {
+        (basepoint_a, basepoint_b)
+    } else {
+        (basepoint_b, basepoint_a)
+    };
+
+    let mut preimage = lesser.serialize().to_vec();
+    preimage.extend_from_slice(&greater.serialize());
+    let expected: [u8; 32] = Sha256::digest(&preimage).into();
+
+    assert_eq!(channel_id_v2(&basepoint_a, &basepoint_b), expected);
+    assert_eq!(channel_id_v2(&basepoint_b, &basepoint_a), expected);
+}
+
+#[test]
+fn test_tx_hex_round_trip() {
+    let commitment_tx = build_commitment_at(42, 0x2bb038521914);
+
+    let hex = tx_to_hex(&commitment_tx);
+    assert_eq!(hex, bitcoin::consensus::encode::serialize_hex(&commitment_tx));
+    assert_eq!(tx_from_hex(&hex).unwrap(), commitment_tx);
+
+    assert!(tx_from_hex("not hex").is_err());
+    assert!(tx_from_hex(&hex[..hex.len() - 2]).is_err());
+}
+
+#[test]
+fn test_millisat_conversions() {
+    let amount = MilliSat::from_msat(1_234_567);
+
+    // commitments round HTLCs and balances down to whole satoshis
+    assert_eq!(amount.to_amount_floor(), Amount::from_sat(1_234));
+    assert_eq!(amount.to_amount_exact(), None);
+    assert_eq!(MilliSat::from_msat(1_234_000).to_amount_exact(), Some(Amount::from_sat(1_234)));
+
+    assert_eq!(MilliSat::from_amount(Amount::from_sat(1_234)), Some(MilliSat::from_msat(1_234_000)));
+    assert_eq!(MilliSat::from_amount(Amount::from_sat(u64::MAX / 1000 + 1)), None);
+
+    assert_eq!(amount.checked_sub(MilliSat::from_msat(567)), Some(MilliSat::from_msat(1_234_000)));
+    assert_eq!(MilliSat::ZERO.checked_sub(MilliSat::from_msat(1)), None);
+    assert_eq!(MilliSat::from_msat(u64::MAX).checked_add(MilliSat::from_msat(1)), None);
+    assert_eq!(amount.to_string(), "1234567 msat");
+}
+
+#[test]
+fn test_musig_key_aggregation_bip327_vectors() {
+    let pubkey = |hex_str: &str| bitcoin::secp256k1::PublicKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap();
+    let x = pubkey("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9");
+    let y = pubkey("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659");
+    let z = pubkey("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66");
+
+    let aggregate = |keys: Vec<bitcoin::secp256k1::PublicKey>| {
+        hex::encode(KeyAggContext::new(keys).unwrap().aggregated_pubkey().serialize())
+    };
+    assert_eq!(aggregate(vec![x, y, z]), "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c");
+    assert_eq!(aggregate(vec![z, y, x]), "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b");
+    assert_eq!(aggregate(vec![x, x, x]), "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935");
+    assert_eq!(KeyAggContext::new(vec![]), Err(MusigError::NoKeys));
+}
+
+#[test]
+fn test_musig_sign_untweaked() {
+    let secp = Secp256k1::new();
+    let alice_key = secp256k1_private_key(&[0x11; 32]);
+    let bob_key = secp256k1_private_key(&[0x22; 32]);
+    let alice_pubkey = alice_key.public_key(&secp);
+    let bob_pubkey = bob_key.public_key(&secp);
+    let msg = [0x42; 32];
+
+    let ctx = KeyAggContext::new(vec![alice_pubkey, bob_pubkey]).unwrap();
+    let aggregated_pubkey = ctx.aggregated_pubkey();
+
+    let (alice_secnonce, alice_pubnonce) = generate_nonce([0x01; 32], &alice_key, &aggregated_pubkey, &msg);
+    let (bob_secnonce, bob_pubnonce) = generate_nonce([0x02; 32], &bob_key, &aggregated_pubkey, &msg);
+    let aggnonce = aggregate_nonces(&[alice_pubnonce, bob_pubnonce]).unwrap();
+
+    let alice_partial = partial_sign(alice_secnonce, &alice_key, &ctx, &aggnonce, &msg).unwrap();
+    let bob_partial = partial_sign(bob_secnonce, &bob_key, &ctx, &aggnonce, &msg).unwrap();
+
+    assert!(verify_partial_signature(&alice_partial, &alice_pubnonce, &alice_pubkey, &ctx, &aggnonce, &msg));
+    assert!(verify_partial_signature(&bob_partial, &bob_pubnonce, &bob_pubkey, &ctx, &aggnonce, &msg));
+    // a share is bound to the signer who made it
+    assert!(!verify_partial_signature(&alice_partial, &bob_pubnonce, &bob_pubkey, &ctx, &aggnonce, &msg));
+
+    let signature = aggregate_partial_signatures(&[alice_partial, bob_partial], &ctx, &aggnonce, &msg);
+    assert!(secp.verify_schnorr(&signature, &Message::from_digest(msg), &aggregated_pubkey).is_ok());
+
+    let (stranger_secnonce, _) = generate_nonce([0x03; 32], &alice_key, &aggregated_pubkey, &msg);
+    let stranger = secp256k1_private_key(&[0x33; 32]);
+    assert_eq!(
+        partial_sign(stranger_secnonce, &stranger, &ctx, &aggnonce, &msg),
+        Err(MusigError::UnknownSigner)
+    );
+}
+
+#[test]
+fn test_adaptor_signature_round_trip() {
+    let secp = Secp256k1::new();
+    let signer = secp256k1_private_key(&[0x11; 32]);
+    let pubkey = signer.public_key(&secp).x_only_public_key().0;
+    let payment_secret = secp256k1_private_key(&[0x22; 32]);
+    let payment_point = payment_secret.public_key(&secp);
+    let other_point = secp256k1pubkey_from_private_key(&[0x33; 32]);
+    let msg = [0x42; 32];
+
+    // enough nonces to hit both parities of R + T
+    let mut negated = [false, false];
+    for aux in 0..8u8 {
+        let adaptor = create_adaptor_signature(&signer, &msg, &payment_point, [aux; 32]);
+        negated[(adaptor.nonce.x_only_public_key().1 == bitcoin::key::Parity::Odd) as usize] = true;
+
+        assert!(verify_adaptor_signature(&adaptor, &pubkey, &msg, &payment_point));
+        assert!(!verify_adaptor_signature(&adaptor, &pubkey, &msg, &other_point));
+        assert!(!verify_adaptor_signature(&adaptor, &pubkey, &[0x43; 32], &payment_point));
+
+        // not a valid signature until completed
+        let unfinished = bitcoin::secp256k1::schnorr::Signature::from_slice(
+            &[&adaptor.nonce.x_only_public_key().0.serialize()[..], &adaptor.s.secret_bytes()[..]].concat(),
+        )
+        .unwrap();
+        assert!(secp.verify_schnorr(&unfinished, &Message::from_digest(msg), &pubkey).is_err());
+
+        let signature = complete_adaptor_signature(&adaptor, &payment_secret);
+        assert!(secp.verify_schnorr(&signature, &Message::from_digest(msg), &pubkey).is_ok());
+        assert_eq!(extract_adaptor_secret(&adaptor, &signature, &payment_point), Some(payment_secret));
+        assert_eq!(extract_adaptor_secret(&adaptor, &signature, &other_point), None);
+    }
+    assert_eq!(negated, [true, true]);
+}
+
+#[test]
+fn test_wire_init_round_trip() {
+    // An init with the option_static_remotekey bit (13) set and a networks TLV.
+    let bytes = hex::decode(
+        "00100000000220000120\
+         6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000",
+    )
+    .unwrap();
+    let message = WireMessage::decode(&bytes).unwrap();
+    let WireMessage::Init(init) = &message else { panic!("expected init, got {:?}", message) };
+
+    assert!(init.global_features.is_empty());
+    assert_eq!(init.features, vec![0x20, 0x00]);
+    assert_eq!(init.tlv_stream.len(), 34);
+    assert_eq!(message.encode(), bytes);
+}
+
+#[test]
+fn test_wire_ping_pong() {
+    let ping = Ping { num_pong_bytes: 3, ignored: vec![0xff; 5] };
+    let bytes = WireMessage::Ping(ping.clone()).encode();
+    assert_eq!(hex::encode(&bytes), "001200030005ffffffffff");
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::Ping(ping.clone()));
+
+    let pong = ping.response().unwrap();
+    assert_eq!(pong.ignored, vec![0; 3]);
+    assert_eq!(WireMessage::decode(&WireMessage::Pong(pong.clone()).encode()).unwrap(), WireMessage::Pong(pong));
+
+    let quiet = Ping { num_pong_bytes: 65532, ignored: vec![] };
+    assert_eq!(quiet.response(), None);
+
+    assert_eq!(WireMessage::decode(&hex::decode("0012000300").unwrap()), Err(WireError::ShortRead));
+}
+
+#[test]
+fn test_wire_unknown_types() {
+    assert_eq!(WireMessage::decode(&[0x80, 0x00, 0x01]), Err(WireError::UnknownEvenType(0x8000)));
+    assert_eq!(
+        WireMessage::decode(&[0x80, 0x01, 0x01]).unwrap(),
+        WireMessage::Unknown { msg_type: 0x8001, payload: vec![0x01] }
+    );
+}
+
+#[test]
+fn test_bigsize_bolt1_vectors() {
+    for (value, encoded) in [
+        (0, "00"),
+        (252, "fc"),
+        (253, "fd00fd"),
+        (65535, "fdffff"),
+        (65536, "fe00010000"),
+        (4294967295, "feffffffff"),
+        (4294967296, "ff0000000100000000"),
+        (u64::MAX, "ffffffffffffffffff"),
+    ] {
+        let mut out = Vec::new();
+        write_bigsize(&mut out, value);
+        assert_eq!(hex::encode(&out), encoded);
+        assert_eq!(read_bigsize(&mut Reader::new(&out)), Ok(value));
+    }
+
+    for (encoded, error) in [
+        ("fd00fc", WireError::NonCanonicalBigSize),
+        ("fe0000ffff", WireError::NonCanonicalBigSize),
+        ("ff00000000ffffffff", WireError::NonCanonicalBigSize),
+        ("fd00", WireError::ShortRead),
+        ("", WireError::ShortRead),
+    ] {
+        let bytes = hex::decode(encoded).unwrap();
+        assert_eq!(read_bigsize(&mut Reader::new(&bytes)), Err(error), "{}", encoded);
+    }
+}
+
+#[test]
+fn test_tlv_stream_ordering_and_unknown_types() {
+    let records = vec![TlvRecord::new(3, vec![0xaa]), TlvRecord::new(0, vec![]), TlvRecord::new(254, vec![1, 2])];
+    let stream = write_tlv_stream(&records).unwrap();
+    assert_eq!(hex::encode(&stream), "00000301aafd00fe020102");
+    let read = read_tlv_stream(&stream, &[0, 254]).unwrap();
+    assert_eq!(read.iter().map(|r| r.tlv_type).collect::<Vec<_>>(), vec![0, 3, 254]);
+
+    let duplicate = vec![TlvRecord::new(1, vec![]), TlvRecord::new(1, vec![])];
+    assert_eq!(write_tlv_stream(&duplicate), Err(WireError::TlvTypesNotAscending { previous: 1, current: 1 }));
+
+    // BOLT 1 failure vectors for the n1 namespace.
+    assert_eq!(
+        read_tlv_stream(&hex::decode("0208000000000000022601012a").unwrap(), &[2, 1]),
+        Err(WireError::TlvTypesNotAscending { previous: 2, current: 1 })
+    );
+    assert_eq!(read_tlv_stream(&hex::decode("1200").unwrap(), &[]), Err(WireError::UnknownEvenTlvType(0x12)));
+    assert_eq!(read_tlv_stream(&hex::decode("fd010200").unwrap(), &[]), Err(WireError::UnknownEvenTlvType(0x102)));
+    assert_eq!(read_tlv_stream(&hex::decode("0f0100").unwrap(), &[]).unwrap(), vec![TlvRecord::new(15, vec![0])]);
+    assert_eq!(read_tlv_stream(&hex::decode("0f02").unwrap(), &[]), Err(WireError::ShortRead));
+}
+
+#[test]
+fn test_truncated_integers() {
+    assert_eq!(encode_tu64(0), Vec::<u8>::new());
+    assert_eq!(encode_tu64(1), vec![1]);
+    assert_eq!(encode_tu64(0x0100), vec![1, 0]);
+    assert_eq!(encode_tu64(u64::MAX), vec![0xff; 8]);
+
+    for value in [0, 1, 0xff, 0x100, 15000, 0x0100_0000, u64::MAX] {
+        assert_eq!(decode_tu64(&encode_tu64(value), 8), Ok(value));
+    }
+    assert!(decode_tu64(&[0, 1], 8).is_err());
+    assert!(decode_tu64(&[1, 0, 0, 0, 0], 4).is_err());
+}
+
+#[test]
+fn test_wire_interactive_tx_messages_round_trip() {
+    let prevtx = Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![dummy_funding_txin()],
+        output: vec![build_output(Amount::from_sat(100_000), ScriptBuf::new_p2wsh(&ScriptBuf::new().wscript_hash()))],
+    };
+    let add_input = TxAddInput {
+        channel_id: [0x42; 32],
+        serial_id: 2,
+        prevtx: prevtx.clone(),
+        prevtx_out: 0,
+        sequence: 0xfffffffd,
+    };
+    let bytes = WireMessage::TxAddInput(add_input.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x42]);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::TxAddInput(add_input));
+
+    let add_output = TxAddOutput {
+        channel_id: [0x42; 32],
+        serial_id: 3,
+        sats: Amount::from_sat(50_000),
+        script: prevtx.output[0].script_pubkey.clone(),
+    };
+    let bytes = WireMessage::TxAddOutput(add_output.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x43]);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::TxAddOutput(add_output));
+
+    let complete = TxComplete { channel_id: [0x42; 32] };
+    assert_eq!(WireMessage::TxComplete(complete.clone()).encode().len(), 2 + 32);
+    assert_eq!(
+        WireMessage::decode(&WireMessage::TxComplete(complete.clone()).encode()).unwrap(),
+        WireMessage::TxComplete(complete)
+    );
+
+    // a prevtx that doesn't parse
+    let mut bytes = vec![0x00, 0x42];
+    bytes.extend_from_slice(&[0x42; 32]);
+    bytes.extend_from_slice(&2u64.to_be_bytes());
+    bytes.extend_from_slice(&[0x00, 0x02, 0xff, 0xff]);
+    bytes.extend_from_slice(&[0x00; 8]);
+    assert_eq!(WireMessage::decode(&bytes), Err(WireError::InvalidValue("prevtx")));
+}
+
+#[test]
+fn test_wire_channel_open_messages_round_trip() {
+    let secp = Secp256k1::new();
+    let bob = dummy_key_manager(0x20).pubkeys();
+
+    let accept_channel = AcceptChannel {
+        temporary_channel_id: [0x42; 32],
+        dust_limit_satoshis: Amount::from_sat(546),
+        max_htlc_value_in_flight_msat: MilliSat::from_msat(5_000_000_000),
+        channel_reserve_satoshis: Amount::from_sat(50_000),
+        htlc_minimum_msat: MilliSat::from_msat(1_000),
+        minimum_depth: 3,
+        to_self_delay: 144,
+        max_accepted_htlcs: 30,
+        funding_pubkey: bob.funding_pubkey,
+        revocation_basepoint: bob.revocation_basepoint,
+        payment_basepoint: bob.payment_basepoint,
+        delayed_payment_basepoint: bob.delayed_payment_basepoint,
+        htlc_basepoint: bob.htlc_basepoint,
+        first_per_commitment_point: dummy_key_manager(0x20).per_commitment_point(0),
+        upfront_shutdown_script: Some(ScriptBuf::new()),
+        channel_type: None,
+    };
+    let bytes = WireMessage::AcceptChannel(accept_channel.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x21]);
+    assert_eq!(&bytes[bytes.len() - 2..], &[0x00, 0x00]);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::AcceptChannel(accept_channel));
+
+    let signature = secp.sign_ecdsa(&Message::from_digest([0x01; 32]), &secp256k1_private_key(&[0x02; 32]));
+    let funding_created = FundingCreated {
+        temporary_channel_id: [0x42; 32],
+        funding_txid: dummy_funding_txin().previous_output.txid,
+        funding_output_index: 1,
+        signature,
+    };
+    let bytes = WireMessage::FundingCreated(funding_created.clone()).encode();
+    assert_eq!(bytes.len(), 2 + 32 + 32 + 2 + 64);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::FundingCreated(funding_created));
+
+    let funding_signed = FundingSigned { channel_id: [0x43; 32], signature };
+    let bytes = WireMessage::FundingSigned(funding_signed.clone()).encode();
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::FundingSigned(funding_signed));
+    assert_eq!(WireMessage::decode(&bytes[..bytes.len() - 1]), Err(WireError::ShortRead));
+}
+
+#[test]
+fn test_wire_closing_messages_round_trip() {
+    let secp = Secp256k1::new();
+    let shutdown =
+        Shutdown { channel_id: [0x42; 32], scriptpubkey: ScriptBuf::new_p2wsh(&ScriptBuf::new().wscript_hash()) };
+    let bytes = WireMessage::Shutdown(shutdown.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x26]);
+    assert_eq!(bytes.len(), 2 + 32 + 2 + 34);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::Shutdown(shutdown));
+
+    let signature = secp.sign_ecdsa(&Message::from_digest([0x01; 32]), &secp256k1_private_key(&[0x02; 32]));
+    let mut closing_signed = ClosingSigned {
+        channel_id: [0x42; 32],
+        fee_satoshis: Amount::from_sat(1_000),
+        signature,
+        fee_range: Some(FeeRange {
+            min_fee_satoshis: Amount::from_sat(500),
+            max_fee_satoshis: Amount::from_sat(2_000),
+        }),
+    };
+    let bytes = WireMessage::ClosingSigned(closing_signed.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x27]);
+    // the fee_range TLV: type 1, length 16, then both fees
+    assert_eq!(&bytes[2 + 32 + 8 + 64..][..2], &[0x01, 0x10]);
+    assert_eq!(bytes.len(), 2 + 32 + 8 + 64 + 2 + 16);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ClosingSigned(closing_signed.clone()));
+
+    // a fee_range of the wrong length
+    let mut bad_range = bytes.clone();
+    bad_range[2 + 32 + 8 + 64 + 1] = 0x0f;
+    bad_range.pop();
+    assert_eq!(WireMessage::decode(&bad_range), Err(WireError::InvalidValue("fee_range")));
+
+    closing_signed.fee_range = None;
+    let bytes = WireMessage::ClosingSigned(closing_signed.clone()).encode();
+    assert_eq!(bytes.len(), 2 + 32 + 8 + 64);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ClosingSigned(closing_signed));
+}
+
+#[test]
+fn test_wire_htlc_update_messages_round_trip() {
+    let secp = Secp256k1::new();
+    let keys = dummy_key_manager(0x20);
+    let signature = |byte: u8| secp.sign_ecdsa(&Message::from_digest([byte; 32]), &keys.htlc_base_key);
+
+    let channel_ready =
+        ChannelReady { channel_id: [0x43; 32], second_per_commitment_point: keys.per_commitment_point(1) };
+    let bytes = WireMessage::ChannelReady(channel_ready.clone()).encode();
+    assert_eq!(bytes.len(), 2 + 32 + 33);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::ChannelReady(channel_ready));
+
+    let preimage = PaymentPreimage([0x07; 32]);
+    let update_add_htlc = UpdateAddHtlc {
+        channel_id: [0x43; 32],
+        id: 3,
+        amount_msat: MilliSat::from_msat(25_000_000),
+        payment_hash: preimage.hash(),
+        cltv_expiry: 800_040,
+        onion_routing_packet: Box::new(OnionPacket {
+            version: 0,
+            public_key: keys.per_commitment_point(0),
+            hop_payloads: [0x11; 1300],
+            hmac: [0x22; 32],
+        }),
+    };
+    let bytes = WireMessage::UpdateAddHtlc(update_add_htlc.clone()).encode();
+    assert_eq!(&bytes[..2], &[0x00, 0x80]);
+    assert_eq!(bytes.len(), 2 + 32 + 8 + 8 + 32 + 4 + ONION_PACKET_LEN);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::UpdateAddHtlc(update_add_htlc));
+
+    // An onion with an unknown public key can't be forwarded.
+    let mut bad_onion = bytes.clone();
+    bad_onion[2 + 32 + 8 + 8 + 32 + 4 + 1] = 0x05;
+    assert_eq!(WireMessage::decode(&bad_onion), Err(WireError::InvalidValue("onion_routing_packet")));
+
+    let update_fulfill_htlc = UpdateFulfillHtlc { channel_id: [0x43; 32], id: 3, payment_preimage: preimage };
+    let bytes = WireMessage::UpdateFulfillHtlc(update_fulfill_htlc.clone()).encode();
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::UpdateFulfillHtlc(update_fulfill_htlc));
+
+    let commitment_signed = CommitmentSigned {
+        channel_id: [0x43; 32],
+        signature: signature(0x01),
+        htlc_signatures: vec![signature(0x02), signature(0x03)],
+    };
+    let bytes = WireMessage::CommitmentSigned(commitment_signed.clone()).encode();
+    assert_eq!(bytes.len(), 2 + 32 + 64 + 2 + 2 * 64);
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::CommitmentSigned(commitment_signed));
+    assert_eq!(WireMessage::decode(&bytes[..bytes.len() - 64]), Err(WireError::ShortRead));
+
+    let revoke_and_ack = RevokeAndAck {
+        channel_id: [0x43; 32],
+        per_commitment_secret: keys.per_commitment_secret(0),
+        next_per_commitment_point: keys.per_commitment_point(2),
+    };
+    let bytes = WireMessage::RevokeAndAck(revoke_and_ack.clone()).encode();
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::RevokeAndAck(revoke_and_ack));
+}
+
+#[test]
+fn test_node_announcement_round_trip_and_signature() {
+    let secp = Secp256k1::new();
+    let node_key = secp256k1_private_key(&[0x31; 32]);
+
+    let mut alias = [0u8; 32];
+    alias[..5].copy_from_slice(b"alice");
+    let mut announcement = NodeAnnouncement {
+        signature: secp.sign_ecdsa(&Message::from_digest([0; 32]), &node_key),
+        features: encode_features(&[STATIC_REMOTE_KEY_REQUIRED]),
+        timestamp: 1_700_000_000,
+        node_id: pubkey_from_secret(node_key),
+        rgb_color: [0xff, 0x99, 0x00],
+        alias,
+        addresses: vec![
+            NetAddress::Ipv4 { addr: [127, 0, 0, 1], port: 9735 },
+            NetAddress::Hostname { hostname: "ln.example.com".to_string(), port: 9735 },
+        ],
+    };
+    assert_eq!(verify_node_announcement(&announcement), Err(GossipError::InvalidSignature("node_id")));
+
+    announcement.signature = secp.sign_ecdsa(&Message::from_digest(announcement.signing_hash()), &node_key);
+    assert_eq!(verify_node_announcement(&announcement), Ok(()));
+    assert_eq!(announcement.alias_str(), "alice");
+
+    let bytes = WireMessage::NodeAnnouncement(announcement.clone()).encode();
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::NodeAnnouncement(announcement.clone()));
+
+    // An unknown address type ends the list we can parse, but still round trips.
+    announcement.addresses.push(NetAddress::Unknown { addr_type: 9, data: vec![1, 2, 3] });
+    let bytes = WireMessage::NodeAnnouncement(announcement.clone()).encode();
+    assert_eq!(WireMessage::decode(&bytes).unwrap(), WireMessage::NodeAnnouncement(announcement));
+}
+
+/// A signed announcement for a channel between `node_a` and `node_b`,
+/// funded by a 2-of-2 of `funding_a` and `funding_b`, and its funding output.
+fn signed_channel_announcement(
+    short_channel_id: ShortChannelId,
+    (node_a, funding_a): (SecretKey, SecretKey),
+    (node_b, funding_b): (SecretKey, SecretKey),
+) -> (ChannelAnnouncement, bitcoin::TxOut) {
+    let secp = Secp256k1::new();
+    let mut nodes = [(node_a, funding_a), (node_b, funding_b)];
+    nodes.sort_by_key(|(node_key, _)| pubkey_from_secret(*node_key).serialize());
+    let [(node_key_1, funding_key_1), (node_key_2, funding_key_2)] = nodes;
+
+    let (first, second) = sort_funding_keys(
+        bitcoin::PublicKey::new(pubkey_from_secret(funding_key_1)),
+        bitcoin::PublicKey::new(pubkey_from_secret(funding_key_2)),
+    );
+    let funding_output = bitcoin::TxOut {
+        value: Amount::from_sat(1_000_000),
+        script_pubkey: two_of_two_multisig_witness_script(&first, &second).to_p2wsh(),
+    };
+
+    let placeholder = secp.sign_ecdsa(&Message::from_digest([0; 32]), &node_key_1);
+    let mut announcement = ChannelAnnouncement {
+        node_signature_1: placeholder,
+        node_signature_2: placeholder,
+        bitcoin_signature_1: placeholder,
+        bitcoin_signature_2: placeholder,
+        features: vec![],
+        chain_hash: ChainHash::REGTEST,
+        short_channel_id,
+        node_id_1: pubkey_from_secret(node_key_1),
+        node_id_2: pubkey_from_secret(node_key_2),
+        bitcoin_key_1: pubkey_from_secret(funding_key_1),
+        bitcoin_key_2: pubkey_from_secret(funding_key_2),
+    };
+    let message = Message::from_digest(announcement.signing_hash());
+    announcement.node_signature_1 = secp.sign_ecdsa(&message, &node_key_1);
+    announcement.node_signature_2 = secp.sign_ecdsa(&message, &node_key_2);
+    announcement.bitcoin_signature_1 = secp.sign_ecdsa(&message, &funding_key_1);
+    announcement.bitcoin_signature_2 = secp.sign_ecdsa(&message, &funding_key_2);
+
+    (announcement, funding_output)
+}
+
+fn signed_channel_update(
+    short_channel_id: ShortChannelId,
+    direction: u8,
+    timestamp: u32,
+    signer: &SecretKey,
+) -> ChannelUpdate {
+    let secp = Secp256k1::new();
+    let mut update = ChannelUpdate {
+        signature: secp.sign_ecdsa(&Message::from_digest([0; 32]), signer),
+        chain_hash: ChainHash::REGTEST,
+        short_channel_id,
+        timestamp,
+        message_flags: MESSAGE_FLAGS_MUST_BE_ONE,
+        channel_flags: direction & CHANNEL_FLAGS_DIRECTION,
+        cltv_expiry_delta: 40,
+        htlc_minimum_msat: MilliSat::from_msat(1_000),
+        fee_base_msat: 1_000,
+        fee_proportional_millionths: 100,
+        htlc_maximum_msat: MilliSat::from_msat(500_000_000),
+    };
+    update.signature = secp.sign_ecdsa(&Message::from_digest(update.signing_hash()), signer);
+    update
+}
+
+#[test]
+fn test_network_graph_gossip_and_pruning() {
+    let key = |byte: u8| secp256k1_private_key(&[byte; 32]);
+    let (alice, bob, carol) = ((key(0x31), key(0x01)), (key(0x32), key(0x02)), (key(0x33), key(0x03)));
+    let alice_bob = ShortChannelId::new(700_000, 1, 0);
+    let bob_carol = ShortChannelId::new(700_001, 1, 0);
+
+    let mut graph = NetworkGraph::new(ChainHash::REGTEST);
+    let (announcement, funding_output) = signed_channel_announcement(alice_bob, alice, bob);
+    assert_eq!(graph.add_channel_announcement(&announcement, &funding_output), Ok(true));
+    assert_eq!(graph.add_channel_announcement(&announcement, &funding_output), Ok(false));
+    let (announcement, funding_output) = signed_channel_announcement(bob_carol, bob, carol);
+    assert_eq!(graph.add_channel_announcement(&announcement, &funding_output), Ok(true));
+
+    let mut testnet = NetworkGraph::new(ChainHash::TESTNET);
+    assert_eq!(testnet.add_channel_announcement(&announcement, &funding_output), Err(GossipError::WrongChain));
+
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.outgoing_channels(&pubkey_from_secret(bob.0)).len(), 2);
+    assert_eq!(graph.channels[&alice_bob].capacity, Amount::from_sat(1_000_000));
+
+    // Each direction is signed by the node it starts from.
+    let channel = &graph.channels[&bob_carol];
+    let (bob_direction, carol_direction) =
+        if channel.node_one == pubkey_from_secret(bob.0) { (0, 1) } else { (1, 0) };
+    assert_eq!(graph.update_channel(&signed_channel_update(bob_carol, bob_direction, 1_000, &bob.0)), Ok(true));
+    assert_eq!(graph.update_channel(&signed_channel_update(bob_carol, carol_direction, 2_000, &carol.0)), Ok(true));
+    assert_eq!(
+        graph.update_channel(&signed_channel_update(ShortChannelId::new(1, 1, 1), 0, 1_000, &bob.0)),
+        Err(GossipError::UnknownChannel(ShortChannelId::new(1, 1, 1).0))
+    );
+
+    let from_bob: Vec<_> = graph
+        .outgoing_channels(&pubkey_from_secret(bob.0))
+        .into_iter()
+        .filter(|channel| channel.policy().is_some())
+        .collect();
+    assert_eq!(from_bob.len(), 1);
+    assert_eq!(from_bob[0].target(), pubkey_from_secret(carol.0));
+
+    // Node announcements only count for nodes with channels.
+    let secp = Secp256k1::new();
+    let mut node_announcement = NodeAnnouncement {
+        signature: secp.sign_ecdsa(&Message::from_digest([0; 32]), &carol.0),
+        features: vec![],
+        timestamp: 10,
+        node_id: pubkey_from_secret(carol.0),
+        rgb_color: [0; 3],
+        alias: [0; 32],
+        addresses: vec![],
+    };
+    node_announcement.signature = secp.sign_ecdsa(&Message::from_digest(node_announcement.signing_hash()), &carol.0);
+    assert_eq!(graph.update_node(&node_announcement), Ok(true));
+    assert_eq!(graph.update_node(&node_announcement), Ok(false));
+    let mut stranger = node_announcement.clone();
+    stranger.node_id = pubkey_from_secret(key(0x34));
+    assert_eq!(graph.update_node(&stranger), Err(GossipError::UnknownNode));
+
+    // alice_bob never got an update, and bob_carol goes stale once its newest
+    // update is two weeks old.
+    assert_eq!(graph.prune_stale_channels(2_000 + STALE_CHANNEL_AGE), vec![alice_bob]);
+    assert!(!graph.nodes.contains_key(&pubkey_from_secret(alice.0)));
+    assert_eq!(graph.prune_stale_channels(2_001 + STALE_CHANNEL_AGE), vec![bob_carol]);
+    assert!(graph.nodes.is_empty());
+}
+
+#[test]
+fn test_grading_parses_cargo_test_output() {
+    let output = "\
+running 5 tests
+test exercises::test::test_01_two_of_two_multisig_witness_script ... ok
+test exercises::test::test_02_build_funding_transaction ... ok
+test exercises::test::test_02_build_funding_psbt ... FAILED
+test exercises_advanced::test::test_01_taproot_to_local ... FAILED
+test exercises::test::test_10_build_commitment_transaction_with_anchors ... ok
+
+failures:
+    exercises::test::test_02_build_funding_psbt
+
+test result: FAILED. 3 passed; 2 failed; 0 ignored; 0 measured; 150 filtered out; finished in 0.10s
+";
+    let results = parse_test_output(output, EXERCISES_TEST_MODULE);
+    assert_eq!(
+        results,
+        vec![
+            ExerciseResult { exercise: 1, passed: 1, failed: vec![] },
+            ExerciseResult { exercise: 2, passed: 1, failed: vec!["test_02_build_funding_psbt".to_string()] },
+            ExerciseResult { exercise: 10, passed: 1, failed: vec![] },
+        ]
+    );
+    assert_eq!(completion_percent(&results), 66);
+    assert_eq!(completion_percent(&[]), 0);
+
+    assert_eq!(test_filter(EXERCISES_TEST_MODULE, Some(7)), "exercises::test::test_07_");
+    assert_eq!(test_filter(EXERCISES_TEST_MODULE, None), "exercises::test::test_");
+}
+
+#[test]
+fn test_exercise_check_hints() {
+    let check = ExerciseCheck { exercise: 7, concept: "the idea", api: "the helpers", solution: "the answer" };
+
+    assert_eq!(check.hints(0), "Exercise 7 failed.\nRun with HINT_LEVEL=1 for a hint.\n");
+    assert_eq!(
+        check.hints(2),
+        "Exercise 7 failed.\nHint 1 (concept): the idea\nHint 2 (API): the helpers\nRun with HINT_LEVEL=3 for a hint.\n"
+    );
+    assert!(check.hints(3).ends_with("Hint 3 (solution): the answer\n"));
+
+    // a passing test returns its result, and a failing one still fails
+    assert_eq!(check.run(|| 42), 42);
+    assert!(std::panic::catch_unwind(|| check.run(|| check.expect_eq("the answer", 1, 2))).is_err());
+
+    // the grader's help says how to ask for them
+    assert!(hints_help().contains("HINT_LEVEL=1 cargo test test_01"));
+}
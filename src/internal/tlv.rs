@@ -0,0 +1,119 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use internal::errors::WireError;
+use internal::wire::Reader;
+
+/// One `type`, `length`, `value` record. The length is implied by `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlvRecord {
+    pub tlv_type: u64,
+    pub value: Vec<u8>,
+}
+
+impl TlvRecord {
+    pub fn new(tlv_type: u64, value: Vec<u8>) -> Self {
+        TlvRecord { tlv_type, value }
+    }
+}
+
+/// BOLT 1 BigSize: a big-endian varint using the smallest of 1, 3, 5 or 9
+/// bytes that fits.
+pub fn write_bigsize(out: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => out.push(value as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+/// Reads a BigSize, rejecting encodings longer than they need to be.
+pub fn read_bigsize(reader: &mut Reader) -> Result<u64, WireError> {
+    let (value, min) = match reader.read_bytes(1)?[0] {
+        0xfd => (u16::from_be_bytes(reader.read_bytes(2)?.try_into().unwrap()) as u64, 0xfd),
+        0xfe => (u32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap()) as u64, 0x10000),
+        0xff => (u64::from_be_bytes(reader.read_bytes(8)?.try_into().unwrap()), 0x1_0000_0000),
+        byte => return Ok(byte as u64),
+    };
+    if value < min {
+        return Err(WireError::NonCanonicalBigSize);
+    }
+    Ok(value)
+}
+
+/// A BOLT 1 truncated integer: big-endian with the leading zero bytes
+/// dropped, so zero is the empty string. Only used as a whole TLV value.
+pub fn encode_tu64(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let leading_zeros = (value.leading_zeros() / 8) as usize;
+    bytes[leading_zeros..].to_vec()
+}
+
+/// Reads a truncated integer of at most `max_len` bytes, rejecting leading
+/// zeros.
+pub fn decode_tu64(bytes: &[u8], max_len: usize) -> Result<u64, WireError> {
+    if bytes.len() > max_len || bytes.first() == Some(&0) {
+        return Err(WireError::InvalidValue("truncated integer"));
+    }
+    Ok(bytes.iter().fold(0, |value, byte| (value << 8) | *byte as u64))
+}
+
+/// Serialize `records` in ascending type order, as BOLT 1 requires.
+pub fn write_tlv_stream(records: &[TlvRecord]) -> Result<Vec<u8>, WireError> {
+    let mut sorted: Vec<&TlvRecord> = records.iter().collect();
+    sorted.sort_by_key(|record| record.tlv_type);
+    for pair in sorted.windows(2) {
+        if pair[0].tlv_type == pair[1].tlv_type {
+            return Err(WireError::TlvTypesNotAscending { previous: pair[0].tlv_type, current: pair[1].tlv_type });
+        }
+    }
+
+    let mut out = Vec::new();
+    for record in sorted {
+        write_bigsize(&mut out, record.tlv_type);
+        write_bigsize(&mut out, record.value.len() as u64);
+        out.extend_from_slice(&record.value);
+    }
+    Ok(out)
+}
+
+/// Parse a whole TLV stream. Types must be strictly ascending, and an even
+/// type missing from `known_types` fails the stream ("it's OK to be odd").
+/// Unknown odd types are returned along with the known ones.
+pub fn read_tlv_stream(bytes: &[u8], known_types: &[u64]) -> Result<Vec<TlvRecord>, WireError> {
+    let mut reader = Reader::new(bytes);
+    let mut records: Vec<TlvRecord> = Vec::new();
+
+    while !reader.is_empty() {
+        let tlv_type = read_bigsize(&mut reader)?;
+        if let Some(previous) = records.last() {
+            if tlv_type <= previous.tlv_type {
+                return Err(WireError::TlvTypesNotAscending { previous: previous.tlv_type, current: tlv_type });
+            }
+        }
+        let len = read_bigsize(&mut reader)?;
+        let len = usize::try_from(len).map_err(|_| WireError::ShortRead)?;
+        let value = reader.read_bytes(len)?.to_vec();
+
+        if tlv_type.is_multiple_of(2) && !known_types.contains(&tlv_type) {
+            return Err(WireError::UnknownEvenTlvType(tlv_type));
+        }
+        records.push(TlvRecord { tlv_type, value });
+    }
+
+    Ok(records)
+}
+
+/// The value of the record with type `tlv_type`, if the stream has one.
+pub fn find_record(records: &[TlvRecord], tlv_type: u64) -> Option<&[u8]> {
+    records.iter().find(|record| record.tlv_type == tlv_type).map(|record| record.value.as_slice())
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use crate::internal;
 use crate::exercises;
+#[cfg(not(target_arch = "wasm32"))]
 use internal::bitcoind_client::BitcoindClient;
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::Hash;
@@ -19,6 +20,7 @@ use bitcoin::{PubkeyHash};
 use bitcoin::{Network};
 use bitcoin::consensus::encode::serialize_hex;
 use internal::hex_utils;
+use internal::errors::{ChannelError, ExerciseError};
 use bitcoin::consensus::{encode};
 use bitcoin::hash_types::Txid;
 use std::env;
@@ -48,6 +50,17 @@ pub fn get_funding_input(input_tx_id_str: String, vout: usize) -> TxIn {
 
 }
 
+/// A made-up txid for the examples and tests to fund channels from, so the
+/// txids they expect never change.
+pub const DUMMY_FUNDING_TXID: &str = "d9334caed6503ebc710d13a5f663f03bec531026d2bc786befdfdb8ef5aad721";
+
+/// The funding input most tests build their transactions on: output 1 of
+/// `DUMMY_FUNDING_TXID`.
+pub fn dummy_funding_txin() -> TxIn {
+    get_funding_input(DUMMY_FUNDING_TXID.to_string(), 1)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn get_unspent_output(bitcoind: BitcoindClient) -> TxIn {
   let utxos = bitcoind.list_unspent().await;
   let utxo = utxos
@@ -56,7 +69,7 @@ pub async fn get_unspent_output(bitcoind: BitcoindClient) -> TxIn {
       .find(|utxo| utxo.amount > 4_999_999 && utxo.amount < 6_000_000)
       .expect("No UTXOs with positive balance found");
 
-    let tx_input = TxIn {
+    TxIn {
         previous_output: OutPoint {
             txid: utxo.txid,
             vout: utxo.vout,
@@ -64,9 +77,7 @@ pub async fn get_unspent_output(bitcoind: BitcoindClient) -> TxIn {
         sequence: Sequence::MAX,
         script_sig: ScriptBuf::new(),
         witness: Witness::new(),
-    };
-
-    tx_input
+    }
 }
 
 pub fn get_htlc_funding_input(input_tx_id_str: String, vout: usize) -> TxIn {
@@ -99,17 +110,17 @@ pub fn build_unsigned_input(txid: String, vout: u32, sequence: Sequence) -> TxIn
     TxIn {
         previous_output: OutPoint {
             txid: input_txid,
-            vout: vout,
+            vout,
         },
-        sequence: sequence,
+        sequence,
         script_sig: ScriptBuf::new(),
         witness: Witness::new(),
     }
 }
 
-pub fn build_output(amount: u64, output_script: ScriptBuf) -> TxOut {
+pub fn build_output(amount: Amount, output_script: ScriptBuf) -> TxOut {
     TxOut {
-        value: Amount::from_sat(amount),
+        value: amount,
         script_pubkey: output_script,
     }
 }
@@ -128,3 +139,128 @@ pub fn build_transaction(
     }
 }
 
+
+/// The BIP 68 input sequence that satisfies the `to_self_delay` OP_CSV of a
+/// `to_local` script.
+pub fn to_self_delay_sequence(to_self_delay: u16) -> Sequence {
+    Sequence::from_height(to_self_delay)
+}
+
+/// Check the feerate of a finalized transaction, witnesses included, against
+/// `min_feerate_per_kw`. `input_values` holds the value of each spent output.
+pub fn verify_min_feerate(tx: &Transaction, input_values: &[Amount], min_feerate_per_kw: u64) -> Result<(), ChannelError> {
+    if input_values.len() != tx.input.len() {
+        return Err(ChannelError::InputValuesMismatch {
+            inputs: tx.input.len(),
+            values: input_values.len(),
+        });
+    }
+
+    let input_value: u64 = input_values.iter().map(|value| value.to_sat()).sum();
+    let output_value: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    if output_value > input_value {
+        return Err(ChannelError::OutputsExceedInputs { input_value, output_value });
+    }
+
+    let fee = input_value - output_value;
+    let feerate_per_kw = fee * 1000 / tx.weight().to_wu();
+    if feerate_per_kw < min_feerate_per_kw {
+        return Err(ChannelError::FeerateTooLow { feerate_per_kw, min_feerate_per_kw });
+    }
+    Ok(())
+}
+
+/// The lower 48 bits of SHA256(opener_payment_basepoint || accepter_payment_basepoint),
+/// which BOLT 3 XORs with the commitment number before encoding it.
+pub fn commitment_number_obscure_factor(
+    opener_payment_basepoint: &BitcoinPublicKey,
+    accepter_payment_basepoint: &BitcoinPublicKey,
+) -> u64 {
+    let mut sha = Sha256::engine();
+    sha.input(&opener_payment_basepoint.inner.serialize());
+    sha.input(&accepter_payment_basepoint.inner.serialize());
+    let hash = Sha256::from_engine(sha).to_byte_array();
+
+    let mut lower_48_bits = [0u8; 8];
+    lower_48_bits[2..].copy_from_slice(&hash[26..]);
+    u64::from_be_bytes(lower_48_bits)
+}
+
+/// The locktime and funding input sequence that carry the obscured commitment
+/// number: its lower 24 bits under a 0x20 byte, and its upper 24 bits under
+/// a 0x80 byte.
+pub fn obscured_commitment_transaction_numbers(obscure_factor: u64, commitment_number: u64) -> (LockTime, Sequence) {
+    let obscured = (obscure_factor ^ commitment_number) & 0xffff_ffff_ffff;
+
+    let locktime = LockTime::from_consensus((0x20 << 24) | (obscured & 0xff_ffff) as u32);
+    let sequence = Sequence((0x80 << 24) | (obscured >> 24) as u32);
+
+    (locktime, sequence)
+}
+
+/// Undo `obscured_commitment_transaction_numbers`: read the commitment number
+/// back out of a commitment's locktime and funding input sequence. Returns
+/// `None` if either is missing its 0x20/0x80 marker byte.
+pub fn recover_commitment_number(tx: &Transaction, obscure_factor: u64) -> Option<u64> {
+    let locktime = tx.lock_time.to_consensus_u32();
+    let sequence = tx.input.first()?.sequence.0;
+
+    if locktime >> 24 != 0x20 || sequence >> 24 != 0x80 {
+        return None;
+    }
+
+    let obscured = (((sequence & 0xff_ffff) as u64) << 24) | (locktime & 0xff_ffff) as u64;
+    Some((obscured ^ obscure_factor) & 0xffff_ffff_ffff)
+}
+
+/// Sort outputs the way BOLT 3 orders commitment outputs: by value, then by
+/// script_pubkey, then by the CLTV expiry of HTLC outputs (`None` for
+/// everything else), so otherwise identical HTLC outputs have a fixed order.
+/// Each output keeps its expiry, so callers can still tell which HTLC ended
+/// up at which index.
+pub fn sort_outputs_bolt3(outputs: Vec<(TxOut, Option<u32>)>) -> Vec<(TxOut, Option<u32>)> {
+    let mut outputs = outputs;
+    outputs.sort_by(|(a, a_cltv), (b, b_cltv)| {
+        a.value
+            .cmp(&b.value)
+            .then_with(|| a.script_pubkey.cmp(&b.script_pubkey))
+            .then_with(|| a_cltv.cmp(b_cltv))
+    });
+    outputs
+}
+
+/// Drop the CLTV expiries `sort_outputs_bolt3` carries along, leaving the
+/// outputs to pass to `build_transaction`.
+pub fn strip_cltv_expiries(outputs: Vec<(TxOut, Option<u32>)>) -> Vec<TxOut> {
+    outputs.into_iter().map(|(output, _)| output).collect()
+}
+
+/// Consensus-serialized hex of `tx`, as accepted by
+/// `bitcoin-cli decoderawtransaction` and block explorers.
+pub fn tx_to_hex(tx: &Transaction) -> String {
+    serialize_hex(tx)
+}
+
+pub fn tx_from_hex(hex: &str) -> Result<Transaction, encode::FromHexError> {
+    encode::deserialize_hex(hex)
+}
+
+/// `to_self_delay` is a BIP 68 block count, so it must fit in 16 bits, and a
+/// delay of 0 would let the broadcaster sweep before it can be punished.
+pub fn check_to_self_delay(to_self_delay: i64) -> Result<(), ExerciseError> {
+    if (1..=u16::MAX as i64).contains(&to_self_delay) {
+        Ok(())
+    } else {
+        Err(ExerciseError::InvalidToSelfDelay(to_self_delay))
+    }
+}
+
+pub fn check_output_amount(name: &'static str, amount: Amount) -> Result<(), ExerciseError> {
+    if amount == Amount::ZERO {
+        return Err(ExerciseError::ZeroValueOutput(name));
+    }
+    if amount > Amount::MAX_MONEY {
+        return Err(ExerciseError::AmountOutOfRange { name, amount: amount.to_sat() });
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use bitcoin::Amount;
+use std::fmt;
+
+/// An amount in millisatoshis, the unit channel balances and HTLCs are
+/// tracked in. Only whole satoshis ever make it on-chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MilliSat(pub u64);
+
+impl MilliSat {
+    pub const ZERO: MilliSat = MilliSat(0);
+
+    pub const fn from_msat(msat: u64) -> MilliSat {
+        MilliSat(msat)
+    }
+
+    pub const fn to_msat(self) -> u64 {
+        self.0
+    }
+
+    /// `None` if `amount` is too large to count in millisatoshis.
+    pub fn from_amount(amount: Amount) -> Option<MilliSat> {
+        amount.to_sat().checked_mul(1000).map(MilliSat)
+    }
+
+    /// The whole satoshis in this amount, dropping any remainder the way
+    /// BOLT 3 does when a balance or HTLC is put on a commitment.
+    pub fn to_amount_floor(self) -> Amount {
+        Amount::from_sat(self.0 / 1000)
+    }
+
+    /// `None` if this amount is not a whole number of satoshis.
+    pub fn to_amount_exact(self) -> Option<Amount> {
+        if self.0.is_multiple_of(1000) {
+            Some(Amount::from_sat(self.0 / 1000))
+        } else {
+            None
+        }
+    }
+
+    pub fn checked_add(self, rhs: MilliSat) -> Option<MilliSat> {
+        self.0.checked_add(rhs.0).map(MilliSat)
+    }
+
+    pub fn checked_sub(self, rhs: MilliSat) -> Option<MilliSat> {
+        self.0.checked_sub(rhs.0).map(MilliSat)
+    }
+}
+
+impl fmt::Display for MilliSat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} msat", self.0)
+    }
+}
@@ -0,0 +1,171 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! The exercises for the browser playground, through `wasm-bindgen`. Keys,
+//! scripts and transactions go in and out as hex strings, so the page can
+//! show learners what their functions build without a Rust toolchain of
+//! its own. Errors are strings the page can print as they are.
+//!
+//! Off wasm the bindings are plain functions, which is how
+//! `tests/wasm_bindings.rs` checks them.
+use crate::exercises::exercises::{
+    build_commitment_transaction, build_funding_transaction, build_refund_transaction, generate_per_commitment_secret,
+    generate_revocation_privkey, generate_revocation_pubkey, to_local, two_of_two_multisig_witness_script,
+};
+use crate::internal;
+use bitcoin::hash_types::Txid;
+use bitcoin::script::ScriptBuf;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use bitcoin::{Amount, OutPoint, PublicKey, Sequence, TxIn, Witness};
+use internal::keys::{DelayedPaymentPubkey, RevocationPubkey};
+use internal::tx_utils::tx_to_hex;
+use std::str::FromStr;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+fn parse_pubkey(name: &str, hex: &str) -> Result<PublicKey, String> {
+    PublicKey::from_str(hex).map_err(|err| format!("{} is not a public key: {}", name, err))
+}
+
+fn parse_secret_key(name: &str, hex: &str) -> Result<SecretKey, String> {
+    SecretKey::from_str(hex).map_err(|err| format!("{} is not a private key: {}", name, err))
+}
+
+fn parse_bytes32(name: &str, hex: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(hex).map_err(|err| format!("{} is not hex: {}", name, err))?;
+    bytes.try_into().map_err(|_| format!("{} is not 32 bytes", name))
+}
+
+fn funding_txin(funding_txid: &str, funding_vout: u32) -> Result<TxIn, String> {
+    let txid = Txid::from_str(funding_txid).map_err(|err| format!("funding txid is not a txid: {}", err))?;
+    Ok(TxIn {
+        previous_output: OutPoint::new(txid, funding_vout),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    })
+}
+
+/// The opcodes of a script, for the page to draw.
+#[wasm_bindgen]
+pub fn script_asm(script_hex: &str) -> Result<String, String> {
+    let bytes = hex::decode(script_hex).map_err(|err| format!("script is not hex: {}", err))?;
+    Ok(ScriptBuf::from_bytes(bytes).to_asm_string())
+}
+
+/// Exercise 1.
+#[wasm_bindgen]
+pub fn multisig_witness_script(pubkey1: &str, pubkey2: &str) -> Result<String, String> {
+    let script =
+        two_of_two_multisig_witness_script(&parse_pubkey("pubkey1", pubkey1)?, &parse_pubkey("pubkey2", pubkey2)?);
+    Ok(hex::encode(script.as_bytes()))
+}
+
+/// Exercise 2.
+#[wasm_bindgen]
+pub fn funding_transaction(
+    input_txid: &str,
+    input_vout: u32,
+    alice_pubkey: &str,
+    bob_pubkey: &str,
+    amount_sat: u64,
+) -> Result<String, String> {
+    let tx = build_funding_transaction(
+        vec![funding_txin(input_txid, input_vout)?],
+        &parse_pubkey("alice pubkey", alice_pubkey)?,
+        &parse_pubkey("bob pubkey", bob_pubkey)?,
+        Amount::from_sat(amount_sat),
+    );
+    Ok(tx_to_hex(&tx))
+}
+
+/// Exercise 3.
+#[wasm_bindgen]
+pub fn refund_transaction(
+    funding_txid: &str,
+    funding_vout: u32,
+    alice_pubkey: &str,
+    bob_pubkey: &str,
+    alice_balance_sat: u64,
+    bob_balance_sat: u64,
+) -> Result<String, String> {
+    let tx = build_refund_transaction(
+        funding_txin(funding_txid, funding_vout)?,
+        parse_pubkey("alice pubkey", alice_pubkey)?,
+        parse_pubkey("bob pubkey", bob_pubkey)?,
+        Amount::from_sat(alice_balance_sat),
+        Amount::from_sat(bob_balance_sat),
+    );
+    Ok(tx_to_hex(&tx))
+}
+
+/// Exercise 4.
+#[wasm_bindgen]
+pub fn revocation_pubkey(countersignatory_basepoint: &str, per_commitment_point: &str) -> Result<String, String> {
+    let pubkey = generate_revocation_pubkey(
+        parse_pubkey("countersignatory basepoint", countersignatory_basepoint)?.inner,
+        parse_pubkey("per-commitment point", per_commitment_point)?.inner,
+    );
+    Ok(pubkey.to_string())
+}
+
+/// Exercise 5.
+#[wasm_bindgen]
+pub fn revocation_privkey(
+    countersignatory_per_commitment_secret: &str,
+    revocation_base_secret: &str,
+) -> Result<String, String> {
+    let secret = generate_revocation_privkey(
+        parse_secret_key("per-commitment secret", countersignatory_per_commitment_secret)?,
+        parse_secret_key("revocation base secret", revocation_base_secret)?,
+    );
+    Ok(hex::encode(secret.secret_bytes()))
+}
+
+/// Exercise 6.
+#[wasm_bindgen]
+pub fn to_local_script(revocation_pubkey: &str, delayed_pubkey: &str, to_self_delay: u32) -> Result<String, String> {
+    let script = to_local(
+        &RevocationPubkey(parse_pubkey("revocation pubkey", revocation_pubkey)?),
+        &DelayedPaymentPubkey(parse_pubkey("delayed pubkey", delayed_pubkey)?),
+        to_self_delay as i64,
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(hex::encode(script.as_bytes()))
+}
+
+/// Exercise 7.
+#[wasm_bindgen]
+pub fn commitment_transaction(
+    funding_txid: &str,
+    funding_vout: u32,
+    revocation_pubkey: &str,
+    delayed_pubkey: &str,
+    remote_pubkey: &str,
+    to_self_delay: u32,
+    local_amount_sat: u64,
+    remote_amount_sat: u64,
+    local_is_funder: bool,
+    feerate_per_kw: u64,
+    obscure_factor: u64,
+    commitment_number: u64,
+) -> Result<String, String> {
+    let tx = build_commitment_transaction(
+        funding_txin(funding_txid, funding_vout)?,
+        &RevocationPubkey(parse_pubkey("revocation pubkey", revocation_pubkey)?),
+        &DelayedPaymentPubkey(parse_pubkey("delayed pubkey", delayed_pubkey)?),
+        parse_pubkey("remote pubkey", remote_pubkey)?,
+        to_self_delay as i64,
+        Amount::from_sat(local_amount_sat),
+        Amount::from_sat(remote_amount_sat),
+        local_is_funder,
+        feerate_per_kw,
+        obscure_factor,
+        commitment_number,
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(tx_to_hex(&tx))
+}
+
+/// Exercise 20.
+#[wasm_bindgen]
+pub fn per_commitment_secret(seed: &str, index: u64) -> Result<String, String> {
+    Ok(hex::encode(generate_per_commitment_secret(&parse_bytes32("seed", seed)?, index)))
+}
@@ -0,0 +1,202 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::open_justice_blob;
+use crate::internal;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::script::ScriptBuf;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, Block, OutPoint, Sequence, Transaction, TxIn, Txid, Witness};
+use internal::errors::{ExerciseError, WatchtowerError};
+use internal::fee_utils::fee_for_weight;
+use internal::tx_utils::{build_output, build_transaction};
+use lightning::chain::chaininterface::BroadcasterInterface;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+
+/// Version, locktime, the input and output counts and the segwit marker.
+const PENALTY_TX_BASE_WEIGHT: u64 = 4 * (4 + 4 + 1 + 1) + 2;
+
+/// Outpoint, empty script_sig and sequence of each swept input.
+const PENALTY_INPUT_WEIGHT: u64 = 4 * (32 + 4 + 1 + 4);
+
+/// A P2WPKH sweep output.
+const PENALTY_OUTPUT_WEIGHT: u64 = 4 * (8 + 1 + 22);
+
+/// `<revocation_sig> 1 <to_local_script>`
+pub const REVOKED_TO_LOCAL_WITNESS_WEIGHT: u64 = 1 + 1 + 73 + 1 + 1 + 1 + 77;
+
+/// `<revocation_sig> <revocation_pubkey> <htlc_script>`, using the larger
+/// received HTLC script so the estimate never underpays.
+pub const REVOKED_HTLC_WITNESS_WEIGHT: u64 = 1 + 1 + 73 + 1 + 33 + 1 + 139;
+
+/// A revoked commitment output the penalty transaction sweeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevokedOutput {
+    pub outpoint: OutPoint,
+    pub value: Amount,
+    pub is_htlc: bool,
+}
+
+pub fn penalty_tx_weight(num_to_local: usize, num_htlcs: usize) -> u64 {
+    PENALTY_TX_BASE_WEIGHT
+        + (PENALTY_INPUT_WEIGHT + REVOKED_TO_LOCAL_WITNESS_WEIGHT) * num_to_local as u64
+        + (PENALTY_INPUT_WEIGHT + REVOKED_HTLC_WITNESS_WEIGHT) * num_htlcs as u64
+        + PENALTY_OUTPUT_WEIGHT
+}
+
+pub fn penalty_tx_fee(num_to_local: usize, num_htlcs: usize, feerate_per_kw: u64) -> Amount {
+    fee_for_weight(feerate_per_kw, penalty_tx_weight(num_to_local, num_htlcs))
+}
+
+/// Build the unsigned penalty transaction sweeping every revoked output to
+/// `destination_script`, paying a fee sized for the revocation witnesses.
+/// Fails if the revoked outputs don't hold more than that fee.
+pub fn build_breach_remedy(
+    revoked_outputs: &[RevokedOutput],
+    destination_script: ScriptBuf,
+    feerate_per_kw: u64,
+) -> Result<Transaction, ExerciseError> {
+    let num_htlcs = revoked_outputs.iter().filter(|output| output.is_htlc).count();
+    let num_to_local = revoked_outputs.len() - num_htlcs;
+
+    let total: Amount = revoked_outputs.iter().map(|output| output.value).sum();
+    let fee = penalty_tx_fee(num_to_local, num_htlcs, feerate_per_kw);
+
+    let txins = revoked_outputs
+        .iter()
+        .map(|output| TxIn {
+            previous_output: output.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        })
+        .collect();
+
+    let value = total
+        .checked_sub(fee)
+        .filter(|value| *value > Amount::ZERO)
+        .ok_or(ExerciseError::InsufficientFunds { needed: fee.to_sat(), available: total.to_sat() })?;
+    let output = build_output(value, destination_script);
+
+    Ok(build_transaction(Version::TWO, LockTime::ZERO, txins, vec![output]))
+}
+
+/// What a tower files a justice blob under: the first 16 bytes of
+/// SHA256(txid) of the revoked commitment. The tower can match it against
+/// the txids it sees on-chain, but can't get the blob's key back from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BreachHint(pub [u8; 16]);
+
+impl BreachHint {
+    pub fn from_txid(revoked_commitment_txid: &Txid) -> BreachHint {
+        let hash = Sha256::hash(revoked_commitment_txid.as_byte_array()).to_byte_array();
+        let mut hint = [0u8; 16];
+        hint.copy_from_slice(&hash[..16]);
+        BreachHint(hint)
+    }
+}
+
+/// The key a justice blob is encrypted with: SHA256(txid || txid) of the
+/// revoked commitment. Only someone who has seen the whole txid can
+/// compute it.
+pub fn breach_key(revoked_commitment_txid: &Txid) -> [u8; 32] {
+    let txid = revoked_commitment_txid.as_byte_array();
+    let mut engine = Sha256::engine();
+    engine.input(txid);
+    engine.input(txid);
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+/// Encrypt `plaintext` with ChaCha20-Poly1305 under a fresh random nonce,
+/// and put the nonce in front of the ciphertext. A client may upload more
+/// than one blob for the same breach, so the nonce can't be fixed.
+pub fn encrypt_blob(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).expect("system randomness is available");
+
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut in_out).unwrap();
+
+    let mut blob = nonce.to_vec();
+    blob.extend_from_slice(&in_out);
+    blob
+}
+
+pub fn decrypt_blob(key: &[u8; 32], blob: &[u8]) -> Result<Vec<u8>, WatchtowerError> {
+    if blob.len() < NONCE_LEN + CHACHA20_POLY1305.tag_len() {
+        return Err(WatchtowerError::BlobTooShort);
+    }
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| WatchtowerError::BlobTooShort)?;
+
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+    let mut in_out = ciphertext.to_vec();
+    let plaintext =
+        key.open_in_place(nonce, Aad::empty(), &mut in_out).map_err(|_| WatchtowerError::DecryptionFailed)?;
+    Ok(plaintext.to_vec())
+}
+
+/// A signed penalty transaction, encrypted under the key of the commitment
+/// it punishes. The tower learns nothing about the channel from it until
+/// that commitment is broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JusticeBlob {
+    pub hint: BreachHint,
+    pub encrypted_justice_tx: Vec<u8>,
+}
+
+/// An altruistic watchtower: it keeps the blobs its clients upload and,
+/// when one of their revoked commitments shows up in a block, broadcasts
+/// the penalty transaction for it. It asks for no reward, so the penalty
+/// pays the client.
+#[derive(Debug, Clone, Default)]
+pub struct Watchtower {
+    blobs: HashMap<BreachHint, Vec<JusticeBlob>>,
+}
+
+impl Watchtower {
+    pub fn new() -> Watchtower {
+        Watchtower::default()
+    }
+
+    pub fn upload(&mut self, blob: JusticeBlob) {
+        self.blobs.entry(blob.hint).or_default().push(blob);
+    }
+
+    /// How many blobs the tower is holding.
+    pub fn len(&self) -> usize {
+        self.blobs.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+
+    /// Check every transaction in a new block against the hints we hold.
+    /// Broadcast each justice transaction that opens for a breach, and
+    /// forget that breach's blobs: they are no use for anything else.
+    /// Returns the justice transactions we broadcast.
+    pub fn block_connected<B: BroadcasterInterface>(&mut self, block: &Block, broadcaster: &B) -> Vec<Transaction> {
+        let mut justice_txs = Vec::new();
+        for tx in &block.txdata {
+            let hint = BreachHint::from_txid(&tx.compute_txid());
+            let Some(blobs) = self.blobs.get(&hint) else {
+                continue;
+            };
+            // anyone can upload under a hint, so skip the blobs that don't open
+            let opened: Vec<Transaction> = blobs.iter().filter_map(|blob| open_justice_blob(blob, tx).ok()).collect();
+            if !opened.is_empty() {
+                self.blobs.remove(&hint);
+                justice_txs.extend(opened);
+            }
+        }
+
+        if !justice_txs.is_empty() {
+            broadcaster.broadcast_transactions(&justice_txs.iter().collect::<Vec<_>>());
+        }
+        justice_txs
+    }
+}
@@ -0,0 +1,1051 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::constants::ChainHash;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::{Amount, ScriptBuf, Transaction, Txid};
+use internal::errors::WireError;
+use internal::onion_utils::{OnionPacket, ONION_PACKET_LEN};
+use internal::payments::{PaymentHash, PaymentPreimage};
+use internal::tlv::{find_record, read_tlv_stream, write_tlv_stream, TlvRecord};
+use internal::units::MilliSat;
+
+pub const INIT_TYPE: u16 = 16;
+pub const PING_TYPE: u16 = 18;
+pub const PONG_TYPE: u16 = 19;
+pub const OPEN_CHANNEL_TYPE: u16 = 32;
+pub const ACCEPT_CHANNEL_TYPE: u16 = 33;
+pub const FUNDING_CREATED_TYPE: u16 = 34;
+pub const FUNDING_SIGNED_TYPE: u16 = 35;
+pub const CHANNEL_READY_TYPE: u16 = 36;
+pub const SHUTDOWN_TYPE: u16 = 38;
+pub const CLOSING_SIGNED_TYPE: u16 = 39;
+pub const TX_ADD_INPUT_TYPE: u16 = 66;
+pub const TX_ADD_OUTPUT_TYPE: u16 = 67;
+pub const TX_COMPLETE_TYPE: u16 = 70;
+pub const UPDATE_ADD_HTLC_TYPE: u16 = 128;
+pub const UPDATE_FULFILL_HTLC_TYPE: u16 = 130;
+pub const COMMITMENT_SIGNED_TYPE: u16 = 132;
+pub const REVOKE_AND_ACK_TYPE: u16 = 133;
+pub const CHANNEL_REESTABLISH_TYPE: u16 = 136;
+pub const CHANNEL_ANNOUNCEMENT_TYPE: u16 = 256;
+pub const NODE_ANNOUNCEMENT_TYPE: u16 = 257;
+pub const CHANNEL_UPDATE_TYPE: u16 = 258;
+
+/// `channel_update` `message_flags` bit that must always be set.
+pub const MESSAGE_FLAGS_MUST_BE_ONE: u8 = 1;
+/// `channel_update` `channel_flags` bits.
+pub const CHANNEL_FLAGS_DIRECTION: u8 = 1;
+pub const CHANNEL_FLAGS_DISABLE: u8 = 2;
+
+/// The most HTLCs BOLT 2 lets either side offer at once.
+pub const MAX_ACCEPTED_HTLCS: u16 = 483;
+
+/// `channel_flags` bit asking for the channel to be announced.
+pub const ANNOUNCE_CHANNEL_FLAG: u8 = 1;
+
+/// `open_channel` and `accept_channel` TLV types.
+pub const UPFRONT_SHUTDOWN_SCRIPT_TLV: u64 = 0;
+pub const CHANNEL_TYPE_TLV: u64 = 1;
+
+/// `closing_signed` TLV type.
+pub const FEE_RANGE_TLV: u64 = 1;
+
+/// BOLT 9 feature bits.
+pub const VAR_ONION_OPTIN_REQUIRED: usize = 8;
+pub const STATIC_REMOTE_KEY_REQUIRED: usize = 12;
+pub const PAYMENT_SECRET_REQUIRED: usize = 14;
+pub const ANCHOR_OUTPUTS_REQUIRED: usize = 20;
+pub const ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED: usize = 22;
+pub const SHUTDOWN_ANYSEGWIT_REQUIRED: usize = 26;
+
+/// A ping asking for at least this many pong bytes wants no reply.
+pub const MAX_PONG_BYTES: u16 = 65531;
+
+/// Reads big-endian fields off the front of a message payload.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        if self.bytes.len() < len {
+            return Err(WireError::ShortRead);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, WireError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, WireError> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, WireError> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], WireError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn read_amount(&mut self) -> Result<Amount, WireError> {
+        Ok(Amount::from_sat(self.read_u64()?))
+    }
+
+    pub fn read_msat(&mut self) -> Result<MilliSat, WireError> {
+        Ok(MilliSat::from_msat(self.read_u64()?))
+    }
+
+    pub fn read_pubkey(&mut self) -> Result<secp256k1PublicKey, WireError> {
+        secp256k1PublicKey::from_slice(self.read_bytes(33)?).map_err(|_| WireError::InvalidValue("public key"))
+    }
+
+    /// A 64-byte compact ECDSA signature.
+    pub fn read_signature(&mut self) -> Result<Signature, WireError> {
+        Signature::from_compact(self.read_bytes(64)?).map_err(|_| WireError::InvalidValue("signature"))
+    }
+
+    /// Reads a `u16` length followed by that many bytes.
+    pub fn read_u16_prefixed(&mut self) -> Result<&'a [u8], WireError> {
+        let len = self.read_u16()? as usize;
+        self.read_bytes(len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Whatever is left, which is where TLV streams and extensions live.
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+pub fn write_u16_prefixed(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Shared by `open_channel` and `accept_channel`.
+fn write_channel_open_tlvs(out: &mut Vec<u8>, upfront_shutdown_script: &Option<ScriptBuf>, channel_type: &Option<Vec<u8>>) {
+    let mut records = Vec::new();
+    if let Some(script) = upfront_shutdown_script {
+        records.push(TlvRecord::new(UPFRONT_SHUTDOWN_SCRIPT_TLV, script.to_bytes()));
+    }
+    if let Some(channel_type) = channel_type {
+        records.push(TlvRecord::new(CHANNEL_TYPE_TLV, channel_type.clone()));
+    }
+    out.extend(write_tlv_stream(&records).unwrap());
+}
+
+fn read_channel_open_tlvs(reader: &mut Reader) -> Result<(Option<ScriptBuf>, Option<Vec<u8>>), WireError> {
+    let records = read_tlv_stream(reader.read_remaining(), &[UPFRONT_SHUTDOWN_SCRIPT_TLV])?;
+    Ok((
+        find_record(&records, UPFRONT_SHUTDOWN_SCRIPT_TLV).map(|script| ScriptBuf::from_bytes(script.to_vec())),
+        find_record(&records, CHANNEL_TYPE_TLV).map(|channel_type| channel_type.to_vec()),
+    ))
+}
+
+/// Feature bits as a big-endian bitfield, with bit 0 the lowest bit of the
+/// last byte and no leading zero bytes.
+pub fn encode_features(bits: &[usize]) -> Vec<u8> {
+    let Some(&highest) = bits.iter().max() else { return Vec::new() };
+    let mut features = vec![0u8; highest / 8 + 1];
+    let len = features.len();
+    for bit in bits {
+        features[len - 1 - bit / 8] |= 1 << (bit % 8);
+    }
+    features
+}
+
+pub fn has_feature(features: &[u8], bit: usize) -> bool {
+    let byte = bit / 8;
+    byte < features.len() && features[features.len() - 1 - byte] & (1 << (bit % 8)) != 0
+}
+
+/// BOLT 1 `init`. The TLV stream is kept as raw bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Init {
+    pub global_features: Vec<u8>,
+    pub features: Vec<u8>,
+    pub tlv_stream: Vec<u8>,
+}
+
+/// BOLT 1 `ping`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ping {
+    pub num_pong_bytes: u16,
+    pub ignored: Vec<u8>,
+}
+
+impl Ping {
+    /// The pong this ping asks for, or `None` if it asks not to be answered.
+    pub fn response(&self) -> Option<Pong> {
+        if self.num_pong_bytes > MAX_PONG_BYTES {
+            return None;
+        }
+        Some(Pong { ignored: vec![0; self.num_pong_bytes as usize] })
+    }
+}
+
+/// BOLT 1 `pong`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pong {
+    pub ignored: Vec<u8>,
+}
+
+/// BOLT 2 `open_channel`, sent by the funder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenChannel {
+    pub chain_hash: ChainHash,
+    pub temporary_channel_id: [u8; 32],
+    pub funding_satoshis: Amount,
+    pub push_msat: MilliSat,
+    pub dust_limit_satoshis: Amount,
+    pub max_htlc_value_in_flight_msat: MilliSat,
+    pub channel_reserve_satoshis: Amount,
+    pub htlc_minimum_msat: MilliSat,
+    pub feerate_per_kw: u32,
+    /// How long the *receiver* has to wait to spend its own `to_local`.
+    pub to_self_delay: u16,
+    pub max_accepted_htlcs: u16,
+    pub funding_pubkey: secp256k1PublicKey,
+    pub revocation_basepoint: secp256k1PublicKey,
+    pub payment_basepoint: secp256k1PublicKey,
+    pub delayed_payment_basepoint: secp256k1PublicKey,
+    pub htlc_basepoint: secp256k1PublicKey,
+    pub first_per_commitment_point: secp256k1PublicKey,
+    pub channel_flags: u8,
+    pub upfront_shutdown_script: Option<ScriptBuf>,
+    pub channel_type: Option<Vec<u8>>,
+}
+
+/// BOLT 2 `accept_channel`, the fundee's answer to `open_channel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptChannel {
+    pub temporary_channel_id: [u8; 32],
+    pub dust_limit_satoshis: Amount,
+    pub max_htlc_value_in_flight_msat: MilliSat,
+    pub channel_reserve_satoshis: Amount,
+    pub htlc_minimum_msat: MilliSat,
+    pub minimum_depth: u32,
+    pub to_self_delay: u16,
+    pub max_accepted_htlcs: u16,
+    pub funding_pubkey: secp256k1PublicKey,
+    pub revocation_basepoint: secp256k1PublicKey,
+    pub payment_basepoint: secp256k1PublicKey,
+    pub delayed_payment_basepoint: secp256k1PublicKey,
+    pub htlc_basepoint: secp256k1PublicKey,
+    pub first_per_commitment_point: secp256k1PublicKey,
+    pub upfront_shutdown_script: Option<ScriptBuf>,
+    pub channel_type: Option<Vec<u8>>,
+}
+
+/// BOLT 2 `funding_created`: the funding outpoint and the funder's
+/// signature on the fundee's first commitment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingCreated {
+    pub temporary_channel_id: [u8; 32],
+    pub funding_txid: Txid,
+    pub funding_output_index: u16,
+    pub signature: Signature,
+}
+
+/// BOLT 2 `funding_signed`: the fundee's signature on the funder's first
+/// commitment, under the real `channel_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingSigned {
+    pub channel_id: [u8; 32],
+    pub signature: Signature,
+}
+
+/// BOLT 2 `channel_ready`, sent once the funding transaction is deep
+/// enough. It carries the point for the sender's second commitment, which
+/// the first `commitment_signed` has to be built with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelReady {
+    pub channel_id: [u8; 32],
+    pub second_per_commitment_point: secp256k1PublicKey,
+}
+
+/// BOLT 2 `shutdown`: the sender won't add any more HTLCs, and wants its
+/// side of the cooperative close paid to `scriptpubkey`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shutdown {
+    pub channel_id: [u8; 32],
+    pub scriptpubkey: ScriptBuf,
+}
+
+/// The fees, inclusive, the sender of a `closing_signed` would agree to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeRange {
+    pub min_fee_satoshis: Amount,
+    pub max_fee_satoshis: Amount,
+}
+
+impl FeeRange {
+    pub fn contains(&self, fee: Amount) -> bool {
+        self.min_fee_satoshis <= fee && fee <= self.max_fee_satoshis
+    }
+}
+
+/// BOLT 2 `closing_signed`: the sender's signature on the closing
+/// transaction paying `fee_satoshis`. The close is agreed once one side
+/// replies with the fee the other proposed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosingSigned {
+    pub channel_id: [u8; 32],
+    pub fee_satoshis: Amount,
+    pub signature: Signature,
+    pub fee_range: Option<FeeRange>,
+}
+
+/// BOLT 2 `tx_add_input`: add an input to the transaction being built. The
+/// whole previous transaction is sent, so the receiver can check the value
+/// and script of the output it spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxAddInput {
+    pub channel_id: [u8; 32],
+    /// Its place in the transaction. Even if the sender is the initiator,
+    /// odd otherwise.
+    pub serial_id: u64,
+    pub prevtx: Transaction,
+    pub prevtx_out: u32,
+    pub sequence: u32,
+}
+
+/// BOLT 2 `tx_add_output`: add an output to the transaction being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxAddOutput {
+    pub channel_id: [u8; 32],
+    pub serial_id: u64,
+    pub sats: Amount,
+    pub script: ScriptBuf,
+}
+
+/// BOLT 2 `tx_complete`: the sender has nothing more to add. Construction
+/// ends once both sides send one in a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxComplete {
+    pub channel_id: [u8; 32],
+}
+
+/// BOLT 2 `update_add_htlc`: offer an HTLC, with the onion telling the
+/// receiver what to do with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateAddHtlc {
+    pub channel_id: [u8; 32],
+    /// Counts up from 0 for each HTLC the sender offers on this channel.
+    pub id: u64,
+    pub amount_msat: MilliSat,
+    pub payment_hash: PaymentHash,
+    pub cltv_expiry: u32,
+    pub onion_routing_packet: Box<OnionPacket>,
+}
+
+/// BOLT 2 `update_fulfill_htlc`: settle an HTLC the sender was offered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateFulfillHtlc {
+    pub channel_id: [u8; 32],
+    pub id: u64,
+    pub payment_preimage: PaymentPreimage,
+}
+
+/// BOLT 2 `commitment_signed`: the sender's signatures on the receiver's
+/// next commitment, and on the second-stage transaction of each of its
+/// HTLC outputs in output order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitmentSigned {
+    pub channel_id: [u8; 32],
+    pub signature: Signature,
+    pub htlc_signatures: Vec<Signature>,
+}
+
+/// BOLT 2 `revoke_and_ack`: the secret of the sender's previous commitment,
+/// and the point its commitment after the next one will use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokeAndAck {
+    pub channel_id: [u8; 32],
+    pub per_commitment_secret: [u8; 32],
+    pub next_per_commitment_point: secp256k1PublicKey,
+}
+
+/// BOLT 2 `channel_reestablish`, sent by both sides on reconnection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelReestablish {
+    pub channel_id: [u8; 32],
+    /// The number of the next `commitment_signed` the sender expects.
+    pub next_commitment_number: u64,
+    /// The number of the next `revoke_and_ack` the sender expects.
+    pub next_revocation_number: u64,
+    /// The last per-commitment secret the sender received from us, or all
+    /// zeroes if it hasn't received any (`option_data_loss_protect`).
+    pub your_last_per_commitment_secret: [u8; 32],
+    pub my_current_per_commitment_point: secp256k1PublicKey,
+}
+
+/// Where a funding output sits on-chain: 3 bytes of block height, 3 of
+/// transaction index and 2 of output index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShortChannelId(pub u64);
+
+impl ShortChannelId {
+    pub fn new(block_height: u32, tx_index: u32, output_index: u16) -> ShortChannelId {
+        ShortChannelId(((block_height as u64) << 40) | ((tx_index as u64) << 16) | output_index as u64)
+    }
+
+    pub fn block_height(&self) -> u32 {
+        (self.0 >> 40) as u32
+    }
+
+    pub fn tx_index(&self) -> u32 {
+        ((self.0 >> 16) & 0xff_ffff) as u32
+    }
+
+    pub fn output_index(&self) -> u16 {
+        self.0 as u16
+    }
+}
+
+impl std::fmt::Display for ShortChannelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}x{}", self.block_height(), self.tx_index(), self.output_index())
+    }
+}
+
+/// BOLT 7 `channel_announcement`: proof that the two nodes control the two
+/// keys of a funding output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelAnnouncement {
+    pub node_signature_1: Signature,
+    pub node_signature_2: Signature,
+    pub bitcoin_signature_1: Signature,
+    pub bitcoin_signature_2: Signature,
+    pub features: Vec<u8>,
+    pub chain_hash: ChainHash,
+    pub short_channel_id: ShortChannelId,
+    pub node_id_1: secp256k1PublicKey,
+    pub node_id_2: secp256k1PublicKey,
+    pub bitcoin_key_1: secp256k1PublicKey,
+    pub bitcoin_key_2: secp256k1PublicKey,
+}
+
+impl ChannelAnnouncement {
+    /// Everything after the four signatures, which is what they sign.
+    pub fn unsigned_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u16_prefixed(&mut out, &self.features);
+        out.extend_from_slice(self.chain_hash.as_bytes());
+        out.extend_from_slice(&self.short_channel_id.0.to_be_bytes());
+        for key in [self.node_id_1, self.node_id_2, self.bitcoin_key_1, self.bitcoin_key_2] {
+            out.extend_from_slice(&key.serialize());
+        }
+        out
+    }
+
+    /// The double-SHA256 all four signatures commit to.
+    pub fn signing_hash(&self) -> [u8; 32] {
+        sha256d::Hash::hash(&self.unsigned_bytes()).to_byte_array()
+    }
+}
+
+/// An address in a `node_announcement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetAddress {
+    Ipv4 { addr: [u8; 4], port: u16 },
+    Ipv6 { addr: [u8; 16], port: u16 },
+    OnionV3 { addr: [u8; 35], port: u16 },
+    Hostname { hostname: String, port: u16 },
+    /// An address type we don't know. Since we can't tell how long it is,
+    /// this holds it and everything after it.
+    Unknown { addr_type: u8, data: Vec<u8> },
+}
+
+fn write_addresses(out: &mut Vec<u8>, addresses: &[NetAddress]) {
+    let mut data = Vec::new();
+    for address in addresses {
+        let (addr_type, port) = match address {
+            NetAddress::Ipv4 { addr, port } => {
+                data.push(1);
+                data.extend_from_slice(addr);
+                (1, *port)
+            }
+            NetAddress::Ipv6 { addr, port } => {
+                data.push(2);
+                data.extend_from_slice(addr);
+                (2, *port)
+            }
+            NetAddress::OnionV3 { addr, port } => {
+                data.push(4);
+                data.extend_from_slice(addr);
+                (4, *port)
+            }
+            NetAddress::Hostname { hostname, port } => {
+                data.push(5);
+                data.push(hostname.len() as u8);
+                data.extend_from_slice(hostname.as_bytes());
+                (5, *port)
+            }
+            NetAddress::Unknown { addr_type, data: rest } => {
+                data.push(*addr_type);
+                data.extend_from_slice(rest);
+                continue;
+            }
+        };
+        data.extend_from_slice(&port.to_be_bytes());
+    }
+    write_u16_prefixed(out, &data);
+}
+
+fn read_addresses(bytes: &[u8]) -> Result<Vec<NetAddress>, WireError> {
+    let mut reader = Reader::new(bytes);
+    let mut addresses = Vec::new();
+    while !reader.is_empty() {
+        let address = match reader.read_bytes(1)?[0] {
+            1 => NetAddress::Ipv4 { addr: reader.read_array()?, port: reader.read_u16()? },
+            2 => NetAddress::Ipv6 { addr: reader.read_array()?, port: reader.read_u16()? },
+            4 => NetAddress::OnionV3 { addr: reader.read_array()?, port: reader.read_u16()? },
+            5 => {
+                let len = reader.read_bytes(1)?[0] as usize;
+                let hostname = reader.read_bytes(len)?;
+                let hostname =
+                    String::from_utf8(hostname.to_vec()).map_err(|_| WireError::InvalidValue("hostname"))?;
+                NetAddress::Hostname { hostname, port: reader.read_u16()? }
+            }
+            addr_type => {
+                addresses.push(NetAddress::Unknown { addr_type, data: reader.read_remaining().to_vec() });
+                break;
+            }
+        };
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+/// BOLT 7 `node_announcement`, signed by the node it describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeAnnouncement {
+    pub signature: Signature,
+    pub features: Vec<u8>,
+    pub timestamp: u32,
+    pub node_id: secp256k1PublicKey,
+    pub rgb_color: [u8; 3],
+    /// UTF-8, padded with zero bytes.
+    pub alias: [u8; 32],
+    pub addresses: Vec<NetAddress>,
+}
+
+impl NodeAnnouncement {
+    pub fn unsigned_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u16_prefixed(&mut out, &self.features);
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&self.node_id.serialize());
+        out.extend_from_slice(&self.rgb_color);
+        out.extend_from_slice(&self.alias);
+        write_addresses(&mut out, &self.addresses);
+        out
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        sha256d::Hash::hash(&self.unsigned_bytes()).to_byte_array()
+    }
+
+    /// The alias without its zero padding.
+    pub fn alias_str(&self) -> String {
+        let end = self.alias.iter().position(|byte| *byte == 0).unwrap_or(self.alias.len());
+        String::from_utf8_lossy(&self.alias[..end]).into_owned()
+    }
+}
+
+/// BOLT 7 `channel_update`: one direction's routing policy for a channel,
+/// signed by the node at the start of that direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelUpdate {
+    pub signature: Signature,
+    pub chain_hash: ChainHash,
+    pub short_channel_id: ShortChannelId,
+    pub timestamp: u32,
+    pub message_flags: u8,
+    pub channel_flags: u8,
+    pub cltv_expiry_delta: u16,
+    pub htlc_minimum_msat: MilliSat,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub htlc_maximum_msat: MilliSat,
+}
+
+impl ChannelUpdate {
+    pub fn unsigned_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(self.chain_hash.as_bytes());
+        out.extend_from_slice(&self.short_channel_id.0.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.push(self.message_flags);
+        out.push(self.channel_flags);
+        out.extend_from_slice(&self.cltv_expiry_delta.to_be_bytes());
+        out.extend_from_slice(&self.htlc_minimum_msat.to_msat().to_be_bytes());
+        out.extend_from_slice(&self.fee_base_msat.to_be_bytes());
+        out.extend_from_slice(&self.fee_proportional_millionths.to_be_bytes());
+        out.extend_from_slice(&self.htlc_maximum_msat.to_msat().to_be_bytes());
+        out
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        sha256d::Hash::hash(&self.unsigned_bytes()).to_byte_array()
+    }
+
+    /// `0` if `node_id_1` signed this update, `1` if `node_id_2` did.
+    pub fn direction(&self) -> u8 {
+        self.channel_flags & CHANNEL_FLAGS_DIRECTION
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.channel_flags & CHANNEL_FLAGS_DISABLE != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Init(Init),
+    Ping(Ping),
+    Pong(Pong),
+    OpenChannel(OpenChannel),
+    AcceptChannel(AcceptChannel),
+    FundingCreated(FundingCreated),
+    FundingSigned(FundingSigned),
+    ChannelReady(ChannelReady),
+    Shutdown(Shutdown),
+    ClosingSigned(ClosingSigned),
+    TxAddInput(TxAddInput),
+    TxAddOutput(TxAddOutput),
+    TxComplete(TxComplete),
+    UpdateAddHtlc(UpdateAddHtlc),
+    UpdateFulfillHtlc(UpdateFulfillHtlc),
+    CommitmentSigned(CommitmentSigned),
+    RevokeAndAck(RevokeAndAck),
+    ChannelReestablish(ChannelReestablish),
+    ChannelAnnouncement(ChannelAnnouncement),
+    NodeAnnouncement(NodeAnnouncement),
+    ChannelUpdate(ChannelUpdate),
+    /// An odd type we don't understand, which BOLT 1 says to ignore.
+    Unknown { msg_type: u16, payload: Vec<u8> },
+}
+
+impl Message {
+    pub fn msg_type(&self) -> u16 {
+        match self {
+            Message::Init(_) => INIT_TYPE,
+            Message::Ping(_) => PING_TYPE,
+            Message::Pong(_) => PONG_TYPE,
+            Message::OpenChannel(_) => OPEN_CHANNEL_TYPE,
+            Message::AcceptChannel(_) => ACCEPT_CHANNEL_TYPE,
+            Message::FundingCreated(_) => FUNDING_CREATED_TYPE,
+            Message::FundingSigned(_) => FUNDING_SIGNED_TYPE,
+            Message::ChannelReady(_) => CHANNEL_READY_TYPE,
+            Message::Shutdown(_) => SHUTDOWN_TYPE,
+            Message::ClosingSigned(_) => CLOSING_SIGNED_TYPE,
+            Message::TxAddInput(_) => TX_ADD_INPUT_TYPE,
+            Message::TxAddOutput(_) => TX_ADD_OUTPUT_TYPE,
+            Message::TxComplete(_) => TX_COMPLETE_TYPE,
+            Message::UpdateAddHtlc(_) => UPDATE_ADD_HTLC_TYPE,
+            Message::UpdateFulfillHtlc(_) => UPDATE_FULFILL_HTLC_TYPE,
+            Message::CommitmentSigned(_) => COMMITMENT_SIGNED_TYPE,
+            Message::RevokeAndAck(_) => REVOKE_AND_ACK_TYPE,
+            Message::ChannelReestablish(_) => CHANNEL_REESTABLISH_TYPE,
+            Message::ChannelAnnouncement(_) => CHANNEL_ANNOUNCEMENT_TYPE,
+            Message::NodeAnnouncement(_) => NODE_ANNOUNCEMENT_TYPE,
+            Message::ChannelUpdate(_) => CHANNEL_UPDATE_TYPE,
+            Message::Unknown { msg_type, .. } => *msg_type,
+        }
+    }
+
+    /// The 2-byte type followed by the payload, ready to be encrypted.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = self.msg_type().to_be_bytes().to_vec();
+        match self {
+            Message::Init(init) => {
+                write_u16_prefixed(&mut out, &init.global_features);
+                write_u16_prefixed(&mut out, &init.features);
+                out.extend_from_slice(&init.tlv_stream);
+            }
+            Message::Ping(ping) => {
+                out.extend_from_slice(&ping.num_pong_bytes.to_be_bytes());
+                write_u16_prefixed(&mut out, &ping.ignored);
+            }
+            Message::Pong(pong) => write_u16_prefixed(&mut out, &pong.ignored),
+            Message::OpenChannel(open) => {
+                out.extend_from_slice(open.chain_hash.as_bytes());
+                out.extend_from_slice(&open.temporary_channel_id);
+                out.extend_from_slice(&open.funding_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&open.push_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&open.dust_limit_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&open.max_htlc_value_in_flight_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&open.channel_reserve_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&open.htlc_minimum_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&open.feerate_per_kw.to_be_bytes());
+                out.extend_from_slice(&open.to_self_delay.to_be_bytes());
+                out.extend_from_slice(&open.max_accepted_htlcs.to_be_bytes());
+                for point in [
+                    open.funding_pubkey,
+                    open.revocation_basepoint,
+                    open.payment_basepoint,
+                    open.delayed_payment_basepoint,
+                    open.htlc_basepoint,
+                    open.first_per_commitment_point,
+                ] {
+                    out.extend_from_slice(&point.serialize());
+                }
+                out.push(open.channel_flags);
+                write_channel_open_tlvs(&mut out, &open.upfront_shutdown_script, &open.channel_type);
+            }
+            Message::AcceptChannel(accept) => {
+                out.extend_from_slice(&accept.temporary_channel_id);
+                out.extend_from_slice(&accept.dust_limit_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&accept.max_htlc_value_in_flight_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&accept.channel_reserve_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&accept.htlc_minimum_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&accept.minimum_depth.to_be_bytes());
+                out.extend_from_slice(&accept.to_self_delay.to_be_bytes());
+                out.extend_from_slice(&accept.max_accepted_htlcs.to_be_bytes());
+                for point in [
+                    accept.funding_pubkey,
+                    accept.revocation_basepoint,
+                    accept.payment_basepoint,
+                    accept.delayed_payment_basepoint,
+                    accept.htlc_basepoint,
+                    accept.first_per_commitment_point,
+                ] {
+                    out.extend_from_slice(&point.serialize());
+                }
+                write_channel_open_tlvs(&mut out, &accept.upfront_shutdown_script, &accept.channel_type);
+            }
+            Message::FundingCreated(created) => {
+                out.extend_from_slice(&created.temporary_channel_id);
+                out.extend_from_slice(created.funding_txid.as_byte_array());
+                out.extend_from_slice(&created.funding_output_index.to_be_bytes());
+                out.extend_from_slice(&created.signature.serialize_compact());
+            }
+            Message::FundingSigned(signed) => {
+                out.extend_from_slice(&signed.channel_id);
+                out.extend_from_slice(&signed.signature.serialize_compact());
+            }
+            Message::ChannelReady(ready) => {
+                out.extend_from_slice(&ready.channel_id);
+                out.extend_from_slice(&ready.second_per_commitment_point.serialize());
+            }
+            Message::Shutdown(shutdown) => {
+                out.extend_from_slice(&shutdown.channel_id);
+                write_u16_prefixed(&mut out, shutdown.scriptpubkey.as_bytes());
+            }
+            Message::ClosingSigned(signed) => {
+                out.extend_from_slice(&signed.channel_id);
+                out.extend_from_slice(&signed.fee_satoshis.to_sat().to_be_bytes());
+                out.extend_from_slice(&signed.signature.serialize_compact());
+                let mut records = Vec::new();
+                if let Some(fee_range) = signed.fee_range {
+                    let mut value = fee_range.min_fee_satoshis.to_sat().to_be_bytes().to_vec();
+                    value.extend_from_slice(&fee_range.max_fee_satoshis.to_sat().to_be_bytes());
+                    records.push(TlvRecord::new(FEE_RANGE_TLV, value));
+                }
+                out.extend(write_tlv_stream(&records).unwrap());
+            }
+            Message::TxAddInput(add) => {
+                out.extend_from_slice(&add.channel_id);
+                out.extend_from_slice(&add.serial_id.to_be_bytes());
+                write_u16_prefixed(&mut out, &serialize(&add.prevtx));
+                out.extend_from_slice(&add.prevtx_out.to_be_bytes());
+                out.extend_from_slice(&add.sequence.to_be_bytes());
+            }
+            Message::TxAddOutput(add) => {
+                out.extend_from_slice(&add.channel_id);
+                out.extend_from_slice(&add.serial_id.to_be_bytes());
+                out.extend_from_slice(&add.sats.to_sat().to_be_bytes());
+                write_u16_prefixed(&mut out, add.script.as_bytes());
+            }
+            Message::TxComplete(complete) => out.extend_from_slice(&complete.channel_id),
+            Message::UpdateAddHtlc(add) => {
+                out.extend_from_slice(&add.channel_id);
+                out.extend_from_slice(&add.id.to_be_bytes());
+                out.extend_from_slice(&add.amount_msat.to_msat().to_be_bytes());
+                out.extend_from_slice(&add.payment_hash.0);
+                out.extend_from_slice(&add.cltv_expiry.to_be_bytes());
+                out.extend(add.onion_routing_packet.encode());
+            }
+            Message::UpdateFulfillHtlc(fulfill) => {
+                out.extend_from_slice(&fulfill.channel_id);
+                out.extend_from_slice(&fulfill.id.to_be_bytes());
+                out.extend_from_slice(&fulfill.payment_preimage.0);
+            }
+            Message::CommitmentSigned(signed) => {
+                out.extend_from_slice(&signed.channel_id);
+                out.extend_from_slice(&signed.signature.serialize_compact());
+                out.extend_from_slice(&(signed.htlc_signatures.len() as u16).to_be_bytes());
+                for signature in &signed.htlc_signatures {
+                    out.extend_from_slice(&signature.serialize_compact());
+                }
+            }
+            Message::RevokeAndAck(revoke) => {
+                out.extend_from_slice(&revoke.channel_id);
+                out.extend_from_slice(&revoke.per_commitment_secret);
+                out.extend_from_slice(&revoke.next_per_commitment_point.serialize());
+            }
+            Message::ChannelReestablish(reestablish) => {
+                out.extend_from_slice(&reestablish.channel_id);
+                out.extend_from_slice(&reestablish.next_commitment_number.to_be_bytes());
+                out.extend_from_slice(&reestablish.next_revocation_number.to_be_bytes());
+                out.extend_from_slice(&reestablish.your_last_per_commitment_secret);
+                out.extend_from_slice(&reestablish.my_current_per_commitment_point.serialize());
+            }
+            Message::ChannelAnnouncement(announcement) => {
+                for signature in [
+                    announcement.node_signature_1,
+                    announcement.node_signature_2,
+                    announcement.bitcoin_signature_1,
+                    announcement.bitcoin_signature_2,
+                ] {
+                    out.extend_from_slice(&signature.serialize_compact());
+                }
+                out.extend(announcement.unsigned_bytes());
+            }
+            Message::NodeAnnouncement(announcement) => {
+                out.extend_from_slice(&announcement.signature.serialize_compact());
+                out.extend(announcement.unsigned_bytes());
+            }
+            Message::ChannelUpdate(update) => {
+                out.extend_from_slice(&update.signature.serialize_compact());
+                out.extend(update.unsigned_bytes());
+            }
+            Message::Unknown { payload, .. } => out.extend_from_slice(payload),
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, WireError> {
+        let mut reader = Reader::new(bytes);
+        let msg_type = reader.read_u16()?;
+        let message = match msg_type {
+            INIT_TYPE => Message::Init(Init {
+                global_features: reader.read_u16_prefixed()?.to_vec(),
+                features: reader.read_u16_prefixed()?.to_vec(),
+                tlv_stream: reader.read_remaining().to_vec(),
+            }),
+            PING_TYPE => Message::Ping(Ping {
+                num_pong_bytes: reader.read_u16()?,
+                ignored: reader.read_u16_prefixed()?.to_vec(),
+            }),
+            PONG_TYPE => Message::Pong(Pong { ignored: reader.read_u16_prefixed()?.to_vec() }),
+            OPEN_CHANNEL_TYPE => {
+                let chain_hash = ChainHash::from(reader.read_array::<32>()?);
+                let temporary_channel_id = reader.read_array()?;
+                let funding_satoshis = reader.read_amount()?;
+                let push_msat = reader.read_msat()?;
+                let dust_limit_satoshis = reader.read_amount()?;
+                let max_htlc_value_in_flight_msat = reader.read_msat()?;
+                let channel_reserve_satoshis = reader.read_amount()?;
+                let htlc_minimum_msat = reader.read_msat()?;
+                let feerate_per_kw = reader.read_u32()?;
+                let to_self_delay = reader.read_u16()?;
+                let max_accepted_htlcs = reader.read_u16()?;
+                let funding_pubkey = reader.read_pubkey()?;
+                let revocation_basepoint = reader.read_pubkey()?;
+                let payment_basepoint = reader.read_pubkey()?;
+                let delayed_payment_basepoint = reader.read_pubkey()?;
+                let htlc_basepoint = reader.read_pubkey()?;
+                let first_per_commitment_point = reader.read_pubkey()?;
+                let channel_flags = reader.read_bytes(1)?[0];
+                let (upfront_shutdown_script, channel_type) = read_channel_open_tlvs(&mut reader)?;
+                Message::OpenChannel(OpenChannel {
+                    chain_hash,
+                    temporary_channel_id,
+                    funding_satoshis,
+                    push_msat,
+                    dust_limit_satoshis,
+                    max_htlc_value_in_flight_msat,
+                    channel_reserve_satoshis,
+                    htlc_minimum_msat,
+                    feerate_per_kw,
+                    to_self_delay,
+                    max_accepted_htlcs,
+                    funding_pubkey,
+                    revocation_basepoint,
+                    payment_basepoint,
+                    delayed_payment_basepoint,
+                    htlc_basepoint,
+                    first_per_commitment_point,
+                    channel_flags,
+                    upfront_shutdown_script,
+                    channel_type,
+                })
+            }
+            ACCEPT_CHANNEL_TYPE => {
+                let temporary_channel_id = reader.read_array()?;
+                let dust_limit_satoshis = reader.read_amount()?;
+                let max_htlc_value_in_flight_msat = reader.read_msat()?;
+                let channel_reserve_satoshis = reader.read_amount()?;
+                let htlc_minimum_msat = reader.read_msat()?;
+                let minimum_depth = reader.read_u32()?;
+                let to_self_delay = reader.read_u16()?;
+                let max_accepted_htlcs = reader.read_u16()?;
+                let funding_pubkey = reader.read_pubkey()?;
+                let revocation_basepoint = reader.read_pubkey()?;
+                let payment_basepoint = reader.read_pubkey()?;
+                let delayed_payment_basepoint = reader.read_pubkey()?;
+                let htlc_basepoint = reader.read_pubkey()?;
+                let first_per_commitment_point = reader.read_pubkey()?;
+                let (upfront_shutdown_script, channel_type) = read_channel_open_tlvs(&mut reader)?;
+                Message::AcceptChannel(AcceptChannel {
+                    temporary_channel_id,
+                    dust_limit_satoshis,
+                    max_htlc_value_in_flight_msat,
+                    channel_reserve_satoshis,
+                    htlc_minimum_msat,
+                    minimum_depth,
+                    to_self_delay,
+                    max_accepted_htlcs,
+                    funding_pubkey,
+                    revocation_basepoint,
+                    payment_basepoint,
+                    delayed_payment_basepoint,
+                    htlc_basepoint,
+                    first_per_commitment_point,
+                    upfront_shutdown_script,
+                    channel_type,
+                })
+            }
+            FUNDING_CREATED_TYPE => Message::FundingCreated(FundingCreated {
+                temporary_channel_id: reader.read_array()?,
+                funding_txid: Txid::from_byte_array(reader.read_array()?),
+                funding_output_index: reader.read_u16()?,
+                signature: reader.read_signature()?,
+            }),
+            FUNDING_SIGNED_TYPE => Message::FundingSigned(FundingSigned {
+                channel_id: reader.read_array()?,
+                signature: reader.read_signature()?,
+            }),
+            CHANNEL_READY_TYPE => Message::ChannelReady(ChannelReady {
+                channel_id: reader.read_array()?,
+                second_per_commitment_point: reader.read_pubkey()?,
+            }),
+            SHUTDOWN_TYPE => Message::Shutdown(Shutdown {
+                channel_id: reader.read_array()?,
+                scriptpubkey: ScriptBuf::from(reader.read_u16_prefixed()?.to_vec()),
+            }),
+            CLOSING_SIGNED_TYPE => {
+                let channel_id = reader.read_array()?;
+                let fee_satoshis = reader.read_amount()?;
+                let signature = reader.read_signature()?;
+                let records = read_tlv_stream(reader.read_remaining(), &[])?;
+                let fee_range = match find_record(&records, FEE_RANGE_TLV) {
+                    Some(value) if value.len() == 16 => {
+                        let mut value = Reader::new(value);
+                        Some(FeeRange { min_fee_satoshis: value.read_amount()?, max_fee_satoshis: value.read_amount()? })
+                    }
+                    Some(_) => return Err(WireError::InvalidValue("fee_range")),
+                    None => None,
+                };
+                Message::ClosingSigned(ClosingSigned { channel_id, fee_satoshis, signature, fee_range })
+            }
+            TX_ADD_INPUT_TYPE => Message::TxAddInput(TxAddInput {
+                channel_id: reader.read_array()?,
+                serial_id: reader.read_u64()?,
+                prevtx: deserialize(reader.read_u16_prefixed()?).map_err(|_| WireError::InvalidValue("prevtx"))?,
+                prevtx_out: reader.read_u32()?,
+                sequence: reader.read_u32()?,
+            }),
+            TX_ADD_OUTPUT_TYPE => Message::TxAddOutput(TxAddOutput {
+                channel_id: reader.read_array()?,
+                serial_id: reader.read_u64()?,
+                sats: reader.read_amount()?,
+                script: ScriptBuf::from(reader.read_u16_prefixed()?.to_vec()),
+            }),
+            TX_COMPLETE_TYPE => Message::TxComplete(TxComplete { channel_id: reader.read_array()? }),
+            UPDATE_ADD_HTLC_TYPE => Message::UpdateAddHtlc(UpdateAddHtlc {
+                channel_id: reader.read_array()?,
+                id: reader.read_u64()?,
+                amount_msat: reader.read_msat()?,
+                payment_hash: PaymentHash(reader.read_array()?),
+                cltv_expiry: reader.read_u32()?,
+                onion_routing_packet: Box::new(
+                    OnionPacket::decode(&reader.read_array::<ONION_PACKET_LEN>()?)
+                        .map_err(|_| WireError::InvalidValue("onion_routing_packet"))?,
+                ),
+            }),
+            UPDATE_FULFILL_HTLC_TYPE => Message::UpdateFulfillHtlc(UpdateFulfillHtlc {
+                channel_id: reader.read_array()?,
+                id: reader.read_u64()?,
+                payment_preimage: PaymentPreimage(reader.read_array()?),
+            }),
+            COMMITMENT_SIGNED_TYPE => {
+                let channel_id = reader.read_array()?;
+                let signature = reader.read_signature()?;
+                let num_htlcs = reader.read_u16()?;
+                let htlc_signatures =
+                    (0..num_htlcs).map(|_| reader.read_signature()).collect::<Result<Vec<_>, _>>()?;
+                Message::CommitmentSigned(CommitmentSigned { channel_id, signature, htlc_signatures })
+            }
+            REVOKE_AND_ACK_TYPE => Message::RevokeAndAck(RevokeAndAck {
+                channel_id: reader.read_array()?,
+                per_commitment_secret: reader.read_array()?,
+                next_per_commitment_point: reader.read_pubkey()?,
+            }),
+            CHANNEL_REESTABLISH_TYPE => Message::ChannelReestablish(ChannelReestablish {
+                channel_id: reader.read_array()?,
+                next_commitment_number: reader.read_u64()?,
+                next_revocation_number: reader.read_u64()?,
+                your_last_per_commitment_secret: reader.read_array()?,
+                my_current_per_commitment_point: reader.read_pubkey()?,
+            }),
+            CHANNEL_ANNOUNCEMENT_TYPE => Message::ChannelAnnouncement(ChannelAnnouncement {
+                node_signature_1: reader.read_signature()?,
+                node_signature_2: reader.read_signature()?,
+                bitcoin_signature_1: reader.read_signature()?,
+                bitcoin_signature_2: reader.read_signature()?,
+                features: reader.read_u16_prefixed()?.to_vec(),
+                chain_hash: ChainHash::from(reader.read_array::<32>()?),
+                short_channel_id: ShortChannelId(reader.read_u64()?),
+                node_id_1: reader.read_pubkey()?,
+                node_id_2: reader.read_pubkey()?,
+                bitcoin_key_1: reader.read_pubkey()?,
+                bitcoin_key_2: reader.read_pubkey()?,
+            }),
+            NODE_ANNOUNCEMENT_TYPE => Message::NodeAnnouncement(NodeAnnouncement {
+                signature: reader.read_signature()?,
+                features: reader.read_u16_prefixed()?.to_vec(),
+                timestamp: reader.read_u32()?,
+                node_id: reader.read_pubkey()?,
+                rgb_color: reader.read_array()?,
+                alias: reader.read_array()?,
+                addresses: read_addresses(reader.read_u16_prefixed()?)?,
+            }),
+            CHANNEL_UPDATE_TYPE => Message::ChannelUpdate(ChannelUpdate {
+                signature: reader.read_signature()?,
+                chain_hash: ChainHash::from(reader.read_array::<32>()?),
+                short_channel_id: ShortChannelId(reader.read_u64()?),
+                timestamp: reader.read_u32()?,
+                message_flags: reader.read_bytes(1)?[0],
+                channel_flags: reader.read_bytes(1)?[0],
+                cltv_expiry_delta: reader.read_u16()?,
+                htlc_minimum_msat: reader.read_msat()?,
+                fee_base_msat: reader.read_u32()?,
+                fee_proportional_millionths: reader.read_u32()?,
+                htlc_maximum_msat: reader.read_msat()?,
+            }),
+            t if t.is_multiple_of(2) => return Err(WireError::UnknownEvenType(t)),
+            t => Message::Unknown { msg_type: t, payload: reader.read_remaining().to_vec() },
+        };
+        Ok(message)
+    }
+}
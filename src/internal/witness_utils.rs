@@ -0,0 +1,21 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::internal;
+use bitcoin::script::{Script, ScriptBuf};
+use bitcoin::Witness;
+use internal::errors::ChannelError;
+
+/// Check that a P2WSH spend ends with the witness script committed to by
+/// `output_script`.
+pub fn validate_p2wsh_witness(witness: &Witness, output_script: &ScriptBuf) -> Result<(), ChannelError> {
+    if !output_script.is_p2wsh() {
+        return Err(ChannelError::NotP2wsh);
+    }
+
+    let witness_script = witness.last().ok_or(ChannelError::EmptyWitness)?;
+
+    if Script::from_bytes(witness_script).to_p2wsh() != *output_script {
+        return Err(ChannelError::WitnessScriptMismatch);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
 pub mod exercises;
 pub mod exercises_appendix;
+pub mod exercises_advanced;
 pub mod internal;
-pub mod interactive;
\ No newline at end of file
+#[cfg(not(target_arch = "wasm32"))]
+pub mod interactive;
+pub mod simulation;
\ No newline at end of file
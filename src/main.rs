@@ -1,16 +1,18 @@
 #![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
 use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(not(target_arch = "wasm32"))]
 use pl_00_intro::interactive::{funding, refund, commit, htlc, htlc_timeout, htlc_demo, htlc_demo2, mempool};
+#[cfg(not(target_arch = "wasm32"))]
 use pl_00_intro::interactive::mempool::MempoolCommand;
 use sha2::{Sha256, Digest};
 use ripemd::{Ripemd160};
 
 /// Main CLI structure
+#[cfg(not(target_arch = "wasm32"))]
 #[derive(Parser)]
 #[command(name = "Programming Lightning CLI")]
 #[command(version = "1.0")]
 #[command(about = "CLI for Programming Lightning Workshop", long_about = None)]
-
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -18,6 +20,7 @@ struct Cli {
 
 
 /// CLI Subcommands
+#[cfg(not(target_arch = "wasm32"))]
 #[derive(Subcommand)]
 enum Commands {
     Funding,
@@ -66,6 +69,12 @@ enum Commands {
   }
 
 
+// The CLI drives a local bitcoind, which a wasm32 build has no way to reach.
+// The browser playground uses the library's `wasm` bindings instead.
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -0,0 +1,651 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{
+    build_all_htlc_transactions, handle_revoke_and_ack, next_htlc_state, sign_commitment_transaction,
+};
+use crate::internal;
+use crate::simulation::node::{finalize_holder_commitment, SimulatedChannel};
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hash_types::Txid;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction};
+use internal::channel::{
+    ChannelParams, CommitmentEvent, CommitmentTransactionBuilder, CounterpartyCommitments, HtlcState,
+};
+use internal::errors::{ChannelError, WireError};
+use internal::fee_utils::HtlcFeeMode;
+use internal::htlc_utils::{Htlc, HtlcOutput};
+use internal::key_utils::{derive_private_key, verify_commitment_signature};
+use internal::keys::{ChannelKeyManager, ChannelKeys, ChannelPublicKeys};
+use internal::onion_utils::{OnionPacket, ONION_PACKET_LEN};
+use internal::payments::{PaymentHash, PaymentPreimage};
+use internal::shachain::ShachainStore;
+use internal::sighash_utils::compute_p2wsh_sighash_with_type;
+use internal::sign_utils::generate_p2wsh_signature;
+use internal::units::MilliSat;
+use internal::wire::{
+    write_u16_prefixed, ChannelReady, CommitmentSigned, Reader, RevokeAndAck, UpdateAddHtlc, UpdateFulfillHtlc,
+};
+
+/// An HTLC on a `Channel`, with the onion it was offered with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelHtlc {
+    /// Counts up separately for each side's offered HTLCs.
+    pub id: u64,
+    /// Whether we offered the HTLC.
+    pub offered: bool,
+    pub amount_msat: MilliSat,
+    pub payment_hash: PaymentHash,
+    pub cltv_expiry: u32,
+    pub onion_routing_packet: Box<OnionPacket>,
+    pub state: HtlcState,
+}
+
+impl ChannelHtlc {
+    /// The HTLC as an output of our commitment, or of the counterparty's.
+    fn commitment_htlc(&self, local: bool) -> Htlc {
+        Htlc {
+            offered: self.offered == local,
+            amount_msat: self.amount_msat,
+            payment_hash: self.payment_hash.hash160(),
+            cltv_expiry: self.cltv_expiry,
+        }
+    }
+}
+
+/// A funded channel between two simulated nodes, moving HTLCs through
+/// `update_add_htlc`, `commitment_signed`, `revoke_and_ack` and
+/// `update_fulfill_htlc`.
+///
+/// Both commitments are tracked: ours, which the counterparty signed, and
+/// theirs, which we signed. An update is in one of them before the other,
+/// and `HtlcState` says which.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub channel_id: [u8; 32],
+    pub params: ChannelParams,
+    pub counterparty_params: ChannelParams,
+    pub funding_script: ScriptBuf,
+    pub funding_amount: Amount,
+    pub(crate) keys: ChannelKeyManager,
+    pub local_upfront_shutdown_script: Option<ScriptBuf>,
+    pub counterparty_upfront_shutdown_script: Option<ScriptBuf>,
+    /// Our balance with every HTLC on the channel still counted on the side
+    /// that offered it. Fulfilled HTLCs are moved over and dropped.
+    pub to_local_msat: MilliSat,
+    pub to_remote_msat: MilliSat,
+    pub htlcs: Vec<ChannelHtlc>,
+    next_htlc_id: u64,
+    next_remote_htlc_id: u64,
+    pub local_commitment_number: u64,
+    /// Our latest commitment, signed by both sides.
+    pub local_commitment: Transaction,
+    /// The counterparty's latest commitment it has revoked the one before of.
+    pub remote_commitment: Transaction,
+    pub counterparty_commitments: CounterpartyCommitments,
+    /// The counterparty commitment we last signed, until it revokes the one
+    /// before it.
+    pending_remote_commitment: Option<Transaction>,
+}
+
+impl Channel {
+    pub fn new(channel: &SimulatedChannel, keys: &ChannelKeyManager, channel_ready: &ChannelReady) -> Channel {
+        Channel {
+            channel_id: channel.channel_id.expect("channel is not funded yet"),
+            params: channel.params,
+            counterparty_params: channel.counterparty_params,
+            funding_script: channel.funding_script.clone().expect("channel is not funded yet"),
+            funding_amount: channel.funding_amount,
+            keys: keys.clone(),
+            local_upfront_shutdown_script: channel.local_upfront_shutdown_script.clone(),
+            counterparty_upfront_shutdown_script: channel.counterparty_upfront_shutdown_script.clone(),
+            to_local_msat: channel.local_msat,
+            to_remote_msat: channel.remote_msat,
+            htlcs: vec![],
+            next_htlc_id: 0,
+            next_remote_htlc_id: 0,
+            local_commitment_number: 0,
+            local_commitment: channel.holder_commitment.clone().expect("channel is not funded yet"),
+            remote_commitment: channel.counterparty_commitment.clone().expect("channel is not funded yet"),
+            counterparty_commitments: CounterpartyCommitments {
+                commitment_number: 0,
+                per_commitment_point: channel.counterparty_first_per_commitment_point,
+                next_per_commitment_point: channel_ready.second_per_commitment_point,
+                revoked_secrets: ShachainStore::new(),
+            },
+            pending_remote_commitment: None,
+        }
+    }
+
+    /// The newest commitment we signed for the counterparty, revoked or not.
+    pub fn latest_remote_commitment(&self) -> &Transaction {
+        self.pending_remote_commitment.as_ref().unwrap_or(&self.remote_commitment)
+    }
+
+    /// Offer an HTLC. It is only proposed: nothing is at stake until both
+    /// sides have signed and revoked.
+    pub fn send_htlc(
+        &mut self,
+        amount_msat: MilliSat,
+        payment_hash: PaymentHash,
+        cltv_expiry: u32,
+        onion_routing_packet: OnionPacket,
+    ) -> Result<UpdateAddHtlc, ChannelError> {
+        check_balance(self.to_local_msat, &self.htlcs, true, amount_msat)?;
+
+        let htlc = ChannelHtlc {
+            id: self.next_htlc_id,
+            offered: true,
+            amount_msat,
+            payment_hash,
+            cltv_expiry,
+            onion_routing_packet: Box::new(onion_routing_packet),
+            state: HtlcState::LocalAddProposed,
+        };
+        self.next_htlc_id += 1;
+        let update_add_htlc = UpdateAddHtlc {
+            channel_id: self.channel_id,
+            id: htlc.id,
+            amount_msat,
+            payment_hash,
+            cltv_expiry,
+            onion_routing_packet: htlc.onion_routing_packet.clone(),
+        };
+        self.htlcs.push(htlc);
+        Ok(update_add_htlc)
+    }
+
+    pub fn handle_update_add_htlc(&mut self, update_add_htlc: &UpdateAddHtlc) -> Result<(), ChannelError> {
+        if update_add_htlc.id != self.next_remote_htlc_id {
+            return Err(ChannelError::UnexpectedHtlcId {
+                expected: self.next_remote_htlc_id,
+                actual: update_add_htlc.id,
+            });
+        }
+        check_balance(self.to_remote_msat, &self.htlcs, false, update_add_htlc.amount_msat)?;
+
+        self.htlcs.push(ChannelHtlc {
+            id: update_add_htlc.id,
+            offered: false,
+            amount_msat: update_add_htlc.amount_msat,
+            payment_hash: update_add_htlc.payment_hash,
+            cltv_expiry: update_add_htlc.cltv_expiry,
+            onion_routing_packet: update_add_htlc.onion_routing_packet.clone(),
+            state: HtlcState::RemoteAddProposed,
+        });
+        self.next_remote_htlc_id += 1;
+        Ok(())
+    }
+
+    /// Settle an HTLC we were offered. It has to be committed on both sides
+    /// first, or the counterparty could still drop it.
+    pub fn fulfill_htlc(
+        &mut self,
+        id: u64,
+        payment_preimage: PaymentPreimage,
+    ) -> Result<UpdateFulfillHtlc, ChannelError> {
+        let htlc = self.committed_htlc(false, id, &payment_preimage)?;
+        htlc.state = HtlcState::LocalFulfillProposed;
+        Ok(UpdateFulfillHtlc { channel_id: self.channel_id, id, payment_preimage })
+    }
+
+    /// The counterparty settled an HTLC we offered. The preimage is ours to
+    /// claim the HTLC we were offered for the same payment, if there is one.
+    pub fn handle_update_fulfill_htlc(
+        &mut self,
+        update_fulfill_htlc: &UpdateFulfillHtlc,
+    ) -> Result<PaymentPreimage, ChannelError> {
+        let htlc = self.committed_htlc(true, update_fulfill_htlc.id, &update_fulfill_htlc.payment_preimage)?;
+        htlc.state = HtlcState::RemoteFulfillProposed;
+        Ok(update_fulfill_htlc.payment_preimage)
+    }
+
+    /// Sign the counterparty's next commitment, with every update it should
+    /// now hold, along with its HTLC transactions.
+    pub fn commitment_signed(&mut self) -> Result<CommitmentSigned, ChannelError> {
+        if self.pending_remote_commitment.is_some() {
+            return Err(ChannelError::AwaitingRevokeAndAck);
+        }
+        let states = self.next_states(CommitmentEvent::SendCommitmentSigned);
+        let per_commitment_point = self.counterparty_commitments.next_per_commitment_point;
+        let keys = self.counterparty_params.local_commitment_keys(per_commitment_point);
+        let commitment_number = self.counterparty_commitments.commitment_number + 1;
+        let (commitment, htlc_outputs) =
+            self.build_commitment(&self.counterparty_params, commitment_number, keys, &states, false)?;
+
+        let signature =
+            sign_commitment_transaction(&commitment, &self.funding_script, self.funding_amount, &self.keys.funding_key);
+        let htlc_key = derive_private_key(self.keys.htlc_base_key, per_commitment_point);
+        let htlc_signatures = htlc_transactions(&self.counterparty_params, &commitment, &htlc_outputs, &keys)?
+            .into_iter()
+            .map(|(htlc_tx, htlc_script, amount)| {
+                generate_p2wsh_signature(
+                    htlc_tx,
+                    0,
+                    &htlc_script,
+                    amount.to_sat(),
+                    self.params.htlc_fee_mode.counterparty_sighash_type(),
+                    htlc_key,
+                )
+            })
+            .collect();
+
+        self.set_states(states);
+        self.pending_remote_commitment = Some(commitment);
+        Ok(CommitmentSigned { channel_id: self.channel_id, signature, htlc_signatures })
+    }
+
+    /// Check the counterparty's signatures on our next commitment, then
+    /// revoke the one before it.
+    pub fn handle_commitment_signed(
+        &mut self,
+        commitment_signed: &CommitmentSigned,
+    ) -> Result<RevokeAndAck, ChannelError> {
+        let states = self.next_states(CommitmentEvent::ReceiveCommitmentSigned);
+        let commitment_number = self.local_commitment_number + 1;
+        let keys = self.params.local_commitment_keys(self.keys.per_commitment_point(commitment_number));
+        let (commitment, htlc_outputs) = self.build_commitment(&self.params, commitment_number, keys, &states, true)?;
+
+        let htlc_txs = htlc_transactions(&self.params, &commitment, &htlc_outputs, &keys)?;
+        if htlc_txs.len() != commitment_signed.htlc_signatures.len() {
+            return Err(ChannelError::HtlcSignatureCountMismatch {
+                expected: htlc_txs.len(),
+                actual: commitment_signed.htlc_signatures.len(),
+            });
+        }
+        let sighash_type = self.params.htlc_fee_mode.counterparty_sighash_type();
+        for (index, ((htlc_tx, htlc_script, amount), signature)) in
+            htlc_txs.iter().zip(&commitment_signed.htlc_signatures).enumerate()
+        {
+            let sighash = compute_p2wsh_sighash_with_type(htlc_tx, 0, htlc_script, amount.to_sat(), sighash_type);
+            if !verify_commitment_signature(signature, &keys.remote_htlc_pubkey.0, &sighash) {
+                return Err(ChannelError::InvalidHtlcSignature { index });
+            }
+        }
+        let commitment = finalize_holder_commitment(
+            &self.params,
+            &self.funding_script,
+            self.funding_amount,
+            &self.keys,
+            commitment,
+            &commitment_signed.signature,
+        )?;
+
+        let revoked_commitment_number = self.local_commitment_number;
+        self.local_commitment = commitment;
+        self.local_commitment_number = commitment_number;
+        self.set_states(states);
+        Ok(RevokeAndAck {
+            channel_id: self.channel_id,
+            per_commitment_secret: self.keys.per_commitment_secret(revoked_commitment_number),
+            next_per_commitment_point: self.keys.per_commitment_point(commitment_number + 1),
+        })
+    }
+
+    /// The counterparty revoked its previous commitment, so the one we signed
+    /// last is now the only one it can broadcast.
+    pub fn handle_revoke_and_ack(&mut self, revoke_and_ack: &RevokeAndAck) -> Result<(), ChannelError> {
+        if self.pending_remote_commitment.is_none() {
+            return Err(ChannelError::UnexpectedRevokeAndAck);
+        }
+        handle_revoke_and_ack(&mut self.counterparty_commitments, revoke_and_ack)?;
+
+        self.remote_commitment = self.pending_remote_commitment.take().unwrap();
+        let states = self.next_states(CommitmentEvent::ReceiveRevokeAndAck);
+        self.set_states(states);
+        Ok(())
+    }
+
+    /// Everything the channel holds, to pick it up again later with
+    /// `decode`. The keys are in it, so keep it as safe as the node seed.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.channel_id);
+        write_channel_parameters(&mut out, &self.params);
+        write_channel_parameters(&mut out, &self.counterparty_params);
+        write_u16_prefixed(&mut out, self.funding_script.as_bytes());
+        out.extend_from_slice(&self.funding_amount.to_sat().to_be_bytes());
+        for key in [
+            &self.keys.funding_key,
+            &self.keys.payment_base_key,
+            &self.keys.delayed_payment_base_key,
+            &self.keys.htlc_base_key,
+            &self.keys.revocation_base_key,
+        ] {
+            out.extend_from_slice(&key.secret_bytes());
+        }
+        out.extend_from_slice(&self.keys.commitment_seed);
+        write_optional(&mut out, self.local_upfront_shutdown_script.as_ref().map(|script| script.to_bytes()));
+        write_optional(&mut out, self.counterparty_upfront_shutdown_script.as_ref().map(|script| script.to_bytes()));
+        out.extend_from_slice(&self.to_local_msat.to_msat().to_be_bytes());
+        out.extend_from_slice(&self.to_remote_msat.to_msat().to_be_bytes());
+
+        out.extend_from_slice(&(self.htlcs.len() as u16).to_be_bytes());
+        for htlc in &self.htlcs {
+            out.extend_from_slice(&htlc.id.to_be_bytes());
+            out.push(htlc.offered as u8);
+            out.extend_from_slice(&htlc.amount_msat.to_msat().to_be_bytes());
+            out.extend_from_slice(&htlc.payment_hash.0);
+            out.extend_from_slice(&htlc.cltv_expiry.to_be_bytes());
+            out.extend(htlc.onion_routing_packet.encode());
+            out.push(HTLC_STATES.iter().position(|state| *state == htlc.state).unwrap() as u8);
+        }
+        out.extend_from_slice(&self.next_htlc_id.to_be_bytes());
+        out.extend_from_slice(&self.next_remote_htlc_id.to_be_bytes());
+
+        out.extend_from_slice(&self.local_commitment_number.to_be_bytes());
+        write_u16_prefixed(&mut out, &serialize(&self.local_commitment));
+        write_u16_prefixed(&mut out, &serialize(&self.remote_commitment));
+        let counterparty_commitments = &self.counterparty_commitments;
+        out.extend_from_slice(&counterparty_commitments.commitment_number.to_be_bytes());
+        out.extend_from_slice(&counterparty_commitments.per_commitment_point.serialize());
+        out.extend_from_slice(&counterparty_commitments.next_per_commitment_point.serialize());
+        let secrets: Vec<_> = counterparty_commitments.revoked_secrets.known_secrets().collect();
+        out.push(secrets.len() as u8);
+        for (index, secret) in secrets {
+            out.extend_from_slice(&index.to_be_bytes());
+            out.extend_from_slice(&secret);
+        }
+        write_optional(&mut out, self.pending_remote_commitment.as_ref().map(serialize));
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Channel, WireError> {
+        let mut reader = Reader::new(bytes);
+        let channel_id = reader.read_array()?;
+        let params = read_channel_parameters(&mut reader)?;
+        let counterparty_params = read_channel_parameters(&mut reader)?;
+        let funding_script = ScriptBuf::from_bytes(reader.read_u16_prefixed()?.to_vec());
+        let funding_amount = reader.read_amount()?;
+        let keys = ChannelKeyManager {
+            funding_key: read_secret_key(&mut reader)?,
+            payment_base_key: read_secret_key(&mut reader)?,
+            delayed_payment_base_key: read_secret_key(&mut reader)?,
+            htlc_base_key: read_secret_key(&mut reader)?,
+            revocation_base_key: read_secret_key(&mut reader)?,
+            commitment_seed: reader.read_array()?,
+        };
+        let local_upfront_shutdown_script =
+            read_optional(&mut reader)?.map(|script| ScriptBuf::from_bytes(script.to_vec()));
+        let counterparty_upfront_shutdown_script =
+            read_optional(&mut reader)?.map(|script| ScriptBuf::from_bytes(script.to_vec()));
+        let to_local_msat = reader.read_msat()?;
+        let to_remote_msat = reader.read_msat()?;
+
+        let mut htlcs = vec![];
+        for _ in 0..reader.read_u16()? {
+            htlcs.push(ChannelHtlc {
+                id: reader.read_u64()?,
+                offered: reader.read_array::<1>()?[0] != 0,
+                amount_msat: reader.read_msat()?,
+                payment_hash: PaymentHash(reader.read_array()?),
+                cltv_expiry: reader.read_u32()?,
+                onion_routing_packet: Box::new(
+                    OnionPacket::decode(&reader.read_array::<ONION_PACKET_LEN>()?)
+                        .map_err(|_| WireError::InvalidValue("onion_routing_packet"))?,
+                ),
+                state: *HTLC_STATES
+                    .get(reader.read_array::<1>()?[0] as usize)
+                    .ok_or(WireError::InvalidValue("htlc state"))?,
+            });
+        }
+        let next_htlc_id = reader.read_u64()?;
+        let next_remote_htlc_id = reader.read_u64()?;
+
+        let local_commitment_number = reader.read_u64()?;
+        let local_commitment = read_transaction(reader.read_u16_prefixed()?)?;
+        let remote_commitment = read_transaction(reader.read_u16_prefixed()?)?;
+        let commitment_number = reader.read_u64()?;
+        let per_commitment_point = reader.read_pubkey()?;
+        let next_per_commitment_point = reader.read_pubkey()?;
+        let mut revoked_secrets = ShachainStore::new();
+        for _ in 0..reader.read_array::<1>()?[0] {
+            let index = reader.read_u64()?;
+            revoked_secrets
+                .insert(index, reader.read_array()?)
+                .map_err(|_| WireError::InvalidValue("per_commitment_secret"))?;
+        }
+        let pending_remote_commitment = read_optional(&mut reader)?.map(read_transaction).transpose()?;
+        if !reader.is_empty() {
+            return Err(WireError::InvalidValue("channel"));
+        }
+
+        Ok(Channel {
+            channel_id,
+            params,
+            counterparty_params,
+            funding_script,
+            funding_amount,
+            keys,
+            local_upfront_shutdown_script,
+            counterparty_upfront_shutdown_script,
+            to_local_msat,
+            to_remote_msat,
+            htlcs,
+            next_htlc_id,
+            next_remote_htlc_id,
+            local_commitment_number,
+            local_commitment,
+            remote_commitment,
+            counterparty_commitments: CounterpartyCommitments {
+                commitment_number,
+                per_commitment_point,
+                next_per_commitment_point,
+                revoked_secrets,
+            },
+            pending_remote_commitment,
+        })
+    }
+
+    fn committed_htlc(
+        &mut self,
+        offered: bool,
+        id: u64,
+        payment_preimage: &PaymentPreimage,
+    ) -> Result<&mut ChannelHtlc, ChannelError> {
+        let htlc = self
+            .htlcs
+            .iter_mut()
+            .find(|htlc| htlc.offered == offered && htlc.id == id)
+            .ok_or(ChannelError::UnknownHtlc(id))?;
+        if htlc.state != HtlcState::Committed {
+            return Err(ChannelError::HtlcNotCommitted(id));
+        }
+        if payment_preimage.hash() != htlc.payment_hash {
+            return Err(ChannelError::InvalidPaymentPreimage(id));
+        }
+        Ok(htlc)
+    }
+
+    fn next_states(&self, event: CommitmentEvent) -> Vec<HtlcState> {
+        self.htlcs.iter().map(|htlc| next_htlc_state(htlc.state, event)).collect()
+    }
+
+    /// Move every HTLC to its new state, and pay out the ones that are now
+    /// fulfilled on both sides.
+    fn set_states(&mut self, states: Vec<HtlcState>) {
+        for (htlc, state) in self.htlcs.iter_mut().zip(states) {
+            htlc.state = state;
+        }
+        for htlc in self.htlcs.iter().filter(|htlc| htlc.state == HtlcState::Fulfilled) {
+            let (from, to) = if htlc.offered {
+                (&mut self.to_local_msat, &mut self.to_remote_msat)
+            } else {
+                (&mut self.to_remote_msat, &mut self.to_local_msat)
+            };
+            *from = from.checked_sub(htlc.amount_msat).unwrap();
+            *to = to.checked_add(htlc.amount_msat).unwrap();
+        }
+        self.htlcs.retain(|htlc| htlc.state != HtlcState::Fulfilled);
+    }
+
+    /// Our commitment (`local`) or the counterparty's, once each HTLC is in
+    /// the matching state of `states`.
+    fn build_commitment(
+        &self,
+        params: &ChannelParams,
+        commitment_number: u64,
+        keys: ChannelKeys,
+        states: &[HtlcState],
+        local: bool,
+    ) -> Result<(Transaction, Vec<HtlcOutput>), ChannelError> {
+        let (mut ours, mut theirs) = (self.to_local_msat, self.to_remote_msat);
+        let mut htlcs = vec![];
+        for (htlc, state) in self.htlcs.iter().zip(states) {
+            let in_commitment = if local { state.in_local_commitment() } else { state.in_remote_commitment() };
+            // Not added to this commitment yet, so its amount hasn't left
+            // the offerer.
+            if !in_commitment && !state.is_fulfill() {
+                continue;
+            }
+            let (from, to) = if htlc.offered { (&mut ours, &mut theirs) } else { (&mut theirs, &mut ours) };
+            *from = from.checked_sub(htlc.amount_msat).unwrap();
+            if in_commitment {
+                htlcs.push(htlc.commitment_htlc(local));
+            } else {
+                *to = to.checked_add(htlc.amount_msat).unwrap();
+            }
+        }
+
+        let (to_local_msat, to_remote_msat) = if local { (ours, theirs) } else { (theirs, ours) };
+        CommitmentTransactionBuilder::new(params, commitment_number, keys)
+            .balances(to_local_msat, to_remote_msat)
+            .htlcs(&htlcs)
+            .build()
+    }
+}
+
+/// Every `HtlcState`, in the order `Channel::encode` numbers them.
+const HTLC_STATES: [HtlcState; 14] = [
+    HtlcState::LocalAddProposed,
+    HtlcState::LocalAddSigned,
+    HtlcState::LocalAddAcked,
+    HtlcState::RemoteAddProposed,
+    HtlcState::RemoteAddAcked,
+    HtlcState::RemoteAddSigned,
+    HtlcState::Committed,
+    HtlcState::LocalFulfillProposed,
+    HtlcState::LocalFulfillSigned,
+    HtlcState::LocalFulfillAcked,
+    HtlcState::RemoteFulfillProposed,
+    HtlcState::RemoteFulfillAcked,
+    HtlcState::RemoteFulfillSigned,
+    HtlcState::Fulfilled,
+];
+
+/// Every `HtlcFeeMode`, in the order `Channel::encode` numbers them.
+const HTLC_FEE_MODES: [HtlcFeeMode; 3] = [HtlcFeeMode::Legacy, HtlcFeeMode::Anchors, HtlcFeeMode::ZeroFeeAnchors];
+
+fn write_channel_parameters(out: &mut Vec<u8>, params: &ChannelParams) {
+    out.extend_from_slice(&params.funding_outpoint.txid.to_byte_array());
+    out.extend_from_slice(&params.funding_outpoint.vout.to_be_bytes());
+    out.extend_from_slice(&params.to_self_delay.to_be_bytes());
+    out.extend_from_slice(&params.dust_limit.to_sat().to_be_bytes());
+    out.extend_from_slice(&params.feerate_per_kw.to_be_bytes());
+    out.push(HTLC_FEE_MODES.iter().position(|mode| *mode == params.htlc_fee_mode).unwrap() as u8);
+    out.push(params.is_funder as u8);
+    for keys in [&params.local_keys, &params.remote_keys] {
+        for key in [
+            keys.funding_pubkey,
+            keys.payment_basepoint,
+            keys.delayed_payment_basepoint,
+            keys.htlc_basepoint,
+            keys.revocation_basepoint,
+        ] {
+            out.extend_from_slice(&key.serialize());
+        }
+    }
+}
+
+fn read_channel_parameters(reader: &mut Reader) -> Result<ChannelParams, WireError> {
+    Ok(ChannelParams {
+        funding_outpoint: OutPoint::new(Txid::from_byte_array(reader.read_array()?), reader.read_u32()?),
+        to_self_delay: reader.read_u16()?,
+        dust_limit: reader.read_amount()?,
+        feerate_per_kw: reader.read_u64()?,
+        htlc_fee_mode: *HTLC_FEE_MODES
+            .get(reader.read_array::<1>()?[0] as usize)
+            .ok_or(WireError::InvalidValue("htlc fee mode"))?,
+        is_funder: reader.read_array::<1>()?[0] != 0,
+        local_keys: read_channel_public_keys(reader)?,
+        remote_keys: read_channel_public_keys(reader)?,
+    })
+}
+
+fn read_channel_public_keys(reader: &mut Reader) -> Result<ChannelPublicKeys, WireError> {
+    Ok(ChannelPublicKeys {
+        funding_pubkey: reader.read_pubkey()?,
+        payment_basepoint: reader.read_pubkey()?,
+        delayed_payment_basepoint: reader.read_pubkey()?,
+        htlc_basepoint: reader.read_pubkey()?,
+        revocation_basepoint: reader.read_pubkey()?,
+    })
+}
+
+fn read_secret_key(reader: &mut Reader) -> Result<SecretKey, WireError> {
+    SecretKey::from_slice(reader.read_bytes(32)?).map_err(|_| WireError::InvalidValue("secret key"))
+}
+
+fn read_transaction(bytes: &[u8]) -> Result<Transaction, WireError> {
+    deserialize(bytes).map_err(|_| WireError::InvalidValue("transaction"))
+}
+
+/// A flag byte, then the bytes if the flag is set.
+fn write_optional(out: &mut Vec<u8>, bytes: Option<Vec<u8>>) {
+    match bytes {
+        Some(bytes) => {
+            out.push(1);
+            write_u16_prefixed(out, &bytes);
+        }
+        None => out.push(0),
+    }
+}
+
+fn read_optional<'a>(reader: &mut Reader<'a>) -> Result<Option<&'a [u8]>, WireError> {
+    match reader.read_array::<1>()?[0] {
+        0 => Ok(None),
+        _ => reader.read_u16_prefixed().map(Some),
+    }
+}
+
+/// Whether the offerer can cover `amount_msat` on top of the HTLCs it
+/// already has on the channel.
+fn check_balance(
+    balance: MilliSat,
+    htlcs: &[ChannelHtlc],
+    offered: bool,
+    amount_msat: MilliSat,
+) -> Result<(), ChannelError> {
+    let available = htlcs
+        .iter()
+        .filter(|htlc| htlc.offered == offered)
+        .try_fold(balance, |available, htlc| available.checked_sub(htlc.amount_msat));
+    let available_msat = available.unwrap_or(MilliSat::ZERO);
+
+    available_msat.checked_sub(amount_msat).map(|_| ()).ok_or(ChannelError::InsufficientBalance {
+        available_msat: available_msat.to_msat(),
+        required_msat: amount_msat.to_msat(),
+    })
+}
+
+/// The second-stage transactions of the commitment's HTLC outputs, with the
+/// script and amount they spend. `commitment_signed` signs them in the order
+/// of the outputs, not of the HTLCs.
+fn htlc_transactions(
+    params: &ChannelParams,
+    commitment: &Transaction,
+    htlc_outputs: &[HtlcOutput],
+    keys: &ChannelKeys,
+) -> Result<Vec<(Transaction, ScriptBuf, Amount)>, ChannelError> {
+    let htlc_txs = build_all_htlc_transactions(commitment, htlc_outputs, keys, params)?;
+    let untrimmed = htlc_outputs.iter().filter(|htlc_output| htlc_output.output_index.is_some());
+
+    let mut htlc_txs: Vec<_> = htlc_txs
+        .into_iter()
+        .zip(untrimmed)
+        .map(|((htlc_tx, htlc_script), htlc_output)| (htlc_tx, htlc_script, htlc_output.amount_sat()))
+        .collect();
+    htlc_txs.sort_by_key(|(htlc_tx, _, _)| htlc_tx.input[0].previous_output.vout);
+    Ok(htlc_txs)
+}
@@ -0,0 +1,211 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! The BOLT 2 cooperative close. Once both sides have sent `shutdown` and
+//! the channel has no HTLCs left, they trade `closing_signed` until one
+//! answers with the fee the other proposed. Each `closing_signed` carries a
+//! signature on the closing transaction paying its fee, so both sides end
+//! up with the same closing transaction, signed by both.
+use crate::exercises::exercises::{check_counterparty_shutdown, closing_signed_fee, sign_commitment_transaction};
+use crate::internal;
+use crate::simulation::channel::Channel;
+use crate::simulation::node::transmit;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, Witness};
+use internal::errors::ClosingError;
+use internal::fee_utils::{closing_fee_proposal, FeeEstimator, FUNDING_WITNESS_WEIGHT};
+use internal::key_utils::verify_counterparty_commitment;
+use internal::sign_utils::finalize_commitment;
+use internal::tx_utils::{build_output, build_transaction, sort_outputs_bolt3, strip_cltv_expiries};
+use internal::wire::{ClosingSigned, FeeRange, Message, Shutdown};
+
+/// Tell the counterparty where our side of the close should go. If we sent
+/// an `upfront_shutdown_script` when the channel opened, it has to be that
+/// one, or the counterparty refuses to close.
+pub fn shutdown(channel: &Channel, scriptpubkey: ScriptBuf) -> Shutdown {
+    Shutdown { channel_id: channel.channel_id, scriptpubkey }
+}
+
+/// One side's view of a cooperative close, after both `shutdown` messages.
+#[derive(Debug, Clone)]
+pub struct ClosingNegotiation {
+    pub channel_id: [u8; 32],
+    pub is_funder: bool,
+    funding_txin: TxIn,
+    funding_script: ScriptBuf,
+    funding_amount: Amount,
+    funding_key: SecretKey,
+    local_funding_pubkey: PublicKey,
+    remote_funding_pubkey: PublicKey,
+    pub local_script: ScriptBuf,
+    pub remote_script: ScriptBuf,
+    pub to_local: Amount,
+    pub to_remote: Amount,
+    pub dust_limit: Amount,
+    /// The fee our estimator would have us propose, and the range we agree
+    /// to.
+    pub proposed_fee: Amount,
+    pub fee_range: FeeRange,
+    pub last_sent_fee: Option<Amount>,
+    /// Signed by both sides, once the fee is agreed.
+    pub closing_tx: Option<Transaction>,
+}
+
+impl ClosingNegotiation {
+    /// `local_script` is the one we sent in our `shutdown`, and
+    /// `counterparty_shutdown` the one we received.
+    pub fn new(
+        channel: &Channel,
+        local_script: ScriptBuf,
+        counterparty_shutdown: &Shutdown,
+        fee_estimator: &dyn FeeEstimator,
+    ) -> Result<ClosingNegotiation, ClosingError> {
+        if counterparty_shutdown.channel_id != channel.channel_id {
+            return Err(ClosingError::UnknownChannelId(counterparty_shutdown.channel_id));
+        }
+        if !channel.htlcs.is_empty() {
+            return Err(ClosingError::PendingHtlcs(channel.htlcs.len()));
+        }
+        // the simulated nodes both support option_shutdown_anysegwit
+        check_counterparty_shutdown(
+            counterparty_shutdown,
+            channel.counterparty_upfront_shutdown_script.as_ref(),
+            true,
+        )?;
+
+        let mut negotiation = ClosingNegotiation {
+            channel_id: channel.channel_id,
+            is_funder: channel.params.is_funder,
+            funding_txin: TxIn {
+                previous_output: channel.params.funding_outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            },
+            funding_script: channel.funding_script.clone(),
+            funding_amount: channel.funding_amount,
+            funding_key: channel.keys.funding_key,
+            local_funding_pubkey: PublicKey::new(channel.params.local_keys.funding_pubkey),
+            remote_funding_pubkey: PublicKey::new(channel.params.remote_keys.funding_pubkey),
+            local_script,
+            remote_script: counterparty_shutdown.scriptpubkey.clone(),
+            to_local: Amount::from_sat(channel.to_local_msat.to_msat() / 1000),
+            to_remote: Amount::from_sat(channel.to_remote_msat.to_msat() / 1000),
+            dust_limit: channel.params.dust_limit,
+            proposed_fee: Amount::ZERO,
+            fee_range: FeeRange { min_fee_satoshis: Amount::ZERO, max_fee_satoshis: Amount::ZERO },
+            last_sent_fee: None,
+            closing_tx: None,
+        };
+        let weight = negotiation.closing_transaction(Amount::ZERO).weight().to_wu() + FUNDING_WITNESS_WEIGHT;
+        (negotiation.proposed_fee, negotiation.fee_range) = closing_fee_proposal(fee_estimator, weight);
+        Ok(negotiation)
+    }
+
+    /// The closing transaction paying `fee`, which comes out of the funder's
+    /// output. Outputs below the dust limit are left out, and the rest are
+    /// sorted like BOLT 3 sorts them, so both sides build the same one.
+    pub fn closing_transaction(&self, fee: Amount) -> Transaction {
+        let (local_fee, remote_fee) = if self.is_funder { (fee, Amount::ZERO) } else { (Amount::ZERO, fee) };
+        let to_local = self.to_local.checked_sub(local_fee).unwrap_or(Amount::ZERO);
+        let to_remote = self.to_remote.checked_sub(remote_fee).unwrap_or(Amount::ZERO);
+        let mut outputs = vec![
+            (build_output(to_local, self.local_script.clone()), None),
+            (build_output(to_remote, self.remote_script.clone()), None),
+        ];
+        outputs.retain(|(output, _)| output.value >= self.dust_limit);
+        let outputs = sort_outputs_bolt3(outputs);
+        build_transaction(Version::TWO, LockTime::ZERO, vec![self.funding_txin.clone()], strip_cltv_expiries(outputs))
+    }
+
+    fn sign(&mut self, fee: Amount) -> ClosingSigned {
+        let closing_tx = self.closing_transaction(fee);
+        self.last_sent_fee = Some(fee);
+        ClosingSigned {
+            channel_id: self.channel_id,
+            fee_satoshis: fee,
+            signature: sign_commitment_transaction(
+                &closing_tx,
+                &self.funding_script,
+                self.funding_amount,
+                &self.funding_key,
+            ),
+            fee_range: Some(self.fee_range),
+        }
+    }
+
+    /// The funder's opening `closing_signed`, at the fee its estimator
+    /// proposes.
+    pub fn propose(&mut self) -> ClosingSigned {
+        self.sign(self.proposed_fee)
+    }
+
+    /// Check the counterparty's signature and answer it. `None` once the fee
+    /// is agreed and there is nothing left to send.
+    pub fn handle_closing_signed(
+        &mut self,
+        closing_signed: &ClosingSigned,
+    ) -> Result<Option<ClosingSigned>, ClosingError> {
+        if closing_signed.channel_id != self.channel_id {
+            return Err(ClosingError::UnknownChannelId(closing_signed.channel_id));
+        }
+        let fee = closing_signed.fee_satoshis;
+        let closing_tx = self.closing_transaction(fee);
+        verify_counterparty_commitment(
+            &closing_tx,
+            &closing_signed.signature,
+            &self.remote_funding_pubkey,
+            &self.funding_script,
+            self.funding_amount.to_sat(),
+        )
+        .map_err(|_| ClosingError::InvalidSignature)?;
+
+        // they agreed to our fee
+        if self.last_sent_fee == Some(fee) {
+            self.finalize(closing_tx, &closing_signed.signature)?;
+            return Ok(None);
+        }
+
+        let our_fee = closing_signed_fee(self.is_funder, &self.fee_range, self.last_sent_fee, closing_signed)?;
+        let reply = self.sign(our_fee);
+        if our_fee == fee {
+            self.finalize(closing_tx, &closing_signed.signature)?;
+        }
+        Ok(Some(reply))
+    }
+
+    fn finalize(&mut self, closing_tx: Transaction, counterparty_signature: &Signature) -> Result<(), ClosingError> {
+        let our_signature =
+            sign_commitment_transaction(&closing_tx, &self.funding_script, self.funding_amount, &self.funding_key);
+        self.closing_tx = Some(finalize_commitment(
+            closing_tx,
+            &self.funding_script,
+            &self.local_funding_pubkey,
+            &our_signature,
+            &self.remote_funding_pubkey,
+            counterparty_signature,
+        )?);
+        Ok(())
+    }
+}
+
+/// Trade `closing_signed` between the funder and the fundee until they agree
+/// on a fee. Returns the closing transaction each side ended up with.
+pub fn negotiate_closing_fee(
+    funder: &mut ClosingNegotiation,
+    fundee: &mut ClosingNegotiation,
+) -> Result<(Transaction, Transaction), ClosingError> {
+    let mut next = Some(funder.propose());
+    let mut funder_turn = false;
+    while let Some(closing_signed) = next {
+        let Message::ClosingSigned(closing_signed) = transmit(Message::ClosingSigned(closing_signed)) else {
+            unreachable!()
+        };
+        let receiver = if funder_turn { &mut *funder } else { &mut *fundee };
+        next = receiver.handle_closing_signed(&closing_signed)?;
+        funder_turn = !funder_turn;
+    }
+
+    Ok((funder.closing_tx.clone().unwrap(), fundee.closing_tx.clone().unwrap()))
+}
@@ -0,0 +1,28 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises_advanced::exercises::build_interactive_funding_transaction;
+use crate::internal;
+use crate::simulation::node::transmit;
+use bitcoin::Transaction;
+use internal::errors::InteractiveTxError;
+use internal::interactive_tx::InteractiveTxConstructor;
+
+/// Run the interactive construction between the two sides of a dual-funded
+/// open until both have sent `tx_complete` in a row, starting with the
+/// initiator. Returns the funding transaction each side built.
+pub fn negotiate_funding_transaction(
+    initiator: &mut InteractiveTxConstructor,
+    acceptor: &mut InteractiveTxConstructor,
+) -> Result<(Transaction, Transaction), InteractiveTxError> {
+    let mut initiator_turn = true;
+    loop {
+        let (sender, receiver) =
+            if initiator_turn { (&mut *initiator, &mut *acceptor) } else { (&mut *acceptor, &mut *initiator) };
+        let Some(message) = sender.next_message() else {
+            break;
+        };
+        receiver.handle_message(&transmit(message))?;
+        initiator_turn = !initiator_turn;
+    }
+
+    Ok((build_interactive_funding_transaction(initiator)?, build_interactive_funding_transaction(acceptor)?))
+}
@@ -0,0 +1,7 @@
+pub mod channel;
+pub mod closing;
+pub mod dual_funding;
+pub mod node;
+pub mod persist;
+pub mod relay;
+pub mod test;
@@ -0,0 +1,463 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{
+    build_funding_transaction_with_script, build_open_channel, create_invoice, sign_commitment_transaction,
+    two_of_two_multisig_witness_script,
+};
+use crate::internal;
+use crate::simulation::channel::Channel;
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, SecretKey};
+use bitcoin::{Amount, Network, OutPoint, PublicKey, ScriptBuf, Transaction, TxIn};
+use internal::channel::{ChannelParams, CommitmentTransactionBuilder};
+use internal::channel_utils::channel_id_from_funding;
+use internal::errors::{ChannelError, InvoiceError};
+use internal::fee_utils::HtlcFeeMode;
+use internal::payments::{PaymentPreimage, PaymentSecret};
+use internal::invoice::DEFAULT_EXPIRY;
+use internal::key_utils::{pubkey_from_secret, sort_funding_keys, verify_counterparty_commitment};
+use internal::keys::{ChannelKeyManager, ChannelPublicKeys, TxCreationKeys};
+use internal::sign_utils::finalize_commitment;
+use internal::units::MilliSat;
+use internal::wire::{
+    has_feature, AcceptChannel, ChannelReady, FundingCreated, FundingSigned, Message, OpenChannel,
+    ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED, ANCHOR_OUTPUTS_REQUIRED,
+};
+
+/// How many confirmations the fundee asks for before the channel is usable.
+pub const MINIMUM_DEPTH: u32 = 3;
+
+/// One side of a channel being opened between two in-memory nodes.
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// Signs our invoices. Its public key is our node id.
+    pub node_key: SecretKey,
+    pub keys: ChannelKeyManager,
+    pub network: Network,
+    pub dust_limit: Amount,
+    /// The delay we ask the counterparty to wait on its own `to_local`.
+    pub to_self_delay: u16,
+    pub feerate_per_kw: u64,
+    pub htlc_fee_mode: HtlcFeeMode,
+    /// Where our side of a cooperative close has to go, committed to in
+    /// `open_channel` or `accept_channel`.
+    pub upfront_shutdown_script: Option<ScriptBuf>,
+    /// Set by the funder between `open_channel` and `accept_channel`.
+    pending_open: Option<(OpenChannel, TxIn)>,
+    pub channel: Option<SimulatedChannel>,
+}
+
+/// What a node knows about its channel once both sides' parameters are in.
+#[derive(Debug, Clone)]
+pub struct SimulatedChannel {
+    pub temporary_channel_id: [u8; 32],
+    pub channel_id: Option<[u8; 32]>,
+    pub funding_amount: Amount,
+    pub local_msat: MilliSat,
+    pub remote_msat: MilliSat,
+    pub params: ChannelParams,
+    /// `params` from the counterparty's point of view, to build its commitment.
+    pub counterparty_params: ChannelParams,
+    pub counterparty_first_per_commitment_point: secp256k1PublicKey,
+    /// The scripts each side committed to closing to, if any.
+    pub local_upfront_shutdown_script: Option<ScriptBuf>,
+    pub counterparty_upfront_shutdown_script: Option<ScriptBuf>,
+    pub funding_script: Option<ScriptBuf>,
+    pub funding_tx: Option<Transaction>,
+    /// Our first commitment, signed by both sides and ready to broadcast.
+    pub holder_commitment: Option<Transaction>,
+    /// The counterparty's first commitment, as we signed it.
+    pub counterparty_commitment: Option<Transaction>,
+}
+
+impl SimulatedChannel {
+    fn holder_commitment_tx(&self, keys: &ChannelKeyManager) -> Result<Transaction, ChannelError> {
+        let commitment_keys = self.params.local_commitment_keys(keys.per_commitment_point(0));
+        let (tx, _) = CommitmentTransactionBuilder::new(&self.params, 0, commitment_keys)
+            .balances(self.local_msat, self.remote_msat)
+            .build()?;
+        Ok(tx)
+    }
+
+    fn counterparty_commitment_tx(&self) -> Result<Transaction, ChannelError> {
+        let commitment_keys =
+            self.counterparty_params.local_commitment_keys(self.counterparty_first_per_commitment_point);
+        let (tx, _) = CommitmentTransactionBuilder::new(&self.counterparty_params, 0, commitment_keys)
+            .balances(self.remote_msat, self.local_msat)
+            .build()?;
+        Ok(tx)
+    }
+
+    fn funding_script(&self) -> &ScriptBuf {
+        self.funding_script.as_ref().expect("funding outpoint not known yet")
+    }
+}
+
+fn mirror(params: &ChannelParams, counterparty_to_self_delay: u16, counterparty_dust_limit: Amount) -> ChannelParams {
+    ChannelParams {
+        to_self_delay: counterparty_to_self_delay,
+        dust_limit: counterparty_dust_limit,
+        is_funder: !params.is_funder,
+        local_keys: params.remote_keys,
+        remote_keys: params.local_keys,
+        ..*params
+    }
+}
+
+impl Node {
+    pub fn new(node_key: SecretKey, keys: ChannelKeyManager, network: Network) -> Node {
+        Node {
+            node_key,
+            keys,
+            network,
+            dust_limit: Amount::from_sat(546),
+            to_self_delay: 144,
+            feerate_per_kw: 2_500,
+            htlc_fee_mode: HtlcFeeMode::ZeroFeeAnchors,
+            upfront_shutdown_script: None,
+            pending_open: None,
+            channel: None,
+        }
+    }
+
+    pub fn node_id(&self) -> secp256k1PublicKey {
+        pubkey_from_secret(self.node_key)
+    }
+
+    /// An invoice asking the payer for `amount_msat`, to be settled by
+    /// revealing `payment_preimage`.
+    pub fn create_invoice(
+        &self,
+        amount_msat: MilliSat,
+        payment_preimage: &PaymentPreimage,
+        payment_secret: &PaymentSecret,
+        description: &str,
+        timestamp: u64,
+    ) -> Result<String, InvoiceError> {
+        create_invoice(
+            &self.node_key,
+            self.network,
+            Some(amount_msat),
+            &payment_preimage.hash(),
+            payment_secret,
+            description,
+            DEFAULT_EXPIRY,
+            timestamp,
+        )
+    }
+
+    /// Funder: propose a channel funded from `funding_txin`.
+    pub fn open_channel(
+        &mut self,
+        temporary_channel_id: [u8; 32],
+        funding_txin: TxIn,
+        funding_amount: Amount,
+        push_msat: MilliSat,
+    ) -> OpenChannel {
+        // Only our side is known before accept_channel, and that is all
+        // build_open_channel reads.
+        let draft = ChannelParams {
+            funding_outpoint: OutPoint::null(),
+            to_self_delay: 0,
+            dust_limit: self.dust_limit,
+            feerate_per_kw: self.feerate_per_kw,
+            htlc_fee_mode: self.htlc_fee_mode,
+            is_funder: true,
+            local_keys: self.keys.pubkeys(),
+            remote_keys: self.keys.pubkeys(),
+        };
+        let mut open_channel = build_open_channel(
+            self.network,
+            temporary_channel_id,
+            &draft,
+            funding_amount,
+            push_msat,
+            self.to_self_delay,
+            self.keys.per_commitment_point(0),
+        );
+        open_channel.upfront_shutdown_script = self.upfront_shutdown_script.clone();
+        self.pending_open = Some((open_channel.clone(), funding_txin));
+        open_channel
+    }
+
+    /// Fundee: take the funder's parameters and answer with ours.
+    pub fn handle_open_channel(&mut self, open_channel: &OpenChannel) -> AcceptChannel {
+        let remote_keys = ChannelPublicKeys {
+            funding_pubkey: open_channel.funding_pubkey,
+            payment_basepoint: open_channel.payment_basepoint,
+            delayed_payment_basepoint: open_channel.delayed_payment_basepoint,
+            htlc_basepoint: open_channel.htlc_basepoint,
+            revocation_basepoint: open_channel.revocation_basepoint,
+        };
+        let channel_type = open_channel.channel_type.as_deref().unwrap_or_default();
+        let htlc_fee_mode = if has_feature(channel_type, ANCHORS_ZERO_FEE_HTLC_TX_REQUIRED) {
+            HtlcFeeMode::ZeroFeeAnchors
+        } else if has_feature(channel_type, ANCHOR_OUTPUTS_REQUIRED) {
+            HtlcFeeMode::Anchors
+        } else {
+            HtlcFeeMode::Legacy
+        };
+
+        let params = ChannelParams {
+            funding_outpoint: OutPoint::null(),
+            to_self_delay: open_channel.to_self_delay,
+            dust_limit: self.dust_limit,
+            feerate_per_kw: open_channel.feerate_per_kw as u64,
+            htlc_fee_mode,
+            is_funder: false,
+            local_keys: self.keys.pubkeys(),
+            remote_keys,
+        };
+        let funding_msat = MilliSat::from_amount(open_channel.funding_satoshis).unwrap();
+
+        self.channel = Some(SimulatedChannel {
+            temporary_channel_id: open_channel.temporary_channel_id,
+            channel_id: None,
+            funding_amount: open_channel.funding_satoshis,
+            local_msat: open_channel.push_msat,
+            remote_msat: funding_msat.checked_sub(open_channel.push_msat).unwrap(),
+            counterparty_params: mirror(&params, self.to_self_delay, open_channel.dust_limit_satoshis),
+            params,
+            counterparty_first_per_commitment_point: open_channel.first_per_commitment_point,
+            local_upfront_shutdown_script: self.upfront_shutdown_script.clone(),
+            counterparty_upfront_shutdown_script: open_channel.upfront_shutdown_script.clone(),
+            funding_script: None,
+            funding_tx: None,
+            holder_commitment: None,
+            counterparty_commitment: None,
+        });
+
+        let keys = self.keys.pubkeys();
+        AcceptChannel {
+            temporary_channel_id: open_channel.temporary_channel_id,
+            dust_limit_satoshis: self.dust_limit,
+            max_htlc_value_in_flight_msat: funding_msat,
+            channel_reserve_satoshis: std::cmp::max(open_channel.funding_satoshis / 100, self.dust_limit),
+            htlc_minimum_msat: MilliSat::from_msat(1),
+            minimum_depth: MINIMUM_DEPTH,
+            to_self_delay: self.to_self_delay,
+            max_accepted_htlcs: open_channel.max_accepted_htlcs,
+            funding_pubkey: keys.funding_pubkey,
+            revocation_basepoint: keys.revocation_basepoint,
+            payment_basepoint: keys.payment_basepoint,
+            delayed_payment_basepoint: keys.delayed_payment_basepoint,
+            htlc_basepoint: keys.htlc_basepoint,
+            first_per_commitment_point: self.keys.per_commitment_point(0),
+            upfront_shutdown_script: self.upfront_shutdown_script.clone(),
+            channel_type: open_channel.channel_type.clone(),
+        }
+    }
+
+    /// Funder: build the funding transaction and sign the fundee's first
+    /// commitment, so it can broadcast a refund if we vanish.
+    pub fn handle_accept_channel(&mut self, accept_channel: &AcceptChannel) -> Result<FundingCreated, ChannelError> {
+        let (open_channel, funding_txin) = self.pending_open.take().expect("accept_channel without open_channel");
+
+        let remote_keys = ChannelPublicKeys {
+            funding_pubkey: accept_channel.funding_pubkey,
+            payment_basepoint: accept_channel.payment_basepoint,
+            delayed_payment_basepoint: accept_channel.delayed_payment_basepoint,
+            htlc_basepoint: accept_channel.htlc_basepoint,
+            revocation_basepoint: accept_channel.revocation_basepoint,
+        };
+        let (funding_tx, funding_script) = build_funding_transaction_with_script(
+            vec![funding_txin],
+            &PublicKey::new(self.keys.pubkeys().funding_pubkey),
+            &PublicKey::new(remote_keys.funding_pubkey),
+            open_channel.funding_satoshis,
+        );
+        let funding_outpoint = OutPoint::new(funding_tx.compute_txid(), 0);
+
+        let params = ChannelParams {
+            funding_outpoint,
+            to_self_delay: accept_channel.to_self_delay,
+            dust_limit: self.dust_limit,
+            feerate_per_kw: self.feerate_per_kw,
+            htlc_fee_mode: self.htlc_fee_mode,
+            is_funder: true,
+            local_keys: self.keys.pubkeys(),
+            remote_keys,
+        };
+        let funding_msat = MilliSat::from_amount(open_channel.funding_satoshis).unwrap();
+
+        let mut channel = SimulatedChannel {
+            temporary_channel_id: open_channel.temporary_channel_id,
+            channel_id: None,
+            funding_amount: open_channel.funding_satoshis,
+            local_msat: funding_msat.checked_sub(open_channel.push_msat).unwrap(),
+            remote_msat: open_channel.push_msat,
+            counterparty_params: mirror(&params, self.to_self_delay, accept_channel.dust_limit_satoshis),
+            params,
+            counterparty_first_per_commitment_point: accept_channel.first_per_commitment_point,
+            local_upfront_shutdown_script: open_channel.upfront_shutdown_script.clone(),
+            counterparty_upfront_shutdown_script: accept_channel.upfront_shutdown_script.clone(),
+            funding_script: Some(funding_script),
+            funding_tx: Some(funding_tx),
+            holder_commitment: None,
+            counterparty_commitment: None,
+        };
+
+        let counterparty_commitment = channel.counterparty_commitment_tx()?;
+        let signature = sign_commitment_transaction(
+            &counterparty_commitment,
+            channel.funding_script(),
+            channel.funding_amount,
+            &self.keys.funding_key,
+        );
+        channel.counterparty_commitment = Some(counterparty_commitment);
+        self.channel = Some(channel);
+
+        Ok(FundingCreated {
+            temporary_channel_id: open_channel.temporary_channel_id,
+            funding_txid: funding_outpoint.txid,
+            funding_output_index: funding_outpoint.vout as u16,
+            signature,
+        })
+    }
+
+    /// Fundee: check the funder's signature on our commitment and return
+    /// ours on theirs, now under the real channel id.
+    pub fn handle_funding_created(&mut self, funding_created: &FundingCreated) -> Result<FundingSigned, ChannelError> {
+        let keys = self.keys.clone();
+        let channel = self.channel.as_mut().expect("funding_created without open_channel");
+
+        let funding_outpoint =
+            OutPoint::new(funding_created.funding_txid, funding_created.funding_output_index as u32);
+        channel.params.funding_outpoint = funding_outpoint;
+        channel.counterparty_params.funding_outpoint = funding_outpoint;
+        // Exercise 2 sorts the keys, so do the same to find the same script.
+        let (first, second) = sort_funding_keys(
+            PublicKey::new(channel.params.remote_keys.funding_pubkey),
+            PublicKey::new(channel.params.local_keys.funding_pubkey),
+        );
+        channel.funding_script = Some(two_of_two_multisig_witness_script(&first, &second));
+
+        let holder_commitment = channel.holder_commitment_tx(&keys)?;
+        channel.holder_commitment = Some(finalize_holder_commitment(
+            &channel.params,
+            channel.funding_script(),
+            channel.funding_amount,
+            &keys,
+            holder_commitment,
+            &funding_created.signature,
+        )?);
+
+        let counterparty_commitment = channel.counterparty_commitment_tx()?;
+        let signature = sign_commitment_transaction(
+            &counterparty_commitment,
+            channel.funding_script(),
+            channel.funding_amount,
+            &keys.funding_key,
+        );
+        channel.counterparty_commitment = Some(counterparty_commitment);
+
+        let channel_id = channel_id_from_funding(funding_outpoint.txid, funding_outpoint.vout as u16);
+        channel.channel_id = Some(channel_id);
+
+        Ok(FundingSigned { channel_id, signature })
+    }
+
+    /// Funder: once our commitment is signed, the funding transaction is
+    /// safe to broadcast.
+    pub fn handle_funding_signed(&mut self, funding_signed: &FundingSigned) -> Result<Transaction, ChannelError> {
+        let keys = self.keys.clone();
+        let channel = self.channel.as_mut().expect("funding_signed without funding_created");
+
+        let holder_commitment = channel.holder_commitment_tx(&keys)?;
+        channel.holder_commitment = Some(finalize_holder_commitment(
+            &channel.params,
+            channel.funding_script(),
+            channel.funding_amount,
+            &keys,
+            holder_commitment,
+            &funding_signed.signature,
+        )?);
+        channel.channel_id = Some(funding_signed.channel_id);
+
+        Ok(channel.funding_tx.clone().unwrap())
+    }
+
+    /// Once the funding transaction is deep enough, hand the counterparty
+    /// the point of our second commitment so it can sign it.
+    pub fn channel_ready(&self) -> ChannelReady {
+        let channel = self.channel.as_ref().expect("channel_ready without a channel");
+        ChannelReady {
+            channel_id: channel.channel_id.expect("channel_ready before funding_signed"),
+            second_per_commitment_point: self.keys.per_commitment_point(1),
+        }
+    }
+
+    /// The counterparty is ready too: from here on the channel moves through
+    /// HTLC updates.
+    pub fn handle_channel_ready(&self, channel_ready: &ChannelReady) -> Channel {
+        let channel = self.channel.as_ref().expect("channel_ready without a channel");
+        Channel::new(channel, &self.keys, channel_ready)
+    }
+}
+
+/// Verify the counterparty's signature on our commitment and add our own.
+pub(crate) fn finalize_holder_commitment(
+    params: &ChannelParams,
+    funding_script: &ScriptBuf,
+    funding_amount: Amount,
+    keys: &ChannelKeyManager,
+    commitment: Transaction,
+    counterparty_signature: &Signature,
+) -> Result<Transaction, ChannelError> {
+    let local_funding_pubkey = PublicKey::new(params.local_keys.funding_pubkey);
+    let remote_funding_pubkey = PublicKey::new(params.remote_keys.funding_pubkey);
+
+    verify_counterparty_commitment(
+        &commitment,
+        counterparty_signature,
+        &remote_funding_pubkey,
+        funding_script,
+        funding_amount.to_sat(),
+    )?;
+    let our_signature = sign_commitment_transaction(&commitment, funding_script, funding_amount, &keys.funding_key);
+
+    finalize_commitment(
+        commitment,
+        funding_script,
+        &local_funding_pubkey,
+        &our_signature,
+        &remote_funding_pubkey,
+        counterparty_signature,
+    )
+}
+
+/// Send `message` over the wire and back, so every step goes through the
+/// same encoding a real peer would see.
+pub fn transmit(message: Message) -> Message {
+    Message::decode(&message.encode()).unwrap()
+}
+
+/// Run the whole BOLT 2 opening dance between `funder` and `fundee` and
+/// return the funding transaction the funder can now broadcast.
+pub fn open_channel_between(
+    funder: &mut Node,
+    fundee: &mut Node,
+    funding_txin: TxIn,
+    funding_amount: Amount,
+    push_msat: MilliSat,
+) -> Result<Transaction, ChannelError> {
+    let open_channel = funder.open_channel([0x42; 32], funding_txin, funding_amount, push_msat);
+    let Message::OpenChannel(open_channel) = transmit(Message::OpenChannel(open_channel)) else { unreachable!() };
+
+    let accept_channel = fundee.handle_open_channel(&open_channel);
+    let Message::AcceptChannel(accept_channel) = transmit(Message::AcceptChannel(accept_channel)) else {
+        unreachable!()
+    };
+
+    let funding_created = funder.handle_accept_channel(&accept_channel)?;
+    let Message::FundingCreated(funding_created) = transmit(Message::FundingCreated(funding_created)) else {
+        unreachable!()
+    };
+
+    let funding_signed = fundee.handle_funding_created(&funding_created)?;
+    let Message::FundingSigned(funding_signed) = transmit(Message::FundingSigned(funding_signed)) else {
+        unreachable!()
+    };
+
+    funder.handle_funding_signed(&funding_signed)
+}
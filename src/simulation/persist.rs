@@ -0,0 +1,83 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+//! Saving simulated channels, so a simulation can stop and pick up again
+//! where it left off, in a later workshop session.
+use crate::internal;
+use crate::simulation::channel::Channel;
+use internal::errors::PersistError;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub trait Persister {
+    /// Save the channel as it is now, in place of what was saved for it
+    /// before.
+    fn persist_channel(&mut self, channel: &Channel) -> Result<(), PersistError>;
+
+    /// Every channel saved, by channel id.
+    fn load_channels(&self) -> Result<Vec<Channel>, PersistError>;
+}
+
+/// Keeps the channels for as long as the process runs, for tests.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryPersister {
+    channels: BTreeMap<[u8; 32], Vec<u8>>,
+}
+
+impl Persister for InMemoryPersister {
+    fn persist_channel(&mut self, channel: &Channel) -> Result<(), PersistError> {
+        self.channels.insert(channel.channel_id, channel.encode());
+        Ok(())
+    }
+
+    fn load_channels(&self) -> Result<Vec<Channel>, PersistError> {
+        self.channels.values().map(|encoded| Ok(Channel::decode(encoded)?)).collect()
+    }
+}
+
+/// Keeps the channels in a JSON file, as an object from each hex channel id
+/// to the hex of `Channel::encode`. The channel keys are in it.
+#[derive(Debug, Clone)]
+pub struct JsonFilePersister {
+    path: PathBuf,
+}
+
+impl JsonFilePersister {
+    /// The file doesn't have to exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> JsonFilePersister {
+        JsonFilePersister { path: path.into() }
+    }
+
+    fn read_store(&self) -> Result<BTreeMap<String, String>, PersistError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|err| PersistError::InvalidStore(err.to_string())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(PersistError::Io(err.to_string())),
+        }
+    }
+
+    /// Write a new file and move it over the old one, so stopping halfway
+    /// through leaves the old one as it was.
+    fn write_store(&self, store: &BTreeMap<String, String>) -> Result<(), PersistError> {
+        let json = serde_json::to_string_pretty(store).unwrap();
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, json).map_err(|err| PersistError::Io(err.to_string()))?;
+        std::fs::rename(&tmp_path, &self.path).map_err(|err| PersistError::Io(err.to_string()))
+    }
+}
+
+impl Persister for JsonFilePersister {
+    fn persist_channel(&mut self, channel: &Channel) -> Result<(), PersistError> {
+        let mut store = self.read_store()?;
+        store.insert(hex::encode(channel.channel_id), hex::encode(channel.encode()));
+        self.write_store(&store)
+    }
+
+    fn load_channels(&self) -> Result<Vec<Channel>, PersistError> {
+        self.read_store()?
+            .values()
+            .map(|encoded| {
+                let encoded = hex::decode(encoded).map_err(|err| PersistError::InvalidStore(err.to_string()))?;
+                Ok(Channel::decode(&encoded)?)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,103 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::onion::{peel_onion, PeeledOnion};
+use crate::internal;
+use crate::simulation::channel::Channel;
+use bitcoin::secp256k1::SecretKey;
+use internal::channel::HtlcState;
+use internal::errors::{ChannelError, ForwardError};
+use internal::graph::ChannelPolicy;
+use internal::onion_utils::HopPayload;
+use internal::wire::{ShortChannelId, UpdateAddHtlc, UpdateFulfillHtlc};
+
+/// An HTLC a relay was offered, and the one it offered the next hop for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Forward {
+    pub incoming_htlc_id: u64,
+    pub outgoing_htlc_id: u64,
+}
+
+/// A node in the middle of a route, forwarding HTLCs from one of its
+/// channels to another. It takes a fee for each payment and a few blocks of
+/// its own to claim the incoming HTLC once the next hop has claimed the
+/// outgoing one.
+#[derive(Debug, Clone)]
+pub struct Relay {
+    /// Peels our layer of each onion.
+    pub node_key: SecretKey,
+    /// The channel with the previous hop.
+    pub incoming: Channel,
+    /// The channel with the next hop, and what we announced for it.
+    pub outgoing: Channel,
+    pub outgoing_short_channel_id: ShortChannelId,
+    pub policy: ChannelPolicy,
+    pub forwards: Vec<Forward>,
+}
+
+impl Relay {
+    /// Offer the next hop the HTLC the onion of incoming HTLC `id` asks for.
+    /// The incoming HTLC has to be committed first: until then, the previous
+    /// hop could still take it back after we have paid the next one.
+    pub fn forward_htlc(&mut self, id: u64) -> Result<UpdateAddHtlc, ForwardError> {
+        let incoming = self
+            .incoming
+            .htlcs
+            .iter()
+            .find(|htlc| !htlc.offered && htlc.id == id)
+            .ok_or(ChannelError::UnknownHtlc(id))?;
+        if incoming.state != HtlcState::Committed {
+            return Err(ChannelError::HtlcNotCommitted(id).into());
+        }
+
+        let PeeledOnion::Forward { payload, next_packet } =
+            peel_onion(&incoming.onion_routing_packet, &self.node_key, &incoming.payment_hash.0)?
+        else {
+            return Err(ForwardError::FinalHop);
+        };
+        let payload = HopPayload::decode(&payload)?;
+        let short_channel_id = payload.short_channel_id.ok_or(ForwardError::FinalHop)?;
+        if short_channel_id != self.outgoing_short_channel_id {
+            return Err(ForwardError::UnknownNextPeer(short_channel_id.0));
+        }
+
+        let fee = self.policy.fee_for(payload.amt_to_forward);
+        if payload.amt_to_forward.checked_add(fee).is_none_or(|required| incoming.amount_msat < required) {
+            return Err(ForwardError::FeeInsufficient {
+                incoming_msat: incoming.amount_msat.to_msat(),
+                amt_to_forward_msat: payload.amt_to_forward.to_msat(),
+                fee_msat: fee.to_msat(),
+            });
+        }
+        let cltv_expiry_delta = self.policy.cltv_expiry_delta;
+        if incoming.cltv_expiry < payload.outgoing_cltv_value.saturating_add(cltv_expiry_delta as u32) {
+            return Err(ForwardError::IncorrectCltvExpiry {
+                incoming: incoming.cltv_expiry,
+                outgoing: payload.outgoing_cltv_value,
+                cltv_expiry_delta,
+            });
+        }
+
+        let payment_hash = incoming.payment_hash;
+        let update_add_htlc =
+            self.outgoing.send_htlc(payload.amt_to_forward, payment_hash, payload.outgoing_cltv_value, *next_packet)?;
+        self.forwards.push(Forward { incoming_htlc_id: id, outgoing_htlc_id: update_add_htlc.id });
+        Ok(update_add_htlc)
+    }
+
+    /// The next hop claimed the HTLC we offered it, so claim the one we were
+    /// offered for it with the same preimage.
+    pub fn handle_update_fulfill_htlc(
+        &mut self,
+        update_fulfill_htlc: &UpdateFulfillHtlc,
+    ) -> Result<UpdateFulfillHtlc, ChannelError> {
+        let payment_preimage = self.outgoing.handle_update_fulfill_htlc(update_fulfill_htlc)?;
+        let forward = self
+            .forwards
+            .iter()
+            .find(|forward| forward.outgoing_htlc_id == update_fulfill_htlc.id)
+            .copied()
+            .ok_or(ChannelError::UnknownHtlc(update_fulfill_htlc.id))?;
+        self.forwards.retain(|other| *other != forward);
+
+        self.incoming.fulfill_htlc(forward.incoming_htlc_id, payment_preimage)
+    }
+}
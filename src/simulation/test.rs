@@ -0,0 +1,561 @@
+#![allow(dead_code, unused_imports, unused_variables, unused_must_use)]
+use crate::exercises::exercises::{htlc_payment_hash, two_of_two_multisig_witness_script};
+use crate::internal;
+use crate::exercises::onion::build_payment_onion;
+use crate::simulation::channel::Channel;
+use crate::simulation::closing::{negotiate_closing_fee, shutdown, ClosingNegotiation};
+use crate::simulation::dual_funding::negotiate_funding_transaction;
+use crate::simulation::node::{open_channel_between, transmit, Node};
+use crate::simulation::persist::{InMemoryPersister, JsonFilePersister, Persister};
+use bitcoin::hash_types::Txid;
+use bitcoin::script::ScriptBuf;
+use bitcoin::locktime::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, Network, OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+use internal::errors::{ChannelError, ClosingError, InteractiveTxError, PersistError, ShutdownScriptError};
+use internal::fee_utils::{fee_for_weight, sat_per_vb_to_per_kw, MempoolSpaceFeeEstimator, FUNDING_WITNESS_WEIGHT};
+use internal::interactive_tx::InteractiveTxConstructor;
+use internal::payments::{PaymentPreimage, PaymentSecret};
+use internal::invoice::decode_invoice;
+use internal::key_utils::{pubkey_from_private_key, secp256k1_private_key};
+use internal::script_utils::p2wpkh_output_script;
+use internal::keys::ChannelKeyManager;
+use internal::routing::{Route, RouteHop};
+use internal::shachain::MAX_SHACHAIN_INDEX;
+use internal::units::MilliSat;
+use internal::wire::{Message, ShortChannelId};
+
+fn key_manager(byte: u8) -> ChannelKeyManager {
+    ChannelKeyManager {
+        funding_key: secp256k1_private_key(&[byte; 32]),
+        payment_base_key: secp256k1_private_key(&[byte + 1; 32]),
+        delayed_payment_base_key: secp256k1_private_key(&[byte + 2; 32]),
+        htlc_base_key: secp256k1_private_key(&[byte + 3; 32]),
+        revocation_base_key: secp256k1_private_key(&[byte + 4; 32]),
+        commitment_seed: [byte + 5; 32],
+    }
+}
+
+fn funding_txin() -> TxIn {
+    TxIn {
+        previous_output: OutPoint::new(
+            "fd2105607605d2302994ffea703b09f66b6351816ee737a93e42a841ea20bbad".parse::<Txid>().unwrap(),
+            0,
+        ),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    }
+}
+
+#[test]
+fn test_channel_open_simulation() {
+    let mut alice = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    bob.to_self_delay = 720;
+    bob.dust_limit = Amount::from_sat(354);
+
+    let funding_amount = Amount::from_sat(5_000_000);
+    let funding_tx = open_channel_between(
+        &mut alice,
+        &mut bob,
+        funding_txin(),
+        funding_amount,
+        MilliSat::from_msat(1_000_000_000),
+    )
+    .unwrap();
+
+    let alice_channel = alice.channel.as_ref().unwrap();
+    let bob_channel = bob.channel.as_ref().unwrap();
+
+    // Both sides agree on the channel and on each other's first commitment.
+    assert_eq!(alice_channel.channel_id, bob_channel.channel_id);
+    assert_eq!(alice_channel.params.funding_outpoint, bob_channel.params.funding_outpoint);
+    assert_eq!(alice_channel.funding_script, bob_channel.funding_script);
+
+    let alice_commitment = alice_channel.holder_commitment.as_ref().unwrap();
+    let bob_commitment = bob_channel.holder_commitment.as_ref().unwrap();
+    assert_eq!(alice_commitment.compute_txid(), bob_channel.counterparty_commitment.as_ref().unwrap().compute_txid());
+    assert_eq!(bob_commitment.compute_txid(), alice_channel.counterparty_commitment.as_ref().unwrap().compute_txid());
+
+    // Each side waits for the delay the other asked for.
+    assert_eq!(alice_channel.params.to_self_delay, 720);
+    assert_eq!(bob_channel.params.to_self_delay, 144);
+
+    // And both signed commitments really spend the funding output.
+    let funding_output = funding_tx.output[0].clone();
+    for commitment in [alice_commitment, bob_commitment] {
+        commitment
+            .verify(|outpoint| (outpoint.txid == funding_tx.compute_txid()).then(|| funding_output.clone()))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_channel_open_simulation_rejects_bad_signature() {
+    let mut alice = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+
+    let open_channel = alice.open_channel([0x42; 32], funding_txin(), Amount::from_sat(5_000_000), MilliSat::ZERO);
+    let accept_channel = bob.handle_open_channel(&open_channel);
+    let mut funding_created = alice.handle_accept_channel(&accept_channel).unwrap();
+
+    // Alice signs something other than the commitment.
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    funding_created.signature =
+        secp.sign_ecdsa(&bitcoin::secp256k1::Message::from_digest([0x01; 32]), &alice.keys.funding_key);
+
+    assert_eq!(bob.handle_funding_created(&funding_created), Err(ChannelError::InvalidCommitmentSignature));
+}
+
+#[test]
+fn test_invoice_between_nodes() {
+    let mut alice = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    open_channel_between(&mut alice, &mut bob, funding_txin(), Amount::from_sat(5_000_000), MilliSat::ZERO).unwrap();
+
+    // Bob asks Alice to pay for a coffee over their new channel.
+    let preimage = PaymentPreimage([0x42; 32]);
+    let amount = MilliSat::from_msat(25_000_000);
+    let invoice = bob.create_invoice(amount, &preimage, &PaymentSecret([0x07; 32]), "coffee", 1_700_000_000).unwrap();
+    assert!(invoice.starts_with("lnbcrt250u1"));
+
+    let decoded = decode_invoice(&invoice).unwrap();
+    assert_eq!(decoded.payee_pubkey, bob.node_id());
+    assert_eq!(decoded.network, Network::Regtest);
+    assert_eq!(decoded.amount, Some(amount));
+    assert!(decoded.amount.unwrap() <= alice.channel.as_ref().unwrap().local_msat);
+
+    // The HTLC Alice offers locks to a hash only Bob's preimage opens.
+    assert_eq!(htlc_payment_hash(&decoded), preimage.hash160());
+}
+
+/// Open a channel from Alice to Bob and exchange `channel_ready`.
+fn ready_channel(alice: &mut Node, bob: &mut Node, push_msat: MilliSat) -> (Channel, Channel) {
+    open_channel_between(alice, bob, funding_txin(), Amount::from_sat(5_000_000), push_msat).unwrap();
+    let Message::ChannelReady(alice_ready) = transmit(Message::ChannelReady(alice.channel_ready())) else {
+        unreachable!()
+    };
+    let Message::ChannelReady(bob_ready) = transmit(Message::ChannelReady(bob.channel_ready())) else { unreachable!() };
+    (alice.handle_channel_ready(&bob_ready), bob.handle_channel_ready(&alice_ready))
+}
+
+/// `sender` signs, `receiver` revokes, and both check they now have the
+/// same version of `receiver`'s commitment.
+fn sign_and_revoke(sender: &mut Channel, receiver: &mut Channel) {
+    let Message::CommitmentSigned(commitment_signed) =
+        transmit(Message::CommitmentSigned(sender.commitment_signed().unwrap()))
+    else {
+        unreachable!()
+    };
+    let revoke_and_ack = receiver.handle_commitment_signed(&commitment_signed).unwrap();
+    assert_eq!(receiver.local_commitment.compute_txid(), sender.latest_remote_commitment().compute_txid());
+
+    let Message::RevokeAndAck(revoke_and_ack) = transmit(Message::RevokeAndAck(revoke_and_ack)) else { unreachable!() };
+    sender.handle_revoke_and_ack(&revoke_and_ack).unwrap();
+    assert_eq!(receiver.local_commitment.compute_txid(), sender.remote_commitment.compute_txid());
+}
+
+#[test]
+fn test_channel_htlc_round_trip() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (mut alice, mut bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::ZERO);
+    let funding_tx = alice_node.channel.as_ref().unwrap().funding_tx.clone().unwrap();
+
+    let preimage = PaymentPreimage([0x42; 32]);
+    let amount = MilliSat::from_msat(25_000_000);
+    let route = Route {
+        hops: vec![RouteHop {
+            node_id: bob_node.node_id(),
+            short_channel_id: ShortChannelId::new(700_000, 1, 0),
+            amount_msat: amount,
+            cltv_expiry_delta: 0,
+        }],
+    };
+    let onion = build_payment_onion(
+        &route,
+        800_000,
+        &PaymentSecret([0x07; 32]),
+        &preimage.hash(),
+        &secp256k1_private_key(&[0x03; 32]),
+    )
+    .unwrap();
+    let update_add_htlc = alice.send_htlc(amount, preimage.hash(), onion.cltv_expiry, onion.packet).unwrap();
+    let Message::UpdateAddHtlc(update_add_htlc) = transmit(Message::UpdateAddHtlc(update_add_htlc)) else {
+        unreachable!()
+    };
+    bob.handle_update_add_htlc(&update_add_htlc).unwrap();
+
+    // Bob can't settle an HTLC Alice could still take back.
+    assert_eq!(bob.fulfill_htlc(0, preimage), Err(ChannelError::HtlcNotCommitted(0)));
+
+    // Alice signs Bob's commitment with the HTLC in it, but can't sign
+    // another until Bob revokes his old one.
+    let commitment_signed = alice.commitment_signed().unwrap();
+    assert_eq!(commitment_signed.htlc_signatures.len(), 1);
+    assert_eq!(alice.commitment_signed(), Err(ChannelError::AwaitingRevokeAndAck));
+    let revoke_and_ack = bob.handle_commitment_signed(&commitment_signed).unwrap();
+    assert_eq!(revoke_and_ack.per_commitment_secret, bob_node.keys.per_commitment_secret(0));
+    alice.handle_revoke_and_ack(&revoke_and_ack).unwrap();
+    assert_eq!(bob.local_commitment.compute_txid(), alice.remote_commitment.compute_txid());
+    sign_and_revoke(&mut bob, &mut alice);
+
+    // The HTLC is an output of both commitments now.
+    for commitment in [&alice.local_commitment, &bob.local_commitment] {
+        assert_eq!(commitment.output.iter().filter(|output| output.value == Amount::from_sat(25_000)).count(), 1);
+    }
+
+    // Bob claims it with the preimage, and the fulfill goes round the same way.
+    assert_eq!(bob.fulfill_htlc(0, PaymentPreimage([0x43; 32])), Err(ChannelError::InvalidPaymentPreimage(0)));
+    let update_fulfill_htlc = bob.fulfill_htlc(0, preimage).unwrap();
+    let Message::UpdateFulfillHtlc(update_fulfill_htlc) = transmit(Message::UpdateFulfillHtlc(update_fulfill_htlc))
+    else {
+        unreachable!()
+    };
+    assert_eq!(alice.handle_update_fulfill_htlc(&update_fulfill_htlc), Ok(preimage));
+    sign_and_revoke(&mut bob, &mut alice);
+    sign_and_revoke(&mut alice, &mut bob);
+
+    // Both sides agree the payment went through, and on both commitments.
+    let funding_msat = MilliSat::from_msat(5_000_000_000);
+    assert!(alice.htlcs.is_empty() && bob.htlcs.is_empty());
+    assert_eq!((alice.to_local_msat, alice.to_remote_msat), (funding_msat.checked_sub(amount).unwrap(), amount));
+    assert_eq!((bob.to_local_msat, bob.to_remote_msat), (alice.to_remote_msat, alice.to_local_msat));
+    assert_eq!((alice.local_commitment_number, bob.local_commitment_number), (2, 2));
+    assert_eq!(alice.local_commitment.compute_txid(), bob.remote_commitment.compute_txid());
+    assert_eq!(bob.local_commitment.compute_txid(), alice.remote_commitment.compute_txid());
+
+    // Just the balances and the anchors are left.
+    let funding_output = funding_tx.output[0].clone();
+    for commitment in [&alice.local_commitment, &bob.local_commitment] {
+        assert_eq!(commitment.output.len(), 4);
+        commitment
+            .verify(|outpoint| (outpoint.txid == funding_tx.compute_txid()).then(|| funding_output.clone()))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_channel_rejects_bad_htlc_signature() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (mut alice, mut bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::ZERO);
+
+    let preimage = PaymentPreimage([0x42; 32]);
+    let packet = build_payment_onion(
+        &Route {
+            hops: vec![RouteHop {
+                node_id: bob_node.node_id(),
+                short_channel_id: ShortChannelId::new(700_000, 1, 0),
+                amount_msat: MilliSat::from_msat(25_000_000),
+                cltv_expiry_delta: 0,
+            }],
+        },
+        800_000,
+        &PaymentSecret([0x07; 32]),
+        &preimage.hash(),
+        &secp256k1_private_key(&[0x03; 32]),
+    )
+    .unwrap()
+    .packet;
+    let update_add_htlc = alice.send_htlc(MilliSat::from_msat(25_000_000), preimage.hash(), 800_000, packet).unwrap();
+    bob.handle_update_add_htlc(&update_add_htlc).unwrap();
+    assert_eq!(
+        bob.handle_update_add_htlc(&update_add_htlc),
+        Err(ChannelError::UnexpectedHtlcId { expected: 1, actual: 0 })
+    );
+
+    let mut commitment_signed = alice.commitment_signed().unwrap();
+    let valid = commitment_signed.clone();
+
+    // The HTLC signature has to be there, and has to sign Bob's HTLC-success.
+    commitment_signed.htlc_signatures.clear();
+    assert_eq!(
+        bob.handle_commitment_signed(&commitment_signed),
+        Err(ChannelError::HtlcSignatureCountMismatch { expected: 1, actual: 0 })
+    );
+    commitment_signed.htlc_signatures.push(valid.signature);
+    assert_eq!(bob.handle_commitment_signed(&commitment_signed), Err(ChannelError::InvalidHtlcSignature { index: 0 }));
+
+    // Rejected signatures leave Bob where he was.
+    assert_eq!(bob.local_commitment_number, 0);
+    assert!(bob.handle_commitment_signed(&valid).is_ok());
+    assert_eq!(bob.local_commitment_number, 1);
+
+    // Ask for more than Alice has left and the HTLC never gets proposed.
+    let packet = *update_add_htlc.onion_routing_packet.clone();
+    assert_eq!(
+        alice.send_htlc(MilliSat::from_msat(4_980_000_000), preimage.hash(), 800_000, packet),
+        Err(ChannelError::InsufficientBalance { available_msat: 4_975_000_000, required_msat: 4_980_000_000 })
+    );
+}
+
+#[test]
+fn test_channel_rejects_bad_revocation() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (mut alice, mut bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::ZERO);
+
+    let commitment_signed = alice.commitment_signed().unwrap();
+    let revoke_and_ack = bob.handle_commitment_signed(&commitment_signed).unwrap();
+
+    // Bob tries to keep his first commitment by revealing the wrong secret.
+    let mut bad_revocation = revoke_and_ack.clone();
+    bad_revocation.per_commitment_secret = bob_node.keys.per_commitment_secret(1);
+    assert_eq!(
+        alice.handle_revoke_and_ack(&bad_revocation),
+        Err(ChannelError::InvalidRevocationSecret { commitment_number: 0 })
+    );
+    assert_eq!(alice.commitment_signed(), Err(ChannelError::AwaitingRevokeAndAck));
+
+    // The real one lets Alice punish him if he ever broadcasts it.
+    alice.handle_revoke_and_ack(&revoke_and_ack).unwrap();
+    assert_eq!(alice.counterparty_commitments.commitment_number, 1);
+    assert_eq!(
+        alice.counterparty_commitments.revoked_secrets.derive(MAX_SHACHAIN_INDEX),
+        Some(bob_node.keys.per_commitment_secret(0))
+    );
+    assert_eq!(alice.handle_revoke_and_ack(&revoke_and_ack), Err(ChannelError::UnexpectedRevokeAndAck));
+}
+
+#[test]
+fn test_dual_funded_open_simulation() {
+    let alice_funding_pubkey = pubkey_from_private_key(&[0x10; 32]);
+    let bob_funding_pubkey = pubkey_from_private_key(&[0x20; 32]);
+    let funding_script = two_of_two_multisig_witness_script(&alice_funding_pubkey, &bob_funding_pubkey).to_p2wsh();
+    let wallet_script = |byte: u8| p2wpkh_output_script(pubkey_from_private_key(&[byte; 32]));
+    let wallet_prevtx = |value: u64, byte: u8| Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![funding_txin()],
+        output: vec![TxOut { value: Amount::from_sat(value), script_pubkey: wallet_script(byte) }],
+    };
+
+    let new_side = |is_initiator| {
+        InteractiveTxConstructor::new(
+            [0x42; 32],
+            is_initiator,
+            funding_script.clone(),
+            Amount::from_sat(354),
+            LockTime::ZERO,
+        )
+    };
+    let mut alice = new_side(true);
+    let mut bob = new_side(false);
+
+    // Alice puts in 3_000_000 sats and Bob 2_000_000, each from one UTXO
+    // with change back to themselves. The initiator adds the funding
+    // output, and pays the common fields of the fee.
+    let alice_prevtx = wallet_prevtx(3_500_000, 0x01);
+    let bob_prevtx = wallet_prevtx(2_200_000, 0x02);
+    alice.contribute_input(alice_prevtx.clone(), 0, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    alice.contribute_output(TxOut { value: Amount::from_sat(5_000_000), script_pubkey: funding_script.clone() });
+    alice.contribute_output(TxOut { value: Amount::from_sat(499_000), script_pubkey: wallet_script(0x01) });
+    bob.contribute_input(bob_prevtx.clone(), 0, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    bob.contribute_output(TxOut { value: Amount::from_sat(199_500), script_pubkey: wallet_script(0x02) });
+
+    let (alice_tx, bob_tx) = negotiate_funding_transaction(&mut alice, &mut bob).unwrap();
+    assert_eq!(alice_tx, bob_tx);
+    assert!(alice.is_complete() && bob.is_complete());
+
+    // inputs and outputs from both sides, in serial id order
+    let spent: Vec<_> = alice_tx.input.iter().map(|input| input.previous_output).collect();
+    assert_eq!(spent, vec![OutPoint::new(alice_prevtx.compute_txid(), 0), OutPoint::new(bob_prevtx.compute_txid(), 0)]);
+    let scripts: Vec<_> = alice_tx.output.iter().map(|output| output.script_pubkey.clone()).collect();
+    assert_eq!(scripts, vec![funding_script.clone(), wallet_script(0x02), wallet_script(0x01)]);
+    assert_eq!(alice_tx.output[0].value, Amount::from_sat(5_000_000));
+
+    // Bob's dust change fails the negotiation on Alice's side
+    let mut alice = new_side(true);
+    let mut bob = new_side(false);
+    alice.contribute_input(alice_prevtx, 0, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    alice.contribute_output(TxOut { value: Amount::from_sat(5_000_000), script_pubkey: funding_script });
+    bob.contribute_input(bob_prevtx, 0, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    bob.contribute_output(TxOut { value: Amount::from_sat(100), script_pubkey: wallet_script(0x02) });
+    assert_eq!(
+        negotiate_funding_transaction(&mut alice, &mut bob),
+        Err(InteractiveTxError::DustOutput { value: 100, dust_limit: 354 })
+    );
+}
+
+/// Both sides of Alice and Bob's channel after trading `shutdown`, each
+/// estimating fees with its own view of the mempool, in sat/vB.
+fn closing_negotiations(
+    alice: &Channel,
+    bob: &Channel,
+    alice_fees: MempoolSpaceFeeEstimator,
+    bob_fees: MempoolSpaceFeeEstimator,
+) -> Result<(ClosingNegotiation, ClosingNegotiation), ClosingError> {
+    let alice_script = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+    let bob_script = p2wpkh_output_script(pubkey_from_private_key(&[0x02; 32]));
+    let Message::Shutdown(alice_shutdown) = transmit(Message::Shutdown(shutdown(alice, alice_script.clone()))) else {
+        unreachable!()
+    };
+    let Message::Shutdown(bob_shutdown) = transmit(Message::Shutdown(shutdown(bob, bob_script.clone()))) else {
+        unreachable!()
+    };
+    Ok((
+        ClosingNegotiation::new(alice, alice_script, &bob_shutdown, &alice_fees)?,
+        ClosingNegotiation::new(bob, bob_script, &alice_shutdown, &bob_fees)?,
+    ))
+}
+
+#[test]
+fn test_cooperative_close_simulation() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (alice, bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::from_msat(1_000_000_000));
+
+    // Alice would pay 5 to 20 sat/vB and proposes 10. Bob would take 2 to 8,
+    // so he counters with 8, and Alice, who pays, has to agree.
+    let alice_fees = MempoolSpaceFeeEstimator { fastest_fee: 20.0, half_hour_fee: 10.0, economy_fee: 5.0 };
+    let bob_fees = MempoolSpaceFeeEstimator { fastest_fee: 8.0, half_hour_fee: 4.0, economy_fee: 2.0 };
+    let (mut alice_close, mut bob_close) = closing_negotiations(&alice, &bob, alice_fees, bob_fees).unwrap();
+    let (alice_closing_tx, bob_closing_tx) = negotiate_closing_fee(&mut alice_close, &mut bob_close).unwrap();
+    assert_eq!(alice_closing_tx, bob_closing_tx);
+
+    let weight = alice_close.closing_transaction(Amount::ZERO).weight().to_wu() + FUNDING_WITNESS_WEIGHT;
+    let fee = fee_for_weight(sat_per_vb_to_per_kw(8.0), weight);
+    assert_eq!(alice_close.last_sent_fee, Some(fee));
+    assert_eq!(bob_close.last_sent_fee, Some(fee));
+
+    // Alice's 4_000_000 sats pay the fee, and Bob gets the 1_000_000 pushed to him
+    let outputs: Vec<(Amount, ScriptBuf)> =
+        alice_closing_tx.output.iter().map(|output| (output.value, output.script_pubkey.clone())).collect();
+    assert_eq!(
+        outputs,
+        vec![
+            (Amount::from_sat(1_000_000), bob_close.local_script.clone()),
+            (Amount::from_sat(4_000_000) - fee, alice_close.local_script.clone()),
+        ]
+    );
+
+    let funding_output = TxOut { value: alice.funding_amount, script_pubkey: alice.funding_script.to_p2wsh() };
+    alice_closing_tx
+        .verify(|outpoint| (*outpoint == alice.params.funding_outpoint).then(|| funding_output.clone()))
+        .unwrap();
+}
+
+#[test]
+fn test_cooperative_close_simulation_without_fee_overlap() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (alice, bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::ZERO);
+
+    // Bob wants at least 30 sat/vB, more than Alice would ever pay
+    let alice_fees = MempoolSpaceFeeEstimator { fastest_fee: 20.0, half_hour_fee: 10.0, economy_fee: 5.0 };
+    let bob_fees = MempoolSpaceFeeEstimator { fastest_fee: 60.0, half_hour_fee: 40.0, economy_fee: 30.0 };
+    let (mut alice_close, mut bob_close) = closing_negotiations(&alice, &bob, alice_fees, bob_fees).unwrap();
+    assert_eq!(
+        negotiate_closing_fee(&mut alice_close, &mut bob_close),
+        Err(ClosingError::NoFeeRangeOverlap { ours: bob_close.fee_range, theirs: alice_close.fee_range })
+    );
+    assert_eq!(alice_close.closing_tx, None);
+}
+
+#[test]
+fn test_cooperative_close_simulation_rejects_shutdown_script() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (alice, bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::ZERO);
+
+    // Bob asks to be paid to an output nobody can spend
+    let op_return = ScriptBuf::from_hex("6a0401020304").unwrap();
+    let fees = MempoolSpaceFeeEstimator { fastest_fee: 20.0, half_hour_fee: 10.0, economy_fee: 5.0 };
+    let alice_script = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+    assert_eq!(
+        ClosingNegotiation::new(&alice, alice_script, &shutdown(&bob, op_return.clone()), &fees).map(|_| ()),
+        Err(ClosingError::InvalidShutdownScript(ShutdownScriptError::Unsupported(op_return)))
+    );
+}
+
+#[test]
+fn test_cooperative_close_simulation_with_upfront_shutdown_scripts() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let alice_script = p2wpkh_output_script(pubkey_from_private_key(&[0x01; 32]));
+    let bob_script = p2wpkh_output_script(pubkey_from_private_key(&[0x02; 32]));
+    alice_node.upfront_shutdown_script = Some(alice_script.clone());
+    bob_node.upfront_shutdown_script = Some(bob_script.clone());
+    let (alice, bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::from_msat(1_000_000_000));
+
+    // each side learned the other's script from open_channel and accept_channel
+    assert_eq!(alice.local_upfront_shutdown_script, Some(alice_script.clone()));
+    assert_eq!(alice.counterparty_upfront_shutdown_script, Some(bob_script.clone()));
+    assert_eq!(bob.local_upfront_shutdown_script, Some(bob_script.clone()));
+    assert_eq!(bob.counterparty_upfront_shutdown_script, Some(alice_script.clone()));
+
+    // closing to the committed scripts works as before
+    let fees = MempoolSpaceFeeEstimator { fastest_fee: 20.0, half_hour_fee: 10.0, economy_fee: 5.0 };
+    let (mut alice_close, mut bob_close) = closing_negotiations(&alice, &bob, fees, fees).unwrap();
+    let (alice_closing_tx, bob_closing_tx) = negotiate_closing_fee(&mut alice_close, &mut bob_close).unwrap();
+    assert_eq!(alice_closing_tx, bob_closing_tx);
+
+    // but Bob, or whoever took over his node, can't close anywhere else
+    let elsewhere = p2wpkh_output_script(pubkey_from_private_key(&[0x66; 32]));
+    let Message::Shutdown(bob_shutdown) = transmit(Message::Shutdown(shutdown(&bob, elsewhere))) else {
+        unreachable!()
+    };
+    assert_eq!(
+        ClosingNegotiation::new(&alice, alice_script, &bob_shutdown, &fees).map(|_| ()),
+        Err(ClosingError::InvalidShutdownScript(ShutdownScriptError::UpfrontScriptMismatch))
+    );
+}
+
+#[test]
+fn test_channel_persistence_resumes_simulation() {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    let mut bob_node = Node::new(secp256k1_private_key(&[0x02; 32]), key_manager(0x20), Network::Regtest);
+    let (mut alice, mut bob) = ready_channel(&mut alice_node, &mut bob_node, MilliSat::from_msat(1_000_000_000));
+    sign_and_revoke(&mut alice, &mut bob);
+
+    // stop with an HTLC offered and signed for, but not yet revoked for
+    let preimage = PaymentPreimage([0x42; 32]);
+    let amount = MilliSat::from_msat(25_000_000);
+    let route = Route {
+        hops: vec![RouteHop {
+            node_id: bob_node.node_id(),
+            short_channel_id: ShortChannelId::new(700_000, 1, 0),
+            amount_msat: amount,
+            cltv_expiry_delta: 0,
+        }],
+    };
+    let onion = build_payment_onion(
+        &route,
+        800_000,
+        &PaymentSecret([0x07; 32]),
+        &preimage.hash(),
+        &secp256k1_private_key(&[0x03; 32]),
+    )
+    .unwrap();
+    let update_add_htlc = alice.send_htlc(amount, preimage.hash(), onion.cltv_expiry, onion.packet).unwrap();
+    bob.handle_update_add_htlc(&update_add_htlc).unwrap();
+    let commitment_signed = alice.commitment_signed().unwrap();
+
+    let alice_path = std::env::temp_dir().join(format!("pl-00-intro-{}-alice.json", std::process::id()));
+    let mut alice_persister = JsonFilePersister::new(&alice_path);
+    let mut bob_persister = InMemoryPersister::default();
+    alice_persister.persist_channel(&alice).unwrap();
+    bob_persister.persist_channel(&bob).unwrap();
+    // persisting again replaces the channel
+    alice_persister.persist_channel(&alice).unwrap();
+
+    let [mut restored_alice] =
+        <[Channel; 1]>::try_from(JsonFilePersister::new(&alice_path).load_channels().unwrap()).unwrap();
+    let [mut restored_bob] = <[Channel; 1]>::try_from(bob_persister.load_channels().unwrap()).unwrap();
+    assert_eq!(restored_alice, alice);
+    assert_eq!(restored_bob, bob);
+
+    // and pick up where the simulation left off
+    let revoke_and_ack = restored_bob.handle_commitment_signed(&commitment_signed).unwrap();
+    restored_alice.handle_revoke_and_ack(&revoke_and_ack).unwrap();
+    sign_and_revoke(&mut restored_bob, &mut restored_alice);
+    let update_fulfill_htlc = restored_bob.fulfill_htlc(0, preimage).unwrap();
+    assert_eq!(restored_alice.handle_update_fulfill_htlc(&update_fulfill_htlc), Ok(preimage));
+
+    std::fs::write(&alice_path, "not json").unwrap();
+    assert!(matches!(alice_persister.load_channels(), Err(PersistError::InvalidStore(_))));
+    std::fs::remove_file(&alice_path).unwrap();
+    assert_eq!(alice_persister.load_channels(), Ok(vec![]));
+}
@@ -0,0 +1,165 @@
+//! BOLT 11 invoice vectors, run through the invoice decoder.
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::Network;
+use pl_00_intro::internal::errors::InvoiceError;
+use pl_00_intro::internal::invoice::{decode_invoice, RouteHintHop};
+use pl_00_intro::internal::payments::{PaymentHash, PaymentSecret};
+use pl_00_intro::internal::units::MilliSat;
+use pl_00_intro::internal::wire::{has_feature, ShortChannelId};
+
+/// The key every spec invoice is signed with.
+const PAYEE_PUBKEY: &str = "03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad";
+const PAYMENT_HASH: &str = "0001020304050607080900010203040506070809000102030405060708090102";
+const TIMESTAMP: u64 = 1496314658;
+
+const DONATION: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+const COFFEE: &str = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+const NONSENSE: &str = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpu9qrsgqhtjpauu9ur7fw2thcl4y9vfvh4m9wlfyz2gem29g5ghe2aak2pm3ps8fdhtceqsaagty2vph7utlgj48u0ged6a337aewvraedendscp573dxr";
+const TESTNET_FALLBACK: &str = "lntb20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygshp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqfpp3x9et2e20v6pu37c5d9vax37wxq72un989qrsgqdj545axuxtnfemtpwkc45hx9d2ft7x04mt8q7y6t0k2dge9e7h8kpy9p34ytyslj3yu569aalz2xdk8xkd7ltxqld94u8h2esmsmacgpghe9k8";
+const ROUTE_HINTS: &str = "lnbc20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqsfpp3qjmp7lwpagxun9pygexvgpjdc4jdj85fr9yq20q82gphp2nflc7jtzrcazrra7wwgzxqc8u7754cdlpfrmccae92qgzqvzq2ps8pqqqqqqpqqqqq9qqqvpeuqafqxu92d8lr6fvg0r5gv0heeeqgcrqlnm6jhphu9y00rrhy4grqszsvpcgpy9qqqqqqgqqqqq7qqzq9qrsgqdfjcdk6w3ak5pca9hwfwfh63zrrz06wwfya0ydlzpgzxkn5xagsqz7x9j4jwe7yj7vaf2k9lqsdk45kts2fd0fkr28am0u4w95tt2nsq76cqw0";
+const BLOCKSTREAM_STORE: &str = "lnbc9678785340p1pwmna7lpp5gc3xfm08u9qy06djf8dfflhugl6p7lgza6dsjxq454gxhj9t7a0sd8dgfkx7cmtwd68yetpd5s9xar0wfjn5gpc8qhrsdfq24f5ggrxdaezqsnvda3kkum5wfjkzmfqf3jkgem9wgsyuctwdus9xgrcyqcjcgpzgfskx6eqf9hzqnteypzxz7fzypfhg6trddjhygrcyqezcgpzfysywmm5ypxxjemgw3hxjmn8yptk7untd9hxwg3q2d6xjcmtv4ezq7pqxgsxzmnyyqcjqmt0wfjjq6t5v4khxsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygsxqyjw5qcqp2rzjq0gxwkzc8w6323m55m4jyxcjwmy7stt9hwkwe2qxmy8zpsgg7jcuwz87fcqqeuqqqyqqqqlgqqqqn3qq9q9qrsgqrvgkpnmps664wgkp43l22qsgdw4ve24aca4nymnxddlnp8vh9v2sdxlu5ywdxefsfvm0fq3sesf08uf6q9a2ke0hc9j6z6wlxg5z5kqpu2v9wz";
+const COFFEE_BEANS: &str = "lnbc25m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5vdhkven9v5sxyetpdeessp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9q5sqqqqqqqqqqqqqqqqsgq2a25dxl5hrntdtn6zvydt7d66hyzsyhqs4wdynavys42xgl6sgx9c4g7me86a27t07mdtfry458rtjr0v92cnmswpsjscgt2vcse3sgpz3uapa";
+const WRONG_LENGTH_FIELDS: &str = "lnbc25m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5vdhkven9v5sxyetpdeessp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9q5sqqqqqqqqqqqqqqqqsgq2qrqqqfppnqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqppnqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqpp4qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqhpnqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqhp4qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqspnqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp4qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqnp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqnpkqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqz599y53s3ujmcfjp5xrdap68qxymkqphwsexhmhr8wdz5usdzkzrse33chw6dlp3jhuhge9ley7j2ayx36kawe7kmgg8sv5ugdyusdcqzn8z9x";
+const EXPLICIT_PAYEE: &str = "lnbc10m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdp9wpshjmt9de6zqmt9w3skgct5vysxjmnnd9jx2mq8q8a04uqnp4q0n326hr8v9zprg8gsvezcch06gfaqqhde2aj730yg0durunfhv66sp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9q2gqqqqqqsgqy9gw6ymamd20jumvdgpfphkhp8fzhhdhycw36egcmla5vlrtrmhs9t7psfy3hkkdqzm9eq64fjg558znccds5nhsfmxveha5xe0dykgpspdha0";
+
+const CAKE: &[u8] = b"One piece of chocolate cake, one icecream cone, one pickle, one slice of swiss cheese, one slice of salami, one lollypop, one piece of cherry pie, one sausage, one cupcake, and one slice of watermelon";
+
+fn pubkey(hex_str: &str) -> secp256k1PublicKey {
+    secp256k1PublicKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+fn payment_hash() -> PaymentHash {
+    PaymentHash::from_slice(&hex::decode(PAYMENT_HASH).unwrap()).unwrap()
+}
+
+#[test]
+fn bolt11_donation_without_amount() {
+    let invoice = decode_invoice(DONATION).unwrap();
+    assert_eq!(invoice.network, Network::Bitcoin);
+    assert_eq!(invoice.amount, None);
+    assert_eq!(invoice.timestamp, TIMESTAMP);
+    assert_eq!(invoice.payment_hash, payment_hash());
+    assert_eq!(invoice.payment_secret, Some(PaymentSecret([0x11; 32])));
+    assert_eq!(invoice.description.as_deref(), Some("Please consider supporting this project"));
+    assert_eq!(invoice.description_hash, None);
+    assert_eq!(invoice.payee_pubkey, pubkey(PAYEE_PUBKEY));
+    assert_eq!(invoice.expiry, 3600);
+    assert_eq!(invoice.min_final_cltv_expiry_delta, 18);
+    assert!(invoice.route_hints.is_empty());
+    assert!(has_feature(&invoice.features, 8));
+    assert!(has_feature(&invoice.features, 14));
+}
+
+#[test]
+fn bolt11_amounts_and_descriptions() {
+    let coffee = decode_invoice(COFFEE).unwrap();
+    assert_eq!(coffee.amount, Some(MilliSat::from_msat(250_000_000)));
+    assert_eq!(coffee.description.as_deref(), Some("1 cup coffee"));
+    assert_eq!(coffee.expiry, 60);
+    assert_eq!(coffee.payee_pubkey, pubkey(PAYEE_PUBKEY));
+
+    let nonsense = decode_invoice(NONSENSE).unwrap();
+    assert_eq!(nonsense.description.as_deref(), Some("ナンセンス 1杯"));
+
+    // The fallback address is skipped; the description is only committed to.
+    let testnet = decode_invoice(TESTNET_FALLBACK).unwrap();
+    assert_eq!(testnet.network, Network::Testnet);
+    assert_eq!(testnet.amount, Some(MilliSat::from_msat(2_000_000_000)));
+    assert_eq!(testnet.description, None);
+    assert_eq!(testnet.description_hash, Some(Sha256::hash(CAKE).to_byte_array()));
+    assert_eq!(testnet.payee_pubkey, pubkey(PAYEE_PUBKEY));
+
+    // 9678785340 pico-bitcoin.
+    let store = decode_invoice(BLOCKSTREAM_STORE).unwrap();
+    assert_eq!(store.amount, Some(MilliSat::from_msat(967_878_534)));
+    assert_eq!(store.timestamp, 1572468703);
+    assert_eq!(store.expiry, 604800);
+    assert_eq!(store.min_final_cltv_expiry_delta, 10);
+}
+
+#[test]
+fn bolt11_route_hints() {
+    let invoice = decode_invoice(ROUTE_HINTS).unwrap();
+    assert_eq!(
+        invoice.route_hints,
+        vec![vec![
+            RouteHintHop {
+                node_id: pubkey("029e03a901b85534ff1e92c43c74431f7ce72046060fcf7a95c37e148f78c77255"),
+                short_channel_id: ShortChannelId::new(66051, 263430, 1800),
+                fee_base_msat: 1,
+                fee_proportional_millionths: 20,
+                cltv_expiry_delta: 3,
+            },
+            RouteHintHop {
+                node_id: pubkey("039e03a901b85534ff1e92c43c74431f7ce72046060fcf7a95c37e148f78c77255"),
+                short_channel_id: ShortChannelId::new(197637, 395016, 2314),
+                fee_base_msat: 2,
+                fee_proportional_millionths: 30,
+                cltv_expiry_delta: 4,
+            },
+        ]]
+    );
+
+    let store = decode_invoice(BLOCKSTREAM_STORE).unwrap();
+    assert_eq!(store.route_hints.len(), 1);
+    assert_eq!(store.route_hints[0][0].short_channel_id, ShortChannelId::new(589390, 3312, 1));
+    assert_eq!(store.route_hints[0][0].fee_base_msat, 1000);
+    assert_eq!(store.route_hints[0][0].fee_proportional_millionths, 2500);
+    assert_eq!(store.route_hints[0][0].cltv_expiry_delta, 40);
+}
+
+#[test]
+fn bolt11_features_and_case() {
+    let invoice = decode_invoice(COFFEE_BEANS).unwrap();
+    assert_eq!(invoice.amount, Some(MilliSat::from_msat(2_500_000_000)));
+    assert_eq!(invoice.description.as_deref(), Some("coffee beans"));
+    for bit in [8, 14, 99] {
+        assert!(has_feature(&invoice.features, bit), "feature bit {}", bit);
+    }
+    assert!(!has_feature(&invoice.features, 100));
+
+    // An all-uppercase invoice is the same invoice.
+    assert_eq!(decode_invoice(&COFFEE_BEANS.to_uppercase()).unwrap(), invoice);
+}
+
+#[test]
+fn bolt11_fields_of_the_wrong_length_are_skipped() {
+    let invoice = decode_invoice(WRONG_LENGTH_FIELDS).unwrap();
+    assert_eq!(invoice.payment_hash, payment_hash());
+    assert_eq!(invoice.payment_secret, Some(PaymentSecret([0x11; 32])));
+    assert_eq!(invoice.description_hash, None);
+    assert_eq!(invoice.payee_pubkey, pubkey(PAYEE_PUBKEY));
+}
+
+#[test]
+fn bolt11_explicit_payee_pubkey() {
+    let invoice = decode_invoice(EXPLICIT_PAYEE).unwrap();
+    assert_eq!(invoice.payee_pubkey, pubkey(PAYEE_PUBKEY));
+    assert_eq!(invoice.description.as_deref(), Some("payment metadata inside"));
+}
+
+#[test]
+fn bolt11_invalid_invoices() {
+    let invalid_checksum = "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrnt";
+    assert!(matches!(decode_invoice(invalid_checksum), Err(InvoiceError::Bech32(_))));
+
+    let missing_separator = "pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrny";
+    assert!(matches!(decode_invoice(missing_separator), Err(InvoiceError::Bech32(_))));
+
+    let mixed_case = "LNBC2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrny";
+    assert!(matches!(decode_invoice(mixed_case), Err(InvoiceError::Bech32(_))));
+
+    let invalid_signature = "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpusp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9qrsgqwgt7mcn5yqw3yx0w94pswkpq6j9uh6xfqqqtsk4tnarugeektd4hg5975x9am52rz4qskukxdmjemg92vvqz8nvmsye63r5ykel43pgz7zq0g2";
+    assert_eq!(decode_invoice(invalid_signature), Err(InvoiceError::InvalidSignature));
+
+    let too_short = "lnbc1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6na6hlh";
+    assert_eq!(decode_invoice(too_short), Err(InvoiceError::TooShort));
+
+    let unknown_multiplier = "lnbc2500x1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpusp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9qrsgqrrzc4cvfue4zp3hggxp47ag7xnrlr8vgcmkjxk3j5jqethnumgkpqp23z9jclu3v0a7e0aruz366e9wqdykw6dxhdzcjjhldxq0w6wgqcnu43j";
+    assert_eq!(decode_invoice(unknown_multiplier), Err(InvoiceError::InvalidAmount));
+
+    let imprecise_amount = "lnbc2500000001p1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpusp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9qrsgq0lzc236j96a95uv0m3umg28gclm5lqxtqqwk32uuk4k6673k6n5kfvx3d2h8s295fad45fdhmusm8sjudfhlf6dcsxmfvkeywmjdkxcp99202x";
+    assert_eq!(decode_invoice(imprecise_amount), Err(InvoiceError::InvalidAmount));
+}
@@ -0,0 +1,209 @@
+//! BOLT 12 offer and merkle vectors, run through the offer decoder.
+use bitcoin::constants::ChainHash;
+use bitcoin::secp256k1::schnorr::Signature as SchnorrSignature;
+use bitcoin::secp256k1::PublicKey as secp256k1PublicKey;
+use bitcoin::Network;
+use pl_00_intro::internal::errors::{OfferError, WireError};
+use pl_00_intro::internal::offers::{
+    decode_bolt12, decode_offer, merkle_root, read_invoice_request, verify_signature, INVOICE_REQUEST_HRP,
+    INVREQ_PAYER_ID_TLV, OFFER_HRP, SIGNATURE_TLV,
+};
+use pl_00_intro::internal::tlv::{find_record, read_tlv_stream};
+use pl_00_intro::internal::wire::has_feature;
+
+/// The key every spec offer is issued under.
+const ISSUER_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+const MINIMAL: &str = "lno1pgx9getnwss8vetrw3hhyuckyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg";
+const TESTNET: &str = "lno1qgsyxjtl6luzd9t3pr62xr7eemp6awnejusgf6gw45q75vcfqqqqqqq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const BITCOIN_OR_LIQUID: &str = "lno1qfqpge38tqmzyrdjj3x2qkdr5y80dlfw56ztq6yd9sme995g3gsxqqm0u2xq4dh3kdevrf4zg6hx8a60jv0gxe0ptgyfc6xkryqqqqqqqq9qc4r9wd6zqan9vd6x7unnzcss9mk8y3wkklfvevcrszlmu23kfrxh49px20665dqwmn4p72pksese";
+const METADATA: &str =
+    "lno1qsgqqqqqqqqqqqqqqqqqqqqqqqqqqzsv23jhxapqwejkxar0wfe3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs";
+const AMOUNT: &str = "lno1pqpzwyq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const CURRENCY: &str =
+    "lno1qcp4256ypqpzwyq2p32x2um5ypmx2cm5dae8x93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const EXPIRY: &str = "lno1pgx9getnwss8vetrw3hhyucwq3ay997czcss9mk8y3wkklfvevcrszlmu23kfrxh49px20665dqwmn4p72pksese";
+const ISSUER: &str = "lno1pgx9getnwss8vetrw3hhyucjy358garswvaz7tmzdak8gvfj9ehhyeeqgf85c4p3xgsxjmnyw4ehgunfv4e3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs";
+const QUANTITY: &str = "lno1pgx9getnwss8vetrw3hhyuc5qyz3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs";
+const FEATURE: &str =
+    "lno1pgx9getnwss8vetrw3hhyucvp5yqqqqqqqqqqqqqqqqqqqqkyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg";
+const BLINDED_PATH: &str = "lno1pgx9getnwss8vetrw3hhyucs5ypjgef743p5fzqq9nqxh0ah7y87rzv3ud0eleps9kl2d5348hq2k8qzqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgqpqqqqqqqqqqqqqqqqqqqqqqqqqqqzqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqqzq3zyg3zyg3zyg3vggzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvs";
+const SCIDDIR_PATH: &str = "lno1pgx9getnwss8vetrw3hhyucs3yqqqqqqqqqqqqp2qgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqqyqqqqqqqqqqqqqqqqqqqqqqqqqqqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqqgzyg3zyg3zyg3z93pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpj";
+const TWO_BLINDED_PATHS: &str = "lno1pgx9getnwss8vetrw3hhyucsl5q5yqeyv5l2cs6y3qqzesrth7mlzrlp3xg7xhulusczm04x6g6nms9trspqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqqsqqqqqqqqqqqqqqqqqqqqqqqqqqpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqsqpqg3zyg3zyg3zygz0uc7h32x9s0aecdhxlk075kn046aafpuuyw8f5j652t3vha2yqrsyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqsqzqqqqqqqqqqqqqqqqqqqqqqqqqqqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqqyzyg3zyg3zyg3zzcss9mk8y3wkklfvevcrszlmu23kfrxh49px20665dqwmn4p72pksese";
+const UNKNOWN_ODD_FIELD: &str =
+    "lno1pgx9getnwss8vetrw3hhyuckyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxfppf5x2mrvdamk7unvvs";
+
+/// An `invoice_request` the payer `0x42...` signed for an offer of 100 USD
+/// cents by `0x41...`.
+const INVOICE_REQUEST: &str = "lnr1qqyqqqqqqqqqqqqqqcp4256ypqqkgzshgysy6ct5dpjk6ct5d93kzmpq23ex2ct5d9ek293pqthvwfzadd7jejes8q9lhc4rvjxd022zv5l44g6qah82ru5rdpnpjkppqvjx204vgdzgsqpvcp4mldl3plscny0rt707gvpdh6ndydfacz43euzqhrurageg3n7kafgsek6gz3e9w52parv8gs2hlxzk95tzeswywffxlkeyhml0hh46kndmwf4m6xma3tkq2lu04qz3slje2rfthc89vss";
+
+fn issuer_id() -> secp256k1PublicKey {
+    secp256k1PublicKey::from_slice(&hex::decode(ISSUER_ID).unwrap()).unwrap()
+}
+
+fn records(hex_str: &str) -> Vec<pl_00_intro::internal::tlv::TlvRecord> {
+    read_tlv_stream(&hex::decode(hex_str).unwrap(), &[2]).unwrap()
+}
+
+#[test]
+fn bolt12_merkle_roots() {
+    let tlv1 = "010203e8";
+    let tlv2 = "02080000010000020003";
+    let tlv3 = "03310266e4598d1d3c415f572a8488830b60f7e744ed9235eb0b1ba93283b315c0351800000000000000010000000000000002";
+
+    assert_eq!(
+        hex::encode(merkle_root(&records(tlv1))),
+        "b013756c8fee86503a0b4abdab4cddeb1af5d344ca6fc2fa8b6c08938caa6f93"
+    );
+    assert_eq!(
+        hex::encode(merkle_root(&records(&format!("{}{}", tlv1, tlv2)))),
+        "c3774abbf4815aa54ccaa026bff6581f01f3be5fe814c620a252534f434bc0d1"
+    );
+    assert_eq!(
+        hex::encode(merkle_root(&records(&format!("{}{}{}", tlv1, tlv2, tlv3)))),
+        "ab2e79b1283b0b31e0b035258de23782df6b89a38cfa7237bde69aed1a658c5d"
+    );
+}
+
+#[test]
+fn bolt12_invoice_request_signature() {
+    let records = read_invoice_request(&decode_bolt12(INVOICE_REQUEST, INVOICE_REQUEST_HRP).unwrap()).unwrap();
+
+    // The signature record is left out of the tree.
+    assert_eq!(hex::encode(merkle_root(&records)), "608407c18ad9a94d9ea2bcdbe170b6c20c462a7833a197621c916f78cf18e624");
+
+    let signature = SchnorrSignature::from_slice(find_record(&records, SIGNATURE_TLV).unwrap()).unwrap();
+    let payer_id = secp256k1PublicKey::from_slice(find_record(&records, INVREQ_PAYER_ID_TLV).unwrap()).unwrap();
+    assert_eq!(verify_signature("invoice_request", &records, &signature, &payer_id), Ok(()));
+
+    // The tag names the message, so the same signature doesn't sign an invoice.
+    assert_eq!(verify_signature("invoice", &records, &signature, &payer_id), Err(OfferError::InvalidSignature));
+    assert_eq!(
+        verify_signature("invoice_request", &records, &signature, &issuer_id()),
+        Err(OfferError::InvalidSignature)
+    );
+}
+
+#[test]
+fn bolt12_minimal_offers() {
+    let offer = decode_offer(MINIMAL).unwrap();
+    assert_eq!(offer.description.as_deref(), Some("Test vectors"));
+    assert_eq!(offer.issuer_id, Some(issuer_id()));
+    assert_eq!(offer.amount, None);
+    assert!(offer.chains.is_empty() && offer.paths.is_empty());
+    assert!(offer.supports_chain(Network::Bitcoin));
+    assert!(!offer.supports_chain(Network::Regtest));
+
+    let offer = decode_offer(TESTNET).unwrap();
+    assert_eq!(offer.chains, vec![ChainHash::TESTNET]);
+    assert!(offer.supports_chain(Network::Testnet));
+    assert!(!offer.supports_chain(Network::Bitcoin));
+
+    let offer = decode_offer(BITCOIN_OR_LIQUID).unwrap();
+    assert_eq!(offer.chains.len(), 2);
+    assert!(offer.supports_chain(Network::Bitcoin));
+
+    assert_eq!(decode_offer(METADATA).unwrap().metadata, Some(vec![0; 16]));
+    assert_eq!(decode_offer(EXPIRY).unwrap().absolute_expiry, Some(2_051_184_600));
+    assert_eq!(decode_offer(ISSUER).unwrap().issuer.as_deref(), Some("https://bolt12.org BOLT12 industries"));
+    assert_eq!(decode_offer(QUANTITY).unwrap().quantity_max, Some(5));
+    assert!(has_feature(&decode_offer(FEATURE).unwrap().features, 99));
+
+    // Nobody knows what type 33 is, but it's odd, so it is kept and ignored.
+    let offer = decode_offer(UNKNOWN_ODD_FIELD).unwrap();
+    assert!(find_record(&offer.records, 33).is_some());
+}
+
+#[test]
+fn bolt12_offer_amounts() {
+    let offer = decode_offer(AMOUNT).unwrap();
+    assert_eq!((offer.amount, offer.currency), (Some(10_000), None));
+
+    let offer = decode_offer(CURRENCY).unwrap();
+    assert_eq!((offer.amount, offer.currency.as_deref()), (Some(10_000), Some("USD")));
+}
+
+#[test]
+fn bolt12_offer_paths() {
+    let offer = decode_offer(BLINDED_PATH).unwrap();
+    assert_eq!(offer.paths.len(), 1);
+    assert_eq!(offer.paths[0].blinded_hops.len(), 2);
+    assert_eq!(offer.paths[0].blinded_hops[1].encrypted_recipient_data, vec![0x11; 8]);
+
+    let offer = decode_offer(TWO_BLINDED_PATHS).unwrap();
+    assert_eq!(offer.paths.len(), 2);
+    assert_ne!(offer.paths[0].introduction_node_id, offer.paths[1].introduction_node_id);
+
+    // A path can start at a short channel id, but finding the node behind it
+    // needs the graph.
+    assert_eq!(decode_offer(SCIDDIR_PATH), Err(OfferError::Wire(WireError::InvalidValue("first_node_id"))));
+}
+
+#[test]
+fn bolt12_split_offers() {
+    let split = format!("{}+{}+ \n {}", &MINIMAL[..20], &MINIMAL[20..50], &MINIMAL[50..]);
+    assert_eq!(decode_offer(&split), decode_offer(MINIMAL));
+    assert_eq!(decode_offer(&MINIMAL.to_uppercase()), decode_offer(MINIMAL));
+
+    assert_eq!(decode_offer(&format!("{}+", MINIMAL)), Err(OfferError::InvalidContinuation));
+    assert_eq!(decode_offer(&format!("+{}", MINIMAL)), Err(OfferError::InvalidContinuation));
+    assert_eq!(decode_offer(&format!("{}++{}", &MINIMAL[..20], &MINIMAL[20..])), Err(OfferError::InvalidContinuation));
+    assert_eq!(
+        decode_offer(&format!("{}+ {} {}", &MINIMAL[..20], &MINIMAL[20..50], &MINIMAL[50..])),
+        Err(OfferError::InvalidContinuation)
+    );
+    assert!(matches!(decode_offer(&format!("{} {}", &MINIMAL[..20], &MINIMAL[20..])), Err(OfferError::Bech32(_))));
+    assert_eq!(decode_offer(INVOICE_REQUEST), Err(OfferError::WrongHrp(OFFER_HRP)));
+}
+
+#[test]
+fn bolt12_invalid_offers() {
+    let cases = [
+        // fields out of order
+        (
+            "lno1zcssyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszpgz5znzfgdzs",
+            OfferError::Wire(WireError::TlvTypesNotAscending { previous: 22, current: 10 }),
+        ),
+        // unknown even type 72
+        (
+            "lno1pgz5znzfgdz3vggzqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpysgr0u2xq4dh3kdevrf4zg6hx8a60jv0gxe0ptgyfc6xkryqqqqqqqq",
+            OfferError::Wire(WireError::UnknownEvenTlvType(72)),
+        ),
+        ("lno1", OfferError::MissingField("offer_issuer_id")),
+        ("lno1pg", OfferError::Wire(WireError::ShortRead)),
+        ("lno1pt7s", OfferError::Wire(WireError::ShortRead)),
+        ("lno1pgpq", OfferError::Wire(WireError::ShortRead)),
+        ("lno1pgpyz", OfferError::Wire(WireError::ShortRead)),
+        // offer_chains of 33 bytes
+        (
+            "lno1qgqszzs9g9xyjs69zcssyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqsz",
+            OfferError::InvalidField("offer_chains"),
+        ),
+        // description that isn't UTF-8
+        (
+            "lno1pgpgqsgkyypqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqs",
+            OfferError::InvalidField("offer_description"),
+        ),
+        // issuer that isn't UTF-8
+        (
+            "lno1pgz5znzfgdz3yq5qgytzzqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqg",
+            OfferError::InvalidField("offer_issuer"),
+        ),
+        // offer_issuer_id that isn't a point
+        (
+            "lno1pgz5znzfgdz3vggzqvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvpsxqcrqvps",
+            OfferError::InvalidField("offer_issuer_id"),
+        ),
+        // invreq_chain, which only an invoice_request may carry
+        (
+            "lno1pgz5znzfgdz3vggzqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgpqyqszqgp9qgr0u2xq4dh3kdevrf4zg6hx8a60jv0gxe0ptgyfc6xkryqqqqqqqq",
+            OfferError::Wire(WireError::UnknownEvenTlvType(80)),
+        ),
+        // an amount with no description
+        ("lno1pqpq86qkyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg", OfferError::MissingField("offer_description")),
+    ];
+
+    for (offer, error) in cases {
+        assert_eq!(decode_offer(offer), Err(error), "{}", offer);
+    }
+}
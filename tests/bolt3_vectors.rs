@@ -0,0 +1,298 @@
+//! BOLT 3 appendix test vectors, run through the exercise functions.
+//!
+//! The commitment vectors are the `option_static_remotekey` ones, signed with
+//! the appendix funding keys and compared byte for byte.
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hash_types::Txid;
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use bitcoin::{Amount, OutPoint, PublicKey};
+use pl_00_intro::exercises::exercises::{
+    build_funding_witness, derive_commitment_keys, generate_per_commitment_secret, generate_revocation_pubkey,
+    sign_commitment_transaction, two_of_two_multisig_witness_script,
+};
+use pl_00_intro::internal::channel::{build_commitment_full, ChannelParams};
+use pl_00_intro::internal::fee_utils::HtlcFeeMode;
+use pl_00_intro::internal::htlc_utils::Htlc;
+use pl_00_intro::internal::payments::PaymentPreimage;
+use pl_00_intro::internal::key_utils::{derive_private_key, sort_funding_keys};
+use pl_00_intro::internal::keys::{ChannelKeys, ChannelPublicKeys, DelayedPaymentPubkey, HtlcPubkey, RevocationPubkey};
+use pl_00_intro::internal::tx_utils::tx_from_hex;
+use pl_00_intro::internal::units::MilliSat;
+
+const LOCAL_FUNDING_PRIVKEY: &str = "30ff4956bbdd3222d44cc5e8a1261dab1e07957bdac5ae88fe3261ef321f3749";
+const REMOTE_FUNDING_PRIVKEY: &str = "1552dfba4f6cf29a62a0af13c8d6981d36d0ef8d61ba10fb0fe90da7634d7e13";
+const FUNDING_AMOUNT: Amount = Amount::from_sat(10_000_000);
+
+fn secret_key(hex_str: &str) -> SecretKey {
+    SecretKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+fn pubkey(hex_str: &str) -> PublicKey {
+    PublicKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+fn secp_pubkey(hex_str: &str) -> secp256k1PublicKey {
+    pubkey(hex_str).inner
+}
+
+fn funding_pubkeys() -> (PublicKey, PublicKey) {
+    let secp = Secp256k1::new();
+    (
+        PublicKey::new(secret_key(LOCAL_FUNDING_PRIVKEY).public_key(&secp)),
+        PublicKey::new(secret_key(REMOTE_FUNDING_PRIVKEY).public_key(&secp)),
+    )
+}
+
+/// One side's keys in appendix C. The commitment keys are given directly, so
+/// only the funding key and payment basepoint are used.
+fn channel_pubkeys(funding_pubkey: PublicKey, payment_basepoint: &str) -> ChannelPublicKeys {
+    let payment_basepoint = secp_pubkey(payment_basepoint);
+    ChannelPublicKeys {
+        funding_pubkey: funding_pubkey.inner,
+        payment_basepoint,
+        delayed_payment_basepoint: payment_basepoint,
+        htlc_basepoint: payment_basepoint,
+        revocation_basepoint: payment_basepoint,
+    }
+}
+
+/// The five HTLCs of appendix C, with preimages of 32 repeated bytes.
+fn appendix_c_htlcs() -> Vec<Htlc> {
+    let htlc = |offered: bool, amount_msat: u64, cltv_expiry: u32, preimage_byte: u8| Htlc {
+        offered,
+        amount_msat: MilliSat::from_msat(amount_msat),
+        payment_hash: PaymentPreimage([preimage_byte; 32]).hash160(),
+        cltv_expiry,
+    };
+    vec![
+        htlc(false, 1_000_000, 500, 0x00),
+        htlc(false, 2_000_000, 501, 0x01),
+        htlc(true, 2_000_000, 502, 0x02),
+        htlc(true, 3_000_000, 503, 0x03),
+        htlc(false, 4_000_000, 504, 0x04),
+    ]
+}
+
+/// Build commitment number 42 of the appendix C channel, sign it with both
+/// funding keys and return its serialization.
+fn signed_commitment_hex(
+    local_msat: u64,
+    remote_msat: u64,
+    htlcs: &[Htlc],
+    feerate_per_kw: u64,
+    htlc_fee_mode: HtlcFeeMode,
+) -> String {
+    let (local_funding_pubkey, remote_funding_pubkey) = funding_pubkeys();
+
+    let funding_outpoint = OutPoint::new(
+        "8984484a580b825b9972d7adb15050b3ab624ccd731946b3eeddb92f4e7ef6be"
+            .parse::<Txid>()
+            .unwrap(),
+        0,
+    );
+    let keys = ChannelKeys {
+        revocation_pubkey: RevocationPubkey(pubkey("0212a140cd0c6539d07cd08dfe09984dec3251ea808b892efeac3ede9402bf2b19")),
+        local_htlc_pubkey: HtlcPubkey(pubkey("030d417a46946384f88d5f3337267c5e579765875dc4daca813e21734b140639e7")),
+        remote_htlc_pubkey: HtlcPubkey(pubkey("0394854aa6eab5b2a8122cc726e9dded053a2184d88256816826d6231c068d4a5b")),
+        to_local_delayed_pubkey: DelayedPaymentPubkey(pubkey("03fd5960528dc152014952efdb702a88f71e3c1653b2314431701ec77e57fde83c")),
+        // with option_static_remotekey to_remote pays the remote payment basepoint
+        remote_pubkey: pubkey("032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991"),
+    };
+    let params = ChannelParams {
+        funding_outpoint,
+        to_self_delay: 144,
+        dust_limit: Amount::from_sat(546),
+        feerate_per_kw,
+        htlc_fee_mode,
+        is_funder: true,
+        local_keys: channel_pubkeys(
+            local_funding_pubkey,
+            "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa",
+        ),
+        remote_keys: channel_pubkeys(
+            remote_funding_pubkey,
+            "032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991",
+        ),
+    };
+
+    let (tx, _) = build_commitment_full(
+        &keys,
+        &params,
+        MilliSat::from_msat(local_msat),
+        MilliSat::from_msat(remote_msat),
+        htlcs,
+        42,
+    )
+    .unwrap();
+
+    let (first, second) = sort_funding_keys(local_funding_pubkey, remote_funding_pubkey);
+    let funding_script = two_of_two_multisig_witness_script(&first, &second);
+
+    let local_signature =
+        sign_commitment_transaction(&tx, &funding_script, FUNDING_AMOUNT, &secret_key(LOCAL_FUNDING_PRIVKEY));
+    let remote_signature =
+        sign_commitment_transaction(&tx, &funding_script, FUNDING_AMOUNT, &secret_key(REMOTE_FUNDING_PRIVKEY));
+
+    let mut signed_tx = tx;
+    signed_tx.input[0].witness = build_funding_witness(
+        &funding_script,
+        &local_funding_pubkey,
+        &local_signature,
+        &remote_funding_pubkey,
+        &remote_signature,
+    );
+
+    serialize_hex(&signed_tx)
+}
+
+#[test]
+fn appendix_b_funding_output() {
+    let funding_tx = tx_from_hex(FUNDING_TX_HEX).unwrap();
+    assert_eq!(
+        funding_tx.compute_txid().to_string(),
+        "8984484a580b825b9972d7adb15050b3ab624ccd731946b3eeddb92f4e7ef6be"
+    );
+
+    let (local_funding_pubkey, remote_funding_pubkey) = funding_pubkeys();
+    let (first, second) = sort_funding_keys(local_funding_pubkey, remote_funding_pubkey);
+    let funding_script = two_of_two_multisig_witness_script(&first, &second);
+
+    assert_eq!(
+        funding_script.to_hex_string(),
+        "5221023da092f6980e58d2c037173180e9a465476026ee50f96695963e8efe436f54eb21030e9f7b623d2ccc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c39fa711c152ae"
+    );
+    assert_eq!(funding_tx.output[0].script_pubkey, funding_script.to_p2wsh());
+    assert_eq!(funding_tx.output[0].value, FUNDING_AMOUNT);
+}
+
+#[test]
+fn appendix_c_simple_commitment() {
+    assert_eq!(
+        signed_commitment_hex(7_000_000_000, 3_000_000_000, &[], 15_000, HtlcFeeMode::Legacy),
+        SIMPLE_COMMITMENT_HEX
+    );
+}
+
+#[test]
+fn appendix_c_five_htlcs_minimum_feerate() {
+    assert_eq!(
+        signed_commitment_hex(6_988_000_000, 3_000_000_000, &appendix_c_htlcs(), 0, HtlcFeeMode::Legacy),
+        FIVE_HTLCS_MINIMUM_FEERATE_HEX
+    );
+}
+
+#[test]
+fn appendix_c_seven_outputs_maximum_feerate() {
+    assert_eq!(
+        signed_commitment_hex(6_988_000_000, 3_000_000_000, &appendix_c_htlcs(), 647, HtlcFeeMode::Legacy),
+        SEVEN_OUTPUTS_MAXIMUM_FEERATE_HEX
+    );
+}
+
+#[test]
+fn appendix_f_anchors_simple_commitment() {
+    assert_eq!(
+        signed_commitment_hex(7_000_000_000, 3_000_000_000, &[], 15_000, HtlcFeeMode::ZeroFeeAnchors),
+        ANCHORS_SIMPLE_COMMITMENT_HEX
+    );
+}
+
+#[test]
+fn appendix_d_per_commitment_secrets() {
+    let vectors = [
+        ([0x00; 32], 281474976710655, "02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148"),
+        ([0xff; 32], 281474976710655, "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc"),
+        ([0xff; 32], 0xaaaaaaaaaaa, "56f4008fb007ca9acf0e15b054d5c9fd12ee06cea347914ddbaed70d1c13a528"),
+        ([0xff; 32], 0x555555555555, "9015daaeb06dba4ccc05b91b2f73bd54405f2be9f217fbacd3c5ac2e62327d31"),
+        ([0x01; 32], 1, "915c75942a26bb3a433a8ce2cb0427c29ec6c1775cfc78328b57f6ba7bfeaa9c"),
+    ];
+
+    for (seed, index, expected) in vectors {
+        assert_eq!(hex::encode(generate_per_commitment_secret(&seed, index)), expected);
+    }
+}
+
+#[test]
+fn appendix_e_key_derivation() {
+    let base_secret = secret_key("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+    let base_point = secp_pubkey("036d6caac248af96f6afa7f904f550253a0f3ef3f5aa2fe6838a95b216691468e2");
+    let per_commitment_point = secp_pubkey("025f7117a78150fe2ef97db7cfc83bd57b2e2c0d0dd25eaf467a4a1c2a45ce1486");
+
+    let (localpubkey, _, _) = derive_commitment_keys(base_point, base_point, base_point, per_commitment_point);
+    assert_eq!(
+        localpubkey.to_string(),
+        "0235f2dbfaa89b57ec7b055afe29849ef7ddfeb1cefdb9ebdc43f5494984db29e5"
+    );
+
+    assert_eq!(
+        hex::encode(derive_private_key(base_secret, per_commitment_point).secret_bytes()),
+        "cbced912d3b21bf196a766651e436aff192362621ce317704ea2f75d87e7be0f"
+    );
+
+    assert_eq!(
+        generate_revocation_pubkey(base_point, per_commitment_point).to_string(),
+        "02916e326636d19c33f13e8c0c3a03dd157f332f3e99c317c141dd865eb01f8ff0"
+    );
+}
+
+const FUNDING_TX_HEX: &str = concat!(
+    "0200000001adbb20ea41a8423ea937e76e8151636bf6093b70eaff942930d20576600521fd000000006b483045022100",
+    "90587b6201e166ad6af0227d3036a9454223d49a1f11839c1a362184340ef0240220577f7cd5cca78719405cbf1de741",
+    "4ac027f0239ef6e214c90fcaab0454d84b3b012103535b32d5eb0a6ed0982a0479bbadc9868d9836f6ba94dd5a63be16",
+    "d875069184ffffffff028096980000000000220020c015c4a6be010e21657068fc2e6a9d02b27ebe4d490a25846f7237",
+    "f104d1a3cd20256d29010000001600143ca33c2e4446f4a305f23c80df8ad1afdcf652f900000000",
+);
+
+const SIMPLE_COMMITMENT_HEX: &str = concat!(
+    "02000000000101bef67e4e2fb9ddeeb3461973cd4c62abb35050b1add772995b820b584a488489000000000038b02b80",
+    "02c0c62d0000000000160014cc1b07838e387deacd0e5232e1e8b49f4c29e48454a56a00000000002200204adb4e2f00",
+    "643db396dd120d4e7dc17625f5f2c11a40d857accc862d6b7dd80e04004730440220616210b2cc4d3afb601013c373bb",
+    "d8aac54febd9f15400379a8cb65ce7deca60022034236c010991beb7ff770510561ae8dc885b8d38d1947248c38f2ae0",
+    "5564714201483045022100c3127b33dcc741dd6b05b1e63cbd1a9a7d816f37af9b6756fa2376b056f032370220408b96",
+    "279808fe57eb7e463710804cdf4f108388bc5cf722d8c848d2c7f9f3b001475221023da092f6980e58d2c037173180e9",
+    "a465476026ee50f96695963e8efe436f54eb21030e9f7b623d2ccc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c3",
+    "9fa711c152ae3e195220",
+);
+
+const FIVE_HTLCS_MINIMUM_FEERATE_HEX: &str = concat!(
+    "02000000000101bef67e4e2fb9ddeeb3461973cd4c62abb35050b1add772995b820b584a488489000000000038b02b80",
+    "07e80300000000000022002052bfef0479d7b293c27e0f1eb294bea154c63a3294ef092c19af51409bce0e2ad0070000",
+    "00000000220020403d394747cae42e98ff01734ad5c08f82ba123d3d9a620abda88989651e2ab5d00700000000000022",
+    "0020748eba944fedc8827f6b06bc44678f93c0f9e6078b35c6331ed31e75f8ce0c2db80b000000000000220020c20b5d",
+    "1f8584fd90443e7b7b720136174fa4b9333c261d04dbbd012635c0f419a00f0000000000002200208c48d15160397c97",
+    "31df9bc3b236656efb6665fbfe92b4a6878e88a499f741c4c0c62d0000000000160014cc1b07838e387deacd0e5232e1",
+    "e8b49f4c29e484e0a06a00000000002200204adb4e2f00643db396dd120d4e7dc17625f5f2c11a40d857accc862d6b7d",
+    "d80e040047304402206fc2d1f10ea59951eefac0b4b7c396a3c3d87b71ff0b019796ef4535beaf36f902201765b0181e",
+    "514d04f4c8ad75659d7037be26cdb3f8bb6f78fe61decef484c3ea01473044022009b048187705a8cbc9ad73adbe5af1",
+    "48c3d012e1f067961486c822c7af08158c022006d66f3704cfab3eb2dc49dae24e4aa22a6910fc9b424007583204e362",
+    "1af2e501475221023da092f6980e58d2c037173180e9a465476026ee50f96695963e8efe436f54eb21030e9f7b623d2c",
+    "cc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c39fa711c152ae3e195220",
+);
+
+const SEVEN_OUTPUTS_MAXIMUM_FEERATE_HEX: &str = concat!(
+    "02000000000101bef67e4e2fb9ddeeb3461973cd4c62abb35050b1add772995b820b584a488489000000000038b02b80",
+    "07e80300000000000022002052bfef0479d7b293c27e0f1eb294bea154c63a3294ef092c19af51409bce0e2ad0070000",
+    "00000000220020403d394747cae42e98ff01734ad5c08f82ba123d3d9a620abda88989651e2ab5d00700000000000022",
+    "0020748eba944fedc8827f6b06bc44678f93c0f9e6078b35c6331ed31e75f8ce0c2db80b000000000000220020c20b5d",
+    "1f8584fd90443e7b7b720136174fa4b9333c261d04dbbd012635c0f419a00f0000000000002200208c48d15160397c97",
+    "31df9bc3b236656efb6665fbfe92b4a6878e88a499f741c4c0c62d0000000000160014cc1b07838e387deacd0e5232e1",
+    "e8b49f4c29e484e09c6a00000000002200204adb4e2f00643db396dd120d4e7dc17625f5f2c11a40d857accc862d6b7d",
+    "d80e04004830450221009ec15c687898bb4da8b3a833e5ab8bfc51ec6e9202aaa8e66611edfd4a85ed1102203d7183e4",
+    "5078b9735c93450bc3415d3e5a8c576141a711ec6ddcb4a893926bb701483045022100a135f9e8a5ed25f7277446c679",
+    "56b00ce6f610ead2bdec2c2f686155b7814772022059f1f6e1a8b336a68efcc1af3fe4d422d4827332b5b067501b099c",
+    "47b7b5b5ee01475221023da092f6980e58d2c037173180e9a465476026ee50f96695963e8efe436f54eb21030e9f7b62",
+    "3d2ccc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c39fa711c152ae3e195220",
+);
+
+const ANCHORS_SIMPLE_COMMITMENT_HEX: &str = concat!(
+    "02000000000101bef67e4e2fb9ddeeb3461973cd4c62abb35050b1add772995b820b584a488489000000000038b02b80",
+    "044a010000000000002200202b1b5854183c12d3316565972c4668929d314d81c5dcdbb21cb45fe8a9a8114f4a010000",
+    "00000000220020e9e86e4823faa62e222ebc858a226636856158f07e69898da3b0d1af0ddb3994c0c62d000000000022",
+    "0020f3394e1e619b0eca1f91be2fb5ab4dfc59ba5b84ebe014ad1d43a564d012994a508b6a00000000002200204adb4e",
+    "2f00643db396dd120d4e7dc17625f5f2c11a40d857accc862d6b7dd80e04004830450221008266ac6db5ea71aac3c95d",
+    "97b0e172ff596844851a3216eb88382a8dddfd33d2022050e240974cfd5d708708b4365574517c18e7ae535ef732a348",
+    "4d43d0d82be9f701483045022100f89034eba16b2be0e5581f750a0a6309192b75cce0f202f0ee2b4ec0cc3948500220",
+    "76c65dc507fe42276152b7a3d90e961e678adbe966e916ecfe85e64d430e75f301475221023da092f6980e58d2c03717",
+    "3180e9a465476026ee50f96695963e8efe436f54eb21030e9f7b623d2ccc7c9bd44d66d5ce21ce504c0acf6385a132ce",
+    "c6d3c39fa711c152ae3e195220",
+);
@@ -0,0 +1,316 @@
+//! BOLT 4 onion construction vectors, run through the onion exercises.
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use pl_00_intro::exercises::onion::{
+    build_failure_packet, build_final_hop_payload, build_hop_payload, build_payment_onion, compute_hop_keys,
+    construct_onion_packet, decrypt_failure_onion, encrypt_failure_packet, peel_onion, PeeledOnion,
+};
+use pl_00_intro::internal::errors::OnionError;
+use pl_00_intro::internal::onion_utils::{
+    shared_secret, HopPayload, OnionPacket, ONION_PACKET_LEN, TEMPORARY_CHANNEL_FAILURE, TEMPORARY_NODE_FAILURE,
+};
+use pl_00_intro::internal::payments::{PaymentHash, PaymentSecret};
+use pl_00_intro::internal::routing::{Route, RouteHop};
+use pl_00_intro::internal::units::MilliSat;
+use pl_00_intro::internal::wire::ShortChannelId;
+
+const SESSION_KEY: &str = "4141414141414141414141414141414141414141414141414141414141414141";
+const ASSOCIATED_DATA: [u8; 32] = [0x42; 32];
+
+/// The hops' node keys are 0x41.., 0x42.., and so on.
+const HOP_PRIVKEY_BYTES: [u8; 5] = [0x41, 0x42, 0x43, 0x44, 0x45];
+
+const HOP_PUBKEYS: [&str; 5] = [
+    "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619",
+    "0324653eac434488002cc06bbfb7f10fe18991e35f9fe4302dbea6d2353dc0ab1c",
+    "027f31ebc5462c1fdce1b737ecff52d37d75dea43ce11c74d25aa297165faa2007",
+    "032c0b7cf95324a07d05398b240174dc0c2be444d96b159aa6c7f7b1e668680991",
+    "02edabbd16b41c8371b92ef2f04c1185b4f03b6dcd52ba9b78d9d7c89c8f221145",
+];
+
+/// Each hop's TLV payload without its length prefix. The second and fifth
+/// carry custom records.
+const HOP_PAYLOADS: [&str; 5] = [
+    "02023a98040205dc06080000000000000001",
+    "020236b00402057806080000000000000002fd02013c0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f0102030405060708090a0b0c0d0e0f",
+    "020230d4040204e206080000000000000003",
+    "02022710040203e806080000000000000004",
+    "02022710040203e8082224a33562c54507a9334e79f0dc4f17d407e6d7c61f0e2f3d0d38599502f617042710fd012de02a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a",
+];
+
+/// The final hop's `payment_secret`.
+const PAYMENT_SECRET: &str = "24a33562c54507a9334e79f0dc4f17d407e6d7c61f0e2f3d0d38599502f61704";
+
+/// Shared secret, blinding factor, ephemeral key, rho and mu for each hop.
+const HOP_KEYS: [[&str; 5]; 5] = [
+    [
+        "53eb63ea8a3fec3b3cd433b85cd62a4b145e1dda09391b348c4e1cd36a03ea66",
+        "2ec2e5da605776054187180343287683aa6a51b4b1c04d6dd49c45d8cffb3c36",
+        "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619",
+        "ce496ec94def95aadd4bec15cdb41a740c9f2b62347c4917325fcc6fb0453986",
+        "b57061dc6d0a2b9f261ac410c8b26d64ac5506cbba30267a649c28c179400eba",
+    ],
+    [
+        "a6519e98832a0b179f62123b3567c106db99ee37bef036e783263602f3488fae",
+        "bf66c28bc22e598cfd574a1931a2bafbca09163df2261e6d0056b2610dab938f",
+        "028f9438bfbf7feac2e108d677e3a82da596be706cc1cf342b75c7b7e22bf4e6e2",
+        "450ffcabc6449094918ebe13d4f03e433d20a3d28a768203337bc40b6e4b2c59",
+        "05ed2b4a3fb023c2ff5dd6ed4b9b6ea7383f5cfe9d59c11d121ec2c81ca2eea9",
+    ],
+    [
+        "3a6b412548762f0dbccce5c7ae7bb8147d1caf9b5471c34120b30bc9c04891cc",
+        "a1f2dadd184eb1627049673f18c6325814384facdee5bfd935d9cb031a1698a5",
+        "03bfd8225241ea71cd0843db7709f4c222f62ff2d4516fd38b39914ab6b83e0da0",
+        "11bf5c4f960239cb37833936aa3d02cea82c0f39fd35f566109c41f9eac8deea",
+        "caafe2820fa00eb2eeb78695ae452eba38f5a53ed6d53518c5c6edf76f3f5b78",
+    ],
+    [
+        "21e13c2d7cfe7e18836df50872466117a295783ab8aab0e7ecc8c725503ad02d",
+        "7cfe0b699f35525029ae0fa437c69d0f20f7ed4e3916133f9cacbb13c82ff262",
+        "031dde6926381289671300239ea8e57ffaf9bebd05b9a5b95beaf07af05cd43595",
+        "cbe784ab745c13ff5cffc2fbe3e84424aa0fd669b8ead4ee562901a4a4e89e9e",
+        "5052aa1b3d9f0655a0932e50d42f0c9ba0705142c25d225515c45f47c0036ee9",
+    ],
+    [
+        "b5756b9b542727dbafc6765a49488b023a725d631af688fc031217e90770c328",
+        "c96e00dddaf57e7edcd4fb5954be5b65b09f17cb6d20651b4e90315be5779205",
+        "03a214ebd875aab6ddfd77f22c5e7311d7f77f17a169e599f157bbcdae8bf071f4",
+        "034e18b8cc718e8af6339106e706c52d8df89e2b1f7e9142d996acf88df8799b",
+        "8e45e5c61c2b24cb6382444db6698727afb063adecd72aada233d4bf273d975a",
+    ],
+];
+
+const ONION: &str = "0002eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619f7f3416a5aa36dc7eeb3ec6d421e9615471ab870a33ac07fa5d5a51df0a8823aabe3fea3f90d387529d4f72837f9e687230371ccd8d263072206dbed0234f6505e21e282abd8c0e4f5b9ff8042800bbab065036eadd0149b37f27dde664725a49866e052e809d2b0198ab9610faa656bbf4ec516763a59f8f42c171b179166ba38958d4f51b39b3e98706e2d14a2dafd6a5df808093abfca5aeaaca16eded5db7d21fb0294dd1a163edf0fb445d5c8d7d688d6dd9c541762bf5a5123bf9939d957fe648416e88f1b0928bfa034982b22548e1a4d922690eecf546275afb233acf4323974680779f1a964cfe687456035cc0fba8a5428430b390f0057b6d1fe9a8875bfa89693eeb838ce59f09d207a503ee6f6299c92d6361bc335fcbf9b5cd44747aadce2ce6069cfdc3d671daef9f8ae590cf93d957c9e873e9a1bc62d9640dc8fc39c14902d49a1c80239b6c5b7fd91d05878cbf5ffc7db2569f47c43d6c0d27c438abff276e87364deb8858a37e5a62c446af95d8b786eaf0b5fcf78d98b41496794f8dcaac4eef34b2acfb94c7e8c32a9e9866a8fa0b6f2a06f00a1ccde569f97eec05c803ba7500acc96691d8898d73d8e6a47b8f43c3d5de74458d20eda61474c426359677001fbd75a74d7d5db6cb4feb83122f133206203e4e2d293f838bf8c8b3a29acb321315100b87e80e0edb272ee80fda944e3fb6084ed4d7f7c7d21c69d9da43d31a90b70693f9b0cc3eac74c11ab8ff655905688916cfa4ef0bd04135f2e50b7c689a21d04e8e981e74c6058188b9b1f9dfc3eec6838e9ffbcf22ce738d8a177c19318dffef090cee67e12de1a3e2a39f61247547ba5257489cbc11d7d91ed34617fcc42f7a9da2e3cf31a94a210a1018143173913c38f60e62b24bf0d7518f38b5bab3e6a1f8aeb35e31d6442c8abb5178efc892d2e787d79c6ad9e2fc271792983fa9955ac4d1d84a36c024071bc6e431b625519d556af38185601f70e29035ea6a09c8b676c9d88cf7e05e0f17098b584c4168735940263f940033a220f40be4c85344128b14beb9e75696db37014107801a59b13e89cd9d2258c169d523be6d31552c44c82ff4bb18ec9f099f3bf0e5b1bb2ba9a87d7e26f98d294927b600b5529c47e04d98956677cbcee8fa2b60f49776d8b8c367465b7c626da53700684fb6c918ead0eab8360e4f60edd25b4f43816a75ecf70f909301825b512469f8389d79402311d8aecb7b3ef8599e79485a4388d87744d899f7c47ee644361e17040a7958c8911be6f463ab6a9b2afacd688ec55ef517b38f1339efc54487232798bb25522ff4572ff68567fe830f92f7b8113efce3e98c3fffbaedce4fd8b50e41da97c0c08e423a72689cc68e68f752a5e3a9003e64e35c957ca2e1c48bb6f64b05f56b70b575ad2f278d57850a7ad568c24a4d32a3d74b29f03dc125488bc7c637da582357f40b0a52d16b3b40bb2c2315d03360bc24209e20972c200566bcf3bbe5c5b0aedd83132a8a4d5b4242ba370b6d67d9b67eb01052d132c7866b9cb502e44796d9d356e4e3cb47cc527322cd24976fe7c9257a2864151a38e568ef7a79f10d6ef27cc04ce382347a2488b1f404fdbf407fe1ca1c9d0d5649e34800e25e18951c98cae9f43555eef65fee1ea8f15828807366c3b612cd5753bf9fb8fced08855f742cddd6f765f74254f03186683d646e6f09ac2805586c7cf11998357cafc5df3f285329366f475130c928b2dceba4aa383758e7a9d20705c4bb9db619e2992f608a1ba65db254bb389468741d0502e2588aeb54390ac600c19af5c8e61383fc1bebe0029e4474051e4ef908828db9cca13277ef65db3fd47ccc2179126aaefb627719f421e20";
+
+/// `temporary_node_failure` from the final hop, before anyone encrypts it.
+const FAILURE_PACKET: &str = "4c2fc8bc08510334b6833ad9c3e79cd1b52ae59dfe5c2a4b23ead50f09f7ee0b0002200200fe0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
+/// The failure after each hop on the way back has encrypted it, from the
+/// final hop to the first.
+const ENCRYPTED_FAILURES: [&str; 5] = [
+    "a5e6bd0c74cb347f10cce367f949098f2457d14c046fd8a22cb96efb30b0fdcda8cb9168b50f2fd45edd73c1b0c8b33002df376801ff58aaa94000bf8a86f92620f343baef38a580102395ae3abf9128d1047a0736ff9b83d456740ebbb4aeb3aa9737f18fb4afb4aa074fb26c4d702f42968888550a3bded8c05247e045b866baef0499f079fdaeef6538f31d44deafffdfd3afa2fb4ca9082b8f1c465371a9894dd8c243fb4847e004f5256b3e90e2edde4c9fb3082ddfe4d1e734cacd96ef0706bf63c9984e22dc98851bcccd1c3494351feb458c9c6af41c0044bea3c47552b1d992ae542b17a2d0bba1a096c78d169034ecb55b6e3a7263c26017f033031228833c1daefc0dedb8cf7c3e37c9c37ebfe42f3225c326e8bcfd338804c145b16e34e4",
+    "c49a1ce81680f78f5f2000cda36268de34a3f0a0662f55b4e837c83a8773c22aa081bab1616a0011585323930fa5b9fae0c85770a2279ff59ec427ad1bbff9001c0cd1497004bd2a0f68b50704cf6d6a4bf3c8b6a0833399a24b3456961ba00736785112594f65b6b2d44d9f5ea4e49b5e1ec2af978cbe31c67114440ac51a62081df0ed46d4a3df295da0b0fe25c0115019f03f15ec86fabb4c852f83449e812f141a9395b3f70b766ebbd4ec2fae2b6955bd8f32684c15abfe8fd3a6261e52650e8807a92158d9f1463261a925e4bfba44bd20b166d532f0017185c3a6ac7957adefe45559e3072c8dc35abeba835a8cb01a71a15c736911126f27d46a36168ca5ef7dccd4e2886212602b181463e0dd30185c96348f9743a02aca8ec27c0b90dca270",
+    "a5d3e8634cfe78b2307d87c6d90be6fe7855b4f2cc9b1dfb19e92e4b79103f61ff9ac25f412ddfb7466e74f81b3e545563cdd8f5524dae873de61d7bdfccd496af2584930d2b566b4f8d3881f8c043df92224f38cf094cfc09d92655989531524593ec6d6caec1863bdfaa79229b5020acc034cd6deeea1021c50586947b9b8e6faa83b81fbfa6133c0af5d6b07c017f7158fa94f0d206baf12dda6b68f785b773b360fd0497e16cc402d779c8d48d0fa6315536ef0660f3f4e1865f5b38ea49c7da4fd959de4e83ff3ab686f059a45c65ba2af4a6a79166aa0f496bf04d06987b6d2ea205bdb0d347718b9aeff5b61dfff344993a275b79717cd815b6ad4c0beb568c4ac9c36ff1c315ec1119a1993c4b61e6eaa0375e0aaf738ac691abd3263bf937e3",
+    "aac3200c4968f56b21f53e5e374e3a2383ad2b1b6501bbcc45abc31e59b26881b7dfadbb56ec8dae8857add94e6702fb4c3a4de22e2e669e1ed926b04447fc73034bb730f4932acd62727b75348a648a1128744657ca6a4e713b9b646c3ca66cac02cdab44dd3439890ef3aaf61708714f7375349b8da541b2548d452d84de7084bb95b3ac2345201d624d31f4d52078aa0fa05a88b4e20202bd2b86ac5b52919ea305a8949de95e935eed0319cf3cf19ebea61d76ba92532497fcdc9411d06bcd4275094d0a4a3c5d3a945e43305a5a9256e333e1f64dbca5fcd4e03a39b9012d197506e06f29339dfee3331995b21615337ae060233d39befea925cc262873e0530408e6990f1cbd233a150ef7b004ff6166c70c68d9f8c853c1abca640b8660db2921",
+    "9c5add3963fc7f6ed7f148623c84134b5647e1306419dbe2174e523fa9e2fbed3a06a19f899145610741c83ad40b7712aefaddec8c6baf7325d92ea4ca4d1df8bce517f7e54554608bf2bd8071a4f52a7a2f7ffbb1413edad81eeea5785aa9d990f2865dc23b4bc3c301a94eec4eabebca66be5cf638f693ec256aec514620cc28ee4a94bd9565bc4d4962b9d3641d4278fb319ed2b84de5b665f307a2db0f7fbb757366067d88c50f7e829138fde4f78d39b5b5802f1b92a8a820865af5cc79f9f30bc3f461c66af95d13e5e1f0381c184572a91dee1c849048a647a1158cf884064deddbf1b0b88dfe2f791428d0ba0f6fb2f04e14081f69165ae66d9297c118f0907705c9c4954a199bae0bb96fad763d690e7daa6cfda59ba7f2c8d11448b604d12d",
+];
+
+fn session_key() -> SecretKey {
+    SecretKey::from_slice(&hex::decode(SESSION_KEY).unwrap()).unwrap()
+}
+
+fn hops() -> Vec<secp256k1PublicKey> {
+    HOP_PUBKEYS.iter().map(|pubkey| secp256k1PublicKey::from_slice(&hex::decode(pubkey).unwrap()).unwrap()).collect()
+}
+
+fn payloads() -> Vec<Vec<u8>> {
+    HOP_PAYLOADS.iter().map(|payload| hex::decode(payload).unwrap()).collect()
+}
+
+fn onion() -> OnionPacket {
+    construct_onion_packet(&session_key(), &hops(), &payloads(), &ASSOCIATED_DATA).unwrap()
+}
+
+#[test]
+fn bolt4_hop_keys() {
+    let keys = compute_hop_keys(&session_key(), &hops());
+    assert_eq!(keys.len(), 5);
+
+    for (hop, expected) in keys.iter().zip(HOP_KEYS) {
+        assert_eq!(hex::encode(hop.shared_secret), expected[0]);
+        assert_eq!(hex::encode(hop.blinding_factor), expected[1]);
+        assert_eq!(hex::encode(hop.ephemeral_pubkey.serialize()), expected[2]);
+        assert_eq!(hex::encode(hop.rho), expected[3]);
+        assert_eq!(hex::encode(hop.mu), expected[4]);
+    }
+}
+
+#[test]
+fn bolt4_onion_packet() {
+    let encoded = onion().encode();
+    assert_eq!(encoded.len(), ONION_PACKET_LEN);
+    assert_eq!(hex::encode(&encoded), ONION);
+
+    let decoded = OnionPacket::decode(&encoded.try_into().unwrap()).unwrap();
+    assert_eq!(decoded, onion());
+}
+
+#[test]
+fn bolt4_peel_every_hop() {
+    let secp = Secp256k1::new();
+    let mut packet = onion();
+
+    for (i, (byte, expected_payload)) in HOP_PRIVKEY_BYTES.iter().zip(payloads()).enumerate() {
+        let node_key = SecretKey::from_slice(&[*byte; 32]).unwrap();
+        assert_eq!(secp256k1PublicKey::from_secret_key(&secp, &node_key), hops()[i]);
+
+        match peel_onion(&packet, &node_key, &ASSOCIATED_DATA).unwrap() {
+            PeeledOnion::Forward { payload, next_packet } => {
+                assert!(i < 4, "final hop was told to forward");
+                assert_eq!(payload, expected_payload);
+                packet = *next_packet;
+            }
+            PeeledOnion::Receive { payload } => {
+                assert_eq!(i, 4, "hop {} was told it is the final hop", i);
+                assert_eq!(payload, expected_payload);
+            }
+        }
+    }
+}
+
+#[test]
+fn bolt4_tampered_onion_is_rejected() {
+    let node_key = SecretKey::from_slice(&[HOP_PRIVKEY_BYTES[0]; 32]).unwrap();
+
+    let mut packet = onion();
+    packet.hop_payloads[100] ^= 1;
+    assert_eq!(peel_onion(&packet, &node_key, &ASSOCIATED_DATA), Err(OnionError::InvalidHmac));
+
+    // The onion is bound to its payment hash.
+    assert_eq!(peel_onion(&onion(), &node_key, &[0x43; 32]), Err(OnionError::InvalidHmac));
+}
+
+#[test]
+fn bolt4_oversized_payloads_are_rejected() {
+    let mut payloads = payloads();
+    payloads[0] = vec![0; 1000];
+    assert!(matches!(
+        construct_onion_packet(&session_key(), &hops(), &payloads, &ASSOCIATED_DATA),
+        Err(OnionError::PayloadsTooLong { .. })
+    ));
+}
+
+#[test]
+fn bolt4_failure_packet() {
+    let keys = compute_hop_keys(&session_key(), &hops());
+
+    let mut packet = build_failure_packet(&keys[4].shared_secret, TEMPORARY_NODE_FAILURE, &[]);
+    assert_eq!(hex::encode(&packet), FAILURE_PACKET);
+
+    for (hop, expected) in keys.iter().rev().zip(ENCRYPTED_FAILURES) {
+        packet = encrypt_failure_packet(&hop.shared_secret, &packet);
+        assert_eq!(hex::encode(&packet), expected);
+    }
+
+    let failure = decrypt_failure_onion(&session_key(), &hops(), &packet).unwrap();
+    assert_eq!(failure.hop_index, 4);
+    assert_eq!(failure.node_id, hops()[4]);
+    assert_eq!(failure.failure_code, TEMPORARY_NODE_FAILURE);
+    assert!(failure.failure_data.is_empty());
+}
+
+#[test]
+fn failing_hop_is_identified() {
+    // The onion reaches the third hop, which has no liquidity on its
+    // outgoing channel and fails the HTLC back with an empty channel_update.
+    let mut packet = onion();
+    let mut shared_secrets = Vec::new();
+    for byte in &HOP_PRIVKEY_BYTES[..3] {
+        let node_key = SecretKey::from_slice(&[*byte; 32]).unwrap();
+        shared_secrets.push(shared_secret(&node_key, &packet.public_key));
+        if let PeeledOnion::Forward { next_packet, .. } = peel_onion(&packet, &node_key, &ASSOCIATED_DATA).unwrap() {
+            packet = *next_packet;
+        }
+    }
+
+    let mut failure = build_failure_packet(&shared_secrets[2], TEMPORARY_CHANNEL_FAILURE, &[0, 0]);
+    for secret in shared_secrets.iter().rev() {
+        failure = encrypt_failure_packet(secret, &failure);
+    }
+
+    let failure = decrypt_failure_onion(&session_key(), &hops(), &failure).unwrap();
+    assert_eq!(failure.hop_index, 2);
+    assert_eq!(failure.node_id, hops()[2]);
+    assert_eq!(failure.failure_code, TEMPORARY_CHANNEL_FAILURE);
+    assert_eq!(failure.failure_data, vec![0, 0]);
+
+    // A hop that garbles the failure on the way back hides who sent it.
+    let mut garbled = encrypt_failure_packet(&shared_secrets[0], &[0; 292]);
+    garbled[0] ^= 1;
+    assert_eq!(decrypt_failure_onion(&session_key(), &hops(), &garbled), Err(OnionError::UnattributableFailure));
+}
+
+#[test]
+fn bolt4_hop_payloads() {
+    let payloads = payloads();
+    assert_eq!(build_hop_payload(ShortChannelId(1), MilliSat(15000), 1500), payloads[0]);
+    assert_eq!(build_hop_payload(ShortChannelId(3), MilliSat(12500), 1250), payloads[2]);
+    assert_eq!(build_hop_payload(ShortChannelId(4), MilliSat(10000), 1000), payloads[3]);
+
+    // The vector's final payload carries a custom record after payment_data.
+    let payment_secret = PaymentSecret::from_slice(&hex::decode(PAYMENT_SECRET).unwrap()).unwrap();
+    let final_payload = build_final_hop_payload(MilliSat(10000), 1000, &payment_secret, MilliSat(10000));
+    assert!(payloads[4].starts_with(&final_payload));
+
+    let decoded = HopPayload::decode(&payloads[4]).unwrap();
+    assert_eq!(decoded.amt_to_forward, MilliSat(10000));
+    assert_eq!(decoded.outgoing_cltv_value, 1000);
+    assert_eq!(decoded.short_channel_id, None);
+    assert_eq!(decoded.payment_data, Some((payment_secret, MilliSat(10000))));
+
+    let decoded = HopPayload::decode(&payloads[1]).unwrap();
+    assert_eq!(decoded.short_channel_id, Some(ShortChannelId(2)));
+    assert_eq!(decoded.amt_to_forward, MilliSat(14000));
+    assert_eq!(decoded.outgoing_cltv_value, 1400);
+}
+
+#[test]
+fn payment_onion_follows_route() {
+    // Three channels, each hop charging 1000 msat and a 40-block delta.
+    let hop_keys: Vec<SecretKey> =
+        HOP_PRIVKEY_BYTES[..3].iter().map(|byte| SecretKey::from_slice(&[*byte; 32]).unwrap()).collect();
+    let route = Route {
+        hops: vec![
+            RouteHop {
+                node_id: hops()[0],
+                short_channel_id: ShortChannelId(10),
+                amount_msat: MilliSat(52_000),
+                cltv_expiry_delta: 80,
+            },
+            RouteHop {
+                node_id: hops()[1],
+                short_channel_id: ShortChannelId(11),
+                amount_msat: MilliSat(51_000),
+                cltv_expiry_delta: 40,
+            },
+            RouteHop {
+                node_id: hops()[2],
+                short_channel_id: ShortChannelId(12),
+                amount_msat: MilliSat(50_000),
+                cltv_expiry_delta: 0,
+            },
+        ],
+    };
+    let payment_secret = PaymentSecret([0x11; 32]);
+    let final_cltv_expiry = 800_000;
+
+    let onion =
+        build_payment_onion(&route, final_cltv_expiry, &payment_secret, &PaymentHash(ASSOCIATED_DATA), &session_key())
+            .unwrap();
+    assert_eq!(onion.amount_msat, MilliSat(52_000));
+    assert_eq!(onion.cltv_expiry, 800_080);
+
+    let PeeledOnion::Forward { payload, next_packet } =
+        peel_onion(&onion.packet, &hop_keys[0], &ASSOCIATED_DATA).unwrap()
+    else {
+        panic!("first hop should forward");
+    };
+    let payload = HopPayload::decode(&payload).unwrap();
+    assert_eq!(payload.short_channel_id, Some(ShortChannelId(11)));
+    assert_eq!((payload.amt_to_forward, payload.outgoing_cltv_value), (MilliSat(51_000), 800_040));
+
+    let PeeledOnion::Forward { payload, next_packet } =
+        peel_onion(&next_packet, &hop_keys[1], &ASSOCIATED_DATA).unwrap()
+    else {
+        panic!("second hop should forward");
+    };
+    let payload = HopPayload::decode(&payload).unwrap();
+    assert_eq!(payload.short_channel_id, Some(ShortChannelId(12)));
+    assert_eq!((payload.amt_to_forward, payload.outgoing_cltv_value), (MilliSat(50_000), 800_000));
+
+    let PeeledOnion::Receive { payload } = peel_onion(&next_packet, &hop_keys[2], &ASSOCIATED_DATA).unwrap() else {
+        panic!("last hop should receive");
+    };
+    let payload = HopPayload::decode(&payload).unwrap();
+    assert_eq!((payload.amt_to_forward, payload.outgoing_cltv_value), (MilliSat(50_000), 800_000));
+    assert_eq!(payload.payment_data, Some((payment_secret, MilliSat(50_000))));
+}
@@ -0,0 +1,169 @@
+//! BOLT 8 appendix handshake vectors, run through the transport exercises.
+use bitcoin::secp256k1::{PublicKey as secp256k1PublicKey, Secp256k1, SecretKey};
+use pl_00_intro::exercises::transport::{
+    act_one, act_three, act_two, decrypt_message, encrypt_message, initialize_handshake_state, process_act_one,
+    process_act_three, process_act_two, TransportKeys,
+};
+use pl_00_intro::internal::errors::TransportError;
+use pl_00_intro::internal::wire::{Message, Ping};
+
+const INITIATOR_STATIC_PRIVKEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+const INITIATOR_EPHEMERAL_PRIVKEY: &str = "1212121212121212121212121212121212121212121212121212121212121212";
+const RESPONDER_STATIC_PRIVKEY: &str = "2121212121212121212121212121212121212121212121212121212121212121";
+const RESPONDER_EPHEMERAL_PRIVKEY: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+
+const ACT_ONE: &str = "00036360e856310ce5d294e8be33fc807077dc56ac80d95d9cd4ddbd21325eff73f70df6086551151f58b8afe6c195782c6a";
+const ACT_TWO: &str = "0002466d7fcae563e5cb09a0d1870bb580344804617879a14949cf22285f1bae3f276e2470b93aac583c9ef6eafca3f730ae";
+const ACT_THREE: &str = "00b9e3a702e93e3a9948c2ed6e5fd7590a6e1c3a0344cfc9d5b57357049aa22355361aa02e55a8fc28fef5bd6d71ad0c38228dc68b1c466263b47fdf31e560e139ba";
+
+const INITIATOR_SENDING_KEY: &str = "969ab31b4d288cedf6218839b27a3e2140827047f2c0f01bf5c04435d43511a9";
+const INITIATOR_RECEIVING_KEY: &str = "bb9020b8965f4df047e07f955f3c4b88418984aadc5cdb35096b9ea8fa5c3442";
+
+const INITIATOR_CHAINING_KEY: &str = "919219dbb2920afa8db80f9a51787a840bcf111ed8d588caf9ab4be716e42b01";
+
+const HELLO_MESSAGES: [(usize, &str); 6] = [
+    (0, "cf2b30ddf0cf3f80e7c35a6e6730b59fe802473180f396d88a8fb0db8cbcf25d2f214cf9ea1d95"),
+    (1, "72887022101f0b6753e0c7de21657d35a4cb2a1f5cde2650528bbc8f837d0f0d7ad833b1a256a1"),
+    (500, "178cb9d7387190fa34db9c2d50027d21793c9bc2d40b1e14dcf30ebeeeb220f48364f7a4c68bf8"),
+    (501, "1b186c57d44eb6de4c057c49940d79bb838a145cb528d6e8fd26dbe50a60ca2c104b56b60e45bd"),
+    (1000, "4a2f3cc3b5e78ddb83dcb426d9863d9d9a723b0337c89dd0b005d89f8d3c05c52b76b29b740f09"),
+    (1001, "2ecd8c8a5629d0d02ab457a0fdd0f7b90a192cd46be5ecb6ca570bfc5e268338b1a16cf4ef2d36"),
+];
+
+fn secret_key(hex_str: &str) -> SecretKey {
+    SecretKey::from_slice(&hex::decode(hex_str).unwrap()).unwrap()
+}
+
+fn pubkey_of(hex_str: &str) -> secp256k1PublicKey {
+    secp256k1PublicKey::from_secret_key(&Secp256k1::new(), &secret_key(hex_str))
+}
+
+#[test]
+fn bolt8_handshake() {
+    let responder_static_pubkey = pubkey_of(RESPONDER_STATIC_PRIVKEY);
+    let mut initiator = initialize_handshake_state(&responder_static_pubkey);
+    let mut responder = initialize_handshake_state(&responder_static_pubkey);
+
+    let act1 = act_one(&mut initiator, &secret_key(INITIATOR_EPHEMERAL_PRIVKEY), &responder_static_pubkey);
+    assert_eq!(hex::encode(act1), ACT_ONE);
+    let initiator_ephemeral_pubkey =
+        process_act_one(&mut responder, &secret_key(RESPONDER_STATIC_PRIVKEY), &act1).unwrap();
+    assert_eq!(initiator_ephemeral_pubkey, pubkey_of(INITIATOR_EPHEMERAL_PRIVKEY));
+
+    let act2 = act_two(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &initiator_ephemeral_pubkey);
+    assert_eq!(hex::encode(act2), ACT_TWO);
+    let responder_ephemeral_pubkey =
+        process_act_two(&mut initiator, &secret_key(INITIATOR_EPHEMERAL_PRIVKEY), &act2).unwrap();
+    assert_eq!(responder_ephemeral_pubkey, pubkey_of(RESPONDER_EPHEMERAL_PRIVKEY));
+
+    let (act3, initiator_keys) =
+        act_three(&mut initiator, &secret_key(INITIATOR_STATIC_PRIVKEY), &responder_ephemeral_pubkey);
+    assert_eq!(hex::encode(act3), ACT_THREE);
+    assert_eq!(hex::encode(initiator_keys.sending_key), INITIATOR_SENDING_KEY);
+    assert_eq!(hex::encode(initiator_keys.receiving_key), INITIATOR_RECEIVING_KEY);
+    assert_eq!(hex::encode(initiator_keys.chaining_key), INITIATOR_CHAINING_KEY);
+
+    let (initiator_static_pubkey, responder_keys) =
+        process_act_three(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &act3).unwrap();
+    assert_eq!(initiator_static_pubkey, pubkey_of(INITIATOR_STATIC_PRIVKEY));
+    assert_eq!(responder_keys.sending_key, initiator_keys.receiving_key);
+    assert_eq!(responder_keys.receiving_key, initiator_keys.sending_key);
+    assert_eq!(responder_keys.chaining_key, initiator_keys.chaining_key);
+}
+
+#[test]
+fn bolt8_act_one_rejects_bad_version_and_mac() {
+    let responder_static_pubkey = pubkey_of(RESPONDER_STATIC_PRIVKEY);
+    let responder_static_key = secret_key(RESPONDER_STATIC_PRIVKEY);
+    let act1: [u8; 50] = hex::decode(ACT_ONE).unwrap().try_into().unwrap();
+
+    let mut bad_version = act1;
+    bad_version[0] = 1;
+    let mut responder = initialize_handshake_state(&responder_static_pubkey);
+    assert_eq!(
+        process_act_one(&mut responder, &responder_static_key, &bad_version),
+        Err(TransportError::UnknownVersion(1))
+    );
+
+    let mut bad_mac = act1;
+    bad_mac[49] ^= 1;
+    let mut responder = initialize_handshake_state(&responder_static_pubkey);
+    assert_eq!(
+        process_act_one(&mut responder, &responder_static_key, &bad_mac),
+        Err(TransportError::DecryptionFailed)
+    );
+}
+
+#[test]
+fn bolt8_act_three_rejects_bad_mac() {
+    let responder_static_pubkey = pubkey_of(RESPONDER_STATIC_PRIVKEY);
+    let mut responder = initialize_handshake_state(&responder_static_pubkey);
+    let act1: [u8; 50] = hex::decode(ACT_ONE).unwrap().try_into().unwrap();
+    let re = process_act_one(&mut responder, &secret_key(RESPONDER_STATIC_PRIVKEY), &act1).unwrap();
+    act_two(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &re);
+
+    let mut act3: [u8; 66] = hex::decode(ACT_THREE).unwrap().try_into().unwrap();
+    act3[65] ^= 1;
+    assert_eq!(
+        process_act_three(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &act3).map(|(rs, _)| rs),
+        Err(TransportError::DecryptionFailed)
+    );
+}
+
+fn handshake_keys() -> (TransportKeys, TransportKeys) {
+    let responder_static_pubkey = pubkey_of(RESPONDER_STATIC_PRIVKEY);
+    let mut initiator = initialize_handshake_state(&responder_static_pubkey);
+    let mut responder = initialize_handshake_state(&responder_static_pubkey);
+
+    let act1 = act_one(&mut initiator, &secret_key(INITIATOR_EPHEMERAL_PRIVKEY), &responder_static_pubkey);
+    let re = process_act_one(&mut responder, &secret_key(RESPONDER_STATIC_PRIVKEY), &act1).unwrap();
+    let act2 = act_two(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &re);
+    let re = process_act_two(&mut initiator, &secret_key(INITIATOR_EPHEMERAL_PRIVKEY), &act2).unwrap();
+    let (act3, initiator_keys) = act_three(&mut initiator, &secret_key(INITIATOR_STATIC_PRIVKEY), &re);
+    let (_, responder_keys) =
+        process_act_three(&mut responder, &secret_key(RESPONDER_EPHEMERAL_PRIVKEY), &act3).unwrap();
+
+    (initiator_keys, responder_keys)
+}
+
+#[test]
+fn bolt8_message_encryption_with_key_rotation() {
+    let (initiator_keys, responder_keys) = handshake_keys();
+    let mut sender = initiator_keys.sending_cipher();
+    let mut receiver = responder_keys.receiving_cipher();
+
+    let mut expected = HELLO_MESSAGES.iter();
+    let mut next = expected.next();
+    for i in 0..=1001 {
+        let message = encrypt_message(&mut sender, b"hello").unwrap();
+        if let Some((index, expected_hex)) = next {
+            if *index == i {
+                assert_eq!(hex::encode(&message), *expected_hex, "message {}", i);
+                next = expected.next();
+            }
+        }
+        assert_eq!(decrypt_message(&mut receiver, &message).unwrap(), b"hello");
+    }
+    assert!(next.is_none());
+}
+
+#[test]
+fn bolt8_decrypt_message_rejects_tampering() {
+    let (initiator_keys, responder_keys) = handshake_keys();
+    let mut sender = initiator_keys.sending_cipher();
+
+    let ping = Message::Ping(Ping { num_pong_bytes: 4, ignored: vec![] }).encode();
+    let message = encrypt_message(&mut sender, &ping).unwrap();
+
+    let mut tampered = message.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        decrypt_message(&mut responder_keys.receiving_cipher(), &tampered),
+        Err(TransportError::InvalidMessageMac)
+    );
+
+    let decrypted = decrypt_message(&mut responder_keys.receiving_cipher(), &message).unwrap();
+    assert_eq!(Message::decode(&decrypted).unwrap(), Message::decode(&ping).unwrap());
+
+    assert_eq!(encrypt_message(&mut sender, &[0; 65536]), Err(TransportError::MessageTooLong(65536)));
+}
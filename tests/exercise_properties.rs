@@ -0,0 +1,264 @@
+//! Properties the script and key exercises must have for any keys and
+//! amounts, not just the ones in the example-based tests. Every case is
+//! drawn from a seeded hash of the property name and case number, so a
+//! failing case prints its number and fails the same way every run.
+use bitcoin::blockdata::opcodes::all as opcodes;
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::script::{Builder, Instruction, ScriptBuf};
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Amount, PublicKey, Transaction};
+use pl_00_intro::exercises::exercises::{
+    build_commitment_transaction, build_htlc_commitment_transaction, generate_revocation_privkey,
+    generate_revocation_pubkey, to_local,
+};
+use pl_00_intro::exercises::solutions;
+use pl_00_intro::internal::fee_utils::HtlcFeeMode;
+use pl_00_intro::internal::htlc_utils::{is_htlc_trimmed, Htlc};
+use pl_00_intro::internal::key_utils::{pubkey_from_secret, secp256k1_private_key};
+use pl_00_intro::internal::keys::{DelayedPaymentPubkey, HtlcPubkey, RevocationPubkey};
+use pl_00_intro::internal::payments::PaymentHash160;
+use pl_00_intro::internal::tx_utils::dummy_funding_txin;
+use pl_00_intro::internal::units::MilliSat;
+
+const CASES: u64 = 256;
+
+const DUST_LIMIT: Amount = Amount::from_sat(546);
+
+/// Amounts close enough together that outputs often tie on value, so the
+/// script_pubkey tie-break gets exercised too.
+const AMOUNTS_SAT: [u64; 4] = [546, 10_000, 100_000, 1_000_000];
+
+/// The values of one case.
+struct Case {
+    seed: [u8; 32],
+    draws: u64,
+}
+
+impl Case {
+    fn new(property: &str, case: u64) -> Case {
+        let mut engine = Sha256::engine();
+        engine.input(property.as_bytes());
+        engine.input(&case.to_be_bytes());
+        Case { seed: Sha256::from_engine(engine).to_byte_array(), draws: 0 }
+    }
+
+    fn bytes(&mut self) -> [u8; 32] {
+        let mut engine = Sha256::engine();
+        engine.input(&self.seed);
+        engine.input(&self.draws.to_be_bytes());
+        self.draws += 1;
+        Sha256::from_engine(engine).to_byte_array()
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        u64::from_be_bytes(self.bytes()[..8].try_into().unwrap()) % n
+    }
+
+    fn secret_key(&mut self) -> SecretKey {
+        secp256k1_private_key(&self.bytes())
+    }
+
+    fn pubkey(&mut self) -> PublicKey {
+        PublicKey::new(pubkey_from_secret(self.secret_key()))
+    }
+
+    fn amount(&mut self) -> Amount {
+        Amount::from_sat(AMOUNTS_SAT[self.below(AMOUNTS_SAT.len() as u64) as usize])
+    }
+
+    fn delay(&mut self) -> i64 {
+        // small delays are pushed as opcodes, and the rest as numbers
+        if self.below(2) == 0 {
+            1 + self.below(16) as i64
+        } else {
+            1 + self.below(u16::MAX as u64) as i64
+        }
+    }
+}
+
+/// BOLT 3 orders outputs by value, then by script_pubkey.
+fn assert_outputs_sorted(tx: &Transaction, case: u64) {
+    for pair in tx.output.windows(2) {
+        assert!(
+            (pair[0].value, &pair[0].script_pubkey) <= (pair[1].value, &pair[1].script_pubkey),
+            "case {}: outputs out of order: {:?}",
+            case,
+            tx.output
+        );
+    }
+}
+
+#[test]
+fn revocation_privkey_matches_revocation_pubkey() {
+    for case in 0..CASES {
+        let mut values = Case::new("revocation", case);
+        let basepoint_secret = values.secret_key();
+        let per_commitment_secret = values.secret_key();
+
+        let revocation_pubkey =
+            generate_revocation_pubkey(pubkey_from_secret(basepoint_secret), pubkey_from_secret(per_commitment_secret));
+        let revocation_privkey = generate_revocation_privkey(basepoint_secret, per_commitment_secret);
+
+        assert_eq!(pubkey_from_secret(revocation_privkey), revocation_pubkey, "case {}", case);
+    }
+}
+
+#[test]
+fn commitment_outputs_are_sorted() {
+    for case in 0..CASES {
+        let mut values = Case::new("commitment outputs", case);
+        let revocation_pubkey = RevocationPubkey(values.pubkey());
+        let delayed_pubkey = DelayedPaymentPubkey(values.pubkey());
+        let remote_pubkey = values.pubkey();
+        let to_self_delay = values.delay();
+        let (local_amount, remote_amount) = (values.amount(), values.amount());
+
+        let tx = build_commitment_transaction(
+            dummy_funding_txin(),
+            &revocation_pubkey,
+            &delayed_pubkey,
+            remote_pubkey,
+            to_self_delay,
+            local_amount,
+            remote_amount,
+            true,
+            253,
+            values.below(1 << 48),
+            values.below(1 << 48),
+        )
+        .unwrap();
+        assert_eq!(tx.output.len(), 2, "case {}", case);
+        assert_outputs_sorted(&tx, case);
+    }
+}
+
+#[test]
+fn solution_outputs_are_sorted() {
+    for case in 0..CASES {
+        let mut values = Case::new("solution outputs", case);
+        let (alice_pubkey, bob_pubkey) = (values.pubkey(), values.pubkey());
+        let (alice_balance, bob_balance) = (values.amount(), values.amount());
+
+        let refund_tx =
+            solutions::build_refund_transaction(dummy_funding_txin(), alice_pubkey, bob_pubkey, alice_balance, bob_balance);
+        assert_outputs_sorted(&refund_tx, case);
+
+        let commitment_tx = solutions::build_commitment_transaction(
+            dummy_funding_txin(),
+            &RevocationPubkey(values.pubkey()),
+            &DelayedPaymentPubkey(values.pubkey()),
+            values.pubkey(),
+            values.delay(),
+            alice_balance,
+            bob_balance,
+            true,
+            253,
+            values.below(1 << 48),
+            values.below(1 << 48),
+        )
+        .unwrap();
+        assert_outputs_sorted(&commitment_tx, case);
+    }
+}
+
+#[test]
+fn htlc_commitment_outputs_are_sorted() {
+    for case in 0..CASES {
+        let mut values = Case::new("htlc commitment outputs", case);
+        let revocation_pubkey = RevocationPubkey(values.pubkey());
+        let remote_htlc_pubkey = HtlcPubkey(values.pubkey());
+        let local_htlc_pubkey = HtlcPubkey(values.pubkey());
+        let delayed_pubkey = DelayedPaymentPubkey(values.pubkey());
+        let remote_pubkey = values.pubkey();
+        let to_self_delay = values.delay();
+        let htlcs: Vec<Htlc> = (0..values.below(5))
+            .map(|_| Htlc {
+                offered: values.below(2) == 0,
+                amount_msat: MilliSat::from_msat(values.amount().to_sat() * 1000),
+                payment_hash: PaymentHash160(values.bytes()[..20].try_into().unwrap()),
+                cltv_expiry: 500_000 + values.below(3) as u32,
+            })
+            .collect();
+        let (local_amount, remote_amount) = (values.amount(), values.amount());
+
+        let tx = build_htlc_commitment_transaction(
+            dummy_funding_txin(),
+            &revocation_pubkey,
+            &remote_htlc_pubkey,
+            &local_htlc_pubkey,
+            &delayed_pubkey,
+            remote_pubkey,
+            to_self_delay,
+            &htlcs,
+            local_amount,
+            remote_amount,
+            true,
+            DUST_LIMIT,
+            253,
+            values.below(1 << 48),
+            values.below(1 << 48),
+        )
+        .unwrap();
+        let untrimmed = htlcs
+            .iter()
+            .filter(|htlc| {
+                let amount = htlc.amount_msat.to_amount_floor();
+                !is_htlc_trimmed(amount, DUST_LIMIT, 253, htlc.offered, HtlcFeeMode::Legacy)
+            })
+            .count();
+        assert_eq!(tx.output.len(), 2 + untrimmed, "case {}", case);
+        assert_outputs_sorted(&tx, case);
+    }
+}
+
+#[test]
+fn to_local_round_trips_through_script_parsing() {
+    for case in 0..CASES {
+        let mut values = Case::new("to_local", case);
+        let revocation_pubkey = RevocationPubkey(values.pubkey());
+        let delayed_pubkey = DelayedPaymentPubkey(values.pubkey());
+        let to_self_delay = values.delay();
+        let script = to_local(&revocation_pubkey, &delayed_pubkey, to_self_delay).unwrap();
+
+        let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().unwrap();
+        let [if_op, revocation_key, else_op, delay, csv, drop, delayed_key, endif, checksig] = instructions.as_slice()
+        else {
+            panic!("case {}: unexpected to_local script {:?}", case, script);
+        };
+        assert_eq!(
+            [if_op, else_op, csv, drop, endif, checksig].map(|instruction| instruction.opcode()),
+            [
+                opcodes::OP_IF,
+                opcodes::OP_ELSE,
+                opcodes::OP_CSV,
+                opcodes::OP_DROP,
+                opcodes::OP_ENDIF,
+                opcodes::OP_CHECKSIG
+            ]
+            .map(Some),
+            "case {}",
+            case
+        );
+        let (revocation_key, delayed_key) = (revocation_key.push_bytes().unwrap(), delayed_key.push_bytes().unwrap());
+        assert_eq!(PublicKey::from_slice(revocation_key.as_bytes()).unwrap(), revocation_pubkey.0, "case {}", case);
+        assert_eq!(PublicKey::from_slice(delayed_key.as_bytes()).unwrap(), delayed_pubkey.0, "case {}", case);
+        assert_eq!(delay.script_num(), Some(to_self_delay), "case {}", case);
+
+        // the parsed parts build the same script again, with minimal pushes
+        let rebuilt = Builder::new()
+            .push_opcode(opcodes::OP_IF)
+            .push_slice(revocation_key)
+            .push_opcode(opcodes::OP_ELSE)
+            .push_int(delay.script_num().unwrap())
+            .push_opcode(opcodes::OP_CSV)
+            .push_opcode(opcodes::OP_DROP)
+            .push_slice(delayed_key)
+            .push_opcode(opcodes::OP_ENDIF)
+            .push_opcode(opcodes::OP_CHECKSIG)
+            .into_script();
+        assert_eq!(rebuilt, script, "case {}", case);
+        assert_eq!(ScriptBuf::from_bytes(script.to_bytes()), script, "case {}", case);
+    }
+}
@@ -0,0 +1,136 @@
+use bitcoin::script::ScriptBuf;
+use bitcoin::{Amount, OutPoint, Transaction};
+use pl_00_intro::exercises::exercises::{
+    build_commitment_transaction_with_anchors, build_funding_transaction, build_funding_transaction_with_script,
+    two_of_two_multisig_witness_script,
+};
+use pl_00_intro::internal::key_utils::{pubkey_from_private_key, secp256k1_private_key, sort_funding_keys};
+use pl_00_intro::internal::script_utils::{build_anchor_output_script, funding_output_descriptor};
+use pl_00_intro::internal::sign_utils::sign_funding_transaction;
+use pl_00_intro::internal::tx_utils::dummy_funding_txin;
+use pl_00_intro::internal::keys::{DelayedPaymentPubkey, FundingPubkey, RevocationPubkey};
+
+#[test]
+fn funding_keys_sort_to_one_canonical_order() {
+    let a = pubkey_from_private_key(&[0x01; 32]);
+    let b = pubkey_from_private_key(&[0x02; 32]);
+
+    let (first, second) = sort_funding_keys(a, b);
+    assert_eq!(sort_funding_keys(b, a), (first, second));
+    assert!(first.inner.serialize() < second.inner.serialize());
+}
+
+#[test]
+fn funding_script_is_independent_of_key_order() {
+    let a = pubkey_from_private_key(&[0x01; 32]);
+    let b = pubkey_from_private_key(&[0x02; 32]);
+    let (first, second) = sort_funding_keys(a, b);
+    let canonical_script = two_of_two_multisig_witness_script(&first, &second);
+
+    let (tx_ab, script_ab) = build_funding_transaction_with_script(vec![dummy_funding_txin()], &a, &b, Amount::from_sat(5_000_000));
+    let (tx_ba, script_ba) = build_funding_transaction_with_script(vec![dummy_funding_txin()], &b, &a, Amount::from_sat(5_000_000));
+
+    assert_eq!(script_ab, canonical_script);
+    assert_eq!(script_ba, canonical_script);
+    assert_eq!(tx_ab.output[0].script_pubkey, canonical_script.to_p2wsh());
+    assert_eq!(tx_ab.compute_txid(), tx_ba.compute_txid());
+}
+
+#[test]
+fn exercise_2_agrees_with_the_sorted_helper_only_for_sorted_keys() {
+    let a = pubkey_from_private_key(&[0x01; 32]);
+    let b = pubkey_from_private_key(&[0x02; 32]);
+    let (first, second) = sort_funding_keys(a, b);
+    let amount = Amount::from_sat(5_000_000);
+
+    let (sorted_tx, _) = build_funding_transaction_with_script(vec![dummy_funding_txin()], &a, &b, amount);
+    let in_order = build_funding_transaction(vec![dummy_funding_txin()], &first, &second, amount);
+    let out_of_order = build_funding_transaction(vec![dummy_funding_txin()], &second, &first, amount);
+
+    // Exercise 2 uses the keys as given, so only the sorted order gives the same outpoint
+    assert_eq!(in_order.compute_txid(), sorted_tx.compute_txid());
+    assert_ne!(out_of_order.compute_txid(), sorted_tx.compute_txid());
+}
+
+#[test]
+fn funding_descriptor_is_independent_of_key_order() {
+    let a = pubkey_from_private_key(&[0x01; 32]);
+    let b = pubkey_from_private_key(&[0x02; 32]);
+    let (first, second) = sort_funding_keys(a, b);
+
+    let expected = format!("wsh(multi(2,{},{}))", first, second);
+    assert_eq!(funding_output_descriptor(&a, &b), expected);
+    assert_eq!(funding_output_descriptor(&b, &a), expected);
+}
+
+#[test]
+fn anchors_follow_their_owners_funding_key() {
+    let a = FundingPubkey(pubkey_from_private_key(&[0x01; 32]));
+    let b = FundingPubkey(pubkey_from_private_key(&[0x02; 32]));
+    let revocation_pubkey = RevocationPubkey(pubkey_from_private_key(&[0x03; 32]));
+    let delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&[0x04; 32]));
+    let remote_pubkey = pubkey_from_private_key(&[0x05; 32]);
+
+    let anchor_scripts = |local_funding, remote_funding| {
+        let tx = build_commitment_transaction_with_anchors(
+            dummy_funding_txin(),
+            &revocation_pubkey,
+            &delayed_pubkey,
+            remote_pubkey,
+            local_funding,
+            remote_funding,
+            144,
+            Amount::from_sat(3_000_000),
+            Amount::from_sat(2_000_000),
+            true,
+            Amount::from_sat(546),
+            253,
+        )
+        .unwrap();
+        let mut scripts: Vec<ScriptBuf> = tx
+            .output
+            .iter()
+            .filter(|output| output.value.to_sat() == 330)
+            .map(|output| output.script_pubkey.clone())
+            .collect();
+        scripts.sort();
+        scripts
+    };
+
+    let mut expected = vec![
+        build_anchor_output_script(&a).to_p2wsh(),
+        build_anchor_output_script(&b).to_p2wsh(),
+    ];
+    expected.sort();
+
+    // anchors are keyed to each party's own funding key, not to the sorted order
+    assert_eq!(anchor_scripts(&a, &b), expected);
+    assert_eq!(anchor_scripts(&b, &a), expected);
+}
+
+#[test]
+fn funding_signatures_match_the_sorted_script() {
+    let a_secret = secp256k1_private_key(&[0x01; 32]);
+    let b_secret = secp256k1_private_key(&[0x02; 32]);
+    let a = pubkey_from_private_key(&[0x01; 32]);
+    let b = pubkey_from_private_key(&[0x02; 32]);
+
+    let (funding_tx, funding_script) = build_funding_transaction_with_script(vec![dummy_funding_txin()], &a, &b, Amount::from_sat(5_000_000));
+
+    let spend = |our_pubkey, our_secret, counterparty_pubkey, counterparty_secret| -> Transaction {
+        let mut tx = funding_tx.clone();
+        tx.input[0].previous_output = OutPoint::new(funding_tx.compute_txid(), 0);
+        tx.output[0].value = bitcoin::Amount::from_sat(4_990_000);
+        sign_funding_transaction(tx, our_pubkey, our_secret, counterparty_pubkey, counterparty_secret)
+    };
+
+    // whichever side signs, the witness spends the funding output
+    for signed in [spend(a, a_secret, b, b_secret), spend(b, b_secret, a, a_secret)] {
+        let witness_script = signed.input[0].witness.last().unwrap();
+        assert_eq!(witness_script, funding_script.as_bytes());
+
+        signed
+            .verify(|_| Some(funding_tx.output[0].clone()))
+            .expect("funding spend should pass script verification");
+    }
+}
@@ -0,0 +1,78 @@
+//! Run the fuzz targets without `cargo fuzz`, with
+//! `cargo test --features fuzz --test fuzz_smoke`. Each gets valid inputs,
+//! then seeded random mutations of them: a few thousand cases rather than
+//! a fuzzer's millions, but enough to find the shallow crashes.
+#![cfg(feature = "fuzz")]
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::hashes::{Hash, HashEngine};
+use pl_00_intro::internal::fuzz::{fuzz_invoice_decode, fuzz_onion_peel, fuzz_tlv_roundtrip};
+use pl_00_intro::internal::onion_utils::{frame_hop_payload, HopPayload};
+use pl_00_intro::internal::tlv::{encode_tu64, write_tlv_stream, TlvRecord};
+
+const MUTATIONS: u64 = 2_000;
+
+/// From BOLT 11: a donation invoice without an amount.
+const INVOICE: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+
+fn random_bytes(target: &str, case: u64) -> [u8; 32] {
+    let mut engine = Sha256::engine();
+    engine.input(target.as_bytes());
+    engine.input(&case.to_be_bytes());
+    Sha256::from_engine(engine).to_byte_array()
+}
+
+/// `seed` with a few bytes overwritten, dropped or added, chosen by `case`.
+fn mutate(seed: &[u8], target: &str, case: u64) -> Vec<u8> {
+    let random = random_bytes(target, case);
+    let mut data = seed.to_vec();
+    for chunk in random.chunks(4).take(1 + random[0] as usize % 4) {
+        let position = u16::from_be_bytes([chunk[0], chunk[1]]) as usize % (data.len() + 1);
+        match chunk[2] % 3 {
+            0 if position < data.len() => data[position] = chunk[3],
+            1 if position < data.len() => {
+                data.remove(position);
+            }
+            _ => data.insert(position, chunk[3]),
+        }
+    }
+    data
+}
+
+fn run(target: &str, seeds: &[Vec<u8>], fuzz: fn(&[u8])) {
+    for seed in seeds {
+        fuzz(seed);
+        for case in 0..MUTATIONS {
+            fuzz(&mutate(seed, target, case));
+        }
+    }
+    for case in 0..MUTATIONS {
+        fuzz(&random_bytes(target, case)[..case as usize % 33]);
+    }
+}
+
+#[test]
+fn tlv_roundtrip() {
+    let stream = write_tlv_stream(&[
+        TlvRecord::new(2, encode_tu64(1_000)),
+        TlvRecord::new(3, vec![0x01; 300]),
+        TlvRecord::new(0xfd01, vec![]),
+    ])
+    .unwrap();
+    run("tlv", &[vec![], stream], fuzz_tlv_roundtrip);
+}
+
+#[test]
+fn invoice_decode() {
+    run("invoice", &[INVOICE.as_bytes().to_vec()], fuzz_invoice_decode);
+}
+
+#[test]
+fn onion_peel() {
+    let payload =
+        write_tlv_stream(&[TlvRecord::new(2, encode_tu64(1_000)), TlvRecord::new(4, encode_tu64(800_000))]).unwrap();
+    HopPayload::decode(&payload).unwrap();
+    let mut framed = frame_hop_payload(&payload);
+    // a final hop's all-zero next HMAC
+    framed.extend_from_slice(&[0; 32]);
+    run("onion", &[framed], fuzz_onion_peel);
+}
@@ -0,0 +1,137 @@
+//! Run against a local regtest bitcoind with
+//! `cargo test --features regtest --test regtest_funding -- --test-threads=1`.
+//! The tests mine blocks on the same chain, so they can't run in parallel.
+#![cfg(feature = "regtest")]
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{Amount, OutPoint, PublicKey, Sequence, Transaction, TxIn, Witness};
+use pl_00_intro::exercises::exercises::{
+    build_commitment_transaction, build_delayed_sweep_transaction, build_funding_transaction, funding_confirmations,
+    sign_commitment_transaction, to_local, two_of_two_multisig_witness_script,
+};
+use pl_00_intro::internal::harness::RegtestHarness;
+use pl_00_intro::internal::key_utils::{pubkey_from_private_key, secp256k1_private_key};
+use pl_00_intro::internal::keys::{DelayedPaymentPubkey, RevocationPubkey};
+use pl_00_intro::internal::script_utils::p2wpkh_output_script;
+use pl_00_intro::internal::sign_utils::finalize_commitment;
+
+const FUNDING_AMOUNT: Amount = Amount::from_sat(5_000_000);
+const TO_SELF_DELAY: u16 = 6;
+
+struct Keys {
+    alice_funding: SecretKey,
+    bob_funding: SecretKey,
+    revocation: RevocationPubkey,
+    bob_delayed: SecretKey,
+    alice_payment: PublicKey,
+}
+
+fn keys() -> Keys {
+    Keys {
+        alice_funding: secp256k1_private_key(&[0x01; 32]),
+        bob_funding: secp256k1_private_key(&[0x02; 32]),
+        revocation: RevocationPubkey(pubkey_from_private_key(&[0x03; 32])),
+        bob_delayed: secp256k1_private_key(&[0x04; 32]),
+        alice_payment: pubkey_from_private_key(&[0x05; 32]),
+    }
+}
+
+/// Fund the learner's funding transaction from the wallet, and check that
+/// the mined block proves it.
+async fn confirmed_funding(harness: &RegtestHarness, keys: &Keys) -> Transaction {
+    let (wallet_txin, _) = harness.fund(FUNDING_AMOUNT + Amount::from_sat(10_000)).await;
+    let funding_tx = build_funding_transaction(
+        vec![wallet_txin],
+        &pubkey_from_private_key(&keys.alice_funding.secret_bytes()),
+        &pubkey_from_private_key(&keys.bob_funding.secret_bytes()),
+        FUNDING_AMOUNT,
+    );
+    let funding_tx = harness.sign_with_wallet(&funding_tx).await;
+
+    let (block, height) = harness.assert_confirms(&funding_tx).await;
+    assert_eq!(funding_confirmations(&block, height, &funding_tx.compute_txid(), height + 2), Some(3));
+    funding_tx
+}
+
+/// Bob's commitment, signed by both sides.
+fn signed_commitment(keys: &Keys, funding_tx: &Transaction) -> Transaction {
+    let alice_funding_pubkey = pubkey_from_private_key(&keys.alice_funding.secret_bytes());
+    let bob_funding_pubkey = pubkey_from_private_key(&keys.bob_funding.secret_bytes());
+    let funding_script = two_of_two_multisig_witness_script(&alice_funding_pubkey, &bob_funding_pubkey);
+
+    let funding_txin = TxIn {
+        previous_output: OutPoint::new(funding_tx.compute_txid(), 0),
+        script_sig: Default::default(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    };
+    let commitment = build_commitment_transaction(
+        funding_txin,
+        &keys.revocation,
+        &DelayedPaymentPubkey(pubkey_from_private_key(&keys.bob_delayed.secret_bytes())),
+        keys.alice_payment,
+        TO_SELF_DELAY as i64,
+        Amount::from_sat(3_995_000),
+        Amount::from_sat(1_000_000),
+        true,
+        253,
+        // the BOLT 3 appendix C obscure factor
+        0x2bb038521914,
+        0,
+    )
+    .unwrap();
+
+    let bob_signature = sign_commitment_transaction(&commitment, &funding_script, FUNDING_AMOUNT, &keys.bob_funding);
+    let alice_signature =
+        sign_commitment_transaction(&commitment, &funding_script, FUNDING_AMOUNT, &keys.alice_funding);
+    finalize_commitment(
+        commitment,
+        &funding_script,
+        &bob_funding_pubkey,
+        &bob_signature,
+        &alice_funding_pubkey,
+        &alice_signature,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn funding_and_commitment_confirm() {
+    let harness = RegtestHarness::new().await;
+    let keys = keys();
+
+    let funding_tx = confirmed_funding(&harness, &keys).await;
+    harness.assert_confirms(&signed_commitment(&keys, &funding_tx)).await;
+}
+
+#[tokio::test]
+async fn delayed_sweep_waits_for_to_self_delay() {
+    let harness = RegtestHarness::new().await;
+    let keys = keys();
+
+    let funding_tx = confirmed_funding(&harness, &keys).await;
+    let commitment = signed_commitment(&keys, &funding_tx);
+    harness.assert_confirms(&commitment).await;
+
+    let bob_delayed_pubkey = DelayedPaymentPubkey(pubkey_from_private_key(&keys.bob_delayed.secret_bytes()));
+    let to_local_script = to_local(&keys.revocation, &bob_delayed_pubkey, TO_SELF_DELAY as i64).unwrap();
+    let to_local_index =
+        commitment.output.iter().position(|output| output.script_pubkey == to_local_script.to_p2wsh()).unwrap();
+    let sweep = build_delayed_sweep_transaction(
+        OutPoint::new(commitment.compute_txid(), to_local_index as u32),
+        commitment.output[to_local_index].value,
+        &keys.bob_delayed,
+        &to_local_script,
+        TO_SELF_DELAY,
+        p2wpkh_output_script(bob_delayed_pubkey.0),
+        253,
+    )
+    .unwrap();
+
+    // The commitment has one confirmation. The sweep can go in the block
+    // where it has `TO_SELF_DELAY`, and not before.
+    harness.mine_blocks(TO_SELF_DELAY as u64 - 2).await;
+    assert!(harness.broadcast(&sweep).await.is_err());
+
+    harness.mine_blocks(1).await;
+    harness.assert_confirms(&sweep).await;
+}
@@ -0,0 +1,302 @@
+use bitcoin::hash_types::Txid;
+use bitcoin::script::ScriptBuf;
+use bitcoin::{Amount, Network, OutPoint, Sequence, Transaction, TxIn, Witness};
+use pl_00_intro::exercises::onion::{build_payment_onion, peel_onion, PeeledOnion};
+use pl_00_intro::internal::errors::{ChannelError, ForwardError};
+use pl_00_intro::internal::graph::ChannelPolicy;
+use pl_00_intro::internal::key_utils::{pubkey_from_secret, secp256k1_private_key};
+use pl_00_intro::internal::keys::ChannelKeyManager;
+use pl_00_intro::internal::onion_utils::HopPayload;
+use pl_00_intro::internal::payments::{PaymentPreimage, PaymentSecret};
+use pl_00_intro::internal::routing::{Route, RouteHop};
+use pl_00_intro::internal::units::MilliSat;
+use pl_00_intro::internal::wire::{Message, ShortChannelId};
+use pl_00_intro::simulation::channel::Channel;
+use pl_00_intro::simulation::node::{open_channel_between, transmit, Node};
+use pl_00_intro::simulation::relay::Relay;
+
+const FINAL_CLTV_EXPIRY: u32 = 800_000;
+
+fn key_manager(byte: u8) -> ChannelKeyManager {
+    ChannelKeyManager {
+        funding_key: secp256k1_private_key(&[byte; 32]),
+        payment_base_key: secp256k1_private_key(&[byte + 1; 32]),
+        delayed_payment_base_key: secp256k1_private_key(&[byte + 2; 32]),
+        htlc_base_key: secp256k1_private_key(&[byte + 3; 32]),
+        revocation_base_key: secp256k1_private_key(&[byte + 4; 32]),
+        commitment_seed: [byte + 5; 32],
+    }
+}
+
+fn funding_txin(vout: u32) -> TxIn {
+    TxIn {
+        previous_output: OutPoint::new(
+            "fd2105607605d2302994ffea703b09f66b6351816ee737a93e42a841ea20bbad".parse::<Txid>().unwrap(),
+            vout,
+        ),
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    }
+}
+
+/// A funded channel from `funder` to `fundee`, with both sides ready.
+fn ready_channel(funder: &mut Node, fundee: &mut Node, funding_txin: TxIn) -> (Channel, Channel, Transaction) {
+    let funding_tx =
+        open_channel_between(funder, fundee, funding_txin, Amount::from_sat(5_000_000), MilliSat::ZERO).unwrap();
+    let funder_ready = funder.channel_ready();
+    let fundee_ready = fundee.channel_ready();
+    (funder.handle_channel_ready(&fundee_ready), fundee.handle_channel_ready(&funder_ready), funding_tx)
+}
+
+/// Checks every commitment a node is handed really spends its channel's
+/// funding output.
+struct CommitmentLog {
+    funding_tx: Transaction,
+    checked: usize,
+}
+
+impl CommitmentLog {
+    fn check(&mut self, commitment: &Transaction) {
+        let funding_output = self.funding_tx.output[0].clone();
+        let funding_txid = self.funding_tx.compute_txid();
+        commitment.verify(|outpoint| (outpoint.txid == funding_txid).then(|| funding_output.clone())).unwrap();
+        self.checked += 1;
+    }
+}
+
+/// `sender` signs `receiver`'s next commitment and `receiver` revokes its
+/// old one.
+fn sign_and_revoke(sender: &mut Channel, receiver: &mut Channel, log: &mut CommitmentLog) {
+    let Message::CommitmentSigned(commitment_signed) =
+        transmit(Message::CommitmentSigned(sender.commitment_signed().unwrap()))
+    else {
+        unreachable!()
+    };
+    let Message::RevokeAndAck(revoke_and_ack) =
+        transmit(Message::RevokeAndAck(receiver.handle_commitment_signed(&commitment_signed).unwrap()))
+    else {
+        unreachable!()
+    };
+    sender.handle_revoke_and_ack(&revoke_and_ack).unwrap();
+
+    assert_eq!(receiver.local_commitment.compute_txid(), sender.remote_commitment.compute_txid());
+    log.check(&receiver.local_commitment);
+}
+
+/// Both sides sign and revoke, `first` first, so that an update `first`
+/// proposed is committed on both.
+fn commit_update(first: &mut Channel, second: &mut Channel, log: &mut CommitmentLog) {
+    sign_and_revoke(first, second, log);
+    sign_and_revoke(second, first, log);
+}
+
+struct Network3 {
+    alice: Channel,
+    bob: Relay,
+    carol: Channel,
+    carol_node: Node,
+    alice_bob: CommitmentLog,
+    bob_carol: CommitmentLog,
+}
+
+/// Alice → Bob → Carol, with Bob charging 1000 msat plus 0.1% and asking
+/// for 40 blocks.
+fn relay_network() -> Network3 {
+    let mut alice_node = Node::new(secp256k1_private_key(&[0x01; 32]), key_manager(0x10), Network::Regtest);
+    // Bob keeps separate channel keys for each channel, but has one node key.
+    let bob_key = secp256k1_private_key(&[0x02; 32]);
+    let mut bob_from_alice = Node::new(bob_key, key_manager(0x20), Network::Regtest);
+    let mut bob_to_carol = Node::new(bob_key, key_manager(0x30), Network::Regtest);
+    let mut carol_node = Node::new(secp256k1_private_key(&[0x03; 32]), key_manager(0x40), Network::Regtest);
+
+    let (alice, bob_incoming, alice_funding) = ready_channel(&mut alice_node, &mut bob_from_alice, funding_txin(0));
+    let (bob_outgoing, carol, carol_funding) = ready_channel(&mut bob_to_carol, &mut carol_node, funding_txin(1));
+
+    let bob = Relay {
+        node_key: bob_key,
+        incoming: bob_incoming,
+        outgoing: bob_outgoing,
+        outgoing_short_channel_id: ShortChannelId::new(700_000, 2, 0),
+        policy: ChannelPolicy {
+            enabled: true,
+            cltv_expiry_delta: 40,
+            htlc_minimum_msat: MilliSat::from_msat(1),
+            htlc_maximum_msat: MilliSat::from_msat(5_000_000_000),
+            fee_base_msat: 1_000,
+            fee_proportional_millionths: 1_000,
+            last_update: 0,
+        },
+        forwards: vec![],
+    };
+    Network3 {
+        alice,
+        bob,
+        carol,
+        carol_node,
+        alice_bob: CommitmentLog { funding_tx: alice_funding, checked: 0 },
+        bob_carol: CommitmentLog { funding_tx: carol_funding, checked: 0 },
+    }
+}
+
+/// The route `find_route` would pick for `amount` through Bob to Carol.
+fn route(bob: &Relay, carol: &Node, amount: MilliSat) -> Route {
+    Route {
+        hops: vec![
+            RouteHop {
+                node_id: pubkey_from_secret(bob.node_key),
+                short_channel_id: ShortChannelId::new(700_000, 1, 0),
+                amount_msat: amount.checked_add(bob.policy.fee_for(amount)).unwrap(),
+                cltv_expiry_delta: bob.policy.cltv_expiry_delta as u32,
+            },
+            RouteHop {
+                node_id: carol.node_id(),
+                short_channel_id: ShortChannelId::new(700_000, 2, 0),
+                amount_msat: amount,
+                cltv_expiry_delta: 0,
+            },
+        ],
+    }
+}
+
+#[test]
+fn relay_forwards_and_settles_backwards() {
+    let mut network = relay_network();
+    let Network3 { alice, bob, carol, carol_node, alice_bob, bob_carol } = &mut network;
+
+    let preimage = PaymentPreimage([0x42; 32]);
+    let payment_secret = PaymentSecret([0x07; 32]);
+    let amount = MilliSat::from_msat(25_000_000);
+    let route = route(bob, carol_node, amount);
+    let onion = build_payment_onion(
+        &route,
+        FINAL_CLTV_EXPIRY,
+        &payment_secret,
+        &preimage.hash(),
+        &secp256k1_private_key(&[0x04; 32]),
+    )
+    .unwrap();
+    let fee = route.total_fees();
+    assert_eq!(fee, MilliSat::from_msat(26_000));
+
+    // Alice offers Bob the HTLC, and Bob waits for it to be committed.
+    let update_add_htlc = alice.send_htlc(onion.amount_msat, preimage.hash(), onion.cltv_expiry, onion.packet).unwrap();
+    let Message::UpdateAddHtlc(update_add_htlc) = transmit(Message::UpdateAddHtlc(update_add_htlc)) else {
+        unreachable!()
+    };
+    bob.incoming.handle_update_add_htlc(&update_add_htlc).unwrap();
+    assert_eq!(bob.forward_htlc(0), Err(ForwardError::Channel(ChannelError::HtlcNotCommitted(0))));
+    commit_update(alice, &mut bob.incoming, alice_bob);
+
+    // Bob forwards it to Carol, keeping his fee and 40 blocks.
+    let forwarded = bob.forward_htlc(0).unwrap();
+    assert_eq!(forwarded.amount_msat, amount);
+    assert_eq!(forwarded.cltv_expiry, FINAL_CLTV_EXPIRY);
+    assert_eq!(update_add_htlc.cltv_expiry - forwarded.cltv_expiry, 40);
+    let Message::UpdateAddHtlc(forwarded) = transmit(Message::UpdateAddHtlc(forwarded)) else { unreachable!() };
+    carol.handle_update_add_htlc(&forwarded).unwrap();
+    commit_update(&mut bob.outgoing, carol, bob_carol);
+
+    // Carol is the last hop, and gets what the invoice asked for.
+    let PeeledOnion::Receive { payload } =
+        peel_onion(&carol.htlcs[0].onion_routing_packet, &carol_node.node_key, &preimage.hash().0).unwrap()
+    else {
+        panic!("Carol should receive");
+    };
+    let payload = HopPayload::decode(&payload).unwrap();
+    assert_eq!((payload.amt_to_forward, payload.outgoing_cltv_value), (amount, FINAL_CLTV_EXPIRY));
+    assert_eq!(payload.payment_data, Some((payment_secret, amount)));
+
+    // She claims it, and Bob immediately claims Alice's HTLC with the same
+    // preimage.
+    let update_fulfill_htlc = carol.fulfill_htlc(0, preimage).unwrap();
+    let Message::UpdateFulfillHtlc(update_fulfill_htlc) = transmit(Message::UpdateFulfillHtlc(update_fulfill_htlc))
+    else {
+        unreachable!()
+    };
+    let backwards = bob.handle_update_fulfill_htlc(&update_fulfill_htlc).unwrap();
+    assert_eq!((backwards.id, backwards.payment_preimage), (0, preimage));
+    assert!(bob.forwards.is_empty());
+    commit_update(carol, &mut bob.outgoing, bob_carol);
+
+    let Message::UpdateFulfillHtlc(backwards) = transmit(Message::UpdateFulfillHtlc(backwards)) else { unreachable!() };
+    assert_eq!(alice.handle_update_fulfill_htlc(&backwards), Ok(preimage));
+    commit_update(&mut bob.incoming, alice, alice_bob);
+
+    // Alice paid the amount plus Bob's fee, Carol got the amount, and Bob
+    // kept the fee.
+    let funding_msat = MilliSat::from_msat(5_000_000_000);
+    let paid = amount.checked_add(fee).unwrap();
+    assert_eq!((alice.to_local_msat, alice.to_remote_msat), (funding_msat.checked_sub(paid).unwrap(), paid));
+    assert_eq!((bob.incoming.to_local_msat, bob.incoming.to_remote_msat), (paid, alice.to_local_msat));
+    assert_eq!(
+        (bob.outgoing.to_local_msat, bob.outgoing.to_remote_msat),
+        (funding_msat.checked_sub(amount).unwrap(), amount)
+    );
+    assert_eq!((carol.to_local_msat, carol.to_remote_msat), (amount, bob.outgoing.to_local_msat));
+    let bob_total = bob.incoming.to_local_msat.checked_add(bob.outgoing.to_local_msat).unwrap();
+    assert_eq!(bob_total, funding_msat.checked_add(fee).unwrap());
+
+    // No HTLCs are left, and both sides of each channel end on the same
+    // commitments.
+    for channel in [&*alice, &bob.incoming, &bob.outgoing, &*carol] {
+        assert!(channel.htlcs.is_empty());
+    }
+    assert_eq!(alice.local_commitment.compute_txid(), bob.incoming.remote_commitment.compute_txid());
+    assert_eq!(bob.incoming.local_commitment.compute_txid(), alice.remote_commitment.compute_txid());
+    assert_eq!(carol.local_commitment.compute_txid(), bob.outgoing.remote_commitment.compute_txid());
+    assert_eq!(bob.outgoing.local_commitment.compute_txid(), carol.remote_commitment.compute_txid());
+
+    // Every commitment along the way spent its funding output: two per
+    // channel for each of the add and the fulfill.
+    assert_eq!((alice_bob.checked, bob_carol.checked), (4, 4));
+}
+
+#[test]
+fn relay_refuses_htlcs_that_break_its_policy() {
+    let mut network = relay_network();
+    let Network3 { alice, bob, carol, carol_node, alice_bob, .. } = &mut network;
+
+    // Alice's route was made before Bob raised his fee and his delta.
+    let preimage = PaymentPreimage([0x42; 32]);
+    let amount = MilliSat::from_msat(25_000_000);
+    let route = route(bob, carol_node, amount);
+    let onion = build_payment_onion(
+        &route,
+        FINAL_CLTV_EXPIRY,
+        &PaymentSecret([0x07; 32]),
+        &preimage.hash(),
+        &secp256k1_private_key(&[0x04; 32]),
+    )
+    .unwrap();
+    let update_add_htlc = alice.send_htlc(onion.amount_msat, preimage.hash(), onion.cltv_expiry, onion.packet).unwrap();
+    bob.incoming.handle_update_add_htlc(&update_add_htlc).unwrap();
+    commit_update(alice, &mut bob.incoming, alice_bob);
+
+    bob.policy.fee_base_msat = 2_000;
+    assert_eq!(
+        bob.forward_htlc(0),
+        Err(ForwardError::FeeInsufficient {
+            incoming_msat: 25_026_000,
+            amt_to_forward_msat: 25_000_000,
+            fee_msat: 27_000
+        })
+    );
+    bob.policy.fee_base_msat = 1_000;
+    bob.policy.cltv_expiry_delta = 144;
+    assert_eq!(
+        bob.forward_htlc(0),
+        Err(ForwardError::IncorrectCltvExpiry {
+            incoming: FINAL_CLTV_EXPIRY + 40,
+            outgoing: FINAL_CLTV_EXPIRY,
+            cltv_expiry_delta: 144
+        })
+    );
+    bob.policy.cltv_expiry_delta = 40;
+    bob.outgoing_short_channel_id = ShortChannelId::new(700_000, 3, 0);
+    assert_eq!(bob.forward_htlc(0), Err(ForwardError::UnknownNextPeer(ShortChannelId::new(700_000, 2, 0).0)));
+
+    // Nothing went out to Carol.
+    assert!(bob.outgoing.htlcs.is_empty() && carol.htlcs.is_empty());
+    assert!(bob.forwards.is_empty());
+}
@@ -0,0 +1,54 @@
+//! Run with `cargo test --features wasm --test wasm_bindings`. The bindings
+//! are plain functions off wasm, so they are checked natively against the
+//! exercises' own vectors.
+#![cfg(feature = "wasm")]
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::Transaction;
+use pl_00_intro::internal::tx_utils::DUMMY_FUNDING_TXID;
+use pl_00_intro::internal::wasm;
+
+const PUBKEY_1: &str = "031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f";
+const PUBKEY_2: &str = "024d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d0766";
+
+#[test]
+fn multisig_witness_script_matches_exercise_1() {
+    let script = wasm::multisig_witness_script(PUBKEY_1, PUBKEY_2).unwrap();
+    assert_eq!(script, format!("5221{}21{}52ae", PUBKEY_1, PUBKEY_2));
+    assert_eq!(
+        wasm::script_asm(&script).unwrap(),
+        format!("OP_PUSHNUM_2 OP_PUSHBYTES_33 {} OP_PUSHBYTES_33 {} OP_PUSHNUM_2 OP_CHECKMULTISIG", PUBKEY_1, PUBKEY_2)
+    );
+}
+
+#[test]
+fn funding_transaction_matches_exercise_2() {
+    let tx_hex = wasm::funding_transaction(DUMMY_FUNDING_TXID, 1, PUBKEY_1, PUBKEY_2, 100_000).unwrap();
+    let tx: Transaction = deserialize(&hex::decode(tx_hex).unwrap()).unwrap();
+    assert_eq!(tx.compute_txid().to_string(), "8f28cec85c8d986559c7bf5760d57d57446e26f27ac3ed623d591e4579b7bc9c");
+}
+
+#[test]
+fn revocation_keys_match_exercises_4_and_5() {
+    assert_eq!(
+        wasm::revocation_pubkey(PUBKEY_1, PUBKEY_2).unwrap(),
+        "02f406b2b2ef2372c08d003e5062e4b34929b86f107a36bfbd406f5644419e9ff6"
+    );
+    assert_eq!(
+        wasm::revocation_privkey(&"01".repeat(32), &"02".repeat(32)).unwrap(),
+        "0c8415f91d9df28009f87442dfc897d3c22a534032d4795467206db994a3c539"
+    );
+}
+
+#[test]
+fn to_local_script_matches_exercise_6() {
+    assert_eq!(
+        wasm::to_local_script(PUBKEY_1, PUBKEY_2, 144).unwrap(),
+        format!("6321{}67029000b27521{}68ac", PUBKEY_1, PUBKEY_2)
+    );
+}
+
+#[test]
+fn bad_hex_is_an_error() {
+    assert!(wasm::multisig_witness_script("not hex", PUBKEY_2).unwrap_err().starts_with("pubkey1"));
+    assert!(wasm::per_commitment_secret("00", 0).unwrap_err().contains("32 bytes"));
+}